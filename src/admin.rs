@@ -0,0 +1,1013 @@
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use metrics::gauge;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tower::ServiceExt;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{persist_backend, Backend, Subsystem},
+    handlers::{route_path, SelectedBackend, TestRequest},
+    ledger::{day_number, day_string, parse_day},
+    methods::is_known_method,
+    profiling::{
+        self, CpuProfileFormat, ProfileError, DEFAULT_CPU_PROFILE_FREQUENCY,
+        DEFAULT_CPU_PROFILE_SECS, MAX_CPU_PROFILE_FREQUENCY, MAX_CPU_PROFILE_SECS,
+    },
+    redact::{key_fingerprint, redact, redact_url},
+    reload::{self, ConfigSource},
+    server::http_router,
+    signing::unix_now,
+    state::AppState,
+    stats::WINDOW_SECS,
+    timing::TIMING_HEADER,
+};
+
+/// Header carrying the second admin token for dual-control actions.
+pub const APPROVAL_HEADER: &str = "x-admin-approval";
+
+/// The admin token that authenticated a request, set by [`require_admin`].
+#[derive(Clone)]
+struct AdminIdentity(String);
+
+/// Routes served under `/admin`, all behind bearer-token auth. Destructive routes
+/// additionally need a second approver when `admin.dual_control` is on.
+pub fn router(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    let destructive = Router::new()
+        .route("/keys/:key", delete(revoke_key))
+        .route(
+            "/backends/:label",
+            delete(remove_backend).patch(patch_backend),
+        )
+        .route("/config", put(push_config))
+        .route("/config/reload", post(reload_config))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_approval,
+        ));
+
+    Router::new()
+        .route("/config", get(effective_config))
+        .route("/config/status", get(config_status))
+        .route("/backends", get(list_backends))
+        .route("/routes", get(routes))
+        .route("/routing-stats", get(routing_stats))
+        .route("/alerts", get(alerts))
+        .route("/dead-letters", get(list_dead_letters))
+        .route("/dead-letters/:id", get(get_dead_letter))
+        .route("/journal/:signature", get(get_journal_entry))
+        .route("/usage", get(usage))
+        .route("/traffic", get(traffic))
+        .route("/latency-heatmap", get(latency_heatmap))
+        .route("/cache/ttls", get(cache_ttls))
+        .route("/profile/cpu", get(cpu_profile))
+        .route("/profile/heap", get(heap_profile))
+        .route("/test-request", post(test_request))
+        .route("/keys/:key/audit", put(enable_audit).delete(disable_audit))
+        .route("/keys/:key/usage", get(key_usage))
+        .route("/kill-switches", get(kill_switches))
+        .route(
+            "/kill-switches/:subsystem",
+            put(engage_kill_switch).delete(release_kill_switch),
+        )
+        .merge(destructive)
+        .layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Reject requests without `Authorization: Bearer <token>` matching `admin.tokens`.
+pub async fn require_admin(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let tokens = state.state.load().admin.tokens.clone();
+    if tokens.is_empty() {
+        return (StatusCode::NOT_FOUND, "Admin API is disabled").into_response();
+    }
+
+    let presented = req
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match presented {
+        Some(token)
+            if tokens
+                .iter()
+                .any(|t| constant_time_eq(t.as_bytes(), token.as_bytes())) =>
+        {
+            let identity = AdminIdentity(token.to_string());
+            req.extensions_mut().insert(identity);
+            next.run(req).await
+        }
+        _ => {
+            warn!("Rejected admin request to {}", route_path(&req));
+            (StatusCode::UNAUTHORIZED, "Unauthorized").into_response()
+        }
+    }
+}
+
+/// `GET /admin/config/status`: outcome of the last configuration reload.
+async fn config_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.reload_status.snapshot())
+}
+
+/// `GET /admin/config`: the running configuration with secrets redacted, plus
+/// its hash, source (the config path, or `admin` if pushed) and load time.
+async fn effective_config(State(state): State<Arc<AppState>>) -> Response {
+    let Some(config) = state.reload_status.active_config() else {
+        return (StatusCode::NOT_FOUND, "No configuration loaded").into_response();
+    };
+    let report = state.reload_status.snapshot();
+    let source = match report.config_source {
+        Some(ConfigSource::Admin) => "admin".to_string(),
+        _ => report.config_path,
+    };
+    Json(json!({
+        "hash": config.hash,
+        "source": source,
+        "active_since": report.active_since,
+        "config": config.redacted(),
+    }))
+    .into_response()
+}
+
+/// `PUT /admin/config`: validate a full TOML config document and hot-swap it,
+/// exactly like a SIGHUP reload of the config file. Rejected documents leave the
+/// running configuration untouched.
+async fn push_config(State(state): State<Arc<AppState>>, contents: String) -> Response {
+    let health_state = state.state.load().health_state.clone();
+    let result = reload::push_config(
+        &contents,
+        &state.client,
+        &state.state,
+        &health_state,
+        &state.reload_status,
+    )
+    .await;
+    match result {
+        Ok(backends) => Json(json!({
+            "hash": state.reload_status.snapshot().config_hash,
+            "backends": backends,
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": redact(&e) })),
+        )
+            .into_response(),
+    }
+}
+
+/// `POST /admin/config/reload`: re-read the config file and hot-swap it, like
+/// a SIGHUP.
+async fn reload_config(State(state): State<Arc<AppState>>) -> Response {
+    let config_path = state.reload_status.snapshot().config_path;
+    if config_path.is_empty() {
+        return (StatusCode::CONFLICT, "No config file to reload").into_response();
+    }
+    let health_state = state.state.load().health_state.clone();
+    let result = reload::reload_config(
+        &config_path,
+        &state.client,
+        &state.state,
+        &health_state,
+        &state.reload_status,
+    )
+    .await;
+    match result {
+        Ok(backends) => Json(json!({
+            "hash": state.reload_status.snapshot().config_hash,
+            "backends": backends,
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": redact(&e) })),
+        )
+            .into_response(),
+    }
+}
+
+/// `GET /admin/backends`: every backend with its rotation flags, health check
+/// state and circuit breaker.
+async fn list_backends(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let current = state.state.load();
+    let statuses = current.health_state.get_all_statuses();
+    let now = unix_now();
+    let backends: Vec<_> = current
+        .backends
+        .iter()
+        .map(|backend| {
+            let status = statuses
+                .get(&backend.config.label)
+                .cloned()
+                .unwrap_or_default();
+            json!({
+                "label": backend.config.label,
+                "url": redact_url(&backend.config.url),
+                "weight": backend.config.weight,
+                "drain": backend.config.drain,
+                "maintenance": backend.config.maintenance,
+                "in_rotation": backend.in_rotation(),
+                "status": status.level(),
+                "consecutive_failures": status.consecutive_failures,
+                "consecutive_successes": status.consecutive_successes,
+                "consecutive_request_failures": status.consecutive_request_failures,
+                "last_error": status.last_error.map(|e| redact(&e).into_owned()),
+                "circuit": backend.circuit.state(now),
+                "genesis_mismatch": backend.identity.mismatch(&backend.config.url),
+            })
+        })
+        .collect();
+    Json(json!({ "backends": backends }))
+}
+
+/// `GET /admin/routes`: the global method routes and per-tier routes in effect.
+async fn routes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let current = state.state.load();
+    Json(json!({
+        "method_routes": current.method_routes,
+        "tier_routes": current.tier_routes,
+    }))
+}
+
+/// `GET /admin/routing-stats`: per-method, per-backend success rate and latency
+/// over the last few minutes, alongside the method routes in effect and, in auto
+/// routing mode, the learned shares (basis points).
+async fn routing_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(json!({
+        "window_secs": WINDOW_SECS,
+        "method_routes": state.state.load().method_routes,
+        "methods": state.routing_stats.snapshot(unix_now()),
+        "auto_weights": **state.auto_weights.load(),
+    }))
+}
+
+/// `GET /admin/alerts`: state of every configured alert rule.
+async fn alerts(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(json!({ "alerts": state.alerts.snapshot() }))
+}
+
+/// Most dead letters returned by one listing.
+const MAX_DEAD_LETTERS: usize = 1_000;
+
+#[derive(Deserialize)]
+struct DeadLetterQuery {
+    limit: Option<usize>,
+}
+
+/// `GET /admin/dead-letters?limit=N`: the most recent undelivered
+/// `sendTransaction` requests, newest first (default 100).
+async fn list_dead_letters(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DeadLetterQuery>,
+) -> Response {
+    let Some(store) = &state.dead_letters else {
+        return (StatusCode::NOT_FOUND, "Dead-letter capture is disabled").into_response();
+    };
+    let limit = query.limit.unwrap_or(100).min(MAX_DEAD_LETTERS);
+    match store.list(limit).await {
+        Ok(letters) => Json(json!({ "dead_letters": letters })).into_response(),
+        Err(e) => {
+            error!("Failed to list dead letters: {}", redact(&e));
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
+        }
+    }
+}
+
+/// `GET /admin/dead-letters/:id`: one dead letter, including the raw request.
+async fn get_dead_letter(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> Response {
+    let Some(store) = &state.dead_letters else {
+        return (StatusCode::NOT_FOUND, "Dead-letter capture is disabled").into_response();
+    };
+    match store.get(&id).await {
+        Ok(Some(letter)) => Json(letter).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Dead letter not found").into_response(),
+        Err(e) => {
+            error!("Failed to read dead letter: {}", redact(&e));
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
+        }
+    }
+}
+
+/// `GET /admin/journal/:signature`: whether and where a transaction was
+/// forwarded, from the `sendTransaction` journal.
+async fn get_journal_entry(
+    State(state): State<Arc<AppState>>,
+    Path(signature): Path<String>,
+) -> Response {
+    let Some(journal) = &state.journal else {
+        return (StatusCode::NOT_FOUND, "Transaction journal is disabled").into_response();
+    };
+    match journal.get(&signature).await {
+        Ok(Some(entry)) => Json(entry).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Transaction not journaled").into_response(),
+        Err(e) => {
+            error!("Failed to read the transaction journal: {}", redact(&e));
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
+        }
+    }
+}
+
+/// With `admin.dual_control`, require `X-Admin-Approval` to hold a valid admin
+/// token other than the one that authenticated the request. Every destructive
+/// action is logged with the fingerprints of its requester and approver.
+async fn require_approval(
+    State(state): State<Arc<AppState>>,
+    params: Option<Path<BTreeMap<String, String>>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let admin = state.state.load().admin.clone();
+    let requester = req
+        .extensions()
+        .get::<AdminIdentity>()
+        .map(|a| a.0.clone())
+        .unwrap_or_default();
+    // The route and its parameters, with API keys logged by fingerprint only
+    let mut action = format!("{} {}", req.method(), route_path(&req));
+    for (name, value) in params.iter().flat_map(|Path(params)| params) {
+        let value = match name.as_str() {
+            "key" => key_fingerprint(value),
+            _ => value.clone(),
+        };
+        action.push_str(&format!(" {}={}", name, value));
+    }
+
+    if !admin.dual_control {
+        warn!("Admin action {} by {}", action, key_fingerprint(&requester));
+        return next.run(req).await;
+    }
+
+    let approver = req
+        .headers()
+        .get(APPROVAL_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let valid = !constant_time_eq(approver.as_bytes(), requester.as_bytes())
+        && admin
+            .tokens
+            .iter()
+            .any(|t| constant_time_eq(t.as_bytes(), approver.as_bytes()));
+    if !valid {
+        warn!(
+            "Rejected admin action {} by {}: missing or invalid second approval",
+            action,
+            key_fingerprint(&requester)
+        );
+        return (
+            StatusCode::FORBIDDEN,
+            "This action requires approval from a second admin token (X-Admin-Approval)",
+        )
+            .into_response();
+    }
+
+    warn!(
+        "Admin action {} by {} approved by {}",
+        action,
+        key_fingerprint(&requester),
+        key_fingerprint(approver)
+    );
+    next.run(req).await
+}
+
+/// `DELETE /admin/keys/:key`: deactivate an API key.
+async fn revoke_key(State(state): State<Arc<AppState>>, Path(key): Path<String>) -> Response {
+    match state.keystore.revoke_key(&key).await {
+        Ok(true) => Json(json!({ "revoked": key_fingerprint(&key) })).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Key not found").into_response(),
+        Err(e) => {
+            error!("Failed to revoke key: {}", redact(&e));
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
+        }
+    }
+}
+
+/// `PUT /admin/keys/:key/audit`: log every request of a key in full.
+async fn enable_audit(state: State<Arc<AppState>>, key: Path<String>) -> Response {
+    set_audit(state, key, true).await
+}
+
+/// `DELETE /admin/keys/:key/audit`
+async fn disable_audit(state: State<Arc<AppState>>, key: Path<String>) -> Response {
+    set_audit(state, key, false).await
+}
+
+async fn set_audit(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+    enabled: bool,
+) -> Response {
+    match state.keystore.set_audit(&key, enabled).await {
+        Ok(true) => {
+            info!(
+                "Audit logging {} for key {}",
+                if enabled { "enabled" } else { "disabled" },
+                key_fingerprint(&key)
+            );
+            Json(json!({ "key": key_fingerprint(&key), "audit": enabled })).into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Key not found").into_response(),
+        Err(e) => {
+            error!("Failed to update audit flag: {}", redact(&e));
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
+        }
+    }
+}
+
+/// `GET /admin/kill-switches`: which subsystems are switched off, and whether by
+/// the config or at runtime.
+async fn kill_switches(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let router_state = state.state.load();
+    let switches: serde_json::Map<_, _> = Subsystem::ALL
+        .into_iter()
+        .map(|subsystem| {
+            let config = router_state.kill_switches.disabled.contains(&subsystem);
+            let runtime = state.kill_switches.is_set(subsystem);
+            (
+                subsystem.as_str().to_string(),
+                json!({ "disabled": config || runtime, "config": config, "runtime": runtime }),
+            )
+        })
+        .collect();
+    Json(json!({ "kill_switches": switches }))
+}
+
+/// `PUT /admin/kill-switches/:subsystem`: switch a subsystem off on this instance.
+async fn engage_kill_switch(state: State<Arc<AppState>>, subsystem: Path<String>) -> Response {
+    set_kill_switch(state, subsystem, true)
+}
+
+/// `DELETE /admin/kill-switches/:subsystem`
+async fn release_kill_switch(state: State<Arc<AppState>>, subsystem: Path<String>) -> Response {
+    set_kill_switch(state, subsystem, false)
+}
+
+fn set_kill_switch(
+    State(state): State<Arc<AppState>>,
+    Path(subsystem): Path<String>,
+    disabled: bool,
+) -> Response {
+    let Some(subsystem) = Subsystem::parse(&subsystem) else {
+        return (StatusCode::NOT_FOUND, "Unknown subsystem").into_response();
+    };
+    state.kill_switches.set(subsystem, disabled);
+    warn!(
+        "Kill switch for {} {}",
+        subsystem.as_str(),
+        if disabled { "engaged" } else { "released" }
+    );
+    let disabled = state.is_disabled(&state.state.load(), subsystem);
+    Json(json!({ "subsystem": subsystem.as_str(), "disabled": disabled })).into_response()
+}
+
+/// `DELETE /admin/backends/:label`: take a backend out of rotation until the
+/// next config reload. Method routes pointing at it fall back to weighted selection.
+async fn remove_backend(State(state): State<Arc<AppState>>, Path(label): Path<String>) -> Response {
+    let current = state.state.load_full();
+    if !current.backends.iter().any(|b| b.config.label == label) {
+        return (StatusCode::NOT_FOUND, "Backend not found").into_response();
+    }
+    if current.backends.len() == 1 {
+        return (StatusCode::CONFLICT, "Cannot remove the last backend").into_response();
+    }
+
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.backends.retain(|b| b.config.label != label);
+        next.method_routes.retain(|_, target| *target != label);
+        for routes in next.tier_routes.values_mut() {
+            routes.retain(|_, target| *target != label);
+        }
+        next
+    });
+
+    Json(json!({ "removed": label })).into_response()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BackendPatch {
+    weight: Option<u32>,
+    drain: Option<bool>,
+    maintenance: Option<bool>,
+    /// Mark the backend healthy or unhealthy until health checks say otherwise
+    healthy: Option<bool>,
+    /// Also write the change to the config file, so it survives reloads
+    #[serde(default)]
+    persist: bool,
+}
+
+/// `PATCH /admin/backends/:label`: change the weight, drain or maintenance flag
+/// of a backend. The change lasts until the next config reload unless it is
+/// persisted to the config file. `healthy` overrides the health check state
+/// instead: a backend marked unhealthy needs `consecutive_successes_threshold`
+/// passing checks to return, one marked healthy is ejected by failing ones.
+async fn patch_backend(
+    State(state): State<Arc<AppState>>,
+    Path(label): Path<String>,
+    Json(patch): Json<BackendPatch>,
+) -> Response {
+    if patch.weight == Some(0) {
+        return (StatusCode::BAD_REQUEST, "weight must be > 0").into_response();
+    }
+    let current = state.state.load_full();
+    let Some(backend) = current.backends.iter().find(|b| b.config.label == label) else {
+        return (StatusCode::NOT_FOUND, "Backend not found").into_response();
+    };
+    let mut config = backend.config.clone();
+    config.weight = patch.weight.unwrap_or(config.weight);
+    config.drain = patch.drain.unwrap_or(config.drain);
+    config.maintenance = patch.maintenance.unwrap_or(config.maintenance);
+
+    let serving = |b: &Backend| !b.drain && !b.maintenance;
+    let others_serving = current
+        .backends
+        .iter()
+        .any(|b| b.config.label != label && serving(&b.config));
+    if !others_serving && (!serving(&config) || patch.healthy == Some(false)) {
+        return (
+            StatusCode::CONFLICT,
+            "Cannot take every backend out of rotation",
+        )
+            .into_response();
+    }
+
+    if patch.persist {
+        let report = state.reload_status.snapshot();
+        if report.config_source == Some(ConfigSource::Admin) || report.config_path.is_empty() {
+            return (
+                StatusCode::CONFLICT,
+                "The running config did not come from the config file",
+            )
+                .into_response();
+        }
+        if let Err(e) = persist_backend(&report.config_path, &config) {
+            error!("Failed to persist backend '{}': {}", label, redact(&e));
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to write the config file",
+            )
+                .into_response();
+        }
+    }
+
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        for backend in next.backends.iter_mut().filter(|b| b.config.label == label) {
+            backend.config.weight = config.weight;
+            backend.config.drain = config.drain;
+            backend.config.maintenance = config.maintenance;
+        }
+        next
+    });
+    if let Some(healthy) = patch.healthy {
+        let mut status = current.health_state.get_status(&label).unwrap_or_default();
+        status.healthy = healthy;
+        status.degraded = false;
+        status.consecutive_failures = 0;
+        status.consecutive_successes = 0;
+        status.consecutive_request_failures = 0;
+        status.last_error = (!healthy).then(|| "Marked unhealthy by an admin".to_string());
+        current.health_state.update_status(&label, status);
+        backend.healthy.store(healthy, Ordering::Relaxed);
+        backend.degraded.store(false, Ordering::Relaxed);
+        let (value, level) = if healthy {
+            (1.0, "HEALTHY")
+        } else {
+            (0.0, "UNHEALTHY")
+        };
+        gauge!("rpc_backend_health", "backend" => label.clone()).set(value);
+        warn!("Backend {} marked {} by an admin", label, level);
+    }
+    info!(
+        "Backend {} set to weight={} drain={} maintenance={}{}",
+        label,
+        config.weight,
+        config.drain,
+        config.maintenance,
+        if patch.persist { " (persisted)" } else { "" }
+    );
+
+    Json(json!({
+        "label": label,
+        "weight": config.weight,
+        "drain": config.drain,
+        "maintenance": config.maintenance,
+        "healthy": backend.healthy.load(Ordering::Relaxed),
+        "persisted": patch.persist,
+    }))
+    .into_response()
+}
+
+/// Longest range `/admin/usage` reports, in days.
+const MAX_USAGE_DAYS: u64 = 366;
+
+#[derive(Deserialize)]
+struct UsageQuery {
+    owner: Option<String>,
+    days: Option<u64>,
+}
+
+/// `GET /admin/usage?owner=X&days=N`: per-key request totals for the last `N`
+/// days including today (default 30), most recent first.
+async fn usage(State(state): State<Arc<AppState>>, Query(query): Query<UsageQuery>) -> Response {
+    let Some(ledger) = &state.usage_ledger else {
+        return (StatusCode::NOT_FOUND, "Usage ledger is disabled").into_response();
+    };
+    let days = query.days.unwrap_or(30).clamp(1, MAX_USAGE_DAYS);
+    let to = day_number(unix_now());
+    let from = (to + 1).saturating_sub(days);
+    match ledger.query(query.owner.as_deref(), from, to).await {
+        Ok(entries) => Json(json!({
+            "from": day_string(from),
+            "to": day_string(to),
+            "usage": entries,
+        }))
+        .into_response(),
+        Err(e) => {
+            error!("Failed to query usage ledger: {}", redact(&e));
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct KeyUsageQuery {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// `GET /admin/keys/:key/usage?from=YYYY-MM-DD&to=YYYY-MM-DD`: a key's daily
+/// request counts and response bytes per method, most recent day first, with
+/// totals per method over the range. `to` defaults to today and `from` to 30
+/// days before it.
+async fn key_usage(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+    Query(query): Query<KeyUsageQuery>,
+) -> Response {
+    let Some(store) = &state.key_usage else {
+        return (StatusCode::NOT_FOUND, "Key usage accounting is disabled").into_response();
+    };
+    let day = |date: Option<&str>, default: u64| match date {
+        Some(date) => parse_day(date).ok_or_else(|| format!("Invalid date '{}'", date)),
+        None => Ok(default),
+    };
+    let today = day_number(unix_now());
+    let to = match day(query.to.as_deref(), today) {
+        Ok(to) => to,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let from = match day(query.from.as_deref(), to.saturating_sub(29)) {
+        Ok(from) => from,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if from > to || to - from >= MAX_USAGE_DAYS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "from must not be after to, and the range at most {} days",
+                MAX_USAGE_DAYS
+            ),
+        )
+            .into_response();
+    }
+
+    let entries = match store.query(&key, from, to).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to query key usage: {}", redact(&e));
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response();
+        }
+    };
+    let mut totals: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+    for entry in &entries {
+        let (requests, bytes) = totals.entry(&entry.method).or_default();
+        *requests += entry.requests;
+        *bytes += entry.response_bytes;
+    }
+    let totals: Vec<Value> = totals
+        .into_iter()
+        .map(|(method, (requests, response_bytes))| {
+            json!({
+                "method": method,
+                "requests": requests,
+                "response_bytes": response_bytes,
+            })
+        })
+        .collect();
+    Json(json!({
+        "key": key_fingerprint(&key),
+        "from": day_string(from),
+        "to": day_string(to),
+        "usage": entries,
+        "totals": totals,
+    }))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct TrafficQuery {
+    hours: Option<u64>,
+    format: Option<String>,
+}
+
+/// `GET /admin/traffic?hours=N&format=csv`: per-method request volumes, payload
+/// sizes and peak rates over the last `N` hours including the current one
+/// (default 24, at most the retention), as JSON or CSV.
+async fn traffic(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TrafficQuery>,
+) -> Response {
+    let config = state.state.load().traffic_report.clone();
+    if !config.enabled {
+        return (StatusCode::NOT_FOUND, "Traffic report is disabled").into_response();
+    }
+    let hours = query
+        .hours
+        .unwrap_or(24)
+        .clamp(1, config.retention_days * 24);
+    let report = state.traffic.report(hours, unix_now());
+    match query.format.as_deref() {
+        None | Some("json") => Json(report).into_response(),
+        Some("csv") => (
+            [
+                ("content-type", "text/csv"),
+                (
+                    "content-disposition",
+                    "attachment; filename=\"traffic.csv\"",
+                ),
+            ],
+            report.to_csv(),
+        )
+            .into_response(),
+        Some(other) => (
+            StatusCode::BAD_REQUEST,
+            format!("Unknown format '{}'; use json or csv", other),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct HeatmapQuery {
+    method: Option<String>,
+    windows: Option<u64>,
+}
+
+/// `GET /admin/latency-heatmap?method=M&windows=N`: upstream latency
+/// distribution of method `M` per backend over the last `N` windows including
+/// the current one (default and at most all windows kept).
+async fn latency_heatmap(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HeatmapQuery>,
+) -> Response {
+    let config = state.state.load().latency_heatmap.clone();
+    if !config.enabled {
+        return (StatusCode::NOT_FOUND, "Latency heatmap is disabled").into_response();
+    }
+    let Some(method) = query.method.filter(|m| is_known_method(m)) else {
+        return (StatusCode::BAD_REQUEST, "method must be a known RPC method").into_response();
+    };
+    let windows = query
+        .windows
+        .unwrap_or(config.windows)
+        .clamp(1, config.windows);
+    Json(
+        state
+            .latency_heatmap
+            .heatmap(&method, windows, unix_now(), config.window_secs),
+    )
+    .into_response()
+}
+
+/// `GET /admin/cache/ttls`: the TTL chosen for each `cache.ttl_tuning` method,
+/// with the hits and revalidation mismatches of every TTL it tries.
+async fn cache_ttls(State(state): State<Arc<AppState>>) -> Response {
+    let config = state.state.load().cache.ttl_tuning.clone();
+    if !config.enabled {
+        return (StatusCode::NOT_FOUND, "Cache TTL tuning is disabled").into_response();
+    }
+    Json(state.ttl_tuner.snapshot(&config)).into_response()
+}
+
+#[derive(Deserialize)]
+struct CpuProfileQuery {
+    seconds: Option<u64>,
+    frequency: Option<u32>,
+    #[serde(default)]
+    format: CpuProfileFormat,
+}
+
+fn profile_response(
+    profile: Result<Vec<u8>, ProfileError>,
+    content_type: &str,
+    file_name: &str,
+) -> Response {
+    match profile {
+        Ok(bytes) => (
+            [
+                ("content-type", content_type.to_string()),
+                (
+                    "content-disposition",
+                    format!("attachment; filename=\"{}\"", file_name),
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => {
+            let status = match e {
+                ProfileError::Unavailable(_) => StatusCode::NOT_IMPLEMENTED,
+                ProfileError::Busy => StatusCode::CONFLICT,
+                ProfileError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, e.to_string()).into_response()
+        }
+    }
+}
+
+/// `GET /admin/profile/cpu?seconds=N&frequency=F&format=pprof|flamegraph`:
+/// sample the router's threads for `N` seconds (default 30, at most 300) and
+/// return the profile. Needs the `profiling` feature.
+async fn cpu_profile(Query(query): Query<CpuProfileQuery>) -> Response {
+    let seconds = query.seconds.unwrap_or(DEFAULT_CPU_PROFILE_SECS);
+    if !(1..=MAX_CPU_PROFILE_SECS).contains(&seconds) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("seconds must be between 1 and {}", MAX_CPU_PROFILE_SECS),
+        )
+            .into_response();
+    }
+    let frequency = query.frequency.unwrap_or(DEFAULT_CPU_PROFILE_FREQUENCY);
+    if !(1..=MAX_CPU_PROFILE_FREQUENCY).contains(&frequency) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "frequency must be between 1 and {}",
+                MAX_CPU_PROFILE_FREQUENCY
+            ),
+        )
+            .into_response();
+    }
+    info!("Admin CPU profile ({}s at {} Hz)", seconds, frequency);
+    let profile =
+        profiling::cpu_profile(Duration::from_secs(seconds), frequency, query.format).await;
+    profile_response(
+        profile,
+        query.format.content_type(),
+        query.format.file_name(),
+    )
+}
+
+/// `GET /admin/profile/heap`: jemalloc's sampled profile of live allocations,
+/// for `jeprof`. Needs the `heap-profiling` feature.
+async fn heap_profile() -> Response {
+    info!("Admin heap profile");
+    let profile = tokio::task::spawn_blocking(profiling::heap_profile)
+        .await
+        .unwrap_or_else(|e| Err(ProfileError::Failed(e.to_string())));
+    profile_response(profile, "application/octet-stream", "heap.prof")
+}
+
+/// Largest response body `POST /admin/test-request` returns.
+const MAX_TEST_RESPONSE_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestRequestBody {
+    /// Key the request is made with: validated, rate limited and metered like
+    /// any other request of that key
+    api_key: String,
+    /// JSON-RPC request or batch to send
+    request: Value,
+    /// Send to this backend instead of routing the request
+    backend: Option<String>,
+}
+
+/// `POST /admin/test-request`: send a JSON-RPC request through the public
+/// router, as a client using `api_key` would, and report the backend that
+/// answered, the retry and cache headers, the time spent per phase and the
+/// response. With `backend`, that backend answers whether or not it is in
+/// rotation, and caching, hedging, broadcast and failover are skipped.
+async fn test_request(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(test): Json<TestRequestBody>,
+) -> Response {
+    if let Some(label) = &test.backend {
+        if !state
+            .state
+            .load()
+            .backends
+            .iter()
+            .any(|b| b.config.label == *label)
+        {
+            return (StatusCode::NOT_FOUND, "Backend not found").into_response();
+        }
+    }
+    // Percent-encoded for the `api-key` query parameter; commas separate fallback keys
+    let api_key: String = test
+        .api_key
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b',' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+
+    let mut req = Request::builder()
+        .method("POST")
+        .uri(format!("/?api-key={}", api_key))
+        .header("content-type", "application/json")
+        .body(Body::from(test.request.to_string()))
+        .expect("encoded request parts");
+    req.extensions_mut()
+        .insert(connect_info.unwrap_or(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0)))));
+    req.extensions_mut().insert(TestRequest {
+        backend: test.backend.clone(),
+    });
+    info!(
+        "Admin test request (key={}, backend={})",
+        key_fingerprint(&test.api_key),
+        test.backend.as_deref().unwrap_or("(routed)")
+    );
+
+    let started = Instant::now();
+    let response = match http_router(state).oneshot(req).await {
+        Ok(response) => response,
+        Err(infallible) => match infallible {},
+    };
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_TEST_RESPONSE_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to read the response: {}", e),
+            )
+                .into_response();
+        }
+    };
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let header = |name: &str| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    // `auth;dur=0.120, queue;dur=0.000, ...` as `{"auth": 0.12, "queue": 0.0, ...}`
+    let timings: Map<String, Value> = header(TIMING_HEADER)
+        .unwrap_or_default()
+        .split(", ")
+        .filter_map(|phase| {
+            let (name, ms) = phase.split_once(";dur=")?;
+            Some((name.to_string(), Value::from(ms.parse::<f64>().ok()?)))
+        })
+        .collect();
+    let headers: Map<String, Value> = parts
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), Value::from(value.to_str().ok()?))))
+        .collect();
+    let response = serde_json::from_slice::<Value>(&body)
+        .unwrap_or_else(|_| Value::from(String::from_utf8_lossy(&body).into_owned()));
+
+    Json(json!({
+        "status": parts.status.as_u16(),
+        "backend": parts.extensions.get::<SelectedBackend>().map(|b| b.0.clone()),
+        "pinned": test.backend.is_some(),
+        "retry": header("x-rpc-router-retry"),
+        "cache": header("x-cache"),
+        "timings_ms": timings,
+        "elapsed_ms": elapsed_ms,
+        "headers": headers,
+        "response": response,
+    }))
+    .into_response()
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{atomic::Ordering, Arc, Mutex},
+};
+
+use axum::body::Body;
+use hyper::Request;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use metrics::{counter, gauge};
+use serde::Serialize;
+use tokio::time::{interval, timeout, Duration};
+use tracing::{info, warn};
+
+use crate::{
+    config::{AlertMetric, AlertOp, AlertRule},
+    consistency::ConsistencyState,
+    redact::redact,
+    signing::unix_now,
+    state::{AppState, RouterState},
+    stats::RoutingStats,
+};
+
+/// How often alert rules are evaluated.
+pub const EVAL_SECS: u64 = 10;
+
+const WEBHOOK_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    #[default]
+    Ok,
+    /// Condition holds, but not yet for `for_secs`
+    Pending,
+    Firing,
+}
+
+/// Current state of one rule, served at `/admin/alerts`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AlertState {
+    pub status: AlertStatus,
+    /// Last evaluated value; `None` when there was no data
+    pub value: Option<f64>,
+    /// Unix time the condition started holding
+    pub since: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertTransition {
+    Firing,
+    Resolved,
+}
+
+/// Webhook payload sent when a rule starts or stops firing.
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertEvent {
+    pub alert: String,
+    pub status: AlertTransition,
+    pub metric: AlertMetric,
+    pub method: Option<String>,
+    pub backend: Option<String>,
+    pub op: AlertOp,
+    pub threshold: f64,
+    pub value: Option<f64>,
+    /// Unix time the condition started holding
+    pub since: Option<u64>,
+    pub at: u64,
+    #[serde(skip)]
+    pub webhook_url: String,
+}
+
+/// Pending/firing state of every configured alert rule.
+#[derive(Default)]
+pub struct AlertEngine {
+    states: Mutex<HashMap<String, AlertState>>,
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Evaluate `rules` with the values returned by `value` (`None` = no data,
+    /// which never satisfies a condition). Returns the rules that started or
+    /// stopped firing. State of rules no longer configured is dropped.
+    pub fn evaluate(
+        &self,
+        rules: &[AlertRule],
+        value: impl Fn(&AlertRule) -> Option<f64>,
+        now: u64,
+    ) -> Vec<AlertEvent> {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        states.retain(|name, _| rules.iter().any(|r| &r.name == name));
+
+        let mut events = Vec::new();
+        for rule in rules {
+            let current = value(rule);
+            let holds = current.is_some_and(|v| rule.op.holds(v, rule.threshold));
+            let state = states.entry(rule.name.clone()).or_default();
+            state.value = current;
+
+            if holds {
+                let since = *state.since.get_or_insert(now);
+                if state.status != AlertStatus::Firing && now - since >= rule.for_secs {
+                    state.status = AlertStatus::Firing;
+                    events.push(event(
+                        rule,
+                        AlertTransition::Firing,
+                        current,
+                        Some(since),
+                        now,
+                    ));
+                } else if state.status == AlertStatus::Ok {
+                    state.status = AlertStatus::Pending;
+                }
+            } else {
+                let since = state.since.take();
+                if state.status == AlertStatus::Firing {
+                    events.push(event(rule, AlertTransition::Resolved, current, since, now));
+                }
+                state.status = AlertStatus::Ok;
+            }
+        }
+        events
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, AlertState> {
+        let states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        states
+            .iter()
+            .map(|(name, state)| (name.clone(), state.clone()))
+            .collect()
+    }
+}
+
+fn event(
+    rule: &AlertRule,
+    status: AlertTransition,
+    value: Option<f64>,
+    since: Option<u64>,
+    now: u64,
+) -> AlertEvent {
+    AlertEvent {
+        alert: rule.name.clone(),
+        status,
+        metric: rule.metric,
+        method: rule.method.clone(),
+        backend: rule.backend.clone(),
+        op: rule.op,
+        threshold: rule.threshold,
+        value,
+        since,
+        at: now,
+        webhook_url: rule.webhook_url.clone(),
+    }
+}
+
+/// Current value of `rule`'s metric.
+pub fn metric_value(
+    rule: &AlertRule,
+    router_state: &RouterState,
+    stats: &RoutingStats,
+    consistency: &ConsistencyState,
+    now: u64,
+) -> Option<f64> {
+    if rule.metric == AlertMetric::DivergentBackends {
+        return Some(consistency.divergent_backends().len() as f64);
+    }
+    if rule.metric == AlertMetric::UnhealthyBackends {
+        let unhealthy = router_state
+            .backends
+            .iter()
+            .filter(|b| !b.config.maintenance && !b.healthy.load(Ordering::Relaxed))
+            .count();
+        return Some(unhealthy as f64);
+    }
+
+    let method = rule.method.as_deref()?;
+    let stats = match &rule.backend {
+        Some(backend) => stats.get(method, backend, now),
+        None => stats.get_method(method, now),
+    }?;
+    match rule.metric {
+        AlertMetric::P50Ms => stats.p50_ms.map(|ms| ms as f64),
+        AlertMetric::P99Ms => stats.p99_ms.map(|ms| ms as f64),
+        AlertMetric::SuccessRate => Some(stats.success_rate),
+        AlertMetric::UnhealthyBackends | AlertMetric::DivergentBackends => None,
+    }
+}
+
+/// POST `event` as JSON to its webhook. Non-2xx responses count as failures.
+pub async fn send_webhook(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    event: &AlertEvent,
+) -> Result<(), String> {
+    post_json(client, &event.webhook_url, event).await
+}
+
+/// POST `payload` as JSON to a webhook `url`. Non-2xx responses count as
+/// failures.
+pub async fn post_json<T: Serialize>(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    url: &str,
+    payload: &T,
+) -> Result<(), String> {
+    let body = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    let request = Request::post(url)
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .map_err(|e| e.to_string())?;
+    match timeout(
+        Duration::from_secs(WEBHOOK_TIMEOUT_SECS),
+        client.request(request),
+    )
+    .await
+    {
+        Ok(Ok(resp)) if resp.status().is_success() => Ok(()),
+        Ok(Ok(resp)) => Err(format!("webhook returned {}", resp.status())),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("webhook timed out after {}s", WEBHOOK_TIMEOUT_SECS)),
+    }
+}
+
+/// Evaluate `alerts` every [`EVAL_SECS`] and notify webhooks of changes.
+pub async fn alerting_loop(state: Arc<AppState>) {
+    let mut ticker = interval(Duration::from_secs(EVAL_SECS));
+    loop {
+        ticker.tick().await;
+        let router_state = state.state.load_full();
+        let now = unix_now();
+        let events = state.alerts.evaluate(
+            &router_state.alerts,
+            |rule| {
+                metric_value(
+                    rule,
+                    &router_state,
+                    &state.routing_stats,
+                    &state.consistency,
+                    now,
+                )
+            },
+            now,
+        );
+        for (name, alert) in state.alerts.snapshot() {
+            let firing = alert.status == AlertStatus::Firing;
+            gauge!("router_alert_firing", "alert" => name).set(if firing { 1.0 } else { 0.0 });
+        }
+
+        for event in events {
+            match event.status {
+                AlertTransition::Firing => warn!(
+                    "Alert '{}' firing (value {:?}, threshold {})",
+                    event.alert, event.value, event.threshold
+                ),
+                AlertTransition::Resolved => info!("Alert '{}' resolved", event.alert),
+            }
+            let client = state.client.clone();
+            tokio::spawn(async move {
+                if let Err(e) = send_webhook(&client, &event).await {
+                    warn!(
+                        "Failed to deliver alert '{}' webhook: {}",
+                        event.alert,
+                        redact(&e)
+                    );
+                    counter!("alert_webhook_failures_total", "alert" => event.alert.clone())
+                        .increment(1);
+                }
+            });
+        }
+    }
+}
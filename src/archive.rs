@@ -0,0 +1,34 @@
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+/// Methods whose first parameter is the slot they read. `getTransaction` and
+/// `getSignaturesForAddress` carry no slot, so their depth cannot be told.
+pub const SLOT_METHODS: &[&str] = &[
+    "getBlock",
+    "getBlockTime",
+    "getBlocks",
+    "getBlocksWithLimit",
+];
+
+#[derive(Deserialize)]
+struct SlotProbe<'a> {
+    #[serde(borrow)]
+    params: Option<Vec<&'a RawValue>>,
+}
+
+/// Whether `method` addresses a slot that [`requested_slot`] can read.
+pub fn is_slot_method(method: &str) -> bool {
+    SLOT_METHODS.contains(&method)
+}
+
+/// The slot a single JSON-RPC request for one of [`SLOT_METHODS`] reads (the
+/// start slot of a range).
+pub fn requested_slot(body: &[u8]) -> Option<u64> {
+    let probe: SlotProbe = serde_json::from_slice(body).ok()?;
+    serde_json::from_str(probe.params?.first()?.get()).ok()
+}
+
+/// Whether `slot` is at least `min_depth` slots behind `tip`.
+pub fn is_deep(slot: u64, tip: u64, min_depth: u64) -> bool {
+    tip.saturating_sub(slot) >= min_depth
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::redact::key_fingerprint;
+
+/// `tracing` target of audit log lines, so they can be routed or filtered on
+/// their own (e.g. `RUST_LOG=audit=info`).
+pub const AUDIT_TARGET: &str = "audit";
+
+/// Accepted values of a key's `privacy` field.
+pub const PRIVACY_LEVELS: &[&str] = &["none", "metadata", "full"];
+
+/// How much the router may log, capture or export about one key's traffic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogPrivacy {
+    /// Nothing: no access log lines, audit records, dead letters or ledger entries
+    None,
+    /// Method, backend, status and timing, but never request parameters or bodies
+    Metadata,
+    #[default]
+    Full,
+}
+
+impl LogPrivacy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(Self::None),
+            "metadata" => Ok(Self::Metadata),
+            "full" => Ok(Self::Full),
+            other => Err(format!(
+                "Invalid privacy level '{}': expected one of {}",
+                other,
+                PRIVACY_LEVELS.join(", ")
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Metadata => "metadata",
+            Self::Full => "full",
+        }
+    }
+
+    /// Whether the key's requests may appear in logs and exports at all.
+    pub fn allows_metadata(self) -> bool {
+        self != Self::None
+    }
+
+    /// Whether request parameters, or anything derived from them, may be kept.
+    pub fn allows_params(self) -> bool {
+        self == Self::Full
+    }
+}
+
+/// Short SHA-256 of the `params` of a JSON-RPC request, or of every request's
+/// `params` in order for a batch. Identical calls hash the same without the
+/// parameters themselves reaching the logs. `None` if the body is not JSON.
+pub fn params_hash(body: &[u8]) -> Option<String> {
+    let request: Value = serde_json::from_slice(body).ok()?;
+    let params_of = |r: &Value| r.get("params").cloned().unwrap_or(Value::Null);
+    let params = match &request {
+        Value::Array(batch) => Value::Array(batch.iter().map(params_of).collect()),
+        single => params_of(single),
+    };
+    let digest = Sha256::digest(params.to_string().as_bytes());
+    Some(hex::encode(&digest[..6]))
+}
+
+/// What is known about an audited request before it is forwarded.
+pub struct AuditRecord {
+    key: String,
+    owner: String,
+    rpc_method: Option<String>,
+    params_hash: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(api_key: &str, owner: &str, rpc_method: Option<&str>, body: &[u8]) -> Self {
+        Self {
+            key: key_fingerprint(api_key),
+            owner: owner.to_string(),
+            rpc_method: rpc_method.map(str::to_string),
+            params_hash: params_hash(body),
+        }
+    }
+
+    /// Drop what `privacy` does not allow to be logged.
+    pub fn with_privacy(mut self, privacy: LogPrivacy) -> Self {
+        if !privacy.allows_params() {
+            self.params_hash = None;
+        }
+        self
+    }
+
+    /// Log the request with its outcome. `latency` is measured to the response
+    /// headers; `backend` is `cache` for cached answers.
+    pub fn log(&self, status: StatusCode, backend: Option<&str>, latency: Duration) {
+        info!(
+            target: AUDIT_TARGET,
+            key = %self.key,
+            owner = %self.owner,
+            rpc_method = self.rpc_method.as_deref().unwrap_or("-"),
+            params_hash = self.params_hash.as_deref().unwrap_or("-"),
+            backend = backend.unwrap_or("-"),
+            status = status.as_u16(),
+            latency_ms = latency.as_secs_f64() * 1000.0,
+            "audited request"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use metrics::gauge;
+use tokio::time::{interval, Duration};
+use tracing::debug;
+
+use crate::{
+    config::{RoutingConfig, RoutingMode},
+    signing::unix_now,
+    state::AppState,
+    stats::BackendMethodStats,
+};
+
+/// Learned backend shares per method, in basis points (1/10000) keyed by method
+/// then backend label. Backends missing from a method's map get the minimum share.
+pub type AutoWeights = HashMap<String, HashMap<String, u32>>;
+
+const TOTAL_BASIS_POINTS: u32 = 10_000;
+
+/// How often shares are recomputed from the routing statistics.
+pub const RECOMPUTE_SECS: u64 = 10;
+
+/// Derive per-method shares from routing statistics. Each backend with at least
+/// `min_samples` requests scores `success_rate² / p50_ms`; every scored backend
+/// keeps `min_share_percent` and the rest is split in proportion to the scores.
+pub fn compute_weights(
+    stats: &BTreeMap<String, BTreeMap<String, BackendMethodStats>>,
+    config: &RoutingConfig,
+) -> AutoWeights {
+    let min_share = config.min_share_percent * 100;
+    stats
+        .iter()
+        .filter_map(|(method, backends)| {
+            let scores: Vec<(&String, f64)> = backends
+                .iter()
+                .filter(|(_, s)| s.requests >= config.min_samples)
+                .map(|(label, s)| {
+                    let score = s
+                        .p50_ms
+                        .map_or(0.0, |p50| s.success_rate.powi(2) / p50.max(1) as f64);
+                    (label, score)
+                })
+                .collect();
+            if scores.is_empty() {
+                return None;
+            }
+
+            let free = TOTAL_BASIS_POINTS.saturating_sub(min_share * scores.len() as u32);
+            let total: f64 = scores.iter().map(|(_, score)| score).sum();
+            let shares = scores
+                .iter()
+                .map(|(label, score)| {
+                    let fraction = if total > 0.0 {
+                        score / total
+                    } else {
+                        1.0 / scores.len() as f64
+                    };
+                    let share = min_share + (free as f64 * fraction).round() as u32;
+                    (label.to_string(), share)
+                })
+                .collect();
+            Some((method.clone(), shares))
+        })
+        .collect()
+}
+
+/// Periodically recompute `AppState::auto_weights` while `routing.mode = "auto"`.
+pub async fn auto_routing_loop(state: Arc<AppState>) {
+    let mut ticker = interval(Duration::from_secs(RECOMPUTE_SECS));
+    loop {
+        ticker.tick().await;
+        let router_state = state.state.load();
+        if router_state.routing.mode != RoutingMode::Auto {
+            if !state.auto_weights.load().is_empty() {
+                state.auto_weights.store(Arc::new(AutoWeights::new()));
+            }
+            continue;
+        }
+
+        let weights = compute_weights(
+            &state.routing_stats.snapshot(unix_now()),
+            &router_state.routing,
+        );
+        for (method, shares) in &weights {
+            for (backend, share) in shares {
+                gauge!("rpc_auto_route_share", "rpc_method" => method.clone(), "backend" => backend.clone())
+                    .set(f64::from(*share) / 100.0);
+            }
+        }
+        debug!("Auto routing shares updated for {} methods", weights.len());
+        state.auto_weights.store(Arc::new(weights));
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use axum::{
+    http::{HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use rand::Rng;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::error_templates::render;
+
+/// JSON-RPC error code of requests the router sheds instead of forwarding.
+pub const SHED_ERROR_CODE: i64 = -32050;
+
+/// Response header carrying the id a shed request is logged under.
+pub const CORRELATION_HEADER: &str = "x-correlation-id";
+
+/// Bounds of the suggested backoff.
+const MIN_RETRY_AFTER: Duration = Duration::from_millis(100);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// How many other requests were waiting when one was shed, relative to the
+/// capacity they wait for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueDepth {
+    /// Nothing queued: the request was refused outright
+    Empty,
+    /// Fewer waiting than there are slots
+    Low,
+    /// At least one full round of slots waiting
+    High,
+}
+
+impl QueueDepth {
+    pub fn classify(waiting: usize, slots: usize) -> Self {
+        match waiting {
+            0 => QueueDepth::Empty,
+            n if n < slots => QueueDepth::Low,
+            _ => QueueDepth::High,
+        }
+    }
+}
+
+/// What a client is told about a request the router shed, so SDKs can back
+/// off adaptively instead of retrying blind.
+#[derive(Debug, Clone)]
+pub struct RetryHint {
+    pub retry_after: Duration,
+    pub queue_depth: QueueDepth,
+    /// Random id tying the client's error to the router's log line
+    pub correlation_id: String,
+}
+
+impl RetryHint {
+    /// Hint with a fresh correlation id; `retry_after` is clamped to 100ms..30s.
+    pub fn new(retry_after: Duration, queue_depth: QueueDepth) -> Self {
+        Self {
+            retry_after: retry_after.clamp(MIN_RETRY_AFTER, MAX_RETRY_AFTER),
+            queue_depth,
+            correlation_id: hex::encode(rand::thread_rng().gen::<[u8; 8]>()),
+        }
+    }
+
+    /// Hint for a request that timed out waiting for one of `slots` heavy slots
+    /// while `waiting` others queued too. Slots free up about once per
+    /// `latency / slots`, so the queue ahead drains in roughly that many turns.
+    pub fn for_queue(latency: Duration, waiting: usize, slots: usize) -> Self {
+        let slots = slots.max(1);
+        let turns = (waiting / slots + 1) as u32;
+        Self::new(latency * turns, QueueDepth::classify(waiting, slots))
+    }
+
+    /// `503` with a JSON-RPC error for every request in `body` (one, or each of a
+    /// batch) carrying this hint, plus `Retry-After` and the correlation id.
+    pub fn response(&self, message: &str, body: &[u8]) -> Response {
+        self.response_with_status(StatusCode::SERVICE_UNAVAILABLE, message, body)
+    }
+
+    /// [`response`](Self::response) with another status, e.g. `429` for
+    /// requests over a concurrency limit.
+    pub fn response_with_status(&self, status: StatusCode, message: &str, body: &[u8]) -> Response {
+        let error = |id: &Value| {
+            json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": SHED_ERROR_CODE,
+                    "message": message,
+                    "data": {
+                        "retry_after_ms": self.retry_after.as_millis() as u64,
+                        "queue_depth": self.queue_depth,
+                        "correlation_id": self.correlation_id,
+                    },
+                },
+                "id": id,
+            })
+        };
+        let id_of = |request: &Value| request.get("id").cloned().unwrap_or(Value::Null);
+        let payload = match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Array(batch)) if !batch.is_empty() => {
+                Value::Array(batch.iter().map(|r| error(&id_of(r))).collect())
+            }
+            Ok(request) => error(&id_of(&request)),
+            Err(_) => error(&Value::Null),
+        };
+
+        self.with_headers(
+            (
+                status,
+                [("content-type", "application/json")],
+                payload.to_string(),
+            )
+                .into_response(),
+        )
+    }
+
+    /// `503` with `template` (from `error_templates.no_backend`) rendered with
+    /// this hint, plus `Retry-After` and the correlation id.
+    pub fn templated_response(&self, template: &Value, message: &str) -> Response {
+        let status = StatusCode::SERVICE_UNAVAILABLE;
+        let vars = [
+            ("status", Value::from(status.as_u16())),
+            ("message", Value::from(message)),
+            (
+                "retry_after_ms",
+                Value::from(self.retry_after.as_millis() as u64),
+            ),
+            ("queue_depth", json!(self.queue_depth)),
+            ("correlation_id", Value::from(self.correlation_id.as_str())),
+        ];
+        self.with_headers((status, Json(render(template, &vars))).into_response())
+    }
+
+    fn with_headers(&self, mut resp: Response) -> Response {
+        let retry_after_secs = self.retry_after.as_secs_f64().ceil() as u64;
+        let headers = resp.headers_mut();
+        headers.insert("retry-after", HeaderValue::from(retry_after_secs));
+        if let Ok(id) = HeaderValue::from_str(&self.correlation_id) {
+            headers.insert(CORRELATION_HEADER, id);
+        }
+        resp
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// Running score of one backend in smooth weighted round-robin, the scheme
+/// nginx uses. Every pick raises each candidate's score by its weight and
+/// lowers the winner's by their total, so weights 3 and 1 give `a a b a`
+/// rather than random runs: every `total` picks hold each backend exactly
+/// `weight` times.
+#[derive(Debug, Default)]
+pub struct CurrentWeight(AtomicI64);
+
+impl CurrentWeight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Pick one of `candidates` by smooth weighted round-robin. Candidates of
+/// weight 0 are passed over; when all are 0 the first is returned.
+///
+/// Scores are updated without a lock. Two concurrent picks may both find the
+/// same leader, but each adds and removes the same total, so the spread
+/// evens out again over the following picks.
+pub fn pick<'a, T>(
+    candidates: impl IntoIterator<Item = &'a T>,
+    weight: impl Fn(&T) -> u32,
+    current: impl Fn(&T) -> &CurrentWeight,
+) -> Option<&'a T> {
+    let mut first = None;
+    let mut best: Option<(&T, i64)> = None;
+    let mut total = 0i64;
+    for candidate in candidates {
+        first.get_or_insert(candidate);
+        let weight = i64::from(weight(candidate));
+        if weight == 0 {
+            continue;
+        }
+        total += weight;
+        let score = current(candidate).0.fetch_add(weight, Ordering::Relaxed) + weight;
+        if best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((candidate, score));
+        }
+    }
+    match best {
+        Some((chosen, _)) => {
+            current(chosen).0.fetch_sub(total, Ordering::Relaxed);
+            Some(chosen)
+        }
+        None => first,
+    }
+}
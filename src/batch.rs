@@ -0,0 +1,125 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use futures_util::stream::{self, StreamExt};
+use serde::{de::IgnoredAny, Deserialize};
+use serde_json::{json, value::RawValue, Value};
+
+/// The request inside a batch of exactly one JSON-RPC request (`[{...}]`),
+/// sliced out of `body` without copying. `None` for anything else, including
+/// batches of notifications mixed with requests and empty batches.
+pub fn unwrap_single(body: &Bytes) -> Option<Bytes> {
+    let trimmed = body.trim_ascii();
+    let inner = trimmed.strip_prefix(b"[")?.strip_suffix(b"]")?.trim_ascii();
+    // One object and nothing after it: `{..},{..}` fails on the trailing characters
+    if !inner.starts_with(b"{") || serde_json::from_slice::<IgnoredAny>(inner).is_err() {
+        return None;
+    }
+    Some(body.slice_ref(inner))
+}
+
+/// Wrap a JSON response body in `[` `]`, back into the batch shape the client
+/// sent. The body is streamed, not buffered. Non-JSON and encoded responses
+/// (plain-text errors, or compressed bodies) are returned as they are.
+pub fn rewrap(response: Response) -> Response {
+    if !is_plain_json(response.headers()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    if let Some(length) = parts
+        .headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+    {
+        parts
+            .headers
+            .insert("content-length", HeaderValue::from(length + 2));
+    }
+    let open = stream::once(async { Ok::<_, axum::Error>(Bytes::from_static(b"[")) });
+    let close = stream::once(async { Ok(Bytes::from_static(b"]")) });
+    let body = Body::from_stream(open.chain(body.into_data_stream()).chain(close));
+    Response::from_parts(parts, body)
+}
+
+/// Whether a response with `headers` has a JSON body that can be used as it is
+/// (not compressed).
+pub fn is_plain_json(headers: &HeaderMap) -> bool {
+    let json = headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    json && !headers.contains_key("content-encoding")
+}
+
+/// One request of a split batch.
+#[derive(Debug, Clone)]
+pub struct BatchItem {
+    /// The request's bytes, sliced out of the batch
+    pub body: Bytes,
+    /// Its `id`, `null` for notifications
+    pub id: Value,
+}
+
+#[derive(Deserialize)]
+struct ItemProbe<'a> {
+    method: Option<&'a str>,
+    #[serde(default)]
+    id: Value,
+}
+
+/// The requests of a batch of two or more, in order. `None` unless every
+/// element is an object with a `method`, so malformed batches reach the
+/// backend as they are and get its error.
+pub fn split(body: &Bytes) -> Option<Vec<BatchItem>> {
+    let raw: Vec<&RawValue> = serde_json::from_slice(body.trim_ascii()).ok()?;
+    if raw.len() < 2 {
+        return None;
+    }
+    raw.into_iter()
+        .map(|item| {
+            let probe: ItemProbe = serde_json::from_str(item.get()).ok()?;
+            probe.method?;
+            Some(BatchItem {
+                body: body.slice_ref(item.get().as_bytes()),
+                id: probe.id,
+            })
+        })
+        .collect()
+}
+
+/// JSON-RPC error standing in for the answer to a batch request that got a
+/// non-JSON response (e.g. a plain-text `429`). Short text bodies become the
+/// message, anything else the status reason.
+pub fn error_entry(id: &Value, status: StatusCode, body: &[u8]) -> Bytes {
+    let message = std::str::from_utf8(body)
+        .ok()
+        .map(str::trim)
+        .filter(|text| !text.is_empty() && text.len() <= 256)
+        .or(status.canonical_reason())
+        .unwrap_or("Request failed");
+    let entry = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": -32603, "message": message, "data": {"status": status.as_u16()}},
+    });
+    Bytes::from(entry.to_string())
+}
+
+/// A JSON array of `entries`, each already JSON.
+pub fn join(entries: &[Bytes]) -> Bytes {
+    let len = entries.iter().map(|e| e.len() + 1).sum::<usize>() + 1;
+    let mut out = Vec::with_capacity(len);
+    out.push(b'[');
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 {
+            out.push(b',');
+        }
+        out.extend_from_slice(entry);
+    }
+    out.push(b']');
+    Bytes::from(out)
+}
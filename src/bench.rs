@@ -0,0 +1,258 @@
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_tls::HttpsConnector;
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use rand::Rng;
+use serde::Deserialize;
+use tokio::{
+    sync::{mpsc, Semaphore},
+    time::{interval, MissedTickBehavior},
+};
+
+/// One entry of the request mix driven by the load generator.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BenchRequest {
+    pub method: String,
+    #[serde(default = "empty_params")]
+    pub params: serde_json::Value,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn empty_params() -> serde_json::Value {
+    serde_json::Value::Array(Vec::new())
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub url: String,
+    pub rps: u32,
+    pub duration: Duration,
+    pub concurrency: usize,
+    pub mix: Vec<BenchRequest>,
+}
+
+/// Latency and outcome summary for one method (or the whole run).
+#[derive(Debug, Clone, Default)]
+pub struct LatencySummary {
+    pub requests: u64,
+    pub http_errors: u64,
+    pub rpc_errors: u64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BenchReport {
+    pub elapsed: Duration,
+    /// Ticks where every concurrency slot was busy, so the target RPS was not met.
+    pub skipped: u64,
+    pub total: LatencySummary,
+    pub per_method: BTreeMap<String, LatencySummary>,
+}
+
+enum Outcome {
+    Ok,
+    HttpError,
+    RpcError,
+}
+
+struct Sample {
+    method: String,
+    latency: Duration,
+    outcome: Outcome,
+}
+
+/// Parse a `method:weight,method:weight` shorthand into a request mix with empty params.
+pub fn parse_mix(spec: &str) -> Result<Vec<BenchRequest>, String> {
+    let mut mix = Vec::new();
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (method, weight) = match entry.split_once(':') {
+            Some((m, w)) => (
+                m,
+                w.parse::<u32>()
+                    .map_err(|_| format!("Invalid weight in mix entry '{}'", entry))?,
+            ),
+            None => (entry, 1),
+        };
+        if weight == 0 {
+            return Err(format!("Mix entry '{}' has weight 0", entry));
+        }
+        mix.push(BenchRequest {
+            method: method.to_string(),
+            params: empty_params(),
+            weight,
+        });
+    }
+    if mix.is_empty() {
+        return Err("Request mix is empty".to_string());
+    }
+    Ok(mix)
+}
+
+fn pick(mix: &[BenchRequest], total_weight: u32) -> &BenchRequest {
+    let mut roll = rand::thread_rng().gen_range(0..total_weight);
+    for entry in mix {
+        if roll < entry.weight {
+            return entry;
+        }
+        roll -= entry.weight;
+    }
+    &mix[0]
+}
+
+fn percentile(sorted: &[u64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let idx = ((sorted.len() as f64) * p) as usize;
+    sorted[idx.min(sorted.len() - 1)] as f64 / 1000.0
+}
+
+fn summarize(samples: &[&Sample]) -> LatencySummary {
+    let mut latencies: Vec<u64> = samples
+        .iter()
+        .map(|s| s.latency.as_micros() as u64)
+        .collect();
+    latencies.sort_unstable();
+
+    LatencySummary {
+        requests: samples.len() as u64,
+        http_errors: samples
+            .iter()
+            .filter(|s| matches!(s.outcome, Outcome::HttpError))
+            .count() as u64,
+        rpc_errors: samples
+            .iter()
+            .filter(|s| matches!(s.outcome, Outcome::RpcError))
+            .count() as u64,
+        p50_ms: percentile(&latencies, 0.50),
+        p90_ms: percentile(&latencies, 0.90),
+        p99_ms: percentile(&latencies, 0.99),
+        max_ms: latencies.last().copied().unwrap_or(0) as f64 / 1000.0,
+    }
+}
+
+async fn send_one(
+    client: &Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    url: &str,
+    request: &BenchRequest,
+) -> Outcome {
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": request.method,
+        "params": request.params,
+    });
+    let req = match hyper::Request::builder()
+        .method("POST")
+        .uri(url)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+    {
+        Ok(req) => req,
+        Err(_) => return Outcome::HttpError,
+    };
+
+    let response = match client.request(req).await {
+        Ok(resp) => resp,
+        Err(_) => return Outcome::HttpError,
+    };
+    if !response.status().is_success() {
+        return Outcome::HttpError;
+    }
+    match response.into_body().collect().await {
+        Ok(collected) => {
+            let bytes = collected.to_bytes();
+            match serde_json::from_slice::<serde_json::Value>(&bytes) {
+                Ok(json) if json.get("error").is_none() => Outcome::Ok,
+                _ => Outcome::RpcError,
+            }
+        }
+        Err(_) => Outcome::HttpError,
+    }
+}
+
+/// Drive the configured request mix against `options.url` at the target rate and
+/// return latency percentiles per method.
+pub async fn run(options: BenchOptions) -> BenchReport {
+    let client: Client<HttpsConnector<HttpConnector>, Full<Bytes>> =
+        Client::builder(TokioExecutor::new()).build(HttpsConnector::new());
+    let mix = Arc::new(options.mix);
+    let total_weight: u32 = mix.iter().map(|r| r.weight).sum();
+    let url = Arc::new(options.url);
+    let slots = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let (tx, mut rx) = mpsc::unbounded_channel::<Sample>();
+
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / options.rps.max(1) as f64));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+    let start = Instant::now();
+    let mut skipped = 0;
+    while start.elapsed() < options.duration {
+        ticker.tick().await;
+        let permit = match slots.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                skipped += 1;
+                continue;
+            }
+        };
+
+        let client = client.clone();
+        let mix = mix.clone();
+        let url = url.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let request = pick(&mix, total_weight);
+            let req_start = Instant::now();
+            let outcome = send_one(&client, &url, request).await;
+            let _ = tx.send(Sample {
+                method: request.method.clone(),
+                latency: req_start.elapsed(),
+                outcome,
+            });
+            drop(permit);
+        });
+    }
+    drop(tx);
+
+    let mut samples = Vec::new();
+    while let Some(sample) = rx.recv().await {
+        samples.push(sample);
+    }
+    let elapsed = start.elapsed();
+
+    let mut by_method: BTreeMap<String, Vec<&Sample>> = BTreeMap::new();
+    for sample in &samples {
+        by_method
+            .entry(sample.method.clone())
+            .or_default()
+            .push(sample);
+    }
+
+    BenchReport {
+        elapsed,
+        skipped,
+        total: summarize(&samples.iter().collect::<Vec<_>>()),
+        per_method: by_method
+            .into_iter()
+            .map(|(method, s)| (method, summarize(&s)))
+            .collect(),
+    }
+}
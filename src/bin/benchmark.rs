@@ -1,8 +1,9 @@
 use std::{
+    alloc::{GlobalAlloc, Layout, System},
     collections::HashMap,
     net::SocketAddr,
     sync::{
-        atomic::{AtomicBool, AtomicUsize, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
     time::Instant,
@@ -17,7 +18,7 @@ use axum::{
 };
 use bytes::Bytes;
 use clap::Parser;
-use http_body_util::Full;
+use http_body_util::{BodyExt, Full};
 use hyper_tls::HttpsConnector;
 use hyper_util::{client::legacy::Client, rt::TokioExecutor};
 use serde_json::{json, Value};
@@ -30,6 +31,35 @@ use sol_rpc_router::{
 };
 use tokio::sync::Barrier;
 
+/// Counts heap allocations so the run can report allocations per request. The
+/// count covers the whole process (load generator and mock upstream included),
+/// so it is meant for comparing builds, not as an absolute figure for the router.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Benchmark for sol-rpc-router")]
 struct Args {
@@ -40,23 +70,37 @@ struct Args {
     /// Duration of the benchmark in seconds
     #[arg(short, long, default_value_t = 10)]
     duration: u64,
+
+    /// Size in bytes of the upstream response body (larger bodies expose copies on the response path)
+    #[arg(long, default_value_t = 64)]
+    response_bytes: usize,
+
+    /// Exit non-zero if allocations per request exceed this (for CI regression checks)
+    #[arg(long)]
+    max_allocs_per_request: Option<f64>,
 }
 
-/// Spawn a mock upstream that returns a fixed JSON-RPC response.
-/// Returns the address it's listening on.
-async fn start_mock_upstream() -> SocketAddr {
+/// Spawn a mock upstream that returns a fixed JSON-RPC response whose `result`
+/// string is `response_bytes` long. Returns the address it's listening on.
+async fn start_mock_upstream(response_bytes: usize) -> SocketAddr {
     let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
 
+    let response = Bytes::from(
+        serde_json::to_vec(&json!({
+            "jsonrpc": "2.0",
+            "result": "x".repeat(response_bytes),
+            "id": 1
+        }))
+        .unwrap(),
+    );
+
     tokio::spawn(async move {
         let app = Router::new().route(
             "/",
-            post(|Json(_payload): Json<Value>| async {
-                Json(json!({
-                    "jsonrpc": "2.0",
-                    "result": "0x1234567890abcdef",
-                    "id": 1
-                }))
+            post(move |Json(_payload): Json<Value>| {
+                let response = response.clone();
+                async move { ([("content-type", "application/json")], response) }
             }),
         );
         axum::serve(listener, app).await.unwrap();
@@ -78,12 +122,10 @@ async fn start_router(upstream_addr: SocketAddr) -> SocketAddr {
         url: format!("http://{}", upstream_addr),
         ws_url: None,
         weight: 1,
+        ..Default::default()
     };
 
-    let runtime_backend = RuntimeBackend {
-        config: backend,
-        healthy: Arc::new(AtomicBool::new(true)),
-    };
+    let runtime_backend = RuntimeBackend::new(backend, true);
 
     let health_state = Arc::new(HealthState::new(vec!["mock-upstream".to_string()]));
 
@@ -93,13 +135,14 @@ async fn start_router(upstream_addr: SocketAddr) -> SocketAddr {
         health_state: health_state.clone(),
         proxy_timeout_secs: 30,
         health_check_config: sol_rpc_router::config::HealthCheckConfig::default(),
+        ..Default::default()
     };
 
-    let state = Arc::new(AppState {
+    let state = Arc::new(AppState::new(
         client,
         keystore,
-        state: Arc::new(ArcSwap::from_pointee(router_state)),
-    });
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
 
     let app = Router::new()
         .route("/", post(proxy))
@@ -128,7 +171,7 @@ async fn main() {
     let args = Args::parse();
 
     // 1. Start mock upstream
-    let upstream_addr = start_mock_upstream().await;
+    let upstream_addr = start_mock_upstream(args.response_bytes).await;
     println!("Mock upstream listening on {}", upstream_addr);
 
     // 2. Start router in-process (no Redis, no config file)
@@ -144,6 +187,8 @@ async fn main() {
         Client::builder(TokioExecutor::new()).build_http();
 
     let start_time = Instant::now();
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes_before = ALLOCATED_BYTES.load(Ordering::Relaxed);
     let duration = std::time::Duration::from_secs(args.duration);
     let success_count = Arc::new(AtomicUsize::new(0));
     let error_count = Arc::new(AtomicUsize::new(0));
@@ -186,7 +231,9 @@ async fn main() {
                     .unwrap();
 
                 match client.request(req).await {
-                    Ok(_) => {
+                    Ok(resp) => {
+                        // Drain the body so response handling is part of the measurement
+                        let _ = resp.into_body().collect().await;
                         local_latencies.push(req_start.elapsed().as_micros() as u64);
                         success_count.fetch_add(1, Ordering::Relaxed);
                     }
@@ -207,6 +254,8 @@ async fn main() {
     }
 
     // 4. Report results
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes_before;
     let total_success = success_count.load(Ordering::Relaxed);
     let total_errors = error_count.load(Ordering::Relaxed);
     let elapsed = start_time.elapsed().as_secs_f64();
@@ -244,4 +293,22 @@ async fn main() {
     println!("P50 Latency:     {:.2}ms", p50);
     println!("P99 Latency:     {:.2}ms", p99);
     println!("P99.9 Latency:   {:.2}ms", p999);
+
+    let total_requests = (total_success + total_errors).max(1) as f64;
+    let allocs_per_request = allocations as f64 / total_requests;
+    println!("Allocs/Request:  {:.1}", allocs_per_request);
+    println!(
+        "Bytes/Request:   {:.0}",
+        allocated_bytes as f64 / total_requests
+    );
+
+    if let Some(max) = args.max_allocs_per_request {
+        if allocs_per_request > max {
+            eprintln!(
+                "Allocations per request {:.1} exceed the limit of {:.1}",
+                allocs_per_request, max
+            );
+            std::process::exit(1);
+        }
+    }
 }
@@ -1,8 +1,16 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-
 use clap::{Parser, Subcommand};
-use rand::{distributions::Alphanumeric, Rng};
 use redis::AsyncCommands;
+use sol_rpc_router::{
+    audit::{LogPrivacy, PRIVACY_LEVELS},
+    defaults::RequestDefaults,
+    keystore::{
+        create_key, generate_key, list_keys, parse_method_list, parse_method_routes, parse_scopes,
+        parse_spend_cap, revoke_key, NewKey, KEY_INVALIDATIONS,
+    },
+    methods::{COMMITMENTS, ENCODINGS},
+    spending,
+    ws::FirehoseThrottle,
+};
 
 #[derive(Parser)]
 #[command(name = "rpc-admin")]
@@ -31,6 +39,52 @@ enum Commands {
         /// Custom API key value (auto-generated if omitted)
         #[arg(long)]
         key: Option<String>,
+        /// Create a browser key usable only from this origin (repeatable)
+        #[arg(long = "origin")]
+        origins: Vec<String>,
+        /// Commitment injected when a request omits it
+        #[arg(long, value_parser = COMMITMENTS.to_vec())]
+        commitment: Option<String>,
+        /// Response encoding injected when a request omits it
+        #[arg(long, value_parser = ENCODINGS.to_vec())]
+        encoding: Option<String>,
+        /// Route a method to a backend for this key only, e.g. `getProgramAccounts=dedicated` (repeatable)
+        #[arg(long = "route")]
+        routes: Vec<String>,
+        /// Routing tier whose `tier_routes` apply to this key
+        #[arg(long)]
+        tier: Option<String>,
+        /// What may be logged or exported about this key's traffic
+        #[arg(long, default_value = "full", value_parser = PRIVACY_LEVELS.to_vec())]
+        privacy: String,
+        /// Most vote/block/slot-update notifications per second of each kind over WebSocket
+        #[arg(long, default_value_t = 0)]
+        ws_max_notifications_per_sec: u32,
+        /// Deliver only every Nth vote/block/slot-update notification over WebSocket
+        #[arg(long, default_value_t = 0)]
+        ws_sample_every: u32,
+        /// Add an `x-timing` latency breakdown to this key's responses
+        #[arg(long)]
+        timing: bool,
+        /// Send this key's `sendTransaction` requests to every healthy backend
+        #[arg(long)]
+        broadcast: bool,
+        /// Only let this key call these methods (repeatable)
+        #[arg(long = "allow-method")]
+        allowed_methods: Vec<String>,
+        /// Let this key call the methods of this category: read, write,
+        /// subscribe, das or admin-extensions (repeatable)
+        #[arg(long = "scope")]
+        scopes: Vec<String>,
+        /// Never let this key call this method (repeatable)
+        #[arg(long = "block-method")]
+        blocked_methods: Vec<String>,
+        /// Spend after which the key is throttled (with `spending_caps` enabled)
+        #[arg(long)]
+        spend_cap: Option<String>,
+        /// Most requests of this key served at once; more are shed with 429
+        #[arg(long, default_value_t = 0)]
+        max_in_flight: u32,
     },
     /// Revoke an API key
     Revoke { key: String },
@@ -47,6 +101,51 @@ enum Commands {
         /// Activate (true) or deactivate (false)
         #[arg(long)]
         active: Option<bool>,
+        /// New default commitment ("none" clears it)
+        #[arg(long)]
+        commitment: Option<String>,
+        /// New default response encoding ("none" clears it)
+        #[arg(long)]
+        encoding: Option<String>,
+        /// Replace the key's method routes with these `method=label` entries (repeatable; "none" clears them)
+        #[arg(long = "route")]
+        routes: Vec<String>,
+        /// New routing tier ("none" clears it)
+        #[arg(long)]
+        tier: Option<String>,
+        /// New logging privacy level
+        #[arg(long, value_parser = PRIVACY_LEVELS.to_vec())]
+        privacy: Option<String>,
+        /// New WebSocket firehose notification rate limit per second (0 clears it)
+        #[arg(long)]
+        ws_max_notifications_per_sec: Option<u32>,
+        /// New WebSocket firehose sampling interval (0 clears it)
+        #[arg(long)]
+        ws_sample_every: Option<u32>,
+        /// Turn the `x-timing` latency breakdown on (true) or off (false)
+        #[arg(long)]
+        timing: Option<bool>,
+        /// Turn `sendTransaction` broadcast on (true) or off (false)
+        #[arg(long)]
+        broadcast: Option<bool>,
+        /// Replace the methods this key is limited to (repeatable; "none" lifts the limit)
+        #[arg(long = "allow-method")]
+        allowed_methods: Vec<String>,
+        /// Replace the method categories this key is limited to (repeatable; "none" clears them)
+        #[arg(long = "scope")]
+        scopes: Vec<String>,
+        /// Replace the methods this key may not call (repeatable; "none" clears them)
+        #[arg(long = "block-method")]
+        blocked_methods: Vec<String>,
+        /// New spend cap ("none" removes it)
+        #[arg(long)]
+        spend_cap: Option<String>,
+        /// Start the key's spend over from zero, e.g. after a top-up
+        #[arg(long)]
+        reset_spend: bool,
+        /// New limit on requests served at once (0 clears it)
+        #[arg(long)]
+        max_in_flight: Option<u32>,
     },
     /// List all API keys
     List,
@@ -66,51 +165,51 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             rate_limit,
             expires_at,
             key: custom_key,
+            origins,
+            commitment,
+            encoding,
+            routes,
+            tier,
+            privacy,
+            ws_max_notifications_per_sec,
+            ws_sample_every,
+            timing,
+            broadcast,
+            allowed_methods,
+            scopes,
+            blocked_methods,
+            spend_cap,
+            max_in_flight,
         } => {
-            let key: String = custom_key.unwrap_or_else(|| {
-                rand::thread_rng()
-                    .sample_iter(&Alphanumeric)
-                    .take(32)
-                    .map(char::from)
-                    .collect()
-            });
-
-            let redis_key = format!("api_key:{}", key);
-
-            let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-
-            let mut pipe = redis::pipe();
-            pipe.atomic()
-                .hset(&redis_key, "owner", &owner)
-                .hset(&redis_key, "rate_limit", rate_limit)
-                .hset(&redis_key, "created_at", created_at)
-                .hset(&redis_key, "active", "true");
-
-            if let Some(exp) = expires_at {
-                pipe.hset(&redis_key, "expires_at", exp);
-            }
-
-            let _: () = pipe.query_async(&mut con).await?;
-
-            // Also store in a set for listing
-            let _: () = con.sadd("api_keys_index", &key).await?;
+            let key = custom_key.unwrap_or_else(generate_key);
+            let new_key = NewKey {
+                owner: owner.clone(),
+                rate_limit,
+                expires_at,
+                allowed_origins: origins,
+                defaults: RequestDefaults::new(commitment, encoding)?,
+                method_routes: parse_method_routes(&routes)?,
+                tier,
+                privacy: LogPrivacy::parse(&privacy)?,
+                firehose: FirehoseThrottle {
+                    max_per_sec: ws_max_notifications_per_sec,
+                    sample_every: ws_sample_every,
+                },
+                timing,
+                broadcast,
+                allowed_methods: parse_method_list(&allowed_methods)?,
+                scopes: parse_scopes(&scopes)?,
+                blocked_methods: parse_method_list(&blocked_methods)?,
+                spend_cap: spend_cap.as_deref().map(parse_spend_cap).transpose()?,
+                max_in_flight,
+            };
+            create_key(&mut con, &key, &new_key).await?;
 
             println!("Created API key for {}:", owner);
             println!("{}", key);
         }
         Commands::Revoke { key } => {
-            let redis_key = format!("api_key:{}", key);
-            // Check existence first
-            let exists: bool = redis::cmd("EXISTS")
-                .arg(&redis_key)
-                .query_async(&mut con)
-                .await?;
-
-            if exists {
-                // Set active=false
-                let _: () = con.hset(&redis_key, "active", "false").await?;
-                // Optionally delete from index if you want to hide it
-                // let _: () = con.srem("api_keys_index", &key).await?;
+            if revoke_key(&mut con, &key).await? {
                 println!("Revoked key: {}", key);
             } else {
                 println!("Key not found: {}", key);
@@ -121,6 +220,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             rate_limit,
             owner,
             active,
+            commitment,
+            encoding,
+            routes,
+            tier,
+            privacy,
+            ws_max_notifications_per_sec,
+            ws_sample_every,
+            timing,
+            broadcast,
+            allowed_methods,
+            scopes,
+            blocked_methods,
+            spend_cap,
+            reset_spend,
+            max_in_flight,
         } => {
             let redis_key = format!("api_key:{}", key);
             // Check existence first
@@ -153,9 +267,130 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 changes.push(format!("active -> {}", status));
             }
 
+            // Validate before writing so the router never reads a bad value
+            let set = |v: &Option<String>| v.clone().filter(|v| v != "none");
+            RequestDefaults::new(set(&commitment), set(&encoding))?;
+            for (field, value) in [
+                ("default_commitment", commitment),
+                ("default_encoding", encoding),
+            ] {
+                match value.as_deref() {
+                    None => {}
+                    Some("none") => {
+                        pipe.hdel(&redis_key, field);
+                        changes.push(format!("{} -> (none)", field));
+                    }
+                    Some(v) => {
+                        pipe.hset(&redis_key, field, v);
+                        changes.push(format!("{} -> {}", field, v));
+                    }
+                }
+            }
+
+            if routes.iter().any(|r| r == "none") {
+                pipe.hdel(&redis_key, "method_routes");
+                changes.push("method_routes -> (none)".to_string());
+            } else if !routes.is_empty() {
+                parse_method_routes(&routes)?;
+                let routes = routes.join(",");
+                pipe.hset(&redis_key, "method_routes", &routes);
+                changes.push(format!("method_routes -> {}", routes));
+            }
+
+            for (field, methods) in [
+                ("allowed_methods", allowed_methods),
+                ("blocked_methods", blocked_methods),
+            ] {
+                if methods.iter().any(|m| m == "none") {
+                    pipe.hdel(&redis_key, field);
+                    changes.push(format!("{} -> (none)", field));
+                } else if !methods.is_empty() {
+                    let methods = parse_method_list(&methods)?.join(",");
+                    pipe.hset(&redis_key, field, &methods);
+                    changes.push(format!("{} -> {}", field, methods));
+                }
+            }
+
+            if scopes.iter().any(|s| s == "none") {
+                pipe.hdel(&redis_key, "scopes");
+                changes.push("scopes -> (none)".to_string());
+            } else if !scopes.is_empty() {
+                let scopes: Vec<&str> = parse_scopes(&scopes)?.iter().map(|s| s.as_str()).collect();
+                let scopes = scopes.join(",");
+                pipe.hset(&redis_key, "scopes", &scopes);
+                changes.push(format!("scopes -> {}", scopes));
+            }
+
+            match tier.as_deref() {
+                None => {}
+                Some("none") => {
+                    pipe.hdel(&redis_key, "tier");
+                    changes.push("tier -> (none)".to_string());
+                }
+                Some(t) => {
+                    pipe.hset(&redis_key, "tier", t);
+                    changes.push(format!("tier -> {}", t));
+                }
+            }
+
+            match spend_cap.as_deref() {
+                None => {}
+                Some("none") => {
+                    pipe.hdel(&redis_key, "spend_cap");
+                    changes.push("spend_cap -> (none)".to_string());
+                }
+                Some(cap) => {
+                    let cap = parse_spend_cap(cap)?;
+                    pipe.hset(&redis_key, "spend_cap", cap);
+                    changes.push(format!("spend_cap -> {}", cap));
+                }
+            }
+            if reset_spend {
+                pipe.del(spending::redis_key(&key));
+                changes.push("spend -> 0".to_string());
+            }
+
+            if let Some(p) = privacy {
+                pipe.hset(&redis_key, "privacy", &p);
+                changes.push(format!("privacy -> {}", p));
+            }
+
+            for (field, value) in [
+                ("ws_max_notifications_per_sec", ws_max_notifications_per_sec),
+                ("ws_sample_every", ws_sample_every),
+                ("max_in_flight", max_in_flight),
+            ] {
+                match value {
+                    None => {}
+                    Some(0) => {
+                        pipe.hdel(&redis_key, field);
+                        changes.push(format!("{} -> (none)", field));
+                    }
+                    Some(v) => {
+                        pipe.hset(&redis_key, field, v);
+                        changes.push(format!("{} -> {}", field, v));
+                    }
+                }
+            }
+
+            for (field, value) in [("timing", timing), ("broadcast", broadcast)] {
+                match value {
+                    None => {}
+                    Some(true) => {
+                        pipe.hset(&redis_key, field, "true");
+                        changes.push(format!("{} -> true", field));
+                    }
+                    Some(false) => {
+                        pipe.hdel(&redis_key, field);
+                        changes.push(format!("{} -> false", field));
+                    }
+                }
+            }
+
             if changes.is_empty() {
                 println!("No changes requested for key: {}", key);
             } else {
+                pipe.publish(KEY_INVALIDATIONS, &key);
                 let _: () = pipe.query_async(&mut con).await?;
                 println!("Updated key: {}", key);
                 for change in changes {
@@ -164,22 +399,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
         Commands::List => {
-            let keys: Vec<String> = con.smembers("api_keys_index").await?;
+            let keys = list_keys(&mut con).await?;
             println!("Found {} keys:", keys.len());
-            for key in keys {
-                let redis_key = format!("api_key:{}", key);
-                let owner: Option<String> = con.hget(&redis_key, "owner").await?;
-                let active: Option<String> = con.hget(&redis_key, "active").await?;
-
-                if let Some(o) = owner {
+            for summary in keys {
+                if let Some(o) = summary.owner {
                     println!(
                         "- {} [owner={}] [active={}]",
-                        key,
-                        o,
-                        active.unwrap_or("true".to_string())
+                        summary.key, o, summary.active
                     );
                 } else {
-                    println!("- {} [missing metadata]", key);
+                    println!("- {} [missing metadata]", summary.key);
                 }
             }
         }
@@ -198,12 +427,75 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .await
                     .unwrap_or("true".to_string());
                 let created_at: u64 = con.hget(&redis_key, "created_at").await.unwrap_or(0);
+                let commitment: Option<String> = con
+                    .hget(&redis_key, "default_commitment")
+                    .await
+                    .unwrap_or(None);
+                let encoding: Option<String> = con
+                    .hget(&redis_key, "default_encoding")
+                    .await
+                    .unwrap_or(None);
+                let privacy: Option<String> = con.hget(&redis_key, "privacy").await.unwrap_or(None);
+                let ws_max_per_sec: Option<String> = con
+                    .hget(&redis_key, "ws_max_notifications_per_sec")
+                    .await
+                    .unwrap_or(None);
+                let ws_sample_every: Option<String> = con
+                    .hget(&redis_key, "ws_sample_every")
+                    .await
+                    .unwrap_or(None);
+                let timing: Option<String> = con.hget(&redis_key, "timing").await.unwrap_or(None);
+                let broadcast: Option<String> =
+                    con.hget(&redis_key, "broadcast").await.unwrap_or(None);
+                let allowed_methods: Option<String> = con
+                    .hget(&redis_key, "allowed_methods")
+                    .await
+                    .unwrap_or(None);
+                let scopes: Option<String> = con.hget(&redis_key, "scopes").await.unwrap_or(None);
+                let blocked_methods: Option<String> = con
+                    .hget(&redis_key, "blocked_methods")
+                    .await
+                    .unwrap_or(None);
+                let spend_cap: Option<String> =
+                    con.hget(&redis_key, "spend_cap").await.unwrap_or(None);
+                let max_in_flight: Option<String> =
+                    con.hget(&redis_key, "max_in_flight").await.unwrap_or(None);
+                let spent_units: Option<u64> =
+                    con.get(spending::redis_key(&key)).await.unwrap_or(None);
 
                 println!("Key: {}", key);
                 println!("Owner: {}", owner);
                 println!("Active: {}", active);
                 println!("Rate Limit: {} RPS", rate_limit);
                 println!("Created At: {}", created_at);
+                println!(
+                    "Default Commitment: {}",
+                    commitment.as_deref().unwrap_or("-")
+                );
+                println!("Default Encoding: {}", encoding.as_deref().unwrap_or("-"));
+                println!("Privacy: {}", privacy.as_deref().unwrap_or("full"));
+                println!(
+                    "WS Firehose Limit: {}/s",
+                    ws_max_per_sec.as_deref().unwrap_or("-")
+                );
+                println!(
+                    "WS Firehose Sampling: every {}",
+                    ws_sample_every.as_deref().unwrap_or("-")
+                );
+                println!("Timing Header: {}", timing.as_deref() == Some("true"));
+                println!("Broadcast: {}", broadcast.as_deref() == Some("true"));
+                println!(
+                    "Allowed Methods: {}",
+                    allowed_methods.as_deref().unwrap_or("(all)")
+                );
+                println!("Scopes: {}", scopes.as_deref().unwrap_or("-"));
+                println!(
+                    "Blocked Methods: {}",
+                    blocked_methods.as_deref().unwrap_or("-")
+                );
+                println!("Spend Cap: {}", spend_cap.as_deref().unwrap_or("-"));
+                println!("Spent: {} cost units", spent_units.unwrap_or(0));
+                println!("Max In Flight: {}", max_in_flight.as_deref().unwrap_or("-"));
             } else {
                 println!("Key not found");
             }
@@ -0,0 +1,283 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use axum::body::Body;
+use base64::Engine;
+use hyper::Request;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use metrics::{counter, gauge};
+use serde_json::{json, Value};
+use tokio::time::{sleep, timeout, Duration};
+use tracing::debug;
+
+use crate::{
+    config::Backend,
+    redact::redact,
+    signing::{apply_backend_headers, apply_signature, unix_now},
+    slot_feed::slot_source,
+    state::AppState,
+};
+
+/// How often the loop checks whether the blockhash check was enabled.
+const DISABLED_POLL_SECS: u64 = 1;
+
+/// Blocks a blockhash stays usable for: the latest blockhash's
+/// `lastValidBlockHeight` is the current block height plus this.
+pub const MAX_PROCESSING_AGE: u64 = 150;
+
+/// Blockhashes remembered. Polled once a second this covers far more than the
+/// ~60-90s a blockhash lives, so expired ones are still known when they come back.
+const MAX_TRACKED: usize = 1024;
+
+#[derive(Default)]
+struct Tracked {
+    /// blockhash (base58) -> last valid block height
+    last_valid: HashMap<String, u64>,
+    /// Insertion order, oldest first, for eviction
+    order: VecDeque<String>,
+    block_height: Option<u64>,
+}
+
+/// Recent blockhashes seen through `getLatestBlockhash`, and the block height
+/// they imply.
+#[derive(Default)]
+pub struct BlockhashCache {
+    tracked: Mutex<Tracked>,
+}
+
+impl BlockhashCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the latest blockhash. The block height never goes backwards, so a
+    /// source slightly behind the last one cannot un-expire blockhashes.
+    pub fn record(&self, blockhash: &str, last_valid_block_height: u64) {
+        let mut tracked = self.tracked.lock().unwrap_or_else(|e| e.into_inner());
+        let height = last_valid_block_height.saturating_sub(MAX_PROCESSING_AGE);
+        tracked.block_height = Some(tracked.block_height.map_or(height, |h| h.max(height)));
+        if tracked
+            .last_valid
+            .insert(blockhash.to_string(), last_valid_block_height)
+            .is_none()
+        {
+            tracked.order.push_back(blockhash.to_string());
+            if tracked.order.len() > MAX_TRACKED {
+                if let Some(oldest) = tracked.order.pop_front() {
+                    tracked.last_valid.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    /// Current block height as of the last recorded blockhash.
+    pub fn block_height(&self) -> Option<u64> {
+        self.tracked
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .block_height
+    }
+
+    /// Whether `blockhash` is known and past its last valid block height. Unknown
+    /// blockhashes (too new, too old to remember, or from another cluster) are
+    /// never reported expired.
+    pub fn is_expired(&self, blockhash: &str) -> bool {
+        let tracked = self.tracked.lock().unwrap_or_else(|e| e.into_inner());
+        match (tracked.last_valid.get(blockhash), tracked.block_height) {
+            (Some(&last_valid), Some(height)) => last_valid < height,
+            _ => false,
+        }
+    }
+}
+
+/// Read a compact-u16 (1-3 bytes, 7 bits each) at `pos`, advancing it.
+fn compact_u16(bytes: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut value = 0;
+    for i in 0..3 {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Recent blockhash of a serialized legacy or versioned transaction.
+pub fn recent_blockhash(tx: &[u8]) -> Option<[u8; 32]> {
+    let mut pos = 0;
+    let signatures = compact_u16(tx, &mut pos)?;
+    pos = pos.checked_add(signatures.checked_mul(64)?)?;
+    // Versioned messages start with 0x80 | version; legacy ones with the header
+    if *tx.get(pos)? & 0x80 != 0 {
+        pos += 1;
+    }
+    // Message header: required signatures, readonly signed, readonly unsigned
+    pos += 3;
+    let accounts = compact_u16(tx, &mut pos)?;
+    pos = pos.checked_add(accounts.checked_mul(32)?)?;
+    tx.get(pos..pos.checked_add(32)?)?.try_into().ok()
+}
+
+/// First signature of a serialized transaction, which is its id on chain.
+pub fn first_signature(tx: &[u8]) -> Option<[u8; 64]> {
+    let mut pos = 0;
+    if compact_u16(tx, &mut pos)? == 0 {
+        return None;
+    }
+    tx.get(pos..pos + 64)?.try_into().ok()
+}
+
+/// A single `sendTransaction` request's id, signature and recent blockhash
+/// (both base58).
+pub struct SendTransaction {
+    pub id: Value,
+    pub signature: String,
+    pub blockhash: String,
+}
+
+impl SendTransaction {
+    /// Parse a `sendTransaction` body. `None` for batches and for anything that
+    /// cannot be decoded, which are forwarded unchecked.
+    pub fn from_request(body: &[u8]) -> Option<Self> {
+        let request: Value = serde_json::from_slice(body).ok()?;
+        let params = request.get("params")?.as_array()?;
+        let encoded = params.first()?.as_str()?;
+        let encoding = params
+            .get(1)
+            .and_then(|config| config.get("encoding"))
+            .and_then(Value::as_str)
+            .unwrap_or("base58");
+        let tx = match encoding {
+            "base58" => bs58::decode(encoded).into_vec().ok()?,
+            "base64" => base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .ok()?,
+            _ => return None,
+        };
+        Some(Self {
+            id: request.get("id").cloned().unwrap_or(Value::Null),
+            signature: bs58::encode(first_signature(&tx)?).into_string(),
+            blockhash: bs58::encode(recent_blockhash(&tx)?).into_string(),
+        })
+    }
+
+    /// The error a node returns from preflight for an unknown blockhash, so
+    /// clients handle the rejection as they already do.
+    pub fn expired_response(&self) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "error": {
+                "code": -32002,
+                "message": "Transaction simulation failed: Blockhash not found",
+                "data": {
+                    "err": "BlockhashNotFound",
+                    "logs": [],
+                    "blockhash": self.blockhash,
+                },
+            },
+            "id": self.id,
+        })
+        .to_string()
+    }
+}
+
+/// `getLatestBlockhash` at confirmed commitment from `backend`:
+/// `(blockhash, lastValidBlockHeight)`.
+pub async fn fetch_latest_blockhash(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    backend: &Backend,
+    timeout_after: Duration,
+) -> Result<(String, u64), String> {
+    let body = serde_json::to_vec(&json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getLatestBlockhash",
+        "params": [{ "commitment": "confirmed" }]
+    }))
+    .map_err(|e| e.to_string())?;
+
+    let mut req = Request::post(&backend.url)
+        .header("content-type", "application/json")
+        .body(Body::empty())
+        .map_err(|e| e.to_string())?;
+    apply_backend_headers(backend, req.headers_mut());
+    if let Some(signing) = &backend.signing {
+        apply_signature(signing, req.headers_mut(), &body, unix_now());
+    }
+    *req.body_mut() = Body::from(body);
+
+    let response = timeout(timeout_after, client.request(req))
+        .await
+        .map_err(|_| {
+            format!(
+                "getLatestBlockhash timed out after {}ms",
+                timeout_after.as_millis()
+            )
+        })?
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "getLatestBlockhash returned status: {}",
+            response.status()
+        ));
+    }
+    let bytes = http_body_util::BodyExt::collect(response.into_body())
+        .await
+        .map_err(|e| e.to_string())?
+        .to_bytes();
+    let json: Value = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    let value = &json["result"]["value"];
+    match (
+        value["blockhash"].as_str(),
+        value["lastValidBlockHeight"].as_u64(),
+    ) {
+        (Some(blockhash), Some(last_valid)) => Ok((blockhash.to_string(), last_valid)),
+        _ => Err("getLatestBlockhash response missing blockhash".to_string()),
+    }
+}
+
+/// Poll the slot source for its latest blockhash every `blockhash_check.poll_ms`
+/// while the check is enabled.
+pub async fn blockhash_loop(state: Arc<AppState>) {
+    loop {
+        let router_state = state.state.load_full();
+        let config = &router_state.blockhash_check;
+        if !config.enabled {
+            sleep(Duration::from_secs(DISABLED_POLL_SECS)).await;
+            continue;
+        }
+        let poll = Duration::from_millis(config.poll_ms);
+
+        if let Some(backend) = slot_source(&router_state) {
+            match fetch_latest_blockhash(
+                &state.client_for(&backend.config, false),
+                &backend.config,
+                poll,
+            )
+            .await
+            {
+                Ok((blockhash, last_valid)) => {
+                    state.blockhash_cache.record(&blockhash, last_valid);
+                    if let Some(height) = state.blockhash_cache.block_height() {
+                        gauge!("blockhash_cache_block_height").set(height as f64);
+                    }
+                }
+                Err(e) => {
+                    debug!(
+                        "Blockhash poll of {} failed: {}",
+                        backend.config.label,
+                        redact(&e)
+                    );
+                    counter!("blockhash_poll_errors_total", "backend" => backend.config.label.clone())
+                        .increment(1);
+                }
+            }
+        }
+        sleep(poll).await;
+    }
+}
@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+/// Remembered signatures above which expired ones are swept on insert.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// Signatures of recently broadcast transactions that a backend accepted, so a
+/// client resending one is not broadcast to every backend again.
+pub struct RecentSignatures {
+    accepted: Mutex<HashMap<String, Instant>>,
+}
+
+impl Default for RecentSignatures {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecentSignatures {
+    pub fn new() -> Self {
+        Self {
+            accepted: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `signature` was accepted within the last `window`.
+    pub fn contains(&self, signature: &str, window: Duration) -> bool {
+        let accepted = self.accepted.lock().unwrap_or_else(|e| e.into_inner());
+        accepted
+            .get(signature)
+            .is_some_and(|at| at.elapsed() < window)
+    }
+
+    /// Remember that `signature` was accepted, forgetting signatures older
+    /// than `window` once many have built up.
+    pub fn insert(&self, signature: &str, window: Duration) {
+        let mut accepted = self.accepted.lock().unwrap_or_else(|e| e.into_inner());
+        if accepted.len() > SWEEP_THRESHOLD {
+            accepted.retain(|_, at| at.elapsed() < window);
+        }
+        accepted.insert(signature.to_string(), Instant::now());
+    }
+}
+
+/// Whether a backend's `sendTransaction` answer accepted the transaction: a
+/// JSON-RPC response with a `result` and no `error`.
+pub fn is_accepted(body: &[u8]) -> bool {
+    serde_json::from_slice::<Value>(body)
+        .is_ok_and(|response| response.get("result").is_some() && response.get("error").is_none())
+}
+
+/// The answer to a transaction that was already accepted: its signature, as a
+/// backend returns it.
+pub fn accepted_response(signature: &str, id: &Value) -> String {
+    json!({ "jsonrpc": "2.0", "result": signature, "id": id }).to_string()
+}
@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::http::HeaderMap;
+
+use crate::{config::BrowserKeyConfig, keystore::KeyInfo, net::rate_limit_subject};
+
+/// Entries older than this are dropped when the limiter map is swept.
+const WINDOW_RETENTION: Duration = Duration::from_secs(10);
+const SWEEP_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BrowserRejection {
+    /// No `Origin`/`Referer` header, or one not in the key's allowed origins
+    OriginNotAllowed,
+    /// The method is blocked for browser keys
+    MethodNotAllowed(String),
+    /// The per-origin+IP rate limit was exceeded
+    RateLimited,
+}
+
+struct Window {
+    started: Instant,
+    count: u64,
+}
+
+/// Fixed one-second window counters keyed by (key owner, origin, client IP).
+/// IPv6 clients are counted per /64 (see [`rate_limit_subject`]).
+///
+/// Browser keys are public, so the key-level rate limit alone would let one page
+/// (or one abusive visitor) consume the whole quota.
+pub struct OriginLimiter {
+    windows: Mutex<HashMap<(String, String, IpAddr), Window>>,
+}
+
+impl Default for OriginLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OriginLimiter {
+    pub fn new() -> Self {
+        Self {
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count one request and return whether it is within `limit` per second.
+    pub fn check(&self, owner: &str, origin: &str, ip: IpAddr, limit: u64) -> bool {
+        if limit == 0 {
+            return true;
+        }
+
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+
+        if windows.len() > SWEEP_THRESHOLD {
+            windows.retain(|_, w| now.duration_since(w.started) < WINDOW_RETENTION);
+        }
+
+        let window = windows
+            .entry((
+                owner.to_string(),
+                origin.to_string(),
+                rate_limit_subject(ip),
+            ))
+            .or_insert(Window {
+                started: now,
+                count: 0,
+            });
+        if now.duration_since(window.started) >= Duration::from_secs(1) {
+            window.started = now;
+            window.count = 0;
+        }
+        window.count += 1;
+        window.count <= limit
+    }
+}
+
+/// Origin of the request: the `Origin` header, or the scheme+host of `Referer`.
+pub fn request_origin(headers: &HeaderMap) -> Option<String> {
+    if let Some(origin) = headers.get("origin").and_then(|v| v.to_str().ok()) {
+        if origin != "null" {
+            return Some(origin.trim_end_matches('/').to_ascii_lowercase());
+        }
+    }
+
+    let referer = headers.get("referer").and_then(|v| v.to_str().ok())?;
+    let (scheme, rest) = referer.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    if host.is_empty() {
+        return None;
+    }
+    Some(format!("{}://{}", scheme, host).to_ascii_lowercase())
+}
+
+/// Match an origin against an allowed pattern. Patterns are exact origins or a
+/// leading wildcard subdomain such as `https://*.example.com`.
+pub fn origin_matches(pattern: &str, origin: &str) -> bool {
+    let pattern = pattern.trim_end_matches('/').to_ascii_lowercase();
+    if pattern == origin {
+        return true;
+    }
+
+    match (pattern.split_once("://*."), origin.split_once("://")) {
+        (Some((p_scheme, p_domain)), Some((o_scheme, o_host))) => {
+            p_scheme == o_scheme
+                && o_host
+                    .strip_suffix(p_domain)
+                    .is_some_and(|prefix| prefix.ends_with('.') && prefix.len() > 1)
+        }
+        _ => false,
+    }
+}
+
+/// Enforce origin binding, method restrictions and the per-origin+IP limit for a
+/// browser key. `method` is `None` for WebSocket upgrades.
+pub fn check_browser_request(
+    info: &KeyInfo,
+    config: &BrowserKeyConfig,
+    limiter: &OriginLimiter,
+    headers: &HeaderMap,
+    client_ip: IpAddr,
+    method: Option<&str>,
+) -> Result<(), BrowserRejection> {
+    let origin = request_origin(headers).ok_or(BrowserRejection::OriginNotAllowed)?;
+    if !info
+        .allowed_origins
+        .iter()
+        .any(|pattern| origin_matches(pattern, &origin))
+    {
+        return Err(BrowserRejection::OriginNotAllowed);
+    }
+
+    if let Some(method) = method {
+        if config.blocked_methods.iter().any(|m| m == method) {
+            return Err(BrowserRejection::MethodNotAllowed(method.to_string()));
+        }
+    }
+
+    if !limiter.check(
+        &info.owner,
+        &origin,
+        client_ip,
+        config.per_origin_ip_rate_limit,
+    ) {
+        return Err(BrowserRejection::RateLimited);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,437 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use moka::{future::Cache, Expiry};
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::config::{Commitment, CommitmentTtl};
+
+/// Roughly one slot: the unit of `cache.commitment_ttl_slots`.
+const SLOT_TTL: Duration = Duration::from_millis(400);
+
+/// Upper bound of each `cache.commitment_ttl_slots` entry, about a minute.
+pub const MAX_TTL_SLOTS: u32 = 150;
+
+/// Upper bound of each `cache.method_ttl_secs` entry, a day.
+pub const MAX_METHOD_TTL_SECS: u64 = 86_400;
+
+/// Distinct param combinations kept per cache; these methods take few params.
+const MAX_ENTRIES: u64 = 1_000;
+
+/// Upper bound of `cache.serve_stale_secs`: last-known results are kept this long.
+pub const MAX_STALE_SECS: u64 = 3_600;
+
+/// Last-known results kept for outages, across all methods.
+const MAX_STALE_ENTRIES: u64 = 10_000;
+
+/// How long a method's result may be served from the router's cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Never changes for the life of the cluster
+    Forever,
+    /// Changes at most once per slot
+    PerSlot,
+    /// Reused for the seconds configured in `cache.method_ttl_secs`
+    Configured,
+    /// Network-wide stats, reused for `cache.network_stats.ttl_secs`
+    Network,
+    /// Never served while a backend is healthy; only remembered for outages
+    /// (`cache.stale_methods`)
+    OutageOnly,
+}
+
+/// Methods the router answers from its own cache.
+pub fn cache_policy(method: &str) -> Option<CachePolicy> {
+    match method {
+        "getGenesisHash" | "getEpochSchedule" => Some(CachePolicy::Forever),
+        // Wallets poll it constantly; keyed by owner, mint or program, and encoding
+        "getEpochInfo" | "getTokenAccountsByOwner" => Some(CachePolicy::PerSlot),
+        _ => None,
+    }
+}
+
+/// Account reads answered with a `context.slot` that accept `minContextSlot`,
+/// and how many params come before their config object.
+pub fn context_config_index(method: &str) -> Option<usize> {
+    match method {
+        "getAccountInfo" | "getBalance" | "getMultipleAccounts" | "getProgramAccounts" => Some(1),
+        "getTokenAccountsByOwner" | "getTokenAccountsByDelegate" => Some(2),
+        _ => None,
+    }
+}
+
+/// JSON-RPC error of a node that has not reached a request's `minContextSlot`.
+const MIN_CONTEXT_SLOT_NOT_REACHED: i64 = -32016;
+
+/// `body` asking for a result newer than `slot`: `minContextSlot` is raised to
+/// `slot + 1` in its config object, which is added if the request has none.
+/// `None` for requests that are not revalidated this way.
+pub fn with_min_context_slot(body: &[u8], slot: u64) -> Option<Vec<u8>> {
+    let mut request: Value = serde_json::from_slice(body).ok()?;
+    let index = context_config_index(request.get("method")?.as_str()?)?;
+    let params = request.get_mut("params")?.as_array_mut()?;
+    if params.len() == index {
+        params.push(Value::Object(Default::default()));
+    }
+    let config = params.get_mut(index)?.as_object_mut()?;
+    let min_slot = config
+        .get("minContextSlot")
+        .and_then(Value::as_u64)
+        .unwrap_or(0)
+        .max(slot + 1);
+    config.insert("minContextSlot".to_string(), min_slot.into());
+    serde_json::to_vec(&request).ok()
+}
+
+/// Whether `body` is the error of a node behind the request's `minContextSlot`.
+pub fn min_context_slot_not_reached(body: &[u8]) -> bool {
+    serde_json::from_slice::<Value>(body).is_ok_and(|response| {
+        response
+            .get("error")
+            .and_then(|e| e.get("code"))
+            .and_then(Value::as_i64)
+            == Some(MIN_CONTEXT_SLOT_NOT_REACHED)
+    })
+}
+
+/// A single JSON-RPC request, identified so that identical reads share a key.
+#[derive(Debug, Clone)]
+pub struct ReadKey {
+    pub method: String,
+    /// Commitment the request asks for; `None` leaves it to the node
+    pub commitment: Option<Commitment>,
+    pub key: String,
+    /// The request's `id`, serialized
+    pub id: String,
+}
+
+impl ReadKey {
+    /// `None` for batches, bodies that are not JSON-RPC and unknown commitments.
+    pub fn from_request(body: &[u8]) -> Option<Self> {
+        let request: Value = serde_json::from_slice(body).ok()?;
+        let method = request.get("method")?.as_str()?;
+        // The commitment is part of the key, normalized so deprecated names share
+        // entries with current ones. Everything else that shapes the answer
+        // (`minContextSlot`, `encoding`, `dataSlice`, ...) stays in the params,
+        // whose object fields serialize in sorted order.
+        let mut params = request.get("params").cloned().unwrap_or(Value::Null);
+        let commitment = match params
+            .as_array_mut()
+            .and_then(|params| params.last_mut())
+            .and_then(Value::as_object_mut)
+            .and_then(|config| config.remove("commitment"))
+        {
+            Some(value) => Some(value.as_str().and_then(Commitment::parse)?),
+            None => None,
+        };
+        Some(Self {
+            method: method.to_string(),
+            key: format!(
+                "{}:{}:{}",
+                method,
+                commitment.map_or("default", Commitment::as_str),
+                params
+            ),
+            commitment,
+            id: request.get("id").unwrap_or(&Value::Null).to_string(),
+        })
+    }
+
+    /// JSON-RPC response carrying `field` (`result` or `error`) under this
+    /// request's id.
+    pub fn response(&self, field: &str, value: &[u8]) -> Vec<u8> {
+        let mut body = Vec::with_capacity(value.len() + self.id.len() + 36);
+        body.extend_from_slice(br#"{"jsonrpc":"2.0",""#);
+        body.extend_from_slice(field.as_bytes());
+        body.extend_from_slice(br#"":"#);
+        body.extend_from_slice(value);
+        body.extend_from_slice(br#","id":"#);
+        body.extend_from_slice(self.id.as_bytes());
+        body.push(b'}');
+        body
+    }
+}
+
+/// A single cacheable request: where its result is stored and the id the
+/// client expects back.
+#[derive(Debug, Clone)]
+pub struct CacheLookup {
+    pub method: String,
+    pub key: String,
+    pub policy: CachePolicy,
+    /// Commitment the request asks for; `None` leaves it to the node
+    pub commitment: Option<Commitment>,
+    /// How long a [`CachePolicy::PerSlot`] or [`CachePolicy::Configured`]
+    /// result is reused
+    pub ttl: Duration,
+    /// Whether `ttl` was chosen by the TTL tuner
+    pub tuned: bool,
+    /// The request, re-sent to a second backend whose answer must agree
+    /// before the result is stored (`cache.network_stats.cross_check`)
+    pub cross_check: Option<Bytes>,
+    /// The expired result and its `context.slot`, when the request went
+    /// upstream asking for a newer slot (`cache.revalidate_methods`)
+    pub revalidating: Option<(Bytes, u64)>,
+    /// The request's `id`, serialized
+    pub id: String,
+}
+
+impl CacheLookup {
+    /// `None` for batches, bodies that are not JSON-RPC, methods without a policy
+    /// and unknown commitments.
+    pub fn from_request(body: &[u8]) -> Option<Self> {
+        Self::from_request_or_stale(body, &[])
+    }
+
+    /// Like [`from_request`](Self::from_request), also accepting `stale_methods`
+    /// as [`CachePolicy::OutageOnly`].
+    pub fn from_request_or_stale(body: &[u8], stale_methods: &[String]) -> Option<Self> {
+        Self::from_request_configured(body, &HashMap::new(), stale_methods)
+    }
+
+    /// Like [`from_request_or_stale`](Self::from_request_or_stale), also
+    /// accepting the methods of `method_ttl_secs` as [`CachePolicy::Configured`],
+    /// ahead of their built-in policy.
+    pub fn from_request_configured(
+        body: &[u8],
+        method_ttl_secs: &HashMap<String, u64>,
+        stale_methods: &[String],
+    ) -> Option<Self> {
+        let read = ReadKey::from_request(body)?;
+        let method = read.method.as_str();
+        let configured_ttl = method_ttl_secs
+            .get(method)
+            .map(|secs| Duration::from_secs(*secs));
+        let policy = configured_ttl
+            .map(|_| CachePolicy::Configured)
+            .or_else(|| cache_policy(method))
+            .or_else(|| {
+                stale_methods
+                    .iter()
+                    .any(|m| m == method)
+                    .then_some(CachePolicy::OutageOnly)
+            })?;
+        Some(Self {
+            method: read.method,
+            key: read.key,
+            policy,
+            commitment: read.commitment,
+            ttl: configured_ttl.unwrap_or(SLOT_TTL),
+            tuned: false,
+            cross_check: None,
+            revalidating: None,
+            id: read.id,
+        })
+    }
+
+    /// Reuse a per-slot result for as many slots as `ttl` allows at this
+    /// request's commitment. Other policies are left as they are.
+    pub fn with_commitment_ttl(mut self, ttl: &CommitmentTtl) -> Self {
+        if self.policy == CachePolicy::PerSlot {
+            self.ttl = SLOT_TTL * ttl.slots(self.commitment);
+        }
+        self
+    }
+
+    /// JSON-RPC response carrying a cached `result` under this request's id.
+    pub fn response(&self, result: &[u8]) -> Vec<u8> {
+        let mut body = Vec::with_capacity(result.len() + self.id.len() + 36);
+        body.extend_from_slice(br#"{"jsonrpc":"2.0","result":"#);
+        body.extend_from_slice(result);
+        body.extend_from_slice(br#","id":"#);
+        body.extend_from_slice(self.id.as_bytes());
+        body.push(b'}');
+        body
+    }
+}
+
+/// Expires each entry after the TTL stored with it, so answers at different
+/// commitments can share one cache.
+struct StoredTtl;
+
+impl Expiry<String, (Bytes, Duration)> for StoredTtl {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &(Bytes, Duration),
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(value.1)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        value: &(Bytes, Duration),
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(value.1)
+    }
+}
+
+/// Upstream fetches of uncached results in progress, by cache key.
+type Flights = Arc<Mutex<HashMap<String, watch::Sender<()>>>>;
+
+/// The outcome of [`ResponseCache::begin`] for a result that is not cached.
+pub enum Flight {
+    /// No other request is fetching it: fetch it upstream and store it while
+    /// holding the guard
+    Leader(FlightGuard),
+    /// Another request is fetching it; the receiver's `changed()` resolves
+    /// once that request is done, successful or not
+    Follower(watch::Receiver<()>),
+}
+
+/// Marks a fetch in progress until dropped, which releases its followers.
+pub struct FlightGuard {
+    flights: Flights,
+    key: String,
+}
+
+impl Drop for FlightGuard {
+    fn drop(&mut self) {
+        let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+        flights.remove(&self.key);
+    }
+}
+
+/// Results of immutable or slow-changing methods, shared by all keys. Entries
+/// are stored with their TTL, which only the expiring cache honours.
+pub struct ResponseCache {
+    forever: Cache<String, (Bytes, Duration)>,
+    /// Per-slot and configured results
+    expiring: Cache<String, (Bytes, Duration)>,
+    /// Every stored result with the time it was last known to be current and
+    /// its `context.slot`, for serving stale responses while no backend is
+    /// healthy and for revalidating expired ones
+    last_known: Cache<String, (Bytes, Instant, Option<u64>)>,
+    flights: Flights,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResponseCache {
+    pub fn new() -> Self {
+        Self {
+            forever: Cache::builder().max_capacity(MAX_ENTRIES).build(),
+            expiring: Cache::builder()
+                .max_capacity(MAX_ENTRIES)
+                .expire_after(StoredTtl)
+                .build(),
+            last_known: Cache::builder()
+                .max_capacity(MAX_STALE_ENTRIES)
+                .time_to_live(Duration::from_secs(MAX_STALE_SECS))
+                .build(),
+            flights: Flights::default(),
+        }
+    }
+
+    fn cache(&self, policy: CachePolicy) -> Option<&Cache<String, (Bytes, Duration)>> {
+        match policy {
+            CachePolicy::Forever => Some(&self.forever),
+            CachePolicy::PerSlot | CachePolicy::Configured | CachePolicy::Network => {
+                Some(&self.expiring)
+            }
+            CachePolicy::OutageOnly => None,
+        }
+    }
+
+    /// The cached `result` JSON for `lookup`, if any.
+    pub async fn get(&self, lookup: &CacheLookup) -> Option<Bytes> {
+        let (result, _) = self.get_with_ttl(lookup).await?;
+        Some(result)
+    }
+
+    /// The cached `result` JSON for `lookup` and the TTL it was stored with.
+    pub async fn get_with_ttl(&self, lookup: &CacheLookup) -> Option<(Bytes, Duration)> {
+        self.cache(lookup.policy)?.get(&lookup.key).await
+    }
+
+    /// Coalesce concurrent misses for `lookup`: the first request fetches the
+    /// result, identical ones wait for it instead of going upstream too.
+    pub fn begin(&self, lookup: &CacheLookup) -> Flight {
+        let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(flight) = flights.get(&lookup.key) {
+            return Flight::Follower(flight.subscribe());
+        }
+        flights.insert(lookup.key.clone(), watch::channel(()).0);
+        Flight::Leader(FlightGuard {
+            flights: self.flights.clone(),
+            key: lookup.key.clone(),
+        })
+    }
+
+    /// The last `result` stored for `lookup` and its age, if no older than `max_age`.
+    pub async fn get_stale(
+        &self,
+        lookup: &CacheLookup,
+        max_age: Duration,
+    ) -> Option<(Bytes, Duration)> {
+        let (result, stored, _) = self.last_known.get(&lookup.key).await?;
+        let age = stored.elapsed();
+        (age <= max_age).then_some((result, age))
+    }
+
+    /// The last `result` stored for `lookup` and its `context.slot`, if it
+    /// had one.
+    pub async fn get_with_slot(&self, lookup: &CacheLookup) -> Option<(Bytes, u64)> {
+        let (result, _, slot) = self.last_known.get(&lookup.key).await?;
+        Some((result, slot?))
+    }
+
+    /// Keep `result` for `lookup` for another TTL: a backend had nothing newer
+    /// than its `slot`.
+    pub async fn extend(&self, lookup: &CacheLookup, result: Bytes, slot: u64) {
+        if let Some(cache) = self.cache(lookup.policy) {
+            cache
+                .insert(lookup.key.clone(), (result.clone(), lookup.ttl))
+                .await;
+        }
+        self.last_known
+            .insert(lookup.key.clone(), (result, Instant::now(), Some(slot)))
+            .await;
+    }
+
+    /// Store the `result` of a successful upstream response. Error responses and
+    /// anything that is not a single JSON-RPC result are ignored. Returns whether
+    /// the result was cached.
+    pub async fn store(&self, lookup: &CacheLookup, response: &[u8]) -> bool {
+        let Ok(response) = serde_json::from_slice::<Value>(response) else {
+            return false;
+        };
+        if response.get("error").is_some() {
+            return false;
+        }
+        let Some(result) = response.get("result") else {
+            return false;
+        };
+        let slot = result
+            .get("context")
+            .and_then(|c| c.get("slot"))
+            .and_then(Value::as_u64);
+        let Ok(result) = serde_json::to_vec(result) else {
+            return false;
+        };
+        let result = Bytes::from(result);
+        let cache = self.cache(lookup.policy);
+        if let Some(cache) = cache {
+            cache
+                .insert(lookup.key.clone(), (result.clone(), lookup.ttl))
+                .await;
+        }
+        self.last_known
+            .insert(lookup.key.clone(), (result, Instant::now(), slot))
+            .await;
+        cache.is_some()
+    }
+}
@@ -0,0 +1,144 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use metrics::{counter, gauge};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config::CircuitBreakerConfig;
+
+/// State of a backend's circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Requests flow; outcomes are counted
+    Closed,
+    /// Tripped: out of rotation until the cooldown ends
+    Open,
+    /// Cooldown over: one probe request decides whether to close again
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    /// (unix second, successes, failures) within the window, oldest first
+    buckets: VecDeque<(u64, u32, u32)>,
+    /// Set while tripped; half-open from this second on
+    open_until: Option<u64>,
+    /// Cooldown in effect when the breaker tripped
+    cooldown_secs: u64,
+    /// A half-open probe is in flight until it reports back or this second passes
+    probe_until: Option<u64>,
+}
+
+/// Passive failure tracking for one backend, fed by the requests it serves.
+/// Shared across config reloads through [`HealthState`](crate::health::HealthState).
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    circuit: Mutex<Circuit>,
+}
+
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn state(&self, now: u64) -> CircuitState {
+        let circuit = self.circuit.lock().unwrap_or_else(|e| e.into_inner());
+        match circuit.open_until {
+            None => CircuitState::Closed,
+            Some(until) if now < until => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Whether the backend may be selected: closed, or half-open without a
+    /// probe in flight.
+    pub fn admits(&self, now: u64) -> bool {
+        let circuit = self.circuit.lock().unwrap_or_else(|e| e.into_inner());
+        match circuit.open_until {
+            None => true,
+            Some(until) => now >= until && circuit.probe_until.is_none_or(|p| now >= p),
+        }
+    }
+
+    /// Note a request being sent. While half-open this is the probe, and the
+    /// backend is not selected again until it reports back (or a cooldown passes).
+    pub fn begin(&self, now: u64) {
+        let mut circuit = self.circuit.lock().unwrap_or_else(|e| e.into_inner());
+        if circuit.open_until.is_some_and(|until| now >= until)
+            && circuit.probe_until.is_none_or(|p| now >= p)
+        {
+            circuit.probe_until = Some(now + circuit.cooldown_secs);
+        }
+    }
+
+    /// Count the outcome of one request to `label`. Trips the breaker when at
+    /// least `min_requests` in the window failed at `failure_percent` or more;
+    /// a half-open probe closes it on success and reopens it on failure.
+    pub fn record(&self, label: &str, success: bool, now: u64, config: &CircuitBreakerConfig) {
+        let mut circuit = self.circuit.lock().unwrap_or_else(|e| e.into_inner());
+        match circuit.open_until {
+            // Requests sent before the breaker tripped
+            Some(until) if now < until => return,
+            Some(_) if success => {
+                *circuit = Circuit::default();
+                gauge!("rpc_circuit_open", "backend" => label.to_string()).set(0.0);
+                info!("Circuit breaker for '{}' closed: probe succeeded", label);
+                return;
+            }
+            Some(_) => {
+                circuit.open_until = Some(now + config.cooldown_secs);
+                circuit.cooldown_secs = config.cooldown_secs;
+                circuit.probe_until = None;
+                warn!("Circuit breaker for '{}' reopened: probe failed", label);
+                return;
+            }
+            None => {}
+        }
+
+        match circuit.buckets.back_mut() {
+            Some((second, ok, failed)) if *second == now => {
+                *if success { ok } else { failed } += 1;
+            }
+            _ => circuit
+                .buckets
+                .push_back((now, success as u32, !success as u32)),
+        }
+        let oldest = now.saturating_sub(config.window_secs.saturating_sub(1));
+        while circuit.buckets.front().is_some_and(|(s, _, _)| *s < oldest) {
+            circuit.buckets.pop_front();
+        }
+
+        let (ok, failed) = circuit
+            .buckets
+            .iter()
+            .fold((0u64, 0u64), |(o, f), (_, ok, failed)| {
+                (o + *ok as u64, f + *failed as u64)
+            });
+        let total = ok + failed;
+        if total >= config.min_requests as u64
+            && failed * 100 >= config.failure_percent as u64 * total
+        {
+            *circuit = Circuit {
+                open_until: Some(now + config.cooldown_secs),
+                cooldown_secs: config.cooldown_secs,
+                ..Default::default()
+            };
+            gauge!("rpc_circuit_open", "backend" => label.to_string()).set(1.0);
+            counter!("rpc_circuit_trips_total", "backend" => label.to_string()).increment(1);
+            warn!(
+                "Circuit breaker for '{}' tripped: {} of {} requests failed in {}s, out of rotation for {}s",
+                label, failed, total, config.window_secs, config.cooldown_secs
+            );
+        }
+    }
+
+    /// Close the breaker and forget its window, e.g. when breakers are disabled.
+    pub fn reset(&self, label: &str) {
+        let mut circuit = self.circuit.lock().unwrap_or_else(|e| e.into_inner());
+        if circuit.open_until.is_some() {
+            gauge!("rpc_circuit_open", "backend" => label.to_string()).set(0.0);
+        }
+        *circuit = Circuit::default();
+    }
+}
@@ -0,0 +1,382 @@
+use std::time::Duration;
+
+use clap::{Args, Subcommand};
+use http_body_util::BodyExt;
+use hyper_tls::HttpsConnector;
+use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+
+use crate::{
+    audit::{LogPrivacy, PRIVACY_LEVELS},
+    bench::{self, BenchOptions, BenchRequest},
+    config::{load_config, ValueSource},
+    defaults::RequestDefaults,
+    keystore::{create_key, generate_key, list_keys, parse_method_routes, revoke_key, NewKey},
+    methods::{COMMITMENTS, ENCODINGS},
+    redact::redact_url,
+    reload::probe_backends,
+    state::RouterState,
+};
+
+/// Subcommands of the `sol-rpc-router` binary. Running without a subcommand is
+/// equivalent to `serve`.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the router
+    Serve,
+    /// Validate the configuration file and print a summary
+    CheckConfig {
+        /// Also send a health check to every backend and fail if any is unreachable
+        #[arg(long)]
+        probe: bool,
+    },
+    /// Manage API keys directly in the keystore
+    Keys {
+        /// Redis connection URL (defaults to `redis_url` from the config file)
+        #[arg(long, env = "REDIS_URL")]
+        redis_url: Option<String>,
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+    /// Inspect the backends of a running router
+    Backends {
+        /// Base URL of the running router (defaults to http://127.0.0.1:<port>)
+        #[arg(long)]
+        router_url: Option<String>,
+        #[command(subcommand)]
+        command: BackendsCommand,
+    },
+    /// Generate load against the router or a backend and report latency percentiles
+    Bench(BenchArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Target URL (defaults to the router at http://127.0.0.1:<port>)
+    #[arg(long)]
+    pub url: Option<String>,
+    /// Send directly to the configured backend with this label instead of the router
+    #[arg(long, conflicts_with = "url")]
+    pub backend: Option<String>,
+    /// API key appended as `?api-key=` to the target URL
+    #[arg(long, env = "ROUTER_API_KEY")]
+    pub api_key: Option<String>,
+    /// Weighted method mix, e.g. `getSlot:5,getLatestBlockhash:1`
+    #[arg(long, default_value = "getSlot")]
+    pub methods: String,
+    /// JSON file with `[{"method", "params", "weight"}]` entries (overrides --methods)
+    #[arg(long)]
+    pub mix_file: Option<String>,
+    /// Target requests per second
+    #[arg(long, default_value_t = 100)]
+    pub rps: u32,
+    /// Duration of the run in seconds
+    #[arg(long, default_value_t = 10)]
+    pub duration: u64,
+    /// Maximum number of requests in flight
+    #[arg(long, default_value_t = 64)]
+    pub concurrency: usize,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KeysCommand {
+    /// Create a new API key
+    Add {
+        /// Owner identifier (e.g. client-name)
+        owner: String,
+        /// Rate limit (requests per second)
+        #[arg(long, default_value_t = 10)]
+        rate_limit: u64,
+        /// Unix time from which the key is rejected (optional)
+        #[arg(long)]
+        expires_at: Option<u64>,
+        /// Custom API key value (auto-generated if omitted)
+        #[arg(long)]
+        key: Option<String>,
+        /// Create a browser key usable only from this origin (repeatable)
+        #[arg(long = "origin")]
+        origins: Vec<String>,
+        /// Commitment injected when a request omits it
+        #[arg(long, value_parser = COMMITMENTS.to_vec())]
+        commitment: Option<String>,
+        /// Response encoding injected when a request omits it
+        #[arg(long, value_parser = ENCODINGS.to_vec())]
+        encoding: Option<String>,
+        /// Route a method to a backend for this key only, e.g. `getProgramAccounts=dedicated` (repeatable)
+        #[arg(long = "route")]
+        routes: Vec<String>,
+        /// Routing tier whose `tier_routes` apply to this key
+        #[arg(long)]
+        tier: Option<String>,
+        /// What may be logged or exported about this key's traffic
+        #[arg(long, default_value = "full", value_parser = PRIVACY_LEVELS.to_vec())]
+        privacy: String,
+    },
+    /// Revoke an API key
+    Revoke { key: String },
+    /// List all API keys
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackendsCommand {
+    /// Print the health of every backend as reported by the router
+    Status,
+}
+
+pub async fn check_config(
+    config_path: &str,
+    probe: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config(config_path)?;
+
+    println!("Configuration OK: {}", config_path);
+    println!(
+        "HTTP port: {} (WebSocket: {})",
+        config.port,
+        config.port + 1
+    );
+    println!(
+        "Metrics and admin port: {} (on {})",
+        config.metrics_port,
+        config
+            .admin_bind_addresses
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if !config.overrides.is_empty() {
+        println!("Environment overrides:");
+        for (path, var) in &config.overrides {
+            println!("  - {} <- {}", path, var);
+        }
+    }
+    println!("Backends ({}):", config.backends.len());
+    for backend in &config.backends {
+        println!(
+            "  - [{}] {} (weight: {}, ws: {})",
+            backend.label,
+            redact_url(&backend.url),
+            backend.weight,
+            backend.ws_url.as_deref().unwrap_or("-")
+        );
+        if !backend.exclude_methods.is_empty() {
+            println!("      never routed: {}", backend.exclude_methods.join(", "));
+        }
+        for (method, path) in &backend.method_paths {
+            println!("      {} -> {}", method, path);
+        }
+    }
+    if !config.method_routes.is_empty() {
+        println!("Method routes:");
+        for (method, label) in &config.method_routes {
+            println!("  - {} -> {}", method, label);
+        }
+    }
+
+    if probe {
+        let client = Client::builder(TokioExecutor::new()).build(HttpsConnector::new());
+        probe_backends(&client, &config, &RouterState::default()).await?;
+        println!("All backends reachable");
+    }
+
+    Ok(())
+}
+
+/// `path = value  # source` for every effective config value.
+pub fn print_effective_config(config_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config(config_path)?;
+    let contents = std::fs::read_to_string(config_path)?;
+    for (path, value, source) in config.effective_values(&contents) {
+        let source = match source {
+            ValueSource::Default => "default".to_string(),
+            ValueSource::File => "file".to_string(),
+            ValueSource::Env(var) => format!("env {}", var),
+        };
+        println!("{:<64} # {}", format!("{} = {}", path, value), source);
+    }
+    Ok(())
+}
+
+pub async fn run_keys(
+    config_path: &str,
+    redis_url: Option<String>,
+    command: KeysCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let redis_url = match redis_url {
+        Some(url) => url,
+        None => load_config(config_path)?.redis_url,
+    };
+    let client = redis::Client::open(redis_url)?;
+    let mut con = client.get_multiplexed_async_connection().await?;
+
+    match command {
+        KeysCommand::Add {
+            owner,
+            rate_limit,
+            expires_at,
+            key,
+            origins,
+            commitment,
+            encoding,
+            routes,
+            tier,
+            privacy,
+        } => {
+            let key = key.unwrap_or_else(generate_key);
+            let new_key = NewKey {
+                owner: owner.clone(),
+                rate_limit,
+                expires_at,
+                allowed_origins: origins,
+                defaults: RequestDefaults::new(commitment, encoding)?,
+                method_routes: parse_method_routes(&routes)?,
+                tier,
+                privacy: LogPrivacy::parse(&privacy)?,
+                ..Default::default()
+            };
+            create_key(&mut con, &key, &new_key).await?;
+            println!("Created API key for {}:", owner);
+            println!("{}", key);
+        }
+        KeysCommand::Revoke { key } => {
+            if revoke_key(&mut con, &key).await? {
+                println!("Revoked key: {}", key);
+            } else {
+                return Err(format!("Key not found: {}", key).into());
+            }
+        }
+        KeysCommand::List => {
+            let keys = list_keys(&mut con).await?;
+            println!("Found {} keys:", keys.len());
+            for summary in keys {
+                println!(
+                    "- {} [owner={}] [active={}]",
+                    summary.key,
+                    summary.owner.as_deref().unwrap_or("?"),
+                    summary.active
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run_backends(
+    config_path: &str,
+    router_url: Option<String>,
+    command: BackendsCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let router_url = match router_url {
+        Some(url) => url,
+        None => format!("http://127.0.0.1:{}", load_config(config_path)?.port),
+    };
+
+    match command {
+        BackendsCommand::Status => {
+            let url = format!("{}/health", router_url.trim_end_matches('/'));
+            let client: Client<_, http_body_util::Empty<bytes::Bytes>> =
+                Client::builder(TokioExecutor::new()).build(HttpsConnector::new());
+            let response = client.get(url.parse()?).await?;
+            if !response.status().is_success() {
+                return Err(format!("Router returned status {}", response.status()).into());
+            }
+            let body = response.into_body().collect().await?.to_bytes();
+            let health: serde_json::Value = serde_json::from_slice(&body)?;
+
+            println!(
+                "Overall: {}",
+                health["overall_status"].as_str().unwrap_or("unknown")
+            );
+            println!(
+                "{:<24} {:<10} {:<9} LAST ERROR",
+                "BACKEND", "STATUS", "FAILURES"
+            );
+            for backend in health["backends"].as_array().into_iter().flatten() {
+                println!(
+                    "{:<24} {:<10} {:<9} {}",
+                    backend["label"].as_str().unwrap_or("?"),
+                    backend["status"].as_str().unwrap_or("unknown"),
+                    backend["consecutive_failures"].as_u64().unwrap_or(0),
+                    backend["last_error"].as_str().unwrap_or("-")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run_bench(
+    config_path: &str,
+    args: BenchArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let base_url = match (args.url, args.backend) {
+        (Some(url), _) => url,
+        (None, Some(label)) => load_config(config_path)?
+            .backends
+            .into_iter()
+            .find(|b| b.label == label)
+            .map(|b| b.url)
+            .ok_or_else(|| format!("Unknown backend label '{}'", label))?,
+        (None, None) => format!("http://127.0.0.1:{}/", load_config(config_path)?.port),
+    };
+    let url = match args.api_key {
+        Some(key) if base_url.contains('?') => format!("{}&api-key={}", base_url, key),
+        Some(key) => format!("{}?api-key={}", base_url, key),
+        None => base_url,
+    };
+
+    let mix = match args.mix_file {
+        Some(path) => {
+            let mix: Vec<BenchRequest> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            if mix.is_empty() || mix.iter().any(|r| r.weight == 0) {
+                return Err("Mix file must contain entries with weight > 0".into());
+            }
+            mix
+        }
+        None => bench::parse_mix(&args.methods)?,
+    };
+
+    println!(
+        "Running {} rps for {}s (concurrency {}) against {}",
+        args.rps,
+        args.duration,
+        args.concurrency,
+        url.split('?').next().unwrap_or(&url)
+    );
+
+    let report = bench::run(BenchOptions {
+        url,
+        rps: args.rps,
+        duration: Duration::from_secs(args.duration),
+        concurrency: args.concurrency,
+        mix,
+    })
+    .await;
+
+    println!(
+        "\n{:<28} {:>8} {:>7} {:>7} {:>9} {:>9} {:>9} {:>9}",
+        "METHOD", "REQS", "HTTP_ERR", "RPC_ERR", "P50(ms)", "P90(ms)", "P99(ms)", "MAX(ms)"
+    );
+    let rows = report
+        .per_method
+        .iter()
+        .map(|(m, s)| (m.as_str(), s))
+        .chain(std::iter::once(("TOTAL", &report.total)));
+    for (method, s) in rows {
+        println!(
+            "{:<28} {:>8} {:>7} {:>7} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+            method, s.requests, s.http_errors, s.rpc_errors, s.p50_ms, s.p90_ms, s.p99_ms, s.max_ms
+        );
+    }
+    println!(
+        "\nAchieved {:.1} rps over {:.2}s ({} ticks skipped at the concurrency limit)",
+        report.total.requests as f64 / report.elapsed.as_secs_f64(),
+        report.elapsed.as_secs_f64(),
+        report.skipped
+    );
+
+    Ok(())
+}
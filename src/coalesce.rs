@@ -0,0 +1,121 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use serde_json::value::RawValue;
+use tokio::{sync::watch, time::timeout};
+
+/// The answer a leader shares with the requests waiting on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shared {
+    /// `result` or `error`
+    pub field: &'static str,
+    /// The field's JSON, as the backend sent it
+    pub value: Bytes,
+    /// Label of the backend that answered
+    pub backend: String,
+}
+
+impl Shared {
+    /// The `result` or `error` of a single JSON-RPC response; `None` for
+    /// anything else, which waiting requests then fetch themselves.
+    pub fn from_response(body: &[u8], backend: &str) -> Option<Self> {
+        let response: HashMap<String, &RawValue> = serde_json::from_slice(body).ok()?;
+        let (field, value) = match (response.get("result"), response.get("error")) {
+            (Some(result), None) => ("result", result),
+            (None, Some(error)) => ("error", error),
+            _ => return None,
+        };
+        Some(Self {
+            field,
+            value: Bytes::copy_from_slice(value.get().as_bytes()),
+            backend: backend.to_string(),
+        })
+    }
+}
+
+type Answer = watch::Receiver<Option<Arc<Shared>>>;
+
+/// Upstream requests in progress, by [`ReadKey`](crate::cache::ReadKey) key.
+type Flights = Arc<Mutex<HashMap<String, Answer>>>;
+
+/// The outcome of [`Coalescer::begin`].
+pub enum Coalesce {
+    /// No identical request is in flight: send this one upstream and
+    /// [`share`](Leader::share) its answer
+    Leader(Leader),
+    /// An identical request is in flight; [`follow`] waits for its answer
+    Follower(Answer),
+}
+
+/// Marks a request in flight until shared or dropped. Dropping it without
+/// sharing releases its followers to go upstream themselves.
+pub struct Leader {
+    flights: Flights,
+    key: String,
+    answer: watch::Sender<Option<Arc<Shared>>>,
+}
+
+impl Leader {
+    pub fn share(self, shared: Shared) {
+        self.answer.send_replace(Some(Arc::new(shared)));
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+        // A timed-out follower may have started a flight of its own since
+        if flights
+            .get(&self.key)
+            .is_some_and(|answer| answer.same_channel(&self.answer.subscribe()))
+        {
+            flights.remove(&self.key);
+        }
+    }
+}
+
+/// Single flight for identical concurrent reads: one request goes upstream
+/// and its answer is fanned out to the others.
+#[derive(Default)]
+pub struct Coalescer {
+    flights: Flights,
+}
+
+impl Coalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin(&self, key: &str) -> Coalesce {
+        let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(answer) = flights.get(key) {
+            return Coalesce::Follower(answer.clone());
+        }
+        let (answer, receiver) = watch::channel(None);
+        flights.insert(key.to_string(), receiver);
+        Coalesce::Leader(Leader {
+            flights: self.flights.clone(),
+            key: key.to_string(),
+            answer,
+        })
+    }
+
+    /// Requests in flight
+    pub fn len(&self) -> usize {
+        self.flights.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The leader's answer, or `None` if it did not share one within `wait`.
+pub async fn follow(mut answer: Answer, wait: Duration) -> Option<Arc<Shared>> {
+    let shared = timeout(wait, answer.wait_for(Option::is_some)).await.ok()?;
+    shared.ok()?.clone()
+}
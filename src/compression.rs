@@ -0,0 +1,62 @@
+use std::io::Read;
+
+/// `Content-Encoding` of a request body the router can decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    Zstd,
+}
+
+impl ContentEncoding {
+    /// Parse a `Content-Encoding` header value. `Ok(None)` means the body is not
+    /// encoded; stacked encodings (`gzip, zstd`) are not supported.
+    pub fn parse(header: &str) -> Result<Option<Self>, String> {
+        match header.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => Ok(None),
+            "gzip" | "x-gzip" => Ok(Some(Self::Gzip)),
+            "zstd" => Ok(Some(Self::Zstd)),
+            other => Err(format!("Unsupported content-encoding: {}", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The decoded body would exceed the limit
+    TooLarge,
+    /// The body is not valid for its encoding
+    Invalid(String),
+}
+
+/// Decode `body`, refusing to produce more than `limit` bytes so a small
+/// compressed payload cannot expand into an unbounded allocation.
+pub fn decode(
+    encoding: ContentEncoding,
+    body: &[u8],
+    limit: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    let reader: Box<dyn Read + '_> = match encoding {
+        ContentEncoding::Gzip => Box::new(flate2::read::MultiGzDecoder::new(body)),
+        ContentEncoding::Zstd => Box::new(
+            zstd::stream::read::Decoder::new(body)
+                .map_err(|e| DecodeError::Invalid(e.to_string()))?,
+        ),
+    };
+
+    let mut decoded = Vec::new();
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|e| DecodeError::Invalid(e.to_string()))?;
+    if decoded.len() > limit {
+        return Err(DecodeError::TooLarge);
+    }
+    Ok(decoded)
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use metrics::gauge;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Duration,
+};
+
+/// Backoff suggested to requests shed at a concurrency limit.
+pub const SHED_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Which limit a shed request ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saturated {
+    /// `proxy.max_in_flight`, across all keys
+    Global,
+    /// The key's own `max_in_flight`
+    Key,
+}
+
+impl Saturated {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Saturated::Global => "global",
+            Saturated::Key => "key",
+        }
+    }
+
+    /// Message of the JSON-RPC error a shed request gets.
+    pub fn message(self) -> &'static str {
+        match self {
+            Saturated::Global => "Router is at its concurrency limit",
+            Saturated::Key => "Too many concurrent requests for this API key",
+        }
+    }
+}
+
+/// Permits for one limit. A changed limit gets a new semaphore, so requests
+/// already in flight are not counted against it.
+struct Slots {
+    limit: usize,
+    permits: Arc<Semaphore>,
+}
+
+impl Slots {
+    fn new(limit: usize) -> Arc<Self> {
+        Arc::new(Self {
+            limit,
+            permits: Arc::new(Semaphore::new(limit)),
+        })
+    }
+
+    fn in_flight(&self) -> usize {
+        self.limit - self.permits.available_permits()
+    }
+
+    fn saturation(&self) -> f64 {
+        self.in_flight() as f64 / self.limit as f64
+    }
+}
+
+/// Requests being served, capped by `proxy.max_in_flight` and by each key's
+/// `max_in_flight`. Requests over a limit are refused rather than queued.
+#[derive(Default)]
+pub struct ConcurrencyLimiter {
+    in_flight: AtomicUsize,
+    global: Mutex<Option<Arc<Slots>>>,
+    /// By API key, only while the key has requests in flight
+    keys: Mutex<HashMap<String, Arc<Slots>>>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests holding a permit.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Requests of `key` holding a permit; 0 for keys without a limit.
+    pub fn key_in_flight(&self, key: &str) -> usize {
+        self.keys
+            .lock()
+            .unwrap()
+            .get(key)
+            .map_or(0, |slots| slots.in_flight())
+    }
+
+    /// Admit a request of `key` (owned by `owner`) under `global_limit` and
+    /// `key_limit`, 0 meaning unlimited. The permit is held until the
+    /// response has been sent.
+    pub fn try_acquire(
+        self: &Arc<Self>,
+        global_limit: u32,
+        key: &str,
+        owner: &str,
+        key_limit: u32,
+    ) -> Result<InFlightPermit, Saturated> {
+        let global = match global_limit as usize {
+            0 => None,
+            limit => {
+                let slots = {
+                    let mut current = self.global.lock().unwrap();
+                    match current.as_ref() {
+                        Some(slots) if slots.limit == limit => slots.clone(),
+                        _ => current.insert(Slots::new(limit)).clone(),
+                    }
+                };
+                let permit = slots
+                    .permits
+                    .clone()
+                    .try_acquire_owned()
+                    .map_err(|_| Saturated::Global)?;
+                gauge!("rpc_in_flight_saturation").set(slots.saturation());
+                Some((slots, permit))
+            }
+        };
+
+        let key_permit = match key_limit as usize {
+            0 => None,
+            limit => {
+                let mut keys = self.keys.lock().unwrap();
+                let slots = match keys.get(key) {
+                    Some(slots) if slots.limit == limit => slots.clone(),
+                    _ => {
+                        let slots = Slots::new(limit);
+                        keys.insert(key.to_string(), slots.clone());
+                        slots
+                    }
+                };
+                // Dropping `global` here gives its permit back
+                let permit = slots
+                    .permits
+                    .clone()
+                    .try_acquire_owned()
+                    .map_err(|_| Saturated::Key)?;
+                gauge!("rpc_key_in_flight_saturation", "owner" => owner.to_string())
+                    .set(slots.saturation());
+                Some(KeyPermit {
+                    key: key.to_string(),
+                    owner: owner.to_string(),
+                    slots,
+                    permit: Some(permit),
+                })
+            }
+        };
+
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        gauge!("rpc_in_flight_requests").set(in_flight as f64);
+        Ok(InFlightPermit {
+            limiter: self.clone(),
+            global,
+            key: key_permit,
+        })
+    }
+}
+
+struct KeyPermit {
+    key: String,
+    owner: String,
+    slots: Arc<Slots>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+/// A request's place under the concurrency limits, given back on drop.
+pub struct InFlightPermit {
+    limiter: Arc<ConcurrencyLimiter>,
+    global: Option<(Arc<Slots>, OwnedSemaphorePermit)>,
+    key: Option<KeyPermit>,
+}
+
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        let in_flight = self.limiter.in_flight.fetch_sub(1, Ordering::Relaxed) - 1;
+        gauge!("rpc_in_flight_requests").set(in_flight as f64);
+        if let Some((slots, permit)) = self.global.take() {
+            drop(permit);
+            gauge!("rpc_in_flight_saturation").set(slots.saturation());
+        }
+        if let Some(mut key) = self.key.take() {
+            let mut keys = self.limiter.keys.lock().unwrap();
+            drop(key.permit.take());
+            gauge!("rpc_key_in_flight_saturation", "owner" => key.owner.clone())
+                .set(key.slots.saturation());
+            // Forget idle keys, unless a new limit has replaced their entry
+            let idle = key.slots.in_flight() == 0;
+            if idle
+                && keys
+                    .get(&key.key)
+                    .is_some_and(|s| Arc::ptr_eq(s, &key.slots))
+            {
+                keys.remove(&key.key);
+            }
+        }
+    }
+}
@@ -1,72 +1,2324 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
+    time::Duration,
+};
 
-use serde::Deserialize;
+use axum::http::{uri::PathAndQuery, HeaderName, HeaderValue};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-#[derive(Debug, Deserialize, Clone)]
+use crate::{
+    cache::{
+        cache_policy, context_config_index, CachePolicy, MAX_METHOD_TTL_SECS, MAX_STALE_SECS,
+        MAX_TTL_SLOTS,
+    },
+    error_templates::{placeholders, RouterError},
+    handlers::MAX_BODY_SIZE,
+    methods::{idempotency, is_known_method, method_info, Idempotency, MethodClass},
+    redact,
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
     pub port: u16,
+    /// Addresses the HTTP and WebSocket servers listen on
+    #[serde(default = "default_bind_addresses")]
+    pub bind_addresses: Vec<IpAddr>,
     pub metrics_port: u16, // Required now
+    /// Addresses the operations server on `metrics_port` (`/metrics` and
+    /// `/admin`) listens on, loopback only unless configured otherwise
+    #[serde(default = "default_admin_bind_addresses")]
+    pub admin_bind_addresses: Vec<IpAddr>,
+    /// HTTPS and WSS on the public listeners
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Where metrics are exported: scraped on `/metrics` or pushed
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// Spans of proxied requests exported over OTLP
+    #[serde(default)]
+    pub tracing: TracingConfig,
+    /// Threads of the Tokio runtime
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
     pub redis_url: String, // Added Redis URL
+    /// In-process cache of API key lookups
+    #[serde(default)]
+    pub key_cache: KeyCacheConfig,
+    /// What requests get while API keys cannot be looked up
+    #[serde(default)]
+    pub keystore_outage: KeystoreOutageConfig,
     pub backends: Vec<Backend>,
     #[serde(default)]
     pub method_routes: HashMap<String, String>,
+    /// Per routing tier: method routes for keys in that tier, ahead of `method_routes`
+    #[serde(default)]
+    pub tier_routes: HashMap<String, HashMap<String, String>>,
+    /// Per routing tier: rules enforced on `sendTransaction` preflight
+    #[serde(default)]
+    pub preflight_policies: HashMap<String, PreflightPolicy>,
+    /// Idempotency of provider-specific methods the router does not know, so
+    /// they can be retried, hedged and cached like built-in ones
+    #[serde(default)]
+    pub method_idempotency: HashMap<String, Idempotency>,
     #[serde(default)]
     pub health_check: HealthCheckConfig,
     #[serde(default)]
     pub proxy: ProxyConfig,
+    #[serde(default)]
+    pub browser_keys: BrowserKeyConfig,
+    #[serde(default)]
+    pub probes: ProbesConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub reload: ReloadConfig,
+    #[serde(default)]
+    pub readiness: ReadinessConfig,
+    #[serde(default)]
+    pub hedging: HedgingConfig,
+    #[serde(default)]
+    pub coalescing: CoalescingConfig,
+    #[serde(default)]
+    pub broadcast: BroadcastConfig,
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub dead_letter: DeadLetterConfig,
+    #[serde(default)]
+    pub journal: JournalConfig,
+    #[serde(default)]
+    pub usage_ledger: UsageLedgerConfig,
+    #[serde(default)]
+    pub key_usage: KeyUsageConfig,
+    #[serde(default)]
+    pub spending_caps: SpendingCapsConfig,
+    #[serde(default)]
+    pub traffic_report: TrafficReportConfig,
+    #[serde(default)]
+    pub latency_heatmap: LatencyHeatmapConfig,
+    #[serde(default)]
+    pub pools: PoolsConfig,
+    #[serde(default)]
+    pub websocket: WebSocketConfig,
+    #[serde(default)]
+    pub offload: OffloadConfig,
+    #[serde(default)]
+    pub blockhash_check: BlockhashCheckConfig,
+    #[serde(default)]
+    pub poll_bridge: PollBridgeConfig,
+    #[serde(default)]
+    pub integrity: IntegrityConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    #[serde(default)]
+    pub consistency: ConsistencyConfig,
+    #[serde(default)]
+    pub genesis_check: GenesisCheckConfig,
+    #[serde(default)]
+    pub archive_routing: ArchiveRoutingConfig,
+    #[serde(default)]
+    pub error_templates: ErrorTemplatesConfig,
+    #[serde(default)]
+    pub kill_switches: KillSwitchConfig,
+    /// Conditions evaluated by the router itself, reported to webhooks
+    #[serde(default)]
+    pub alerts: Vec<AlertRule>,
+    /// Short SHA-256 of the config document and its environment overrides, set
+    /// by `parse_config`
+    #[serde(skip)]
+    pub hash: String,
+    /// Values set by `ROUTER__*` environment variables: config path -> variable
+    #[serde(skip)]
+    pub overrides: BTreeMap<String, String>,
+}
+
+/// Dual-stack: every IPv4 and every IPv6 interface.
+fn default_bind_addresses() -> Vec<IpAddr> {
+    vec![
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    ]
+}
+
+/// Operational endpoints stay off the network until explicitly exposed.
+fn default_admin_bind_addresses() -> Vec<IpAddr> {
+    vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]
+}
+
+impl Config {
+    /// The effective config as JSON with credentials masked: backend and Redis URLs
+    /// go through `redact_url`, signing keys, backend header and query param
+    /// values, the offload secret, admin tokens and probe keys are replaced.
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        let mask = |v: &mut serde_json::Value| {
+            if let Some(url) = v.as_str() {
+                *v = redact::redact_url(url).into();
+            }
+        };
+
+        mask(&mut value["redis_url"]);
+        if let Some(backends) = value["backends"].as_array_mut() {
+            for backend in backends {
+                mask(&mut backend["url"]);
+                mask(&mut backend["ws_url"]);
+                if let Some(signing) = backend["signing"].as_object_mut() {
+                    if signing.get("key").is_some_and(|k| !k.is_null()) {
+                        signing.insert("key".to_string(), "[REDACTED]".into());
+                    }
+                }
+                for field in ["headers", "url_query_params"] {
+                    if let Some(values) = backend[field].as_object_mut() {
+                        values.values_mut().for_each(|v| *v = "[REDACTED]".into());
+                    }
+                }
+            }
+        }
+        if let Some(sources) = value["health_check"]["reference_sources"].as_array_mut() {
+            for source in sources {
+                mask(&mut source["url"]);
+            }
+        }
+        if let Some(alerts) = value["alerts"].as_array_mut() {
+            for alert in alerts {
+                mask(&mut alert["webhook_url"]);
+            }
+        }
+        mask(&mut value["spending_caps"]["webhook_url"]);
+        if value["offload"]["secret_access_key"].is_string() {
+            value["offload"]["secret_access_key"] = "[REDACTED]".into();
+        }
+        for section in ["metrics", "tracing"] {
+            mask(&mut value[section]["otlp"]["endpoint"]);
+            if let Some(headers) = value[section]["otlp"]["headers"].as_object_mut() {
+                headers.values_mut().for_each(|v| *v = "[REDACTED]".into());
+            }
+        }
+        if let Some(tokens) = value["admin"]["tokens"].as_array_mut() {
+            tokens.iter_mut().for_each(|t| *t = "[REDACTED]".into());
+        }
+        if let Some(keys) = value["probes"]["keys"].as_array_mut() {
+            keys.iter_mut().for_each(|k| *k = "[REDACTED]".into());
+        }
+        if let Some(keys) = value["keystore_outage"]["static_keys"].as_array_mut() {
+            keys.iter_mut().for_each(|k| k["key"] = "[REDACTED]".into());
+        }
+        value
+    }
+
+    /// Every value of the redacted effective config by dotted path, with
+    /// whether it came from an override, the config document `contents` or the
+    /// defaults.
+    pub fn effective_values(
+        &self,
+        contents: &str,
+    ) -> Vec<(String, serde_json::Value, ValueSource)> {
+        let file: toml::Value =
+            toml::from_str(contents).unwrap_or(toml::Value::Table(Default::default()));
+        let mut leaves = Vec::new();
+        flatten("", &self.redacted(), &mut leaves);
+        leaves
+            .into_iter()
+            .map(|(path, value)| {
+                // An override may set a whole table
+                let var = self
+                    .overrides
+                    .iter()
+                    .find(|(set, _)| path == **set || path.starts_with(&format!("{}.", set)));
+                let source = match var {
+                    Some((_, var)) => ValueSource::Env(var.clone()),
+                    None if lookup(&file, &path).is_some() => ValueSource::File,
+                    None => ValueSource::Default,
+                };
+                (path, value, source)
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// TLS terminated by the router on the HTTP and WebSocket listeners, so it can
+/// run without a fronting reverse proxy. Read at startup only; the certificate
+/// files themselves are re-read every `reload_secs`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(default)]
-pub struct ProxyConfig {
-    pub timeout_secs: u64,
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM certificate chain, leaf first
+    pub cert_path: String,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_path: String,
+    /// How often the files are checked for a renewed certificate
+    pub reload_secs: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: String::new(),
+            key_path: String::new(),
+            reload_secs: 300,
+        }
+    }
+}
+
+/// Metrics exporter, chosen at startup. Prometheus is scraped on `/metrics`;
+/// StatsD and OTLP push every `interval_secs` and need the `statsd` and `otlp`
+/// features.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub exporter: MetricsExporterKind,
+    /// How often StatsD and OTLP exporters push
+    pub interval_secs: u64,
+    pub statsd: StatsdConfig,
+    pub otlp: OtlpConfig,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            exporter: MetricsExporterKind::Prometheus,
+            interval_secs: 10,
+            statsd: StatsdConfig::default(),
+            otlp: OtlpConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsExporterKind {
+    Prometheus,
+    Statsd,
+    Otlp,
+}
+
+impl MetricsExporterKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Prometheus => "prometheus",
+            Self::Statsd => "statsd",
+            Self::Otlp => "otlp",
+        }
+    }
+}
+
+/// StatsD or DogStatsD agent metrics are sent to over UDP.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct StatsdConfig {
+    /// `host:port` of the agent
+    pub address: String,
+    /// Prepended to every metric name, e.g. `"sol_rpc_router."`
+    pub prefix: String,
+    /// Send labels as DogStatsD tags (`|#key:value`). Off, label values are
+    /// appended to the metric name instead.
+    pub tags: bool,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:8125".to_string(),
+            prefix: String::new(),
+            tags: true,
+        }
+    }
+}
+
+/// OpenTelemetry collector metrics are sent to with OTLP/HTTP (JSON).
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct OtlpConfig {
+    /// Collector base URL; metrics are posted to `{endpoint}/v1/metrics`
+    pub endpoint: String,
+    /// Extra request headers, e.g. an API key. `${VAR}` is expanded from the
+    /// environment.
+    pub headers: HashMap<String, String>,
+    /// `service.name` resource attribute
+    pub service_name: String,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://127.0.0.1:4318".to_string(),
+            headers: HashMap::new(),
+            service_name: "sol-rpc-router".to_string(),
+        }
+    }
+}
+
+/// Distributed tracing: a span per proxied request, exported to an
+/// OpenTelemetry collector with OTLP/HTTP (JSON). Needs the `otlp` feature.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct TracingConfig {
+    pub enabled: bool,
+    /// Collector spans are posted to, at `{endpoint}/v1/traces`
+    pub otlp: OtlpConfig,
+    /// Share of requests starting a new trace that are recorded, 0-100.
+    /// Requests with a `traceparent` follow its sampled flag instead.
+    pub sample_percent: u32,
+    /// How often finished spans are pushed
+    pub interval_secs: u64,
+    /// Finished spans held between pushes; more are dropped
+    pub max_queued_spans: usize,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            otlp: OtlpConfig::default(),
+            sample_percent: 100,
+            interval_secs: 5,
+            max_queued_spans: 2048,
+        }
+    }
+}
+
+/// In-process cache of API key lookups, so requests with a known key skip the
+/// Redis round trip. Read at startup only.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct KeyCacheConfig {
+    /// How long a valid key's settings are used before they are read again
+    pub ttl_secs: u64,
+    /// How long an unknown or inactive key is rejected without asking Redis;
+    /// 0 asks every time
+    pub negative_ttl_secs: u64,
+    /// Most keys cached at once
+    pub max_entries: u64,
+}
+
+impl Default for KeyCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 60,
+            negative_ttl_secs: 10,
+            max_entries: 100_000,
+        }
+    }
+}
+
+/// How requests are authenticated while the key store fails, typically
+/// because Redis is unreachable. Read at startup only.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct KeystoreOutageConfig {
+    pub policy: KeystoreOutagePolicy,
+    /// Under `fail_open`, how long after this router last validated a key it
+    /// is still accepted
+    pub remember_secs: u64,
+    /// Keys accepted under `static_keys`
+    pub static_keys: Vec<StaticKey>,
+}
+
+impl Default for KeystoreOutageConfig {
+    fn default() -> Self {
+        Self {
+            policy: KeystoreOutagePolicy::FailClosed,
+            remember_secs: 3600,
+            static_keys: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeystoreOutagePolicy {
+    /// Reject every request
+    FailClosed,
+    /// Accept keys validated within `remember_secs`, with their last known settings
+    FailOpen,
+    /// Accept only the configured `static_keys`
+    StaticKeys,
+}
+
+impl KeystoreOutagePolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::FailClosed => "fail_closed",
+            Self::FailOpen => "fail_open",
+            Self::StaticKeys => "static_keys",
+        }
+    }
+}
+
+/// An API key accepted while the key store is down.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StaticKey {
+    /// The key, or `${VAR}` to read it from the environment
+    pub key: String,
+    pub owner: String,
+}
+
+/// Sizing of the Tokio runtime. Read at startup only.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    /// Worker threads of the proxy; 0 for one per CPU core
+    pub worker_threads: usize,
+    /// Most threads running blocking work, such as decompressing request
+    /// bodies, at once
+    pub max_blocking_threads: usize,
+    /// Run health checks, metrics and trace export and the other periodic
+    /// background loops on a single-threaded runtime of their own, so they
+    /// never take a worker thread from the proxy
+    pub isolate_background: bool,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            worker_threads: 0,
+            max_blocking_threads: 512,
+            isolate_background: false,
+        }
+    }
+}
+
+/// Admin API under `/admin`. Disabled while no tokens are configured.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// Bearer tokens accepted by the admin API
+    pub tokens: Vec<String>,
+    /// Destructive actions (revoking keys, removing backends) also need a second,
+    /// different admin token in `X-Admin-Approval`
+    pub dual_control: bool,
+}
+
+/// What `/readyz` requires. Without groups, any backend in rotation is enough.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ReadinessConfig {
+    /// Each listed group (see `Backend::groups`) needs at least one backend in rotation
+    pub required_groups: Vec<String>,
+}
+
+/// Race latency-critical methods across the two fastest backends.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct HedgingConfig {
+    pub enabled: bool,
+    /// Methods sent to two backends at once; the first successful response wins
+    pub methods: Vec<String>,
+    /// Extra upstream requests allowed, as a percentage of requests for hedged methods
+    pub budget_percent: u32,
+    /// Wait this long for the faster backend before sending the request to the
+    /// second one (0 = send to both at once)
+    pub delay_ms: u64,
+    /// Wait for this percentile (1-99) of the faster backend's recent latency
+    /// for the method instead, or `delay_ms` until it has samples (0 = off)
+    pub delay_percentile: u32,
+}
+
+impl Default for HedgingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            methods: vec![
+                "getSlot".to_string(),
+                "getBlockHeight".to_string(),
+                "getLatestBlockhash".to_string(),
+            ],
+            budget_percent: 10,
+            delay_ms: 0,
+            delay_percentile: 0,
+        }
+    }
+}
+
+impl HedgingConfig {
+    /// Whether the second request waits for the first to be slow.
+    pub fn is_delayed(&self) -> bool {
+        self.delay_ms > 0 || self.delay_percentile > 0
+    }
+}
+
+/// Upper bound of `coalescing.max_response_bytes`; shared responses are
+/// buffered in memory.
+pub const MAX_COALESCED_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Send one upstream request for identical reads in flight at the same time
+/// and fan its response out to every waiting client.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CoalescingConfig {
+    pub enabled: bool,
+    /// Methods whose identical concurrent requests share one upstream request
+    pub methods: Vec<String>,
+    /// Larger responses are not shared; waiting requests go upstream themselves
+    pub max_response_bytes: usize,
+}
+
+impl Default for CoalescingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            methods: vec![
+                "getSlot".to_string(),
+                "getBlockHeight".to_string(),
+                "getLatestBlockhash".to_string(),
+            ],
+            max_response_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Send `sendTransaction` to every healthy backend at once and answer with the
+/// first that accepts it.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct BroadcastConfig {
+    /// Broadcast for every key; keys can also opt in on their own
+    pub enabled: bool,
+    /// Backends each transaction goes to, fastest first; 0 = every healthy one
+    pub max_backends: usize,
+    /// Answer a transaction accepted this recently from the router instead of
+    /// broadcasting it again; 0 = off
+    pub dedup_secs: u64,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_backends: 0,
+            dedup_secs: 30,
+        }
+    }
+}
+
+/// Router subsystems that can be switched off during an incident, leaving plain
+/// pass-through proxying.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Subsystem {
+    /// The response cache, including stale serving
+    Cache,
+    /// Re-sending a request after a bad response (transaction version, integrity,
+    /// failover)
+    Retries,
+    Hedging,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 3] = [Subsystem::Cache, Subsystem::Retries, Subsystem::Hedging];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Subsystem::Cache => "cache",
+            Subsystem::Retries => "retries",
+            Subsystem::Hedging => "hedging",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str() == value)
+    }
+}
+
+/// Subsystems switched off from startup. The admin API can switch off more at
+/// runtime, but not switch these back on.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct KillSwitchConfig {
+    pub disabled: Vec<Subsystem>,
+}
+
+/// How backends are chosen for methods without a `method_routes` pin.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutingMode {
+    /// Weighted round-robin by configured backend weight
+    #[default]
+    Weighted,
+    /// Per-method shares learned from observed success rate and latency
+    Auto,
+    /// The backends with the lowest moving-average latency, weighted among themselves
+    #[serde(rename = "least_latency")]
+    LeastLatency,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub mode: RoutingMode,
+    /// auto: share of each method's traffic every eligible backend keeps, so its stats stay fresh
+    pub min_share_percent: u32,
+    /// auto: requests a backend needs in the stats window before its share is learned
+    pub min_samples: u64,
+    /// Region this router runs in; backends with the same `region` are preferred
+    pub region: Option<String>,
+    /// least_latency: backends this much slower than the fastest still count as tied
+    pub latency_tolerance_percent: u32,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        Self {
+            mode: RoutingMode::Weighted,
+            min_share_percent: 5,
+            min_samples: 20,
+            region: None,
+            latency_tolerance_percent: 20,
+        }
+    }
+}
+
+/// Built-in caching of `getGenesisHash`, `getEpochSchedule` and `getEpochInfo`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// While no backend is healthy, answer cacheable requests with results up to
+    /// this old instead of 503; 0 = never serve stale
+    pub serve_stale_secs: u64,
+    /// Further read methods whose last result is remembered for serving stale
+    pub stale_methods: Vec<String>,
+    /// Slots a per-slot answer is reused for, by the request's commitment
+    pub commitment_ttl_slots: CommitmentTtl,
+    /// Further read methods answered from the cache: method -> seconds an
+    /// answer is reused for. Overrides the built-in policy of a method.
+    pub method_ttl_secs: HashMap<String, u64>,
+    /// Account reads that, once expired, go upstream asking for a newer
+    /// `minContextSlot` than the cached result's, which is kept if the backend
+    /// has nothing newer
+    pub revalidate_methods: Vec<String>,
+    /// Pick the TTLs of some `method_ttl_secs` methods by their hit rates and
+    /// revalidation mismatches
+    pub ttl_tuning: TtlTuningConfig,
+    /// Minutes-long caching of `getSupply`, `getInflationRate` and past-epoch
+    /// `getInflationReward`
+    pub network_stats: NetworkStatsCacheConfig,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            serve_stale_secs: 0,
+            stale_methods: Vec::new(),
+            commitment_ttl_slots: CommitmentTtl::default(),
+            method_ttl_secs: HashMap::new(),
+            revalidate_methods: Vec::new(),
+            ttl_tuning: TtlTuningConfig::default(),
+            network_stats: NetworkStatsCacheConfig::default(),
+        }
+    }
+}
+
+/// Caching of network-wide stats, which change rarely but are heavy to serve.
+/// Optionally a second backend must give the same answer before one is cached.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct NetworkStatsCacheConfig {
+    pub enabled: bool,
+    /// Seconds an answer is reused for
+    pub ttl_secs: u64,
+    /// Cache an answer only once another backend in rotation agrees with it
+    pub cross_check: bool,
+    /// Relative difference, in basis points, up to which numbers in the two
+    /// answers still agree (supply moves a little from slot to slot)
+    pub tolerance_bps: u32,
+}
+
+impl Default for NetworkStatsCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: 300,
+            cross_check: false,
+            tolerance_bps: 10,
+        }
+    }
+}
+
+/// Auto-tuning of per-method cache TTLs: each tuned method tries TTLs between
+/// its bounds and settles on the one with the best hit rate, less a penalty for
+/// hits found stale when revalidated against a backend.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TtlTuningConfig {
+    pub enabled: bool,
+    /// Tuned methods, each also in `cache.method_ttl_secs`, whose TTL then only
+    /// applies while tuning is off
+    pub methods: HashMap<String, TtlBounds>,
+    /// Percent of cache misses stored with a random TTL rather than the best one
+    pub explore_percent: u32,
+    /// Percent of cache hits re-fetched from a backend in the background to
+    /// check they were still current
+    pub revalidate_percent: u32,
+    /// How much a stale hit costs against a fresh one: a TTL scores its hit
+    /// ratio times `1 - staleness_penalty * mismatch_rate`
+    pub staleness_penalty: u32,
+}
+
+impl Default for TtlTuningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            methods: HashMap::new(),
+            explore_percent: 10,
+            revalidate_percent: 5,
+            staleness_penalty: 10,
+        }
+    }
+}
+
+/// Range a tuned method's TTL is chosen from, in seconds.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct TtlBounds {
+    pub min_secs: u64,
+    pub max_secs: u64,
+}
+
+/// Per-slot cache lifetime, in slots, of answers at each commitment. Requests
+/// without a commitment are answered at `finalized`, the node default.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct CommitmentTtl {
+    pub processed: u32,
+    pub confirmed: u32,
+    pub finalized: u32,
+}
+
+impl Default for CommitmentTtl {
+    fn default() -> Self {
+        Self {
+            processed: 1,
+            confirmed: 1,
+            finalized: 4,
+        }
+    }
+}
+
+impl CommitmentTtl {
+    pub fn slots(&self, commitment: Option<Commitment>) -> u32 {
+        match commitment.unwrap_or(Commitment::Finalized) {
+            Commitment::Processed => self.processed,
+            Commitment::Confirmed => self.confirmed,
+            Commitment::Finalized => self.finalized,
+        }
+    }
+}
+
+/// Upstream capacity kept apart by method class, so heavy reads cannot starve writes.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct PoolsConfig {
+    /// Send write methods (`sendTransaction`, `requestAirdrop`) over their own connection pool
+    pub separate_write_pool: bool,
+    /// Per backend: concurrent heavy reads allowed; 0 = unlimited
+    pub heavy_max_in_flight: u32,
+    /// Methods costing at least this many request units count as heavy reads
+    pub heavy_min_cost: u32,
+}
+
+impl Default for PoolsConfig {
+    fn default() -> Self {
+        Self {
+            separate_write_pool: true,
+            heavy_max_in_flight: 0,
+            heavy_min_cost: 5,
+        }
+    }
+}
+
+/// Heartbeats and slow-consumer handling for proxied WebSocket connections.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct WebSocketConfig {
+    /// How often the router pings each client; 0 disables heartbeats
+    pub ping_interval_secs: u64,
+    /// Disconnect a client that has not answered a ping within this long
+    pub pong_timeout_secs: u64,
+    /// Disconnect after this long without a text or binary message either way; 0 = never
+    pub idle_timeout_secs: u64,
+    /// Per client: messages queued for delivery before the oldest notification is dropped
+    pub max_queued_messages: usize,
+    /// Disconnect a client that takes longer than this to accept a single message
+    pub slow_consumer_timeout_secs: u64,
+    /// Answer `slotSubscribe`/`rootSubscribe` from the router's own slot feed
+    /// instead of opening an upstream subscription per client
+    pub local_slot_subscriptions: bool,
+    /// Lowest commitment local slot subscriptions report; lower requests are upgraded
+    pub slot_commitment: Commitment,
+    /// How often the slot feed polls its source backend
+    pub slot_poll_ms: u64,
+}
+
+/// Solana commitment levels, lowest first.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Commitment {
+    #[default]
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    pub const ALL: [Commitment; 3] = [
+        Commitment::Processed,
+        Commitment::Confirmed,
+        Commitment::Finalized,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Commitment::Processed => "processed",
+            Commitment::Confirmed => "confirmed",
+            Commitment::Finalized => "finalized",
+        }
+    }
+
+    /// Level of a `commitment` value, including the deprecated names nodes
+    /// still accept.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "processed" | "recent" => Some(Commitment::Processed),
+            "confirmed" | "single" | "singleGossip" => Some(Commitment::Confirmed),
+            "finalized" | "root" | "max" => Some(Commitment::Finalized),
+            _ => None,
+        }
+    }
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval_secs: 30,
+            pong_timeout_secs: 10,
+            idle_timeout_secs: 0,
+            max_queued_messages: 1024,
+            slow_consumer_timeout_secs: 30,
+            local_slot_subscriptions: false,
+            slot_commitment: Commitment::Processed,
+            slot_poll_ms: 400,
+        }
+    }
+}
+
+/// Value an alert rule watches.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    /// Backends out of rotation
+    UnhealthyBackends,
+    /// Routing-stats latency of `method` (optionally on one `backend`)
+    P50Ms,
+    P99Ms,
+    /// Routing-stats success rate of `method`, 0.0 to 1.0
+    SuccessRate,
+    /// Backends disagreeing with the majority on any `consistency.canaries` entry
+    DivergentBackends,
+}
+
+impl AlertMetric {
+    /// Whether the metric is taken from the routing statistics of one method.
+    pub fn per_method(self) -> bool {
+        !matches!(
+            self,
+            AlertMetric::UnhealthyBackends | AlertMetric::DivergentBackends
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum AlertOp {
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+}
+
+impl AlertOp {
+    pub fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            AlertOp::Gt => value > threshold,
+            AlertOp::Ge => value >= threshold,
+            AlertOp::Lt => value < threshold,
+            AlertOp::Le => value <= threshold,
+        }
+    }
+}
+
+/// Fire `webhook_url` once `metric op threshold` has held for `for_secs`, and
+/// again when it stops holding.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AlertRule {
+    pub name: String,
+    pub metric: AlertMetric,
+    pub op: AlertOp,
+    pub threshold: f64,
+    #[serde(default)]
+    pub for_secs: u64,
+    /// Required for per-method metrics
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Per-method metrics: one backend instead of all backends combined
+    #[serde(default)]
+    pub backend: Option<String>,
+    pub webhook_url: String,
+}
+
+/// Where failed `sendTransaction` payloads are kept.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeadLetterStoreKind {
+    /// A capped Redis stream on `redis_url`
+    #[default]
+    Redis,
+    /// JSON lines appended to `dead_letter.path`
+    File,
+}
+
+/// Capture of `sendTransaction` requests that failed upstream. Read at startup only.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct DeadLetterConfig {
+    pub enabled: bool,
+    pub store: DeadLetterStoreKind,
+    /// file: path of the JSON lines file
+    pub path: String,
+    /// redis: approximate cap on the stream length; oldest entries are trimmed
+    pub max_entries: u64,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            store: DeadLetterStoreKind::Redis,
+            path: "dead_letters.jsonl".to_string(),
+            max_entries: 10_000,
+        }
+    }
+}
+
+/// Where the `sendTransaction` journal is kept.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalStoreKind {
+    /// One expiring entry per signature on `redis_url`
+    #[default]
+    Redis,
+    /// JSON lines appended to `journal.path`
+    File,
+}
+
+/// Write-ahead journal of `sendTransaction` requests and their outcomes. Read
+/// at startup only.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct JournalConfig {
+    pub enabled: bool,
+    pub store: JournalStoreKind,
+    /// file: path of the JSON lines file
+    pub path: String,
+    /// How long entries can be looked up
+    pub retention_secs: u64,
+    /// At startup, re-send transactions journaled at most this long ago whose
+    /// outcome was never recorded; 0 = off
+    pub resume_secs: u64,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            store: JournalStoreKind::Redis,
+            path: "journal.jsonl".to_string(),
+            retention_secs: 86_400,
+            resume_secs: 90,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageLedgerStoreKind {
+    /// Per-day hashes on `redis_url`, expired after the retention period
+    #[default]
+    Redis,
+    /// A local SQLite database at `usage_ledger.path`
+    Sqlite,
+}
+
+/// Per-key daily request totals served at `/admin/usage`. Read at startup only.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct UsageLedgerConfig {
+    pub enabled: bool,
+    pub store: UsageLedgerStoreKind,
+    /// sqlite: database file, created if missing
+    pub path: String,
+    /// Days of totals kept, including today
+    pub retention_days: u64,
+    /// How often counts buffered in memory are written to the store
+    pub flush_secs: u64,
+}
+
+impl Default for UsageLedgerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            store: UsageLedgerStoreKind::Redis,
+            path: "usage.db".to_string(),
+            retention_days: 90,
+            flush_secs: 10,
+        }
+    }
+}
+
+/// Per-key, per-method daily request counts and response bytes in Redis,
+/// served at `/admin/keys/<key>/usage`. Read at startup only.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct KeyUsageConfig {
+    pub enabled: bool,
+    /// Days of counts kept, including today
+    pub retention_days: u64,
+    /// How often counts buffered in memory are written to Redis
+    pub flush_secs: u64,
+}
+
+impl Default for KeyUsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retention_days: 400,
+            flush_secs: 10,
+        }
+    }
+}
+
+/// Per-key spending caps: keys whose `spend_cap` is reached are throttled
+/// rather than cut off (see `spending`). Read at startup only.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct SpendingCapsConfig {
+    pub enabled: bool,
+    /// Price of one cost unit (see `methods`) for keys of each tier
+    pub tier_prices: HashMap<String, f64>,
+    /// Price of one cost unit for keys without a priced tier
+    pub default_price: f64,
+    /// Requests per second, per router instance, left to a key over its cap
+    pub throttled_rate_limit: u64,
+    /// Notified when a key reaches its cap; empty for none
+    pub webhook_url: String,
+    /// How often charges buffered in memory are written to Redis
+    pub flush_secs: u64,
+}
+
+impl Default for SpendingCapsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tier_prices: HashMap::new(),
+            default_price: 0.000001,
+            throttled_rate_limit: 1,
+            webhook_url: String::new(),
+            flush_secs: 10,
+        }
+    }
+}
+
+/// Longest `traffic_report.retention_days`, bounding the memory the report takes.
+pub const MAX_TRAFFIC_RETENTION_DAYS: u64 = 90;
+
+/// Hourly per-method request volumes, payload sizes and peak rates served at
+/// `/admin/traffic`. Kept in memory, so a restart starts them over.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct TrafficReportConfig {
+    pub enabled: bool,
+    /// Days of hourly totals kept
+    pub retention_days: u64,
+}
+
+impl Default for TrafficReportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retention_days: 30,
+        }
+    }
+}
+
+/// Longest `latency_heatmap.window_secs`.
+pub const MAX_HEATMAP_WINDOW_SECS: u64 = 3600;
+
+/// Most `latency_heatmap.windows`, bounding the memory the heatmap takes.
+pub const MAX_HEATMAP_WINDOWS: u64 = 1440;
+
+/// Per-window latency histograms of each method and backend served at
+/// `/admin/latency-heatmap`. Kept in memory, so a restart starts them over.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct LatencyHeatmapConfig {
+    pub enabled: bool,
+    /// Width of one window (one row of the heatmap)
+    pub window_secs: u64,
+    /// Windows kept
+    pub windows: u64,
+}
+
+impl Default for LatencyHeatmapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: 60,
+            windows: 60,
+        }
+    }
+}
+
+/// Rejection of `sendTransaction` calls whose recent blockhash the router
+/// already knows to be expired, without forwarding them.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct BlockhashCheckConfig {
+    pub enabled: bool,
+    /// How often `getLatestBlockhash` is polled to keep the blockhash cache current
+    pub poll_ms: u64,
+}
+
+impl Default for BlockhashCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_ms: 1000,
+        }
+    }
+}
+
+/// HTTP long-polling access to WebSocket subscriptions (`/v1/poll`), for
+/// clients that cannot keep a connection open.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct PollBridgeConfig {
+    pub enabled: bool,
+    /// Bridged subscriptions one API key may hold at once
+    pub max_subscriptions_per_key: usize,
+    /// Longest a poll is held open waiting for a notification
+    pub max_wait_secs: u64,
+    /// Close subscriptions that have not been polled for this long
+    pub idle_timeout_secs: u64,
+    /// Notifications kept per subscription between polls; the oldest are dropped beyond this
+    pub max_buffered: usize,
+}
+
+impl Default for PollBridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_subscriptions_per_key: 10,
+            max_wait_secs: 30,
+            idle_timeout_secs: 120,
+            max_buffered: 1000,
+        }
+    }
+}
+
+/// Verification of upstream response bodies before they reach the client, so
+/// a response cut short during a provider incident is retried elsewhere
+/// instead of handed to the client as broken JSON.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct IntegrityConfig {
+    pub enabled: bool,
+    /// Methods whose responses are verified; empty verifies every method
+    pub methods: Vec<String>,
+    /// Also require the body to parse as JSON, which catches responses cut short without a `Content-Length`
+    pub verify_json: bool,
+    /// Check the body against a sha-256 `Repr-Digest`/`Digest` header when the backend sends one
+    pub verify_digest: bool,
+    /// Responses larger than this are streamed through unverified
+    pub max_bytes: u64,
+}
+
+impl Default for IntegrityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            methods: Vec::new(),
+            verify_json: true,
+            verify_digest: true,
+            max_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl IntegrityConfig {
+    /// Whether responses to `rpc_method` are verified.
+    pub fn applies_to(&self, rpc_method: Option<&str>) -> bool {
+        self.enabled
+            && (self.methods.is_empty()
+                || rpc_method.is_some_and(|m| self.methods.iter().any(|v| v == m)))
+    }
+}
+
+/// Upper bound of `retry.max_attempts`.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// Failover of requests whose backend failed them: an error status, a
+/// connection error or a timeout is tried again on another backend in rotation.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Backends tried per request, the first included; 1 = never fail over
+    pub max_attempts: u32,
+    /// Timeout of each attempt while failing over; 0 = `proxy.timeout_secs`
+    pub attempt_timeout_secs: u64,
+    /// Upstream statuses sent to another backend
+    pub retryable_status: Vec<u16>,
+    /// Try the next backend when response headers take longer than this;
+    /// 0 = off. Backends may override it with `first_byte_timeout_ms`.
+    pub first_byte_timeout_ms: u64,
+    /// Let an attempt that missed its first byte keep running and race the
+    /// next one, instead of cancelling it
+    pub keep_slow_attempts: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            attempt_timeout_secs: 0,
+            retryable_status: vec![429, 500, 502, 503, 504],
+            first_byte_timeout_ms: 0,
+            keep_slow_attempts: false,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// First-byte timeout for a backend with `backend_override`, if any applies.
+    pub fn first_byte_timeout(&self, backend_override: Option<u64>) -> Option<Duration> {
+        let ms = backend_override.unwrap_or(self.first_byte_timeout_ms);
+        (ms > 0).then(|| Duration::from_millis(ms))
+    }
+
+    /// Timeout of one upstream attempt, given the overall `proxy.timeout_secs`.
+    pub fn attempt_timeout(&self, proxy_timeout_secs: u64) -> Duration {
+        match self.attempt_timeout_secs {
+            secs if secs > 0 && self.max_attempts > 1 => Duration::from_secs(secs),
+            _ => Duration::from_secs(proxy_timeout_secs),
+        }
+    }
+
+    pub fn retries_status(&self, status: u16) -> bool {
+        self.retryable_status.contains(&status)
+    }
+}
+
+/// Passive per-backend circuit breaker fed by proxied requests. Health checks
+/// only probe every few seconds; a backend failing live traffic in between
+/// leaves rotation once its failure rate over the window crosses the threshold.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    pub enabled: bool,
+    /// Sliding window the failure rate is measured over
+    pub window_secs: u64,
+    /// Requests the window must hold before the breaker can trip
+    pub min_requests: u32,
+    /// Share of failed requests (5xx, connection errors, timeouts) that trips it
+    pub failure_percent: u32,
+    /// Time out of rotation before a probe request is let through
+    pub cooldown_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: 30,
+            min_requests: 20,
+            failure_percent: 50,
+            cooldown_secs: 30,
+        }
+    }
+}
+
+/// Canary queries sent to every backend in rotation, whose answers must agree.
+/// Catches a backend on a fork or serving stale state while its slot looks fine.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ConsistencyConfig {
+    pub enabled: bool,
+    /// Seconds between rounds of canary queries
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+    pub canaries: Vec<Canary>,
+}
+
+impl Default for ConsistencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 60,
+            timeout_secs: 5,
+            canaries: Vec::new(),
+        }
+    }
+}
+
+/// Genesis hashes of the public clusters, by the names `genesis_check.network`
+/// accepts.
+pub const KNOWN_NETWORKS: &[(&str, &str)] = &[
+    (
+        "mainnet-beta",
+        "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d",
+    ),
+    ("devnet", "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG"),
+    ("testnet", "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY"),
+];
+
+/// `getGenesisHash` asked of every backend, taking those on another cluster
+/// out of rotation. Catches a devnet URL pasted into a mainnet pool.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct GenesisCheckConfig {
+    pub enabled: bool,
+    /// Expected cluster: "mainnet-beta", "devnet" or "testnet"
+    pub network: Option<String>,
+    /// Expected genesis hash, for other clusters (e.g. a local validator)
+    pub expected_hash: Option<String>,
+    /// Seconds between checks; backends are also checked at startup
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for GenesisCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            network: None,
+            expected_hash: None,
+            interval_secs: 300,
+            timeout_secs: 5,
+        }
+    }
+}
+
+impl GenesisCheckConfig {
+    /// The genesis hash backends must report: `expected_hash`, else that of `network`.
+    pub fn expected(&self) -> Option<&str> {
+        self.expected_hash.as_deref().or_else(|| {
+            let network = self.network.as_deref()?;
+            KNOWN_NETWORKS
+                .iter()
+                .find(|(name, _)| *name == network)
+                .map(|(_, hash)| *hash)
+        })
+    }
+}
+
+/// Requests for slots older than non-archive nodes keep, sent to backends
+/// flagged `archive`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ArchiveRoutingConfig {
+    pub enabled: bool,
+    /// Slots behind the consensus tip from which a request counts as deep history
+    pub min_depth_slots: u64,
+}
+
+impl Default for ArchiveRoutingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_depth_slots: 100_000,
+        }
+    }
+}
+
+/// A read whose answer every backend should give identically, e.g. `getBalance`
+/// of an account that never changes, at `finalized`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Canary {
+    pub name: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+/// JSON bodies replacing the router's own plain-text errors, e.g. to add support
+/// links and error codes. Unset errors keep the default body.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorTemplatesConfig {
+    /// Missing or unknown API key
+    pub unauthorized: Option<serde_json::Value>,
+    /// Key over its rate limit
+    pub rate_limited: Option<serde_json::Value>,
+    /// No backend in rotation for the request
+    pub no_backend: Option<serde_json::Value>,
+    /// Key store unreachable and the key not accepted by `keystore_outage`
+    pub keystore_unavailable: Option<serde_json::Value>,
+}
+
+impl ErrorTemplatesConfig {
+    pub fn get(&self, error: RouterError) -> Option<&serde_json::Value> {
+        match error {
+            RouterError::Unauthorized => self.unauthorized.as_ref(),
+            RouterError::RateLimited => self.rate_limited.as_ref(),
+            RouterError::NoBackend => self.no_backend.as_ref(),
+            RouterError::KeystoreUnavailable => self.keystore_unavailable.as_ref(),
+        }
+    }
+}
+
+/// Preflight settings forced on `sendTransaction` requests from keys of one tier.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreflightPolicy {
+    /// Turn `skipPreflight: true` into `false`, so every transaction is simulated
+    pub require_preflight: bool,
+    /// Raise `preflightCommitment` to at least this
+    pub min_preflight_commitment: Option<Commitment>,
+    /// Replace the client's `maxRetries`; 0 leaves rebroadcasting to the caller's infrastructure
+    pub max_retries: Option<u64>,
+}
+
+impl PreflightPolicy {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Longest validity S3 allows for a presigned URL.
+pub const MAX_PRESIGN_EXPIRY_SECS: u64 = 7 * 24 * 3600;
+
+/// Upload of very large responses to S3-compatible storage, answered with a
+/// presigned URL for clients that send `X-RPC-Router-Offload`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct OffloadConfig {
+    pub enabled: bool,
+    /// S3 API endpoint, e.g. `https://s3.us-east-1.amazonaws.com`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    /// `endpoint/bucket/key` URLs (MinIO and most S3-compatible stores) instead
+    /// of `bucket.endpoint/key`
+    pub path_style: bool,
+    pub access_key_id: String,
+    /// Secret key (prefer `secret_access_key_env` to keep it out of the config file)
+    pub secret_access_key: Option<String>,
+    /// Environment variable holding the secret key; resolved at load time
+    pub secret_access_key_env: Option<String>,
+    /// Prepended to every object key
+    pub prefix: String,
+    /// Responses with a `Content-Length` of at least this many bytes are offloaded
+    pub min_bytes: u64,
+    /// Validity of the presigned URL
+    pub url_expiry_secs: u64,
+    /// Limit on the whole upload, from the first byte read upstream
+    pub upload_timeout_secs: u64,
+}
+
+impl Default for OffloadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            path_style: true,
+            access_key_id: String::new(),
+            secret_access_key: None,
+            secret_access_key_env: None,
+            prefix: "rpc-responses/".to_string(),
+            min_bytes: 8 * 1024 * 1024,
+            url_expiry_secs: 900,
+            upload_timeout_secs: 300,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ReloadConfig {
+    /// Probe new or changed backends on hot reload and reject the reload if any is unreachable
+    pub probe_backends: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Extra regexes whose matches are replaced with `[REDACTED]` in logs and error bodies
+    pub redact_patterns: Vec<String>,
+    /// `json` writes each log line as a JSON object, with access log fields
+    /// (client IP, owner, method, backend, status, duration, request id) as keys
+    pub log_format: LogFormat,
+}
+
+/// How log lines are written.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub timeout_secs: u64,
+    /// Re-send `getBlock`/`getTransaction` requests that omitted
+    /// `maxSupportedTransactionVersion` with this value when the backend rejects
+    /// them as unsupported. Disabled when unset.
+    pub retry_transaction_version: Option<u8>,
+    /// Largest request body accepted after decoding `Content-Encoding: gzip`
+    /// or `zstd`
+    pub max_decompressed_bytes: usize,
+    /// Serve a batch of one request (`[{...}]`) as the plain request and wrap
+    /// the response back into a batch
+    pub unwrap_single_batches: bool,
+    /// Route each request of a batch by its own method and answer with the
+    /// responses reassembled in order
+    pub split_batches: bool,
+    /// Requests of one split batch in flight at a time
+    pub split_batch_concurrency: usize,
+    /// Give up on opening an upstream connection after this long, so a hung
+    /// connect fails over instead of using the whole timeout; 0 = off.
+    /// Applies on restart.
+    pub connect_timeout_ms: u64,
+    /// Requests served at once across all keys; further ones are shed with
+    /// `429`. 0 = unlimited
+    pub max_in_flight: u32,
+    /// Largest upstream response held in memory to filter its fields or to
+    /// join it into a split batch
+    pub max_buffered_bytes: u64,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 30,
+            retry_transaction_version: None,
+            max_decompressed_bytes: MAX_BODY_SIZE,
+            unwrap_single_batches: false,
+            split_batches: false,
+            split_batch_concurrency: 8,
+            connect_timeout_ms: 0,
+            max_in_flight: 0,
+            max_buffered_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Synthetic monitoring callers, exempt from rate limiting and usage metering.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ProbesConfig {
+    /// Client networks (e.g. `10.20.0.0/16`) whose requests are probes
+    pub cidrs: Vec<IpNet>,
+    /// API keys that only probes use
+    pub keys: Vec<String>,
+}
+
+impl ProbesConfig {
+    /// Whether a request from `ip` presenting `api_keys` (the raw `api-key`
+    /// value) comes from a probe.
+    pub fn is_probe(&self, ip: IpAddr, api_keys: &str) -> bool {
+        self.cidrs.iter().any(|net| net.contains(&ip))
+            || api_keys
+                .split(',')
+                .any(|key| self.keys.iter().any(|k| k == key.trim()))
+    }
+}
+
+/// Restrictions applied to browser keys (keys validated by origin rather than secrecy).
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct BrowserKeyConfig {
+    /// Requests per second allowed for each (key, origin, client IP) combination
+    pub per_origin_ip_rate_limit: u64,
+    /// Methods browser keys may never call
+    pub blocked_methods: Vec<String>,
+}
+
+impl Default for BrowserKeyConfig {
+    fn default() -> Self {
+        Self {
+            per_origin_ip_rate_limit: 5,
+            blocked_methods: [
+                "getProgramAccounts",
+                "getLargestAccounts",
+                "getTokenLargestAccounts",
+                "getBlock",
+                "getBlocks",
+                "getBlocksWithLimit",
+                "requestAirdrop",
+            ]
+            .iter()
+            .map(|m| m.to_string())
+            .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct HealthCheckConfig {
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+    pub method: String,
+    pub consecutive_failures_threshold: u32,
+    pub consecutive_successes_threshold: u32,
+    /// Consecutive failed proxied requests (timeouts, 5xx, connection errors)
+    /// that take a backend out of rotation until its checks pass again; 0 = off
+    pub passive_failures_threshold: u32,
+    pub max_slot_lag: u64,
+    /// Weight (as a percentage of the configured weight) of a DEGRADED backend
+    pub degraded_weight_percent: u32,
+    /// Check stable backends less often and failing ones more often, between the bounds below
+    pub adaptive: bool,
+    pub min_interval_secs: u64,
+    pub max_interval_secs: u64,
+    /// Trusted slot sources outside the backend pool, used as the consensus tip
+    /// when they are ahead of every backend
+    pub reference_sources: Vec<ReferenceSource>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceKind {
+    /// JSON-RPC endpoint queried with `health_check.method`
+    #[default]
+    Rpc,
+    /// Plain HTTP GET returning JSON; the slot is read at `json_pointer`
+    Http,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReferenceSource {
+    pub label: String,
+    pub url: String,
+    #[serde(default)]
+    pub kind: ReferenceKind,
+    /// RFC 6901 pointer to the slot in an `http` source's response, e.g. `/data/slot`
+    pub json_pointer: Option<String>,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            timeout_secs: 5,
+            method: "getSlot".to_string(),
+            consecutive_failures_threshold: 3,
+            consecutive_successes_threshold: 2,
+            passive_failures_threshold: 0,
+            max_slot_lag: 50,
+            degraded_weight_percent: 25,
+            adaptive: false,
+            min_interval_secs: 5,
+            max_interval_secs: 300,
+            reference_sources: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Backend {
+    pub label: String,
+    pub url: String,
+    pub weight: u32,
+    pub ws_url: Option<String>,
+    /// Methods this backend must never receive, regardless of routing rules
+    #[serde(default)]
+    pub exclude_methods: Vec<String>,
+    /// HMAC-sign every request sent to this backend
+    #[serde(default)]
+    pub signing: Option<SigningConfig>,
+    /// Capability groups (e.g. "archival", "das") used for readiness gating
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Where the backend runs (e.g. "fra"); see `routing.region`
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Take no new requests or WebSocket sessions; in-flight ones complete and
+    /// health checks continue
+    #[serde(default)]
+    pub drain: bool,
+    /// Out of rotation for planned work: like `drain`, and neither health-checked
+    /// nor counted by the `unhealthy_backends` alert
+    #[serde(default)]
+    pub maintenance: bool,
+    /// Overrides `retry.first_byte_timeout_ms` for this backend (0 = off)
+    #[serde(default)]
+    pub first_byte_timeout_ms: Option<u64>,
+    /// Method -> URL path for methods the provider serves on their own path
+    /// (e.g. `"/v0/transactions"`), replacing the path of `url`. `{method}` in
+    /// a path is replaced with the method name.
+    #[serde(default)]
+    pub method_paths: HashMap<String, String>,
+    /// Keeps the full ledger history; see `archive_routing`
+    #[serde(default)]
+    pub archive: bool,
+    /// HTTP version, ALPN and plaintext controls for this backend
+    #[serde(default)]
+    pub protocol: BackendProtocol,
+    /// Headers sent with every request to this backend (e.g. a provider API
+    /// key). `${VAR}` in a value is replaced with the environment variable.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Query parameters added to `url` and `ws_url`, expanded like `headers`
+    #[serde(default)]
+    pub url_query_params: HashMap<String, String>,
+}
+
+/// Per-backend HMAC request signing. The signature covers `"{timestamp}.{body}"`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct SigningConfig {
+    /// Shared secret (prefer `key_env` to keep it out of the config file)
+    pub key: Option<String>,
+    /// Environment variable holding the shared secret; resolved at load time
+    pub key_env: Option<String>,
+    /// Optional key identifier sent alongside the signature, for key rotation
+    pub key_id: Option<String>,
+    pub header: String,
+    pub timestamp_header: String,
+    pub key_id_header: String,
+    /// Clock skew the backend tolerates; health checks fail when the backend's
+    /// `Date` header drifts further than this from the local clock
+    pub max_clock_skew_secs: u64,
+}
+
+impl Default for SigningConfig {
+    fn default() -> Self {
+        Self {
+            key: None,
+            key_env: None,
+            key_id: None,
+            header: "X-Signature".to_string(),
+            timestamp_header: "X-Signature-Timestamp".to_string(),
+            key_id_header: "X-Signature-Key-Id".to_string(),
+            max_clock_skew_secs: 30,
+        }
+    }
+}
+
+/// ALPN protocol ids a backend may be offered.
+pub const ALPN_PROTOCOLS: [&str; 2] = ["h2", "http/1.1"];
+
+/// HTTP version spoken to a backend.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpVersion {
+    /// HTTP/1.1, or HTTP/2 when `alpn` offers `h2` and the backend picks it
+    #[default]
+    Auto,
+    /// HTTP/1.1 only, for backends that mis-negotiate HTTP/2
+    Http1,
+    /// HTTP/2 only: negotiated with ALPN over `https`, with prior knowledge
+    /// (h2c) over `http`
+    Http2,
+}
+
+/// How the router talks to one backend. The defaults speak HTTP/1.1 without
+/// offering ALPN, over TLS for `https` URLs.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct BackendProtocol {
+    pub http_version: HttpVersion,
+    /// ALPN ids offered in the TLS handshake, most preferred first (`h2`,
+    /// `http/1.1`); `https` URLs only. Empty offers none, except `h2` for
+    /// `http_version = "http2"`.
+    pub alpn: Vec<String>,
+    /// Whether `url` and `ws_url` may be plain `http` / `ws`. Set to false to
+    /// refuse a backend that would otherwise be reached unencrypted.
+    pub allow_plaintext: bool,
+}
+
+impl Default for BackendProtocol {
+    fn default() -> Self {
+        Self {
+            http_version: HttpVersion::Auto,
+            alpn: Vec::new(),
+            allow_plaintext: true,
+        }
+    }
+}
+
+impl BackendProtocol {
+    /// Whether requests need a client of their own rather than the shared pools.
+    pub fn is_custom(&self) -> bool {
+        self.http_version != HttpVersion::Auto || !self.alpn.is_empty()
+    }
+
+    /// ALPN ids to offer in the TLS handshake.
+    pub fn alpn_offer(&self) -> Vec<&str> {
+        match (self.http_version, self.alpn.is_empty()) {
+            (HttpVersion::Http2, true) => vec!["h2"],
+            _ => self.alpn.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// Replace each `${VAR}` in `value` with the environment variable `VAR`.
+pub fn expand_env(value: &str) -> Result<String, String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("unterminated '${{' in '{}'", value))?;
+        let var = &after[..end];
+        let set = std::env::var(var).map_err(|_| format!("'{}' is not set", var))?;
+        expanded.push_str(&set);
+        rest = &after[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Percent-encode a query parameter name or value.
+fn encode_query(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Expand a backend's `headers` and `url_query_params`, check the headers and
+/// add the params to its URLs. Expanded values are registered as secrets.
+fn apply_backend_auth(backend: &mut Backend) -> Result<(), String> {
+    let label = backend.label.clone();
+    let expand = |field: &str, name: &str, value: &str| {
+        let expanded = expand_env(value)
+            .map_err(|e| format!("Backend '{}' {} '{}': {}", label, field, name, e))?;
+        if expanded != value {
+            redact::register_secret(&expanded);
+        }
+        Ok::<_, String>(expanded)
+    };
+
+    for (name, value) in backend.headers.iter_mut() {
+        *value = expand("header", name, value)?;
+        if HeaderName::try_from(name.as_str()).is_err()
+            || HeaderValue::try_from(value.as_str()).is_err()
+        {
+            return Err(format!("Backend '{}' has invalid header '{}'", label, name));
+        }
+    }
+
+    if backend.url_query_params.is_empty() {
+        return Ok(());
+    }
+    let mut params = Vec::with_capacity(backend.url_query_params.len());
+    for (name, value) in backend.url_query_params.iter_mut() {
+        if name.is_empty() {
+            return Err(format!("Backend '{}' has an empty query param name", label));
+        }
+        *value = expand("query param", name, value)?;
+        params.push(format!("{}={}", encode_query(name), encode_query(value)));
+    }
+    params.sort();
+    let query = params.join("&");
+    let append = |url: &mut String| {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        url.push_str(&query);
+    };
+    append(&mut backend.url);
+    if let Some(ws_url) = &mut backend.ws_url {
+        append(ws_url);
+    }
+    Ok(())
+}
+
+/// Check the metrics exporter was compiled in and its settings, expanding the
+/// OTLP headers.
+fn validate_metrics(metrics: &mut MetricsConfig) -> Result<(), String> {
+    let compiled = match metrics.exporter {
+        MetricsExporterKind::Prometheus => true,
+        MetricsExporterKind::Statsd => cfg!(feature = "statsd"),
+        MetricsExporterKind::Otlp => cfg!(feature = "otlp"),
+    };
+    if !compiled {
+        return Err(format!(
+            "metrics.exporter = \"{0}\" needs the router built with the '{0}' feature",
+            metrics.exporter.as_str()
+        ));
+    }
+    if metrics.interval_secs == 0 {
+        return Err("metrics.interval_secs must be > 0".into());
+    }
+    match metrics.exporter {
+        MetricsExporterKind::Prometheus => {}
+        MetricsExporterKind::Statsd => {
+            let valid = metrics
+                .statsd
+                .address
+                .rsplit_once(':')
+                .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+            if !valid {
+                return Err(format!(
+                    "metrics.statsd.address '{}' must be host:port",
+                    metrics.statsd.address
+                ));
+            }
+        }
+        MetricsExporterKind::Otlp => validate_otlp("metrics.otlp", &mut metrics.otlp)?,
+    }
+    Ok(())
+}
+
+/// Check the tracing settings, expanding the collector headers.
+fn validate_tracing(tracing: &mut TracingConfig) -> Result<(), String> {
+    if !tracing.enabled {
+        return Ok(());
+    }
+    if !cfg!(feature = "otlp") {
+        return Err("tracing needs the router built with the 'otlp' feature".into());
+    }
+    if tracing.sample_percent > 100 {
+        return Err("tracing.sample_percent must be between 0 and 100".into());
+    }
+    if tracing.interval_secs == 0 {
+        return Err("tracing.interval_secs must be > 0".into());
+    }
+    if tracing.max_queued_spans == 0 {
+        return Err("tracing.max_queued_spans must be > 0".into());
+    }
+    validate_otlp("tracing.otlp", &mut tracing.otlp)
+}
+
+/// Check the settings applied while the key store fails, expanding `${VAR}`
+/// in the static keys.
+fn validate_keystore_outage(outage: &mut KeystoreOutageConfig) -> Result<(), String> {
+    match outage.policy {
+        KeystoreOutagePolicy::FailClosed => {}
+        KeystoreOutagePolicy::FailOpen => {
+            if outage.remember_secs == 0 {
+                return Err("keystore_outage.remember_secs must be > 0".into());
+            }
+        }
+        KeystoreOutagePolicy::StaticKeys => {
+            if outage.static_keys.is_empty() {
+                return Err("keystore_outage.static_keys must not be empty".into());
+            }
+        }
+    }
+    let mut seen = HashSet::new();
+    for static_key in outage.static_keys.iter_mut() {
+        static_key.key = expand_env(&static_key.key).map_err(|e| {
+            format!(
+                "keystore_outage.static_keys key of '{}': {}",
+                static_key.owner, e
+            )
+        })?;
+        if static_key.key.is_empty() || static_key.owner.is_empty() {
+            return Err("keystore_outage.static_keys entries need a key and an owner".into());
+        }
+        redact::register_secret(&static_key.key);
+        if !seen.insert(static_key.key.as_str()) {
+            return Err(format!(
+                "keystore_outage.static_keys lists the key of '{}' twice",
+                static_key.owner
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check an OTLP collector's endpoint and headers, expanding `${VAR}` in the
+/// header values.
+fn validate_otlp(section: &str, otlp: &mut OtlpConfig) -> Result<(), String> {
+    if !otlp.endpoint.starts_with("http://") && !otlp.endpoint.starts_with("https://") {
+        return Err(format!(
+            "{}.endpoint '{}' must be an http(s) URL",
+            section,
+            redact::redact_url(&otlp.endpoint)
+        ));
+    }
+    for (name, value) in otlp.headers.iter_mut() {
+        let expanded =
+            expand_env(value).map_err(|e| format!("{} header '{}': {}", section, name, e))?;
+        if expanded != *value {
+            redact::register_secret(&expanded);
+        }
+        if HeaderName::try_from(name.as_str()).is_err()
+            || HeaderValue::try_from(expanded.as_str()).is_err()
+        {
+            return Err(format!("{} has invalid header '{}'", section, name));
+        }
+        *value = expanded;
+    }
+    Ok(())
+}
+
+/// Check a backend's protocol settings against the schemes of its URLs.
+fn validate_protocol(backend: &Backend) -> Result<(), String> {
+    let label = &backend.label;
+    let protocol = &backend.protocol;
+    let tls = backend.url.starts_with("https://");
+    if backend.url.starts_with("http://") && !protocol.allow_plaintext {
+        return Err(format!(
+            "Backend '{}' url is plain http but protocol.allow_plaintext = false",
+            label
+        ));
+    }
+    if let Some(ws_url) = &backend.ws_url {
+        if ws_url.starts_with("ws://") && !protocol.allow_plaintext {
+            return Err(format!(
+                "Backend '{}' ws_url is plain ws but protocol.allow_plaintext = false",
+                label
+            ));
+        }
+    }
+    if !protocol.alpn.is_empty() && !tls {
+        return Err(format!(
+            "Backend '{}' protocol.alpn needs an https url; plain http has no ALPN",
+            label
+        ));
+    }
+    for (i, id) in protocol.alpn.iter().enumerate() {
+        if !ALPN_PROTOCOLS.contains(&id.as_str()) {
+            return Err(format!(
+                "Backend '{}' protocol.alpn has unknown protocol '{}' (expected one of {})",
+                label,
+                id,
+                ALPN_PROTOCOLS.join(", ")
+            ));
+        }
+        if protocol.alpn[..i].contains(id) {
+            return Err(format!(
+                "Backend '{}' protocol.alpn lists '{}' twice",
+                label, id
+            ));
+        }
+    }
+    let offers_h2 = protocol.alpn.iter().any(|id| id == "h2");
+    match protocol.http_version {
+        HttpVersion::Http1 if offers_h2 => Err(format!(
+            "Backend '{}' protocol.http_version = \"http1\" conflicts with 'h2' in protocol.alpn",
+            label
+        )),
+        HttpVersion::Http2 if !protocol.alpn.is_empty() && !offers_h2 => Err(format!(
+            "Backend '{}' protocol.http_version = \"http2\" needs 'h2' in protocol.alpn",
+            label
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Prefix of environment variables overriding config values, with `__`
+/// separating path segments: `ROUTER__HEALTH_CHECK__INTERVAL_SECS=10` sets
+/// `health_check.interval_secs`.
+pub const ENV_PREFIX: &str = "ROUTER__";
+
+/// Where an effective config value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueSource {
+    Default,
+    File,
+    /// The `ROUTER__*` variable that set it
+    Env(String),
+}
+
+/// `ROUTER__*` variables of the process environment, sorted by name.
+pub fn env_overrides() -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect();
+    vars.sort();
+    vars
+}
+
+/// Config path (`health_check.interval_secs`, `backends.0.weight`) set by an
+/// override variable. Segments are lowercased.
+pub fn override_path(var: &str) -> Option<String> {
+    let segments: Vec<String> = var
+        .strip_prefix(ENV_PREFIX)?
+        .split("__")
+        .map(str::to_ascii_lowercase)
+        .collect();
+    if segments.iter().any(String::is_empty) {
+        return None;
+    }
+    Some(segments.join("."))
 }
 
-impl Default for ProxyConfig {
-    fn default() -> Self {
-        Self { timeout_secs: 30 }
+/// An override's value in TOML syntax (`30`, `true`, `["a", "b"]`,
+/// `{ weight = 2 }`), or taken as a plain string if it does not parse.
+fn override_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("v = {}", raw))
+        .ok()
+        .filter(|table| table.len() == 1)
+        .and_then(|mut table| table.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// Set `path` in `doc`, creating missing tables. Numeric segments index into
+/// existing arrays (`backends.0`).
+fn apply_override(doc: &mut toml::Value, path: &str, value: toml::Value) -> Result<(), String> {
+    let mut node = doc;
+    for segment in path.split('.') {
+        node = match node {
+            toml::Value::Table(table) => table
+                .entry(segment)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new())),
+            toml::Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| items.get_mut(i))
+                .ok_or_else(|| format!("no entry '{}' in array", segment))?,
+            _ => return Err(format!("'{}' is not a table", segment)),
+        };
     }
+    *node = value;
+    Ok(())
 }
 
-#[derive(Debug, Deserialize, Clone)]
-#[serde(default)]
-pub struct HealthCheckConfig {
-    pub interval_secs: u64,
-    pub timeout_secs: u64,
-    pub method: String,
-    pub consecutive_failures_threshold: u32,
-    pub consecutive_successes_threshold: u32,
-    pub max_slot_lag: u64,
+fn lookup<'a>(doc: &'a toml::Value, path: &str) -> Option<&'a toml::Value> {
+    path.split('.').try_fold(doc, |node, segment| match node {
+        toml::Value::Table(table) => table.get(segment),
+        toml::Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
 }
 
-impl Default for HealthCheckConfig {
-    fn default() -> Self {
-        Self {
-            interval_secs: 30,
-            timeout_secs: 5,
-            method: "getSlot".to_string(),
-            consecutive_failures_threshold: 3,
-            consecutive_successes_threshold: 2,
-            max_slot_lag: 50,
+/// Leaves of a JSON document by dotted path. Arrays of scalars are leaves.
+fn flatten(prefix: &str, value: &serde_json::Value, out: &mut Vec<(String, serde_json::Value)>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+    match value {
+        serde_json::Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                flatten(&join(key), child, out);
+            }
         }
+        serde_json::Value::Array(items) if items.iter().any(|i| i.is_object()) => {
+            for (i, child) in items.iter().enumerate() {
+                flatten(&join(&i.to_string()), child, out);
+            }
+        }
+        _ => out.push((prefix.to_string(), value.clone())),
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct Backend {
-    pub label: String,
-    pub url: String,
-    pub weight: u32,
-    pub ws_url: Option<String>,
-}
-
 pub fn load_config(config_path: &str) -> Result<Config, Box<dyn std::error::Error>> {
     if !Path::new(config_path).exists() {
         return Err(format!("Configuration file not found: {}", config_path).into());
     }
 
     let contents = fs::read_to_string(config_path)?;
-    let config: Config = toml::from_str(&contents)?;
+    parse_config(&contents)
+}
+
+/// Write the `weight`, `drain` and `maintenance` of `backend` to its
+/// `[[backends]]` entry in the config file at `path`. The rest of the file,
+/// comments included, is kept as it is.
+pub fn persist_backend(path: &str, backend: &Backend) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut doc: toml_edit::DocumentMut = contents.parse().map_err(|e| format!("{}", e))?;
+    let entry = doc
+        .get_mut("backends")
+        .and_then(|b| b.as_array_of_tables_mut())
+        .and_then(|tables| {
+            tables
+                .iter_mut()
+                .find(|t| t.get("label").and_then(|l| l.as_str()) == Some(&backend.label))
+        })
+        .ok_or_else(|| format!("No [[backends]] entry for '{}' in {}", backend.label, path))?;
+
+    entry["weight"] = toml_edit::value(i64::from(backend.weight));
+    for (flag, set) in [
+        ("drain", backend.drain),
+        ("maintenance", backend.maintenance),
+    ] {
+        if set {
+            entry[flag] = toml_edit::value(true);
+        } else {
+            entry.remove(flag);
+        }
+    }
+
+    // Replace the file in one step so a concurrent reload never reads half of it
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, doc.to_string()).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+/// Parse and validate a configuration document, as read from the config file or
+/// pushed through `PUT /admin/config`, with the `ROUTER__*` environment
+/// overrides layered over it.
+pub fn parse_config(contents: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    parse_config_with_overrides(contents, &env_overrides())
+}
+
+/// [`parse_config`] with explicit `(variable, value)` overrides in place of the
+/// process environment.
+pub fn parse_config_with_overrides(
+    contents: &str,
+    overrides: &[(String, String)],
+) -> Result<Config, Box<dyn std::error::Error>> {
+    let mut hasher = Sha256::new();
+    hasher.update(contents.as_bytes());
+    let mut config: Config = if overrides.is_empty() {
+        // Straight from the text, so errors point at lines
+        toml::from_str(contents)?
+    } else {
+        let mut doc: toml::Value = toml::from_str(contents)?;
+        let mut paths = BTreeMap::new();
+        for (var, raw) in overrides {
+            let path = override_path(var)
+                .ok_or_else(|| format!("Invalid config override variable '{}'", var))?;
+            apply_override(&mut doc, &path, override_value(raw))
+                .map_err(|e| format!("{} does not match the config: {}", var, e))?;
+            hasher.update(format!("\n{}={}", var, raw).as_bytes());
+            paths.insert(path, var.clone());
+        }
+        let mut config: Config = doc
+            .try_into()
+            .map_err(|e| format!("Invalid config after environment overrides: {}", e))?;
+        config.overrides = paths;
+        config
+    };
+    config.hash = hex::encode(&hasher.finalize()[..6]);
 
     if config.redis_url.is_empty() {
         return Err("Redis URL must be configured".into());
@@ -85,6 +2337,10 @@ pub fn load_config(config_path: &str) -> Result<Config, Box<dyn std::error::Erro
         return Err("Duplicate backend labels found in configuration".into());
     }
 
+    if config.backends.iter().all(|b| b.drain || b.maintenance) {
+        return Err("At least one backend must be out of drain and maintenance".into());
+    }
+
     for backend in &config.backends {
         if backend.weight == 0 {
             return Err(format!("Backend '{}' has invalid weight 0", backend.label).into());
@@ -92,12 +2348,174 @@ pub fn load_config(config_path: &str) -> Result<Config, Box<dyn std::error::Erro
         if backend.label.is_empty() {
             return Err(format!("Backend with URL '{}' has empty label", backend.url).into());
         }
+        for method in &backend.exclude_methods {
+            if !is_known_method(method) {
+                return Err(format!(
+                    "Backend '{}' excludes unknown method '{}'",
+                    backend.label, method
+                )
+                .into());
+            }
+        }
+        validate_protocol(backend)?;
+        for (method, path) in &backend.method_paths {
+            if method.is_empty() || !path.starts_with('/') {
+                return Err(format!(
+                    "Backend '{}' method path for '{}' must start with '/'",
+                    backend.label, method
+                )
+                .into());
+            }
+            if PathAndQuery::try_from(path.replace("{method}", method)).is_err() {
+                return Err(format!(
+                    "Backend '{}' has invalid method path '{}'",
+                    backend.label, path
+                )
+                .into());
+            }
+        }
+    }
+
+    for backend in &mut config.backends {
+        apply_backend_auth(backend)?;
+        if let Some(signing) = &mut backend.signing {
+            if let Some(var) = &signing.key_env {
+                let key = std::env::var(var).map_err(|_| {
+                    format!(
+                        "Backend '{}' signing key_env '{}' is not set",
+                        backend.label, var
+                    )
+                })?;
+                signing.key = Some(key);
+            }
+            match &signing.key {
+                Some(key) if !key.is_empty() => redact::register_secret(key),
+                _ => {
+                    return Err(format!(
+                        "Backend '{}' signing requires key or key_env",
+                        backend.label
+                    )
+                    .into())
+                }
+            }
+        }
+    }
+
+    if let Some(var) = &config.offload.secret_access_key_env {
+        let key = std::env::var(var)
+            .map_err(|_| format!("offload.secret_access_key_env '{}' is not set", var))?;
+        config.offload.secret_access_key = Some(key);
+    }
+    let offload = &config.offload;
+    let secret = offload.secret_access_key.as_deref().unwrap_or_default();
+    if !secret.is_empty() {
+        redact::register_secret(secret);
+    }
+    if offload.enabled {
+        if !offload.endpoint.starts_with("http://") && !offload.endpoint.starts_with("https://") {
+            return Err("offload.endpoint must be an http(s) URL".into());
+        }
+        if offload.bucket.is_empty()
+            || offload.region.is_empty()
+            || offload.access_key_id.is_empty()
+        {
+            return Err("offload needs a bucket, region and access_key_id".into());
+        }
+        if secret.is_empty() {
+            return Err("offload needs secret_access_key or secret_access_key_env".into());
+        }
+        if offload.min_bytes == 0 {
+            return Err("offload.min_bytes must be > 0".into());
+        }
+        if !(1..=MAX_PRESIGN_EXPIRY_SECS).contains(&offload.url_expiry_secs) {
+            return Err(format!(
+                "offload.url_expiry_secs must be between 1 and {}",
+                MAX_PRESIGN_EXPIRY_SECS
+            )
+            .into());
+        }
+        if offload.upload_timeout_secs == 0 {
+            return Err("offload.upload_timeout_secs must be > 0".into());
+        }
+    }
+
+    if config.health_check.degraded_weight_percent > 100 {
+        return Err("health_check.degraded_weight_percent must be <= 100".into());
+    }
+
+    let hc = &config.health_check;
+    for source in &hc.reference_sources {
+        if source.label.is_empty() || source.url.is_empty() {
+            return Err("health_check.reference_sources entries need a label and url".into());
+        }
+        match source.kind {
+            ReferenceKind::Rpc if hc.method != "getSlot" && hc.method != "getBlockHeight" => {
+                return Err(format!(
+                    "Reference source '{}' requires health_check.method getSlot or getBlockHeight",
+                    source.label
+                )
+                .into())
+            }
+            // The value read is a slot, which only compares to backends' slots
+            ReferenceKind::Http if hc.method != "getSlot" => {
+                return Err(format!(
+                    "Reference source '{}' of kind http requires health_check.method getSlot",
+                    source.label
+                )
+                .into())
+            }
+            ReferenceKind::Http
+                if !source
+                    .json_pointer
+                    .as_deref()
+                    .is_some_and(|p| p.starts_with('/')) =>
+            {
+                return Err(format!(
+                    "Reference source '{}' of kind http requires a json_pointer starting with '/'",
+                    source.label
+                )
+                .into())
+            }
+            _ => {}
+        }
+    }
+    if hc.interval_secs == 0 {
+        return Err("health_check.interval_secs must be > 0".into());
+    }
+    if hc.adaptive
+        && !(hc.min_interval_secs > 0
+            && hc.min_interval_secs <= hc.interval_secs
+            && hc.interval_secs <= hc.max_interval_secs)
+    {
+        return Err(format!(
+            "health_check adaptive bounds must satisfy 0 < min_interval_secs ({}) <= interval_secs ({}) <= max_interval_secs ({})",
+            hc.min_interval_secs, hc.interval_secs, hc.max_interval_secs
+        )
+        .into());
     }
 
     if config.proxy.timeout_secs == 0 {
         return Err("Proxy timeout_secs must be > 0".into());
     }
 
+    if config.proxy.max_decompressed_bytes == 0
+        || config.proxy.max_decompressed_bytes > MAX_BODY_SIZE
+    {
+        return Err(format!(
+            "proxy.max_decompressed_bytes must be between 1 and {}",
+            MAX_BODY_SIZE
+        )
+        .into());
+    }
+
+    if config.proxy.max_buffered_bytes == 0 {
+        return Err("proxy.max_buffered_bytes must be > 0".into());
+    }
+
+    if config.proxy.split_batches && config.proxy.split_batch_concurrency == 0 {
+        return Err("proxy.split_batch_concurrency must be > 0".into());
+    }
+
     for (method, label) in &config.method_routes {
         if !backend_labels.contains_key(label) {
             return Err(format!(
@@ -106,6 +2524,559 @@ pub fn load_config(config_path: &str) -> Result<Config, Box<dyn std::error::Erro
             )
             .into());
         }
+        if config
+            .backends
+            .iter()
+            .any(|b| b.label == *label && b.exclude_methods.contains(method))
+        {
+            return Err(format!(
+                "Method route '{}' targets backend '{}' which excludes it",
+                method, label
+            )
+            .into());
+        }
+    }
+
+    for (tier, routes) in &config.tier_routes {
+        for (method, label) in routes {
+            if !is_known_method(method) {
+                return Err(format!("tier_routes.{}: unknown method '{}'", tier, method).into());
+            }
+            if !backend_labels.contains_key(label) {
+                return Err(format!(
+                    "tier_routes.{}: method '{}' references unknown backend label '{}'",
+                    tier, method, label
+                )
+                .into());
+            }
+            if config
+                .backends
+                .iter()
+                .any(|b| b.label == *label && b.exclude_methods.contains(method))
+            {
+                return Err(format!(
+                    "tier_routes.{}: method '{}' targets backend '{}' which excludes it",
+                    tier, method, label
+                )
+                .into());
+            }
+        }
+    }
+
+    redact::compile_patterns(&config.logging.redact_patterns)?;
+
+    for token in &config.admin.tokens {
+        if token.len() < 16 {
+            return Err("Admin tokens must be at least 16 characters".into());
+        }
+        redact::register_secret(token);
+    }
+    for key in &config.probes.keys {
+        if key.trim().is_empty() || key.contains(',') {
+            return Err("probes.keys entries must be non-empty keys without commas".into());
+        }
+    }
+    for group in &config.readiness.required_groups {
+        if !config.backends.iter().any(|b| b.groups.contains(group)) {
+            return Err(format!(
+                "readiness.required_groups: no backend is in group '{}'",
+                group
+            )
+            .into());
+        }
+    }
+
+    for method in config.method_idempotency.keys() {
+        if method.is_empty() {
+            return Err("method_idempotency: method names must not be empty".into());
+        }
+        if is_known_method(method) {
+            return Err(format!(
+                "method_idempotency: '{}' is a built-in method and cannot be reclassified",
+                method
+            )
+            .into());
+        }
+    }
+
+    if config.hedging.budget_percent > 100 {
+        return Err("hedging.budget_percent must be <= 100".into());
+    }
+    if config.hedging.delay_percentile > 99 {
+        return Err("hedging.delay_percentile must be <= 99".into());
+    }
+    if config.hedging.delay_ms >= config.proxy.timeout_secs.saturating_mul(1000) {
+        return Err("hedging.delay_ms must be below proxy.timeout_secs".into());
+    }
+    for method in &config.hedging.methods {
+        match idempotency(method, &config.method_idempotency) {
+            None => {
+                return Err(format!("hedging.methods: unknown method '{}'", method).into());
+            }
+            Some(Idempotency::Safe) => {}
+            Some(_) => {
+                return Err(format!("hedging.methods: '{}' cannot be sent twice", method).into());
+            }
+        }
+    }
+
+    for method in &config.coalescing.methods {
+        match idempotency(method, &config.method_idempotency) {
+            None => {
+                return Err(format!("coalescing.methods: unknown method '{}'", method).into());
+            }
+            Some(Idempotency::Safe) => {}
+            Some(_) => {
+                return Err(format!("coalescing.methods: '{}' is not a read", method).into());
+            }
+        }
+    }
+    if !(1..=MAX_COALESCED_RESPONSE_BYTES).contains(&config.coalescing.max_response_bytes) {
+        return Err(format!(
+            "coalescing.max_response_bytes must be between 1 and {}",
+            MAX_COALESCED_RESPONSE_BYTES
+        )
+        .into());
+    }
+
+    if config.cache.serve_stale_secs > MAX_STALE_SECS {
+        return Err(format!("cache.serve_stale_secs must be <= {}", MAX_STALE_SECS).into());
+    }
+    let ttl = &config.cache.commitment_ttl_slots;
+    for (commitment, slots) in [
+        ("processed", ttl.processed),
+        ("confirmed", ttl.confirmed),
+        ("finalized", ttl.finalized),
+    ] {
+        if !(1..=MAX_TTL_SLOTS).contains(&slots) {
+            return Err(format!(
+                "cache.commitment_ttl_slots.{} must be between 1 and {}",
+                commitment, MAX_TTL_SLOTS
+            )
+            .into());
+        }
+    }
+    for method in &config.cache.stale_methods {
+        match idempotency(method, &config.method_idempotency) {
+            None => {
+                return Err(format!("cache.stale_methods: unknown method '{}'", method).into());
+            }
+            Some(Idempotency::Safe) => {}
+            Some(_) => {
+                return Err(format!("cache.stale_methods: '{}' is not a read", method).into());
+            }
+        }
+    }
+    for (method, secs) in &config.cache.method_ttl_secs {
+        match idempotency(method, &config.method_idempotency) {
+            None => {
+                return Err(format!("cache.method_ttl_secs: unknown method '{}'", method).into());
+            }
+            Some(Idempotency::Safe) => {}
+            Some(_) => {
+                return Err(format!("cache.method_ttl_secs: '{}' is not a read", method).into());
+            }
+        }
+        if !(1..=MAX_METHOD_TTL_SECS).contains(secs) {
+            return Err(format!(
+                "cache.method_ttl_secs.{} must be between 1 and {}",
+                method, MAX_METHOD_TTL_SECS
+            )
+            .into());
+        }
+    }
+    for method in &config.cache.revalidate_methods {
+        if context_config_index(method).is_none() {
+            return Err(format!(
+                "cache.revalidate_methods: '{}' does not take minContextSlot",
+                method
+            )
+            .into());
+        }
+        if !config.cache.method_ttl_secs.contains_key(method)
+            && cache_policy(method) != Some(CachePolicy::PerSlot)
+        {
+            return Err(format!(
+                "cache.revalidate_methods: '{}' is not cached; add it to cache.method_ttl_secs",
+                method
+            )
+            .into());
+        }
+    }
+    let tuning = &config.cache.ttl_tuning;
+    if tuning.enabled {
+        if tuning.methods.is_empty() {
+            return Err("cache.ttl_tuning.methods must not be empty when enabled".into());
+        }
+        if tuning.explore_percent > 100 {
+            return Err("cache.ttl_tuning.explore_percent must be <= 100".into());
+        }
+        if tuning.revalidate_percent > 100 {
+            return Err("cache.ttl_tuning.revalidate_percent must be <= 100".into());
+        }
+    }
+    for (method, bounds) in &tuning.methods {
+        if !config.cache.method_ttl_secs.contains_key(method) {
+            return Err(format!(
+                "cache.ttl_tuning.methods.{} must also be in cache.method_ttl_secs",
+                method
+            )
+            .into());
+        }
+        if bounds.min_secs == 0
+            || bounds.min_secs > bounds.max_secs
+            || bounds.max_secs > MAX_METHOD_TTL_SECS
+        {
+            return Err(format!(
+                "cache.ttl_tuning.methods.{}: need 1 <= min_secs <= max_secs <= {}",
+                method, MAX_METHOD_TTL_SECS
+            )
+            .into());
+        }
+    }
+
+    let network_stats = &config.cache.network_stats;
+    if !(1..=MAX_METHOD_TTL_SECS).contains(&network_stats.ttl_secs) {
+        return Err(format!(
+            "cache.network_stats.ttl_secs must be between 1 and {}",
+            MAX_METHOD_TTL_SECS
+        )
+        .into());
+    }
+    if network_stats.tolerance_bps > 10_000 {
+        return Err("cache.network_stats.tolerance_bps must be <= 10000".into());
+    }
+
+    if !(1..=50).contains(&config.routing.min_share_percent) {
+        return Err("routing.min_share_percent must be between 1 and 50".into());
+    }
+    if config.routing.latency_tolerance_percent > 100 {
+        return Err("routing.latency_tolerance_percent must be <= 100".into());
+    }
+
+    if config
+        .backends
+        .iter()
+        .any(|b| b.region.as_deref() == Some(""))
+    {
+        return Err("Backend region must not be empty".into());
+    }
+    if let Some(region) = &config.routing.region {
+        if !config
+            .backends
+            .iter()
+            .any(|b| b.region.as_ref() == Some(region))
+        {
+            return Err(format!("routing.region: no backend is in region '{}'", region).into());
+        }
+    }
+
+    for (i, rule) in config.alerts.iter().enumerate() {
+        if rule.name.is_empty() {
+            return Err("alerts entries need a name".into());
+        }
+        if config.alerts[..i].iter().any(|r| r.name == rule.name) {
+            return Err(format!("Alert '{}' is defined twice", rule.name).into());
+        }
+        if !rule.threshold.is_finite() {
+            return Err(format!("Alert '{}' has an invalid threshold", rule.name).into());
+        }
+        if !(rule.webhook_url.starts_with("http://") || rule.webhook_url.starts_with("https://")) {
+            return Err(format!("Alert '{}' needs an http(s) webhook_url", rule.name).into());
+        }
+        match rule.method.as_deref() {
+            None if rule.metric.per_method() => {
+                return Err(format!("Alert '{}' needs a method", rule.name).into());
+            }
+            Some(method) if !is_known_method(method) => {
+                return Err(
+                    format!("Alert '{}' watches unknown method '{}'", rule.name, method).into(),
+                );
+            }
+            _ => {}
+        }
+        if let Some(backend) = &rule.backend {
+            if !backend_labels.contains_key(backend) {
+                return Err(format!(
+                    "Alert '{}' references unknown backend '{}'",
+                    rule.name, backend
+                )
+                .into());
+            }
+        }
+    }
+
+    if config.pools.heavy_min_cost == 0 {
+        return Err("pools.heavy_min_cost must be > 0".into());
+    }
+
+    let websocket = &config.websocket;
+    if websocket.ping_interval_secs > 0 && websocket.pong_timeout_secs == 0 {
+        return Err("websocket.pong_timeout_secs must be > 0 when heartbeats are enabled".into());
+    }
+    if websocket.max_queued_messages == 0 {
+        return Err("websocket.max_queued_messages must be > 0".into());
+    }
+    if websocket.slow_consumer_timeout_secs == 0 {
+        return Err("websocket.slow_consumer_timeout_secs must be > 0".into());
+    }
+    if websocket.local_slot_subscriptions && websocket.slot_poll_ms == 0 {
+        return Err("websocket.slot_poll_ms must be > 0 for local slot subscriptions".into());
+    }
+    if config.blockhash_check.enabled && config.blockhash_check.poll_ms == 0 {
+        return Err("blockhash_check.poll_ms must be > 0".into());
+    }
+    let bridge = &config.poll_bridge;
+    if bridge.enabled
+        && (bridge.max_subscriptions_per_key == 0
+            || bridge.max_wait_secs == 0
+            || bridge.idle_timeout_secs == 0
+            || bridge.max_buffered == 0)
+    {
+        return Err("poll_bridge limits and timeouts must be > 0".into());
+    }
+    if config.integrity.enabled && config.integrity.max_bytes == 0 {
+        return Err("integrity.max_bytes must be > 0".into());
+    }
+    for method in &config.integrity.methods {
+        if !is_known_method(method) {
+            return Err(format!("integrity.methods: unknown method '{}'", method).into());
+        }
+    }
+    let breaker = &config.circuit_breaker;
+    if breaker.enabled
+        && (breaker.window_secs == 0 || breaker.min_requests == 0 || breaker.cooldown_secs == 0)
+    {
+        return Err(
+            "circuit_breaker.window_secs, min_requests and cooldown_secs must be > 0".into(),
+        );
+    }
+    if !(1..=100).contains(&breaker.failure_percent) {
+        return Err("circuit_breaker.failure_percent must be between 1 and 100".into());
+    }
+    let consistency = &config.consistency;
+    if consistency.enabled && (consistency.interval_secs == 0 || consistency.timeout_secs == 0) {
+        return Err("consistency.interval_secs and timeout_secs must be > 0".into());
+    }
+    for (i, canary) in consistency.canaries.iter().enumerate() {
+        if canary.name.is_empty()
+            || consistency.canaries[..i]
+                .iter()
+                .any(|c| c.name == canary.name)
+        {
+            return Err(format!(
+                "consistency.canaries: name '{}' is empty or not unique",
+                canary.name
+            )
+            .into());
+        }
+        match method_info(&canary.method).map(|m| m.class) {
+            None => {
+                return Err(
+                    format!("consistency.canaries: unknown method '{}'", canary.method).into(),
+                );
+            }
+            Some(MethodClass::Write | MethodClass::Subscription) => {
+                return Err(
+                    format!("consistency.canaries: '{}' is not a read", canary.method).into(),
+                );
+            }
+            Some(_) => {}
+        }
+        if !(canary.params.is_null() || canary.params.is_array()) {
+            return Err(format!(
+                "consistency.canaries: params of '{}' must be an array",
+                canary.name
+            )
+            .into());
+        }
+    }
+
+    let genesis = &config.genesis_check;
+    if let Some(network) = &genesis.network {
+        if !KNOWN_NETWORKS.iter().any(|(name, _)| name == network) {
+            return Err(format!("genesis_check.network: unknown network '{}'", network).into());
+        }
+    }
+    if let Some(hash) = &genesis.expected_hash {
+        if bs58::decode(hash).into_vec().map(|h| h.len()) != Ok(32) {
+            return Err(format!(
+                "genesis_check.expected_hash: '{}' is not a base58 hash",
+                hash
+            )
+            .into());
+        }
+    }
+    if genesis.enabled {
+        if genesis.network.is_some() == genesis.expected_hash.is_some() {
+            return Err("genesis_check needs exactly one of network and expected_hash".into());
+        }
+        if genesis.interval_secs == 0 || genesis.timeout_secs == 0 {
+            return Err("genesis_check.interval_secs and timeout_secs must be > 0".into());
+        }
+    }
+
+    let archive = &config.archive_routing;
+    if archive.enabled {
+        if config.health_check.method != "getSlot" {
+            return Err("archive_routing requires health_check.method getSlot".into());
+        }
+        if !config.backends.iter().any(|b| b.archive) {
+            return Err("archive_routing needs at least one backend with archive = true".into());
+        }
+        if archive.min_depth_slots == 0 {
+            return Err("archive_routing.min_depth_slots must be > 0".into());
+        }
+    }
+
+    for (name, error) in [
+        ("unauthorized", RouterError::Unauthorized),
+        ("rate_limited", RouterError::RateLimited),
+        ("no_backend", RouterError::NoBackend),
+        ("keystore_unavailable", RouterError::KeystoreUnavailable),
+    ] {
+        let Some(template) = config.error_templates.get(error) else {
+            continue;
+        };
+        if !template.is_object() {
+            return Err(format!("error_templates.{} must be a table", name).into());
+        }
+        if let Some(unknown) = placeholders(template)
+            .into_iter()
+            .find(|p| !error.placeholders().contains(&p.as_str()))
+        {
+            return Err(format!(
+                "error_templates.{}: unknown placeholder '{{{}}}'",
+                name, unknown
+            )
+            .into());
+        }
+    }
+
+    if !(1..=MAX_ATTEMPTS).contains(&config.retry.max_attempts) {
+        return Err(format!("retry.max_attempts must be between 1 and {}", MAX_ATTEMPTS).into());
+    }
+    if let Some(status) = config
+        .retry
+        .retryable_status
+        .iter()
+        .find(|s| !(400..=599).contains(*s))
+    {
+        return Err(format!("retry.retryable_status: {} is not an error status", status).into());
+    }
+
+    if config.dead_letter.max_entries == 0 {
+        return Err("dead_letter.max_entries must be > 0".into());
+    }
+    if config.dead_letter.store == DeadLetterStoreKind::File && config.dead_letter.path.is_empty() {
+        return Err("dead_letter.path must be set for the file store".into());
+    }
+
+    let journal = &config.journal;
+    if journal.retention_secs == 0 {
+        return Err("journal.retention_secs must be > 0".into());
+    }
+    if journal.resume_secs > journal.retention_secs {
+        return Err("journal.resume_secs must be <= retention_secs".into());
+    }
+    if journal.store == JournalStoreKind::File && journal.path.is_empty() {
+        return Err("journal.path must be set for the file store".into());
+    }
+
+    let ledger = &config.usage_ledger;
+    if ledger.retention_days == 0 || ledger.flush_secs == 0 {
+        return Err("usage_ledger.retention_days and flush_secs must be > 0".into());
+    }
+    if ledger.store == UsageLedgerStoreKind::Sqlite && ledger.path.is_empty() {
+        return Err("usage_ledger.path must be set for the sqlite store".into());
+    }
+    if config.key_usage.retention_days == 0 || config.key_usage.flush_secs == 0 {
+        return Err("key_usage.retention_days and flush_secs must be > 0".into());
+    }
+    let caps = &config.spending_caps;
+    if let Some((tier, _)) = caps
+        .tier_prices
+        .iter()
+        .find(|(_, price)| !price.is_finite() || **price < 0.0)
+    {
+        return Err(format!("spending_caps.tier_prices.{} must be >= 0", tier).into());
+    }
+    if !caps.default_price.is_finite() || caps.default_price < 0.0 {
+        return Err("spending_caps.default_price must be >= 0".into());
+    }
+    if caps.throttled_rate_limit == 0 || caps.flush_secs == 0 {
+        return Err("spending_caps.throttled_rate_limit and flush_secs must be > 0".into());
+    }
+    if !(caps.webhook_url.is_empty()
+        || caps.webhook_url.starts_with("http://")
+        || caps.webhook_url.starts_with("https://"))
+    {
+        return Err("spending_caps.webhook_url must be an http(s) URL".into());
+    }
+    if !(1..=MAX_TRAFFIC_RETENTION_DAYS).contains(&config.traffic_report.retention_days) {
+        return Err(format!(
+            "traffic_report.retention_days must be between 1 and {}",
+            MAX_TRAFFIC_RETENTION_DAYS
+        )
+        .into());
+    }
+
+    let heatmap = &config.latency_heatmap;
+    if !(1..=MAX_HEATMAP_WINDOW_SECS).contains(&heatmap.window_secs) {
+        return Err(format!(
+            "latency_heatmap.window_secs must be between 1 and {}",
+            MAX_HEATMAP_WINDOW_SECS
+        )
+        .into());
+    }
+    if !(1..=MAX_HEATMAP_WINDOWS).contains(&heatmap.windows) {
+        return Err(format!(
+            "latency_heatmap.windows must be between 1 and {}",
+            MAX_HEATMAP_WINDOWS
+        )
+        .into());
+    }
+
+    if config.admin.dual_control && config.admin.tokens.len() < 2 {
+        return Err("admin.dual_control requires at least two admin tokens".into());
+    }
+
+    if config.bind_addresses.is_empty() {
+        return Err("bind_addresses must not be empty".into());
+    }
+    for (i, addr) in config.bind_addresses.iter().enumerate() {
+        if config.bind_addresses[..i].contains(addr) {
+            return Err(format!("bind_addresses lists {} twice", addr).into());
+        }
+    }
+    if config.admin_bind_addresses.is_empty() {
+        return Err("admin_bind_addresses must not be empty".into());
+    }
+    for (i, addr) in config.admin_bind_addresses.iter().enumerate() {
+        if config.admin_bind_addresses[..i].contains(addr) {
+            return Err(format!("admin_bind_addresses lists {} twice", addr).into());
+        }
+    }
+
+    let tls = &config.tls;
+    if tls.enabled && (tls.cert_path.is_empty() || tls.key_path.is_empty()) {
+        return Err("tls.cert_path and tls.key_path must be set when TLS is enabled".into());
+    }
+    if tls.reload_secs == 0 {
+        return Err("tls.reload_secs must be > 0".into());
+    }
+
+    validate_metrics(&mut config.metrics)?;
+    validate_tracing(&mut config.tracing)?;
+    if config.key_cache.ttl_secs == 0 {
+        return Err("key_cache.ttl_secs must be > 0".into());
+    }
+    if config.key_cache.max_entries == 0 {
+        return Err("key_cache.max_entries must be > 0".into());
+    }
+    validate_keystore_outage(&mut config.keystore_outage)?;
+    if config.runtime.max_blocking_threads == 0 {
+        return Err("runtime.max_blocking_threads must be > 0".into());
     }
 
     if config.port == config.metrics_port {
@@ -115,7 +3086,11 @@ pub fn load_config(config_path: &str) -> Result<Config, Box<dyn std::error::Erro
     // Check for WebSocket port conflict (port + 1)
     let ws_port = config.port.checked_add(1).ok_or("Port overflow")?;
     if ws_port == config.metrics_port {
-        return Err(format!("Metrics port {} conflicts with WebSocket port (HTTP port + 1)", config.metrics_port).into());
+        return Err(format!(
+            "Metrics port {} conflicts with WebSocket port (HTTP port + 1)",
+            config.metrics_port
+        )
+        .into());
     }
 
     Ok(config)
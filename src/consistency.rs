@@ -0,0 +1,213 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+use axum::body::Body;
+use futures_util::future;
+use hyper::Request;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use metrics::{counter, gauge};
+use serde_json::{json, Value};
+use tokio::time::{sleep, timeout, Duration};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{Backend, Canary},
+    redact::redact,
+    signing::{apply_backend_headers, apply_signature, unix_now},
+    state::AppState,
+};
+
+/// How often a disabled check looks at the config again.
+const DISABLED_POLL_SECS: u64 = 1;
+
+/// Backends whose answer to each canary disagreed with the majority in the
+/// latest round.
+#[derive(Default)]
+pub struct ConsistencyState {
+    divergent: Mutex<BTreeMap<String, Vec<String>>>,
+}
+
+impl ConsistencyState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the result of `canary`; returns whether the set of divergent
+    /// backends changed.
+    pub fn record(&self, canary: &str, divergent: Vec<String>) -> bool {
+        let mut state = self.divergent.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .insert(canary.to_string(), divergent.clone())
+            .unwrap_or_default()
+            != divergent
+    }
+
+    /// Forget canaries no longer configured.
+    pub fn retain(&self, canaries: &[Canary]) {
+        let mut state = self.divergent.lock().unwrap_or_else(|e| e.into_inner());
+        state.retain(|name, _| canaries.iter().any(|c| &c.name == name));
+    }
+
+    /// Backends diverging on at least one canary.
+    pub fn divergent_backends(&self) -> BTreeSet<String> {
+        let state = self.divergent.lock().unwrap_or_else(|e| e.into_inner());
+        state.values().flatten().cloned().collect()
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, Vec<String>> {
+        self.divergent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// The part of a `result` that should agree across backends: `value` for
+/// results wrapped with a `context`, whose slot differs from node to node.
+pub fn comparable(mut result: Value) -> Value {
+    match result.as_object_mut() {
+        Some(object) if object.contains_key("context") && object.contains_key("value") => {
+            object.remove("value").unwrap_or_default()
+        }
+        _ => result,
+    }
+}
+
+/// Labels of the backends whose answer differs from the majority's. Without a
+/// strict majority (e.g. two backends that disagree) every backend counts as
+/// divergent, since none can be trusted over the others.
+pub fn divergent(answers: &[(String, Value)]) -> Vec<String> {
+    let mut groups: Vec<(&Value, usize)> = Vec::new();
+    for (_, answer) in answers {
+        match groups.iter_mut().find(|(value, _)| *value == answer) {
+            Some(group) => group.1 += 1,
+            None => groups.push((answer, 1)),
+        }
+    }
+    if groups.len() < 2 {
+        return Vec::new();
+    }
+    groups.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let majority = (groups[0].1 > groups[1].1).then_some(groups[0].0);
+    answers
+        .iter()
+        .filter(|(_, answer)| majority != Some(answer))
+        .map(|(label, _)| label.clone())
+        .collect()
+}
+
+/// Send `canary` to `backend` and return the comparable part of its result.
+pub async fn query_canary(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    backend: &Backend,
+    canary: &Canary,
+    timeout_after: Duration,
+) -> Result<Value, String> {
+    let body = serde_json::to_vec(&json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": canary.method,
+        "params": canary.params,
+    }))
+    .map_err(|e| e.to_string())?;
+
+    let mut req = Request::post(&backend.url)
+        .header("content-type", "application/json")
+        .body(Body::empty())
+        .map_err(|e| e.to_string())?;
+    apply_backend_headers(backend, req.headers_mut());
+    if let Some(signing) = &backend.signing {
+        apply_signature(signing, req.headers_mut(), &body, unix_now());
+    }
+    *req.body_mut() = Body::from(body);
+
+    let response = timeout(timeout_after, client.request(req))
+        .await
+        .map_err(|_| format!("timed out after {}s", timeout_after.as_secs()))?
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("returned status: {}", response.status()));
+    }
+    let bytes = http_body_util::BodyExt::collect(response.into_body())
+        .await
+        .map_err(|e| e.to_string())?
+        .to_bytes();
+    let mut json: Value = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    match json.get_mut("result") {
+        Some(result) => Ok(comparable(result.take())),
+        None => Err(format!("no result: {}", json["error"])),
+    }
+}
+
+/// Every `consistency.interval_secs`, send each canary to all backends in
+/// rotation and record which of them disagree with the rest.
+pub async fn consistency_loop(state: Arc<AppState>) {
+    loop {
+        let router_state = state.state.load_full();
+        let config = &router_state.consistency;
+        state.consistency.retain(&config.canaries);
+        if !config.enabled || config.canaries.is_empty() {
+            sleep(Duration::from_secs(DISABLED_POLL_SECS)).await;
+            continue;
+        }
+        let timeout_after = Duration::from_secs(config.timeout_secs);
+
+        for canary in &config.canaries {
+            let queries = router_state
+                .backends
+                .iter()
+                .filter(|b| b.in_rotation())
+                .map(|b| async {
+                    let answer = query_canary(
+                        &state.client_for(&b.config, false),
+                        &b.config,
+                        canary,
+                        timeout_after,
+                    )
+                    .await;
+                    (b.config.label.clone(), answer)
+                });
+            // Backends that fail to answer are left to the health checks
+            let mut answers = Vec::new();
+            for (label, answer) in future::join_all(queries).await {
+                match answer {
+                    Ok(answer) => answers.push((label, answer)),
+                    Err(e) => {
+                        debug!(
+                            "Canary '{}' on {} failed: {}",
+                            canary.name,
+                            label,
+                            redact(&e)
+                        );
+                        counter!("rpc_consistency_errors_total", "canary" => canary.name.clone(), "backend" => label)
+                            .increment(1);
+                    }
+                }
+            }
+
+            let diverging = divergent(&answers);
+            for (label, _) in &answers {
+                let diverges = diverging.contains(label);
+                gauge!("rpc_consistency_divergent", "canary" => canary.name.clone(), "backend" => label.clone())
+                    .set(if diverges { 1.0 } else { 0.0 });
+            }
+            gauge!("rpc_consistency_divergent_backends", "canary" => canary.name.clone())
+                .set(diverging.len() as f64);
+            if state.consistency.record(&canary.name, diverging.clone()) {
+                if diverging.is_empty() {
+                    info!("Backends agree on canary '{}' again", canary.name);
+                } else {
+                    warn!(
+                        "Backends diverge on canary '{}': {} disagree with the majority",
+                        canary.name,
+                        diverging.join(", ")
+                    );
+                }
+            }
+        }
+        sleep(Duration::from_secs(config.interval_secs)).await;
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, Client};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+use crate::config::{DeadLetterConfig, DeadLetterStoreKind};
+
+/// Redis stream holding dead letters.
+const STREAM_KEY: &str = "dead_letters:sendTransaction";
+
+/// A `sendTransaction` request the router could not deliver, with enough context
+/// to rebroadcast it by hand.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// Assigned by the store (`<unix ms>-<seq>`, like a Redis stream id)
+    #[serde(default)]
+    pub id: String,
+    pub captured_at: u64,
+    pub owner: String,
+    /// Backend the request was sent to, if one was available
+    pub backend: Option<String>,
+    /// Status the client received
+    pub status: u16,
+    pub error: String,
+    /// The JSON-RPC request body as forwarded, including the signed transaction
+    pub request: String,
+}
+
+#[async_trait]
+pub trait DeadLetterStore: Send + Sync {
+    async fn push(&self, letter: &DeadLetter) -> Result<(), String>;
+
+    /// Up to `limit` letters, most recent first.
+    async fn list(&self, limit: usize) -> Result<Vec<DeadLetter>, String>;
+
+    async fn get(&self, id: &str) -> Result<Option<DeadLetter>, String>;
+}
+
+/// Open the store selected by `config`.
+pub async fn open_store(
+    config: &DeadLetterConfig,
+    redis_url: &str,
+) -> Result<Box<dyn DeadLetterStore>, String> {
+    match config.store {
+        DeadLetterStoreKind::Redis => Ok(Box::new(
+            RedisDeadLetterStore::new(redis_url, config.max_entries).await?,
+        )),
+        DeadLetterStoreKind::File => Ok(Box::new(FileDeadLetterStore::new(&config.path))),
+    }
+}
+
+/// Dead letters in a Redis stream capped at roughly `max_entries`.
+pub struct RedisDeadLetterStore {
+    conn: ConnectionManager,
+    max_entries: u64,
+}
+
+impl RedisDeadLetterStore {
+    pub async fn new(redis_url: &str, max_entries: u64) -> Result<Self, String> {
+        let client = Client::open(redis_url).map_err(|e| e.to_string())?;
+        let conn = client
+            .get_connection_manager()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Self { conn, max_entries })
+    }
+}
+
+type StreamEntry = (String, HashMap<String, String>);
+
+fn from_stream_entry((id, fields): StreamEntry) -> Option<DeadLetter> {
+    let mut letter: DeadLetter = serde_json::from_str(fields.get("letter")?).ok()?;
+    letter.id = id;
+    Some(letter)
+}
+
+#[async_trait]
+impl DeadLetterStore for RedisDeadLetterStore {
+    async fn push(&self, letter: &DeadLetter) -> Result<(), String> {
+        let json = serde_json::to_string(letter).map_err(|e| e.to_string())?;
+        let mut conn = self.conn.clone();
+        redis::cmd("XADD")
+            .arg(STREAM_KEY)
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(self.max_entries)
+            .arg("*")
+            .arg("letter")
+            .arg(json)
+            .query_async::<String>(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn list(&self, limit: usize) -> Result<Vec<DeadLetter>, String> {
+        let mut conn = self.conn.clone();
+        let entries: Vec<StreamEntry> = redis::cmd("XREVRANGE")
+            .arg(STREAM_KEY)
+            .arg("+")
+            .arg("-")
+            .arg("COUNT")
+            .arg(limit)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(entries.into_iter().filter_map(from_stream_entry).collect())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<DeadLetter>, String> {
+        let mut conn = self.conn.clone();
+        let entries: Vec<StreamEntry> = redis::cmd("XRANGE")
+            .arg(STREAM_KEY)
+            .arg(id)
+            .arg(id)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(entries.into_iter().find_map(from_stream_entry))
+    }
+}
+
+/// Dead letters appended as JSON lines to a local file. Not capped.
+pub struct FileDeadLetterStore {
+    path: PathBuf,
+    /// Keeps appends whole and ids unique
+    write: Mutex<()>,
+    seq: AtomicU64,
+}
+
+impl FileDeadLetterStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            write: Mutex::new(()),
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    async fn read_all(&self) -> Result<Vec<DeadLetter>, String> {
+        let contents = match fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.to_string()),
+        };
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for FileDeadLetterStore {
+    async fn push(&self, letter: &DeadLetter) -> Result<(), String> {
+        let _write = self.write.lock().await;
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let letter = DeadLetter {
+            id: format!("{}-{}", now_ms, self.seq.fetch_add(1, Ordering::Relaxed)),
+            ..letter.clone()
+        };
+        let mut line = serde_json::to_vec(&letter).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| e.to_string())?;
+        file.write_all(&line).await.map_err(|e| e.to_string())?;
+        file.flush().await.map_err(|e| e.to_string())
+    }
+
+    async fn list(&self, limit: usize) -> Result<Vec<DeadLetter>, String> {
+        let mut letters = self.read_all().await?;
+        letters.reverse();
+        letters.truncate(limit);
+        Ok(letters)
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<DeadLetter>, String> {
+        Ok(self.read_all().await?.into_iter().find(|l| l.id == id))
+    }
+}
@@ -0,0 +1,196 @@
+use serde_json::{Map, Value};
+
+use crate::{
+    config::{Commitment, PreflightPolicy},
+    methods::{config_param, COMMITMENTS, ENCODINGS},
+};
+
+/// Per-key defaults injected into requests that omit them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestDefaults {
+    pub commitment: Option<String>,
+    pub encoding: Option<String>,
+}
+
+impl RequestDefaults {
+    /// Build from optional values, rejecting unknown commitments and encodings.
+    pub fn new(commitment: Option<String>, encoding: Option<String>) -> Result<Self, String> {
+        if let Some(c) = commitment.as_deref().filter(|c| !COMMITMENTS.contains(c)) {
+            return Err(format!("Invalid default commitment: {}", c));
+        }
+        if let Some(e) = encoding.as_deref().filter(|e| !ENCODINGS.contains(e)) {
+            return Err(format!("Invalid default encoding: {}", e));
+        }
+        Ok(Self {
+            commitment,
+            encoding,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commitment.is_none() && self.encoding.is_none()
+    }
+}
+
+/// Rewrite a JSON-RPC request (or batch) so every call that supports them gets
+/// the key's default `commitment` and `encoding` when the client left them out.
+/// Values the method does not accept (e.g. `processed` for `getTransaction`) are
+/// not injected. Returns `None` when nothing changed, so the original bytes are
+/// forwarded untouched.
+pub fn apply_defaults(body: &[u8], defaults: &RequestDefaults) -> Option<Vec<u8>> {
+    let mut value: Value = serde_json::from_slice(body).ok()?;
+    let changed = match &mut value {
+        Value::Array(batch) => batch.iter_mut().fold(false, |changed, request| {
+            apply_to_request(request, defaults) | changed
+        }),
+        request => apply_to_request(request, defaults),
+    };
+    if !changed {
+        return None;
+    }
+    serde_json::to_vec(&value).ok()
+}
+
+fn apply_to_request(request: &mut Value, defaults: &RequestDefaults) -> bool {
+    let Some(param) = request
+        .get("method")
+        .and_then(Value::as_str)
+        .and_then(config_param)
+    else {
+        return false;
+    };
+
+    let mut fields = Map::new();
+    if let Some(c) = defaults.commitment.as_deref() {
+        if param.commitments.contains(&c) {
+            fields.insert("commitment".to_string(), c.into());
+        }
+    }
+    if let Some(e) = defaults.encoding.as_deref() {
+        if param.encodings.contains(&e) {
+            fields.insert("encoding".to_string(), e.into());
+        }
+    }
+    if fields.is_empty() {
+        return false;
+    }
+
+    let Some(config) = config_object(request, param.index) else {
+        return false;
+    };
+
+    let mut changed = false;
+    for (key, value) in fields {
+        if !config.contains_key(&key) {
+            config.insert(key, value);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// The config object of `request` at params position `index`, created when the
+/// client left it out (or sent `null`). `None` if the params don't allow one.
+fn config_object(request: &mut Value, index: usize) -> Option<&mut Map<String, Value>> {
+    let object = request.as_object_mut()?;
+    if index == 0 && object.get("params").is_none_or(Value::is_null) {
+        object.insert("params".to_string(), Value::Array(Vec::new()));
+    }
+    let Some(Value::Array(params)) = object.get_mut("params") else {
+        return None;
+    };
+
+    // The config object is optional: append it when every earlier param is present
+    if params.len() == index {
+        params.push(Value::Object(Map::new()));
+    }
+    match params.get_mut(index) {
+        Some(config @ Value::Null) => {
+            *config = Value::Object(Map::new());
+            config.as_object_mut()
+        }
+        Some(Value::Object(config)) => Some(config),
+        _ => None,
+    }
+}
+
+/// Rewrite a `sendTransaction` request (or each one in a batch) to follow
+/// `policy`. Returns `None` when nothing changed.
+pub fn apply_preflight_policy(body: &[u8], policy: &PreflightPolicy) -> Option<Vec<u8>> {
+    let mut value: Value = serde_json::from_slice(body).ok()?;
+    let changed = match &mut value {
+        Value::Array(batch) => batch.iter_mut().fold(false, |changed, request| {
+            apply_policy_to_request(request, policy) | changed
+        }),
+        request => apply_policy_to_request(request, policy),
+    };
+    if !changed {
+        return None;
+    }
+    serde_json::to_vec(&value).ok()
+}
+
+fn apply_policy_to_request(request: &mut Value, policy: &PreflightPolicy) -> bool {
+    if request.get("method").and_then(Value::as_str) != Some("sendTransaction") {
+        return false;
+    }
+    let Some(config) = config_object(request, 1) else {
+        return false;
+    };
+
+    let mut changed = false;
+    if policy.require_preflight && config.get("skipPreflight") == Some(&Value::Bool(true)) {
+        config.insert("skipPreflight".to_string(), false.into());
+        changed = true;
+    }
+    // Nodes simulate at `finalized` when the client leaves it out
+    if let Some(min) = policy.min_preflight_commitment {
+        let current = config.get("preflightCommitment").map(Value::as_str);
+        if current.is_some_and(|c| c.and_then(Commitment::parse).is_none_or(|c| c < min)) {
+            config.insert("preflightCommitment".to_string(), min.as_str().into());
+            changed = true;
+        }
+    }
+    if let Some(retries) = policy.max_retries {
+        if config.get("maxRetries").and_then(Value::as_u64) != Some(retries) {
+            config.insert("maxRetries".to_string(), retries.into());
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// JSON-RPC error code for a block or transaction containing versioned
+/// transactions when the request did not set `maxSupportedTransactionVersion`.
+pub const UNSUPPORTED_TRANSACTION_VERSION: i64 = -32015;
+
+/// Methods that fail on versioned transactions unless the client opts in with
+/// `maxSupportedTransactionVersion`.
+pub fn takes_transaction_version(method: &str) -> bool {
+    matches!(method, "getBlock" | "getTransaction")
+}
+
+/// Rewrite a single `getBlock`/`getTransaction` request that omits
+/// `maxSupportedTransactionVersion` so it asks for `version`. Returns `None` for
+/// batches, other methods and requests that already set it.
+pub fn with_transaction_version(body: &[u8], version: u8) -> Option<Vec<u8>> {
+    let mut request: Value = serde_json::from_slice(body).ok()?;
+    let method = request.get("method")?.as_str()?;
+    if !takes_transaction_version(method) {
+        return None;
+    }
+    let config = config_object(&mut request, 1)?;
+    if config.contains_key("maxSupportedTransactionVersion") {
+        return None;
+    }
+    config.insert("maxSupportedTransactionVersion".to_string(), version.into());
+    serde_json::to_vec(&request).ok()
+}
+
+/// Whether a JSON-RPC response body is the unsupported-transaction-version error.
+pub fn is_unsupported_version_error(body: &[u8]) -> bool {
+    serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|response| response.get("error")?.get("code")?.as_i64())
+        == Some(UNSUPPORTED_TRANSACTION_VERSION)
+}
@@ -0,0 +1,58 @@
+use serde_json::{json, Value};
+
+use crate::{
+    methods::{MethodClass, KNOWN_METHODS},
+    state::RouterState,
+};
+
+pub const OPENRPC_VERSION: &str = "1.2.6";
+
+/// OpenRPC-style description of the methods this router serves, generated from the
+/// live routing state. Router-specific details use `x-` extension fields.
+pub fn discovery_document(state: &RouterState, max_body_bytes: usize) -> Value {
+    let ws_available = state.backends.iter().any(|b| b.config.ws_url.is_some());
+
+    let methods: Vec<Value> = KNOWN_METHODS
+        .iter()
+        .filter_map(|info| {
+            let backends: Vec<&str> = state
+                .backends
+                .iter()
+                .filter(|b| b.accepts(Some(info.name)))
+                .filter(|b| info.class != MethodClass::Subscription || b.config.ws_url.is_some())
+                .map(|b| b.config.label.as_str())
+                .collect();
+            if backends.is_empty() {
+                return None;
+            }
+
+            Some(json!({
+                "name": info.name,
+                "params": [],
+                "result": { "name": "result", "schema": {} },
+                "x-routing-class": info.class,
+                "x-cost": info.cost,
+                "x-transport": if info.class == MethodClass::Subscription { "websocket" } else { "http" },
+                "x-backends": backends,
+                "x-pinned-backend": state.method_routes.get(info.name),
+                "x-browser-keys-allowed": !state.browser_keys.blocked_methods.iter().any(|m| m == info.name),
+            }))
+        })
+        .collect();
+
+    json!({
+        "openrpc": OPENRPC_VERSION,
+        "info": {
+            "title": "sol-rpc-router",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Solana JSON-RPC methods available through this router",
+        },
+        "methods": methods,
+        "x-limits": {
+            "max_body_bytes": max_body_bytes,
+            "upstream_timeout_secs": state.proxy_timeout_secs,
+            "browser_per_origin_ip_rate_limit": state.browser_keys.per_origin_ip_rate_limit,
+            "websocket": ws_available,
+        },
+    })
+}
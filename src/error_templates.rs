@@ -0,0 +1,147 @@
+use axum::{
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::Value;
+
+use crate::config::ErrorTemplatesConfig;
+
+/// Errors the router answers itself whose body `[error_templates]` can replace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouterError {
+    Unauthorized,
+    RateLimited,
+    NoBackend,
+    /// The key store could not be reached and the `keystore_outage` policy
+    /// did not accept the key
+    KeystoreUnavailable,
+}
+
+impl RouterError {
+    pub fn status(self) -> StatusCode {
+        match self {
+            RouterError::Unauthorized => StatusCode::UNAUTHORIZED,
+            RouterError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            RouterError::NoBackend | RouterError::KeystoreUnavailable => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+        }
+    }
+
+    /// Plain-text body without a template.
+    pub fn message(self) -> &'static str {
+        match self {
+            RouterError::Unauthorized => "Unauthorized",
+            RouterError::RateLimited => "Rate limit exceeded",
+            RouterError::NoBackend => "No healthy backends available",
+            RouterError::KeystoreUnavailable => "API key validation is temporarily unavailable",
+        }
+    }
+
+    /// Placeholders a template for this error may use.
+    pub fn placeholders(self) -> &'static [&'static str] {
+        match self {
+            RouterError::Unauthorized
+            | RouterError::RateLimited
+            | RouterError::KeystoreUnavailable => &["status", "message"],
+            RouterError::NoBackend => &[
+                "status",
+                "message",
+                "retry_after_ms",
+                "queue_depth",
+                "correlation_id",
+            ],
+        }
+    }
+}
+
+/// Fill `{name}` placeholders in the strings of `template`. A string that is
+/// exactly one placeholder takes the value with its JSON type, so `"{status}"`
+/// becomes a number; elsewhere values are inserted as text.
+pub fn render(template: &Value, vars: &[(&str, Value)]) -> Value {
+    match template {
+        Value::String(text) => {
+            let whole = text.strip_prefix('{').and_then(|t| t.strip_suffix('}'));
+            if let Some((_, value)) = vars.iter().find(|(name, _)| whole == Some(*name)) {
+                return value.clone();
+            }
+            let mut out = text.clone();
+            for (name, value) in vars {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                out = out.replace(&format!("{{{}}}", name), &value);
+            }
+            Value::String(out)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|i| render(i, vars)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| (key.clone(), render(value, vars)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Names of the `{name}` placeholders used anywhere in `template`.
+pub fn placeholders(template: &Value) -> Vec<String> {
+    let mut names = Vec::new();
+    match template {
+        Value::String(text) => {
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find('{') {
+                rest = &rest[start + 1..];
+                let Some(end) = rest.find('}') else { break };
+                let name = &rest[..end];
+                if !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+                    names.push(name.to_string());
+                    rest = &rest[end + 1..];
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|i| names.extend(placeholders(i))),
+        Value::Object(map) => map.values().for_each(|v| names.extend(placeholders(v))),
+        _ => {}
+    }
+    names
+}
+
+/// Response header with the requests left in the key's rate limit bucket.
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+
+/// `Retry-After` of requests refused while the key store is unreachable.
+pub const KEYSTORE_RETRY_AFTER_SECS: u64 = 5;
+
+/// The response for `error`: its template rendered with `status` and `message`
+/// when one is configured, otherwise the message as plain text. Rate limited
+/// and key store outage responses say when to retry; buckets refill within a
+/// second.
+pub fn error_response(templates: &ErrorTemplatesConfig, error: RouterError) -> Response {
+    let mut response = match templates.get(error) {
+        Some(template) => {
+            let vars = [
+                ("status", Value::from(error.status().as_u16())),
+                ("message", Value::from(error.message())),
+            ];
+            (error.status(), Json(render(template, &vars))).into_response()
+        }
+        None => (error.status(), error.message()).into_response(),
+    };
+    match error {
+        RouterError::RateLimited => {
+            let headers = response.headers_mut();
+            headers.insert(RETRY_AFTER, HeaderValue::from_static("1"));
+            headers.insert(RATE_LIMIT_REMAINING_HEADER, HeaderValue::from_static("0"));
+        }
+        RouterError::KeystoreUnavailable => {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(KEYSTORE_RETRY_AFTER_SECS));
+        }
+        RouterError::Unauthorized | RouterError::NoBackend => {}
+    }
+    response
+}
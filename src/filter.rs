@@ -0,0 +1,112 @@
+use serde_json::{Map, Value};
+
+/// Request header listing the response fields a client wants, as comma-separated
+/// JSON pointers (e.g. `/result/blockhash,/result/parentSlot`).
+pub const FIELDS_HEADER: &str = "x-response-fields";
+
+/// Most pointers accepted in one header.
+const MAX_FIELDS: usize = 32;
+
+/// Parse the `x-response-fields` header into JSON pointers.
+pub fn parse_fields(header: &str) -> Result<Vec<String>, String> {
+    let fields: Vec<String> = header
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .map(str::to_string)
+        .collect();
+    if fields.is_empty() {
+        return Err(format!("{} is empty", FIELDS_HEADER));
+    }
+    if fields.len() > MAX_FIELDS {
+        return Err(format!(
+            "{} lists more than {} fields",
+            FIELDS_HEADER, MAX_FIELDS
+        ));
+    }
+    if let Some(field) = fields.iter().find(|f| !f.starts_with('/')) {
+        return Err(format!(
+            "{}: '{}' is not a JSON pointer (must start with '/')",
+            FIELDS_HEADER, field
+        ));
+    }
+    Ok(fields)
+}
+
+/// Strip a JSON-RPC response (or batch) down to the fields in `pointers`.
+/// `jsonrpc` and `id` are always kept, and error responses are returned whole.
+/// Pointers that match nothing are skipped. Returns `None` if the body is not JSON.
+pub fn filter_response(body: &[u8], pointers: &[String]) -> Option<Vec<u8>> {
+    let value: Value = serde_json::from_slice(body).ok()?;
+    let filtered = match value {
+        Value::Array(batch) => Value::Array(
+            batch
+                .into_iter()
+                .map(|response| filter_one(response, pointers))
+                .collect(),
+        ),
+        response => filter_one(response, pointers),
+    };
+    serde_json::to_vec(&filtered).ok()
+}
+
+fn filter_one(response: Value, pointers: &[String]) -> Value {
+    if response.get("error").is_some() || !response.is_object() {
+        return response;
+    }
+
+    let mut filtered = Value::Object(Map::new());
+    for key in ["jsonrpc", "id"] {
+        if let Some(value) = response.get(key) {
+            filtered[key] = value.clone();
+        }
+    }
+    for pointer in pointers {
+        if response.pointer(pointer).is_some() {
+            copy_at(&mut filtered, &response, pointer);
+        }
+    }
+    filtered
+}
+
+/// Copy the value at `pointer` (which must exist) from `source` into `target`,
+/// creating the objects and arrays on the way with the shape they have in
+/// `source`. Array positions before an index are filled with `null`.
+fn copy_at(target: &mut Value, source: &Value, pointer: &str) {
+    let tokens: Vec<String> = pointer
+        .split('/')
+        .skip(1)
+        .map(|t| t.replace("~1", "/").replace("~0", "~"))
+        .collect();
+
+    let mut target = target;
+    let mut source = source;
+    for token in &tokens {
+        match source {
+            Value::Array(items) => {
+                let Ok(index) = token.parse::<usize>() else {
+                    return;
+                };
+                if !target.is_array() {
+                    *target = Value::Array(Vec::new());
+                }
+                let array = target.as_array_mut().expect("just set");
+                if array.len() <= index {
+                    array.resize(index + 1, Value::Null);
+                }
+                target = &mut array[index];
+                source = &items[index];
+            }
+            Value::Object(fields) => {
+                if !target.is_object() {
+                    *target = Value::Object(Map::new());
+                }
+                let object = target.as_object_mut().expect("just set");
+                target = object.entry(token.clone()).or_insert(Value::Null);
+                source = &fields[token];
+            }
+            _ => return,
+        }
+    }
+    *target = source.clone();
+}
@@ -0,0 +1,188 @@
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use futures_util::future;
+use hyper::Request;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use metrics::{counter, gauge};
+use serde_json::{json, Value};
+use tokio::time::{sleep, timeout, Duration};
+use tracing::{debug, error, info};
+
+use crate::{
+    config::Backend,
+    redact::redact,
+    signing::{apply_backend_headers, apply_signature, unix_now},
+    state::AppState,
+};
+
+/// How often a disabled check looks at the config again.
+const DISABLED_POLL_SECS: u64 = 1;
+
+/// A backend found on another cluster: the URL that was checked and the genesis
+/// hash it reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub url: String,
+    pub genesis_hash: String,
+}
+
+/// Outcome of a backend's genesis hash checks, kept per label across config
+/// reloads. A backend is quarantined while the URL it was caught on is still
+/// its URL, so fixing the URL in a reload lifts the quarantine.
+#[derive(Debug, Default)]
+pub struct Identity {
+    mismatch: Mutex<Option<Mismatch>>,
+}
+
+impl Identity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The genesis hash a backend at `url` reported when it failed the check.
+    pub fn mismatch(&self, url: &str) -> Option<String> {
+        let mismatch = self.mismatch.lock().unwrap_or_else(|e| e.into_inner());
+        mismatch
+            .as_ref()
+            .filter(|m| m.url == url)
+            .map(|m| m.genesis_hash.clone())
+    }
+
+    /// Whether the backend at `url` is held out of rotation.
+    pub fn quarantines(&self, url: &str) -> bool {
+        self.mismatch(url).is_some()
+    }
+
+    /// Record that the backend at `url` reported `genesis_hash`; returns whether
+    /// that changed its quarantine.
+    pub fn record(&self, url: &str, genesis_hash: &str, expected: &str) -> bool {
+        let next = (genesis_hash != expected).then(|| Mismatch {
+            url: url.to_string(),
+            genesis_hash: genesis_hash.to_string(),
+        });
+        let mut mismatch = self.mismatch.lock().unwrap_or_else(|e| e.into_inner());
+        let changed = mismatch.as_ref().map(|m| &m.url) != next.as_ref().map(|m| &m.url);
+        *mismatch = next;
+        changed
+    }
+
+    /// Lift the quarantine, e.g. once the check is disabled.
+    pub fn clear(&self) {
+        *self.mismatch.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// `getGenesisHash` of `backend`.
+pub async fn fetch_genesis_hash(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    backend: &Backend,
+    timeout_after: Duration,
+) -> Result<String, String> {
+    let body = serde_json::to_vec(&json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getGenesisHash",
+    }))
+    .map_err(|e| e.to_string())?;
+
+    let mut req = Request::post(&backend.url)
+        .header("content-type", "application/json")
+        .body(Body::empty())
+        .map_err(|e| e.to_string())?;
+    apply_backend_headers(backend, req.headers_mut());
+    if let Some(signing) = &backend.signing {
+        apply_signature(signing, req.headers_mut(), &body, unix_now());
+    }
+    *req.body_mut() = Body::from(body);
+
+    let response = timeout(timeout_after, client.request(req))
+        .await
+        .map_err(|_| format!("timed out after {}s", timeout_after.as_secs()))?
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("returned status: {}", response.status()));
+    }
+    let bytes = http_body_util::BodyExt::collect(response.into_body())
+        .await
+        .map_err(|e| e.to_string())?
+        .to_bytes();
+    let json: Value = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    match json["result"].as_str() {
+        Some(hash) => Ok(hash.to_string()),
+        None => Err(format!("no result: {}", json["error"])),
+    }
+}
+
+/// Ask every backend for its genesis hash and quarantine those on another
+/// cluster than `genesis_check` expects. Backends that do not answer keep
+/// their previous verdict; their health checks deal with them.
+pub async fn verify_genesis(state: &AppState) {
+    let router_state = state.state.load_full();
+    let config = &router_state.genesis_check;
+    let Some(expected) = config.expected().filter(|_| config.enabled) else {
+        return;
+    };
+    let timeout_after = Duration::from_secs(config.timeout_secs);
+
+    let checks = router_state.backends.iter().map(|b| async move {
+        let answer = fetch_genesis_hash(
+            &state.client_for(&b.config, false),
+            &b.config,
+            timeout_after,
+        )
+        .await;
+        (b, answer)
+    });
+    for (backend, answer) in future::join_all(checks).await {
+        let label = &backend.config.label;
+        let genesis_hash = match answer {
+            Ok(hash) => hash,
+            Err(e) => {
+                debug!("Genesis hash check of {} failed: {}", label, redact(&e));
+                counter!("rpc_genesis_check_errors_total", "backend" => label.clone()).increment(1);
+                continue;
+            }
+        };
+        let url = &backend.config.url;
+        let changed = backend.identity.record(url, &genesis_hash, expected);
+        let mismatched = genesis_hash != expected;
+        gauge!("rpc_backend_genesis_mismatch", "backend" => label.clone()).set(if mismatched {
+            1.0
+        } else {
+            0.0
+        });
+        match (changed, mismatched) {
+            (true, true) => error!(
+                "Backend {} is on another cluster (genesis hash {}, expected {}); quarantined",
+                label, genesis_hash, expected
+            ),
+            (true, false) => info!("Backend {} genesis hash verified again", label),
+            _ => {}
+        }
+    }
+}
+
+/// Check genesis hashes every `genesis_check.interval_secs` while enabled, and
+/// right away when a reload enables the check. The first round is left to
+/// startup, which runs [`verify_genesis`] before serving.
+pub async fn genesis_check_loop(state: Arc<AppState>) {
+    let mut check_now = false;
+    loop {
+        let router_state = state.state.load_full();
+        let config = &router_state.genesis_check;
+        if !config.enabled {
+            for backend in &router_state.backends {
+                backend.identity.clear();
+            }
+            check_now = true;
+            sleep(Duration::from_secs(DISABLED_POLL_SECS)).await;
+            continue;
+        }
+        if !std::mem::take(&mut check_now) {
+            sleep(Duration::from_secs(config.interval_secs)).await;
+        }
+        verify_genesis(&state).await;
+    }
+}
@@ -1,26 +1,91 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{atomic::Ordering, Arc},
+};
 
 use axum::{
-    body::{to_bytes, Body},
+    body::{to_bytes, Body, HttpBody},
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        ConnectInfo, Query, State,
+        ConnectInfo, MatchedPath, Path, Query, State,
     },
-    http::{Request, StatusCode, Uri},
+    http::{Extensions, HeaderMap, Request, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
+use bytes::{Bytes, BytesMut};
 use futures_util::{SinkExt, StreamExt};
+use http_body_util::{BodyExt, Limited};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client, ResponseFuture};
 use metrics::{counter, gauge, histogram};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use tokio::time::{timeout, Duration};
-use tokio_tungstenite::{connect_async, tungstenite::Message as TungsteniteMessage};
+use tokio::time::{timeout, timeout_at, Duration, Instant};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Message as TungsteniteMessage},
+};
 use tracing::{error, info, warn};
 
-use crate::state::AppState;
+use crate::{
+    archive::{is_slot_method, requested_slot},
+    audit::{AuditRecord, LogPrivacy},
+    backpressure::{QueueDepth, RetryHint},
+    batch::{error_entry, is_plain_json, join, rewrap, split, unwrap_single},
+    blockhash::SendTransaction,
+    broadcast::{accepted_response, is_accepted},
+    browser::{check_browser_request, BrowserRejection},
+    cache::{
+        cache_policy, min_context_slot_not_reached, with_min_context_slot, CacheLookup,
+        CachePolicy, Flight, ReadKey,
+    },
+    circuit::CircuitState,
+    coalesce::{follow, Coalesce, Leader, Shared},
+    compression::{decode, ContentEncoding, DecodeError},
+    concurrency::SHED_RETRY_AFTER,
+    config::{
+        Backend, HedgingConfig, LatencyHeatmapConfig, OffloadConfig, RetryConfig, RoutingMode,
+        Subsystem,
+    },
+    dead_letter::DeadLetter,
+    defaults::{
+        apply_defaults, apply_preflight_policy, is_unsupported_version_error,
+        takes_transaction_version, with_transaction_version,
+    },
+    discovery::discovery_document,
+    error_templates::{error_response, RouterError, RATE_LIMIT_REMAINING_HEADER},
+    filter::{filter_response, parse_fields, FIELDS_HEADER},
+    health::{record_passive, HealthLevel},
+    heatmap::LatencyHeatmap,
+    integrity::{buffer, verify, Buffered},
+    journal::{JournalEntry, JournalStore},
+    key_usage::{usage_method, KeyUsageMeter},
+    keystore::{lookup_key_list, validate_key_list, KeyInfo, KeyKind, KeyStoreError},
+    log_format, method_acl,
+    methods::{is_known_method, method_info, MethodClass},
+    net::{canonical_addr, canonical_ip},
+    network_stats::{self, NETWORK_STATS_METHODS},
+    offload::{object_key, presign_get, put_request, upload, OFFLOADED_HEADER, OFFLOAD_HEADER},
+    poll_bridge::{self, BridgeError},
+    redact::{key_fingerprint, redact, redact_url},
+    signing::{apply_backend_headers, apply_signature, unix_now},
+    sniff::{sniff_method, PrefixedBody, Sniff, SNIFF_LIMIT},
+    spending::request_cost,
+    state::{AppState, RouterState, RuntimeBackend},
+    timing::{Phase, RequestTiming},
+    trace::{unix_nanos, OpenSpan, SpanContext, SpanData, TRACEPARENT},
+    traffic::ResponseMeter,
+    ttl_tuning,
+    usage::{Outcome, WindowStats},
+    ws::{
+        ClientQueue, ConnectionActivity, FirehoseThrottler, LocalSubscriptions, HEARTBEAT_PAYLOAD,
+    },
+};
 
-const MAX_BODY_SIZE: usize = 10 * 1024 * 1024; // 10 MB
+pub const MAX_BODY_SIZE: usize = 10 * 1024 * 1024; // 10 MB
 
 #[derive(Clone)]
 pub struct RpcMethod(pub String);
@@ -31,9 +96,83 @@ pub struct SelectedBackend(pub String);
 #[derive(Clone)]
 pub struct ClientOwner(pub String);
 
+/// The API key that served a request, set on its response for per-key usage
+/// accounting (see `key_usage`).
+#[derive(Clone)]
+pub struct ClientKey(pub String);
+
+/// Marks the response to a request from a configured probe (see `probes`).
+#[derive(Clone, Copy)]
+pub struct ProbeRequest;
+
+/// Marks a synthetic request sent through the router by `POST
+/// /admin/test-request`. Its response always carries the timing breakdown.
+#[derive(Clone, Debug, Default)]
+pub struct TestRequest {
+    /// Send the request to this backend whether or not it is in rotation,
+    /// without caching, hedging, broadcast, archive routing or failover
+    pub backend: Option<String>,
+}
+
+/// Tracing target of the line logged for each probe request.
+pub const PROBE_TARGET: &str = "probe";
+
+/// Tracing target of the access log line of each request in JSON log format.
+pub const ACCESS_TARGET: &str = "access";
+
+/// Header carrying the id of a request, taken from the client or generated,
+/// and passed on to backends and back to the client.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied request id that is kept rather than replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The id of a request, as sent in [`REQUEST_ID_HEADER`].
+#[derive(Clone)]
+pub struct RequestId(pub String);
+
 #[derive(Deserialize)]
 struct MethodProbe<'a> {
-    method: Option<&'a str>,
+    /// Borrowed unless the method has escapes
+    #[serde(borrow)]
+    method: Option<std::borrow::Cow<'a, str>>,
+}
+
+/// Buffer the rest of a request body after `read`, with at most one more
+/// allocation. A single frame is passed through as-is; otherwise the buffer is
+/// sized from `Content-Length` so it does not regrow while frames arrive.
+async fn buffer_body(
+    read: Bytes,
+    mut body: Limited<Body>,
+    content_length: Option<usize>,
+) -> Result<Bytes, axum::BoxError> {
+    let mut first: Option<Bytes> = Some(read).filter(|read| !read.is_empty());
+    let mut buf: Option<BytesMut> = None;
+
+    while let Some(frame) = body.frame().await {
+        let Ok(chunk) = frame?.into_data() else {
+            continue; // trailers
+        };
+        match (&mut buf, first.take()) {
+            (Some(buf), _) => buf.extend_from_slice(&chunk),
+            (None, None) => first = Some(chunk),
+            (None, Some(prev)) => {
+                let capacity = content_length
+                    .unwrap_or(0)
+                    .min(MAX_BODY_SIZE)
+                    .max(prev.len() + chunk.len());
+                let mut b = BytesMut::with_capacity(capacity);
+                b.extend_from_slice(&prev);
+                b.extend_from_slice(&chunk);
+                buf = Some(b);
+            }
+        }
+    }
+
+    Ok(match buf {
+        Some(buf) => buf.freeze(),
+        None => first.unwrap_or_default(),
+    })
 }
 
 #[derive(Deserialize)]
@@ -42,10 +181,334 @@ pub struct Params {
     pub api_key: Option<String>,
 }
 
+/// Client IP from the connection info, or `0.0.0.0` when unavailable (e.g. in tests).
+fn client_ip(extensions: &Extensions) -> IpAddr {
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| canonical_ip(addr.ip()))
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+/// The router's own error for `error`, in the body `[error_templates]` gives it.
+fn router_error(state: &AppState, error: RouterError) -> Response {
+    error_response(&state.state.load().error_templates, error)
+}
+
+/// The response to a failed key validation other than a rate limit: `503`
+/// with a retry hint while the key store is unreachable, `500` otherwise.
+fn key_error_response(state: &AppState, context: &str, error: &KeyStoreError) -> Response {
+    match error {
+        KeyStoreError::Unavailable(e) => {
+            warn!("{}Key store unavailable: {}", context, redact(e));
+            router_error(state, RouterError::KeystoreUnavailable)
+        }
+        _ => {
+            error!(
+                "{}Key validation error: {}",
+                context,
+                redact(&error.to_string())
+            );
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
+        }
+    }
+}
+
+/// `503` for a WebSocket or bridged subscription without a backend, as
+/// `error_templates.no_backend` with a retry hint when configured.
+fn no_ws_backend_response(state: &AppState) -> Response {
+    let router_state = state.state.load();
+    let hint = RetryHint::new(
+        Duration::from_secs(router_state.health_check_config.interval_secs),
+        QueueDepth::Empty,
+    );
+    error!(
+        "No healthy WebSocket backends available (correlation_id={})",
+        hint.correlation_id
+    );
+    let message = "No healthy WebSocket backends available";
+    match &router_state.error_templates.no_backend {
+        Some(template) => hint.templated_response(template, message),
+        None => (StatusCode::SERVICE_UNAVAILABLE, message).into_response(),
+    }
+}
+
+fn browser_rejection_response(
+    state: &AppState,
+    rejection: BrowserRejection,
+    owner: &str,
+) -> Response {
+    match rejection {
+        BrowserRejection::OriginNotAllowed => {
+            info!("Browser key origin rejected (owner={})", owner);
+            (StatusCode::FORBIDDEN, "Origin not allowed").into_response()
+        }
+        BrowserRejection::MethodNotAllowed(method) => {
+            info!("Browser key method {} rejected (owner={})", method, owner);
+            (
+                StatusCode::FORBIDDEN,
+                format!("Method {} is not allowed for browser keys", method),
+            )
+                .into_response()
+        }
+        BrowserRejection::RateLimited => {
+            warn!(
+                "Browser key per-origin rate limit exceeded (owner={})",
+                owner
+            );
+            router_error(state, RouterError::RateLimited)
+        }
+    }
+}
+
+/// Decode `Content-Encoding: gzip`/`zstd` request bodies before the method is
+/// extracted, so everything downstream sees plain JSON. The decoded body may be
+/// at most `proxy.max_decompressed_bytes`.
+pub async fn decompress_request(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(header) = req.headers().get("content-encoding") else {
+        return next.run(req).await;
+    };
+    let encoding = match header
+        .to_str()
+        .map_err(|e| e.to_string())
+        .and_then(ContentEncoding::parse)
+    {
+        Ok(Some(encoding)) => encoding,
+        Ok(None) => return next.run(req).await,
+        Err(e) => return (StatusCode::UNSUPPORTED_MEDIA_TYPE, e).into_response(),
+    };
+
+    let (mut parts, body) = req.into_parts();
+    let compressed = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+        }
+    };
+    let limit = state.state.load().max_decompressed_bytes;
+    let decoded = tokio::task::spawn_blocking(move || decode(encoding, &compressed, limit)).await;
+    let outcome = match &decoded {
+        Ok(Ok(_)) => "ok",
+        Ok(Err(DecodeError::TooLarge)) => "too_large",
+        Ok(Err(DecodeError::Invalid(_))) | Err(_) => "invalid",
+    };
+    counter!("rpc_compressed_requests_total", "encoding" => encoding.as_str(), "outcome" => outcome)
+        .increment(1);
+    let decoded = match decoded {
+        Ok(Ok(decoded)) => decoded,
+        Ok(Err(DecodeError::TooLarge)) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Decompressed request body too large",
+            )
+                .into_response();
+        }
+        Ok(Err(DecodeError::Invalid(e))) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid {} request body: {}", encoding.as_str(), e),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!("Request body decoding task failed: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response();
+        }
+    };
+
+    parts.headers.remove("content-encoding");
+    parts.headers.insert(
+        "content-length",
+        axum::http::HeaderValue::from(decoded.len()),
+    );
+    let req = Request::from_parts(parts, Body::from(decoded));
+    next.run(req).await
+}
+
+/// With `proxy.unwrap_single_batches`, turn a batch of one request into the
+/// plain request before the method is extracted, so routing, caching and
+/// coalescing treat it like any other call, then wrap the response back into
+/// a batch.
+pub async fn unwrap_single_batch(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if !state.state.load().unwrap_single_batches {
+        return next.run(req).await;
+    }
+    let (mut parts, body) = req.into_parts();
+    let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+        }
+    };
+    let Some(single) = unwrap_single(&body_bytes) else {
+        let req = Request::from_parts(parts, Body::from(body_bytes));
+        return next.run(req).await;
+    };
+    counter!("rpc_batch_unwrapped_total").increment(1);
+    // The response is wrapped as it streams, so ask the backend not to compress it
+    parts.headers.remove("accept-encoding");
+    parts.headers.insert(
+        "content-length",
+        axum::http::HeaderValue::from(single.len()),
+    );
+    let req = Request::from_parts(parts, Body::from(single));
+    rewrap(next.run(req).await)
+}
+
+/// With `proxy.split_batches`, send each request of a batch through the rest
+/// of the stack on its own, so it is authenticated, routed, cached and metered
+/// by its method, then answer with the responses in the batch's order.
+/// Requests without a JSON answer get a JSON-RPC error in their place; when
+/// none has one (e.g. an unknown key) the first response is returned as is.
+pub async fn split_batch(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let (concurrency, limit) = {
+        let router_state = state.state.load();
+        if !router_state.split_batches {
+            return next.run(req).await;
+        }
+        (
+            router_state.split_batch_concurrency,
+            router_state.max_buffered_bytes,
+        )
+    };
+    let (mut parts, body) = req.into_parts();
+    let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+        }
+    };
+    let Some(items) = split(&body_bytes) else {
+        let req = Request::from_parts(parts, Body::from(body_bytes));
+        return next.run(req).await;
+    };
+    counter!("rpc_batch_split_total").increment(1);
+    counter!("rpc_batch_split_items_total").increment(items.len() as u64);
+
+    // Answers are parsed to be reassembled, so ask for them uncompressed
+    parts.headers.remove("accept-encoding");
+    parts.headers.remove("content-length");
+    let requests: Vec<Request<Body>> = items
+        .iter()
+        .map(|item| Request::from_parts(parts.clone(), Body::from(item.body.clone())))
+        .collect();
+    let responses: Vec<Response> = futures_util::stream::iter(requests)
+        .map(move |req| next.clone().run(req))
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    if !responses.iter().any(|r| is_plain_json(r.headers())) {
+        return responses.into_iter().next().unwrap_or_default();
+    }
+    let mut entries = Vec::with_capacity(items.len());
+    for (item, response) in items.iter().zip(responses) {
+        let status = response.status();
+        let json = is_plain_json(response.headers());
+        let body = match buffer(response.into_body(), limit).await {
+            Ok(Buffered::Complete(body)) => body,
+            Ok(Buffered::TooLarge(_)) => {
+                warn!("Split batch answer over {} bytes dropped", limit);
+                entries.push(error_entry(
+                    &item.id,
+                    StatusCode::BAD_GATEWAY,
+                    b"Response too large",
+                ));
+                continue;
+            }
+            Err(_) => {
+                entries.push(error_entry(&item.id, StatusCode::BAD_GATEWAY, b""));
+                continue;
+            }
+        };
+        if json && !body.trim_ascii().is_empty() {
+            entries.push(body);
+        } else if !json {
+            entries.push(error_entry(&item.id, status, &body));
+        }
+        // An empty JSON answer is a notification's; it has no entry
+    }
+    ([("content-type", "application/json")], join(&entries)).into_response()
+}
+
 pub async fn extract_rpc_method(mut req: Request<Body>, next: Next) -> Response {
-    // Read body, extract "method" field, then reconstruct the request
+    // Read only as much of the body as it takes to find "method", then pass the
+    // body on: as is if that was all of it, else the bytes read followed by the
+    // rest as it arrives, so large requests reach the backend without buffering.
     let (parts, body) = req.into_parts();
-    let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+    let content_length = parts
+        .headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let mut body = Limited::new(body, MAX_BODY_SIZE);
+    let mut read = Bytes::new();
+
+    let method = loop {
+        match sniff_method(&read) {
+            Sniff::Method(method) => break Some(method.to_string()),
+            Sniff::NoMethod => break None,
+            Sniff::Incomplete if read.len() < SNIFF_LIMIT => {}
+            // Escapes, or the method comes late: parse the whole body
+            Sniff::Incomplete | Sniff::Unsure => {
+                return with_parsed_method(parts, read, body, content_length, next).await;
+            }
+        }
+        match body.frame().await {
+            // Ended mid-object: not JSON
+            None => break None,
+            Some(Ok(frame)) => {
+                let Ok(chunk) = frame.into_data() else {
+                    continue; // trailers
+                };
+                read = if read.is_empty() {
+                    chunk
+                } else {
+                    let mut joined = BytesMut::with_capacity(read.len() + chunk.len());
+                    joined.extend_from_slice(&read);
+                    joined.extend_from_slice(&chunk);
+                    joined.freeze()
+                };
+            }
+            Some(Err(_)) => {
+                // If body read fails, pass empty body downstream
+                return next.run(Request::from_parts(parts, Body::empty())).await;
+            }
+        }
+    };
+
+    let body = if body.is_end_stream() {
+        Body::from(read)
+    } else {
+        Body::new(PrefixedBody::new(read, body))
+    };
+    req = Request::from_parts(parts, body);
+    if let Some(method) = method {
+        req.extensions_mut().insert(RpcMethod(method));
+    }
+    next.run(req).await
+}
+
+/// Buffer the whole body and take its method from a full parse.
+async fn with_parsed_method(
+    parts: axum::http::request::Parts,
+    read: Bytes,
+    body: Limited<Body>,
+    content_length: Option<usize>,
+    next: Next,
+) -> Response {
+    let body_bytes = match buffer_body(read, body, content_length).await {
         Ok(bytes) => bytes,
         Err(_) => {
             // If body read fails, pass empty body downstream
@@ -56,35 +519,87 @@ pub async fn extract_rpc_method(mut req: Request<Body>, next: Next) -> Response
     // Optimize: Partial Zero-Copy Deserialization
     // Instead of parsing the full JSON (which allocates for params),
     // we use a struct that only captures 'method' and borrows the string from the buffer.
-    if let Ok(probe) = serde_json::from_slice::<MethodProbe>(&body_bytes) {
-        if let Some(method) = probe.method {
-            req = Request::from_parts(parts, Body::from(body_bytes.clone()));
-            req.extensions_mut().insert(RpcMethod(method.to_string()));
-            return next.run(req).await;
-        }
+    let method = serde_json::from_slice::<MethodProbe>(&body_bytes)
+        .ok()
+        .and_then(|probe| probe.method.map(|method| method.into_owned()));
+    let mut req = Request::from_parts(parts, Body::from(body_bytes));
+    if let Some(method) = method {
+        req.extensions_mut().insert(RpcMethod(method));
     }
-
-    // If no method found, reconstruct request with original body
-    req = Request::from_parts(parts, Body::from(body_bytes));
     next.run(req).await
 }
 
+/// Give each request an id: the client's `X-Request-ID` if it sent a usable
+/// one, else a fresh one. The header is forwarded to backends with the request
+/// and returned on the response; batches split later share their id.
+pub async fn assign_request_id(mut req: Request<Body>, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| {
+            (1..=MAX_REQUEST_ID_LEN).contains(&id.len()) && id.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| hex::encode(rand::random::<[u8; 16]>()));
+    let value = axum::http::HeaderValue::from_str(&id).expect("visible ASCII");
+    req.headers_mut().insert(REQUEST_ID_HEADER, value.clone());
+    req.extensions_mut().insert(RequestId(id));
+
+    let mut response = next.run(req).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    response
+}
+
+/// The route a request matched, e.g. `/admin/keys/:key`, so path parameters
+/// such as API keys stay out of logs. The raw path when no route matched.
+pub fn route_path<B>(req: &Request<B>) -> String {
+    match req.extensions().get::<MatchedPath>() {
+        Some(route) => route.as_str().to_string(),
+        None => req.uri().path().to_string(),
+    }
+}
+
 pub async fn log_requests(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     req: Request<Body>,
     next: Next,
 ) -> Response {
+    let addr = canonical_addr(addr);
     let method = req.method().clone();
-    let path = req.uri().path().to_string();
+    let path = route_path(&req);
     let rpc_method = req.extensions().get::<RpcMethod>().cloned();
+    let request_id = req.extensions().get::<RequestId>().cloned();
 
     let start = std::time::Instant::now();
     let response = next.run(req).await;
     let duration = start.elapsed();
 
+    // Keys with privacy "none" leave no access log lines
+    if response.extensions().get::<LogPrivacy>() == Some(&LogPrivacy::None) {
+        return response;
+    }
+
     // Extract backend from response extensions (set by proxy handler)
     let backend = response.extensions().get::<SelectedBackend>().cloned();
 
+    if log_format::is_json() {
+        info!(
+            target: ACCESS_TARGET,
+            http_method = %method,
+            path,
+            client_ip = %addr.ip(),
+            owner = response.extensions().get::<ClientOwner>().map(|o| o.0.as_str()),
+            rpc_method = rpc_method.as_ref().map(|m| m.0.as_str()),
+            backend = backend.as_ref().map(|b| b.0.as_str()),
+            status = response.status().as_u16(),
+            duration_ms = duration.as_secs_f64() * 1000.0,
+            request_id = request_id.as_ref().map(|id| id.0.as_str()),
+            "request"
+        );
+        return response;
+    }
+
     match (rpc_method, backend) {
         (Some(RpcMethod(m)), Some(SelectedBackend(b))) => info!(
             "{} {} {} {:?} rpc_method={} backend={}",
@@ -103,6 +618,58 @@ pub async fn log_requests(
     response
 }
 
+/// Strip successful responses down to the fields listed in `x-response-fields`.
+/// The header is consumed here and never forwarded upstream. Responses over
+/// `proxy.max_buffered_bytes` are streamed through unfiltered.
+pub async fn filter_response_fields(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(header) = req.headers_mut().remove(FIELDS_HEADER) else {
+        return next.run(req).await;
+    };
+    let fields = match header
+        .to_str()
+        .map_err(|e| e.to_string())
+        .and_then(parse_fields)
+    {
+        Ok(fields) => fields,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    // The body has to be parsed, so ask the backend not to compress it
+    req.headers_mut().remove("accept-encoding");
+
+    let response = next.run(req).await;
+    if response.status() != StatusCode::OK || response.headers().contains_key(OFFLOADED_HEADER) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let limit = state.state.load().max_buffered_bytes;
+    let body = match buffer(body, limit).await {
+        Ok(Buffered::Complete(bytes)) => bytes,
+        Ok(Buffered::TooLarge(body)) => {
+            counter!("rpc_response_filter_skipped_total").increment(1);
+            return Response::from_parts(parts, body);
+        }
+        Err(_) => {
+            info!("Backend response failed mid-body");
+            return (StatusCode::BAD_GATEWAY, "Proxy error: response truncated").into_response();
+        }
+    };
+    match filter_response(&body, &fields) {
+        Some(filtered) => {
+            counter!("rpc_response_filter_bytes_saved_total")
+                .increment(body.len().saturating_sub(filtered.len()) as u64);
+            // Let hyper recompute the length of the filtered body
+            parts.headers.remove("content-length");
+            Response::from_parts(parts, Body::from(filtered))
+        }
+        None => Response::from_parts(parts, Body::from(body)),
+    }
+}
+
 pub async fn track_metrics(req: Request<Body>, next: Next) -> Response {
     let start = std::time::Instant::now();
     let method = req.method().to_string();
@@ -119,176 +686,2597 @@ pub async fn track_metrics(req: Request<Body>, next: Next) -> Response {
     let duration = start.elapsed().as_secs_f64();
     let status = response.status().as_u16().to_string();
 
-    let backend = response
-        .extensions()
-        .get::<SelectedBackend>()
-        .map(|b| b.0.clone())
-        .unwrap_or_else(|| "none".to_string());
+    let backend = response
+        .extensions()
+        .get::<SelectedBackend>()
+        .map(|b| b.0.clone())
+        .unwrap_or_else(|| "none".to_string());
+
+    // Probe traffic is kept out of the per-customer series
+    let owner = if response.extensions().get::<ProbeRequest>().is_some() {
+        "probe".to_string()
+    } else {
+        response
+            .extensions()
+            .get::<ClientOwner>()
+            .map(|o| o.0.clone())
+            .unwrap_or_else(|| "none".to_string())
+    };
+
+    histogram!("rpc_request_duration_seconds", "rpc_method" => rpc_method.clone(), "backend" => backend.clone(), "owner" => owner.clone()).record(duration);
+    counter!("rpc_requests_total", "method" => method, "status" => status, "rpc_method" => rpc_method, "backend" => backend, "owner" => owner).increment(1);
+
+    response
+}
+
+/// Open a span for each request when `tracing` is enabled, as a child of the
+/// client's `traceparent` if it sent one. The request's `traceparent` is
+/// replaced with the span's, so backends join the trace on every attempt.
+/// Keys with privacy "none" leave no spans.
+pub async fn trace_requests(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(tracer) = state.tracer.clone() else {
+        return next.run(req).await;
+    };
+    let parent = req
+        .headers()
+        .get(TRACEPARENT)
+        .and_then(|v| v.to_str().ok())
+        .and_then(SpanContext::from_traceparent);
+    let context = tracer.start(parent.as_ref());
+    let traceparent = axum::http::HeaderValue::from_str(&context.traceparent()).expect("hex");
+    req.headers_mut().insert(TRACEPARENT, traceparent);
+    if !context.sampled {
+        return next.run(req).await;
+    }
+
+    let start_nanos = unix_nanos();
+    let rpc_method = req.extensions().get::<RpcMethod>().map(|m| m.0.clone());
+    let name = rpc_method
+        .clone()
+        .unwrap_or_else(|| req.method().to_string());
+    let mut attributes = vec![("rpc.system", "jsonrpc".to_string())];
+    if let Some(method) = rpc_method {
+        attributes.push(("rpc.method", method));
+    }
+    if let Some(RequestId(id)) = req.extensions().get::<RequestId>() {
+        attributes.push(("request_id", id.clone()));
+    }
+
+    let response = next.run(req).await;
+
+    if response.extensions().get::<LogPrivacy>() == Some(&LogPrivacy::None) {
+        return response;
+    }
+    if let Some(SelectedBackend(backend)) = response.extensions().get::<SelectedBackend>() {
+        attributes.push(("backend", backend.clone()));
+    }
+    let owner = if response.extensions().get::<ProbeRequest>().is_some() {
+        Some("probe".to_string())
+    } else {
+        response
+            .extensions()
+            .get::<ClientOwner>()
+            .map(|o| o.0.clone())
+    };
+    if let Some(owner) = owner {
+        attributes.push(("owner", owner));
+    }
+    let span = SpanData {
+        name,
+        context,
+        parent_span_id: parent.map(|p| p.span_id),
+        start_nanos,
+        end_nanos: start_nanos,
+        attributes,
+        status: response.status().as_u16(),
+    };
+    // The span ends once the body has been sent
+    hold_until_sent(response, OpenSpan::new(tracer, span))
+}
+
+/// Record the outcome of each authenticated request in the per-key error-rate
+/// windows. Probe requests are not metered; each gets a line of its own instead.
+pub async fn track_usage(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let rpc_method = req.extensions().get::<RpcMethod>().cloned();
+    let valid_request = rpc_method.is_some();
+    let request_bytes = req.body().size_hint().exact().unwrap_or(0);
+    let start = std::time::Instant::now();
+    let mut response = next.run(req).await;
+
+    if response.extensions().get::<ProbeRequest>().is_some() {
+        let status = response.status().as_u16();
+        info!(
+            target: PROBE_TARGET,
+            owner = response.extensions().get::<ClientOwner>().map_or("-", |o| o.0.as_str()),
+            rpc_method = rpc_method.as_ref().map_or("-", |m| m.0.as_str()),
+            backend = response.extensions().get::<SelectedBackend>().map_or("-", |b| b.0.as_str()),
+            status,
+            latency_ms = start.elapsed().as_secs_f64() * 1000.0,
+            "probe request"
+        );
+        counter!("rpc_probe_requests_total", "status" => status.to_string()).increment(1);
+        return response;
+    }
+
+    // Hourly per-method totals for `/admin/traffic`; response bytes are counted
+    // as the body streams out
+    let traffic = &state.state.load().traffic_report;
+    if traffic.enabled {
+        let now = unix_now();
+        let method = rpc_method.as_ref().map(|m| m.0.as_str());
+        let retention_secs = traffic.retention_days * 86_400;
+        state
+            .traffic
+            .record_request(method, request_bytes, now, retention_secs);
+        let mut meter = ResponseMeter::new(state.traffic.clone(), method.map(str::to_string), now);
+        response = response.map(|body| {
+            Body::from_stream(body.into_data_stream().map(move |chunk| {
+                if let Ok(bytes) = &chunk {
+                    meter.add(bytes.len());
+                }
+                chunk
+            }))
+        });
+    }
+
+    // Per-key, per-method daily counts and response bytes (`key_usage`)
+    let exported = response
+        .extensions()
+        .get::<LogPrivacy>()
+        .is_none_or(|p| p.allows_metadata());
+    if let Some(ClientKey(key)) = response
+        .extensions()
+        .get::<ClientKey>()
+        .filter(|_| state.key_usage.is_some() && exported)
+    {
+        let method = usage_method(rpc_method.as_ref().map(|m| m.0.as_str()));
+        let mut meter = KeyUsageMeter::new(
+            state.key_usage_buffer.clone(),
+            key.clone(),
+            method,
+            unix_now(),
+        );
+        response = response.map(|body| {
+            Body::from_stream(body.into_data_stream().map(move |chunk| {
+                if let Ok(bytes) = &chunk {
+                    meter.add(bytes.len());
+                }
+                chunk
+            }))
+        });
+    }
+
+    if let Some(ClientOwner(owner)) = response.extensions().get::<ClientOwner>() {
+        let now = unix_now();
+        let outcome = Outcome::classify(response.status().as_u16(), valid_request);
+        state.usage.record(owner, outcome, now);
+        if state.usage_ledger.is_some() && exported {
+            state
+                .usage_buffer
+                .record(owner, outcome != Outcome::Success, now);
+        }
+    }
+
+    response
+}
+
+#[derive(Serialize)]
+pub struct UsageResponse {
+    pub owner: String,
+    pub windows: BTreeMap<&'static str, WindowStats>,
+}
+
+/// `GET /v1/usage?api-key=`: the caller's own request outcomes over rolling windows.
+pub async fn usage_endpoint(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<Params>,
+) -> Response {
+    let Some(api_key) = params.api_key else {
+        return router_error(&state, RouterError::Unauthorized);
+    };
+    let owner = match validate_key_list(state.keystore.as_ref(), &api_key).await {
+        Ok(Some((_, info))) => info.owner,
+        Ok(None) => return router_error(&state, RouterError::Unauthorized),
+        Err(KeyStoreError::RateLimited) => return router_error(&state, RouterError::RateLimited),
+        Err(e) => return key_error_response(&state, "", &e),
+    };
+
+    let windows = state
+        .usage
+        .all_windows(&owner, unix_now())
+        .into_iter()
+        .collect();
+    Json(UsageResponse { owner, windows }).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct PollParams {
+    #[serde(rename = "api-key")]
+    pub api_key: Option<String>,
+    /// How long to wait for a notification, capped at `poll_bridge.max_wait_secs`
+    pub wait_ms: Option<u64>,
+}
+
+/// API key and owner of a `/v1/poll` caller, or the response refusing them.
+async fn poll_caller(
+    state: &AppState,
+    api_key: Option<String>,
+) -> Result<(String, String), Response> {
+    if !state.state.load().poll_bridge.enabled {
+        return Err((StatusCode::NOT_FOUND, "Polling bridge is disabled").into_response());
+    }
+    let Some(api_key) = api_key else {
+        return Err(router_error(state, RouterError::Unauthorized));
+    };
+    match state.keystore.validate_key(&api_key).await {
+        Ok(Some(info)) if info.kind == KeyKind::Browser => Err((
+            StatusCode::FORBIDDEN,
+            "Browser keys cannot use the polling bridge",
+        )
+            .into_response()),
+        Ok(Some(info)) => Ok((api_key, info.owner)),
+        Ok(None) => Err(router_error(state, RouterError::Unauthorized)),
+        Err(KeyStoreError::RateLimited) => Err(router_error(state, RouterError::RateLimited)),
+        Err(e) => Err(key_error_response(state, "", &e)),
+    }
+}
+
+/// `POST /v1/poll?api-key=` with a JSON-RPC subscribe request: open a bridged
+/// subscription and return its id.
+pub async fn poll_subscribe(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<PollParams>,
+    body: Bytes,
+) -> Response {
+    let (api_key, owner) = match poll_caller(&state, params.api_key).await {
+        Ok(caller) => caller,
+        Err(resp) => return resp,
+    };
+    let Ok(request) = serde_json::from_slice::<serde_json::Value>(&body) else {
+        return (StatusCode::BAD_REQUEST, "Invalid JSON").into_response();
+    };
+    match poll_bridge::subscribe(&state, &api_key, &owner, request).await {
+        Ok(id) => Json(serde_json::json!({ "subscription_id": id })).into_response(),
+        Err(BridgeError::InvalidRequest(e)) => (StatusCode::BAD_REQUEST, e).into_response(),
+        Err(BridgeError::LimitReached) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            "Bridged subscription limit reached for this key",
+        )
+            .into_response(),
+        Err(BridgeError::NoBackend) => no_ws_backend_response(&state),
+        Err(BridgeError::Upstream(e)) => {
+            warn!("Bridged subscription for {} failed: {}", owner, e);
+            (
+                StatusCode::BAD_GATEWAY,
+                "Backend did not confirm the subscription",
+            )
+                .into_response()
+        }
+        Err(BridgeError::Rejected(response)) => {
+            (StatusCode::BAD_REQUEST, Json(response)).into_response()
+        }
+    }
+}
+
+/// `GET /v1/poll/<id>?api-key=&wait_ms=`: notifications received since the
+/// last poll, waiting for one if there are none yet.
+pub async fn poll_endpoint(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<PollParams>,
+) -> Response {
+    let (api_key, _) = match poll_caller(&state, params.api_key).await {
+        Ok(caller) => caller,
+        Err(resp) => return resp,
+    };
+    let Some(bridged) = state.poll_bridge.get(&id, &api_key) else {
+        return (StatusCode::NOT_FOUND, "Unknown subscription").into_response();
+    };
+    let max_wait_ms = state.state.load().poll_bridge.max_wait_secs * 1000;
+    let wait = params.wait_ms.unwrap_or(max_wait_ms).min(max_wait_ms);
+    let batch = bridged.poll(Duration::from_millis(wait)).await;
+    if batch.closed {
+        state.poll_bridge.remove(&id, &api_key);
+    }
+    Json(batch).into_response()
+}
+
+/// `DELETE /v1/poll/<id>?api-key=`: close a bridged subscription.
+pub async fn poll_unsubscribe(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<PollParams>,
+) -> Response {
+    let (api_key, _) = match poll_caller(&state, params.api_key).await {
+        Ok(caller) => caller,
+        Err(resp) => return resp,
+    };
+    if state.poll_bridge.remove(&id, &api_key) {
+        gauge!("poll_bridge_subscriptions").set(state.poll_bridge.len() as f64);
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        (StatusCode::NOT_FOUND, "Unknown subscription").into_response()
+    }
+}
+
+pub async fn proxy(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<Params>,
+    mut req: Request<Body>,
+) -> impl IntoResponse {
+    let mut timing = RequestTiming::start(Instant::now());
+    let api_keys = match params.api_key {
+        Some(k) => k,
+        None => {
+            info!("No API key provided");
+            return router_error(&state, RouterError::Unauthorized);
+        }
+    };
+
+    // `api-key` may list fallback keys; the first usable one serves the request.
+    // Probes are not rate limited.
+    let probe = state
+        .state
+        .load()
+        .probes
+        .is_probe(client_ip(req.extensions()), &api_keys);
+    let validated = if probe {
+        lookup_key_list(state.keystore.as_ref(), &api_keys).await
+    } else {
+        validate_key_list(state.keystore.as_ref(), &api_keys).await
+    };
+    let (api_key, key_info) = match validated {
+        Ok(Some(validated)) => validated,
+        Ok(None) => {
+            info!(
+                "Invalid API key presented (key={})",
+                key_fingerprint(&api_keys)
+            );
+            return router_error(&state, RouterError::Unauthorized);
+        }
+        Err(e) => {
+            if e == KeyStoreError::RateLimited {
+                warn!("API key rate limited (key={})", key_fingerprint(&api_keys));
+                return router_error(&state, RouterError::RateLimited);
+            } else {
+                return key_error_response(&state, "", &e);
+            }
+        }
+    };
+
+    if key_info.kind == KeyKind::Browser {
+        let router_state = state.state.load();
+        let rpc_method = req.extensions().get::<RpcMethod>().map(|m| m.0.as_str());
+        if let Err(rejection) = check_browser_request(
+            &key_info,
+            &router_state.browser_keys,
+            &state.origin_limiter,
+            req.headers(),
+            client_ip(req.extensions()),
+            rpc_method,
+        ) {
+            let mut resp = browser_rejection_response(&state, rejection, &key_info.owner);
+            resp.extensions_mut().insert(ClientOwner(key_info.owner));
+            resp.extensions_mut().insert(key_info.privacy);
+            if probe {
+                resp.extensions_mut().insert(ProbeRequest);
+            }
+            return resp;
+        }
+    }
+
+    // Keys at their spend cap are throttled rather than cut off; requests
+    // that succeed are charged by cost units
+    let mut charge = None;
+    if let Some(spending) = state
+        .spending
+        .as_ref()
+        .filter(|_| !probe && key_info.spend_cap.is_some())
+    {
+        if !spending.admit(&api_key, &key_info, Instant::now().into_std()) {
+            warn!(
+                "API key throttled at its spend cap (key={})",
+                key_fingerprint(&api_key)
+            );
+            counter!("rpc_spend_throttled_total", "owner" => key_info.owner.clone()).increment(1);
+            let mut resp = router_error(&state, RouterError::RateLimited);
+            resp.extensions_mut().insert(ClientOwner(key_info.owner));
+            resp.extensions_mut().insert(key_info.privacy);
+            return resp;
+        }
+        let units = match req.extensions().get::<RpcMethod>() {
+            Some(method) => request_cost(Some(&method.0), &[]),
+            // Batches are charged for each of their requests
+            None => {
+                let (parts, body) = req.into_parts();
+                let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+                    Ok(bytes) => bytes,
+                    Err(_) => {
+                        return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large")
+                            .into_response();
+                    }
+                };
+                let units = request_cost(None, &body_bytes);
+                req = Request::from_parts(parts, Body::from(body_bytes));
+                units
+            }
+        };
+        charge = Some((spending.clone(), units));
+    }
+
+    // Past the concurrency limits requests are shed rather than queued, so
+    // latency stays flat under overload. Probes only count toward the global
+    // limit. The permit is held until the response body has been sent.
+    let key_limit = if probe { 0 } else { key_info.max_in_flight };
+    let global_limit = state.state.load().max_in_flight;
+    let acquired =
+        state
+            .concurrency
+            .try_acquire(global_limit, &api_key, &key_info.owner, key_limit);
+    let in_flight = match acquired {
+        Ok(permit) => permit,
+        Err(saturated) => {
+            let hint = RetryHint::new(SHED_RETRY_AFTER, QueueDepth::Empty);
+            warn!(
+                "Shed request at the {} concurrency limit (key={}, correlation_id={})",
+                saturated.as_str(),
+                key_fingerprint(&api_key),
+                hint.correlation_id
+            );
+            counter!("rpc_requests_shed_total", "limit" => saturated.as_str()).increment(1);
+            let body = to_bytes(req.into_body(), MAX_BODY_SIZE)
+                .await
+                .unwrap_or_default();
+            let mut resp = hint.response_with_status(
+                StatusCode::TOO_MANY_REQUESTS,
+                saturated.message(),
+                &body,
+            );
+            resp.extensions_mut().insert(ClientOwner(key_info.owner));
+            resp.extensions_mut().insert(key_info.privacy);
+            return resp;
+        }
+    };
+
+    timing.mark(Phase::Auth);
+    req.extensions_mut().insert(ClientKey(api_key.clone()));
+    // Only keys that asked for it, and admin test requests, get the breakdown
+    let timed = key_info.timing || req.extensions().get::<TestRequest>().is_some();
+    let remaining = key_info.rate_limit_remaining;
+    let annotate = |timing: RequestTiming, resp: &mut Response| {
+        if probe {
+            resp.extensions_mut().insert(ProbeRequest);
+        }
+        resp.extensions_mut().insert(ClientKey(api_key.clone()));
+        if let Some((spending, units)) = &charge {
+            if resp.status().is_success() {
+                spending.charge(&api_key, *units);
+            }
+        }
+        if let Some(remaining) = remaining {
+            let headers = resp.headers_mut();
+            headers.insert(RATE_LIMIT_REMAINING_HEADER, remaining.into());
+        }
+        if timed {
+            timing.annotate(resp.headers_mut(), Instant::now());
+        }
+    };
+
+    // Outer middleware (access log, usage export) read the key's privacy level
+    // from the response
+    let privacy = key_info.privacy;
+    if !key_info.audit || !privacy.allows_metadata() {
+        let mut resp = hold_until_sent(forward(state, key_info, req, &mut timing).await, in_flight);
+        resp.extensions_mut().insert(privacy);
+        annotate(timing, &mut resp);
+        return resp;
+    }
+
+    // Audited keys get one structured log line per request
+    let (parts, body) = req.into_parts();
+    let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+        }
+    };
+    let rpc_method = parts.extensions.get::<RpcMethod>().map(|m| m.0.as_str());
+    let record =
+        AuditRecord::new(&api_key, &key_info.owner, rpc_method, &body_bytes).with_privacy(privacy);
+    let req = Request::from_parts(parts, Body::from(body_bytes));
+    let started = Instant::now();
+    let mut resp = hold_until_sent(forward(state, key_info, req, &mut timing).await, in_flight);
+    resp.extensions_mut().insert(privacy);
+    let backend = resp.extensions().get::<SelectedBackend>();
+    record.log(
+        resp.status(),
+        backend.map(|b| b.0.as_str()),
+        started.elapsed(),
+    );
+    annotate(timing, &mut resp);
+    resp
+}
+
+/// Keep `guard` (e.g. a semaphore permit) alive until `resp`'s body has been
+/// sent.
+fn hold_until_sent<T: Send + 'static>(resp: Response, guard: T) -> Response {
+    resp.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            let _guard = &guard;
+            chunk
+        }))
+    })
+}
+
+/// Serve an authenticated request: key defaults, cache, backend selection and
+/// the upstream call. Phases after authentication are marked in `timing`.
+async fn forward(
+    state: Arc<AppState>,
+    key_info: KeyInfo,
+    mut req: Request<Body>,
+    timing: &mut RequestTiming,
+) -> Response {
+    // Keys limited to some methods are answered here for the others, batches
+    // included. Unrestricted keys keep the passthrough path.
+    if key_info.restricts_methods() {
+        let (parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        if let Some(denied) = method_acl::check(&body_bytes, &key_info) {
+            let method = Some(denied.method)
+                .filter(|m| is_known_method(m))
+                .unwrap_or_else(|| "other".to_string());
+            counter!("rpc_key_method_denied_total", "owner" => key_info.owner.clone(), "rpc_method" => method)
+                .increment(1);
+            let mut resp = ([("content-type", "application/json")], denied.body).into_response();
+            resp.extensions_mut().insert(ClientOwner(key_info.owner));
+            return resp;
+        }
+        req = Request::from_parts(parts, Body::from(body_bytes));
+    }
+
+    // Inject the key's default commitment/encoding. Only keys with defaults pay
+    // for parsing the body; everyone else keeps the passthrough path.
+    if !key_info.defaults.is_empty() {
+        let (mut parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        let body_bytes = match apply_defaults(&body_bytes, &key_info.defaults) {
+            Some(rewritten) => {
+                counter!("rpc_key_defaults_applied_total", "owner" => key_info.owner.clone())
+                    .increment(1);
+                // Let hyper recompute the length of the rewritten body
+                parts.headers.remove("content-length");
+                Bytes::from(rewritten)
+            }
+            None => body_bytes,
+        };
+        req = Request::from_parts(parts, Body::from(body_bytes));
+    }
+
+    // Store owner in request extensions for metrics middleware
+    req.extensions_mut()
+        .insert(ClientOwner(key_info.owner.clone()));
+
+    // One state snapshot for the whole request: selection, URI parts, signing and timeout
+    let router_state = state.state.load_full();
+    // Admin test requests may name the backend that must answer them
+    let pinned = req
+        .extensions()
+        .get::<TestRequest>()
+        .and_then(|test| test.backend.clone());
+
+    // Enforce the key tier's preflight policy on sendTransaction
+    let preflight_policy = key_info
+        .tier
+        .as_ref()
+        .and_then(|tier| router_state.preflight_policies.get(tier))
+        .filter(|policy| !policy.is_empty());
+    if let Some(policy) = preflight_policy.filter(|_| {
+        req.extensions()
+            .get::<RpcMethod>()
+            .is_some_and(|m| m.0 == "sendTransaction")
+    }) {
+        let (mut parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        let body_bytes = match apply_preflight_policy(&body_bytes, policy) {
+            Some(rewritten) => {
+                counter!("rpc_preflight_policy_applied_total", "owner" => key_info.owner.clone())
+                    .increment(1);
+                parts.headers.remove("content-length");
+                Bytes::from(rewritten)
+            }
+            None => body_bytes,
+        };
+        req = Request::from_parts(parts, Body::from(body_bytes));
+    }
+
+    // Answer immutable and slow-changing methods, and those given a TTL in
+    // `cache.method_ttl_secs`, from the built-in cache
+    // With stale serving on, `cache.stale_methods` are remembered too
+    let stale_methods: &[String] = if router_state.cache.serve_stale_secs > 0 {
+        &router_state.cache.stale_methods
+    } else {
+        &[]
+    };
+    let method_ttl_secs = &router_state.cache.method_ttl_secs;
+    let network_stats = &router_state.cache.network_stats;
+    // Request bodies of tuned methods, kept to revalidate their hits
+    let mut tuned_body = None;
+    let cacheable = pinned.is_none()
+        && req.extensions().get::<RpcMethod>().is_some_and(|m| {
+            cache_policy(&m.0).is_some()
+                || method_ttl_secs.contains_key(&m.0)
+                || stale_methods.contains(&m.0)
+                || (network_stats.enabled && NETWORK_STATS_METHODS.contains(&m.0.as_str()))
+        });
+    let mut cache_lookup = if cacheable
+        && router_state.cache.enabled
+        && !state.is_disabled(&router_state, Subsystem::Cache)
+    {
+        let (parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        let mut lookup =
+            CacheLookup::from_request_configured(&body_bytes, method_ttl_secs, stale_methods)
+                .map(|lookup| lookup.with_commitment_ttl(&router_state.cache.commitment_ttl_slots));
+        // Network stats are cached unless `method_ttl_secs` gives them a TTL
+        if network_stats.enabled
+            && lookup
+                .as_ref()
+                .is_none_or(|l| l.policy == CachePolicy::OutageOnly)
+        {
+            if let Some(network) = network_stats::lookup(&body_bytes, network_stats) {
+                lookup = Some(network);
+            }
+        }
+        if let Some(lookup) = lookup
+            .as_mut()
+            .filter(|l| l.policy == CachePolicy::Configured)
+        {
+            if let Some(ttl) = state
+                .ttl_tuner
+                .choose(&lookup.method, &router_state.cache.ttl_tuning)
+            {
+                lookup.ttl = ttl;
+                lookup.tuned = true;
+                tuned_body = Some(body_bytes.clone());
+            }
+        }
+        req = Request::from_parts(parts, Body::from(body_bytes));
+        lookup
+    } else {
+        None
+    };
+    // Of concurrent misses for the same result, only the first goes upstream.
+    // The others wait for it (up to the proxy timeout) and are answered from the
+    // cache, or go upstream themselves if it failed.
+    let mut flight = None;
+    if let Some(lookup) = &cache_lookup {
+        let mut cached = state.response_cache.get_with_ttl(lookup).await;
+        if cached.is_none() && lookup.policy != CachePolicy::OutageOnly {
+            match state.response_cache.begin(lookup) {
+                Flight::Leader(guard) => flight = Some(guard),
+                Flight::Follower(mut done) => {
+                    let wait = Duration::from_secs(router_state.proxy_timeout_secs);
+                    timing.mark(Phase::Routing);
+                    let _ = timeout(wait, done.changed()).await;
+                    timing.mark(Phase::Queue);
+                    cached = state.response_cache.get_with_ttl(lookup).await;
+                    if cached.is_some() {
+                        counter!("rpc_cache_coalesced_total", "rpc_method" => lookup.method.clone())
+                            .increment(1);
+                    }
+                }
+            }
+        }
+        if let Some((result, ttl)) = cached {
+            counter!("rpc_cache_hits_total", "rpc_method" => lookup.method.clone()).increment(1);
+            if let Some(request) = tuned_body {
+                state.ttl_tuner.record_hit(&lookup.method, ttl);
+                let percent = router_state.cache.ttl_tuning.revalidate_percent.min(100);
+                if rand::thread_rng().gen_ratio(percent, 100) {
+                    let state = state.clone();
+                    let lookup = lookup.clone();
+                    let cached = result.clone();
+                    tokio::spawn(async move {
+                        ttl_tuning::revalidate(&state, &lookup, request, cached, ttl).await;
+                    });
+                }
+            }
+            let mut resp = (
+                [("content-type", "application/json"), ("x-cache", "HIT")],
+                lookup.response(&result),
+            )
+                .into_response();
+            resp.extensions_mut()
+                .insert(SelectedBackend("cache".to_string()));
+            if let Some(owner) = req.extensions().get::<ClientOwner>().cloned() {
+                resp.extensions_mut().insert(owner);
+            }
+            return resp;
+        }
+    }
+    // An expired account read asks for a newer slot than the result it had;
+    // a backend with nothing newer lets the cached result be kept
+    let revalidate = flight.is_some()
+        && cache_lookup
+            .as_ref()
+            .is_some_and(|l| router_state.cache.revalidate_methods.contains(&l.method));
+    if let Some(lookup) = cache_lookup.as_mut().filter(|_| revalidate) {
+        if let Some((result, slot)) = state.response_cache.get_with_slot(lookup).await {
+            let (mut parts, body) = req.into_parts();
+            let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large")
+                        .into_response();
+                }
+            };
+            let body_bytes = match with_min_context_slot(&body_bytes, slot) {
+                Some(rewritten) => {
+                    parts.headers.remove("content-length");
+                    lookup.revalidating = Some((result, slot));
+                    Bytes::from(rewritten)
+                }
+                None => body_bytes,
+            };
+            req = Request::from_parts(parts, Body::from(body_bytes));
+        }
+    }
+
+    // Transactions are journaled before they are forwarded, and their outcome
+    // before it is acknowledged
+    let journaled = state.journal.clone().filter(|_| {
+        req.extensions()
+            .get::<RpcMethod>()
+            .is_some_and(|m| m.0 == "sendTransaction")
+    });
+    if let Some(journal) = journaled {
+        let (parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        let tx = SendTransaction::from_request(&body_bytes);
+        let key = parts
+            .extensions
+            .get::<ClientKey>()
+            .map(|key| key_fingerprint(&key.0))
+            .unwrap_or_default();
+        req = Request::from_parts(parts, Body::from(body_bytes.clone()));
+        if let Some(tx) = tx {
+            // Only keys with full logging have the transaction itself kept
+            let request = key_info
+                .privacy
+                .allows_params()
+                .then(|| String::from_utf8_lossy(&body_bytes).into_owned());
+            let mut entry = JournalEntry::new(
+                tx.signature,
+                key,
+                key_info.owner.clone(),
+                unix_now(),
+                request,
+            );
+            write_journal(journal.as_ref(), &entry).await;
+            let resp = forward_upstream(
+                state,
+                key_info,
+                req,
+                timing,
+                router_state,
+                pinned,
+                cache_lookup,
+            )
+            .await;
+            return journal_response(journal.as_ref(), &mut entry, resp).await;
+        }
+    }
+
+    // Identical concurrent reads of `coalescing.methods` share one upstream
+    // request. The others wait for its answer (up to the proxy timeout) and go
+    // upstream themselves if it has none to share.
+    let coalesce = router_state.coalescing.enabled
+        && pinned.is_none()
+        && cache_lookup.is_none()
+        && req
+            .extensions()
+            .get::<RpcMethod>()
+            .is_some_and(|m| router_state.coalescing.methods.contains(&m.0));
+    if coalesce {
+        let (parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        let read = ReadKey::from_request(&body_bytes);
+        req = Request::from_parts(parts, Body::from(body_bytes));
+        if let Some(read) = read {
+            match state.coalescer.begin(&read.key) {
+                Coalesce::Leader(leader) => {
+                    let max_bytes = router_state.coalescing.max_response_bytes;
+                    let resp = forward_upstream(
+                        state,
+                        key_info,
+                        req,
+                        timing,
+                        router_state,
+                        pinned,
+                        cache_lookup,
+                    )
+                    .await;
+                    return share_response(resp, leader, max_bytes).await;
+                }
+                Coalesce::Follower(answer) => {
+                    let wait = Duration::from_secs(router_state.proxy_timeout_secs);
+                    timing.mark(Phase::Routing);
+                    let shared = follow(answer, wait).await;
+                    timing.mark(Phase::Queue);
+                    if let Some(shared) = shared {
+                        counter!("rpc_coalesced_requests_total", "rpc_method" => read.method.clone())
+                            .increment(1);
+                        let mut resp = (
+                            [("content-type", "application/json")],
+                            read.response(shared.field, &shared.value),
+                        )
+                            .into_response();
+                        resp.extensions_mut()
+                            .insert(SelectedBackend(shared.backend.clone()));
+                        if let Some(owner) = req.extensions().get::<ClientOwner>().cloned() {
+                            resp.extensions_mut().insert(owner);
+                        }
+                        return resp;
+                    }
+                    counter!("rpc_coalesce_fallbacks_total", "rpc_method" => read.method.clone())
+                        .increment(1);
+                }
+            }
+        }
+    }
+
+    forward_upstream(
+        state,
+        key_info,
+        req,
+        timing,
+        router_state,
+        pinned,
+        cache_lookup,
+    )
+    .await
+}
+
+/// Forward a request no earlier step answered: transaction checks and
+/// broadcast, hedging, backend selection and the upstream call.
+async fn forward_upstream(
+    state: Arc<AppState>,
+    key_info: KeyInfo,
+    mut req: Request<Body>,
+    timing: &mut RequestTiming,
+    router_state: Arc<RouterState>,
+    pinned: Option<String>,
+    cache_lookup: Option<CacheLookup>,
+) -> Response {
+    // Keep sendTransaction payloads so an undelivered one can be dead-lettered,
+    // and so its blockhash can be checked
+    let is_send_transaction = req
+        .extensions()
+        .get::<RpcMethod>()
+        .is_some_and(|m| m.0 == "sendTransaction");
+    let check_blockhash = router_state.blockhash_check.enabled;
+    let broadcast = pinned.is_none() && (router_state.broadcast.enabled || key_info.broadcast);
+    let send_body = if is_send_transaction
+        && (state.dead_letters.is_some() || check_blockhash || broadcast)
+    {
+        let (parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        req = Request::from_parts(parts, Body::from(body_bytes.clone()));
+        Some(body_bytes)
+    } else {
+        None
+    };
+
+    // A transaction whose blockhash is known to be expired cannot land; answer
+    // it here rather than spend upstream quota on it
+    if let Some(tx) = send_body
+        .as_deref()
+        .filter(|_| check_blockhash)
+        .and_then(SendTransaction::from_request)
+        .filter(|tx| state.blockhash_cache.is_expired(&tx.blockhash))
+    {
+        counter!("rpc_blockhash_expired_total", "owner" => key_info.owner.clone()).increment(1);
+        let mut resp = (
+            [("content-type", "application/json")],
+            tx.expired_response(),
+        )
+            .into_response();
+        resp.extensions_mut()
+            .insert(SelectedBackend("blockhash-check".to_string()));
+        if let Some(owner) = req.extensions().get::<ClientOwner>().cloned() {
+            resp.extensions_mut().insert(owner);
+        }
+        return resp;
+    }
+    let broadcast_tx = send_body
+        .as_deref()
+        .filter(|_| broadcast)
+        .and_then(SendTransaction::from_request);
+    // Dead letters keep the whole request, so only keys with full logging get them
+    let dead_letter_body =
+        send_body.filter(|_| state.dead_letters.is_some() && key_info.privacy.allows_params());
+
+    // Broadcast transactions to every healthy backend. One accepted recently
+    // is answered with its signature rather than sent again.
+    if let Some(tx) = broadcast_tx {
+        let dedup = Duration::from_secs(router_state.broadcast.dedup_secs);
+        if !dedup.is_zero() && state.broadcast_signatures.contains(&tx.signature, dedup) {
+            counter!("rpc_broadcast_duplicates_total", "owner" => key_info.owner.clone())
+                .increment(1);
+            let mut resp = (
+                [("content-type", "application/json")],
+                accepted_response(&tx.signature, &tx.id),
+            )
+                .into_response();
+            resp.extensions_mut()
+                .insert(SelectedBackend("broadcast-dedup".to_string()));
+            if let Some(owner) = req.extensions().get::<ClientOwner>().cloned() {
+                resp.extensions_mut().insert(owner);
+            }
+            return resp;
+        }
+        let targets = router_state.broadcast_targets("sendTransaction");
+        if !targets.is_empty() {
+            timing.mark(Phase::Routing);
+            let resp =
+                broadcast_proxy(&state, &router_state, targets, req, tx, dead_letter_body).await;
+            timing.mark(Phase::Upstream);
+            return resp;
+        }
+    }
+
+    // Reads of slots older than non-archive nodes keep are sent to archive
+    // backends (`archive_routing`)
+    let reads_slot = router_state.archive_routing.enabled
+        && pinned.is_none()
+        && req
+            .extensions()
+            .get::<RpcMethod>()
+            .is_some_and(|m| is_slot_method(&m.0));
+    let deep_history = if reads_slot {
+        let (parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        let slot = requested_slot(&body_bytes);
+        req = Request::from_parts(parts, Body::from(body_bytes));
+        slot.is_some_and(|slot| router_state.is_deep_history(slot))
+    } else {
+        false
+    };
+
+    // Opt-in to receiving very large responses as a presigned object URL. The
+    // header is not forwarded upstream.
+    let offload =
+        req.headers_mut().remove(OFFLOAD_HEADER).is_some() && router_state.offload.enabled;
+
+    // Get RPC method from extension (set by extract_rpc_method middleware)
+    let rpc_method = req.extensions().get::<RpcMethod>().map(|m| m.0.as_str());
+    let takes_version = rpc_method.is_some_and(takes_transaction_version);
+    let class = rpc_method
+        .and_then(method_info)
+        .map(|info| (info.class, info.cost));
+    let is_write = matches!(class, Some((MethodClass::Write, _)));
+    let is_heavy = class.is_some_and(|(class, cost)| {
+        class != MethodClass::Write && cost >= router_state.pools.heavy_min_cost
+    });
+    // The key's own or tier route for this method, ahead of `method_routes`
+    let key_route = rpc_method.and_then(|m| key_info.route(m, &router_state.tier_routes));
+
+    // Race latency-critical methods on the two fastest backends, within the hedging
+    // budget. Cacheable requests skip this: the response is stored on the normal path.
+    // Delayed hedging sends every such request to the faster backend and spends
+    // the budget only on those it has to hedge.
+    if let Some(pair) = rpc_method
+        .filter(|_| cache_lookup.is_none() && key_route.is_none() && pinned.is_none())
+        .filter(|_| !deep_history)
+        .filter(|_| !state.is_disabled(&router_state, Subsystem::Hedging))
+        .and_then(|m| router_state.hedge_pair(m))
+    {
+        let hedging = &router_state.hedging;
+        let hedge = if hedging.is_delayed() {
+            state.hedge_budget.earn(hedging.budget_percent);
+            true
+        } else {
+            state.hedge_budget.try_acquire(hedging.budget_percent)
+        };
+        if hedge {
+            timing.mark(Phase::Routing);
+            let resp =
+                hedged_proxy(&state, pair, req, hedging, router_state.proxy_timeout_secs).await;
+            timing.mark(Phase::Upstream);
+            return resp;
+        }
+    }
+
+    // Deep history goes to an archive backend while one is in rotation, unless
+    // the method is pinned to a backend
+    let archive = rpc_method
+        .filter(|m| {
+            deep_history && key_route.is_none() && !router_state.method_routes.contains_key(*m)
+        })
+        .and_then(|_| router_state.select_archive(rpc_method, &[]));
+    if let (Some(method), Some(backend)) = (rpc_method, archive) {
+        counter!("rpc_archive_routed_total", "rpc_method" => method.to_string(), "backend" => backend.config.label.clone())
+            .increment(1);
+    }
+
+    // Select backend based on method routing, then learned shares or weighted round-robin
+    let selected = match rpc_method {
+        _ if pinned.is_some() => router_state
+            .backends
+            .iter()
+            .find(|b| pinned.as_ref() == Some(&b.config.label)),
+        _ if archive.is_some() => archive,
+        Some(method) if key_route.is_none() && router_state.routing.mode == RoutingMode::Auto => {
+            router_state.select_auto(method, &state.auto_weights.load())
+        }
+        _ => router_state.select_backend_routed(rpc_method, key_route),
+    };
+    let backend = match selected {
+        Some(backend) => backend,
+        None => {
+            // Backends return to rotation on a health check at the earliest
+            let hint = RetryHint::new(
+                Duration::from_secs(router_state.health_check_config.interval_secs),
+                QueueDepth::Empty,
+            );
+            tracing::error!(
+                "No healthy backends available for request (correlation_id={})",
+                hint.correlation_id
+            );
+            let stale = stale_response(&state, &router_state, cache_lookup.as_ref()).await;
+            if let Some(mut resp) = stale {
+                if let Some(owner) = req.extensions().get::<ClientOwner>().cloned() {
+                    resp.extensions_mut().insert(owner);
+                }
+                return resp;
+            }
+            capture_dead_letter(
+                &state,
+                dead_letter_body,
+                req.extensions().get::<ClientOwner>(),
+                None,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "No healthy backends available",
+            );
+            let owner = req.extensions().get::<ClientOwner>().cloned();
+            let body = to_bytes(req.into_body(), MAX_BODY_SIZE)
+                .await
+                .unwrap_or_default();
+            let message = RouterError::NoBackend.message();
+            let mut resp = match &router_state.error_templates.no_backend {
+                Some(template) => hint.templated_response(template, message),
+                None => hint.response(message, &body),
+            };
+            if let Some(owner) = owner {
+                resp.extensions_mut().insert(owner);
+            }
+            return resp;
+        }
+    };
+    let backend_label = backend.config.label.as_str();
+    if router_state.is_cross_region(backend) {
+        counter!(
+            "rpc_cross_region_requests_total",
+            "region" => backend.config.region.clone().unwrap_or_default(),
+            "backend" => backend_label.to_string()
+        )
+        .increment(1);
+    }
+
+    // Heavy reads wait for one of the backend's heavy slots, so a flood of them
+    // cannot occupy every upstream connection. The slot is held until the
+    // response body has been sent.
+    let heavy_permit = match &backend.heavy_slots {
+        Some(slots) if is_heavy => {
+            let wait = Duration::from_secs(router_state.proxy_timeout_secs);
+            timing.mark(Phase::Routing);
+            let acquired = slots.acquire(wait).await;
+            timing.mark(Phase::Queue);
+            match acquired {
+                Ok(permit) => Some(permit),
+                Err(waiting) => {
+                    counter!("rpc_heavy_slot_timeouts_total", "backend" => backend_label.to_string())
+                        .increment(1);
+                    let latency = Duration::from_micros(backend.latency_us.load(Ordering::Relaxed));
+                    let hint = RetryHint::for_queue(latency, waiting, slots.limit());
+                    warn!(
+                        "Shed heavy request to '{}' with {} waiting (correlation_id={})",
+                        backend_label, waiting, hint.correlation_id
+                    );
+                    let owner = req.extensions().get::<ClientOwner>().cloned();
+                    let body = to_bytes(req.into_body(), MAX_BODY_SIZE)
+                        .await
+                        .unwrap_or_default();
+                    let mut resp =
+                        hint.response("Backend is at its limit for heavy requests", &body);
+                    resp.extensions_mut()
+                        .insert(SelectedBackend(backend_label.to_string()));
+                    if let Some(owner) = owner {
+                        resp.extensions_mut().insert(owner);
+                    }
+                    return resp;
+                }
+            }
+        }
+        _ => None,
+    };
+
+    // Keep a copy of requests whose responses are verified, or that may fail
+    // over, to re-send to another backend if the response arrives damaged or
+    // the backend fails. Taken before the URI, host and signature are set for
+    // this backend.
+    let retries = !state.is_disabled(&router_state, Subsystem::Retries);
+    let verified = retries && router_state.integrity.applies_to(rpc_method);
+    let max_attempts = if retries {
+        router_state.retry.max_attempts as usize
+    } else {
+        1
+    };
+    let stored_request = if verified || max_attempts > 1 {
+        let rpc_method = rpc_method.map(str::to_string);
+        let (parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        let stored = StoredRequest {
+            method: parts.method.clone(),
+            uri: parts.uri.clone(),
+            headers: parts.headers.clone(),
+            body: body_bytes.clone(),
+            rpc_method,
+        };
+        req = Request::from_parts(parts, Body::from(body_bytes));
+        Some(stored)
+    } else {
+        None
+    };
+    // Non-idempotent requests, and batches holding one, are only sent once
+    let resendable = pinned.is_none()
+        && stored_request.as_ref().is_some_and(|stored| {
+            router_state.may_resend(stored.rpc_method.as_deref(), &stored.body)
+        });
+    let max_attempts = if resendable { max_attempts } else { 1 };
+
+    // Build the upstream URI from the backend's pre-split parts (strips api-key)
+    let rpc_method = req.extensions().get::<RpcMethod>().map(|m| m.0.as_str());
+    let parsed_uri = match backend.uri_for(rpc_method, req.uri().path(), req.uri().query()) {
+        Ok(uri) => uri,
+        Err(e) => {
+            error!(
+                "Failed to build URI for backend '{}': {}",
+                backend_label,
+                redact(&e)
+            );
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Invalid backend configuration",
+            )
+                .into_response();
+        }
+    };
+
+    // Update Host header to match the backend. The client's header map is reused
+    // as-is for the upstream request.
+    if let Some(target) = &backend.target {
+        req.headers_mut().insert("host", target.host.clone());
+    }
+
+    *req.uri_mut() = parsed_uri;
+
+    // Add the backend's own headers, and sign the request if the backend
+    // requires it. The signature covers the whole body, so a request still
+    // streaming in is buffered here.
+    apply_backend_headers(&backend.config, req.headers_mut());
+    if let Some(signing) = &backend.config.signing {
+        let (mut parts, body) = req.into_parts();
+        let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            }
+        };
+        apply_signature(signing, &mut parts.headers, &body_bytes, unix_now());
+        req = Request::from_parts(parts, Body::from(body_bytes));
+    }
+
+    // Keep a copy of getBlock/getTransaction requests that omit
+    // maxSupportedTransactionVersion, rewritten to set it, in case the backend
+    // rejects the original.
+    let version_retry = match router_state.retry_transaction_version {
+        Some(version) if takes_version && retries => {
+            let (parts, body) = req.into_parts();
+            let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large")
+                        .into_response();
+                }
+            };
+            let retry = with_transaction_version(&body_bytes, version).map(|body| VersionRetry {
+                method: parts.method.clone(),
+                uri: parts.uri.clone(),
+                headers: parts.headers.clone(),
+                body: Bytes::from(body),
+                version,
+            });
+            req = Request::from_parts(parts, Body::from(body_bytes));
+            retry
+        }
+        _ => None,
+    };
+
+    // Capture owner before request is consumed
+    let client_owner = req.extensions().get::<ClientOwner>().cloned();
+
+    // Moved out rather than cloned: the upstream request does not need it
+    let forwarded_method = req.extensions_mut().remove::<RpcMethod>();
+
+    // Forward request. Writes get their own pool so heavy reads to the same
+    // backend cannot hold every connection.
+    let write_pool = is_write && router_state.pools.separate_write_pool;
+    let proxy_timeout = router_state.proxy_timeout_secs;
+    let attempt_timeout = router_state.retry.attempt_timeout(proxy_timeout);
+    timing.mark(Phase::Routing);
+    let keep_slow = router_state.retry.keep_slow_attempts;
+    // A first-byte timeout only applies while another backend may still be tried
+    let first_byte = |backend: &RuntimeBackend, tried: usize| {
+        (stored_request.is_some() && tried + 1 < max_attempts)
+            .then(|| {
+                router_state
+                    .retry
+                    .first_byte_timeout(backend.config.first_byte_timeout_ms)
+            })
+            .flatten()
+    };
+    let mut slow = None;
+    backend.circuit.begin(unix_now());
+    let wait = first_byte(backend, 0);
+    let outcome = send_attempt(
+        &state.client_for(&backend.config, write_pool),
+        req,
+        backend,
+        attempt_timeout,
+        wait,
+        false,
+        &mut slow,
+    )
+    .await;
+    let forwarded = forwarded_method.as_ref();
+    record_attempt(
+        &state,
+        &router_state,
+        forwarded,
+        backend,
+        &outcome.result,
+        outcome.started,
+    );
+    let AttemptOutcome {
+        mut result,
+        mut started,
+        mut missed_first_byte,
+        ..
+    } = outcome;
+
+    // Fail over to other backends in rotation while the backend fails the request
+    let mut backend = backend;
+    let mut tried = vec![backend_label];
+    while tried.len() < max_attempts {
+        let Some(reason) = failover_reason(&result, &router_state.retry) else {
+            break;
+        };
+        let reason = if missed_first_byte {
+            "first_byte".to_string()
+        } else {
+            reason
+        };
+        let Some(stored) = &stored_request else {
+            break;
+        };
+        let rpc_method = stored.rpc_method.as_deref();
+        let next = match archive {
+            Some(_) => router_state
+                .select_archive(rpc_method, &tried)
+                .or_else(|| router_state.select_retry_backend(rpc_method, &tried)),
+            None => router_state.select_retry_backend(rpc_method, &tried),
+        };
+        let Some(next) = next else {
+            break;
+        };
+        let Some(next_req) = stored.to_backend(next) else {
+            break;
+        };
+        let next_label = next.config.label.as_str();
+        counter!("rpc_failover_retries_total", "backend" => backend.config.label.clone(), "reason" => reason.clone())
+            .increment(1);
+        warn!(
+            "Backend '{}' failed request ({}), failing over to '{}'",
+            backend.config.label, reason, next_label
+        );
+        next.circuit.begin(unix_now());
+        let wait = first_byte(next, tried.len());
+        let outcome = send_attempt(
+            &state.client_for(&next.config, write_pool),
+            next_req,
+            next,
+            attempt_timeout,
+            wait,
+            keep_slow,
+            &mut slow,
+        )
+        .await;
+        if !outcome.kept {
+            record_attempt(
+                &state,
+                &router_state,
+                forwarded,
+                next,
+                &outcome.result,
+                outcome.started,
+            );
+        }
+        result = outcome.result;
+        started = outcome.started;
+        missed_first_byte = outcome.missed_first_byte;
+        backend = outcome.backend;
+        tried.push(next_label);
+    }
+    // Out of backends: an attempt that missed its first byte gets the rest of its time
+    if let Some(earlier) = slow.take() {
+        if failover_reason(&result, &router_state.retry).is_some() {
+            result = timeout_at(earlier.deadline, earlier.response).await;
+            started = earlier.started;
+            backend = earlier.backend;
+        }
+    }
+    timing.mark(Phase::Upstream);
+    let failed_over = tried.len() > 1;
+    let backend_label = backend.config.label.as_str();
+    // The version retry was addressed to the first backend
+    let version_retry = version_retry.filter(|_| !failed_over);
+    let integrity_retry = stored_request.as_ref().filter(|_| verified && resendable);
+
+    match result {
+        Ok(Ok(resp)) => {
+            backend.record_latency(started.elapsed());
+            if resp.status().is_server_error() {
+                capture_dead_letter(
+                    &state,
+                    dead_letter_body,
+                    client_owner.as_ref(),
+                    Some(backend_label),
+                    resp.status(),
+                    &format!("Backend returned {}", resp.status()),
+                );
+            }
+            // The upstream body is streamed back as-is: never buffered, parsed or
+            // re-serialized. Features that need to inspect responses must opt in per
+            // method and leave this path untouched for everything else.
+            let offload_size = resp
+                .headers()
+                .get("content-length")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|size| offload && *size >= router_state.offload.min_bytes);
+            let mut resp = match (version_retry, integrity_retry, cache_lookup, offload_size) {
+                (_, _, _, Some(size)) if resp.status() == StatusCode::OK => {
+                    offload_response(&state, &router_state.offload, resp, size, backend_label).await
+                }
+                (Some(retry), _, _, _) if resp.status() == StatusCode::OK => {
+                    retry_transaction_version(&state, resp, retry, &backend.config, proxy_timeout)
+                        .await
+                }
+                (_, Some(retry), lookup, _) if resp.status() == StatusCode::OK => {
+                    verified_response(
+                        &state,
+                        &router_state,
+                        resp,
+                        retry,
+                        backend_label,
+                        lookup.as_ref(),
+                    )
+                    .await
+                }
+                (_, _, Some(lookup), _) if resp.status() == StatusCode::OK => {
+                    cache_response(&state, resp, &lookup, backend_label).await
+                }
+                _ => resp.into_response(),
+            };
+            if let Some(permit) = heavy_permit {
+                resp = hold_until_sent(resp, permit);
+            }
+            // Store selected backend label and owner in response extensions for logging/metrics.
+            // A response re-sent elsewhere already names the backend that served it.
+            if resp.extensions().get::<SelectedBackend>().is_none() {
+                resp.extensions_mut()
+                    .insert(SelectedBackend(backend_label.to_string()));
+            }
+            if failed_over && !resp.headers().contains_key("x-rpc-router-retry") {
+                resp.headers_mut().insert(
+                    "x-rpc-router-retry",
+                    axum::http::HeaderValue::from_static("failover"),
+                );
+            }
+            if let Some(owner) = client_owner {
+                resp.extensions_mut().insert(owner);
+            }
+            resp.into_response()
+        }
+        Ok(Err(err)) => {
+            let err = redact(&err.to_string()).into_owned();
+            info!("Backend request failed: {}", err);
+            capture_dead_letter(
+                &state,
+                dead_letter_body,
+                client_owner.as_ref(),
+                Some(backend_label),
+                StatusCode::BAD_GATEWAY,
+                &err,
+            );
+            let mut resp =
+                (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", err)).into_response();
+            resp.extensions_mut()
+                .insert(SelectedBackend(backend_label.to_string()));
+            if let Some(owner) = client_owner {
+                resp.extensions_mut().insert(owner);
+            }
+            resp
+        }
+        Err(_) => {
+            let err = format!(
+                "Upstream request timed out after {}s",
+                attempt_timeout.as_secs()
+            );
+            capture_dead_letter(
+                &state,
+                dead_letter_body,
+                client_owner.as_ref(),
+                Some(backend_label),
+                StatusCode::GATEWAY_TIMEOUT,
+                &err,
+            );
+            let mut resp = (StatusCode::GATEWAY_TIMEOUT, err).into_response();
+            resp.extensions_mut()
+                .insert(SelectedBackend(backend_label.to_string()));
+            if let Some(owner) = client_owner {
+                resp.extensions_mut().insert(owner);
+            }
+            resp
+        }
+    }
+}
+
+/// Write a journal entry. A journal that cannot be written does not hold up the
+/// transaction; the failure is logged and counted.
+async fn write_journal(journal: &dyn JournalStore, entry: &JournalEntry) {
+    if let Err(e) = journal.put(entry).await {
+        warn!(
+            "Failed to journal transaction {}: {}",
+            entry.signature,
+            redact(&e)
+        );
+        counter!("rpc_journal_write_failures_total").increment(1);
+    }
+}
+
+/// Record the outcome of a journaled transaction, then pass its response on.
+async fn journal_response(
+    journal: &dyn JournalStore,
+    entry: &mut JournalEntry,
+    resp: Response,
+) -> Response {
+    let (parts, body) = resp.into_parts();
+    let body = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(err) => {
+            let err = redact(&err.to_string()).into_owned();
+            info!("Backend response failed: {}", err);
+            entry.record(
+                None,
+                StatusCode::BAD_GATEWAY.as_u16(),
+                &[],
+                unix_now(),
+                false,
+            );
+            write_journal(journal, entry).await;
+            return (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", err)).into_response();
+        }
+    };
+    let backend = parts
+        .extensions
+        .get::<SelectedBackend>()
+        .map(|b| b.0.as_str());
+    entry.record(backend, parts.status.as_u16(), &body, unix_now(), false);
+    write_journal(journal, entry).await;
+    counter!("rpc_journal_entries_total", "status" => entry.status.as_str()).increment(1);
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Re-send transactions journaled in the last `resume_secs` whose outcome was
+/// never recorded, e.g. because the router stopped while they were in flight.
+/// Entries without the transaction itself stay pending.
+pub async fn resume_journal(state: Arc<AppState>, resume_secs: u64) {
+    let Some(journal) = state.journal.clone() else {
+        return;
+    };
+    let since = unix_now().saturating_sub(resume_secs);
+    let entries = match journal.pending(since).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to read the transaction journal: {}", redact(&e));
+            return;
+        }
+    };
+    for mut entry in entries {
+        let Some(request) = entry.request.clone() else {
+            info!(
+                "Journaled transaction {} cannot be resumed: it was not kept",
+                entry.signature
+            );
+            continue;
+        };
+        let router_state = state.state.load_full();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "content-type",
+            axum::http::HeaderValue::from_static("application/json"),
+        );
+        let stored = StoredRequest {
+            method: axum::http::Method::POST,
+            uri: axum::http::Uri::from_static("/"),
+            headers,
+            body: Bytes::from(request),
+            rpc_method: Some("sendTransaction".to_string()),
+        };
+        let Some((backend, upstream)) = router_state
+            .select_backend_routed(Some("sendTransaction"), None)
+            .and_then(|backend| Some((backend, stored.to_backend(backend)?)))
+        else {
+            warn!(
+                "No backend available to resume journaled transaction {}",
+                entry.signature
+            );
+            continue;
+        };
+        let client = state.client_for(&backend.config, router_state.pools.separate_write_pool);
+        let wait = Duration::from_secs(router_state.proxy_timeout_secs);
+        let answer = match timeout(wait, client.request(upstream)).await {
+            Ok(Ok(resp)) => {
+                let status = resp.status();
+                match resp.into_body().collect().await {
+                    Ok(body) => Some((status, body.to_bytes())),
+                    Err(_) => None,
+                }
+            }
+            _ => None,
+        };
+        match answer {
+            Some((status, body)) => entry.record(
+                Some(&backend.config.label),
+                status.as_u16(),
+                &body,
+                unix_now(),
+                true,
+            ),
+            None => entry.record(
+                None,
+                StatusCode::BAD_GATEWAY.as_u16(),
+                &[],
+                unix_now(),
+                true,
+            ),
+        }
+        write_journal(journal.as_ref(), &entry).await;
+        info!(
+            "Resumed journaled transaction {} (status={})",
+            entry.signature,
+            entry.status.as_str()
+        );
+        counter!("rpc_journal_resumed_total", "status" => entry.status.as_str()).increment(1);
+    }
+}
+
+/// Pass a coalesced request's response on to its client, sharing its answer
+/// with the requests waiting on it. Only complete `200` JSON-RPC responses of
+/// up to `max_bytes` are shared; for anything else the waiting requests go
+/// upstream themselves.
+async fn share_response(resp: Response, leader: Leader, max_bytes: usize) -> Response {
+    let shareable = resp.status() == StatusCode::OK
+        && !resp.headers().contains_key("content-encoding")
+        && resp
+            .headers()
+            .get("content-length")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok())
+            .is_some_and(|len| len <= max_bytes);
+    if !shareable {
+        return resp;
+    }
+    let (parts, body) = resp.into_parts();
+    let body = match to_bytes(body, max_bytes).await {
+        Ok(body) => body,
+        Err(err) => {
+            let err = redact(&err.to_string()).into_owned();
+            info!("Backend response failed: {}", err);
+            return (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", err)).into_response();
+        }
+    };
+    let backend = parts
+        .extensions
+        .get::<SelectedBackend>()
+        .map_or("", |b| b.0.as_str());
+    if let Some(shared) = Shared::from_response(&body, backend) {
+        leader.share(shared);
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// One upstream attempt: the response, a connection error, or a timeout.
+type Attempt = Result<
+    Result<Response<hyper::body::Incoming>, hyper_util::client::legacy::Error>,
+    tokio::time::error::Elapsed,
+>;
+
+/// An attempt that missed its first-byte timeout, still running in case it
+/// answers before the next one (`retry.keep_slow_attempts`), or before its
+/// own timeout when no other backend is left.
+struct SlowAttempt<'a> {
+    backend: &'a RuntimeBackend,
+    started: Instant,
+    /// End of the attempt's full timeout
+    deadline: Instant,
+    response: ResponseFuture,
+}
+
+/// Result of [`send_attempt`], with the backend and start time it belongs to.
+struct AttemptOutcome<'a> {
+    result: Attempt,
+    backend: &'a RuntimeBackend,
+    started: Instant,
+    /// Timed out waiting for the first byte; the attempt was left in `slow`
+    missed_first_byte: bool,
+    /// Answered by the earlier slow attempt, whose outcome is already recorded
+    kept: bool,
+}
+
+/// Send `req` to `backend`, waiting up to `first_byte` for the response (the
+/// full `limit` without one). An attempt that misses its first byte ends as a
+/// timeout, so the next backend is tried early, and is left in `slow`. With
+/// `keep_slow` an earlier slow attempt races this one, and wins if it answers
+/// successfully first; otherwise it is dropped.
+async fn send_attempt<'a>(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    req: Request<Body>,
+    backend: &'a RuntimeBackend,
+    limit: Duration,
+    first_byte: Option<Duration>,
+    keep_slow: bool,
+    slow: &mut Option<SlowAttempt<'a>>,
+) -> AttemptOutcome<'a> {
+    enum Race {
+        Earlier(Attempt),
+        Ours(Attempt),
+    }
+
+    let started = Instant::now();
+    let first_byte = first_byte.filter(|wait| *wait < limit);
+    let wait_until = started + first_byte.unwrap_or(limit);
+    let mut response = client.request(req);
+    let earlier = slow.take().filter(|_| keep_slow);
+
+    let result = match earlier {
+        Some(mut earlier) => {
+            let race = tokio::select! {
+                result = timeout_at(earlier.deadline, &mut earlier.response) => Race::Earlier(result),
+                result = timeout_at(wait_until, &mut response) => Race::Ours(result),
+            };
+            match race {
+                Race::Earlier(result @ Ok(Ok(_))) => {
+                    return AttemptOutcome {
+                        result,
+                        backend: earlier.backend,
+                        started: earlier.started,
+                        missed_first_byte: false,
+                        kept: true,
+                    };
+                }
+                Race::Earlier(_) => timeout_at(wait_until, &mut response).await,
+                Race::Ours(result) => {
+                    // Both are slow: the earlier one is closer to answering
+                    if result.is_err() && first_byte.is_some() {
+                        *slow = Some(earlier);
+                        return AttemptOutcome {
+                            result,
+                            backend,
+                            started,
+                            missed_first_byte: true,
+                            kept: false,
+                        };
+                    }
+                    result
+                }
+            }
+        }
+        None => timeout_at(wait_until, &mut response).await,
+    };
+
+    let missed_first_byte = result.is_err() && first_byte.is_some();
+    if missed_first_byte {
+        *slow = Some(SlowAttempt {
+            backend,
+            started,
+            deadline: started + limit,
+            response,
+        });
+    }
+    AttemptOutcome {
+        result,
+        backend,
+        started,
+        missed_first_byte,
+        kept: false,
+    }
+}
+
+/// Record an upstream attempt of `method` in the routing statistics.
+fn record_attempt(
+    state: &AppState,
+    router_state: &RouterState,
+    method: Option<&RpcMethod>,
+    backend: &RuntimeBackend,
+    result: &Attempt,
+    started: Instant,
+) {
+    let label = backend.config.label.as_str();
+    let success = matches!(result, Ok(Ok(resp)) if !resp.status().is_server_error());
+    let breaker = &router_state.circuit_breaker;
+    if breaker.enabled {
+        backend.circuit.record(label, success, unix_now(), breaker);
+    }
+    record_passive(
+        &router_state.health_state,
+        backend,
+        success,
+        router_state.health_check_config.passive_failures_threshold,
+    );
+    if let Some(RpcMethod(method)) = method {
+        state
+            .routing_stats
+            .record(method, label, success, started.elapsed(), unix_now());
+        if matches!(result, Ok(Ok(_))) {
+            record_heatmap(
+                &state.latency_heatmap,
+                &router_state.latency_heatmap,
+                method,
+                label,
+                started.elapsed(),
+            );
+        }
+    }
+}
+
+/// Add an upstream answer to the latency heatmap, if it is enabled.
+fn record_heatmap(
+    heatmap: &LatencyHeatmap,
+    config: &LatencyHeatmapConfig,
+    method: &str,
+    backend: &str,
+    latency: Duration,
+) {
+    if config.enabled {
+        heatmap.record(
+            method,
+            backend,
+            latency,
+            unix_now(),
+            config.window_secs,
+            config.windows,
+        );
+    }
+}
+
+/// Why `result` should be tried on another backend, if it should: its
+/// status if listed in `retry.retryable_status`, `error` for a connection
+/// error, or `timeout`.
+fn failover_reason(result: &Attempt, config: &RetryConfig) -> Option<String> {
+    match result {
+        Ok(Ok(resp)) => config
+            .retries_status(resp.status().as_u16())
+            .then(|| resp.status().as_u16().to_string()),
+        Ok(Err(_)) => Some("error".to_string()),
+        Err(_) => Some("timeout".to_string()),
+    }
+}
+
+/// Persist an undelivered `sendTransaction` request in the dead-letter store. The
+/// write happens in the background so the client's error is not delayed by it.
+fn capture_dead_letter(
+    state: &AppState,
+    request: Option<Bytes>,
+    owner: Option<&ClientOwner>,
+    backend: Option<&str>,
+    status: StatusCode,
+    error: &str,
+) {
+    let (Some(store), Some(request)) = (state.dead_letters.clone(), request) else {
+        return;
+    };
+    let letter = DeadLetter {
+        id: String::new(),
+        captured_at: unix_now(),
+        owner: owner.map(|o| o.0.clone()).unwrap_or_default(),
+        backend: backend.map(str::to_string),
+        status: status.as_u16(),
+        error: error.to_string(),
+        request: String::from_utf8_lossy(&request).into_owned(),
+    };
+    counter!("rpc_dead_letters_total", "backend" => letter.backend.clone().unwrap_or_default())
+        .increment(1);
+    tokio::spawn(async move {
+        if let Err(e) = store.push(&letter).await {
+            error!("Failed to store dead letter: {}", redact(&e));
+        }
+    });
+}
+
+/// Send `req` to both backends and return the first successful response. With
+/// a hedging delay, the second backend only gets the request once the first has
+/// taken longer than the delay (or failed sooner) and the budget allows. The
+/// slower request still runs to completion in the background so its backend's
+/// latency keeps being measured.
+async fn hedged_proxy(
+    state: &AppState,
+    backends: [&RuntimeBackend; 2],
+    req: Request<Body>,
+    hedging: &HedgingConfig,
+    proxy_timeout: u64,
+) -> Response {
+    let client_owner = req.extensions().get::<ClientOwner>().cloned();
+    let rpc_method = req
+        .extensions()
+        .get::<RpcMethod>()
+        .map(|m| m.0.clone())
+        .unwrap_or_default();
+    let (parts, body) = req.into_parts();
+    let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+        }
+    };
+
+    let send = |backend: &RuntimeBackend| {
+        let upstream = upstream_request(backend, &parts, &body_bytes);
+        let client = state.client_for(&backend.config, false);
+        let routing_stats = state.routing_stats.clone();
+        let heatmap = state.latency_heatmap.clone();
+        let router_state = state.state.load_full();
+        let rpc_method = rpc_method.clone();
+        let label = backend.config.label.clone();
+        let backend = backend.clone();
+        Box::pin(async move {
+            let upstream = upstream.map_err(|e| format!("invalid backend '{}': {}", label, e))?;
+            let started = Instant::now();
+            backend.circuit.begin(unix_now());
+            let result = client.request(upstream).await;
+            let success = matches!(&result, Ok(resp) if !resp.status().is_server_error());
+            routing_stats.record(&rpc_method, &label, success, started.elapsed(), unix_now());
+            let breaker = &router_state.circuit_breaker;
+            if breaker.enabled {
+                backend.circuit.record(&label, success, unix_now(), breaker);
+            }
+            record_passive(
+                &router_state.health_state,
+                &backend,
+                success,
+                router_state.health_check_config.passive_failures_threshold,
+            );
+            match result {
+                Ok(resp) => {
+                    backend.record_latency(started.elapsed());
+                    record_heatmap(
+                        &heatmap,
+                        &router_state.latency_heatmap,
+                        &rpc_method,
+                        &label,
+                        started.elapsed(),
+                    );
+                    if resp.status().is_server_error() {
+                        Err(format!("'{}' returned {}", label, resp.status()))
+                    } else {
+                        Ok((label, resp))
+                    }
+                }
+                Err(err) => Err(format!("'{}': {}", label, redact(&err.to_string()))),
+            }
+        })
+    };
 
-    let owner = response
-        .extensions()
-        .get::<ClientOwner>()
-        .map(|o| o.0.clone())
-        .unwrap_or_else(|| "none".to_string());
+    let delay = hedging
+        .is_delayed()
+        .then(|| hedge_delay(state, hedging, &rpc_method, &backends[0].config.label));
+    let mut hedged = false;
+    let race = async {
+        let mut first = send(backends[0]);
+        if let Some(delay) = delay {
+            let failed = match timeout(delay, &mut first).await {
+                Ok(Ok(answer)) => return Ok((answer, Vec::new())),
+                Ok(Err(err)) => Some(err),
+                Err(_) => None,
+            };
+            if !state.hedge_budget.try_spend() {
+                return match failed {
+                    Some(err) => Err(err),
+                    None => first.await.map(|answer| (answer, Vec::new())),
+                };
+            }
+            hedged = true;
+            counter!("rpc_hedged_requests_total", "rpc_method" => rpc_method.clone()).increment(1);
+            let second = send(backends[1]);
+            return match failed {
+                Some(_) => second.await.map(|answer| (answer, Vec::new())),
+                None => futures_util::future::select_ok([first, second]).await,
+            };
+        }
+        hedged = true;
+        counter!("rpc_hedged_requests_total", "rpc_method" => rpc_method.clone()).increment(1);
+        futures_util::future::select_ok([first, send(backends[1])]).await
+    };
+    let result = timeout(Duration::from_secs(proxy_timeout), race).await;
+    let mut resp = match result {
+        Ok(Ok(((label, resp), slower))) => {
+            tokio::spawn(futures_util::future::join_all(slower));
+            if hedged {
+                counter!("rpc_hedge_wins_total", "backend" => label.clone()).increment(1);
+            }
+            let mut resp = resp.into_response();
+            resp.extensions_mut().insert(SelectedBackend(label));
+            resp
+        }
+        Ok(Err(err)) => {
+            info!("Hedged backend requests failed: {}", err);
+            (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", err)).into_response()
+        }
+        Err(_) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            format!("Upstream request timed out after {}s", proxy_timeout),
+        )
+            .into_response(),
+    };
+    if let Some(owner) = client_owner {
+        resp.extensions_mut().insert(owner);
+    }
+    resp
+}
 
-    histogram!("rpc_request_duration_seconds", "rpc_method" => rpc_method.clone(), "backend" => backend.clone(), "owner" => owner.clone()).record(duration);
-    counter!("rpc_requests_total", "method" => method, "status" => status, "rpc_method" => rpc_method, "backend" => backend, "owner" => owner).increment(1);
+/// How long to wait for `backend` before hedging `method`: the configured
+/// percentile of its recent latency when known, else `hedging.delay_ms`.
+fn hedge_delay(state: &AppState, hedging: &HedgingConfig, method: &str, backend: &str) -> Duration {
+    let percentile = (hedging.delay_percentile > 0)
+        .then(|| {
+            let quantile = f64::from(hedging.delay_percentile) / 100.0;
+            state
+                .routing_stats
+                .latency_percentile(method, backend, quantile, unix_now())
+        })
+        .flatten();
+    Duration::from_millis(percentile.unwrap_or(hedging.delay_ms))
+}
 
-    response
+/// `parts` and `body` of a client request, addressed and signed for `backend`.
+fn upstream_request(
+    backend: &RuntimeBackend,
+    parts: &axum::http::request::Parts,
+    body: &Bytes,
+) -> Result<Request<Body>, String> {
+    let target = backend
+        .target
+        .as_ref()
+        .ok_or_else(|| "not an absolute URL".to_string())?;
+    let method = parts.extensions.get::<RpcMethod>().map(|m| m.0.as_str());
+    let uri = backend.uri_for(method, parts.uri.path(), parts.uri.query())?;
+    let mut headers = parts.headers.clone();
+    headers.insert("host", target.host.clone());
+    apply_backend_headers(&backend.config, &mut headers);
+    if let Some(signing) = &backend.config.signing {
+        apply_signature(signing, &mut headers, body, unix_now());
+    }
+    let mut upstream = Request::new(Body::from(body.clone()));
+    *upstream.method_mut() = parts.method.clone();
+    *upstream.uri_mut() = uri;
+    *upstream.headers_mut() = headers;
+    Ok(upstream)
 }
 
-pub async fn proxy(
-    State(state): State<Arc<AppState>>,
-    Query(params): Query<Params>,
-    mut req: Request<Body>,
-) -> impl IntoResponse {
-    let api_key = match params.api_key {
-        Some(k) => k,
-        None => {
-            info!("No API key provided");
-            return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+/// Send a `sendTransaction` request to every backend in `targets` at once and
+/// return the first answer that accepts the transaction. The other requests
+/// run to completion in the background, so every backend's outcome is counted
+/// in `rpc_broadcast_results_total`. Without an acceptance the first backend
+/// answer is returned as it is.
+async fn broadcast_proxy(
+    state: &AppState,
+    router_state: &RouterState,
+    targets: Vec<&RuntimeBackend>,
+    req: Request<Body>,
+    tx: SendTransaction,
+    dead_letter_body: Option<Bytes>,
+) -> Response {
+    let proxy_timeout = router_state.proxy_timeout_secs;
+    let client_owner = req.extensions().get::<ClientOwner>().cloned();
+    let (parts, body) = req.into_parts();
+    let body_bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
         }
     };
 
-    let owner = match state.keystore.validate_key(&api_key).await {
-        Ok(Some(info)) => {
-            info.owner
-        }
-        Ok(None) => {
-            info!("Invalid API key presented (prefix={}...)", &api_key[..api_key.len().min(6)]);
-            return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    let send = |backend: &RuntimeBackend| {
+        let upstream = upstream_request(backend, &parts, &body_bytes);
+        let client = state.client_for(&backend.config, false);
+        let routing_stats = state.routing_stats.clone();
+        let heatmap = state.latency_heatmap.clone();
+        let heatmap_config = router_state.latency_heatmap.clone();
+        let breaker = router_state.circuit_breaker.clone();
+        let health_state = router_state.health_state.clone();
+        let passive_threshold = router_state.health_check_config.passive_failures_threshold;
+        let label = backend.config.label.clone();
+        let backend = backend.clone();
+        async move {
+            let answer = async {
+                let upstream =
+                    upstream.map_err(|e| format!("invalid backend '{}': {}", label, e))?;
+                let started = Instant::now();
+                backend.circuit.begin(unix_now());
+                let result = client.request(upstream).await;
+                let success = matches!(&result, Ok(resp) if !resp.status().is_server_error());
+                routing_stats.record(
+                    "sendTransaction",
+                    &label,
+                    success,
+                    started.elapsed(),
+                    unix_now(),
+                );
+                if breaker.enabled {
+                    backend
+                        .circuit
+                        .record(&label, success, unix_now(), &breaker);
+                }
+                record_passive(&health_state, &backend, success, passive_threshold);
+                let resp =
+                    result.map_err(|e| format!("'{}': {}", label, redact(&e.to_string())))?;
+                backend.record_latency(started.elapsed());
+                record_heatmap(
+                    &heatmap,
+                    &heatmap_config,
+                    "sendTransaction",
+                    &label,
+                    started.elapsed(),
+                );
+                let (parts, body) = resp.into_parts();
+                let body = to_bytes(Body::new(body), MAX_BODY_SIZE)
+                    .await
+                    .map_err(|e| format!("'{}': {}", label, e))?;
+                Ok::<_, String>((parts, body))
+            }
+            .await;
+            let outcome = match &answer {
+                Ok((parts, body)) if parts.status.is_success() && is_accepted(body) => "accepted",
+                Ok(_) => "rejected",
+                Err(_) => "error",
+            };
+            counter!("rpc_broadcast_results_total", "backend" => label.clone(), "result" => outcome)
+                .increment(1);
+            (label, outcome == "accepted", answer)
         }
-        Err(e) => {
-            if e == "Rate limit exceeded" {
-                warn!("API key rate limited (prefix={}...)", &api_key[..api_key.len().min(6)]);
-                return (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded").into_response();
-            } else {
-                error!("Key validation error: {}", e);
-                return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
-                    .into_response();
+    };
+
+    counter!("rpc_broadcast_requests_total").increment(1);
+    let mut pending: futures_util::stream::FuturesUnordered<_> =
+        targets.into_iter().map(send).collect();
+    let deadline = Instant::now() + Duration::from_secs(proxy_timeout);
+    let mut fallback = None;
+    let mut errors = Vec::new();
+    let mut resp = loop {
+        match timeout_at(deadline, pending.next()).await {
+            Ok(Some((label, true, Ok((parts, body))))) => {
+                counter!("rpc_broadcast_wins_total", "backend" => label.clone()).increment(1);
+                let dedup = Duration::from_secs(router_state.broadcast.dedup_secs);
+                if !dedup.is_zero() {
+                    state.broadcast_signatures.insert(&tx.signature, dedup);
+                }
+                tokio::spawn(pending.collect::<Vec<_>>());
+                let mut resp = Response::from_parts(parts, Body::from(body));
+                resp.extensions_mut().insert(SelectedBackend(label));
+                break resp;
+            }
+            Ok(Some((label, _, Ok(answer)))) => {
+                fallback.get_or_insert((label, answer));
+            }
+            Ok(Some((_, _, Err(err)))) => errors.push(err),
+            Ok(None) | Err(_) => {
+                tokio::spawn(pending.collect::<Vec<_>>());
+                if let Some((label, (parts, body))) = fallback {
+                    let mut resp = Response::from_parts(parts, Body::from(body));
+                    resp.extensions_mut().insert(SelectedBackend(label));
+                    break resp;
+                }
+                let err = if errors.is_empty() {
+                    format!("Upstream request timed out after {}s", proxy_timeout)
+                } else {
+                    format!("Proxy error: {}", errors.join("; "))
+                };
+                info!("Broadcast of {} failed: {}", tx.signature, err);
+                capture_dead_letter(
+                    state,
+                    dead_letter_body,
+                    client_owner.as_ref(),
+                    None,
+                    StatusCode::BAD_GATEWAY,
+                    &err,
+                );
+                break (StatusCode::BAD_GATEWAY, err).into_response();
             }
         }
     };
+    if let Some(owner) = client_owner {
+        resp.extensions_mut().insert(owner);
+    }
+    resp
+}
 
-    // Store owner in request extensions for metrics middleware
-    req.extensions_mut().insert(ClientOwner(owner));
+/// A recently cached result for `lookup`, served with `x-served-stale: true` and
+/// its `age` when stale serving is enabled and the result is recent enough.
+async fn stale_response(
+    state: &AppState,
+    router_state: &RouterState,
+    lookup: Option<&CacheLookup>,
+) -> Option<Response> {
+    let lookup = lookup?;
+    let max_age = Duration::from_secs(router_state.cache.serve_stale_secs);
+    if max_age.is_zero() {
+        return None;
+    }
+    let (result, age) = state.response_cache.get_stale(lookup, max_age).await?;
+    warn!(
+        "Serving stale {} response ({}s old): no healthy backends",
+        lookup.method,
+        age.as_secs()
+    );
+    counter!("rpc_stale_responses_total", "rpc_method" => lookup.method.clone()).increment(1);
+    let mut resp = (
+        [
+            ("content-type", "application/json".to_string()),
+            ("x-served-stale", "true".to_string()),
+            ("age", age.as_secs().to_string()),
+        ],
+        lookup.response(&result),
+    )
+        .into_response();
+    resp.extensions_mut()
+        .insert(SelectedBackend("stale".to_string()));
+    Some(resp)
+}
 
-    // Get RPC method from extension (set by extract_rpc_method middleware)
-    let rpc_method = req.extensions().get::<RpcMethod>().map(|m| m.0.as_str());
+/// Stream the upstream body of `resp` (`size` bytes) to the object store and
+/// answer with a presigned URL for it instead. The upstream body is consumed
+/// either way, so a failed upload is a `502`.
+async fn offload_response(
+    state: &AppState,
+    config: &OffloadConfig,
+    resp: Response<hyper::body::Incoming>,
+    size: u64,
+    backend_label: &str,
+) -> Response {
+    let now = unix_now();
+    let key = object_key(config, now);
+    let (parts, body) = resp.into_parts();
+    let uploaded = match put_request(
+        config,
+        &key,
+        now,
+        Body::new(body),
+        size,
+        parts.headers.get("content-type"),
+        parts.headers.get("content-encoding"),
+    ) {
+        Ok(request) => upload(&state.client, config, request).await,
+        Err(e) => Err(e),
+    };
+    match uploaded.and_then(|_| presign_get(config, &key, now)) {
+        Ok(url) => {
+            counter!("rpc_offloaded_responses_total", "backend" => backend_label.to_string())
+                .increment(1);
+            counter!("rpc_offloaded_bytes_total", "backend" => backend_label.to_string())
+                .increment(size);
+            let body = serde_json::json!({
+                "url": url,
+                "expires_at": now + config.url_expiry_secs,
+                "size_bytes": size,
+            });
+            ([(OFFLOADED_HEADER, "true")], Json(body)).into_response()
+        }
+        Err(e) => {
+            warn!(
+                "Failed to offload response from {}: {}",
+                backend_label,
+                redact(&e)
+            );
+            counter!("rpc_offload_failures_total", "backend" => backend_label.to_string())
+                .increment(1);
+            (StatusCode::BAD_GATEWAY, "Failed to offload response").into_response()
+        }
+    }
+}
 
-    // Select backend based on method routing or weighted random
-    let (backend_label, backend_url) = match state.select_backend(rpc_method) {
-        Some(selection) => selection,
-        None => {
-            tracing::error!("No healthy backends available for request");
-            return (
-                StatusCode::SERVICE_UNAVAILABLE,
-                "No healthy backends available",
-            )
-                .into_response();
+/// Buffer a successful upstream response to a cacheable request and store its
+/// result for later requests.
+async fn cache_response(
+    state: &AppState,
+    resp: Response<hyper::body::Incoming>,
+    lookup: &CacheLookup,
+    backend_label: &str,
+) -> Response {
+    let (parts, body) = resp.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(err) => {
+            let err = redact(&err.to_string()).into_owned();
+            info!("Backend response failed: {}", err);
+            return (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", err)).into_response();
         }
     };
+    store_response(state, parts, body, lookup, backend_label).await
+}
 
-    // Rebuild URI: strip api-key from query params while preserving others
-    let path = req.uri().path();
-    let cleaned_query = req
-        .uri()
-        .query()
-        .map(|q| {
-            q.split('&')
-                .filter(|p| !p.starts_with("api-key="))
-                .collect::<Vec<_>>()
-                .join("&")
-        })
-        .unwrap_or_default();
+/// Store a complete `body` from `backend_label` for `lookup` and return it to
+/// the client. Results to cross-check are only stored once another backend
+/// agrees with them.
+async fn store_response(
+    state: &AppState,
+    mut parts: axum::http::response::Parts,
+    body: Bytes,
+    lookup: &CacheLookup,
+    backend_label: &str,
+) -> Response {
+    if let Some((cached, slot)) = &lookup.revalidating {
+        let extended = min_context_slot_not_reached(&body);
+        counter!(
+            "rpc_cache_slot_revalidations_total",
+            "rpc_method" => lookup.method.clone(),
+            "result" => if extended { "extended" } else { "refreshed" }
+        )
+        .increment(1);
+        if extended {
+            state
+                .response_cache
+                .extend(lookup, cached.clone(), *slot)
+                .await;
+            parts.headers.remove("content-length");
+            parts.headers.insert(
+                "x-cache",
+                axum::http::HeaderValue::from_static("REVALIDATED"),
+            );
+            return Response::from_parts(parts, Body::from(lookup.response(cached)));
+        }
+    }
+    let confirmed = lookup.cross_check.is_none()
+        || network_stats::confirm(state, lookup, backend_label, &body).await;
+    if confirmed && state.response_cache.store(lookup, &body).await && lookup.tuned {
+        state.ttl_tuner.record_store(&lookup.method, lookup.ttl);
+    }
+    // Outage-only entries are remembered, not cached
+    if lookup.policy != CachePolicy::OutageOnly {
+        counter!("rpc_cache_misses_total", "rpc_method" => lookup.method.clone()).increment(1);
+        parts
+            .headers
+            .insert("x-cache", axum::http::HeaderValue::from_static("MISS"));
+    }
+    Response::from_parts(parts, Body::from(body))
+}
 
-    let cleaned_request_path = if cleaned_query.is_empty() {
-        path.to_string()
-    } else {
-        format!("{}?{}", path, cleaned_query)
+/// A request as received from the client, kept so it can be re-sent to another
+/// backend when its response is damaged or its backend fails it.
+struct StoredRequest {
+    method: axum::http::Method,
+    uri: axum::http::Uri,
+    headers: HeaderMap,
+    body: Bytes,
+    rpc_method: Option<String>,
+}
+
+impl StoredRequest {
+    /// The request addressed to `backend`, signed if the backend requires it.
+    /// `None` if the backend's URL cannot be used.
+    fn to_backend(&self, backend: &RuntimeBackend) -> Option<Request<Body>> {
+        let target = backend.target.as_ref()?;
+        let uri = match backend.uri_for(
+            self.rpc_method.as_deref(),
+            self.uri.path(),
+            self.uri.query(),
+        ) {
+            Ok(uri) => uri,
+            Err(e) => {
+                error!(
+                    "Failed to build URI for backend '{}': {}",
+                    backend.config.label,
+                    redact(&e)
+                );
+                return None;
+            }
+        };
+        let mut headers = self.headers.clone();
+        headers.insert("host", target.host.clone());
+        apply_backend_headers(&backend.config, &mut headers);
+        if let Some(signing) = &backend.config.signing {
+            apply_signature(signing, &mut headers, &self.body, unix_now());
+        }
+        let mut req = Request::new(Body::from(self.body.clone()));
+        *req.method_mut() = self.method.clone();
+        *req.uri_mut() = uri;
+        *req.headers_mut() = headers;
+        Some(req)
+    }
+}
+
+/// Read `resp` in full and check it against its `Content-Length` and digest
+/// before passing it on. A damaged response is re-sent once to another backend
+/// in rotation, annotated with `x-rpc-router-retry: integrity`; if that fails
+/// too, or there is no other backend, the client gets a 502 rather than a
+/// partial body.
+async fn verified_response(
+    state: &AppState,
+    router_state: &RouterState,
+    resp: Response<hyper::body::Incoming>,
+    retry: &StoredRequest,
+    backend_label: &str,
+    cache_lookup: Option<&CacheLookup>,
+) -> Response {
+    let config = &router_state.integrity;
+    let (parts, body) = resp.into_parts();
+    let failure = match buffer(body, config.max_bytes).await {
+        Ok(Buffered::Complete(body)) => match verify(&parts.headers, &body, config) {
+            Ok(()) => {
+                return match cache_lookup {
+                    Some(lookup) => store_response(state, parts, body, lookup, backend_label).await,
+                    None => Response::from_parts(parts, Body::from(body)),
+                }
+            }
+            Err(failure) => failure,
+        },
+        Ok(Buffered::TooLarge(body)) => return Response::from_parts(parts, body),
+        Err(failure) => failure,
     };
+    counter!(
+        "rpc_response_integrity_failures_total",
+        "backend" => backend_label.to_string(),
+        "reason" => failure.as_str()
+    )
+    .increment(1);
+    warn!(
+        "Response from '{}' failed verification ({}), retrying elsewhere",
+        backend_label,
+        failure.as_str()
+    );
 
-    // Build URI with selected backend
-    let uri_string = if cleaned_request_path == "/" {
-        // For root path requests, don't add trailing slash
-        backend_url.trim_end_matches('/').to_string()
-    } else if backend_url.ends_with('/') && cleaned_request_path.starts_with('/') {
-        // Avoid double slashes
-        format!("{}{}", backend_url, &cleaned_request_path[1..])
-    } else {
-        format!("{}{}", backend_url, cleaned_request_path)
+    let Some(other) =
+        router_state.select_retry_backend(retry.rpc_method.as_deref(), &[backend_label])
+    else {
+        return (
+            StatusCode::BAD_GATEWAY,
+            format!("Proxy error: {} response from backend", failure.as_str()),
+        )
+            .into_response();
+    };
+    let other_label = other.config.label.as_str();
+    let Some(req) = retry.to_backend(other) else {
+        return (StatusCode::BAD_GATEWAY, "Proxy error: no backend to retry").into_response();
     };
 
-    // Ensure we have a valid URI
-    let parsed_uri = match uri_string.parse::<Uri>() {
-        Ok(uri) => uri,
-        Err(e) => {
-            error!("Failed to parse backend URI '{}': {}", uri_string, e);
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Invalid backend configuration",
+    let proxy_timeout = router_state.proxy_timeout_secs;
+    let outcome = match timeout(
+        Duration::from_secs(proxy_timeout),
+        state.client_for(&other.config, false).request(req),
+    )
+    .await
+    {
+        Ok(Ok(retried)) if retried.status() == StatusCode::OK => {
+            let (parts, body) = retried.into_parts();
+            match buffer(body, config.max_bytes).await {
+                Ok(Buffered::Complete(body)) => verify(&parts.headers, &body, config)
+                    .map(|()| Response::from_parts(parts, Body::from(body))),
+                Ok(Buffered::TooLarge(body)) => Ok(Response::from_parts(parts, body)),
+                Err(failure) => Err(failure),
+            }
+            .map_err(|failure| failure.as_str().to_string())
+        }
+        Ok(Ok(retried)) => Ok(retried.into_response()),
+        Ok(Err(err)) => Err(redact(&err.to_string()).into_owned()),
+        Err(_) => Err(format!("timed out after {}s", proxy_timeout)),
+    };
+    counter!("rpc_response_integrity_retries_total", "backend" => other_label.to_string())
+        .increment(1);
+    let mut resp = match outcome {
+        Ok(mut resp) => {
+            resp.headers_mut().insert(
+                "x-rpc-router-retry",
+                axum::http::HeaderValue::from_static("integrity"),
+            );
+            resp
+        }
+        Err(err) => {
+            info!("Integrity retry to '{}' failed: {}", other_label, err);
+            (
+                StatusCode::BAD_GATEWAY,
+                format!("Proxy error: {} response from backend", failure.as_str()),
             )
-                .into_response();
+                .into_response()
         }
     };
+    resp.extensions_mut()
+        .insert(SelectedBackend(other_label.to_string()));
+    resp
+}
 
-    // Update Host header to match the backend
-    if let Some(host) = parsed_uri.host() {
-        let host_value = if let Some(port) = parsed_uri.port_u16() {
-            format!("{}:{}", host, port)
-        } else {
-            host.to_string()
-        };
-        req.headers_mut()
-            .insert("host", host_value.parse().unwrap());
+/// A `getBlock`/`getTransaction` request rewritten with an explicit
+/// `maxSupportedTransactionVersion`, sent only if the original is rejected.
+struct VersionRetry {
+    method: axum::http::Method,
+    uri: axum::http::Uri,
+    headers: HeaderMap,
+    body: Bytes,
+    version: u8,
+}
+
+/// Largest response that can still be the unsupported-version error. Anything
+/// bigger is a real block or transaction and is streamed through untouched.
+const VERSION_ERROR_MAX_BYTES: u64 = 1024;
+
+/// Re-send `retry` to the same backend if `resp` is the unsupported
+/// transaction version error. The retried response is annotated with
+/// `x-rpc-router-retry`; if the retry itself fails, the original error is returned.
+async fn retry_transaction_version(
+    state: &AppState,
+    resp: Response<hyper::body::Incoming>,
+    retry: VersionRetry,
+    backend: &Backend,
+    proxy_timeout: u64,
+) -> Response {
+    let backend_label = backend.label.as_str();
+    let content_length = resp
+        .headers()
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|len| len > VERSION_ERROR_MAX_BYTES) {
+        return resp.into_response();
     }
 
-    *req.uri_mut() = parsed_uri;
+    // Without a length (chunked), read just past the limit; a longer response
+    // goes on as the bytes read followed by the rest as it arrives
+    let (parts, mut body) = resp.into_parts();
+    let mut read = BytesMut::new();
+    while read.len() as u64 <= VERSION_ERROR_MAX_BYTES {
+        match body.frame().await {
+            None => break,
+            Some(Ok(frame)) => {
+                if let Ok(chunk) = frame.into_data() {
+                    read.extend_from_slice(&chunk);
+                }
+            }
+            Some(Err(err)) => {
+                let err = redact(&err.to_string()).into_owned();
+                info!("Backend response failed: {}", err);
+                return (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", err)).into_response();
+            }
+        }
+    }
+    let original = read.freeze();
+    if original.len() as u64 > VERSION_ERROR_MAX_BYTES {
+        return Response::from_parts(parts, Body::new(PrefixedBody::new(original, body)));
+    }
+    if !is_unsupported_version_error(&original) {
+        return Response::from_parts(parts, Body::from(original));
+    }
 
-    // Capture owner before request is consumed
-    let client_owner = req.extensions().get::<ClientOwner>().cloned();
+    let VersionRetry {
+        method,
+        uri,
+        mut headers,
+        body,
+        version,
+    } = retry;
+    // Let hyper recompute the length of the rewritten body
+    headers.remove("content-length");
+    apply_backend_headers(backend, &mut headers);
+    if let Some(signing) = &backend.signing {
+        apply_signature(signing, &mut headers, &body, unix_now());
+    }
+    let mut req = Request::new(Body::from(body));
+    *req.method_mut() = method;
+    *req.uri_mut() = uri;
+    *req.headers_mut() = headers;
 
-    // Forward request
-    let proxy_timeout = state.state.load().proxy_timeout_secs;
     let result = timeout(
         Duration::from_secs(proxy_timeout),
-        state.client.request(req),
+        state.client_for(backend, false).request(req),
     )
     .await;
 
     match result {
-        Ok(Ok(mut resp)) => {
-            // Store selected backend label and owner in response extensions for logging/metrics
-            resp.extensions_mut()
-                .insert(SelectedBackend(backend_label.to_string()));
-            if let Some(owner) = client_owner {
-                resp.extensions_mut().insert(owner);
+        Ok(Ok(retried)) => {
+            counter!("rpc_transaction_version_retries_total", "backend" => backend_label.to_string())
+                .increment(1);
+            let mut resp = retried.into_response();
+            if let Ok(value) = format!("maxSupportedTransactionVersion={}", version).parse() {
+                resp.headers_mut().insert("x-rpc-router-retry", value);
             }
-            resp.into_response()
+            resp
         }
         Ok(Err(err)) => {
-            info!("Backend request failed: {} (error type: {:?})", err, err);
-            let mut resp =
-                (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", err)).into_response();
-            resp.extensions_mut()
-                .insert(SelectedBackend(backend_label.to_string()));
-            if let Some(owner) = client_owner {
-                resp.extensions_mut().insert(owner);
-            }
-            resp
+            info!(
+                "Transaction version retry to '{}' failed: {}",
+                backend_label,
+                redact(&err.to_string())
+            );
+            Response::from_parts(parts, Body::from(original))
         }
         Err(_) => {
-            let mut resp = (
-                StatusCode::GATEWAY_TIMEOUT,
-                format!("Upstream request timed out after {}s", proxy_timeout),
-            )
-                .into_response();
-            resp.extensions_mut()
-                .insert(SelectedBackend(backend_label.to_string()));
-            if let Some(owner) = client_owner {
-                resp.extensions_mut().insert(owner);
-            }
-            resp
+            info!(
+                "Transaction version retry to '{}' timed out after {}s",
+                backend_label, proxy_timeout
+            );
+            Response::from_parts(parts, Body::from(original))
         }
     }
 }
@@ -303,10 +3291,16 @@ pub struct HealthResponse {
 pub struct BackendHealth {
     pub label: String,
     pub healthy: bool,
+    pub status: HealthLevel,
     pub last_check: Option<String>,
     pub consecutive_failures: u32,
     pub consecutive_successes: u32,
+    /// Proxied requests failed in a row (`health_check.passive_failures_threshold`)
+    pub consecutive_request_failures: u32,
     pub last_error: Option<String>,
+    pub circuit: CircuitState,
+    /// Genesis hash reported by a backend quarantined on another cluster
+    pub genesis_mismatch: Option<String>,
 }
 
 pub async fn health_endpoint(State(state): State<Arc<AppState>>) -> impl IntoResponse {
@@ -314,7 +3308,7 @@ pub async fn health_endpoint(State(state): State<Arc<AppState>>) -> impl IntoRes
     let all_statuses = current_state.health_state.get_all_statuses();
 
     let mut backends = Vec::new();
-    let mut any_healthy = false;
+    let mut best_level = HealthLevel::Unhealthy;
 
     for backend in &current_state.backends {
         let status = all_statuses
@@ -322,21 +3316,31 @@ pub async fn health_endpoint(State(state): State<Arc<AppState>>) -> impl IntoRes
             .cloned()
             .unwrap_or_default();
 
-        if status.healthy {
-            any_healthy = true;
-        }
+        best_level = match (best_level, status.level()) {
+            (HealthLevel::Healthy, _) | (_, HealthLevel::Healthy) => HealthLevel::Healthy,
+            (HealthLevel::Degraded, _) | (_, HealthLevel::Degraded) => HealthLevel::Degraded,
+            _ => HealthLevel::Unhealthy,
+        };
 
         backends.push(BackendHealth {
             label: backend.config.label.clone(),
             healthy: status.healthy,
+            status: status.level(),
             last_check: status.last_check_time.map(|t| format!("{:?}", t)),
             consecutive_failures: status.consecutive_failures,
             consecutive_successes: status.consecutive_successes,
-            last_error: status.last_error,
+            consecutive_request_failures: status.consecutive_request_failures,
+            last_error: status.last_error.map(|e| redact(&e).into_owned()),
+            circuit: backend.circuit.state(unix_now()),
+            genesis_mismatch: backend.identity.mismatch(&backend.config.url),
         });
     }
 
-    let overall_status = if any_healthy { "healthy" } else { "unhealthy" };
+    let overall_status = match best_level {
+        HealthLevel::Healthy => "healthy",
+        HealthLevel::Degraded => "degraded",
+        HealthLevel::Unhealthy => "unhealthy",
+    };
 
     let response = HealthResponse {
         overall_status: overall_status.to_string(),
@@ -346,70 +3350,168 @@ pub async fn health_endpoint(State(state): State<Arc<AppState>>) -> impl IntoRes
     Json(response)
 }
 
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    /// Backends in rotation / total, per required group (or "any" without groups)
+    pub groups: BTreeMap<String, GroupReadiness>,
+}
+
+#[derive(Serialize)]
+pub struct GroupReadiness {
+    pub in_rotation: usize,
+    pub total: usize,
+}
+
+/// `GET /readyz`: 200 when every group in `readiness.required_groups` has a
+/// backend in rotation (any backend when no groups are required), else 503.
+pub async fn readyz_endpoint(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let current_state = state.state.load();
+    let count = |in_group: &dyn Fn(&Backend) -> bool| GroupReadiness {
+        in_rotation: current_state
+            .backends
+            .iter()
+            .filter(|b| in_group(&b.config) && b.in_rotation())
+            .count(),
+        total: current_state
+            .backends
+            .iter()
+            .filter(|b| in_group(&b.config))
+            .count(),
+    };
+
+    let required = &current_state.readiness.required_groups;
+    let groups: BTreeMap<String, GroupReadiness> = if required.is_empty() {
+        BTreeMap::from([("any".to_string(), count(&|_| true))])
+    } else {
+        required
+            .iter()
+            .map(|group| (group.clone(), count(&|b| b.groups.contains(group))))
+            .collect()
+    };
+
+    let ready = groups.values().all(|g| g.in_rotation > 0);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(ReadinessResponse { ready, groups }))
+}
+
+/// `GET /v1/rpc-discovery`: machine-readable description of supported methods.
+pub async fn discovery_endpoint(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(discovery_document(&state.state.load(), MAX_BODY_SIZE))
+}
+
 pub async fn ws_proxy(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
     Query(params): Query<Params>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    let api_key = match params.api_key {
+    let addr = canonical_addr(addr);
+    let api_keys = match params.api_key {
         Some(k) => k,
         None => {
             info!("WebSocket: No API key provided from {}", addr);
             counter!("ws_connections_total", "backend" => "none", "owner" => "none", "status" => "auth_failed").increment(1);
-            return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+            return router_error(&state, RouterError::Unauthorized);
         }
     };
 
-    // Validate API key
-    let owner = match state.keystore.validate_key(&api_key).await {
-        Ok(Some(info)) => {
-            info.owner
-        }
+    // Validate API key, or the first usable one of a fallback list. Probes are
+    // not rate limited.
+    let validated = if state.state.load().probes.is_probe(addr.ip(), &api_keys) {
+        lookup_key_list(state.keystore.as_ref(), &api_keys).await
+    } else {
+        validate_key_list(state.keystore.as_ref(), &api_keys).await
+    };
+    let key_info = match validated {
+        Ok(Some((_, info))) => info,
         Ok(None) => {
-            info!("WebSocket: Invalid API key from {} (prefix={}...)", addr, &api_key[..api_key.len().min(6)]);
+            info!(
+                "WebSocket: Invalid API key from {} (key={})",
+                addr,
+                key_fingerprint(&api_keys)
+            );
             counter!("ws_connections_total", "backend" => "none", "owner" => "none", "status" => "auth_failed").increment(1);
-            return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+            return router_error(&state, RouterError::Unauthorized);
         }
         Err(e) => {
-            if e == "Rate limit exceeded" {
+            if e == KeyStoreError::RateLimited {
                 warn!(
-                    "WebSocket: API key rate limited from {} (prefix={}...)",
-                    addr, &api_key[..api_key.len().min(6)]
+                    "WebSocket: API key rate limited from {} (key={})",
+                    addr,
+                    key_fingerprint(&api_keys)
                 );
                 counter!("ws_connections_total", "backend" => "none", "owner" => "none", "status" => "rate_limited").increment(1);
-                return (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded").into_response();
+                return router_error(&state, RouterError::RateLimited);
             }
-            error!("WebSocket: Key validation error: {}", e);
             counter!("ws_connections_total", "backend" => "none", "owner" => "none", "status" => "error").increment(1);
-            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response();
+            return key_error_response(&state, "WebSocket: ", &e);
         }
     };
 
+    if key_info.kind == KeyKind::Browser {
+        if let Err(rejection) = check_browser_request(
+            &key_info,
+            &state.state.load().browser_keys,
+            &state.origin_limiter,
+            &headers,
+            addr.ip(),
+            None,
+        ) {
+            counter!("ws_connections_total", "backend" => "none", "owner" => key_info.owner.clone(), "status" => "browser_rejected").increment(1);
+            return browser_rejection_response(&state, rejection, &key_info.owner);
+        }
+    }
+    let owner = key_info.owner.clone();
+
     // Select a backend with WebSocket support
     let (backend_label, backend_ws_url) = match state.select_ws_backend() {
         Some(selection) => selection,
         None => {
-            error!("No healthy WebSocket backends available");
             counter!("ws_connections_total", "backend" => "none", "owner" => owner.clone(), "status" => "no_backend").increment(1);
-            return (
-                StatusCode::SERVICE_UNAVAILABLE,
-                "No healthy WebSocket backends available",
-            )
-                .into_response();
+            return no_ws_backend_response(&state);
         }
     };
 
     let backend_label = backend_label.to_string();
     let backend_ws_url = backend_ws_url.to_string();
+    let router_state = state.state.load_full();
+    let selected = router_state
+        .backends
+        .iter()
+        .find(|b| b.config.label == backend_label);
+    if let Some(backend) = selected.filter(|b| router_state.is_cross_region(b)) {
+        counter!(
+            "ws_cross_region_connections_total",
+            "region" => backend.config.region.clone().unwrap_or_default(),
+            "backend" => backend_label.clone()
+        )
+        .increment(1);
+    }
+    let upstream = selected.map(|b| b.config.clone());
 
-    info!(
-        "WebSocket: {} upgrading connection, backend={}, owner={}",
-        addr, backend_label, owner
-    );
+    if key_info.privacy.allows_metadata() {
+        info!(
+            "WebSocket: {} upgrading connection, backend={}, owner={}",
+            addr, backend_label, owner
+        );
+    }
 
     ws.on_upgrade(move |client_socket| {
-        handle_ws_connection(client_socket, backend_ws_url, backend_label, owner, addr)
+        handle_ws_connection(
+            client_socket,
+            backend_ws_url,
+            upstream,
+            backend_label,
+            key_info,
+            addr,
+            state,
+        )
     })
     .into_response()
 }
@@ -417,17 +3519,34 @@ pub async fn ws_proxy(
 async fn handle_ws_connection(
     client_socket: WebSocket,
     backend_url: String,
+    upstream: Option<Backend>,
     backend_label: String,
-    owner: String,
+    key_info: KeyInfo,
     client_addr: SocketAddr,
+    state: Arc<AppState>,
 ) {
-    // Connect to the backend WebSocket
-    let backend_socket = match connect_async(&backend_url).await {
+    let logged = key_info.privacy.allows_metadata();
+    let owner = key_info.owner.clone();
+    // Connect to the backend WebSocket with its headers, signing the
+    // (empty-bodied) handshake if required
+    let connect = async {
+        let mut request = backend_url.as_str().into_client_request()?;
+        if let Some(upstream) = &upstream {
+            apply_backend_headers(upstream, request.headers_mut());
+            if let Some(signing) = &upstream.signing {
+                apply_signature(signing, request.headers_mut(), b"", unix_now());
+            }
+        }
+        connect_async(request).await
+    };
+    let backend_socket = match connect.await {
         Ok((socket, _)) => socket,
         Err(e) => {
             error!(
                 "WebSocket: Failed to connect to backend {} ({}): {}",
-                backend_label, backend_url, e
+                backend_label,
+                redact_url(&backend_url),
+                redact(&e.to_string())
             );
             counter!("ws_connections_total", "backend" => backend_label, "owner" => owner, "status" => "backend_connect_failed").increment(1);
             return;
@@ -435,18 +3554,37 @@ async fn handle_ws_connection(
     };
 
     counter!("ws_connections_total", "backend" => backend_label.clone(), "owner" => owner.clone(), "status" => "connected").increment(1);
-    gauge!("ws_active_connections", "backend" => backend_label.clone(), "owner" => owner.clone()).increment(1.0);
+    gauge!("ws_active_connections", "backend" => backend_label.clone(), "owner" => owner.clone())
+        .increment(1.0);
     let connect_time = std::time::Instant::now();
 
-    info!(
-        "WebSocket: {} connected to backend {}",
-        client_addr, backend_label
-    );
+    if logged {
+        info!(
+            "WebSocket: {} connected to backend {}",
+            client_addr, backend_label
+        );
+    }
+
+    let config = state.state.load().websocket.clone();
 
     // Split both connections
     let (mut client_write, mut client_read) = client_socket.split();
     let (mut backend_write, mut backend_read) = backend_socket.split();
 
+    // Backend messages wait here until the client accepts them
+    let queue = ClientQueue::new(config.max_queued_messages);
+    let activity = ConnectionActivity::new();
+    let send_timeout = Duration::from_secs(config.slow_consumer_timeout_secs);
+    // Slot and root subscriptions answered here rather than upstream, if enabled
+    let local = config
+        .local_slot_subscriptions
+        .then(|| std::sync::Mutex::new(LocalSubscriptions::new(config.slot_commitment)));
+    // The key's limits on vote/block/slot-update notifications, if it has any
+    let mut throttler =
+        (!key_info.firehose.is_empty()).then(|| FirehoseThrottler::new(key_info.firehose));
+    // Subscriptions the key may not open are refused here
+    let restricted = key_info.restricts_methods();
+
     // Clones for use inside async blocks
     let bl1 = backend_label.clone();
     let ow1 = owner.clone();
@@ -456,101 +3594,200 @@ async fn handle_ws_connection(
     // Forward client -> backend
     let client_to_backend = async {
         while let Some(msg) = client_read.next().await {
-            match msg {
+            let forward = match msg {
                 Ok(Message::Text(text)) => {
-                    counter!("ws_messages_total", "backend" => bl1.clone(), "owner" => ow1.clone(), "direction" => "client_to_backend").increment(1);
-                    if backend_write
-                        .send(TungsteniteMessage::Text(text))
-                        .await
-                        .is_err()
+                    activity.data();
+                    if let Some(denied) = restricted
+                        .then(|| method_acl::check(text.as_bytes(), &key_info))
+                        .flatten()
                     {
-                        break;
+                        counter!("ws_method_denied_total", "owner" => ow1.clone()).increment(1);
+                        queue.push(Message::Text(denied.body));
+                        continue;
                     }
-                }
-                Ok(Message::Binary(data)) => {
-                    counter!("ws_messages_total", "backend" => bl1.clone(), "owner" => ow1.clone(), "direction" => "client_to_backend").increment(1);
-                    if backend_write
-                        .send(TungsteniteMessage::Binary(data))
-                        .await
-                        .is_err()
-                    {
-                        break;
+                    if let Some(local) = &local {
+                        let response = local
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .handle_request(&text);
+                        if let Some(response) = response {
+                            queue.push(Message::Text(response));
+                            continue;
+                        }
                     }
+                    counter!("ws_messages_total", "backend" => bl1.clone(), "owner" => ow1.clone(), "direction" => "client_to_backend").increment(1);
+                    TungsteniteMessage::Text(text)
                 }
-                Ok(Message::Ping(data)) => {
-                    if backend_write
-                        .send(TungsteniteMessage::Ping(data))
-                        .await
-                        .is_err()
+                Ok(Message::Binary(data)) => {
+                    if let Some(denied) = restricted
+                        .then(|| method_acl::check(&data, &key_info))
+                        .flatten()
                     {
-                        break;
+                        counter!("ws_method_denied_total", "owner" => ow1.clone()).increment(1);
+                        queue.push(Message::Text(denied.body));
+                        continue;
                     }
+                    counter!("ws_messages_total", "backend" => bl1.clone(), "owner" => ow1.clone(), "direction" => "client_to_backend").increment(1);
+                    activity.data();
+                    TungsteniteMessage::Binary(data)
                 }
-                Ok(Message::Pong(data)) => {
-                    if backend_write
-                        .send(TungsteniteMessage::Pong(data))
-                        .await
-                        .is_err()
-                    {
-                        break;
-                    }
+                Ok(Message::Ping(data)) => TungsteniteMessage::Ping(data),
+                // Answers to the router's own heartbeat stay here
+                Ok(Message::Pong(data)) if data == HEARTBEAT_PAYLOAD => {
+                    activity.pong();
+                    continue;
                 }
+                Ok(Message::Pong(data)) => TungsteniteMessage::Pong(data),
                 Ok(Message::Close(_)) | Err(_) => break,
+            };
+            if backend_write.send(forward).await.is_err() {
+                return "backend_closed";
             }
         }
+        "client_closed"
     };
 
-    // Forward backend -> client
-    let backend_to_client = async {
+    // Forward backend -> queue
+    let backend_to_queue = async {
         while let Some(msg) = backend_read.next().await {
-            match msg {
+            let message = match msg {
                 Ok(TungsteniteMessage::Text(text)) => {
                     counter!("ws_messages_total", "backend" => bl2.clone(), "owner" => ow2.clone(), "direction" => "backend_to_client").increment(1);
-                    if client_write.send(Message::Text(text)).await.is_err() {
-                        break;
+                    activity.data();
+                    if let Some(throttler) = &mut throttler {
+                        if !throttler.admit(&text, std::time::Instant::now()) {
+                            counter!("ws_throttled_notifications_total", "backend" => bl2.clone(), "owner" => ow2.clone()).increment(1);
+                            continue;
+                        }
                     }
+                    Message::Text(text)
                 }
                 Ok(TungsteniteMessage::Binary(data)) => {
                     counter!("ws_messages_total", "backend" => bl2.clone(), "owner" => ow2.clone(), "direction" => "backend_to_client").increment(1);
-                    if client_write.send(Message::Binary(data)).await.is_err() {
-                        break;
-                    }
+                    activity.data();
+                    Message::Binary(data)
                 }
-                Ok(TungsteniteMessage::Ping(data)) => {
-                    if client_write.send(Message::Ping(data)).await.is_err() {
-                        break;
+                Ok(TungsteniteMessage::Ping(data)) => Message::Ping(data),
+                Ok(TungsteniteMessage::Pong(data)) => Message::Pong(data),
+                Ok(TungsteniteMessage::Close(_)) | Ok(TungsteniteMessage::Frame(_)) | Err(_) => {
+                    break
+                }
+            };
+            if queue.push(message) {
+                counter!("ws_dropped_notifications_total", "backend" => bl2.clone(), "owner" => ow2.clone()).increment(1);
+            }
+        }
+        "backend_closed"
+    };
+
+    // Fan the shared slot feed out to this client's local subscriptions
+    let slot_notifications = async {
+        if let Some(local) = &local {
+            let mut slots = state.slot_feed.subscribe();
+            while slots.changed().await.is_ok() {
+                let view = *slots.borrow_and_update();
+                let notifications = local
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .notifications(&view);
+                for notification in notifications {
+                    if queue.push(Message::Text(notification)) {
+                        counter!("ws_dropped_notifications_total", "backend" => bl2.clone(), "owner" => ow2.clone()).increment(1);
                     }
                 }
-                Ok(TungsteniteMessage::Pong(data)) => {
-                    if client_write.send(Message::Pong(data)).await.is_err() {
-                        break;
+            }
+        }
+        std::future::pending().await
+    };
+
+    // Drain queue -> client, with heartbeats and the idle timeout
+    let queue_to_client = async {
+        let ping_interval = Duration::from_secs(config.ping_interval_secs);
+        let pong_timeout = Duration::from_secs(config.pong_timeout_secs);
+        let idle_timeout_ms = config.idle_timeout_secs * 1000;
+        let mut next_ping = Instant::now() + ping_interval;
+        // When the outstanding heartbeat was sent, as an `Instant` and in activity ms
+        let mut awaiting_pong: Option<(Instant, u64)> = None;
+
+        loop {
+            while let Some(message) = queue.pop() {
+                match timeout(send_timeout, client_write.send(message)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(_)) => return "client_closed",
+                    Err(_) => return "slow_consumer",
+                }
+            }
+
+            let heartbeat_at = match awaiting_pong {
+                Some((sent, _)) => sent + pong_timeout,
+                None => next_ping,
+            };
+            let idle_at = Instant::now()
+                + Duration::from_millis(
+                    (activity.last_data_ms() + idle_timeout_ms).saturating_sub(activity.now_ms()),
+                );
+
+            tokio::select! {
+                _ = queue.pushed() => {}
+                _ = tokio::time::sleep_until(heartbeat_at), if config.ping_interval_secs > 0 => {
+                    if let Some((_, sent_ms)) = awaiting_pong.take() {
+                        if activity.last_pong_ms() < sent_ms {
+                            return "missed_pong";
+                        }
+                        continue;
                     }
+                    let ping = Message::Ping(HEARTBEAT_PAYLOAD.to_vec());
+                    match timeout(send_timeout, client_write.send(ping)).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(_)) => return "client_closed",
+                        Err(_) => return "slow_consumer",
+                    }
+                    let sent = Instant::now();
+                    awaiting_pong = Some((sent, activity.now_ms()));
+                    next_ping = sent + ping_interval;
                 }
-                Ok(TungsteniteMessage::Close(_)) | Ok(TungsteniteMessage::Frame(_)) | Err(_) => {
-                    break
+                _ = tokio::time::sleep_until(idle_at), if idle_timeout_ms > 0 => {
+                    if activity.now_ms() >= activity.last_data_ms() + idle_timeout_ms {
+                        return "idle";
+                    }
                 }
             }
         }
     };
 
-    // Run both directions concurrently, stop when either ends
-    tokio::select! {
-        _ = client_to_backend => {
-            // Client side ended; send close to backend
+    // Run all directions concurrently, stop when any ends
+    let reason = tokio::select! {
+        reason = client_to_backend => reason,
+        reason = backend_to_queue => reason,
+        reason = queue_to_client => reason,
+        reason = slot_notifications => reason,
+    };
+    if reason == "client_closed" {
+        let _ = backend_write.send(TungsteniteMessage::Close(None)).await;
+    } else {
+        // A client that stopped reading may never accept the close frame either
+        let _ = timeout(send_timeout, client_write.send(Message::Close(None))).await;
+        if reason != "backend_closed" {
             let _ = backend_write.send(TungsteniteMessage::Close(None)).await;
-        },
-        _ = backend_to_client => {
-            // Backend side ended; send close to client
-            let _ = client_write.send(Message::Close(None)).await;
-        },
+        }
+    }
+    if logged && reason != "client_closed" && reason != "backend_closed" {
+        warn!(
+            "WebSocket: {} disconnected by router ({}), backend={}, owner={}",
+            client_addr, reason, backend_label, owner
+        );
     }
+    counter!("ws_disconnects_total", "backend" => backend_label.clone(), "owner" => owner.clone(), "reason" => reason).increment(1);
 
     let duration = connect_time.elapsed().as_secs_f64();
-    gauge!("ws_active_connections", "backend" => backend_label.clone(), "owner" => owner.clone()).decrement(1.0);
+    gauge!("ws_active_connections", "backend" => backend_label.clone(), "owner" => owner.clone())
+        .decrement(1.0);
     histogram!("ws_connection_duration_seconds", "backend" => backend_label.clone(), "owner" => owner.clone()).record(duration);
 
-    info!(
-        "WebSocket: {} disconnected from backend {} (duration={:.1}s)",
-        client_addr, backend_label, duration
-    );
+    if logged {
+        info!(
+            "WebSocket: {} disconnected from backend {} (duration={:.1}s)",
+            client_addr, backend_label, duration
+        );
+    }
 }
@@ -1,28 +1,57 @@
 use std::{
+    any::Any,
     collections::HashMap,
-    sync::{atomic::Ordering, Arc, RwLock},
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::SystemTime,
 };
 
 use arc_swap::ArcSwap;
 use axum::{body::Body, http::Request};
-use futures_util::future;
+use futures_util::{future, FutureExt};
 use hyper_tls::HttpsConnector;
 use hyper_util::client::legacy::{connect::HttpConnector, Client};
-use metrics::gauge;
-use tokio::time::{sleep, timeout, Duration};
+use metrics::{counter, gauge, histogram};
+use serde::Serialize;
+use tokio::time::{sleep, timeout, Duration, Instant};
 
 use crate::{
-    config::{Backend, HealthCheckConfig},
-    state::RouterState,
+    circuit::CircuitBreaker,
+    config::{Backend, HealthCheckConfig, ReferenceKind, ReferenceSource},
+    genesis::Identity,
+    protocol::BackendClients,
+    redact::redact,
+    signing::{apply_backend_headers, apply_signature, clock_skew, unix_now},
+    state::{RouterState, RuntimeBackend},
 };
 
+/// Health level of a backend. DEGRADED backends stay in rotation at reduced weight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthLevel {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
 #[derive(Debug, Clone)]
 pub struct BackendHealthStatus {
+    /// In rotation (HEALTHY or DEGRADED)
     pub healthy: bool,
+    /// Lagging within 2x `max_slot_lag`, or failing checks below the unhealthy threshold
+    pub degraded: bool,
     pub last_check_time: Option<SystemTime>,
+    /// When the health check loop will next probe this backend
+    pub next_check_time: Option<SystemTime>,
+    /// Slot (or block height) reported by the last successful check
+    pub last_slot: Option<u64>,
     pub consecutive_failures: u32,
     pub consecutive_successes: u32,
+    /// Proxied requests failed in a row since the last success
+    pub consecutive_request_failures: u32,
     pub last_error: Option<String>,
 }
 
@@ -30,60 +59,297 @@ impl Default for BackendHealthStatus {
     fn default() -> Self {
         Self {
             healthy: true, // Start optimistic - assume backends are healthy
+            degraded: false,
             last_check_time: None,
+            next_check_time: None,
+            last_slot: None,
             consecutive_failures: 0,
             consecutive_successes: 0,
+            consecutive_request_failures: 0,
             last_error: None,
         }
     }
 }
 
+impl BackendHealthStatus {
+    pub fn level(&self) -> HealthLevel {
+        match (self.healthy, self.degraded) {
+            (false, _) => HealthLevel::Unhealthy,
+            (true, true) => HealthLevel::Degraded,
+            (true, false) => HealthLevel::Healthy,
+        }
+    }
+}
+
+/// Advance the health state machine by one check result.
+///
+/// - Failures (including lag beyond 2x `max_slot_lag`) below the failure threshold
+///   mark a backend DEGRADED; reaching the threshold marks it UNHEALTHY.
+/// - Lag between `max_slot_lag` and 2x is DEGRADED, not a failure.
+/// - `consecutive_successes_threshold` clean checks restore HEALTHY (or DEGRADED
+///   when recovering while still lagging).
+pub fn record_check(
+    status: &mut BackendHealthStatus,
+    result: &Result<Option<u64>, String>,
+    max_slot: Option<u64>,
+    config: &HealthCheckConfig,
+) {
+    if let Ok(Some(slot)) = result {
+        status.last_slot = Some(*slot);
+    }
+
+    let lag = slot_lag(result, max_slot).unwrap_or(0);
+
+    let error = match result {
+        Err(e) => Some(e.clone()),
+        Ok(_) if lag > config.max_slot_lag.saturating_mul(2) => Some(format!(
+            "Backend lagging: {} slots behind max {}",
+            lag,
+            max_slot.unwrap_or_default()
+        )),
+        Ok(_) => None,
+    };
+
+    if let Some(error) = error {
+        status.consecutive_failures += 1;
+        status.consecutive_successes = 0;
+        status.last_error = Some(error);
+        if status.consecutive_failures >= config.consecutive_failures_threshold {
+            status.healthy = false;
+            status.degraded = false;
+        } else if status.healthy {
+            status.degraded = true;
+        }
+        return;
+    }
+
+    let lagging = lag > config.max_slot_lag;
+    status.consecutive_successes += 1;
+    status.consecutive_failures = 0;
+    status.last_error = lagging.then(|| {
+        format!(
+            "Backend lagging: {} slots behind max {} (degraded)",
+            lag,
+            max_slot.unwrap_or_default()
+        )
+    });
+
+    if lagging {
+        if status.healthy {
+            status.degraded = true;
+        } else if status.consecutive_successes >= config.consecutive_successes_threshold {
+            status.healthy = true;
+            status.degraded = true;
+        }
+    } else if status.consecutive_successes >= config.consecutive_successes_threshold {
+        status.healthy = true;
+        status.degraded = false;
+    }
+}
+
+/// Advance the health state machine by the outcome of one proxied request.
+///
+/// `threshold` consecutive failures (timeouts, 5xx, connection errors) mark a
+/// backend in rotation UNHEALTHY at once, usually well before its checks
+/// would. Only checks bring it back, after `consecutive_successes_threshold`
+/// clean ones. Returns whether this request took the backend out of rotation.
+pub fn record_request(status: &mut BackendHealthStatus, success: bool, threshold: u32) -> bool {
+    if success {
+        status.consecutive_request_failures = 0;
+        return false;
+    }
+    if !status.healthy {
+        return false;
+    }
+    status.consecutive_request_failures += 1;
+    if status.consecutive_request_failures < threshold {
+        return false;
+    }
+    status.healthy = false;
+    status.degraded = false;
+    status.consecutive_successes = 0;
+    status.last_error = Some(format!(
+        "Failing proxied requests: {} consecutive failures",
+        status.consecutive_request_failures
+    ));
+    status.consecutive_request_failures = 0;
+    // Probe it on the next round rather than at its scheduled time
+    status.next_check_time = None;
+    true
+}
+
+/// Feed the outcome of a proxied request to `backend` into its health when
+/// `health_check.passive_failures_threshold` is set, taking the backend out of
+/// rotation once the threshold is reached.
+pub fn record_passive(
+    health_state: &HealthState,
+    backend: &RuntimeBackend,
+    success: bool,
+    threshold: u32,
+) {
+    if threshold == 0 || !health_state.record_request(&backend.config.label, success, threshold) {
+        return;
+    }
+    let label = &backend.config.label;
+    backend.healthy.store(false, Ordering::Relaxed);
+    backend.degraded.store(false, Ordering::Relaxed);
+    tracing::warn!(
+        "Backend {} marked as UNHEALTHY after {} consecutive failed requests",
+        label,
+        threshold
+    );
+    counter!("rpc_backend_passive_ejections_total", "backend" => label.clone()).increment(1);
+    gauge!("rpc_backend_health", "backend" => label.clone()).set(0.0);
+}
+
+/// Slots a check result is behind the round's tip, for checks that reported a
+/// slot in a round with a known tip.
+pub fn slot_lag(result: &Result<Option<u64>, String>, max_slot: Option<u64>) -> Option<u64> {
+    match (result, max_slot) {
+        (Ok(Some(slot)), Some(max)) => Some(max.saturating_sub(*slot)),
+        _ => None,
+    }
+}
+
+/// Consecutive clean checks per doubling of a stable backend's check interval.
+const STABLE_CHECKS_PER_BACKOFF: u32 = 10;
+
+/// Delay before the next check of a backend in `status`.
+///
+/// Without `adaptive` this is always `interval_secs`. With it, backends that are
+/// failing, degraded or unhealthy are probed every `min_interval_secs` so recovery
+/// (or the unhealthy threshold) is reached quickly, while fully healthy backends
+/// double their interval every `STABLE_CHECKS_PER_BACKOFF` clean checks, up to
+/// `max_interval_secs`.
+pub fn next_check_interval(status: &BackendHealthStatus, config: &HealthCheckConfig) -> Duration {
+    if !config.adaptive {
+        return Duration::from_secs(config.interval_secs);
+    }
+    if status.level() != HealthLevel::Healthy || status.consecutive_failures > 0 {
+        return Duration::from_secs(config.min_interval_secs);
+    }
+
+    let doublings = (status.consecutive_successes / STABLE_CHECKS_PER_BACKOFF).min(16);
+    let secs = config
+        .interval_secs
+        .saturating_mul(1 << doublings)
+        .min(config.max_interval_secs);
+    Duration::from_secs(secs)
+}
+
+/// Health bookkeeping shared by the health checks, the proxy and the admin
+/// API. Every map is an immutable snapshot swapped whole, so readers never
+/// block and a panic mid-update leaves the previous snapshot in place.
 #[derive(Debug)]
 pub struct HealthState {
-    statuses: RwLock<HashMap<String, BackendHealthStatus>>,
+    statuses: ArcSwap<HashMap<String, BackendHealthStatus>>,
+    circuits: ArcSwap<HashMap<String, Arc<CircuitBreaker>>>,
+    identities: ArcSwap<HashMap<String, Arc<Identity>>>,
+    /// Consensus tip of the last health check round (0 = none yet)
+    tip: AtomicU64,
+}
+
+/// The entry of `label` in `map`, inserting a default one if there is none.
+pub(crate) fn shared_entry<T: Default>(
+    map: &ArcSwap<HashMap<String, Arc<T>>>,
+    label: &str,
+) -> Arc<T> {
+    if let Some(entry) = map.load().get(label) {
+        return entry.clone();
+    }
+    map.rcu(|current| {
+        if current.contains_key(label) {
+            return current.clone();
+        }
+        let mut next = HashMap::clone(current);
+        next.insert(label.to_string(), Arc::default());
+        Arc::new(next)
+    });
+    map.load()[label].clone()
 }
 
 impl HealthState {
     pub fn new(backend_labels: Vec<String>) -> Self {
-        let mut statuses = HashMap::new();
-        for label in backend_labels {
-            statuses.insert(label, BackendHealthStatus::default());
-        }
+        let statuses = backend_labels
+            .into_iter()
+            .map(|label| (label, BackendHealthStatus::default()))
+            .collect();
         Self {
-            statuses: RwLock::new(statuses),
+            statuses: ArcSwap::from_pointee(statuses),
+            circuits: ArcSwap::default(),
+            identities: ArcSwap::default(),
+            tip: AtomicU64::new(0),
         }
     }
 
+    /// The consensus tip the last health check round measured lag against: a
+    /// slot, or a block height with `health_check.method = "getBlockHeight"`.
+    pub fn tip(&self) -> Option<u64> {
+        Some(self.tip.load(Ordering::Relaxed)).filter(|tip| *tip > 0)
+    }
+
+    pub fn set_tip(&self, tip: u64) {
+        self.tip.store(tip, Ordering::Relaxed);
+    }
+
+    /// The circuit breaker of `label`, kept across config reloads.
+    pub fn circuit(&self, label: &str) -> Arc<CircuitBreaker> {
+        shared_entry(&self.circuits, label)
+    }
+
+    /// The genesis hash verdict of `label`, kept across config reloads.
+    pub fn identity(&self, label: &str) -> Arc<Identity> {
+        shared_entry(&self.identities, label)
+    }
+
     pub fn get_status(&self, label: &str) -> Option<BackendHealthStatus> {
-        self.statuses
-            .read()
-            .unwrap_or_else(|e| e.into_inner())
-            .get(label)
-            .cloned()
+        self.statuses.load().get(label).cloned()
     }
 
+    /// Replace the status of `label`, adding it if the backend is new (hot reload).
     pub fn update_status(&self, label: &str, status: BackendHealthStatus) {
-        let mut statuses = self.statuses.write().unwrap_or_else(|e| e.into_inner());
-        if let Some(s) = statuses.get_mut(label) {
-            *s = status;
-        } else {
-            // If backend is new (hot reload), insert it
-            statuses.insert(label.to_string(), status);
+        self.statuses.rcu(|current| {
+            let mut next = HashMap::clone(current);
+            next.insert(label.to_string(), status.clone());
+            next
+        });
+    }
+
+    /// Feed the outcome of a proxied request to `label`'s status (see
+    /// [`record_request`]). The snapshot is only swapped when the outcome
+    /// changes something, so healthy traffic costs a read. Returns whether the
+    /// backend was taken out of rotation.
+    pub fn record_request(&self, label: &str, success: bool, threshold: u32) -> bool {
+        let unchanged = self.statuses.load().get(label).is_none_or(|status| {
+            if success {
+                status.consecutive_request_failures == 0
+            } else {
+                !status.healthy
+            }
+        });
+        if unchanged {
+            return false;
         }
+        let mut ejected = false;
+        self.statuses.rcu(|current| {
+            let mut next = HashMap::clone(current);
+            ejected = next
+                .get_mut(label)
+                .is_some_and(|status| record_request(status, success, threshold));
+            next
+        });
+        ejected
     }
 
     pub fn get_all_statuses(&self) -> HashMap<String, BackendHealthStatus> {
-        self.statuses
-            .read()
-            .unwrap_or_else(|e| e.into_inner())
-            .clone()
+        HashMap::clone(&self.statuses.load())
     }
 }
 
 /// Performs a health check against a backend.
 /// Returns `Ok(Some(slot))` if the method is `getSlot` or `getBlockHeight` and the response
 /// contains a numeric result. Returns `Ok(None)` for other methods. Returns `Err` on failure.
-async fn perform_health_check(
+pub async fn perform_health_check(
     client: &Client<HttpsConnector<HttpConnector>, Body>,
     backend: &Backend,
     health_config: &HealthCheckConfig,
@@ -99,12 +365,17 @@ async fn perform_health_check(
     let body_bytes = serde_json::to_vec(&health_request)
         .map_err(|e| format!("Failed to serialize health check: {}", e))?;
 
-    let req = Request::builder()
+    let mut req = Request::builder()
         .method("POST")
         .uri(&backend.url)
         .header("content-type", "application/json")
-        .body(Body::from(body_bytes))
+        .body(Body::empty())
         .map_err(|e| format!("Failed to build request: {}", e))?;
+    apply_backend_headers(backend, req.headers_mut());
+    if let Some(signing) = &backend.signing {
+        apply_signature(signing, req.headers_mut(), &body_bytes, unix_now());
+    }
+    *req.body_mut() = Body::from(body_bytes);
 
     // Perform request with timeout
     let result = timeout(
@@ -115,6 +386,18 @@ async fn perform_health_check(
 
     match result {
         Ok(Ok(response)) => {
+            // Signed requests are rejected once clocks drift past the backend's tolerance
+            if let Some(signing) = &backend.signing {
+                if let Some(skew) = clock_skew(response.headers(), SystemTime::now()) {
+                    if skew > signing.max_clock_skew_secs {
+                        return Err(format!(
+                            "Clock skew of {}s exceeds signing tolerance of {}s",
+                            skew, signing.max_clock_skew_secs
+                        ));
+                    }
+                }
+            }
+
             if !response.status().is_success() {
                 return Err(format!(
                     "Health check returned status: {}",
@@ -153,163 +436,310 @@ async fn perform_health_check(
     }
 }
 
+/// Largest response read from an `http` reference source.
+const REFERENCE_MAX_BYTES: usize = 64 * 1024;
+
+/// Current slot (or block height, matching `health_check.method`) according to
+/// an external reference source.
+pub async fn fetch_reference_slot(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    source: &ReferenceSource,
+    health_config: &HealthCheckConfig,
+) -> Result<u64, String> {
+    if source.kind == ReferenceKind::Rpc {
+        let backend = Backend {
+            label: source.label.clone(),
+            url: source.url.clone(),
+            ..Default::default()
+        };
+        return perform_health_check(client, &backend, health_config)
+            .await?
+            .ok_or_else(|| format!("Method {} does not report a slot", health_config.method));
+    }
+
+    let req = Request::builder()
+        .method("GET")
+        .uri(&source.url)
+        .header("accept", "application/json")
+        .body(Body::empty())
+        .map_err(|e| format!("Failed to build request: {}", e))?;
+    let response = timeout(
+        Duration::from_secs(health_config.timeout_secs),
+        client.request(req),
+    )
+    .await
+    .map_err(|_| format!("Timed out after {}s", health_config.timeout_secs))?
+    .map_err(|e| format!("Request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Returned status: {}", response.status()));
+    }
+
+    let body = http_body_util::Limited::new(response.into_body(), REFERENCE_MAX_BYTES);
+    let body_bytes = http_body_util::BodyExt::collect(body)
+        .await
+        .map_err(|e| format!("Failed to read response body: {}", e))?
+        .to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body_bytes)
+        .map_err(|e| format!("Failed to parse response JSON: {}", e))?;
+    let pointer = source.json_pointer.as_deref().unwrap_or_default();
+    let value = json
+        .pointer(pointer)
+        .ok_or_else(|| format!("Response has no value at {}", pointer))?;
+    // Explorer APIs often return numbers as strings
+    value
+        .as_u64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+        .ok_or_else(|| format!("Value at {} is not a slot number", pointer))
+}
+
+/// Highest slot across the reference sources that answered. Failures are logged
+/// and skipped so a broken reference never marks backends unhealthy.
+async fn reference_tip(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    health_config: &HealthCheckConfig,
+) -> Option<u64> {
+    let fetches = health_config
+        .reference_sources
+        .iter()
+        .map(|source| async move {
+            match fetch_reference_slot(client, source, health_config).await {
+                Ok(slot) => {
+                    gauge!("rpc_reference_slot", "source" => source.label.clone()).set(slot as f64);
+                    Some(slot)
+                }
+                Err(e) => {
+                    tracing::warn!("Reference source {} failed: {}", source.label, redact(&e));
+                    None
+                }
+            }
+        });
+    future::join_all(fetches).await.into_iter().flatten().max()
+}
+
 pub async fn health_check_loop(
     client: Client<HttpsConnector<HttpConnector>, Body>,
+    clients: Arc<BackendClients>,
     router_state: Arc<ArcSwap<RouterState>>,
 ) {
     loop {
-        // Load the current state for this iteration
-        let current_state = router_state.load();
-
-        let health_config = &current_state.health_check_config;
-        let health_state = &current_state.health_state;
-        let check_interval = Duration::from_secs(health_config.interval_secs);
-
-        // Run all health checks concurrently so one slow backend doesn't block others
-        let check_futures: Vec<_> = current_state
-            .backends
-            .iter()
-            .map(|backend| {
-                let client = client.clone();
-                let config = backend.config.clone();
-                let hc = health_config.clone();
-                async move {
-                    let result = perform_health_check(&client, &config, &hc).await;
-                    (config.label.clone(), result)
-                }
-            })
-            .collect();
+        // A panicking round must not stop health checks for good: statuses keep
+        // their last snapshot and the next round starts on schedule
+        let round =
+            AssertUnwindSafe(health_check_round(&client, &clients, &router_state)).catch_unwind();
+        let sleep_for = match round.await {
+            Ok(sleep_for) => sleep_for,
+            Err(panic) => {
+                tracing::error!(
+                    "Health check round panicked; backend health kept as last recorded: {}",
+                    panic_message(&*panic)
+                );
+                counter!("rpc_health_state_errors_total").increment(1);
+                Duration::from_secs(router_state.load().health_check_config.interval_secs)
+            }
+        };
+        sleep(sleep_for).await;
+    }
+}
 
-        let results = future::join_all(check_futures).await;
-
-        // Collect slot numbers from successful checks to determine the max (consensus tip)
-        let max_slot: Option<u64> = results
-            .iter()
-            .filter_map(|(_, result)| match result {
-                Ok(Some(slot)) => Some(*slot),
-                _ => None,
-            })
-            .max();
-
-        for (i, (label, check_result)) in results.into_iter().enumerate() {
-            let backend = &current_state.backends[i];
-
-            // Get current status from the detailed state
-            let mut current_status = health_state
-                .get_status(&label)
-                .unwrap_or_default();
-
-            let previous_healthy = current_status.healthy;
-
-            match check_result {
-                Ok(slot_opt) => {
-                    // Check for slot lag against consensus
-                    let lagging = match (slot_opt, max_slot) {
-                        (Some(slot), Some(max)) if max > slot && (max - slot) > health_config.max_slot_lag => {
-                            true
-                        }
-                        _ => false,
-                    };
-
-                    if lagging {
-                        let slot = slot_opt.unwrap();
-                        let max = max_slot.unwrap();
-                        current_status.consecutive_failures += 1;
-                        current_status.consecutive_successes = 0;
-                        current_status.last_error = Some(format!(
-                            "Backend lagging: slot {} is {} behind max {}",
-                            slot,
-                            max - slot,
-                            max
-                        ));
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
 
-                        if current_status.consecutive_failures
-                            >= health_config.consecutive_failures_threshold
-                        {
-                            current_status.healthy = false;
-                        }
-
-                        tracing::warn!(
-                            "Backend {} is lagging: slot {} is {} behind consensus max {} (threshold: {})",
-                            label,
-                            slot,
-                            max - slot,
-                            max,
-                            health_config.max_slot_lag
-                        );
-                    } else {
-                        current_status.consecutive_successes += 1;
-                        current_status.consecutive_failures = 0;
-                        current_status.last_error = None;
-
-                        // Mark healthy if threshold reached
-                        if current_status.consecutive_successes
-                            >= health_config.consecutive_successes_threshold
-                        {
-                            current_status.healthy = true;
-                        }
-
-                        tracing::debug!(
-                            "Health check succeeded for backend {} (consecutive successes: {})",
-                            label,
-                            current_status.consecutive_successes
-                        );
-                    }
-                }
-                Err(error) => {
-                    current_status.consecutive_failures += 1;
-                    current_status.consecutive_successes = 0;
-                    current_status.last_error = Some(error.clone());
-
-                    // Mark unhealthy if threshold reached
-                    if current_status.consecutive_failures
-                        >= health_config.consecutive_failures_threshold
-                    {
-                        current_status.healthy = false;
-                    }
+/// Check every backend that is due and record the results; returns how long
+/// to wait before the next round.
+async fn health_check_round(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    clients: &BackendClients,
+    router_state: &ArcSwap<RouterState>,
+) -> Duration {
+    // Load the current state for this iteration
+    let current_state = router_state.load();
+
+    let health_config = &current_state.health_check_config;
+    let health_state = &current_state.health_state;
+
+    // Only backends whose next check is due are probed this round; backends in
+    // maintenance are left alone
+    let now = SystemTime::now();
+    let statuses: Vec<BackendHealthStatus> = current_state
+        .backends
+        .iter()
+        .map(|b| health_state.get_status(&b.config.label).unwrap_or_default())
+        .collect();
+    let mut due: Vec<usize> = Vec::new();
+    for (i, status) in statuses.iter().enumerate() {
+        let skipped = if current_state.backends[i].config.maintenance {
+            "maintenance"
+        } else if status.next_check_time.is_some_and(|t| t > now) {
+            "not_due"
+        } else {
+            due.push(i);
+            continue;
+        };
+        counter!(
+            "rpc_backend_health_checks_skipped_total",
+            "backend" => current_state.backends[i].config.label.clone(),
+            "reason" => skipped
+        )
+        .increment(1);
+    }
 
-                    tracing::warn!(
-                        "Health check failed for backend {} (consecutive failures: {}): {}",
-                        label,
-                        current_status.consecutive_failures,
-                        error
-                    );
-                }
+    // Run all health checks concurrently so one slow backend doesn't block others
+    let check_futures: Vec<_> = due
+        .iter()
+        .map(|&i| {
+            let config = current_state.backends[i].config.clone();
+            let client = clients.pick(client, &config, false);
+            let hc = health_config.clone();
+            async move {
+                let started = Instant::now();
+                let result = perform_health_check(&client, &config, &hc).await;
+                (i, config.label.clone(), result, started.elapsed())
             }
+        })
+        .collect();
 
-            current_status.last_check_time = Some(SystemTime::now());
+    let (results, reference_slot) = future::join(future::join_all(check_futures), async {
+        if due.is_empty() {
+            None
+        } else {
+            reference_tip(client, health_config).await
+        }
+    })
+    .await;
 
-            // Log state transitions
-            if previous_healthy && !current_status.healthy {
-                tracing::warn!(
+    // Determine the consensus tip from fresh results, reference sources and, for
+    // backends not probed this round, their last reported slot
+    let max_slot: Option<u64> = results
+        .iter()
+        .filter_map(|(_, _, result, _)| match result {
+            Ok(Some(slot)) => Some(*slot),
+            _ => None,
+        })
+        .chain(
+            statuses
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !due.contains(i))
+                .filter_map(|(_, s)| s.last_slot),
+        )
+        .chain(reference_slot)
+        .max();
+    if let Some(tip) = max_slot {
+        health_state.set_tip(tip);
+    }
+
+    let checked_at = SystemTime::now();
+    for (i, label, check_result, elapsed) in results {
+        let backend = &current_state.backends[i];
+
+        // Get current status from the detailed state
+        let mut current_status = health_state.get_status(&label).unwrap_or_default();
+
+        let previous_level = current_status.level();
+        record_check(&mut current_status, &check_result, max_slot, health_config);
+
+        match &check_result {
+            Ok(_) => tracing::debug!(
+                "Health check succeeded for backend {} (consecutive successes: {})",
+                label,
+                current_status.consecutive_successes
+            ),
+            Err(error) => tracing::warn!(
+                "Health check failed for backend {} (consecutive failures: {}): {}",
+                label,
+                current_status.consecutive_failures,
+                error
+            ),
+        }
+
+        current_status.last_check_time = Some(checked_at);
+        let interval = next_check_interval(&current_status, health_config);
+        current_status.next_check_time = Some(checked_at + interval);
+        gauge!("rpc_backend_health_check_interval_seconds", "backend" => label.clone())
+            .set(interval.as_secs_f64());
+
+        // Log state transitions
+        let level = current_status.level();
+        if level != previous_level {
+            let reason = current_status
+                .last_error
+                .as_deref()
+                .unwrap_or("checks passing");
+            match level {
+                HealthLevel::Unhealthy => tracing::warn!(
                     "Backend {} marked as UNHEALTHY after {} consecutive failures",
                     label,
                     current_status.consecutive_failures
-                );
-            } else if !previous_healthy && current_status.healthy {
-                tracing::info!(
+                ),
+                HealthLevel::Degraded => {
+                    tracing::warn!("Backend {} marked as DEGRADED: {}", label, reason)
+                }
+                HealthLevel::Healthy => tracing::info!(
                     "Backend {} marked as HEALTHY after {} consecutive successes",
                     label,
                     current_status.consecutive_successes
-                );
+                ),
             }
+        }
 
-            // Update metrics
-            gauge!("rpc_backend_health", "backend" => label.clone())
-                .set(if current_status.healthy { 1.0 } else { 0.0 });
-
-            // Update detailed state (locked)
-            health_state.update_status(&label, current_status.clone());
-
-            // Update atomic boolean (lock-free)
-            backend
-                .healthy
-                .store(current_status.healthy, Ordering::Relaxed);
+        // Update metrics
+        gauge!("rpc_backend_health", "backend" => label.clone()).set(if current_status.healthy {
+            1.0
+        } else {
+            0.0
+        });
+        gauge!("rpc_backend_degraded", "backend" => label.clone()).set(
+            if level == HealthLevel::Degraded {
+                1.0
+            } else {
+                0.0
+            },
+        );
+        // Finer-grained signals, to alert on a backend drifting before it is ejected
+        let outcome = if check_result.is_ok() {
+            "success"
+        } else {
+            "failure"
+        };
+        counter!("rpc_backend_health_checks_total", "backend" => label.clone(), "result" => outcome)
+            .increment(1);
+        histogram!("rpc_backend_health_check_duration_seconds", "backend" => label.clone())
+            .record(elapsed.as_secs_f64());
+        gauge!("rpc_backend_health_check_consecutive_failures", "backend" => label.clone())
+            .set(current_status.consecutive_failures as f64);
+        if let Some(lag) = slot_lag(&check_result, max_slot) {
+            gauge!("rpc_backend_slot_lag", "backend" => label.clone()).set(lag as f64);
         }
 
-        // Release the guard before sleeping so we don't hold old state in memory if it gets swapped
-        drop(current_state);
+        // Update detailed state (copy-on-write `rcu` of the status snapshot)
+        health_state.update_status(&label, current_status.clone());
 
-        sleep(check_interval).await;
+        // Update atomic boolean (lock-free)
+        backend
+            .healthy
+            .store(current_status.healthy, Ordering::Relaxed);
+        backend
+            .degraded
+            .store(current_status.degraded, Ordering::Relaxed);
     }
+
+    // Sleep until the earliest next check (new backends from a reload are due
+    // immediately, so cap the wait at the base interval)
+    let base_interval = Duration::from_secs(health_config.interval_secs);
+    let sleep_for = current_state
+        .backends
+        .iter()
+        .filter_map(|b| health_state.get_status(&b.config.label)?.next_check_time)
+        .map(|t| t.duration_since(SystemTime::now()).unwrap_or_default())
+        .min()
+        .unwrap_or(base_interval)
+        .min(base_interval)
+        .max(Duration::from_secs(1));
+
+    sleep_for
 }
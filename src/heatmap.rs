@@ -0,0 +1,154 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::methods::is_known_method;
+
+/// Upper bounds (ms) of the latency buckets, plus one open-ended bucket.
+pub const BUCKET_BOUNDS_MS: [u64; 14] = [
+    1, 2, 5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000,
+];
+const SLOTS: usize = BUCKET_BOUNDS_MS.len() + 1;
+
+#[derive(Debug, Default)]
+struct Window {
+    start: u64,
+    /// Width the window was counted with
+    secs: u64,
+    /// method -> backend -> counts, indexed like `BUCKET_BOUNDS_MS`
+    methods: HashMap<String, HashMap<String, [u64; SLOTS]>>,
+}
+
+/// Latency distribution of one backend: one row per window, one column per
+/// bucket.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BackendHeatmap {
+    pub backend: String,
+    pub counts: Vec<Vec<u64>>,
+    pub requests: u64,
+}
+
+/// Latencies of one method over `from..to` (unix seconds), per backend.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Heatmap {
+    pub method: String,
+    pub from: u64,
+    pub to: u64,
+    pub window_secs: u64,
+    /// Start of each window, oldest first; the rows of every backend's `counts`
+    pub windows: Vec<u64>,
+    /// Upper bound of each bucket in ms; the last bucket (`null`) is open-ended
+    pub bounds_ms: Vec<Option<u64>>,
+    /// Backends that answered the method, by label
+    pub backends: Vec<BackendHeatmap>,
+}
+
+/// A ring of per-window latency histograms behind `/admin/latency-heatmap`,
+/// one per method and backend. Kept in memory, so a restart starts it over.
+#[derive(Default)]
+pub struct LatencyHeatmap {
+    /// Oldest first
+    windows: Mutex<VecDeque<Window>>,
+}
+
+impl LatencyHeatmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an upstream answer of `method` from `backend` that took
+    /// `latency`, keeping `windows` windows of `window_secs`. Unknown method
+    /// names are ignored so clients cannot grow the table.
+    pub fn record(
+        &self,
+        method: &str,
+        backend: &str,
+        latency: Duration,
+        now: u64,
+        window_secs: u64,
+        windows: u64,
+    ) {
+        if !is_known_method(method) {
+            return;
+        }
+        let start = now - now % window_secs;
+        let mut ring = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        // A reload changed the window width: earlier windows no longer line up
+        if ring.back().is_some_and(|w| w.secs != window_secs) {
+            ring.clear();
+        }
+        if ring.back().map(|w| w.start) != Some(start) {
+            ring.push_back(Window {
+                start,
+                secs: window_secs,
+                ..Default::default()
+            });
+        }
+        while ring
+            .front()
+            .is_some_and(|w| w.start + windows * window_secs <= start)
+        {
+            ring.pop_front();
+        }
+
+        let window = ring.back_mut().expect("window just pushed");
+        // Look up by &str first so the hot path does not allocate
+        if !window.methods.contains_key(method) {
+            window.methods.insert(method.to_string(), HashMap::new());
+        }
+        let backends = window.methods.get_mut(method).expect("just inserted");
+        if !backends.contains_key(backend) {
+            backends.insert(backend.to_string(), [0; SLOTS]);
+        }
+        let ms = latency.as_millis() as u64;
+        let slot = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(BUCKET_BOUNDS_MS.len());
+        backends.get_mut(backend).expect("just inserted")[slot] += 1;
+    }
+
+    /// The last `windows` windows of `method` up to `now`, the current one
+    /// included. Windows without requests are rows of zeros.
+    pub fn heatmap(&self, method: &str, windows: u64, now: u64, window_secs: u64) -> Heatmap {
+        let to = now - now % window_secs + window_secs;
+        let from = to.saturating_sub(windows * window_secs);
+        let starts: Vec<u64> = (from..to).step_by(window_secs as usize).collect();
+
+        let mut backends: BTreeMap<&str, BackendHeatmap> = BTreeMap::new();
+        let ring = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        for window in ring.iter().filter(|w| w.secs == window_secs) {
+            let Some(row) = starts.iter().position(|start| *start == window.start) else {
+                continue;
+            };
+            for (backend, counts) in window.methods.get(method).into_iter().flatten() {
+                let heatmap = backends.entry(backend).or_insert_with(|| BackendHeatmap {
+                    backend: backend.clone(),
+                    counts: vec![vec![0; SLOTS]; starts.len()],
+                    requests: 0,
+                });
+                heatmap.counts[row] = counts.to_vec();
+                heatmap.requests += counts.iter().sum::<u64>();
+            }
+        }
+
+        Heatmap {
+            method: method.to_string(),
+            from,
+            to,
+            window_secs,
+            windows: starts,
+            bounds_ms: BUCKET_BOUNDS_MS
+                .iter()
+                .copied()
+                .map(Some)
+                .chain([None])
+                .collect(),
+            backends: backends.into_values().collect(),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Credit needed for one hedged request, in hundredths of a request.
+const HEDGE_COST: u64 = 100;
+
+/// Most hedges credit saved by delayed hedging can pay for at once.
+const MAX_SAVED_HEDGES: u64 = 10;
+
+/// Credit counter bounding the extra upstream load from hedging.
+///
+/// Every request for a hedged method earns `budget_percent` hundredths of a
+/// hedge, so at most `budget_percent`% of them are sent to a second backend.
+pub struct HedgeBudget {
+    credit: AtomicU64,
+}
+
+impl Default for HedgeBudget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HedgeBudget {
+    pub fn new() -> Self {
+        Self {
+            credit: AtomicU64::new(0),
+        }
+    }
+
+    /// Earn credit for one request and spend it on a hedge if enough has built up.
+    /// Credit is spent as soon as it covers a hedge, so it never piles up into a burst.
+    pub fn try_acquire(&self, budget_percent: u32) -> bool {
+        let mut acquired = false;
+        let _ = self
+            .credit
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |credit| {
+                let credit = credit + u64::from(budget_percent);
+                acquired = credit >= HEDGE_COST;
+                Some(if acquired {
+                    credit - HEDGE_COST
+                } else {
+                    credit
+                })
+            });
+        acquired
+    }
+
+    /// Earn credit for one request without spending it, for delayed hedging
+    /// where most requests answer before a hedge is needed. Savings are capped
+    /// so a quiet stretch cannot pay for a burst.
+    pub fn earn(&self, budget_percent: u32) {
+        let _ = self
+            .credit
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |credit| {
+                Some((credit + u64::from(budget_percent)).min(MAX_SAVED_HEDGES * HEDGE_COST))
+            });
+    }
+
+    /// Spend earned credit on one hedge if enough has built up.
+    pub fn try_spend(&self) -> bool {
+        self.credit
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |credit| {
+                credit.checked_sub(HEDGE_COST)
+            })
+            .is_ok()
+    }
+}
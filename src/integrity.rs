@@ -0,0 +1,119 @@
+use axum::{body::Body, http::HeaderMap};
+use base64::Engine;
+use bytes::{Bytes, BytesMut};
+use futures_util::StreamExt;
+use http_body_util::BodyExt;
+use serde::de::IgnoredAny;
+use sha2::{Digest, Sha256};
+
+use crate::config::IntegrityConfig;
+
+/// Digest headers checked, in order: RFC 9530's `Content-Digest` and
+/// `Repr-Digest` (`sha-256=:<base64>:`) and RFC 3230's `Digest`
+/// (`SHA-256=<base64>`). For complete, uncompressed-in-transit responses all
+/// three cover the bytes received.
+const DIGEST_HEADERS: [&str; 3] = ["content-digest", "repr-digest", "digest"];
+
+/// Why an upstream response was not passed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityFailure {
+    /// The connection failed before the body was complete
+    Truncated,
+    /// Fewer or more bytes arrived than `Content-Length` announced
+    LengthMismatch,
+    /// The body does not match the backend's own digest header
+    DigestMismatch,
+    /// The body is not JSON
+    InvalidJson,
+}
+
+impl IntegrityFailure {
+    /// Label for the `reason` of `rpc_response_integrity_failures_total`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IntegrityFailure::Truncated => "truncated",
+            IntegrityFailure::LengthMismatch => "length_mismatch",
+            IntegrityFailure::DigestMismatch => "digest_mismatch",
+            IntegrityFailure::InvalidJson => "invalid_json",
+        }
+    }
+}
+
+/// A response body read up to the verification limit.
+pub enum Buffered {
+    Complete(Bytes),
+    /// Past `integrity.max_bytes`: what was read so far followed by the rest of
+    /// the stream, passed on unverified
+    TooLarge(Body),
+}
+
+/// Read `body` into memory, up to `limit` bytes. A stream error means the
+/// backend went away mid-response.
+pub async fn buffer<B>(mut body: B, limit: u64) -> Result<Buffered, IntegrityFailure>
+where
+    B: hyper::body::Body<Data = Bytes> + Send + Unpin + 'static,
+    B::Error: Into<axum::BoxError>,
+{
+    let mut buf = BytesMut::new();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|_| IntegrityFailure::Truncated)?;
+        let Ok(data) = frame.into_data() else {
+            continue;
+        };
+        buf.extend_from_slice(&data);
+        if buf.len() as u64 > limit {
+            let read = futures_util::stream::once(async move { Ok::<_, B::Error>(buf.freeze()) });
+            return Ok(Buffered::TooLarge(Body::from_stream(
+                read.chain(body.into_data_stream()),
+            )));
+        }
+    }
+    Ok(Buffered::Complete(buf.freeze()))
+}
+
+/// sha-256 digest the backend sent for the body, if any.
+pub fn expected_digest(headers: &HeaderMap) -> Option<Vec<u8>> {
+    DIGEST_HEADERS.iter().find_map(|name| {
+        let value = headers.get(*name)?.to_str().ok()?;
+        value.split(',').find_map(|entry| {
+            let (algorithm, encoded) = entry.trim().split_once('=')?;
+            if !algorithm.trim().eq_ignore_ascii_case("sha-256") {
+                return None;
+            }
+            base64::engine::general_purpose::STANDARD
+                .decode(encoded.trim().trim_matches(':'))
+                .ok()
+        })
+    })
+}
+
+/// Check a complete body against the response headers.
+pub fn verify(
+    headers: &HeaderMap,
+    body: &[u8],
+    config: &IntegrityConfig,
+) -> Result<(), IntegrityFailure> {
+    let content_length = headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|len| len != body.len() as u64) {
+        return Err(IntegrityFailure::LengthMismatch);
+    }
+    if config.verify_digest {
+        if let Some(expected) = expected_digest(headers) {
+            if Sha256::digest(body).as_slice() != expected.as_slice() {
+                return Err(IntegrityFailure::DigestMismatch);
+            }
+        }
+    }
+    // Compressed bodies are not JSON until decoded; their length and digest
+    // checks above still apply
+    let encoded = headers
+        .get("content-encoding")
+        .is_some_and(|v| v.as_bytes() != b"identity");
+    if config.verify_json && !encoded && serde_json::from_slice::<IgnoredAny>(body).is_err() {
+        return Err(IntegrityFailure::InvalidJson);
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, Client};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+use crate::{
+    broadcast::is_accepted,
+    config::{JournalConfig, JournalStoreKind},
+    signing::unix_now,
+};
+
+/// Redis entry of one transaction: `journal:<signature>`.
+pub const REDIS_KEY_PREFIX: &str = "journal";
+
+/// Sorted set of the signatures still pending, scored by journal time.
+const PENDING_KEY: &str = "journal:pending";
+
+pub fn redis_key(signature: &str) -> String {
+    format!("{}:{}", REDIS_KEY_PREFIX, signature)
+}
+
+/// What is known of a journaled transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalStatus {
+    /// Journaled; no outcome recorded yet
+    Pending,
+    /// A backend accepted it
+    Accepted,
+    /// A backend answered with an error, e.g. a failed preflight
+    Rejected,
+    /// No backend answered
+    Failed,
+}
+
+impl JournalStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Accepted => "accepted",
+            Self::Rejected => "rejected",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// One attempt to deliver a journaled transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalOutcome {
+    /// Label of the backend that answered, if one did
+    pub backend: Option<String>,
+    /// `accepted`, `rejected` or `error`
+    pub result: String,
+    /// Status the client (or, when resumed, the router) received
+    pub status: u16,
+    pub at: u64,
+    /// Re-sent after a restart rather than on the client's request
+    #[serde(default)]
+    pub resumed: bool,
+}
+
+/// A `sendTransaction` request as journaled before it was forwarded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub signature: String,
+    /// Fingerprint of the key that sent it (see `redact::key_fingerprint`)
+    pub key: String,
+    pub owner: String,
+    pub journaled_at: u64,
+    pub status: JournalStatus,
+    #[serde(default)]
+    pub outcomes: Vec<JournalOutcome>,
+    /// The JSON-RPC request body, kept for keys whose privacy level allows
+    /// params; needed to resume the transaction after a crash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<String>,
+}
+
+impl JournalEntry {
+    pub fn new(
+        signature: String,
+        key: String,
+        owner: String,
+        journaled_at: u64,
+        request: Option<String>,
+    ) -> Self {
+        Self {
+            signature,
+            key,
+            owner,
+            journaled_at,
+            status: JournalStatus::Pending,
+            outcomes: Vec::new(),
+            request,
+        }
+    }
+
+    /// Record the answer of one delivery attempt. An accepted transaction
+    /// stays accepted whatever later attempts answer.
+    pub fn record(
+        &mut self,
+        backend: Option<&str>,
+        status: u16,
+        body: &[u8],
+        at: u64,
+        resumed: bool,
+    ) {
+        let (result, outcome) = if (200..300).contains(&status) && is_accepted(body) {
+            ("accepted", JournalStatus::Accepted)
+        } else if status < 500 {
+            ("rejected", JournalStatus::Rejected)
+        } else {
+            ("error", JournalStatus::Failed)
+        };
+        self.outcomes.push(JournalOutcome {
+            backend: backend.map(str::to_string),
+            result: result.to_string(),
+            status,
+            at,
+            resumed,
+        });
+        if self.status != JournalStatus::Accepted {
+            self.status = outcome;
+        }
+    }
+}
+
+#[async_trait]
+pub trait JournalStore: Send + Sync {
+    /// Write `entry`, replacing any earlier version of it.
+    async fn put(&self, entry: &JournalEntry) -> Result<(), String>;
+
+    async fn get(&self, signature: &str) -> Result<Option<JournalEntry>, String>;
+
+    /// Entries still pending that were journaled at or after `since`, oldest
+    /// first.
+    async fn pending(&self, since: u64) -> Result<Vec<JournalEntry>, String>;
+}
+
+/// Open the store selected by `config`.
+pub async fn open_journal(
+    config: &JournalConfig,
+    redis_url: &str,
+) -> Result<Box<dyn JournalStore>, String> {
+    match config.store {
+        JournalStoreKind::Redis => Ok(Box::new(
+            RedisJournalStore::new(redis_url, config.retention_secs).await?,
+        )),
+        JournalStoreKind::File => Ok(Box::new(FileJournalStore::new(
+            &config.path,
+            config.retention_secs,
+        ))),
+    }
+}
+
+/// Entries on `redis_url`, each expiring after the retention period.
+pub struct RedisJournalStore {
+    conn: ConnectionManager,
+    retention_secs: u64,
+}
+
+impl RedisJournalStore {
+    pub async fn new(redis_url: &str, retention_secs: u64) -> Result<Self, String> {
+        let client = Client::open(redis_url).map_err(|e| e.to_string())?;
+        let conn = client
+            .get_connection_manager()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            conn,
+            retention_secs,
+        })
+    }
+}
+
+#[async_trait]
+impl JournalStore for RedisJournalStore {
+    async fn put(&self, entry: &JournalEntry) -> Result<(), String> {
+        let json = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .set_ex(redis_key(&entry.signature), json, self.retention_secs)
+            .ignore();
+        if entry.status == JournalStatus::Pending {
+            pipe.zadd(PENDING_KEY, &entry.signature, entry.journaled_at)
+                .ignore();
+        } else {
+            pipe.zrem(PENDING_KEY, &entry.signature).ignore();
+        }
+        let mut conn = self.conn.clone();
+        pipe.query_async::<()>(&mut conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn get(&self, signature: &str) -> Result<Option<JournalEntry>, String> {
+        let mut conn = self.conn.clone();
+        let json: Option<String> = redis::cmd("GET")
+            .arg(redis_key(signature))
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    async fn pending(&self, since: u64) -> Result<Vec<JournalEntry>, String> {
+        let mut conn = self.conn.clone();
+        // Older entries can no longer be resumed
+        let (_, signatures): (u64, Vec<String>) = redis::pipe()
+            .atomic()
+            .zrembyscore(PENDING_KEY, "-inf", format!("({}", since))
+            .zrangebyscore(PENDING_KEY, since, "+inf")
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        if signatures.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<String> = signatures.iter().map(|s| redis_key(s)).collect();
+        let entries: Vec<Option<String>> = redis::cmd("MGET")
+            .arg(keys)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(entries
+            .into_iter()
+            .flatten()
+            .filter_map(|json| serde_json::from_str::<JournalEntry>(&json).ok())
+            .filter(|entry| entry.status == JournalStatus::Pending)
+            .collect())
+    }
+}
+
+/// Entries appended as JSON lines to a local file, each update as a new line;
+/// the last line of a signature wins. The file is not compacted: entries past
+/// the retention period are skipped when read.
+pub struct FileJournalStore {
+    path: PathBuf,
+    retention_secs: u64,
+    /// Keeps appends whole
+    write: Mutex<()>,
+}
+
+impl FileJournalStore {
+    pub fn new(path: impl Into<PathBuf>, retention_secs: u64) -> Self {
+        Self {
+            path: path.into(),
+            retention_secs,
+            write: Mutex::new(()),
+        }
+    }
+
+    /// The latest version of every entry still retained, oldest first.
+    async fn read_all(&self) -> Result<Vec<JournalEntry>, String> {
+        let contents = match fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let cutoff = unix_now().saturating_sub(self.retention_secs);
+        let mut order = Vec::new();
+        let mut latest: HashMap<String, JournalEntry> = HashMap::new();
+        for entry in contents
+            .lines()
+            .filter_map(|line| serde_json::from_str::<JournalEntry>(line).ok())
+            .filter(|entry| entry.journaled_at >= cutoff)
+        {
+            if !latest.contains_key(&entry.signature) {
+                order.push(entry.signature.clone());
+            }
+            latest.insert(entry.signature.clone(), entry);
+        }
+        Ok(order
+            .iter()
+            .filter_map(|signature| latest.remove(signature))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl JournalStore for FileJournalStore {
+    async fn put(&self, entry: &JournalEntry) -> Result<(), String> {
+        let _write = self.write.lock().await;
+        let mut line = serde_json::to_vec(entry).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| e.to_string())?;
+        file.write_all(&line).await.map_err(|e| e.to_string())?;
+        // The entry must survive a crash right after the request is forwarded
+        file.sync_data().await.map_err(|e| e.to_string())
+    }
+
+    async fn get(&self, signature: &str) -> Result<Option<JournalEntry>, String> {
+        Ok(self
+            .read_all()
+            .await?
+            .into_iter()
+            .find(|entry| entry.signature == signature))
+    }
+
+    async fn pending(&self, since: u64) -> Result<Vec<JournalEntry>, String> {
+        Ok(self
+            .read_all()
+            .await?
+            .into_iter()
+            .filter(|entry| entry.status == JournalStatus::Pending && entry.journaled_at >= since)
+            .collect())
+    }
+}
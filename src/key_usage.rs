@@ -0,0 +1,246 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use metrics::counter;
+use redis::{aio::ConnectionManager, Client};
+use serde::Serialize;
+use tokio::time::{interval, Duration};
+use tracing::warn;
+
+use crate::{
+    config::KeyUsageConfig,
+    ledger::{day_number, day_string},
+    methods::is_known_method,
+    redact::redact,
+    traffic::OTHER_METHOD,
+};
+
+/// Redis hash of one key's counts on one day: `usage:key:<api_key>:<YYYY-MM-DD>`,
+/// with `<method>:requests` and `<method>:bytes` fields.
+const REDIS_KEY_PREFIX: &str = "usage:key";
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Requests one API key made for one method on one (UTC) day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyUsageEntry {
+    #[serde(skip)]
+    pub key: String,
+    /// `YYYY-MM-DD`
+    pub day: String,
+    pub method: String,
+    pub requests: u64,
+    /// Response body bytes sent to the client
+    pub response_bytes: u64,
+}
+
+/// Name a request is counted under: its method if known, else
+/// [`OTHER_METHOD`], so clients cannot add fields at will.
+pub fn usage_method(method: Option<&str>) -> &str {
+    method
+        .filter(|m| is_known_method(m))
+        .unwrap_or(OTHER_METHOD)
+}
+
+#[async_trait]
+pub trait KeyUsageStore: Send + Sync {
+    /// Add `entries` to the stored counts.
+    async fn add(&self, entries: &[KeyUsageEntry]) -> Result<(), String>;
+
+    /// Counts of `key` for days `from..=to` (day numbers); most recent day
+    /// first, then by method.
+    async fn query(&self, key: &str, from: u64, to: u64) -> Result<Vec<KeyUsageEntry>, String>;
+}
+
+/// Per-key daily hashes on `redis_url`, expiring after the retention period.
+pub struct RedisKeyUsageStore {
+    conn: ConnectionManager,
+    retention_days: u64,
+}
+
+impl RedisKeyUsageStore {
+    pub async fn new(redis_url: &str, retention_days: u64) -> Result<Self, String> {
+        let client = Client::open(redis_url).map_err(|e| e.to_string())?;
+        let conn = client
+            .get_connection_manager()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            conn,
+            retention_days,
+        })
+    }
+}
+
+fn redis_key(key: &str, day: &str) -> String {
+    format!("{}:{}:{}", REDIS_KEY_PREFIX, key, day)
+}
+
+/// Entries of one day's hash, by method.
+pub fn entries_from_fields(
+    key: &str,
+    day: &str,
+    fields: &HashMap<String, u64>,
+) -> Vec<KeyUsageEntry> {
+    let mut methods: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+    for (field, value) in fields {
+        match field.rsplit_once(':') {
+            Some((method, "requests")) => methods.entry(method).or_default().0 += value,
+            Some((method, "bytes")) => methods.entry(method).or_default().1 += value,
+            _ => {}
+        }
+    }
+    methods
+        .into_iter()
+        .map(|(method, (requests, response_bytes))| KeyUsageEntry {
+            key: key.to_string(),
+            day: day.to_string(),
+            method: method.to_string(),
+            requests,
+            response_bytes,
+        })
+        .collect()
+}
+
+#[async_trait]
+impl KeyUsageStore for RedisKeyUsageStore {
+    async fn add(&self, entries: &[KeyUsageEntry]) -> Result<(), String> {
+        let ttl = (self.retention_days + 1) * SECS_PER_DAY;
+        let mut pipe = redis::pipe();
+        for entry in entries {
+            let hash = redis_key(&entry.key, &entry.day);
+            pipe.hincr(&hash, format!("{}:requests", entry.method), entry.requests)
+                .ignore()
+                .hincr(
+                    &hash,
+                    format!("{}:bytes", entry.method),
+                    entry.response_bytes,
+                )
+                .ignore()
+                .expire(&hash, ttl as i64)
+                .ignore();
+        }
+        let mut conn = self.conn.clone();
+        pipe.query_async::<()>(&mut conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn query(&self, key: &str, from: u64, to: u64) -> Result<Vec<KeyUsageEntry>, String> {
+        let days: Vec<String> = (from..=to).rev().map(day_string).collect();
+        let mut pipe = redis::pipe();
+        for day in &days {
+            pipe.hgetall(redis_key(key, day));
+        }
+        let mut conn = self.conn.clone();
+        let hashes: Vec<HashMap<String, u64>> = pipe
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(days
+            .iter()
+            .zip(&hashes)
+            .flat_map(|(day, fields)| entries_from_fields(key, day, fields))
+            .collect())
+    }
+}
+
+/// (key, day number, method) -> (requests, response bytes)
+type Counts = HashMap<(String, u64, String), (u64, u64)>;
+
+/// Counts collected since the last flush, so Redis is written every
+/// `key_usage.flush_secs` rather than on every request.
+#[derive(Default)]
+pub struct KeyUsageBuffer {
+    counts: Mutex<Counts>,
+}
+
+impl KeyUsageBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, key: &str, method: &str, response_bytes: u64, now: u64) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let (requests, bytes) = counts
+            .entry((key.to_string(), day_number(now), method.to_string()))
+            .or_default();
+        *requests += 1;
+        *bytes += response_bytes;
+    }
+
+    /// Everything recorded since the last call.
+    pub fn take(&self) -> Vec<KeyUsageEntry> {
+        let counts = std::mem::take(&mut *self.counts.lock().unwrap_or_else(|e| e.into_inner()));
+        counts
+            .into_iter()
+            .map(
+                |((key, day, method), (requests, response_bytes))| KeyUsageEntry {
+                    key,
+                    day: day_string(day),
+                    method,
+                    requests,
+                    response_bytes,
+                },
+            )
+            .collect()
+    }
+}
+
+/// Counts the response bytes of one request as its body streams out, and
+/// records the request when the body is done or dropped.
+pub struct KeyUsageMeter {
+    buffer: Arc<KeyUsageBuffer>,
+    key: String,
+    method: String,
+    at: u64,
+    bytes: u64,
+}
+
+impl KeyUsageMeter {
+    pub fn new(buffer: Arc<KeyUsageBuffer>, key: String, method: &str, at: u64) -> Self {
+        Self {
+            buffer,
+            key,
+            method: method.to_string(),
+            at,
+            bytes: 0,
+        }
+    }
+
+    pub fn add(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+    }
+}
+
+impl Drop for KeyUsageMeter {
+    fn drop(&mut self) {
+        self.buffer
+            .record(&self.key, &self.method, self.bytes, self.at);
+    }
+}
+
+/// Write buffered counts to `store` every `flush_secs`. Counts that fail to
+/// write are dropped after a warning rather than retried, so a down Redis
+/// cannot grow the buffer.
+pub async fn key_usage_loop(
+    buffer: Arc<KeyUsageBuffer>,
+    store: Arc<dyn KeyUsageStore>,
+    config: KeyUsageConfig,
+) {
+    let mut ticker = interval(Duration::from_secs(config.flush_secs));
+    loop {
+        ticker.tick().await;
+        let entries = buffer.take();
+        if entries.is_empty() {
+            continue;
+        }
+        if let Err(e) = store.add(&entries).await {
+            warn!("Failed to write key usage: {}", redact(&e));
+            counter!("key_usage_write_failures_total").increment(1);
+        }
+    }
+}
@@ -1,141 +1,932 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use async_trait::async_trait;
-use moka::future::Cache;
-use redis::{aio::ConnectionManager, Client};
+use futures_util::StreamExt;
+use metrics::counter;
+use moka::{future::Cache, Expiry};
+use rand::{distributions::Alphanumeric, Rng};
+use redis::{
+    aio::{ConnectionLike, ConnectionManager},
+    AsyncCommands, Client, RedisResult,
+};
 
-#[derive(Clone, Debug)]
+use tracing::warn;
+
+use crate::{
+    audit::LogPrivacy,
+    config::{KeyCacheConfig, KeystoreOutageConfig, KeystoreOutagePolicy},
+    defaults::RequestDefaults,
+    methods::{is_known_method, MethodCategory},
+    signing::unix_now,
+    ws::FirehoseThrottle,
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum KeyKind {
+    #[default]
+    Standard,
+    /// Public key embedded in a dApp frontend, validated by request origin rather than secrecy
+    Browser,
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct KeyInfo {
     pub owner: String,
     pub rate_limit: u64,
+    pub kind: KeyKind,
+    /// Origins (e.g. `https://app.example.com`) a browser key may be used from
+    pub allowed_origins: Vec<String>,
+    /// Commitment and encoding injected when a request omits them
+    pub defaults: RequestDefaults,
+    /// Method -> backend label routes for this key, ahead of its tier's and the global ones
+    pub method_routes: HashMap<String, String>,
+    /// Routing tier whose `tier_routes` apply to this key
+    pub tier: Option<String>,
+    /// Log every request of this key in full (see `audit`)
+    pub audit: bool,
+    /// What may be logged or exported about this key's requests
+    pub privacy: LogPrivacy,
+    /// Limits on vote, block and slot-update notifications over WebSocket
+    pub firehose: FirehoseThrottle,
+    /// Break down where each request's time went in an `x-timing` header
+    pub timing: bool,
+    /// Send this key's `sendTransaction` requests to every healthy backend
+    /// (see `broadcast`)
+    pub broadcast: bool,
+    /// The only methods this key may call; empty allows every method
+    pub allowed_methods: Vec<String>,
+    /// Categories of methods this key may call, on top of `allowed_methods`;
+    /// both empty allows every method
+    pub scopes: Vec<MethodCategory>,
+    /// Methods this key may never call, even if allowed above
+    pub blocked_methods: Vec<String>,
+    /// Spend after which the key is throttled (see `spending`)
+    pub spend_cap: Option<f64>,
+    /// Requests of this key served at once; 0 = only the global limit
+    pub max_in_flight: u32,
+    /// Unix time from which the key is no longer accepted
+    pub expires_at: Option<u64>,
+    /// Requests left in the key's token bucket after this one, filled in on
+    /// validation. `None` for keys without a rate limit.
+    pub rate_limit_remaining: Option<u64>,
+}
+
+impl KeyInfo {
+    /// Build from the fields of an `api_key:<key>` Redis hash.
+    pub fn from_fields(fields: &HashMap<String, String>) -> Result<Self, String> {
+        let owner = fields
+            .get("owner")
+            .cloned()
+            .ok_or_else(|| "API key is missing 'owner' field".to_string())?;
+        let rate_limit = fields
+            .get("rate_limit")
+            .ok_or_else(|| "API key is missing 'rate_limit' field".to_string())?
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid rate_limit: {}", e))?;
+        let kind = match fields.get("kind").map(String::as_str) {
+            Some("browser") => KeyKind::Browser,
+            _ => KeyKind::Standard,
+        };
+        let allowed_origins = fields
+            .get("allowed_origins")
+            .map(|v| split_list(v))
+            .unwrap_or_default();
+        let defaults = RequestDefaults::new(
+            fields.get("default_commitment").cloned(),
+            fields.get("default_encoding").cloned(),
+        )?;
+        let method_routes = match fields.get("method_routes") {
+            Some(routes) => parse_method_routes(&split_list(routes))?,
+            None => HashMap::new(),
+        };
+        let tier = fields.get("tier").filter(|t| !t.is_empty()).cloned();
+        let audit = fields.get("audit").map(String::as_str) == Some("true");
+        let timing = fields.get("timing").map(String::as_str) == Some("true");
+        let broadcast = fields.get("broadcast").map(String::as_str) == Some("true");
+        let method_list = |field: &str| match fields.get(field) {
+            Some(methods) => parse_method_list(&split_list(methods)),
+            None => Ok(Vec::new()),
+        };
+        let allowed_methods = method_list("allowed_methods")?;
+        let blocked_methods = method_list("blocked_methods")?;
+        let scopes = match fields.get("scopes") {
+            Some(scopes) => parse_scopes(&split_list(scopes))?,
+            None => Vec::new(),
+        };
+        let spend_cap = fields
+            .get("spend_cap")
+            .map(|v| parse_spend_cap(v))
+            .transpose()?;
+        let privacy = match fields.get("privacy") {
+            Some(level) => LogPrivacy::parse(level)?,
+            None => LogPrivacy::default(),
+        };
+        let throttle_field = |field: &str| -> Result<u32, String> {
+            fields.get(field).map_or(Ok(0), |v| {
+                v.parse::<u32>()
+                    .map_err(|e| format!("Invalid {}: {}", field, e))
+            })
+        };
+        let firehose = FirehoseThrottle {
+            max_per_sec: throttle_field("ws_max_notifications_per_sec")?,
+            sample_every: throttle_field("ws_sample_every")?,
+        };
+        let max_in_flight = throttle_field("max_in_flight")?;
+        let expires_at = fields
+            .get("expires_at")
+            .map(|v| {
+                v.parse::<u64>()
+                    .map_err(|e| format!("Invalid expires_at: {}", e))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            owner,
+            rate_limit,
+            kind,
+            allowed_origins,
+            defaults,
+            method_routes,
+            tier,
+            audit,
+            privacy,
+            firehose,
+            timing,
+            broadcast,
+            allowed_methods,
+            scopes,
+            blocked_methods,
+            spend_cap,
+            max_in_flight,
+            expires_at,
+            rate_limit_remaining: None,
+        })
+    }
+
+    /// Whether the key's `expires_at` has passed at `now` (unix seconds).
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| now >= at)
+    }
+
+    /// Whether this key is limited to, or kept from, some methods.
+    pub fn restricts_methods(&self) -> bool {
+        !self.allowed_methods.is_empty()
+            || !self.scopes.is_empty()
+            || !self.blocked_methods.is_empty()
+    }
+
+    /// Whether this key may call `method`: listed in `allowed_methods` or in
+    /// one of its `scopes` (or neither is set), and not blocked. The HTTP and
+    /// WebSocket paths both check requests with this.
+    pub fn allows_method(&self, method: &str) -> bool {
+        let allowed = (self.allowed_methods.is_empty() && self.scopes.is_empty())
+            || self.allowed_methods.iter().any(|m| m == method)
+            || self.scopes.contains(&MethodCategory::of(method));
+        allowed && !self.blocked_methods.iter().any(|m| m == method)
+    }
+
+    /// Backend label this key routes `method` to: its own route first, then its
+    /// tier's. `None` leaves the choice to the global `method_routes`.
+    pub fn route<'a>(
+        &'a self,
+        method: &str,
+        tier_routes: &'a HashMap<String, HashMap<String, String>>,
+    ) -> Option<&'a str> {
+        self.method_routes
+            .get(method)
+            .or_else(|| tier_routes.get(self.tier.as_ref()?)?.get(method))
+            .map(String::as_str)
+    }
+}
+
+/// Parse `method=label` entries, rejecting unknown methods.
+pub fn parse_method_routes(entries: &[String]) -> Result<HashMap<String, String>, String> {
+    entries
+        .iter()
+        .map(|entry| {
+            let (method, label) = entry
+                .split_once('=')
+                .map(|(m, l)| (m.trim(), l.trim()))
+                .filter(|(m, l)| !m.is_empty() && !l.is_empty())
+                .ok_or_else(|| {
+                    format!("Invalid method route '{}': expected method=label", entry)
+                })?;
+            if !is_known_method(method) {
+                return Err(format!("Invalid method route: unknown method '{}'", method));
+            }
+            Ok((method.to_string(), label.to_string()))
+        })
+        .collect()
+}
+
+/// Check a list of method names, rejecting unknown ones.
+pub fn parse_method_list(methods: &[String]) -> Result<Vec<String>, String> {
+    methods
+        .iter()
+        .map(|method| {
+            if is_known_method(method) {
+                Ok(method.clone())
+            } else {
+                Err(format!("Invalid method list: unknown method '{}'", method))
+            }
+        })
+        .collect()
+}
+
+/// Parse a list of method category names (`read`, `write`, `subscribe`,
+/// `das`, `admin-extensions`), dropping repeats.
+pub fn parse_scopes(scopes: &[String]) -> Result<Vec<MethodCategory>, String> {
+    let mut parsed = Vec::new();
+    for scope in scopes {
+        let scope = MethodCategory::parse(scope)?;
+        if !parsed.contains(&scope) {
+            parsed.push(scope);
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parse a spend cap: a non-negative amount in the currency of
+/// `spending_caps` prices.
+pub fn parse_spend_cap(value: &str) -> Result<f64, String> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|cap| cap.is_finite() && *cap >= 0.0)
+        .ok_or_else(|| format!("Invalid spend_cap '{}': expected an amount >= 0", value))
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Why a key could not be validated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyStoreError {
+    /// The key's token bucket is empty
+    RateLimited,
+    /// The store could not be reached or failed, e.g. Redis is down
+    Unavailable(String),
+    /// The key's stored record cannot be read
+    Invalid(String),
+}
+
+impl std::fmt::Display for KeyStoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RateLimited => f.write_str("Rate limit exceeded"),
+            Self::Unavailable(e) | Self::Invalid(e) => f.write_str(e),
+        }
+    }
 }
 
 #[async_trait]
 pub trait KeyStore: Send + Sync {
-    async fn validate_key(&self, key: &str) -> Result<Option<KeyInfo>, String>;
+    async fn validate_key(&self, key: &str) -> Result<Option<KeyInfo>, KeyStoreError>;
+
+    /// Like [`validate_key`](Self::validate_key), without counting the request
+    /// against the key's rate limit. Used for probe traffic.
+    async fn lookup_key(&self, key: &str) -> Result<Option<KeyInfo>, KeyStoreError>;
+
+    /// Deactivate `key` immediately. Returns `false` if the key does not exist.
+    async fn revoke_key(&self, key: &str) -> Result<bool, String>;
+
+    /// Turn audit logging for `key` on or off. Returns `false` if the key does
+    /// not exist.
+    async fn set_audit(&self, key: &str, enabled: bool) -> Result<bool, String>;
+}
+
+/// Most keys tried from one comma-separated `api-key` list, so a single
+/// request cannot cause an unbounded number of key lookups.
+pub const MAX_KEYS_PER_REQUEST: usize = 3;
+
+/// Validate an `api-key` value holding one key or an ordered, comma-separated
+/// list of them. The first key that is valid and within its rate limit is
+/// returned along with its info, which lets clients roll over to a new key by
+/// listing it first. When no key is usable the most telling failure is
+/// returned: a rate limit, then a lookup error, then `Ok(None)`.
+pub async fn validate_key_list(
+    keystore: &dyn KeyStore,
+    keys: &str,
+) -> Result<Option<(String, KeyInfo)>, KeyStoreError> {
+    check_key_list(keystore, keys, true).await
+}
+
+/// Like [`validate_key_list`], without rate limiting, for probe traffic.
+pub async fn lookup_key_list(
+    keystore: &dyn KeyStore,
+    keys: &str,
+) -> Result<Option<(String, KeyInfo)>, KeyStoreError> {
+    check_key_list(keystore, keys, false).await
+}
+
+async fn check_key_list(
+    keystore: &dyn KeyStore,
+    keys: &str,
+    rate_limited: bool,
+) -> Result<Option<(String, KeyInfo)>, KeyStoreError> {
+    let mut rejection = Ok(None);
+    for (position, key) in split_list(keys)
+        .into_iter()
+        .take(MAX_KEYS_PER_REQUEST)
+        .enumerate()
+    {
+        let result = if rate_limited {
+            keystore.validate_key(&key).await
+        } else {
+            keystore.lookup_key(&key).await
+        };
+        match result {
+            Ok(Some(info)) => {
+                if position > 0 {
+                    counter!("api_key_fallbacks_total", "owner" => info.owner.clone()).increment(1);
+                }
+                return Ok(Some((key, info)));
+            }
+            Ok(None) => {}
+            Err(KeyStoreError::RateLimited) => rejection = Err(KeyStoreError::RateLimited),
+            Err(e) => {
+                if rejection.is_ok() {
+                    rejection = Err(e);
+                }
+            }
+        }
+    }
+    rejection
+}
+
+/// Redis pub/sub channel on which changed keys are announced, so every router
+/// drops its cached copy at once.
+pub const KEY_INVALIDATIONS: &str = "api_key_invalidations";
+
+/// Key lookups kept in process: valid keys for `ttl`, and unknown or inactive
+/// ones for `negative_ttl`, or not at all when it is zero.
+#[derive(Clone)]
+pub struct KeyCache {
+    cache: Cache<String, Option<KeyInfo>>,
+    negative_ttl: Duration,
+}
+
+struct KeyExpiry {
+    ttl: Duration,
+    negative_ttl: Duration,
+}
+
+impl Expiry<String, Option<KeyInfo>> for KeyExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &Option<KeyInfo>,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(if value.is_some() {
+            self.ttl
+        } else {
+            self.negative_ttl
+        })
+    }
+}
+
+impl KeyCache {
+    pub fn new(ttl: Duration, negative_ttl: Duration, max_entries: u64) -> Self {
+        let cache = Cache::builder()
+            .max_capacity(max_entries)
+            .expire_after(KeyExpiry { ttl, negative_ttl })
+            .build();
+        Self {
+            cache,
+            negative_ttl,
+        }
+    }
+
+    /// `Some(None)` for a key cached as invalid, `None` when it is not cached.
+    pub async fn get(&self, key: &str) -> Option<Option<KeyInfo>> {
+        self.cache.get(key).await
+    }
+
+    pub async fn insert(&self, key: &str, info: Option<KeyInfo>) {
+        if info.is_none() && self.negative_ttl.is_zero() {
+            return;
+        }
+        self.cache.insert(key.to_string(), info).await;
+    }
+
+    pub async fn invalidate(&self, key: &str) {
+        self.cache.invalidate(key).await;
+    }
+
+    pub fn invalidate_all(&self) {
+        self.cache.invalidate_all();
+    }
 }
 
 pub struct RedisKeyStore {
+    client: Client,
     conn: ConnectionManager,
-    cache: Cache<String, Option<KeyInfo>>,
+    cache: KeyCache,
 }
 
 impl RedisKeyStore {
-    pub async fn new(redis_url: &str) -> Result<Self, String> {
+    pub async fn new(redis_url: &str, config: &KeyCacheConfig) -> Result<Self, String> {
         let client = Client::open(redis_url).map_err(|e| e.to_string())?;
         let conn = client
             .get_connection_manager()
             .await
             .map_err(|e| e.to_string())?;
 
-        let cache = Cache::builder()
-            .time_to_live(Duration::from_secs(60)) // Cache keys for 1 min
-            .build();
+        let cache = KeyCache::new(
+            Duration::from_secs(config.ttl_secs),
+            Duration::from_secs(config.negative_ttl_secs),
+            config.max_entries,
+        );
 
-        Ok(Self { conn, cache })
+        Ok(Self {
+            client,
+            conn,
+            cache,
+        })
     }
 
-    async fn get_key_info(&self, key: &str) -> Result<Option<KeyInfo>, String> {
+    /// Drop cached keys as they are announced on [`KEY_INVALIDATIONS`]. Runs
+    /// until the process exits, subscribing again after a lost connection.
+    pub fn invalidation_loop(&self) -> impl Future<Output = ()> + Send + 'static {
+        let client = self.client.clone();
+        let cache = self.cache.clone();
+        async move {
+            loop {
+                match client.get_async_pubsub().await {
+                    Ok(mut pubsub) => match pubsub.subscribe(KEY_INVALIDATIONS).await {
+                        Ok(()) => {
+                            // Changes made while unsubscribed were missed
+                            cache.invalidate_all();
+                            let mut messages = pubsub.on_message();
+                            while let Some(msg) = messages.next().await {
+                                if let Ok(key) = msg.get_payload::<String>() {
+                                    cache.invalidate(&key).await;
+                                }
+                            }
+                            warn!("Key invalidation subscription lost, resubscribing");
+                        }
+                        Err(e) => warn!("Failed to subscribe to key invalidations: {}", e),
+                    },
+                    Err(e) => warn!("Failed to subscribe to key invalidations: {}", e),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+
+    async fn get_key_info(&self, key: &str) -> Result<Option<KeyInfo>, KeyStoreError> {
         // Check local cache
         if let Some(info) = self.cache.get(key).await {
-            return Ok(info);
+            counter!("api_key_cache_lookups_total", "result" => "hit").increment(1);
+            return Ok(info.filter(|info| !info.is_expired(unix_now())));
         }
+        counter!("api_key_cache_lookups_total", "result" => "miss").increment(1);
 
         // Check Redis
         let mut conn = self.conn.clone();
 
         let redis_key = format!("api_key:{}", key);
-        // Check if exists first to avoid errors on empty keys
-        let exists: bool = redis::cmd("EXISTS")
+        // A missing key yields an empty hash
+        let fields: HashMap<String, String> = redis::cmd("HGETALL")
             .arg(&redis_key)
             .query_async(&mut conn)
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| KeyStoreError::Unavailable(e.to_string()))?;
 
-        if !exists {
-            self.cache.insert(key.to_string(), None).await;
+        if fields.is_empty() || fields.get("active").map(String::as_str) == Some("false") {
+            self.cache.insert(key, None).await;
             return Ok(None);
         }
 
-        let owner: String = redis::cmd("HGET")
-            .arg(&redis_key)
-            .arg("owner")
-            .query_async(&mut conn)
-            .await
-            .map_err(|e| e.to_string())?;
-        let active: String = redis::cmd("HGET")
-            .arg(&redis_key)
-            .arg("active")
-            .query_async(&mut conn)
-            .await
-            .unwrap_or("true".to_string());
+        let info = KeyInfo::from_fields(&fields).map_err(KeyStoreError::Invalid)?;
+        self.cache.insert(key, Some(info.clone())).await;
 
-        if active == "false" {
-            self.cache.insert(key.to_string(), None).await;
-            return Ok(None);
-        }
+        Ok(Some(info).filter(|info| !info.is_expired(unix_now())))
+    }
 
-        let rate_limit: u64 = redis::cmd("HGET")
-            .arg(&redis_key)
-            .arg("rate_limit")
-            .query_async(&mut conn)
+    /// Take a token from the key's bucket. `Some(remaining)` when the request
+    /// is allowed, `None` when the bucket is empty.
+    async fn check_rate_limit(&self, key: &str, limit: u64) -> Result<Option<u64>, KeyStoreError> {
+        let mut conn = self.conn.clone();
+        let redis_key = format!("rate_bucket:{}", key);
+        let (allowed, remaining): (u64, u64) = redis::Script::new(TOKEN_BUCKET_SCRIPT)
+            .key(&redis_key)
+            .arg(limit)
+            .invoke_async(&mut conn)
             .await
-            .map_err(|e| e.to_string())?;
-
-        let info = KeyInfo { owner, rate_limit };
-        self.cache.insert(key.to_string(), Some(info.clone())).await;
+            .map_err(|e| KeyStoreError::Unavailable(e.to_string()))?;
 
-        Ok(Some(info))
+        Ok((allowed == 1).then_some(remaining))
     }
+}
+
+/// Token bucket holding up to `ARGV[1]` tokens, refilled at `ARGV[1]` per
+/// second, in the hash `KEYS[1]`. Runs atomically and uses the Redis clock, so
+/// every router instance shares one bucket per key. Returns `{allowed, remaining}`.
+pub const TOKEN_BUCKET_SCRIPT: &str = r#"
+    local capacity = tonumber(ARGV[1])
+    local time = redis.call("TIME")
+    local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+    local state = redis.call("HMGET", KEYS[1], "tokens", "ts")
+    local tokens = tonumber(state[1]) or capacity
+    local ts = tonumber(state[2]) or now
+    tokens = math.min(capacity, tokens + math.max(0, now - ts) * capacity / 1000)
+    local allowed = 0
+    if tokens >= 1 then
+        tokens = tokens - 1
+        allowed = 1
+    end
+    redis.call("HSET", KEYS[1], "tokens", tostring(tokens), "ts", now)
+    redis.call("PEXPIRE", KEYS[1], 2000)
+    return {allowed, math.floor(tokens)}
+"#;
+
+#[async_trait]
+impl KeyStore for RedisKeyStore {
+    async fn validate_key(&self, key: &str) -> Result<Option<KeyInfo>, KeyStoreError> {
+        // 1. Get Key Info (Cache -> Redis)
+        let info_opt = self.get_key_info(key).await?;
 
-    async fn check_rate_limit(&self, key: &str, limit: u64) -> Result<bool, String> {
-        if limit == 0 {
-            return Ok(true); // No limit
+        if let Some(info) = info_opt {
+            // 2. Check Rate Limit (0 means no limit)
+            let mut rate_limit_remaining = None;
+            if info.rate_limit > 0 {
+                match self.check_rate_limit(key, info.rate_limit).await? {
+                    Some(remaining) => rate_limit_remaining = Some(remaining),
+                    None => return Err(KeyStoreError::RateLimited),
+                }
+            }
+            return Ok(Some(KeyInfo {
+                rate_limit_remaining,
+                ..info
+            }));
         }
 
+        Ok(None)
+    }
+
+    async fn lookup_key(&self, key: &str) -> Result<Option<KeyInfo>, KeyStoreError> {
+        self.get_key_info(key).await
+    }
+
+    async fn revoke_key(&self, key: &str) -> Result<bool, String> {
         let mut conn = self.conn.clone();
-        let redis_key = format!("rate_limit:{}", key);
-
-        // Atomic INCR and Expire if needed
-        // Script to ensure atomicity: INCR key; IF == 1 THEN EXPIRE key 1; END; RETURN val
-        let script = redis::Script::new(
-            r#"
-            local count = redis.call("INCR", KEYS[1])
-            if count == 1 then
-                redis.call("EXPIRE", KEYS[1], 1)
-            end
-            return count
-        "#,
-        );
+        let revoked = revoke_key(&mut conn, key)
+            .await
+            .map_err(|e| e.to_string())?;
+        // Drop the cached entry so this instance stops accepting the key right away
+        self.cache.invalidate(key).await;
+        Ok(revoked)
+    }
 
-        let count: u64 = script
-            .key(&redis_key)
-            .invoke_async(&mut conn)
+    async fn set_audit(&self, key: &str, enabled: bool) -> Result<bool, String> {
+        let mut conn = self.conn.clone();
+        let found = set_key_audit(&mut conn, key, enabled)
             .await
             .map_err(|e| e.to_string())?;
+        self.cache.invalidate(key).await;
+        Ok(found)
+    }
+}
+
+/// How often a key validated again is remembered afresh under `fail_open`,
+/// so busy keys do not write to the cache on every request.
+const REMEMBER_REFRESH: Duration = Duration::from_secs(60);
+
+/// A [`KeyStore`] that authenticates requests by the `keystore_outage` policy
+/// while `inner` is [unavailable](KeyStoreError::Unavailable), typically
+/// because Redis is unreachable. Keys accepted this way are not rate limited,
+/// as their buckets live in the failed store.
+pub struct OutageKeyStore {
+    inner: Arc<dyn KeyStore>,
+    policy: KeystoreOutagePolicy,
+    static_keys: HashMap<String, KeyInfo>,
+    /// Keys `inner` validated, with when, for `fail_open`
+    remembered: Option<Cache<String, (KeyInfo, Instant)>>,
+}
+
+impl OutageKeyStore {
+    pub fn new(inner: Arc<dyn KeyStore>, config: &KeystoreOutageConfig, max_entries: u64) -> Self {
+        let static_keys = config
+            .static_keys
+            .iter()
+            .map(|k| {
+                let info = KeyInfo {
+                    owner: k.owner.clone(),
+                    ..KeyInfo::default()
+                };
+                (k.key.clone(), info)
+            })
+            .collect();
+        let remembered = (config.policy == KeystoreOutagePolicy::FailOpen).then(|| {
+            Cache::builder()
+                .max_capacity(max_entries)
+                .time_to_live(Duration::from_secs(config.remember_secs))
+                .build()
+        });
+        Self {
+            inner,
+            policy: config.policy,
+            static_keys,
+            remembered,
+        }
+    }
 
-        if count > limit {
-            return Ok(false);
+    /// Pass on `inner`'s answer for `key`, or apply the policy when it failed.
+    async fn check(
+        &self,
+        key: &str,
+        result: Result<Option<KeyInfo>, KeyStoreError>,
+    ) -> Result<Option<KeyInfo>, KeyStoreError> {
+        let error = match result {
+            Err(KeyStoreError::Unavailable(e)) => e,
+            result => {
+                if let Ok(found) = &result {
+                    self.remember(key, found.as_ref()).await;
+                }
+                return result;
+            }
+        };
+        let info = match self.policy {
+            KeystoreOutagePolicy::FailClosed => None,
+            KeystoreOutagePolicy::FailOpen => match &self.remembered {
+                Some(remembered) => remembered
+                    .get(key)
+                    .await
+                    .map(|(info, _)| info)
+                    .filter(|info| !info.is_expired(unix_now())),
+                None => None,
+            },
+            KeystoreOutagePolicy::StaticKeys => self.static_keys.get(key).cloned(),
+        };
+        let result = if info.is_some() {
+            "allowed"
+        } else {
+            "rejected"
+        };
+        counter!("api_key_outage_requests_total", "policy" => self.policy.as_str(), "result" => result)
+            .increment(1);
+        match info {
+            Some(info) => Ok(Some(KeyInfo {
+                rate_limit_remaining: None,
+                ..info
+            })),
+            None => Err(KeyStoreError::Unavailable(error)),
         }
+    }
 
-        Ok(true)
+    async fn remember(&self, key: &str, info: Option<&KeyInfo>) {
+        let Some(remembered) = &self.remembered else {
+            return;
+        };
+        let Some(info) = info else {
+            // Unknown, inactive or revoked keys are no longer accepted
+            remembered.invalidate(key).await;
+            return;
+        };
+        let fresh = remembered
+            .get(key)
+            .await
+            .is_some_and(|(_, at)| at.elapsed() < REMEMBER_REFRESH);
+        if !fresh {
+            remembered
+                .insert(key.to_string(), (info.clone(), Instant::now()))
+                .await;
+        }
     }
 }
 
 #[async_trait]
-impl KeyStore for RedisKeyStore {
-    async fn validate_key(&self, key: &str) -> Result<Option<KeyInfo>, String> {
-        // 1. Get Key Info (Cache -> Redis)
-        let info_opt = self.get_key_info(key).await?;
+impl KeyStore for OutageKeyStore {
+    async fn validate_key(&self, key: &str) -> Result<Option<KeyInfo>, KeyStoreError> {
+        let result = self.inner.validate_key(key).await;
+        self.check(key, result).await
+    }
 
-        if let Some(info) = info_opt {
-            // 2. Check Rate Limit
-            if !self.check_rate_limit(key, info.rate_limit).await? {
-                return Err("Rate limit exceeded".to_string());
-            }
-            return Ok(Some(info));
+    async fn lookup_key(&self, key: &str) -> Result<Option<KeyInfo>, KeyStoreError> {
+        let result = self.inner.lookup_key(key).await;
+        self.check(key, result).await
+    }
+
+    async fn revoke_key(&self, key: &str) -> Result<bool, String> {
+        if let Some(remembered) = &self.remembered {
+            remembered.invalidate(key).await;
         }
+        self.inner.revoke_key(key).await
+    }
 
-        Ok(None)
+    async fn set_audit(&self, key: &str, enabled: bool) -> Result<bool, String> {
+        self.inner.set_audit(key, enabled).await
+    }
+}
+
+/// Redis set holding every key created through the admin tooling, used for listing.
+pub const KEYS_INDEX: &str = "api_keys_index";
+
+/// Summary of a stored API key, as returned by [`list_keys`].
+#[derive(Debug, Clone)]
+pub struct KeySummary {
+    pub key: String,
+    pub owner: Option<String>,
+    pub active: bool,
+}
+
+/// Generate a random 32-character alphanumeric API key.
+pub fn generate_key() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// Attributes of a key created through [`create_key`].
+#[derive(Debug, Clone, Default)]
+pub struct NewKey {
+    pub owner: String,
+    pub rate_limit: u64,
+    pub expires_at: Option<u64>,
+    /// When non-empty the key is created as a browser key bound to these origins
+    pub allowed_origins: Vec<String>,
+    pub defaults: RequestDefaults,
+    pub method_routes: HashMap<String, String>,
+    pub tier: Option<String>,
+    pub privacy: LogPrivacy,
+    pub firehose: FirehoseThrottle,
+    pub timing: bool,
+    pub broadcast: bool,
+    pub allowed_methods: Vec<String>,
+    pub scopes: Vec<MethodCategory>,
+    pub blocked_methods: Vec<String>,
+    pub spend_cap: Option<f64>,
+    pub max_in_flight: u32,
+}
+
+/// Store a new API key hash and add it to the listing index.
+pub async fn create_key<C: ConnectionLike + Send>(
+    con: &mut C,
+    key: &str,
+    new_key: &NewKey,
+) -> RedisResult<()> {
+    let redis_key = format!("api_key:{}", key);
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .hset(&redis_key, "owner", &new_key.owner)
+        .hset(&redis_key, "rate_limit", new_key.rate_limit)
+        .hset(&redis_key, "created_at", created_at)
+        .hset(&redis_key, "active", "true");
+
+    if let Some(exp) = new_key.expires_at {
+        pipe.hset(&redis_key, "expires_at", exp);
+    }
+    if let Some(commitment) = &new_key.defaults.commitment {
+        pipe.hset(&redis_key, "default_commitment", commitment);
+    }
+    if let Some(encoding) = &new_key.defaults.encoding {
+        pipe.hset(&redis_key, "default_encoding", encoding);
+    }
+    if !new_key.method_routes.is_empty() {
+        let mut routes: Vec<String> = new_key
+            .method_routes
+            .iter()
+            .map(|(method, label)| format!("{}={}", method, label))
+            .collect();
+        routes.sort();
+        pipe.hset(&redis_key, "method_routes", routes.join(","));
+    }
+    if let Some(tier) = &new_key.tier {
+        pipe.hset(&redis_key, "tier", tier);
+    }
+    if new_key.privacy != LogPrivacy::Full {
+        pipe.hset(&redis_key, "privacy", new_key.privacy.as_str());
+    }
+    if new_key.firehose.max_per_sec > 0 {
+        pipe.hset(
+            &redis_key,
+            "ws_max_notifications_per_sec",
+            new_key.firehose.max_per_sec,
+        );
+    }
+    if new_key.firehose.sample_every > 1 {
+        pipe.hset(&redis_key, "ws_sample_every", new_key.firehose.sample_every);
+    }
+    if new_key.timing {
+        pipe.hset(&redis_key, "timing", "true");
+    }
+    if new_key.broadcast {
+        pipe.hset(&redis_key, "broadcast", "true");
+    }
+    if !new_key.allowed_methods.is_empty() {
+        pipe.hset(
+            &redis_key,
+            "allowed_methods",
+            new_key.allowed_methods.join(","),
+        );
+    }
+    if !new_key.scopes.is_empty() {
+        let scopes: Vec<&str> = new_key.scopes.iter().map(|s| s.as_str()).collect();
+        pipe.hset(&redis_key, "scopes", scopes.join(","));
+    }
+    if !new_key.blocked_methods.is_empty() {
+        pipe.hset(
+            &redis_key,
+            "blocked_methods",
+            new_key.blocked_methods.join(","),
+        );
+    }
+    if let Some(cap) = new_key.spend_cap {
+        pipe.hset(&redis_key, "spend_cap", cap);
+    }
+    if new_key.max_in_flight > 0 {
+        pipe.hset(&redis_key, "max_in_flight", new_key.max_in_flight);
+    }
+    if !new_key.allowed_origins.is_empty() {
+        pipe.hset(&redis_key, "kind", "browser").hset(
+            &redis_key,
+            "allowed_origins",
+            new_key.allowed_origins.join(","),
+        );
+    }
+
+    let _: () = pipe.query_async(con).await?;
+    let _: () = con.sadd(KEYS_INDEX, key).await?;
+    // Routers may have cached the key as unknown
+    announce_key_change(con, key).await
+}
+
+/// Tell every router to drop its cached copy of `key`.
+pub async fn announce_key_change<C: ConnectionLike + Send>(
+    con: &mut C,
+    key: &str,
+) -> RedisResult<()> {
+    let _: () = con.publish(KEY_INVALIDATIONS, key).await?;
+    Ok(())
+}
+
+/// Mark an API key inactive. Returns `false` if the key does not exist.
+pub async fn revoke_key<C: ConnectionLike + Send>(con: &mut C, key: &str) -> RedisResult<bool> {
+    let redis_key = format!("api_key:{}", key);
+    let exists: bool = con.exists(&redis_key).await?;
+    if !exists {
+        return Ok(false);
+    }
+    let _: () = con.hset(&redis_key, "active", "false").await?;
+    announce_key_change(con, key).await?;
+    Ok(true)
+}
+
+/// Set or clear the `audit` flag of an API key. Returns `false` if the key does
+/// not exist.
+pub async fn set_key_audit<C: ConnectionLike + Send>(
+    con: &mut C,
+    key: &str,
+    enabled: bool,
+) -> RedisResult<bool> {
+    let redis_key = format!("api_key:{}", key);
+    let exists: bool = con.exists(&redis_key).await?;
+    if !exists {
+        return Ok(false);
+    }
+    if enabled {
+        let _: () = con.hset(&redis_key, "audit", "true").await?;
+    } else {
+        let _: () = con.hdel(&redis_key, "audit").await?;
+    }
+    announce_key_change(con, key).await?;
+    Ok(true)
+}
+
+/// List every indexed API key with its owner and active flag.
+pub async fn list_keys<C: ConnectionLike + Send>(con: &mut C) -> RedisResult<Vec<KeySummary>> {
+    let keys: Vec<String> = con.smembers(KEYS_INDEX).await?;
+    let mut summaries = Vec::with_capacity(keys.len());
+    for key in keys {
+        let redis_key = format!("api_key:{}", key);
+        let owner: Option<String> = con.hget(&redis_key, "owner").await?;
+        let active: Option<String> = con.hget(&redis_key, "active").await?;
+        summaries.push(KeySummary {
+            key,
+            owner,
+            active: active.as_deref() != Some("false"),
+        });
     }
+    Ok(summaries)
 }
@@ -0,0 +1,25 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::Subsystem;
+
+/// Subsystems switched off through the admin API, on top of
+/// `kill_switches.disabled` in the config. Local to this instance and kept
+/// across config reloads until switched back on.
+#[derive(Default)]
+pub struct KillSwitches {
+    disabled: [AtomicBool; Subsystem::ALL.len()],
+}
+
+impl KillSwitches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, subsystem: Subsystem, disabled: bool) {
+        self.disabled[subsystem as usize].store(disabled, Ordering::Relaxed);
+    }
+
+    pub fn is_set(&self, subsystem: Subsystem) -> bool {
+        self.disabled[subsystem as usize].load(Ordering::Relaxed)
+    }
+}
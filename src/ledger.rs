@@ -0,0 +1,361 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use metrics::counter;
+use redis::{aio::ConnectionManager, Client};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::time::{interval, Duration};
+use tracing::{info, warn};
+
+use crate::{
+    config::{UsageLedgerConfig, UsageLedgerStoreKind},
+    redact::redact,
+    signing::{unix_now, utc_date},
+};
+
+/// Redis keys of one day's totals: `usage:daily:<YYYY-MM-DD>:{requests,errors}`.
+const REDIS_KEY_PREFIX: &str = "usage:daily";
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Requests one key made on one (UTC) day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageEntry {
+    pub owner: String,
+    /// `YYYY-MM-DD`
+    pub day: String,
+    pub requests: u64,
+    /// Requests answered with a 4xx or 5xx, or that were not valid JSON-RPC
+    pub errors: u64,
+}
+
+/// Days since the unix epoch.
+pub fn day_number(unix: u64) -> u64 {
+    unix / SECS_PER_DAY
+}
+
+/// `YYYY-MM-DD` of a day number.
+pub fn day_string(day: u64) -> String {
+    let (year, month, day) = utc_date(day * SECS_PER_DAY);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Day number of a `YYYY-MM-DD` date; `None` if it is not a valid date from
+/// 1970 on.
+pub fn parse_day(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Civil date to days since 1970-01-01 (Howard Hinnant's algorithm)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let number = u64::try_from(era * 146_097 + doe - 719_468).ok()?;
+    // Reject days past the end of their month, such as 2024-02-30
+    (day_string(number) == date).then_some(number)
+}
+
+#[async_trait]
+pub trait UsageLedger: Send + Sync {
+    /// Add `entries` to the stored totals.
+    async fn add(&self, entries: &[UsageEntry]) -> Result<(), String>;
+
+    /// Totals for days `from..=to` (day numbers), for one owner or all of them;
+    /// most recent day first, then by owner.
+    async fn query(
+        &self,
+        owner: Option<&str>,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<UsageEntry>, String>;
+
+    /// Drop totals for days before `before`. Returns how many were removed.
+    async fn prune(&self, before: u64) -> Result<u64, String>;
+}
+
+/// Open the ledger selected by `config`.
+pub async fn open_ledger(
+    config: &UsageLedgerConfig,
+    redis_url: &str,
+) -> Result<Box<dyn UsageLedger>, String> {
+    match config.store {
+        UsageLedgerStoreKind::Redis => Ok(Box::new(
+            RedisUsageLedger::new(redis_url, config.retention_days).await?,
+        )),
+        UsageLedgerStoreKind::Sqlite => Ok(Box::new(SqliteUsageLedger::open(&config.path)?)),
+    }
+}
+
+/// Daily totals in per-day Redis hashes keyed by owner, expiring after the
+/// retention period (so `prune` has nothing to do).
+pub struct RedisUsageLedger {
+    conn: ConnectionManager,
+    retention_days: u64,
+}
+
+impl RedisUsageLedger {
+    pub async fn new(redis_url: &str, retention_days: u64) -> Result<Self, String> {
+        let client = Client::open(redis_url).map_err(|e| e.to_string())?;
+        let conn = client
+            .get_connection_manager()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            conn,
+            retention_days,
+        })
+    }
+}
+
+fn redis_keys(day: &str) -> (String, String) {
+    (
+        format!("{}:{}:requests", REDIS_KEY_PREFIX, day),
+        format!("{}:{}:errors", REDIS_KEY_PREFIX, day),
+    )
+}
+
+#[async_trait]
+impl UsageLedger for RedisUsageLedger {
+    async fn add(&self, entries: &[UsageEntry]) -> Result<(), String> {
+        let ttl = (self.retention_days + 1) * SECS_PER_DAY;
+        let mut pipe = redis::pipe();
+        for entry in entries {
+            let (requests_key, errors_key) = redis_keys(&entry.day);
+            pipe.hincr(&requests_key, &entry.owner, entry.requests)
+                .ignore()
+                .expire(&requests_key, ttl as i64)
+                .ignore();
+            if entry.errors > 0 {
+                pipe.hincr(&errors_key, &entry.owner, entry.errors)
+                    .ignore()
+                    .expire(&errors_key, ttl as i64)
+                    .ignore();
+            }
+        }
+        let mut conn = self.conn.clone();
+        pipe.query_async::<()>(&mut conn)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn query(
+        &self,
+        owner: Option<&str>,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<UsageEntry>, String> {
+        let mut conn = self.conn.clone();
+        let mut entries = Vec::new();
+        for day in (from..=to).rev() {
+            let day = day_string(day);
+            let (requests_key, errors_key) = redis_keys(&day);
+            let (requests, errors): (HashMap<String, u64>, HashMap<String, u64>) = redis::pipe()
+                .hgetall(&requests_key)
+                .hgetall(&errors_key)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut day_entries: Vec<UsageEntry> = requests
+                .into_iter()
+                .filter(|(o, _)| owner.is_none_or(|owner| owner == o))
+                .map(|(o, count)| UsageEntry {
+                    errors: errors.get(&o).copied().unwrap_or(0),
+                    owner: o,
+                    day: day.clone(),
+                    requests: count,
+                })
+                .collect();
+            day_entries.sort_by(|a, b| a.owner.cmp(&b.owner));
+            entries.extend(day_entries);
+        }
+        Ok(entries)
+    }
+
+    async fn prune(&self, _before: u64) -> Result<u64, String> {
+        Ok(0)
+    }
+}
+
+/// Daily totals in a local SQLite database, for deployments without Redis
+/// analytics. Queries run on the blocking pool.
+pub struct SqliteUsageLedger {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteUsageLedger {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS usage_daily (
+                owner TEXT NOT NULL,
+                day TEXT NOT NULL,
+                requests INTEGER NOT NULL DEFAULT 0,
+                errors INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (owner, day)
+            )",
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    ) -> Result<T, String> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut conn)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+    }
+}
+
+#[async_trait]
+impl UsageLedger for SqliteUsageLedger {
+    async fn add(&self, entries: &[UsageEntry]) -> Result<(), String> {
+        let entries = entries.to_vec();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            {
+                let mut upsert = tx.prepare_cached(
+                    "INSERT INTO usage_daily (owner, day, requests, errors) VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT (owner, day) DO UPDATE SET
+                        requests = requests + excluded.requests,
+                        errors = errors + excluded.errors",
+                )?;
+                for entry in &entries {
+                    upsert.execute(params![
+                        entry.owner,
+                        entry.day,
+                        entry.requests as i64,
+                        entry.errors as i64
+                    ])?;
+                }
+            }
+            tx.commit()
+        })
+        .await
+    }
+
+    async fn query(
+        &self,
+        owner: Option<&str>,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<UsageEntry>, String> {
+        let owner = owner.map(str::to_string);
+        let (from, to) = (day_string(from), day_string(to));
+        self.run(move |conn| {
+            let mut select = conn.prepare_cached(
+                "SELECT owner, day, requests, errors FROM usage_daily
+                 WHERE day BETWEEN ?1 AND ?2 AND (?3 IS NULL OR owner = ?3)
+                 ORDER BY day DESC, owner",
+            )?;
+            let rows = select.query_map(params![from, to, owner], |row| {
+                Ok(UsageEntry {
+                    owner: row.get(0)?,
+                    day: row.get(1)?,
+                    requests: row.get::<_, i64>(2)? as u64,
+                    errors: row.get::<_, i64>(3)? as u64,
+                })
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+
+    async fn prune(&self, before: u64) -> Result<u64, String> {
+        let before = day_string(before);
+        self.run(move |conn| {
+            conn.execute("DELETE FROM usage_daily WHERE day < ?1", params![before])
+                .map(|removed| removed as u64)
+        })
+        .await
+    }
+}
+
+/// Counts collected since the last flush, so the ledger is written every
+/// `usage_ledger.flush_secs` rather than on every request.
+#[derive(Default)]
+pub struct UsageBuffer {
+    /// (owner, day number) -> (requests, errors)
+    counts: Mutex<HashMap<(String, u64), (u64, u64)>>,
+}
+
+impl UsageBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, owner: &str, error: bool, now: u64) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let (requests, errors) = counts
+            .entry((owner.to_string(), day_number(now)))
+            .or_default();
+        *requests += 1;
+        *errors += u64::from(error);
+    }
+
+    /// Everything recorded since the last call.
+    pub fn take(&self) -> Vec<UsageEntry> {
+        let counts = std::mem::take(&mut *self.counts.lock().unwrap_or_else(|e| e.into_inner()));
+        counts
+            .into_iter()
+            .map(|((owner, day), (requests, errors))| UsageEntry {
+                owner,
+                day: day_string(day),
+                requests,
+                errors,
+            })
+            .collect()
+    }
+}
+
+/// Write buffered counts to `ledger` every `flush_secs`, and drop days past the
+/// retention period once a day. Counts that fail to write are dropped after a
+/// warning rather than retried, so a down store cannot grow the buffer.
+pub async fn usage_ledger_loop(
+    buffer: Arc<UsageBuffer>,
+    ledger: Arc<dyn UsageLedger>,
+    config: UsageLedgerConfig,
+) {
+    let mut ticker = interval(Duration::from_secs(config.flush_secs));
+    let mut pruned_day = None;
+    loop {
+        ticker.tick().await;
+        let entries = buffer.take();
+        if !entries.is_empty() {
+            if let Err(e) = ledger.add(&entries).await {
+                warn!("Failed to write usage ledger: {}", redact(&e));
+                counter!("usage_ledger_write_failures_total").increment(1);
+            }
+        }
+
+        let today = day_number(unix_now());
+        if pruned_day != Some(today) {
+            let before = (today + 1).saturating_sub(config.retention_days);
+            match ledger.prune(before).await {
+                Ok(0) => {}
+                Ok(removed) => info!("Pruned {} usage ledger rows", removed),
+                Err(e) => warn!("Failed to prune usage ledger: {}", redact(&e)),
+            }
+            pruned_day = Some(today);
+        }
+    }
+}
@@ -1,6 +1,64 @@
+pub mod admin;
+pub mod alerts;
+pub mod archive;
+pub mod audit;
+pub mod auto_route;
+pub mod backpressure;
+pub mod balancer;
+pub mod batch;
+pub mod bench;
+pub mod blockhash;
+pub mod broadcast;
+pub mod browser;
+pub mod cache;
+pub mod circuit;
+pub mod cli;
+pub mod coalesce;
+pub mod compression;
+pub mod concurrency;
 pub mod config;
+pub mod consistency;
+pub mod dead_letter;
+pub mod defaults;
+pub mod discovery;
+pub mod error_templates;
+pub mod filter;
+pub mod genesis;
 pub mod handlers;
 pub mod health;
+pub mod heatmap;
+pub mod hedging;
+pub mod integrity;
+pub mod journal;
+pub mod key_usage;
 pub mod keystore;
+pub mod kill_switch;
+pub mod ledger;
+pub mod log_format;
+pub mod method_acl;
+pub mod methods;
+pub mod metrics_export;
 pub mod mock;
+pub mod net;
+pub mod network_stats;
+pub mod offload;
+pub mod poll_bridge;
+pub mod profiling;
+pub mod protocol;
+pub mod redact;
+pub mod reload;
+pub mod runtime;
+pub mod server;
+pub mod signing;
+pub mod slot_feed;
+pub mod sniff;
+pub mod spending;
 pub mod state;
+pub mod stats;
+pub mod timing;
+pub mod tls;
+pub mod trace;
+pub mod traffic;
+pub mod ttl_tuning;
+pub mod usage;
+pub mod ws;
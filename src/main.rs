@@ -1,63 +1,174 @@
-use std::{net::SocketAddr, sync::{atomic::AtomicBool, Arc}};
+use std::{net::IpAddr, sync::Arc, time::Duration};
 
 use arc_swap::ArcSwap;
-use axum::{
-    middleware,
-    routing::{get, post},
-    Router,
-};
 use clap::Parser;
-use hyper_tls::HttpsConnector;
-use hyper_util::client::legacy::Client;
-use metrics_exporter_prometheus::PrometheusBuilder;
 use sol_rpc_router::{
-    config::load_config,
-    handlers::{extract_rpc_method, health_endpoint, log_requests, proxy, track_metrics, ws_proxy},
-    health::{health_check_loop, HealthState},
-    keystore::RedisKeyStore,
-    state::{AppState, RouterState, RuntimeBackend},
+    alerts::alerting_loop,
+    auto_route::auto_routing_loop,
+    blockhash::blockhash_loop,
+    cli::{self, Command},
+    config::{load_config, Config},
+    consistency::consistency_loop,
+    dead_letter::open_store,
+    genesis::{genesis_check_loop, verify_genesis},
+    handlers::resume_journal,
+    health::HealthState,
+    journal::open_journal,
+    key_usage::{key_usage_loop, KeyUsageStore, RedisKeyUsageStore},
+    keystore::{OutageKeyStore, RedisKeyStore},
+    ledger::{open_ledger, usage_ledger_loop, UsageLedger},
+    log_format::{self, RouterFormat},
+    metrics_export::{export_loop, install_push_recorder, push_exporter},
+    net::bind_all,
+    poll_bridge::poll_bridge_loop,
+    protocol::BackendClients,
+    redact::{self, redact_url, RedactingMakeWriter},
+    reload::{reload_config, router_state_from_config, ReloadStatus},
+    runtime,
+    server::{
+        http_router, install_metrics_recorder, operations_router, start_health_checks,
+        start_listeners, start_tls_listeners, ws_router,
+    },
+    slot_feed::slot_feed_loop,
+    spending::{spending_loop, RedisSpendStore, SpendTracker},
+    state::{upstream_client, AppState},
+    tls::{acceptor, cert_reload_loop, CertResolver},
+    trace::Tracer,
+    usage::usage_metrics_loop,
 };
 use tokio::signal::unix::{signal, SignalKind};
-use tower_http::cors::CorsLayer;
 use tracing::{error, info};
 
+/// jemalloc, so `/admin/profile/heap` can dump its heap profile
+#[cfg(feature = "heap-profiling")]
+#[global_allocator]
+static ALLOCATOR: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+/// Heap profiling on from the start, sampling an allocation every 512 KiB on
+/// average. `_RJEM_MALLOC_CONF` in the environment overrides it.
+#[cfg(feature = "heap-profiling")]
+#[export_name = "_rjem_malloc_conf"]
+pub static MALLOC_CONF: &[u8; 45] = b"prof:true,prof_active:true,lg_prof_sample:19\0";
+
 #[derive(Parser, Debug)]
 #[command(name = "rpc-router")]
 #[command(about = "RPC router with load balancing and health monitoring", long_about = None)]
 struct Args {
     /// Path to configuration file
-    #[arg(short, long, default_value = "config.toml")]
+    #[arg(short, long, default_value = "config.toml", global = true)]
     config: String,
-}
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
+    /// Print every config value after `ROUTER__*` environment overrides, with
+    /// where it came from, and exit
+    #[arg(long)]
+    print_effective_config: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    // Initialize Prometheus recorder with histogram buckets
-    // Using set_buckets makes the exporter emit true Prometheus histograms (_bucket/_sum/_count)
-    // instead of summaries, which is required for histogram_quantile() in Grafana.
-    let builder = PrometheusBuilder::new()
-        .set_buckets(&[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0])
-        .expect("failed to set histogram buckets");
-    let handle = builder
-        .install_recorder()
-        .expect("failed to install Prometheus recorder");
+fn main() {
+    tracing_subscriber::fmt()
+        .with_writer(RedactingMakeWriter)
+        .event_format(RouterFormat::default())
+        .init();
 
     // Parse command-line arguments
     let args = Args::parse();
 
+    if args.print_effective_config {
+        if let Err(e) = cli::print_effective_config(&args.config) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let result = match args.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            serve(args.config);
+            Ok(())
+        }
+        command => tokio::runtime::Runtime::new()
+            .expect("Failed to start the Tokio runtime")
+            .block_on(run_command(&args.config, command)),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run_command(
+    config_path: &str,
+    command: Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Serve => unreachable!("served on the configured runtime"),
+        Command::CheckConfig { probe } => cli::check_config(config_path, probe).await,
+        Command::Keys { redis_url, command } => {
+            cli::run_keys(config_path, redis_url, command).await
+        }
+        Command::Backends {
+            router_url,
+            command,
+        } => cli::run_backends(config_path, router_url, command).await,
+        Command::Bench(bench_args) => cli::run_bench(config_path, bench_args).await,
+    }
+}
+
+/// Load the config, then run the router on a runtime sized by its `[runtime]`
+/// section.
+fn serve(config_path: String) {
     // Load configuration from TOML file
-    let config = load_config(&args.config).expect("Failed to load router configuration");
+    let config = load_config(&config_path).expect("Failed to load router configuration");
+    let runtime = runtime::build(&config.runtime)
+        .unwrap_or_else(|e| panic!("Failed to start the Tokio runtime: {}", e));
+    runtime.block_on(run(config_path, config));
+}
 
-    info!("Loaded configuration from: {}", args.config);
+async fn run(config_path: String, config: Config) {
+    log_format::set_log_format(config.logging.log_format);
+    // Periodic loops; on their own thread with runtime.isolate_background
+    let background = runtime::background(&config.runtime)
+        .unwrap_or_else(|e| panic!("Failed to start the background runtime: {}", e));
+
+    // Prometheus is scraped on /metrics; the other exporters push
+    let handle = match push_exporter(&config.metrics).await {
+        Ok(None) => Some(install_metrics_recorder().unwrap_or_else(|e| panic!("{}", e))),
+        Ok(Some(exporter)) => {
+            let registry = install_push_recorder().unwrap_or_else(|e| panic!("{}", e));
+            info!(
+                "Pushing metrics to {} every {}s",
+                exporter.name(),
+                config.metrics.interval_secs
+            );
+            background.spawn(export_loop(
+                registry,
+                exporter,
+                Duration::from_secs(config.metrics.interval_secs),
+            ));
+            None
+        }
+        Err(e) => panic!("Failed to start metrics exporter: {}", e),
+    };
+
+    info!(
+        "Loaded configuration from: {} (hash={})",
+        config_path, config.hash
+    );
+    redact::set_custom_patterns(&config.logging.redact_patterns)
+        .expect("Redaction patterns were validated at load");
     info!("Redis URL configured (host redacted)");
 
     info!("Loaded {} backends", config.backends.len());
     for backend in &config.backends {
         info!(
             "  - [{}] {} (weight: {})",
-            backend.label, backend.url, backend.weight
+            backend.label,
+            redact_url(&backend.url),
+            backend.weight
         );
     }
 
@@ -68,196 +179,296 @@ async fn main() {
         }
     }
 
-    // Initialize runtime backends with atomic health status
-    let runtime_backends: Vec<RuntimeBackend> = config
-        .backends
-        .iter()
-        .map(|b| RuntimeBackend {
-            config: b.clone(),
-            healthy: Arc::new(AtomicBool::new(true)), // Default to healthy
-        })
-        .collect();
-
     // Initialize health state
     let backend_labels: Vec<String> = config.backends.iter().map(|b| b.label.clone()).collect();
     let health_state = Arc::new(HealthState::new(backend_labels));
 
-    let initial_router_state = RouterState {
-        backends: runtime_backends,
-        method_routes: config.method_routes,
-        health_state: health_state.clone(),
-        proxy_timeout_secs: config.proxy.timeout_secs,
-        health_check_config: config.health_check.clone(),
-    };
-
+    let initial_router_state = router_state_from_config(&config, health_state.clone());
     let router_state = Arc::new(ArcSwap::from_pointee(initial_router_state));
 
-    let https = HttpsConnector::new();
-    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(https);
+    let connect_timeout = (config.proxy.connect_timeout_ms > 0)
+        .then(|| Duration::from_millis(config.proxy.connect_timeout_ms));
+    let client = upstream_client(connect_timeout);
 
     // Initialize Redis KeyStore
-    let keystore = match RedisKeyStore::new(&config.redis_url).await {
+    let keystore = match RedisKeyStore::new(&config.redis_url, &config.key_cache).await {
         Ok(ks) => ks,
         Err(e) => {
             error!("Failed to initialize Redis KeyStore: {}", e);
             std::process::exit(1);
         }
     };
+    // Keys changed by the admin API or rpc-admin leave every router's cache at once
+    background.spawn(keystore.invalidation_loop());
+    let keystore = OutageKeyStore::new(
+        Arc::new(keystore),
+        &config.keystore_outage,
+        config.key_cache.max_entries,
+    );
+
+    let dead_letters = if config.dead_letter.enabled {
+        match open_store(&config.dead_letter, &config.redis_url).await {
+            Ok(store) => Some(Arc::from(store)),
+            Err(e) => {
+                error!("Failed to initialize dead-letter store: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let journal = if config.journal.enabled {
+        match open_journal(&config.journal, &config.redis_url).await {
+            Ok(store) => Some(Arc::from(store)),
+            Err(e) => {
+                error!("Failed to initialize transaction journal: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let usage_ledger: Option<Arc<dyn UsageLedger>> = if config.usage_ledger.enabled {
+        match open_ledger(&config.usage_ledger, &config.redis_url).await {
+            Ok(ledger) => Some(Arc::from(ledger)),
+            Err(e) => {
+                error!("Failed to initialize usage ledger: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let key_usage: Option<Arc<dyn KeyUsageStore>> = if config.key_usage.enabled {
+        match RedisKeyUsageStore::new(&config.redis_url, config.key_usage.retention_days).await {
+            Ok(store) => Some(Arc::new(store)),
+            Err(e) => {
+                error!("Failed to initialize key usage accounting: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let spending = if config.spending_caps.enabled {
+        match RedisSpendStore::new(&config.redis_url).await {
+            Ok(store) => Some(Arc::new(SpendTracker::new(
+                config.spending_caps.clone(),
+                Arc::new(store),
+            ))),
+            Err(e) => {
+                error!("Failed to initialize spending caps: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
 
+    let tracer = config
+        .tracing
+        .enabled
+        .then(|| Arc::new(Tracer::new(&config.tracing)));
+
+    let reload_status = ReloadStatus::new(&config_path);
+    reload_status.set_active(Arc::new(config.clone()));
     let state = Arc::new(AppState {
-        client: client.clone(),
-        keystore: Arc::new(keystore),
-        state: router_state.clone(),
+        reload_status: Arc::new(reload_status),
+        dead_letters,
+        journal,
+        usage_ledger: usage_ledger.clone(),
+        key_usage: key_usage.clone(),
+        spending: spending.clone(),
+        tracer: tracer.clone(),
+        write_client: upstream_client(connect_timeout),
+        backend_clients: Arc::new(BackendClients::new(connect_timeout)),
+        ..AppState::new(client.clone(), Arc::new(keystore), router_state.clone())
     });
 
-    // Spawn background health check task
-    let health_check_client = client.clone();
-    let health_check_state = router_state.clone();
+    // Background health checks; the loop reads config from state each iteration
+    info!("Starting health check loop");
+    let _health_checks = {
+        let _background = background.enter();
+        start_health_checks(&state)
+    };
 
-    tokio::spawn(async move {
-        info!("Starting health check loop");
-        // Loop will read config from state each iteration
-        health_check_loop(
-            health_check_client,
-            health_check_state,
-        )
-        .await;
-    });
+    // Learned routing shares; idle unless routing.mode = "auto"
+    background.spawn(auto_routing_loop(state.clone()));
+
+    // Local alert rules; idle without [[alerts]]
+    background.spawn(alerting_loop(state.clone()));
+
+    // Canary queries compared across backends; idle unless enabled
+    background.spawn(consistency_loop(state.clone()));
+
+    // Backends on another cluster are quarantined before any traffic is served
+    verify_genesis(&state).await;
+    background.spawn(genesis_check_loop(state.clone()));
+
+    // Shared slot source for local slot subscriptions; idle unless enabled
+    tokio::spawn(slot_feed_loop(state.clone()));
+
+    // Closes bridged subscriptions nobody polls any more
+    tokio::spawn(poll_bridge_loop(state.clone()));
+
+    // Latest blockhashes for the sendTransaction freshness check; idle unless enabled
+    background.spawn(blockhash_loop(state.clone()));
+
+    // Transactions left in flight by the last run
+    if config.journal.resume_secs > 0 {
+        tokio::spawn(resume_journal(state.clone(), config.journal.resume_secs));
+    }
+
+    // Per-key error rate gauges for the usage windows
+    background.spawn(usage_metrics_loop(state.usage.clone()));
+
+    // Daily usage totals for /admin/usage
+    if let Some(ledger) = usage_ledger {
+        let ledger_config = config.usage_ledger.clone();
+        let buffer = state.usage_buffer.clone();
+        background.spawn(usage_ledger_loop(buffer, ledger, ledger_config));
+    }
+
+    // Per-key, per-method daily counts for /admin/keys/<key>/usage
+    if let Some(store) = key_usage {
+        let buffer = state.key_usage_buffer.clone();
+        background.spawn(key_usage_loop(buffer, store, config.key_usage.clone()));
+    }
+
+    // Spend of capped keys, and notice of those reaching their cap
+    if let Some(tracker) = spending {
+        background.spawn(spending_loop(tracker, state.client.clone()));
+    }
 
-    // Spawn SIGHUP handler for hot reload
-    let reload_state = router_state.clone();
-    let config_path = args.config.clone();
+    // Spans of proxied requests, pushed to the OTLP collector
+    #[cfg(feature = "otlp")]
+    if let Some(tracer) = tracer {
+        info!(
+            "Exporting traces to {}",
+            redact_url(&config.tracing.otlp.endpoint)
+        );
+        background.spawn(sol_rpc_router::trace::otlp::export_loop(
+            tracer,
+            config.tracing.clone(),
+        ));
+    }
+
+    // Spawn SIGHUP handler for hot reload. The new config is fully validated (and
+    // optionally probed) before it replaces the running one.
+    let reload_app_state = state.clone();
+    let config_path = config_path.clone();
     // We keep the original health_state to preserve history across reloads if backends match
-    let persistent_health_state = health_state.clone(); 
+    let persistent_health_state = health_state.clone();
 
     tokio::spawn(async move {
         let mut sighup = signal(SignalKind::hangup()).expect("Failed to register SIGHUP handler");
-        
+
         loop {
             sighup.recv().await;
-            info!("Received SIGHUP, reloading configuration from {}", config_path);
-
-            match load_config(&config_path) {
-                Ok(new_config) => {
-                    info!("Configuration reloaded successfully");
-                    info!("New backend count: {}", new_config.backends.len());
-                    
-                    // Re-initialize runtime backends
-                    // We attempt to preserve health status if backend label matches
-                    let new_runtime_backends: Vec<RuntimeBackend> = new_config
-                        .backends
-                        .iter()
-                        .map(|b| {
-                            // Check if we have existing status for this label
-                            let is_healthy = if let Some(status) = persistent_health_state.get_status(&b.label) {
-                                status.healthy
-                            } else {
-                                true // Default new backends to healthy
-                            };
-
-                            RuntimeBackend {
-                                config: b.clone(),
-                                healthy: Arc::new(AtomicBool::new(is_healthy)),
-                            }
-                        })
-                        .collect();
-                    
-                    // Update method routes info
-                    if !new_config.method_routes.is_empty() {
-                         info!("Updated method routing overrides:");
-                         for (method, label) in &new_config.method_routes {
-                             info!("  - {} -> {}", method, label);
-                         }
-                    }
-
-                    // Create new router state
-                    let new_router_state = RouterState {
-                        backends: new_runtime_backends,
-                        method_routes: new_config.method_routes,
-                        health_state: persistent_health_state.clone(), // Reuse the persistent health state container
-                        proxy_timeout_secs: new_config.proxy.timeout_secs,
-                        health_check_config: new_config.health_check,
-                    };
-
-                    // Atomically swap the state
-                    reload_state.store(Arc::new(new_router_state));
-                    info!("Router state atomically swapped");
-                }
-                Err(e) => {
-                    error!("Failed to reload configuration: {}", e);
-                }
-            }
+            info!(
+                "Received SIGHUP, reloading configuration from {}",
+                config_path
+            );
+
+            let _ = reload_config(
+                &config_path,
+                &reload_app_state.client,
+                &reload_app_state.state,
+                &persistent_health_state,
+                &reload_app_state.reload_status,
+            )
+            .await;
         }
     });
 
     // HTTP server (JSON-RPC over HTTP + WebSocket on same port)
-    let http_app = Router::new()
-        .route("/", get(ws_proxy).post(proxy))
-        .route("/*path", post(proxy))
-        .route("/health", get(health_endpoint))
-        .with_state(state.clone())
-        .layer(middleware::from_fn(track_metrics))
-        .layer(middleware::from_fn(log_requests))
-        .layer(middleware::from_fn(extract_rpc_method))
-        .layer(CorsLayer::permissive());
-
+    let http_app = http_router(state.clone());
     // WebSocket server (following Solana convention: WS port = HTTP port + 1)
-    let ws_app = Router::new()
-        .route("/", get(ws_proxy))
-        .with_state(state)
-        .layer(middleware::from_fn(log_requests))
-        .layer(CorsLayer::permissive());
-
-    // Metrics server (dedicated port)
-    let metrics_app = Router::new()
-        .route("/metrics", get(move || std::future::ready(handle.render())));
+    let ws_app = ws_router(state.clone());
+    // Operations server (metrics and admin API), kept off the public listeners
+    let metrics_app = operations_router(state, handle);
 
-    let http_addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     let ws_port = config
         .port
         .checked_add(1)
         .expect("WebSocket port overflow: HTTP port cannot be 65535");
-    let ws_addr = SocketAddr::from(([0, 0, 0, 0], ws_port));
-    let metrics_addr = SocketAddr::from(([0, 0, 0, 0], config.metrics_port));
-
-    info!("HTTP server listening on http://{}", http_addr);
-    info!("WebSocket server listening on ws://{}", ws_addr);
-    info!("Metrics server listening on http://{}", metrics_addr);
-    info!("Health monitoring endpoint: http://{}/health", http_addr);
-
-    // Start all servers concurrently
-    let http_server = async {
-        axum::serve(
-            tokio::net::TcpListener::bind(http_addr)
-                .await
-                .expect("Failed to bind HTTP server"),
-            http_app.into_make_service_with_connect_info::<SocketAddr>(),
-        )
-        .await
-        .expect("HTTP server error");
+    let bind = |addresses: &[IpAddr], port: u16, name: &str| {
+        bind_all(addresses, port)
+            .unwrap_or_else(|e| panic!("Failed to bind {} server: {}", name, e))
     };
-
-    let ws_server = async {
-        axum::serve(
-            tokio::net::TcpListener::bind(ws_addr)
-                .await
-                .expect("Failed to bind WebSocket server"),
-            ws_app.into_make_service_with_connect_info::<SocketAddr>(),
-        )
-        .await
-        .expect("WebSocket server error");
+    let http_listeners = bind(&config.bind_addresses, config.port, "HTTP");
+    let ws_listeners = bind(&config.bind_addresses, ws_port, "WebSocket");
+    let metrics_listeners = bind(&config.admin_bind_addresses, config.metrics_port, "Metrics");
+
+    // TLS on the public listeners; the certificate files are re-read for renewals
+    let tls = if config.tls.enabled {
+        let resolver = match CertResolver::load(&config.tls) {
+            Ok(resolver) => Arc::new(resolver),
+            Err(e) => {
+                error!("Failed to load TLS certificate: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let tls_acceptor = acceptor(resolver.clone()).unwrap_or_else(|e| {
+            error!("Failed to configure TLS: {}", e);
+            std::process::exit(1);
+        });
+        tokio::spawn(cert_reload_loop(resolver, config.tls.reload_secs));
+        Some(tls_acceptor)
+    } else {
+        None
     };
+    let (http_scheme, ws_scheme) = if tls.is_some() {
+        ("https", "wss")
+    } else {
+        ("http", "ws")
+    };
+
+    for addr in http_listeners.iter().filter_map(|l| l.local_addr().ok()) {
+        info!("HTTP server listening on {}://{}", http_scheme, addr);
+        info!(
+            "Health monitoring endpoint: {}://{}/health",
+            http_scheme, addr
+        );
+        info!(
+            "Discovery document: {}://{}/v1/rpc-discovery",
+            http_scheme, addr
+        );
+    }
+    for addr in ws_listeners.iter().filter_map(|l| l.local_addr().ok()) {
+        info!("WebSocket server listening on {}://{}", ws_scheme, addr);
+    }
+    for addr in metrics_listeners.iter().filter_map(|l| l.local_addr().ok()) {
+        info!("Metrics and admin server listening on http://{}", addr);
+    }
 
-    let metrics_server = async {
-        axum::serve(
-            tokio::net::TcpListener::bind(metrics_addr)
-                .await
-                .expect("Failed to bind Metrics server"),
-            metrics_app.into_make_service_with_connect_info::<SocketAddr>(),
-        )
-        .await
-        .expect("Metrics server error");
+    // Start all servers concurrently, one per bind address. They only stop on
+    // error, which takes the whole process down.
+    let (http_server, ws_server) = match tls {
+        Some(tls) => (
+            start_tls_listeners("HTTP", http_listeners, http_app, tls.clone()),
+            start_tls_listeners("WebSocket", ws_listeners, ws_app, tls),
+        ),
+        None => (
+            start_listeners("HTTP", http_listeners, http_app),
+            start_listeners("WebSocket", ws_listeners, ws_app),
+        ),
     };
+    let metrics_server = start_listeners("Metrics", metrics_listeners, metrics_app);
 
-    tokio::join!(http_server, ws_server, metrics_server);
+    let (name, result) = tokio::select! {
+        r = http_server.wait() => ("HTTP", r),
+        r = ws_server.wait() => ("WebSocket", r),
+        r = metrics_server.wait() => ("Metrics", r),
+    };
+    error!(
+        "{} server stopped: {}",
+        name,
+        result.err().unwrap_or_else(|| "no error".to_string())
+    );
+    std::process::exit(1);
 }
@@ -0,0 +1,75 @@
+use serde_json::{json, Value};
+
+use crate::keystore::KeyInfo;
+
+/// JSON-RPC error code of a request for a method its key may not call: the
+/// code nodes answer methods they do not serve with.
+pub const METHOD_NOT_ALLOWED_CODE: i64 = -32601;
+
+/// A request refused because of its key's `allowed_methods`, `scopes` or
+/// `blocked_methods`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Denied {
+    /// The first method the key may not call
+    pub method: String,
+    /// JSON-RPC response to send instead of forwarding the request
+    pub body: String,
+}
+
+fn error(id: &Value, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {"code": METHOD_NOT_ALLOWED_CODE, "message": message},
+        "id": id,
+    })
+}
+
+fn method_of(request: &Value) -> Option<&str> {
+    request.get("method").and_then(Value::as_str)
+}
+
+fn id_of(request: &Value) -> Value {
+    request.get("id").cloned().unwrap_or(Value::Null)
+}
+
+fn not_allowed(method: &str) -> String {
+    format!("Method '{}' is not allowed for this API key", method)
+}
+
+/// Check every request of a single or batch JSON-RPC `body` against `key`.
+/// A batch calling any method the key may not call is refused whole, with an
+/// error for each of its requests. Bodies that are not JSON-RPC are left for
+/// the backend to reject.
+pub fn check(body: &[u8], key: &KeyInfo) -> Option<Denied> {
+    let request: Value = serde_json::from_slice(body).ok()?;
+    match &request {
+        Value::Array(requests) => {
+            let method = requests
+                .iter()
+                .filter_map(method_of)
+                .find(|m| !key.allows_method(m))?
+                .to_string();
+            let errors: Vec<Value> = requests
+                .iter()
+                .map(|request| {
+                    let message = match method_of(request) {
+                        Some(m) if !key.allows_method(m) => not_allowed(m),
+                        _ => format!("Batch refused: {}", not_allowed(&method)),
+                    };
+                    error(&id_of(request), message)
+                })
+                .collect();
+            Some(Denied {
+                method,
+                body: Value::Array(errors).to_string(),
+            })
+        }
+        _ => {
+            let method = method_of(&request).filter(|m| !key.allows_method(m))?;
+            Some(Denied {
+                method: method.to_string(),
+                body: error(&id_of(&request), not_allowed(method)).to_string(),
+            })
+        }
+    }
+}
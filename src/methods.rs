@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// How the router treats a method for routing and capability reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MethodClass {
+    /// Ordinary reads served from current state
+    Standard,
+    /// Responses that change rarely (genesis hash, epoch schedule, version...)
+    Cached,
+    /// Historical data that needs a backend with full ledger history
+    Archival,
+    /// Submits to the cluster rather than reading from it
+    Write,
+    /// WebSocket subscription management
+    Subscription,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MethodInfo {
+    pub name: &'static str,
+    pub class: MethodClass,
+    /// Relative cost in request units (1 = a cheap point lookup)
+    pub cost: u32,
+}
+
+const fn method(name: &'static str, class: MethodClass, cost: u32) -> MethodInfo {
+    MethodInfo { name, class, cost }
+}
+
+/// Solana JSON-RPC methods known to the router (HTTP and WebSocket subscription APIs).
+pub const KNOWN_METHODS: &[MethodInfo] = &[
+    method("getAccountInfo", MethodClass::Standard, 1),
+    method("getBalance", MethodClass::Standard, 1),
+    method("getBlock", MethodClass::Archival, 5),
+    method("getBlockCommitment", MethodClass::Archival, 1),
+    method("getBlockHeight", MethodClass::Standard, 1),
+    method("getBlockProduction", MethodClass::Archival, 1),
+    method("getBlockTime", MethodClass::Archival, 1),
+    method("getBlocks", MethodClass::Archival, 2),
+    method("getBlocksWithLimit", MethodClass::Archival, 2),
+    method("getClusterNodes", MethodClass::Cached, 2),
+    method("getEpochInfo", MethodClass::Standard, 1),
+    method("getEpochSchedule", MethodClass::Cached, 1),
+    method("getFeeForMessage", MethodClass::Standard, 1),
+    method("getFirstAvailableBlock", MethodClass::Cached, 1),
+    method("getGenesisHash", MethodClass::Cached, 1),
+    method("getHealth", MethodClass::Standard, 1),
+    method("getHighestSnapshotSlot", MethodClass::Standard, 1),
+    method("getIdentity", MethodClass::Cached, 1),
+    method("getInflationGovernor", MethodClass::Cached, 1),
+    method("getInflationRate", MethodClass::Cached, 1),
+    method("getInflationReward", MethodClass::Archival, 3),
+    method("getLargestAccounts", MethodClass::Standard, 10),
+    method("getLatestBlockhash", MethodClass::Standard, 1),
+    method("getLeaderSchedule", MethodClass::Cached, 2),
+    method("getMaxRetransmitSlot", MethodClass::Standard, 1),
+    method("getMaxShredInsertSlot", MethodClass::Standard, 1),
+    method("getMinimumBalanceForRentExemption", MethodClass::Cached, 1),
+    method("getMultipleAccounts", MethodClass::Standard, 2),
+    method("getProgramAccounts", MethodClass::Standard, 10),
+    method("getRecentPerformanceSamples", MethodClass::Standard, 1),
+    method("getRecentPrioritizationFees", MethodClass::Standard, 1),
+    method("getSignatureStatuses", MethodClass::Standard, 1),
+    method("getSignaturesForAddress", MethodClass::Archival, 3),
+    method("getSlot", MethodClass::Standard, 1),
+    method("getSlotLeader", MethodClass::Standard, 1),
+    method("getSlotLeaders", MethodClass::Standard, 1),
+    method("getStakeMinimumDelegation", MethodClass::Cached, 1),
+    method("getSupply", MethodClass::Standard, 2),
+    method("getTokenAccountBalance", MethodClass::Standard, 1),
+    method("getTokenAccountsByDelegate", MethodClass::Standard, 5),
+    method("getTokenAccountsByOwner", MethodClass::Standard, 5),
+    method("getTokenLargestAccounts", MethodClass::Standard, 5),
+    method("getTokenSupply", MethodClass::Standard, 1),
+    method("getTransaction", MethodClass::Archival, 2),
+    method("getTransactionCount", MethodClass::Standard, 1),
+    method("getVersion", MethodClass::Cached, 1),
+    method("getVoteAccounts", MethodClass::Standard, 2),
+    method("isBlockhashValid", MethodClass::Standard, 1),
+    method("minimumLedgerSlot", MethodClass::Standard, 1),
+    method("requestAirdrop", MethodClass::Write, 1),
+    method("sendTransaction", MethodClass::Write, 1),
+    method("simulateTransaction", MethodClass::Standard, 2),
+    method("accountSubscribe", MethodClass::Subscription, 1),
+    method("accountUnsubscribe", MethodClass::Subscription, 1),
+    method("blockSubscribe", MethodClass::Subscription, 5),
+    method("blockUnsubscribe", MethodClass::Subscription, 1),
+    method("logsSubscribe", MethodClass::Subscription, 1),
+    method("logsUnsubscribe", MethodClass::Subscription, 1),
+    method("programSubscribe", MethodClass::Subscription, 5),
+    method("programUnsubscribe", MethodClass::Subscription, 1),
+    method("rootSubscribe", MethodClass::Subscription, 1),
+    method("rootUnsubscribe", MethodClass::Subscription, 1),
+    method("signatureSubscribe", MethodClass::Subscription, 1),
+    method("signatureUnsubscribe", MethodClass::Subscription, 1),
+    method("slotSubscribe", MethodClass::Subscription, 1),
+    method("slotUnsubscribe", MethodClass::Subscription, 1),
+    method("slotsUpdatesSubscribe", MethodClass::Subscription, 1),
+    method("slotsUpdatesUnsubscribe", MethodClass::Subscription, 1),
+    method("voteSubscribe", MethodClass::Subscription, 1),
+    method("voteUnsubscribe", MethodClass::Subscription, 1),
+];
+
+pub fn method_info(method: &str) -> Option<&'static MethodInfo> {
+    KNOWN_METHODS.iter().find(|m| m.name == method)
+}
+
+pub fn is_known_method(method: &str) -> bool {
+    method_info(method).is_some()
+}
+
+/// Whether a request may be sent more than once, or answered with an earlier
+/// response. Retries, hedging and caching all go by it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Idempotency {
+    /// Reads: may be retried, hedged and cached
+    Safe,
+    /// Same effect however often it is sent, like a signed transaction that
+    /// the cluster deduplicates by signature: may be retried, but not hedged
+    /// or cached
+    Idempotent,
+    /// Each call has an effect of its own, like an airdrop: sent once
+    NonIdempotent,
+}
+
+/// Built-in idempotency of a known method.
+pub fn builtin_idempotency(method: &str) -> Option<Idempotency> {
+    let class = method_info(method)?.class;
+    Some(match (method, class) {
+        ("requestAirdrop", _) | (_, MethodClass::Subscription) => Idempotency::NonIdempotent,
+        (_, MethodClass::Write) => Idempotency::Idempotent,
+        _ => Idempotency::Safe,
+    })
+}
+
+/// Idempotency of `method`: built in for known methods, else from the
+/// operator's `method_idempotency` table. `None` for methods in neither.
+pub fn idempotency(method: &str, custom: &HashMap<String, Idempotency>) -> Option<Idempotency> {
+    builtin_idempotency(method).or_else(|| custom.get(method).copied())
+}
+
+/// Digital Asset Standard methods served by providers with a DAS indexer.
+pub const DAS_METHODS: &[&str] = &[
+    "getAsset",
+    "getAssetBatch",
+    "getAssetProof",
+    "getAssetProofBatch",
+    "getAssetsByAuthority",
+    "getAssetsByCreator",
+    "getAssetsByGroup",
+    "getAssetsByOwner",
+    "getNftEditions",
+    "getSignaturesForAsset",
+    "getTokenAccounts",
+    "searchAssets",
+];
+
+/// Group of methods an API key can be scoped to instead of listing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MethodCategory {
+    /// Known methods that read cluster state
+    Read,
+    /// `sendTransaction` and `requestAirdrop`
+    Write,
+    /// WebSocket subscriptions and unsubscriptions
+    Subscribe,
+    /// DAS methods (see [`DAS_METHODS`])
+    Das,
+    /// Every other method the router does not know: provider and node
+    /// extensions
+    AdminExtensions,
+}
+
+pub const METHOD_CATEGORIES: &[MethodCategory] = &[
+    MethodCategory::Read,
+    MethodCategory::Write,
+    MethodCategory::Subscribe,
+    MethodCategory::Das,
+    MethodCategory::AdminExtensions,
+];
+
+impl MethodCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Subscribe => "subscribe",
+            Self::Das => "das",
+            Self::AdminExtensions => "admin-extensions",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        METHOD_CATEGORIES
+            .iter()
+            .copied()
+            .find(|c| c.as_str() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = METHOD_CATEGORIES.iter().map(|c| c.as_str()).collect();
+                format!(
+                    "Invalid scope '{}': expected one of {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// Category of any method name, known to the router or not.
+    pub fn of(method: &str) -> Self {
+        match method_info(method).map(|m| m.class) {
+            Some(MethodClass::Write) => Self::Write,
+            Some(MethodClass::Subscription) => Self::Subscribe,
+            Some(_) => Self::Read,
+            None if DAS_METHODS.contains(&method) => Self::Das,
+            None => Self::AdminExtensions,
+        }
+    }
+}
+
+pub const COMMITMENTS: &[&str] = &["processed", "confirmed", "finalized"];
+const CONFIRMED_OR_FINALIZED: &[&str] = &["confirmed", "finalized"];
+const ACCOUNT_ENCODINGS: &[&str] = &["base58", "base64", "base64+zstd", "jsonParsed"];
+const TRANSACTION_ENCODINGS: &[&str] = &["json", "jsonParsed", "base58", "base64"];
+
+/// Every encoding accepted by at least one method.
+pub const ENCODINGS: &[&str] = &["base58", "base64", "base64+zstd", "json", "jsonParsed"];
+
+/// Position of a method's trailing config object and the `commitment` and
+/// response `encoding` values it accepts there.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigParam {
+    pub index: usize,
+    pub commitments: &'static [&'static str],
+    pub encodings: &'static [&'static str],
+}
+
+const fn config(
+    index: usize,
+    commitments: &'static [&'static str],
+    encodings: &'static [&'static str],
+) -> Option<ConfigParam> {
+    Some(ConfigParam {
+        index,
+        commitments,
+        encodings,
+    })
+}
+
+/// Config object layout for methods whose config sits at a fixed position.
+/// Methods with an optional positional parameter before the config (e.g.
+/// `getBlocks`, `getLeaderSchedule`) and methods whose `encoding` describes the
+/// input transaction (`sendTransaction`, `simulateTransaction`) are left out or
+/// listed without encodings.
+pub fn config_param(method: &str) -> Option<ConfigParam> {
+    match method {
+        "getAccountInfo" | "getMultipleAccounts" | "getProgramAccounts" => {
+            config(1, COMMITMENTS, ACCOUNT_ENCODINGS)
+        }
+        "getTokenAccountsByDelegate" | "getTokenAccountsByOwner" => {
+            config(2, COMMITMENTS, ACCOUNT_ENCODINGS)
+        }
+        "getBlock" | "getTransaction" => config(1, CONFIRMED_OR_FINALIZED, TRANSACTION_ENCODINGS),
+        "getSignaturesForAddress" => config(1, CONFIRMED_OR_FINALIZED, &[]),
+        "getBlocksWithLimit" => config(2, CONFIRMED_OR_FINALIZED, &[]),
+        "getBalance"
+        | "getFeeForMessage"
+        | "getInflationReward"
+        | "getMinimumBalanceForRentExemption"
+        | "getTokenAccountBalance"
+        | "getTokenLargestAccounts"
+        | "getTokenSupply"
+        | "isBlockhashValid"
+        | "simulateTransaction" => config(1, COMMITMENTS, &[]),
+        "requestAirdrop" => config(2, COMMITMENTS, &[]),
+        "getBlockHeight"
+        | "getBlockProduction"
+        | "getEpochInfo"
+        | "getInflationGovernor"
+        | "getLargestAccounts"
+        | "getLatestBlockhash"
+        | "getSlot"
+        | "getSlotLeader"
+        | "getStakeMinimumDelegation"
+        | "getSupply"
+        | "getTransactionCount"
+        | "getVoteAccounts" => config(0, COMMITMENTS, &[]),
+        _ => None,
+    }
+}
@@ -0,0 +1,527 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use metrics::{
+    Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+};
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tracing::warn;
+
+use crate::{
+    config::{MetricsConfig, MetricsExporterKind},
+    redact::redact,
+};
+
+/// Histogram bucket bounds (seconds) of every exporter. Explicit buckets make
+/// Prometheus emit true histograms (`_bucket`/`_sum`/`_count`) instead of
+/// summaries, which `histogram_quantile()` in Grafana requires.
+pub const HISTOGRAM_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Raw histogram values kept per series between pushes, for exporters that
+/// send samples rather than buckets. Later values are dropped.
+pub const MAX_PENDING_SAMPLES: usize = 10_000;
+
+/// A push-based metrics backend, fed a snapshot of every series each
+/// `metrics.interval_secs`.
+#[async_trait]
+pub trait MetricsExporter: Send {
+    fn name(&self) -> &'static str;
+
+    async fn export(&mut self, samples: &[Sample]) -> Result<(), String>;
+}
+
+/// One series in a snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub value: SampleValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SampleValue {
+    /// Total since startup
+    Counter(u64),
+    Gauge(f64),
+    Histogram(HistogramSnapshot),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    /// Totals since startup
+    pub count: u64,
+    pub sum: f64,
+    /// Values per bucket of [`HISTOGRAM_BUCKETS`] (not cumulative), the last
+    /// one above the highest bound
+    pub bucket_counts: Vec<u64>,
+    /// Values recorded since the previous snapshot
+    pub pending: Vec<f64>,
+}
+
+#[derive(Default)]
+struct HistogramState {
+    count: u64,
+    sum: f64,
+    bucket_counts: Vec<u64>,
+    pending: Vec<f64>,
+}
+
+#[derive(Default)]
+struct HistogramCell(Mutex<HistogramState>);
+
+impl HistogramFn for HistogramCell {
+    fn record(&self, value: f64) {
+        let mut state = self.0.lock().unwrap();
+        if state.bucket_counts.is_empty() {
+            state.bucket_counts = vec![0; HISTOGRAM_BUCKETS.len() + 1];
+        }
+        let bucket = HISTOGRAM_BUCKETS.partition_point(|bound| *bound < value);
+        state.bucket_counts[bucket] += 1;
+        state.count += 1;
+        state.sum += value;
+        if state.pending.len() < MAX_PENDING_SAMPLES {
+            state.pending.push(value);
+        }
+    }
+}
+
+/// Every series recorded through [`PushRecorder`], read by the exporters.
+#[derive(Default)]
+pub struct MetricsRegistry {
+    counters: Mutex<HashMap<Key, Arc<AtomicU64>>>,
+    gauges: Mutex<HashMap<Key, Arc<AtomicU64>>>,
+    histograms: Mutex<HashMap<Key, Arc<HistogramCell>>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All series sorted by name and labels. Histogram values recorded since
+    /// the last snapshot are handed over in `pending` and cleared.
+    pub fn snapshot(&self) -> Vec<Sample> {
+        let sample = |key: &Key, value| Sample {
+            name: key.name().to_string(),
+            labels: key
+                .labels()
+                .map(|l| (l.key().to_string(), l.value().to_string()))
+                .collect(),
+            value,
+        };
+        let mut samples: Vec<Sample> = Vec::new();
+        for (key, counter) in self.counters.lock().unwrap().iter() {
+            samples.push(sample(
+                key,
+                SampleValue::Counter(counter.load(Ordering::Relaxed)),
+            ));
+        }
+        for (key, gauge) in self.gauges.lock().unwrap().iter() {
+            let value = f64::from_bits(gauge.load(Ordering::Relaxed));
+            samples.push(sample(key, SampleValue::Gauge(value)));
+        }
+        for (key, histogram) in self.histograms.lock().unwrap().iter() {
+            let mut state = histogram.0.lock().unwrap();
+            if state.count == 0 {
+                continue;
+            }
+            let snapshot = HistogramSnapshot {
+                count: state.count,
+                sum: state.sum,
+                bucket_counts: state.bucket_counts.clone(),
+                pending: std::mem::take(&mut state.pending),
+            };
+            samples.push(sample(key, SampleValue::Histogram(snapshot)));
+        }
+        samples.sort_by(|a, b| (&a.name, &a.labels).cmp(&(&b.name, &b.labels)));
+        samples
+    }
+}
+
+/// `metrics` recorder that aggregates into a [`MetricsRegistry`] for the push
+/// exporters.
+pub struct PushRecorder(pub Arc<MetricsRegistry>);
+
+impl Recorder for PushRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        let mut counters = self.0.counters.lock().unwrap();
+        Counter::from_arc(counters.entry(key.clone()).or_default().clone())
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        let mut gauges = self.0.gauges.lock().unwrap();
+        Gauge::from_arc(gauges.entry(key.clone()).or_default().clone())
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        let mut histograms = self.0.histograms.lock().unwrap();
+        Histogram::from_arc(histograms.entry(key.clone()).or_default().clone())
+    }
+}
+
+/// Install a [`PushRecorder`] as the global recorder.
+pub fn install_push_recorder() -> Result<Arc<MetricsRegistry>, String> {
+    let registry = Arc::new(MetricsRegistry::new());
+    metrics::set_global_recorder(PushRecorder(registry.clone()))
+        .map_err(|e| format!("failed to install metrics recorder: {}", e))?;
+    Ok(registry)
+}
+
+/// The push exporter configured in `metrics.exporter`, or `None` for
+/// Prometheus, which is scraped instead.
+pub async fn push_exporter(
+    config: &MetricsConfig,
+) -> Result<Option<Box<dyn MetricsExporter>>, String> {
+    match config.exporter {
+        MetricsExporterKind::Prometheus => Ok(None),
+        #[cfg(feature = "statsd")]
+        MetricsExporterKind::Statsd => Ok(Some(Box::new(
+            statsd::StatsdExporter::connect(&config.statsd).await?,
+        ))),
+        #[cfg(feature = "otlp")]
+        MetricsExporterKind::Otlp => Ok(Some(Box::new(otlp::OtlpExporter::new(&config.otlp)))),
+        #[allow(unreachable_patterns)]
+        kind => Err(format!(
+            "metrics exporter '{}' was not compiled in",
+            kind.as_str()
+        )),
+    }
+}
+
+/// Every `every`, hand a snapshot of `registry` to `exporter`.
+pub async fn export_loop(
+    registry: Arc<MetricsRegistry>,
+    mut exporter: Box<dyn MetricsExporter>,
+    every: Duration,
+) {
+    let mut ticker = interval(every);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if let Err(e) = exporter.export(&registry.snapshot()).await {
+            warn!("{} metrics export failed: {}", exporter.name(), redact(&e));
+        }
+    }
+}
+
+#[cfg(feature = "statsd")]
+pub mod statsd {
+    use super::*;
+    use crate::config::StatsdConfig;
+    use tokio::net::UdpSocket;
+
+    /// Largest datagram sent, to stay under a 1500-byte MTU.
+    pub const MAX_PACKET_BYTES: usize = 1432;
+
+    /// Sends counters as deltas (`|c`), gauges (`|g`) and each histogram value
+    /// (`|h`) to a StatsD or DogStatsD agent over UDP.
+    pub struct StatsdExporter {
+        socket: UdpSocket,
+        config: StatsdConfig,
+        /// Counter totals as of the last push
+        sent: HashMap<(String, Vec<(String, String)>), u64>,
+    }
+
+    impl StatsdExporter {
+        pub async fn connect(config: &StatsdConfig) -> Result<Self, String> {
+            let socket = UdpSocket::bind("0.0.0.0:0")
+                .await
+                .map_err(|e| format!("StatsD socket: {}", e))?;
+            socket
+                .connect(&config.address)
+                .await
+                .map_err(|e| format!("StatsD agent {}: {}", config.address, e))?;
+            Ok(Self {
+                socket,
+                config: config.clone(),
+                sent: HashMap::new(),
+            })
+        }
+
+        /// StatsD lines for `samples`, skipping counters that have not moved.
+        pub fn lines(&mut self, samples: &[Sample]) -> Vec<String> {
+            let mut lines = Vec::new();
+            for sample in samples {
+                let name = self.metric_name(sample);
+                let tags = self.tags(sample);
+                match &sample.value {
+                    SampleValue::Counter(total) => {
+                        let last = self
+                            .sent
+                            .insert((sample.name.clone(), sample.labels.clone()), *total)
+                            .unwrap_or(0);
+                        let delta = total.saturating_sub(last);
+                        if delta > 0 {
+                            lines.push(format!("{}:{}|c{}", name, delta, tags));
+                        }
+                    }
+                    SampleValue::Gauge(value) => {
+                        lines.push(format!("{}:{}|g{}", name, value, tags));
+                    }
+                    SampleValue::Histogram(histogram) => {
+                        for value in &histogram.pending {
+                            lines.push(format!("{}:{}|h{}", name, value, tags));
+                        }
+                    }
+                }
+            }
+            lines
+        }
+
+        fn metric_name(&self, sample: &Sample) -> String {
+            let mut name = format!("{}{}", self.config.prefix, sample.name);
+            if !self.config.tags {
+                for (_, value) in &sample.labels {
+                    name.push('.');
+                    name.extend(value.chars().map(|c| {
+                        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    }));
+                }
+            }
+            name
+        }
+
+        fn tags(&self, sample: &Sample) -> String {
+            if !self.config.tags || sample.labels.is_empty() {
+                return String::new();
+            }
+            let tags: Vec<String> = sample
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{}:{}", key, value.replace([',', '|', '#'], "_")))
+                .collect();
+            format!("|#{}", tags.join(","))
+        }
+    }
+
+    #[async_trait]
+    impl MetricsExporter for StatsdExporter {
+        fn name(&self) -> &'static str {
+            "StatsD"
+        }
+
+        async fn export(&mut self, samples: &[Sample]) -> Result<(), String> {
+            let mut packet = String::new();
+            for line in self.lines(samples) {
+                if !packet.is_empty() && packet.len() + 1 + line.len() > MAX_PACKET_BYTES {
+                    self.socket
+                        .send(packet.as_bytes())
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    packet.clear();
+                }
+                if !packet.is_empty() {
+                    packet.push('\n');
+                }
+                packet.push_str(&line);
+            }
+            if !packet.is_empty() {
+                self.socket
+                    .send(packet.as_bytes())
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+pub mod otlp {
+    use super::*;
+    use crate::{
+        config::{BackendProtocol, OtlpConfig},
+        protocol::protocol_client,
+        trace::unix_nanos,
+    };
+    use axum::{body::Body, http::Request};
+    use hyper_tls::HttpsConnector;
+    use hyper_util::client::legacy::{connect::HttpConnector, Client};
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+    use tokio::time::timeout;
+
+    pub type OtlpClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+    /// How long a push may take.
+    pub const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Posts cumulative sums, gauges and histograms to an OpenTelemetry
+    /// collector with OTLP/HTTP (JSON).
+    pub struct OtlpExporter {
+        client: OtlpClient,
+        url: String,
+        config: OtlpConfig,
+        start_nanos: u64,
+    }
+
+    impl OtlpExporter {
+        pub fn new(config: &OtlpConfig) -> Self {
+            Self {
+                client: client(),
+                url: signal_url(config, "metrics"),
+                config: config.clone(),
+                start_nanos: unix_nanos(),
+            }
+        }
+    }
+
+    /// Client for pushes to a collector.
+    pub fn client() -> OtlpClient {
+        protocol_client(&BackendProtocol::default(), Some(PUSH_TIMEOUT))
+    }
+
+    /// Where a collector takes one signal: `{endpoint}/v1/{signal}`.
+    pub fn signal_url(config: &OtlpConfig, signal: &str) -> String {
+        format!("{}/v1/{}", config.endpoint.trim_end_matches('/'), signal)
+    }
+
+    /// POST an OTLP JSON `body` to `url` with the collector's headers.
+    pub async fn post(
+        client: &OtlpClient,
+        url: &str,
+        config: &OtlpConfig,
+        body: &Value,
+    ) -> Result<(), String> {
+        let mut request = Request::post(url).header("content-type", "application/json");
+        for (name, value) in &config.headers {
+            request = request.header(name, value);
+        }
+        let request = request
+            .body(Body::from(body.to_string()))
+            .map_err(|e| e.to_string())?;
+        let response = timeout(PUSH_TIMEOUT, client.request(request))
+            .await
+            .map_err(|_| format!("timed out after {}s", PUSH_TIMEOUT.as_secs()))?
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("collector returned status: {}", response.status()));
+        }
+        Ok(())
+    }
+
+    fn attributes(labels: &[(String, String)]) -> Value {
+        labels
+            .iter()
+            .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+            .collect()
+    }
+
+    /// `ExportMetricsServiceRequest` for `samples`. 64-bit integers are
+    /// strings, as the OTLP JSON encoding requires.
+    pub fn payload(
+        samples: &[Sample],
+        service_name: &str,
+        start_nanos: u64,
+        now_nanos: u64,
+    ) -> Value {
+        let start = start_nanos.to_string();
+        let now = now_nanos.to_string();
+        let mut metrics: BTreeMap<&str, Value> = BTreeMap::new();
+        for sample in samples {
+            let attributes = attributes(&sample.labels);
+            let (kind, point) = match &sample.value {
+                SampleValue::Counter(total) => (
+                    "sum",
+                    json!({
+                        "attributes": attributes,
+                        "startTimeUnixNano": start,
+                        "timeUnixNano": now,
+                        "asInt": total.to_string(),
+                    }),
+                ),
+                SampleValue::Gauge(value) => (
+                    "gauge",
+                    json!({
+                        "attributes": attributes,
+                        "timeUnixNano": now,
+                        "asDouble": value,
+                    }),
+                ),
+                SampleValue::Histogram(histogram) => (
+                    "histogram",
+                    json!({
+                        "attributes": attributes,
+                        "startTimeUnixNano": start,
+                        "timeUnixNano": now,
+                        "count": histogram.count.to_string(),
+                        "sum": histogram.sum,
+                        "bucketCounts": histogram
+                            .bucket_counts
+                            .iter()
+                            .map(u64::to_string)
+                            .collect::<Vec<_>>(),
+                        "explicitBounds": HISTOGRAM_BUCKETS,
+                    }),
+                ),
+            };
+            let metric = metrics.entry(&sample.name).or_insert_with(|| {
+                let data = match kind {
+                    "sum" => json!({
+                        "dataPoints": [],
+                        "aggregationTemporality": 2,
+                        "isMonotonic": true,
+                    }),
+                    "histogram" => json!({"dataPoints": [], "aggregationTemporality": 2}),
+                    _ => json!({"dataPoints": []}),
+                };
+                json!({"name": sample.name, kind: data})
+            });
+            if let Some(points) = metric[kind]["dataPoints"].as_array_mut() {
+                points.push(point);
+            }
+        }
+        json!({
+            "resourceMetrics": [{
+                "resource": {
+                    "attributes": [
+                        {"key": "service.name", "value": {"stringValue": service_name}},
+                    ],
+                },
+                "scopeMetrics": [{
+                    "scope": {"name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION")},
+                    "metrics": metrics.into_values().collect::<Vec<_>>(),
+                }],
+            }],
+        })
+    }
+
+    #[async_trait]
+    impl MetricsExporter for OtlpExporter {
+        fn name(&self) -> &'static str {
+            "OTLP"
+        }
+
+        async fn export(&mut self, samples: &[Sample]) -> Result<(), String> {
+            if samples.is_empty() {
+                return Ok(());
+            }
+            let body = payload(
+                samples,
+                &self.config.service_name,
+                self.start_nanos,
+                unix_nanos(),
+            );
+            post(&self.client, &self.url, &self.config, &body).await
+        }
+    }
+}
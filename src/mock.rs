@@ -5,7 +5,17 @@ use std::{
 
 use async_trait::async_trait;
 
-use crate::keystore::{KeyInfo, KeyStore};
+use crate::{
+    audit::LogPrivacy,
+    defaults::RequestDefaults,
+    key_usage::{KeyUsageEntry, KeyUsageStore},
+    keystore::{KeyInfo, KeyKind, KeyStore, KeyStoreError},
+    ledger::day_string,
+    methods::MethodCategory,
+    signing::unix_now,
+    spending::SpendStore,
+    ws::FirehoseThrottle,
+};
 
 #[derive(Clone)]
 pub struct MockKeyStore {
@@ -13,7 +23,7 @@ pub struct MockKeyStore {
     pub call_counts: Arc<Mutex<HashMap<String, u64>>>,
     pub inactive_keys: Arc<Mutex<Vec<String>>>,
     pub rate_limited_keys: Arc<Mutex<Vec<String>>>,
-    pub error_keys: Arc<Mutex<HashMap<String, String>>>,
+    pub error_keys: Arc<Mutex<HashMap<String, KeyStoreError>>>,
 }
 
 impl Default for MockKeyStore {
@@ -39,10 +49,101 @@ impl MockKeyStore {
             KeyInfo {
                 owner: owner.to_string(),
                 rate_limit,
+                ..Default::default()
             },
         );
     }
 
+    pub fn add_browser_key(&self, key: &str, owner: &str, rate_limit: u64, origins: &[&str]) {
+        self.keys.lock().unwrap().insert(
+            key.to_string(),
+            KeyInfo {
+                owner: owner.to_string(),
+                rate_limit,
+                kind: KeyKind::Browser,
+                allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn set_defaults(&self, key: &str, defaults: RequestDefaults) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.defaults = defaults;
+        }
+    }
+
+    pub fn set_routes(&self, key: &str, routes: &[(&str, &str)], tier: Option<&str>) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.method_routes = routes
+                .iter()
+                .map(|(method, label)| (method.to_string(), label.to_string()))
+                .collect();
+            info.tier = tier.map(str::to_string);
+        }
+    }
+
+    pub fn set_privacy(&self, key: &str, privacy: LogPrivacy) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.privacy = privacy;
+        }
+    }
+
+    pub fn set_firehose(&self, key: &str, firehose: FirehoseThrottle) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.firehose = firehose;
+        }
+    }
+
+    pub fn set_timing(&self, key: &str, timing: bool) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.timing = timing;
+        }
+    }
+
+    pub fn set_broadcast(&self, key: &str, broadcast: bool) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.broadcast = broadcast;
+        }
+    }
+
+    pub fn set_expires_at(&self, key: &str, expires_at: Option<u64>) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.expires_at = expires_at;
+        }
+    }
+
+    pub fn set_methods(&self, key: &str, allowed: &[&str], blocked: &[&str]) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.allowed_methods = allowed.iter().map(|m| m.to_string()).collect();
+            info.blocked_methods = blocked.iter().map(|m| m.to_string()).collect();
+        }
+    }
+
+    pub fn set_scopes(&self, key: &str, scopes: &[MethodCategory]) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.scopes = scopes.to_vec();
+        }
+    }
+
+    pub fn set_spend_cap(&self, key: &str, cap: Option<f64>) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.spend_cap = cap;
+        }
+    }
+
+    pub fn set_max_in_flight(&self, key: &str, max_in_flight: u32) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.max_in_flight = max_in_flight;
+        }
+    }
+
+    pub fn set_rate_limit_remaining(&self, key: &str, remaining: Option<u64>) {
+        if let Some(info) = self.keys.lock().unwrap().get_mut(key) {
+            info.rate_limit_remaining = remaining;
+        }
+    }
+
     pub fn set_inactive(&self, key: &str) {
         self.inactive_keys.lock().unwrap().push(key.to_string());
     }
@@ -51,17 +152,40 @@ impl MockKeyStore {
         *self.call_counts.lock().unwrap().get(key).unwrap_or(&0)
     }
 
+    /// Fail lookups of `key` as if the store were down.
     pub fn set_error(&self, key: &str, msg: &str) {
         self.error_keys
             .lock()
             .unwrap()
-            .insert(key.to_string(), msg.to_string());
+            .insert(key.to_string(), KeyStoreError::Unavailable(msg.to_string()));
+    }
+
+    /// Fail lookups of `key` as if its stored record were malformed.
+    pub fn set_invalid(&self, key: &str, msg: &str) {
+        self.error_keys
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), KeyStoreError::Invalid(msg.to_string()));
     }
 }
 
 #[async_trait]
 impl KeyStore for MockKeyStore {
-    async fn validate_key(&self, key: &str) -> Result<Option<KeyInfo>, String> {
+    async fn validate_key(&self, key: &str) -> Result<Option<KeyInfo>, KeyStoreError> {
+        let info = self.lookup_key(key).await?;
+        if info.is_some()
+            && self
+                .rate_limited_keys
+                .lock()
+                .unwrap()
+                .contains(&key.to_string())
+        {
+            return Err(KeyStoreError::RateLimited);
+        }
+        Ok(info)
+    }
+
+    async fn lookup_key(&self, key: &str) -> Result<Option<KeyInfo>, KeyStoreError> {
         let mut counts = self.call_counts.lock().unwrap();
         *counts.entry(key.to_string()).or_insert(0) += 1;
         drop(counts);
@@ -80,19 +204,107 @@ impl KeyStore for MockKeyStore {
             return Ok(None);
         }
 
-        if let Some(info) = self.keys.lock().unwrap().get(key) {
-            if self
-                .rate_limited_keys
-                .lock()
-                .unwrap()
-                .contains(&key.to_string())
-            {
-                return Err("Rate limit exceeded".to_string());
+        Ok(self
+            .keys
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|info| !info.is_expired(unix_now()))
+            .cloned())
+    }
+
+    async fn revoke_key(&self, key: &str) -> Result<bool, String> {
+        if !self.keys.lock().unwrap().contains_key(key) {
+            return Ok(false);
+        }
+        self.set_inactive(key);
+        Ok(true)
+    }
+
+    async fn set_audit(&self, key: &str, enabled: bool) -> Result<bool, String> {
+        match self.keys.lock().unwrap().get_mut(key) {
+            Some(info) => {
+                info.audit = enabled;
+                Ok(true)
             }
+            None => Ok(false),
+        }
+    }
+}
+
+/// In-memory [`KeyUsageStore`] for tests.
+#[derive(Clone, Default)]
+pub struct MockKeyUsageStore {
+    pub entries: Arc<Mutex<Vec<KeyUsageEntry>>>,
+}
+
+impl MockKeyUsageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
 
-            return Ok(Some(info.clone()));
+#[async_trait]
+impl KeyUsageStore for MockKeyUsageStore {
+    async fn add(&self, entries: &[KeyUsageEntry]) -> Result<(), String> {
+        let mut stored = self.entries.lock().unwrap();
+        for entry in entries {
+            match stored
+                .iter_mut()
+                .find(|e| e.key == entry.key && e.day == entry.day && e.method == entry.method)
+            {
+                Some(existing) => {
+                    existing.requests += entry.requests;
+                    existing.response_bytes += entry.response_bytes;
+                }
+                None => stored.push(entry.clone()),
+            }
         }
+        Ok(())
+    }
+
+    async fn query(&self, key: &str, from: u64, to: u64) -> Result<Vec<KeyUsageEntry>, String> {
+        let (from, to) = (day_string(from), day_string(to));
+        let mut entries: Vec<KeyUsageEntry> = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| e.key == key && e.day >= from && e.day <= to)
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| b.day.cmp(&a.day).then_with(|| a.method.cmp(&b.method)));
+        Ok(entries)
+    }
+}
+
+/// In-memory [`SpendStore`] for tests. Writes fail while `fail` is set.
+#[derive(Clone, Default)]
+pub struct MockSpendStore {
+    pub totals: Arc<Mutex<HashMap<String, u64>>>,
+    pub fail: Arc<Mutex<bool>>,
+}
 
-        Ok(None)
+impl MockSpendStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SpendStore for MockSpendStore {
+    async fn add(&self, charges: &[(String, u64)]) -> Result<Vec<u64>, String> {
+        if *self.fail.lock().unwrap() {
+            return Err("mock spend store unavailable".to_string());
+        }
+        let mut totals = self.totals.lock().unwrap();
+        Ok(charges
+            .iter()
+            .map(|(key, units)| {
+                let total = totals.entry(key.clone()).or_default();
+                *total += units;
+                *total
+            })
+            .collect())
     }
 }
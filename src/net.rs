@@ -0,0 +1,56 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::TcpListener;
+
+/// Listen backlog for every bound socket.
+const BACKLOG: i32 = 1024;
+
+/// Client address with IPv4-mapped IPv6 (`::ffff:a.b.c.d`, as reported by
+/// dual-stack sockets) turned back into plain IPv4.
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    ip.to_canonical()
+}
+
+/// Like [`canonical_ip`], keeping the port.
+pub fn canonical_addr(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(canonical_ip(addr.ip()), addr.port())
+}
+
+/// Address that per-client rate limits are keyed by. IPv4 addresses count
+/// individually; IPv6 clients by their /64, since a single host usually holds
+/// a whole /64 and could otherwise rotate addresses to dodge the limit.
+pub fn rate_limit_subject(ip: IpAddr) -> IpAddr {
+    match canonical_ip(ip) {
+        IpAddr::V6(v6) => {
+            let prefix = u128::from(v6) & (u128::MAX << 64);
+            IpAddr::V6(Ipv6Addr::from(prefix))
+        }
+        v4 => v4,
+    }
+}
+
+/// Bind `port` on each of `addrs`. IPv6 sockets are IPv6-only so `::` and
+/// `0.0.0.0` can share a port instead of the IPv6 socket claiming both families.
+pub fn bind_all(addrs: &[IpAddr], port: u16) -> io::Result<Vec<TcpListener>> {
+    addrs
+        .iter()
+        .map(|ip| {
+            let addr = SocketAddr::new(*ip, port);
+            let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+            if addr.is_ipv6() {
+                socket.set_only_v6(true)?;
+            }
+            socket.set_reuse_address(true)?;
+            socket.set_nonblocking(true)?;
+            socket
+                .bind(&addr.into())
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", addr, e)))?;
+            socket.listen(BACKLOG)?;
+            TcpListener::from_std(socket.into())
+        })
+        .collect()
+}
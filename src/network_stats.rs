@@ -0,0 +1,144 @@
+use bytes::Bytes;
+use metrics::counter;
+use serde_json::Value;
+use tokio::time::Duration;
+use tracing::debug;
+
+use crate::{
+    cache::{CacheLookup, CachePolicy, ReadKey},
+    config::{Canary, NetworkStatsCacheConfig},
+    consistency::{comparable, query_canary},
+    redact::redact,
+    state::AppState,
+};
+
+/// Network-wide stats methods cached under `cache.network_stats`.
+pub const NETWORK_STATS_METHODS: &[&str] = &["getSupply", "getInflationRate", "getInflationReward"];
+
+/// Whether a request for `method` with `params` asks for network-wide stats
+/// that can be cached. `getInflationReward` only counts with an explicit
+/// `epoch`: without one it answers for the latest epoch, which moves on at
+/// every epoch boundary, and a node has no answer yet for one that has not
+/// ended.
+pub fn is_network_stats(method: &str, params: Option<&Value>) -> bool {
+    match method {
+        "getSupply" | "getInflationRate" => true,
+        "getInflationReward" => params
+            .and_then(|params| params.get(1))
+            .and_then(|config| config.get("epoch"))
+            .is_some_and(Value::is_u64),
+        _ => false,
+    }
+}
+
+/// Cache lookup for a single network-stats request in `body`, reused for
+/// `config.ttl_secs` and cross-checked if configured. `None` for any other
+/// request.
+pub fn lookup(body: &[u8], config: &NetworkStatsCacheConfig) -> Option<CacheLookup> {
+    let request: Value = serde_json::from_slice(body).ok()?;
+    let method = request.get("method")?.as_str()?;
+    if !is_network_stats(method, request.get("params")) {
+        return None;
+    }
+    let read = ReadKey::from_request(body)?;
+    Some(CacheLookup {
+        method: read.method,
+        key: read.key,
+        policy: CachePolicy::Network,
+        commitment: read.commitment,
+        ttl: Duration::from_secs(config.ttl_secs),
+        tuned: false,
+        cross_check: config.cross_check.then(|| Bytes::copy_from_slice(body)),
+        revalidating: None,
+        id: read.id,
+    })
+}
+
+/// Whether two answers agree: the same shape and values, except that numbers
+/// may differ by up to `tolerance_bps` basis points of the larger one.
+pub fn agrees(a: &Value, b: &Value, tolerance_bps: u32) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            if x == y {
+                return true;
+            }
+            let (Some(x), Some(y)) = (x.as_f64(), y.as_f64()) else {
+                return false;
+            };
+            (x - y).abs() <= x.abs().max(y.abs()) * f64::from(tolerance_bps) / 10_000.0
+        }
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| agrees(x, y, tolerance_bps))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(k, v)| y.get(k).is_some_and(|w| agrees(v, w, tolerance_bps)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Ask another backend in rotation for the stats `answered_by` returned in
+/// `response`, and whether its answer agrees. Without another backend, or an
+/// answer from it, the result is not confirmed.
+pub async fn confirm(
+    state: &AppState,
+    lookup: &CacheLookup,
+    answered_by: &str,
+    response: &[u8],
+) -> bool {
+    let Some(request) = &lookup.cross_check else {
+        return true;
+    };
+    let router_state = state.state.load_full();
+    let tolerance_bps = router_state.cache.network_stats.tolerance_bps;
+    let Some(result) = serde_json::from_slice::<Value>(response)
+        .ok()
+        .and_then(|mut r| r.get_mut("result").map(Value::take))
+    else {
+        return false;
+    };
+    let outcome = match router_state.select_retry_backend(Some(&lookup.method), &[answered_by]) {
+        None => "no_backend",
+        Some(backend) => {
+            let params = serde_json::from_slice::<Value>(request)
+                .ok()
+                .and_then(|mut r| r.get_mut("params").map(Value::take))
+                .unwrap_or(Value::Null);
+            let canary = Canary {
+                name: lookup.method.clone(),
+                method: lookup.method.clone(),
+                params,
+            };
+            let timeout_after = Duration::from_secs(router_state.proxy_timeout_secs);
+            match query_canary(
+                &state.client_for(&backend.config, false),
+                &backend.config,
+                &canary,
+                timeout_after,
+            )
+            .await
+            {
+                Ok(other) if agrees(&comparable(result), &other, tolerance_bps) => "agree",
+                Ok(_) => "disagree",
+                Err(e) => {
+                    debug!(
+                        "Cross-checking {} on {} failed: {}",
+                        lookup.method,
+                        backend.config.label,
+                        redact(&e)
+                    );
+                    "error"
+                }
+            }
+        }
+    };
+    counter!(
+        "rpc_cache_cross_checks_total",
+        "rpc_method" => lookup.method.clone(),
+        "result" => outcome
+    )
+    .increment(1);
+    outcome == "agree"
+}
@@ -0,0 +1,209 @@
+use axum::{
+    body::Body,
+    http::{HeaderValue, Request},
+};
+use hmac::{Hmac, Mac};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use sha2::{Digest, Sha256};
+use tokio::time::{timeout, Duration};
+
+use crate::{config::OffloadConfig, signing::utc_date};
+
+/// Request header opting a client into offloaded responses.
+pub const OFFLOAD_HEADER: &str = "x-rpc-router-offload";
+
+/// Response header marking a presigned-URL answer.
+pub const OFFLOADED_HEADER: &str = "x-rpc-router-offloaded";
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// URL, `Host` and canonical path of one object.
+struct ObjectLocation {
+    url: String,
+    host: String,
+    path: String,
+}
+
+fn location(config: &OffloadConfig, key: &str) -> Result<ObjectLocation, String> {
+    let endpoint = config.endpoint.trim_end_matches('/');
+    let (scheme, rest) = endpoint
+        .split_once("://")
+        .ok_or_else(|| format!("invalid offload endpoint: {}", endpoint))?;
+    let (authority, base_path) = match rest.split_once('/') {
+        Some((authority, path)) => (authority, format!("/{}", path)),
+        None => (rest, String::new()),
+    };
+    let key = uri_encode(key, false);
+    let (host, path) = if config.path_style {
+        let bucket = uri_encode(&config.bucket, true);
+        (
+            authority.to_string(),
+            format!("{}/{}/{}", base_path, bucket, key),
+        )
+    } else {
+        let host = format!("{}.{}", config.bucket, authority);
+        (host, format!("{}/{}", base_path, key))
+    };
+    Ok(ObjectLocation {
+        url: format!("{}://{}{}", scheme, host, path),
+        host,
+        path,
+    })
+}
+
+/// Percent-encode everything but unreserved characters (and `/`, unless
+/// `encode_slash`), as SigV4 canonical URIs and query strings require.
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// `(YYYYMMDD, YYYYMMDDTHHMMSSZ)` for a unix timestamp.
+fn amz_dates(unix: u64) -> (String, String) {
+    let (year, month, day) = utc_date(unix);
+    let secs = unix % 86_400;
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let datetime = format!(
+        "{}T{:02}{:02}{:02}Z",
+        date,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+    (date, datetime)
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn scope(config: &OffloadConfig, date: &str) -> String {
+    format!("{}/{}/s3/aws4_request", date, config.region)
+}
+
+/// SigV4 signature of `canonical_request`.
+fn signature(
+    config: &OffloadConfig,
+    date: &str,
+    datetime: &str,
+    canonical_request: &str,
+) -> String {
+    let secret = config.secret_access_key.as_deref().unwrap_or_default();
+    let string_to_sign = format!(
+        "{}\n{}\n{}\n{}",
+        ALGORITHM,
+        datetime,
+        scope(config, date),
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = [config.region.as_str(), "s3", "aws4_request"].iter().fold(
+        hmac(format!("AWS4{}", secret).as_bytes(), date),
+        |key, part| hmac(&key, part),
+    );
+    hex::encode(hmac(&key, &string_to_sign))
+}
+
+/// Presigned `GET` URL for `key`, valid for `offload.url_expiry_secs` from `now`.
+pub fn presign_get(config: &OffloadConfig, key: &str, now: u64) -> Result<String, String> {
+    let location = location(config, key)?;
+    let (date, datetime) = amz_dates(now);
+    let credential = format!("{}/{}", config.access_key_id, scope(config, &date));
+    // Already in canonical (sorted) order
+    let query = format!(
+        "X-Amz-Algorithm={}&X-Amz-Credential={}&X-Amz-Date={}&X-Amz-Expires={}&X-Amz-SignedHeaders=host",
+        ALGORITHM,
+        uri_encode(&credential, true),
+        datetime,
+        config.url_expiry_secs
+    );
+    let canonical_request = format!(
+        "GET\n{}\n{}\nhost:{}\n\nhost\n{}",
+        location.path, query, location.host, UNSIGNED_PAYLOAD
+    );
+    let signature = signature(config, &date, &datetime, &canonical_request);
+    Ok(format!(
+        "{}?{}&X-Amz-Signature={}",
+        location.url, query, signature
+    ))
+}
+
+/// Signed `PUT` of `body` (exactly `content_length` bytes) to `key`. The
+/// payload is not hashed, so the body can be streamed.
+pub fn put_request(
+    config: &OffloadConfig,
+    key: &str,
+    now: u64,
+    body: Body,
+    content_length: u64,
+    content_type: Option<&HeaderValue>,
+    content_encoding: Option<&HeaderValue>,
+) -> Result<Request<Body>, String> {
+    let location = location(config, key)?;
+    let (date, datetime) = amz_dates(now);
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        location.path, location.host, UNSIGNED_PAYLOAD, datetime, signed_headers, UNSIGNED_PAYLOAD
+    );
+    let authorization = format!(
+        "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+        ALGORITHM,
+        config.access_key_id,
+        scope(config, &date),
+        signed_headers,
+        signature(config, &date, &datetime, &canonical_request)
+    );
+
+    let mut builder = Request::put(&location.url)
+        .header("host", &location.host)
+        .header("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+        .header("x-amz-date", &datetime)
+        .header("authorization", authorization)
+        .header("content-length", content_length);
+    // Stored with the object and returned on download
+    if let Some(value) = content_type {
+        builder = builder.header("content-type", value);
+    }
+    if let Some(value) = content_encoding {
+        builder = builder.header("content-encoding", value);
+    }
+    builder.body(body).map_err(|e| e.to_string())
+}
+
+/// Object key for a response offloaded at `now`.
+pub fn object_key(config: &OffloadConfig, now: u64) -> String {
+    format!("{}{}-{:016x}", config.prefix, now, rand::random::<u64>())
+}
+
+/// Send `request` (from [`put_request`]) within `offload.upload_timeout_secs`.
+pub async fn upload(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    config: &OffloadConfig,
+    request: Request<Body>,
+) -> Result<(), String> {
+    let limit = Duration::from_secs(config.upload_timeout_secs);
+    match timeout(limit, client.request(request)).await {
+        Ok(Ok(resp)) if resp.status().is_success() => Ok(()),
+        Ok(Ok(resp)) => Err(format!("object store returned {}", resp.status())),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!(
+            "upload timed out after {}s",
+            config.upload_timeout_secs
+        )),
+    }
+}
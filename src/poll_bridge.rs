@@ -0,0 +1,443 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use futures_util::{SinkExt, StreamExt};
+use metrics::{counter, gauge};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::{
+    sync::Notify,
+    task::AbortHandle,
+    time::{sleep, timeout, timeout_at, Duration, Instant},
+};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Message},
+};
+use tracing::debug;
+
+use crate::{
+    config::Commitment,
+    methods::{method_info, MethodClass},
+    redact::redact,
+    signing::{apply_backend_headers, apply_signature, unix_now},
+    state::AppState,
+    ws::{is_notification, LocalSubscriptions},
+};
+
+/// How often idle subscriptions are looked for.
+const SWEEP_SECS: u64 = 1;
+
+/// Identity of the API key owning a subscription. The full digest, so one key
+/// cannot reach another's subscriptions through a fingerprint collision.
+fn key_id(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
+}
+
+/// Notifications returned by one poll.
+#[derive(Debug, Serialize)]
+pub struct PollBatch {
+    pub notifications: Vec<Value>,
+    /// Notifications dropped since the last poll because the buffer was full
+    pub dropped: u64,
+    /// The subscription ended (upstream closed, or it was closed for being idle)
+    pub closed: bool,
+}
+
+/// One subscription held on behalf of a polling client, buffering its
+/// notifications between polls.
+pub struct Bridged {
+    key: String,
+    owner: String,
+    method: String,
+    pending: Mutex<VecDeque<Value>>,
+    capacity: usize,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+    notify: Notify,
+    last_poll: AtomicU64,
+    task: Mutex<Option<AbortHandle>>,
+}
+
+impl Bridged {
+    /// Buffer `notification`, dropping the oldest one when full.
+    pub fn push(&self, notification: Value) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.len() >= self.capacity {
+            pending.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            counter!("poll_bridge_notifications_dropped_total", "owner" => self.owner.clone())
+                .increment(1);
+        }
+        pending.push_back(notification);
+        drop(pending);
+        self.notify.notify_one();
+    }
+
+    /// End the subscription; a waiting poll returns at once.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if let Some(task) = self.task.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            task.abort();
+        }
+        self.notify.notify_one();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    fn set_task(&self, task: AbortHandle) {
+        let mut slot = self.task.lock().unwrap_or_else(|e| e.into_inner());
+        if self.is_closed() {
+            task.abort();
+        } else {
+            *slot = Some(task);
+        }
+    }
+
+    /// Everything buffered, waiting up to `wait` for the first notification
+    /// when there is none yet.
+    pub async fn poll(&self, wait: Duration) -> PollBatch {
+        self.last_poll.store(unix_now(), Ordering::Relaxed);
+        let deadline = Instant::now() + wait;
+        loop {
+            let notifications: Vec<Value> = self
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .drain(..)
+                .collect();
+            let closed = self.is_closed();
+            if !notifications.is_empty()
+                || closed
+                || timeout_at(deadline, self.notify.notified()).await.is_err()
+            {
+                self.last_poll.store(unix_now(), Ordering::Relaxed);
+                return PollBatch {
+                    notifications,
+                    dropped: self.dropped.swap(0, Ordering::Relaxed),
+                    closed,
+                };
+            }
+        }
+    }
+}
+
+/// Subscriptions held for `/v1/poll` clients, by subscription id.
+#[derive(Default)]
+pub struct PollBridge {
+    subscriptions: Mutex<HashMap<String, Arc<Bridged>>>,
+}
+
+impl PollBridge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriptions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Register a subscription for `api_key` unless it already holds `limit`.
+    /// Returns the new subscription's id.
+    pub fn reserve(
+        &self,
+        api_key: &str,
+        owner: &str,
+        method: &str,
+        capacity: usize,
+        limit: usize,
+    ) -> Option<(String, Arc<Bridged>)> {
+        let key = key_id(api_key);
+        let mut subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        if subscriptions.values().filter(|b| b.key == key).count() >= limit {
+            return None;
+        }
+        let id = hex::encode(rand::random::<[u8; 16]>());
+        let bridged = Arc::new(Bridged {
+            key,
+            owner: owner.to_string(),
+            method: method.to_string(),
+            pending: Mutex::new(VecDeque::new()),
+            capacity,
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            notify: Notify::new(),
+            last_poll: AtomicU64::new(unix_now()),
+            task: Mutex::new(None),
+        });
+        subscriptions.insert(id.clone(), bridged.clone());
+        Some((id, bridged))
+    }
+
+    /// Subscription `id`, if it belongs to `api_key`.
+    pub fn get(&self, id: &str, api_key: &str) -> Option<Arc<Bridged>> {
+        let key = key_id(api_key);
+        self.subscriptions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .filter(|b| b.key == key)
+            .cloned()
+    }
+
+    /// Close and forget subscription `id` of `api_key`. Returns `false` if there
+    /// is no such subscription.
+    pub fn remove(&self, id: &str, api_key: &str) -> bool {
+        let key = key_id(api_key);
+        let mut subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        if subscriptions.get(id).is_none_or(|b| b.key != key) {
+            return false;
+        }
+        if let Some(bridged) = subscriptions.remove(id) {
+            bridged.close();
+        }
+        true
+    }
+
+    /// Close subscriptions not polled for `idle_secs` as of `now`. Returns how
+    /// many were closed.
+    pub fn expire_idle(&self, now: u64, idle_secs: u64) -> usize {
+        self.retain(|b| b.last_poll.load(Ordering::Relaxed) + idle_secs > now)
+    }
+
+    /// Close every subscription, e.g. when the bridge is disabled.
+    pub fn clear(&self) -> usize {
+        self.retain(|_| false)
+    }
+
+    fn retain(&self, keep: impl Fn(&Bridged) -> bool) -> usize {
+        let mut subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        let before = subscriptions.len();
+        subscriptions.retain(|_, bridged| {
+            let kept = keep(bridged);
+            if !kept {
+                bridged.close();
+            }
+            kept
+        });
+        before - subscriptions.len()
+    }
+}
+
+/// Why a bridged subscription could not be opened.
+#[derive(Debug)]
+pub enum BridgeError {
+    /// The body is not a JSON-RPC subscribe request
+    InvalidRequest(String),
+    /// The key already holds `poll_bridge.max_subscriptions_per_key`
+    LimitReached,
+    /// No backend in rotation has a `ws_url`
+    NoBackend,
+    /// The backend could not be reached or did not confirm the subscription
+    Upstream(String),
+    /// The subscription was refused: the JSON-RPC error response
+    Rejected(Value),
+}
+
+/// Open the subscription described by `request` for `api_key` and return its
+/// id. Slot and root subscriptions come from the router's slot feed when
+/// `websocket.local_slot_subscriptions` is on; anything else gets its own
+/// upstream WebSocket connection.
+pub async fn subscribe(
+    state: &AppState,
+    api_key: &str,
+    owner: &str,
+    request: Value,
+) -> Result<String, BridgeError> {
+    let method = request
+        .get("method")
+        .and_then(Value::as_str)
+        .filter(|m| m.ends_with("Subscribe"))
+        .filter(|m| method_info(m).is_some_and(|info| info.class == MethodClass::Subscription))
+        .ok_or_else(|| {
+            BridgeError::InvalidRequest("expected a JSON-RPC subscribe request".to_string())
+        })?
+        .to_string();
+
+    let router_state = state.state.load_full();
+    let config = &router_state.poll_bridge;
+    let (id, bridged) = state
+        .poll_bridge
+        .reserve(
+            api_key,
+            owner,
+            &method,
+            config.max_buffered,
+            config.max_subscriptions_per_key,
+        )
+        .ok_or(BridgeError::LimitReached)?;
+
+    let websocket = &router_state.websocket;
+    let started = if websocket.local_slot_subscriptions
+        && matches!(method.as_str(), "slotSubscribe" | "rootSubscribe")
+    {
+        start_local(state, &bridged, &request, websocket.slot_commitment)
+    } else {
+        let timeout_after = Duration::from_secs(router_state.proxy_timeout_secs);
+        start_upstream(state, &bridged, &request, timeout_after).await
+    };
+    let status = if started.is_ok() { "opened" } else { "failed" };
+    counter!("poll_bridge_subscriptions_total", "owner" => owner.to_string(), "status" => status)
+        .increment(1);
+    match started {
+        Ok(task) => {
+            bridged.set_task(task);
+            gauge!("poll_bridge_subscriptions").set(state.poll_bridge.len() as f64);
+            Ok(id)
+        }
+        Err(e) => {
+            state.poll_bridge.remove(&id, api_key);
+            Err(e)
+        }
+    }
+}
+
+fn start_local(
+    state: &AppState,
+    bridged: &Arc<Bridged>,
+    request: &Value,
+    min_commitment: Commitment,
+) -> Result<AbortHandle, BridgeError> {
+    let mut local = LocalSubscriptions::new(min_commitment);
+    let response = local
+        .handle_request(&request.to_string())
+        .and_then(|r| serde_json::from_str::<Value>(&r).ok())
+        .ok_or_else(|| BridgeError::InvalidRequest("invalid subscribe request".to_string()))?;
+    if response.get("error").is_some() {
+        return Err(BridgeError::Rejected(response));
+    }
+
+    let mut view = state.slot_feed.subscribe();
+    let bridged = bridged.clone();
+    let task = tokio::spawn(async move {
+        loop {
+            let current = *view.borrow_and_update();
+            for notification in local.notifications(&current) {
+                if let Ok(notification) = serde_json::from_str(&notification) {
+                    bridged.push(notification);
+                }
+            }
+            if view.changed().await.is_err() {
+                break;
+            }
+        }
+        bridged.close();
+    });
+    Ok(task.abort_handle())
+}
+
+async fn start_upstream(
+    state: &AppState,
+    bridged: &Arc<Bridged>,
+    request: &Value,
+    timeout_after: Duration,
+) -> Result<AbortHandle, BridgeError> {
+    let (label, url) = state.select_ws_backend().ok_or(BridgeError::NoBackend)?;
+    let backend = state
+        .state
+        .load()
+        .backends
+        .iter()
+        .find(|b| b.config.label == label)
+        .map(|b| b.config.clone());
+    let upstream = |e: String| BridgeError::Upstream(format!("{}: {}", label, redact(&e)));
+
+    let connect = async {
+        let mut upstream_request = url
+            .as_str()
+            .into_client_request()
+            .map_err(|e| e.to_string())?;
+        if let Some(backend) = &backend {
+            apply_backend_headers(backend, upstream_request.headers_mut());
+            if let Some(signing) = &backend.signing {
+                apply_signature(signing, upstream_request.headers_mut(), b"", unix_now());
+            }
+        }
+        connect_async(upstream_request)
+            .await
+            .map(|(socket, _)| socket)
+            .map_err(|e| e.to_string())
+    };
+    let mut socket = timeout(timeout_after, connect)
+        .await
+        .map_err(|_| upstream("connection timed out".to_string()))?
+        .map_err(upstream)?;
+    socket
+        .send(Message::Text(request.to_string()))
+        .await
+        .map_err(|e| upstream(e.to_string()))?;
+
+    // The subscribe response comes before any notification
+    let confirm = async {
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Text(text) = message {
+                if !is_notification(&text) {
+                    return serde_json::from_str::<Value>(&text).ok();
+                }
+            }
+        }
+        None
+    };
+    let response = timeout(timeout_after, confirm)
+        .await
+        .map_err(|_| upstream("subscription not confirmed in time".to_string()))?
+        .ok_or_else(|| {
+            upstream("connection closed before the subscription was confirmed".to_string())
+        })?;
+    if response.get("error").is_some() {
+        return Err(BridgeError::Rejected(response));
+    }
+
+    let bridged = bridged.clone();
+    let task = tokio::spawn(async move {
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Text(text) = message {
+                if let Ok(notification) = serde_json::from_str(&text) {
+                    bridged.push(notification);
+                }
+            }
+        }
+        debug!(
+            "Bridged {} upstream connection to {} closed",
+            bridged.method, label
+        );
+        bridged.close();
+    });
+    Ok(task.abort_handle())
+}
+
+/// Close bridged subscriptions nobody polls any more, and all of them while the
+/// bridge is disabled.
+pub async fn poll_bridge_loop(state: Arc<AppState>) {
+    loop {
+        sleep(Duration::from_secs(SWEEP_SECS)).await;
+        let router_state = state.state.load_full();
+        let config = &router_state.poll_bridge;
+        let closed = if config.enabled {
+            state
+                .poll_bridge
+                .expire_idle(unix_now(), config.idle_timeout_secs)
+        } else {
+            state.poll_bridge.clear()
+        };
+        if closed > 0 {
+            debug!("Closed {} idle bridged subscriptions", closed);
+            gauge!("poll_bridge_subscriptions").set(state.poll_bridge.len() as f64);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use serde::Deserialize;
+use tokio::time::Duration;
+
+/// Length of a CPU profile when none is asked for.
+pub const DEFAULT_CPU_PROFILE_SECS: u64 = 30;
+
+/// Longest CPU profile one request may take.
+pub const MAX_CPU_PROFILE_SECS: u64 = 300;
+
+/// Stack samples per second when no frequency is asked for.
+pub const DEFAULT_CPU_PROFILE_FREQUENCY: u32 = 99;
+
+/// Highest sampling frequency accepted.
+pub const MAX_CPU_PROFILE_FREQUENCY: u32 = 1000;
+
+/// Encoding of a CPU profile.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CpuProfileFormat {
+    /// pprof protobuf, for `go tool pprof` or Pyroscope
+    #[default]
+    Pprof,
+    /// SVG flame graph, viewed in a browser
+    Flamegraph,
+}
+
+impl CpuProfileFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Pprof => "application/octet-stream",
+            Self::Flamegraph => "image/svg+xml",
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Pprof => "cpu.pb",
+            Self::Flamegraph => "cpu.svg",
+        }
+    }
+}
+
+/// Why a profile could not be taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileError {
+    /// Not compiled in, or not enabled at startup
+    Unavailable(&'static str),
+    /// Another CPU profile is being taken
+    Busy,
+    Failed(String),
+}
+
+impl std::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unavailable(reason) => f.write_str(reason),
+            Self::Busy => f.write_str("A CPU profile is already being taken"),
+            Self::Failed(e) => write!(f, "Profiling failed: {}", e),
+        }
+    }
+}
+
+/// Sample the stacks of every thread `frequency` times a second for
+/// `duration`, then encode them as `format`. One profile is taken at a time.
+#[cfg(feature = "profiling")]
+pub async fn cpu_profile(
+    duration: Duration,
+    frequency: u32,
+    format: CpuProfileFormat,
+) -> Result<Vec<u8>, ProfileError> {
+    use pprof::protos::Message;
+    use tokio::sync::Mutex;
+
+    static RUNNING: Mutex<()> = Mutex::const_new(());
+    let _running = RUNNING.try_lock().map_err(|_| ProfileError::Busy)?;
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(frequency as i32)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(|e| ProfileError::Failed(e.to_string()))?;
+    tokio::time::sleep(duration).await;
+    let report = guard
+        .report()
+        .build()
+        .map_err(|e| ProfileError::Failed(e.to_string()))?;
+    let mut out = Vec::new();
+    match format {
+        CpuProfileFormat::Pprof => report
+            .pprof()
+            .map_err(|e| ProfileError::Failed(e.to_string()))?
+            .encode(&mut out)
+            .map_err(|e| ProfileError::Failed(e.to_string()))?,
+        CpuProfileFormat::Flamegraph => report
+            .flamegraph(&mut out)
+            .map_err(|e| ProfileError::Failed(e.to_string()))?,
+    }
+    Ok(out)
+}
+
+#[cfg(not(feature = "profiling"))]
+pub async fn cpu_profile(
+    _duration: Duration,
+    _frequency: u32,
+    _format: CpuProfileFormat,
+) -> Result<Vec<u8>, ProfileError> {
+    Err(ProfileError::Unavailable(
+        "CPU profiles need the router built with the 'profiling' feature",
+    ))
+}
+
+/// jemalloc's sampled profile of live heap allocations, in the format
+/// `jeprof` reads. Blocks while the profile is written out.
+#[cfg(feature = "heap-profiling")]
+pub fn heap_profile() -> Result<Vec<u8>, ProfileError> {
+    use std::{
+        ffi::{c_char, CString},
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    static DUMPS: AtomicU64 = AtomicU64::new(0);
+    let enabled = tikv_jemalloc_ctl::profiling::prof::read().unwrap_or(false);
+    if !enabled {
+        return Err(ProfileError::Unavailable(
+            "Heap profiling is off: jemalloc was started without prof:true",
+        ));
+    }
+    let path = std::env::temp_dir().join(format!(
+        "sol-rpc-router-heap-{}-{}.prof",
+        std::process::id(),
+        DUMPS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let c_path = CString::new(path.to_string_lossy().as_bytes())
+        .map_err(|e| ProfileError::Failed(e.to_string()))?;
+    // SAFETY: prof.dump takes a NUL-terminated file name, read during the call
+    unsafe { tikv_jemalloc_ctl::raw::write(b"prof.dump\0", c_path.as_ptr() as *const c_char) }
+        .map_err(|e| ProfileError::Failed(e.to_string()))?;
+    let profile = std::fs::read(&path).map_err(|e| ProfileError::Failed(e.to_string()));
+    let _ = std::fs::remove_file(&path);
+    profile
+}
+
+#[cfg(not(feature = "heap-profiling"))]
+pub fn heap_profile() -> Result<Vec<u8>, ProfileError> {
+    Err(ProfileError::Unavailable(
+        "Heap profiles need the router built with the 'heap-profiling' feature",
+    ))
+}
@@ -0,0 +1,74 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use axum::body::Body;
+use hyper_tls::HttpsConnector;
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+
+use crate::config::{Backend, BackendProtocol, HttpVersion};
+
+type UpstreamClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+/// HTTP(S) client speaking `protocol`, giving up on opening a connection after
+/// `connect_timeout`.
+pub fn protocol_client(
+    protocol: &BackendProtocol,
+    connect_timeout: Option<Duration>,
+) -> UpstreamClient {
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_connect_timeout(connect_timeout);
+    let mut tls = native_tls::TlsConnector::builder();
+    let alpn = protocol.alpn_offer();
+    if !alpn.is_empty() {
+        tls.request_alpns(&alpn);
+    }
+    // Fails only when the system TLS library cannot start, as in `HttpsConnector::new`
+    let tls = tls.build().expect("native-tls connector");
+
+    let mut builder = Client::builder(TokioExecutor::new());
+    if protocol.http_version == HttpVersion::Http2 {
+        builder.http2_only(true);
+    }
+    builder.build(HttpsConnector::from((http, tls.into())))
+}
+
+/// Connection pools of backends with their own `protocol` settings, created on
+/// first use. Backends with the same settings share them; the rest use the
+/// shared pools.
+pub struct BackendClients {
+    connect_timeout: Option<Duration>,
+    /// (settings, write pool) -> client
+    clients: Mutex<HashMap<(BackendProtocol, bool), UpstreamClient>>,
+}
+
+impl BackendClients {
+    pub fn new(connect_timeout: Option<Duration>) -> Self {
+        Self {
+            connect_timeout,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The client for requests to `backend`: `shared` unless the backend sets
+    /// its own protocol, in which case a pool for those settings (a separate
+    /// one for writes when `write`).
+    pub fn pick(&self, shared: &UpstreamClient, backend: &Backend, write: bool) -> UpstreamClient {
+        if !backend.protocol.is_custom() {
+            return shared.clone();
+        }
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        clients
+            .entry((backend.protocol.clone(), write))
+            .or_insert_with(|| protocol_client(&backend.protocol, self.connect_timeout))
+            .clone()
+    }
+}
+
+impl Default for BackendClients {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    sync::{LazyLock, RwLock},
+};
+
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use tracing_subscriber::fmt::MakeWriter;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Literal secrets shorter than this are not registered, to avoid mangling
+/// unrelated log text.
+const MIN_SECRET_LEN: usize = 8;
+
+struct Redactor {
+    builtin: Vec<(Regex, &'static str)>,
+    custom: Vec<Regex>,
+    secrets: Vec<String>,
+}
+
+static REDACTOR: LazyLock<RwLock<Redactor>> = LazyLock::new(|| {
+    let builtin = [
+        // ?api-key=... / api_key=... in URLs and query strings
+        (r#"(?i)(api[-_]?key=)[^&\s"']+"#, "${1}[REDACTED]"),
+        // Other credential-like query parameters
+        (
+            r#"(?i)([?&](?:token|access_token|secret|auth|password)=)[^&\s"']+"#,
+            "${1}[REDACTED]",
+        ),
+        // Authorization headers
+        (
+            r"(?i)(authorization:\s*(?:bearer|basic)\s+)\S+",
+            "${1}[REDACTED]",
+        ),
+        // Passwords in URL userinfo
+        (r"(://[^/\s:@]+:)[^@/\s]+@", "${1}[REDACTED]@"),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| {
+        (
+            Regex::new(pattern).expect("valid builtin pattern"),
+            replacement,
+        )
+    })
+    .collect();
+
+    RwLock::new(Redactor {
+        builtin,
+        custom: Vec::new(),
+        secrets: Vec::new(),
+    })
+});
+
+/// Compile operator-supplied secret patterns, reporting the first invalid one.
+pub fn compile_patterns(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).map_err(|e| format!("Invalid redaction pattern '{}': {}", p, e)))
+        .collect()
+}
+
+/// Replace the configured secret patterns (called on config load and reload).
+pub fn set_custom_patterns(patterns: &[String]) -> Result<(), String> {
+    let compiled = compile_patterns(patterns)?;
+    REDACTOR.write().unwrap_or_else(|e| e.into_inner()).custom = compiled;
+    Ok(())
+}
+
+/// Register a literal secret (e.g. a backend credential) that must never be logged.
+pub fn register_secret(secret: &str) {
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut redactor = REDACTOR.write().unwrap_or_else(|e| e.into_inner());
+    if !redactor.secrets.iter().any(|s| s == secret) {
+        redactor.secrets.push(secret.to_string());
+    }
+}
+
+/// Redact every known secret from `input`. Borrows when nothing matched.
+pub fn redact(input: &str) -> Cow<'_, str> {
+    let redactor = REDACTOR.read().unwrap_or_else(|e| e.into_inner());
+    let mut output = Cow::Borrowed(input);
+
+    for (regex, replacement) in &redactor.builtin {
+        if regex.is_match(&output) {
+            output = Cow::Owned(regex.replace_all(&output, *replacement).into_owned());
+        }
+    }
+    for regex in &redactor.custom {
+        if regex.is_match(&output) {
+            output = Cow::Owned(regex.replace_all(&output, REDACTED).into_owned());
+        }
+    }
+    for secret in &redactor.secrets {
+        if output.contains(secret.as_str()) {
+            output = Cow::Owned(output.replace(secret.as_str(), REDACTED));
+        }
+    }
+
+    output
+}
+
+/// Render a backend URL for logs: userinfo and query values are masked, as are
+/// path segments that look like embedded provider tokens.
+pub fn redact_url(url: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (url, None),
+    };
+
+    let mut result = match base.split_once("://") {
+        Some((scheme, rest)) => {
+            let (authority, path) = match rest.find('/') {
+                Some(idx) => rest.split_at(idx),
+                None => (rest, ""),
+            };
+            let authority = match authority.rsplit_once('@') {
+                Some((_, host)) => format!("{}@{}", REDACTED, host),
+                None => authority.to_string(),
+            };
+            let path = path
+                .split('/')
+                .map(|segment| {
+                    if looks_like_token(segment) {
+                        REDACTED
+                    } else {
+                        segment
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            format!("{}://{}{}", scheme, authority, path)
+        }
+        None => base.to_string(),
+    };
+
+    if let Some(query) = query {
+        let masked = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) => format!("{}={}", name, REDACTED),
+                None => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        result.push('?');
+        result.push_str(&masked);
+    }
+
+    result
+}
+
+fn looks_like_token(segment: &str) -> bool {
+    segment.len() >= 24
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Stable, non-reversible identifier for an API key, safe to log.
+pub fn key_fingerprint(key: &str) -> String {
+    let digest = Sha256::digest(key.as_bytes());
+    hex::encode(&digest[..6])
+}
+
+/// `MakeWriter` for `tracing_subscriber` that redacts each formatted event
+/// before it reaches stdout.
+#[derive(Clone, Copy, Default)]
+pub struct RedactingMakeWriter;
+
+pub struct RedactingWriter {
+    inner: io::Stdout,
+}
+
+impl Write for RedactingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.inner.write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RedactingMakeWriter {
+    type Writer = RedactingWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: io::stdout(),
+        }
+    }
+}
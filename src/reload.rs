@@ -0,0 +1,372 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc, RwLock},
+};
+
+use arc_swap::ArcSwap;
+use axum::body::Body;
+use futures_util::future;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use metrics::{counter, gauge};
+use serde::Serialize;
+use tokio::{sync::Mutex, time::Duration};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{load_config, parse_config, Backend, BackendProtocol, Config, GenesisCheckConfig},
+    genesis::fetch_genesis_hash,
+    health::{perform_health_check, HealthState},
+    log_format,
+    protocol::BackendClients,
+    redact,
+    signing::unix_now,
+    state::{RouterState, RuntimeBackend},
+};
+
+/// Where the active configuration came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    /// `config_path`, at startup or on SIGHUP
+    File,
+    /// `PUT /admin/config`
+    Admin,
+}
+
+impl ConfigSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            ConfigSource::File => "file",
+            ConfigSource::Admin => "admin",
+        }
+    }
+}
+
+/// Outcome of the most recent configuration (re)load, served at `/admin/config/status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadReport {
+    pub config_path: String,
+    /// Hash of the active config document (see `Config::hash`)
+    pub config_hash: Option<String>,
+    pub config_source: Option<ConfigSource>,
+    /// Unix time the active configuration was applied
+    pub active_since: Option<u64>,
+    pub last_attempt_at: Option<u64>,
+    pub last_error: Option<String>,
+    pub successes: u64,
+    pub failures: u64,
+    pub backends: usize,
+}
+
+/// Tracks the active configuration and the outcome of reload attempts.
+#[derive(Debug, Default)]
+pub struct ReloadStatus {
+    report: RwLock<ReloadReport>,
+    active: RwLock<Option<Arc<Config>>>,
+    /// Serializes file reloads and pushes so each validates against the state it replaces
+    apply: Mutex<()>,
+}
+
+impl ReloadStatus {
+    pub fn new(config_path: &str) -> Self {
+        Self {
+            report: RwLock::new(ReloadReport {
+                config_path: config_path.to_string(),
+                ..Default::default()
+            }),
+            active: RwLock::new(None),
+            apply: Mutex::new(()),
+        }
+    }
+
+    pub fn snapshot(&self) -> ReloadReport {
+        self.report
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn active_config(&self) -> Option<Arc<Config>> {
+        self.active
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Record `config`, loaded from the config file, as the running configuration
+    /// and export its hash.
+    pub fn set_active(&self, config: Arc<Config>) {
+        self.set_active_from(config, ConfigSource::File);
+    }
+
+    fn set_active_from(&self, config: Arc<Config>, source: ConfigSource) {
+        let previous = self
+            .active
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .replace(config.clone());
+        if let Some(previous) = previous {
+            gauge!("config_info", "hash" => previous.hash.clone()).set(0.0);
+        }
+        gauge!("config_info", "hash" => config.hash.clone()).set(1.0);
+
+        let mut report = self.report.write().unwrap_or_else(|e| e.into_inner());
+        report.config_hash = Some(config.hash.clone());
+        report.config_source = Some(source);
+        report.active_since = Some(unix_now());
+        report.backends = config.backends.len();
+    }
+
+    fn record_attempt(&self, error: Option<&str>) {
+        let mut report = self.report.write().unwrap_or_else(|e| e.into_inner());
+        report.last_attempt_at = Some(unix_now());
+        report.last_error = error.map(str::to_string);
+        match error {
+            None => report.successes += 1,
+            Some(_) => report.failures += 1,
+        }
+    }
+}
+
+/// Build routing state from a validated config. Backends already tracked in
+/// `health_state` keep their health; new ones start healthy.
+pub fn router_state_from_config(config: &Config, health_state: Arc<HealthState>) -> RouterState {
+    let backends = config
+        .backends
+        .iter()
+        .map(|b| {
+            let status = health_state.get_status(&b.label).unwrap_or_default();
+            let circuit = health_state.circuit(&b.label);
+            if !config.circuit_breaker.enabled {
+                circuit.reset(&b.label);
+            }
+            let runtime_backend = RuntimeBackend::new(b.clone(), status.healthy)
+                .with_heavy_limit(config.pools.heavy_max_in_flight)
+                .with_circuit(circuit)
+                .with_identity(health_state.identity(&b.label));
+            runtime_backend
+                .degraded
+                .store(status.degraded, Ordering::Relaxed);
+            runtime_backend
+        })
+        .collect();
+
+    RouterState {
+        backends,
+        method_routes: config.method_routes.clone(),
+        tier_routes: config.tier_routes.clone(),
+        preflight_policies: config.preflight_policies.clone(),
+        method_idempotency: config.method_idempotency.clone(),
+        health_state,
+        proxy_timeout_secs: config.proxy.timeout_secs,
+        retry_transaction_version: config.proxy.retry_transaction_version,
+        max_decompressed_bytes: config.proxy.max_decompressed_bytes,
+        unwrap_single_batches: config.proxy.unwrap_single_batches,
+        split_batches: config.proxy.split_batches,
+        split_batch_concurrency: config.proxy.split_batch_concurrency,
+        max_in_flight: config.proxy.max_in_flight,
+        max_buffered_bytes: config.proxy.max_buffered_bytes,
+        health_check_config: config.health_check.clone(),
+        browser_keys: config.browser_keys.clone(),
+        probes: config.probes.clone(),
+        admin: config.admin.clone(),
+        readiness: config.readiness.clone(),
+        hedging: config.hedging.clone(),
+        coalescing: config.coalescing.clone(),
+        broadcast: config.broadcast.clone(),
+        routing: config.routing.clone(),
+        cache: config.cache.clone(),
+        pools: config.pools.clone(),
+        websocket: config.websocket.clone(),
+        offload: config.offload.clone(),
+        blockhash_check: config.blockhash_check.clone(),
+        poll_bridge: config.poll_bridge.clone(),
+        integrity: config.integrity.clone(),
+        retry: config.retry.clone(),
+        circuit_breaker: config.circuit_breaker.clone(),
+        consistency: config.consistency.clone(),
+        genesis_check: config.genesis_check.clone(),
+        archive_routing: config.archive_routing.clone(),
+        traffic_report: config.traffic_report.clone(),
+        latency_heatmap: config.latency_heatmap.clone(),
+        error_templates: config.error_templates.clone(),
+        kill_switches: config.kill_switches.clone(),
+        alerts: config.alerts.clone(),
+    }
+}
+
+/// Probe the backends of `config` that are new or whose URL or protocol
+/// changed relative to `current`. Fails with the first unreachable backend.
+pub async fn probe_backends(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    config: &Config,
+    current: &RouterState,
+) -> Result<(), String> {
+    let existing: HashMap<&str, (&str, &BackendProtocol)> = current
+        .backends
+        .iter()
+        .map(|b| {
+            (
+                b.config.label.as_str(),
+                (b.config.url.as_str(), &b.config.protocol),
+            )
+        })
+        .collect();
+
+    // Probes speak each backend's protocol over connections of their own
+    let clients = BackendClients::default();
+    let probes = config
+        .backends
+        .iter()
+        .filter(|b| existing.get(b.label.as_str()) != Some(&(b.url.as_str(), &b.protocol)))
+        .map(|backend| {
+            let client = clients.pick(client, backend, false);
+            async move {
+                perform_health_check(&client, backend, &config.health_check)
+                    .await
+                    .map_err(|e| format!("Backend '{}' unreachable: {}", backend.label, e))?;
+                verify_backend_genesis(&client, backend, &config.genesis_check).await
+            }
+        });
+
+    future::join_all(probes)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .map(|_| ())
+}
+
+/// With `genesis_check` enabled, fail if `backend` is on another cluster.
+async fn verify_backend_genesis(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    backend: &Backend,
+    genesis: &GenesisCheckConfig,
+) -> Result<(), String> {
+    let Some(expected) = genesis.expected().filter(|_| genesis.enabled) else {
+        return Ok(());
+    };
+    let timeout_after = Duration::from_secs(genesis.timeout_secs);
+    let genesis_hash = fetch_genesis_hash(client, backend, timeout_after)
+        .await
+        .map_err(|e| {
+            format!(
+                "Backend '{}' genesis hash check failed: {}",
+                backend.label, e
+            )
+        })?;
+    if genesis_hash != expected {
+        return Err(format!(
+            "Backend '{}' is on another cluster: genesis hash {}, expected {}",
+            backend.label, genesis_hash, expected
+        ));
+    }
+    Ok(())
+}
+
+/// Validate `config_path` and, only if it is fully valid (and reachable when
+/// probing is enabled), atomically swap it in. On failure the running state is
+/// untouched. In-flight requests and open WebSocket sessions on removed backends
+/// finish on the state they started with.
+pub async fn reload_config(
+    config_path: &str,
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    router_state: &ArcSwap<RouterState>,
+    health_state: &Arc<HealthState>,
+    status: &ReloadStatus,
+) -> Result<usize, String> {
+    let _apply = status.apply.lock().await;
+    // The parse error is not `Send`, so it must not be held across the swap
+    let parsed = load_config(config_path).map_err(|e| e.to_string());
+    let result = match parsed {
+        Ok(config) => swap_config(config, client, router_state, health_state).await,
+        Err(e) => Err(e),
+    };
+    finish(result, status, ConfigSource::File)
+}
+
+/// Like [`reload_config`], for a full config document pushed over the admin API
+/// instead of read from disk. The config file is not rewritten, so a later
+/// SIGHUP or restart goes back to its contents.
+pub async fn push_config(
+    contents: &str,
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    router_state: &ArcSwap<RouterState>,
+    health_state: &Arc<HealthState>,
+    status: &ReloadStatus,
+) -> Result<usize, String> {
+    let _apply = status.apply.lock().await;
+    let parsed = parse_config(contents).map_err(|e| e.to_string());
+    let result = match parsed {
+        Ok(config) => swap_config(config, client, router_state, health_state).await,
+        Err(e) => Err(e),
+    };
+    finish(result, status, ConfigSource::Admin)
+}
+
+fn finish(
+    result: Result<Config, String>,
+    status: &ReloadStatus,
+    source: ConfigSource,
+) -> Result<usize, String> {
+    status.record_attempt(result.as_ref().err().map(String::as_str));
+    match result {
+        Ok(config) => {
+            info!(
+                "Configuration reloaded from {}: {} backends (hash={})",
+                source.as_str(),
+                config.backends.len(),
+                config.hash
+            );
+            counter!("config_reloads_total", "result" => "success", "source" => source.as_str())
+                .increment(1);
+            gauge!("config_last_reload_successful").set(1.0);
+            let backends = config.backends.len();
+            status.set_active_from(Arc::new(config), source);
+            Ok(backends)
+        }
+        Err(e) => {
+            error!(
+                "Configuration reload from {} rejected, keeping previous config: {}",
+                source.as_str(),
+                e
+            );
+            counter!("config_reloads_total", "result" => "failure", "source" => source.as_str())
+                .increment(1);
+            gauge!("config_last_reload_successful").set(0.0);
+            Err(e)
+        }
+    }
+}
+
+async fn swap_config(
+    config: Config,
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    router_state: &ArcSwap<RouterState>,
+    health_state: &Arc<HealthState>,
+) -> Result<Config, String> {
+    let current = router_state.load_full();
+
+    if config.reload.probe_backends {
+        probe_backends(client, &config, &current).await?;
+    }
+
+    let removed: Vec<&str> = current
+        .backends
+        .iter()
+        .map(|b| b.config.label.as_str())
+        .filter(|label| !config.backends.iter().any(|b| b.label == *label))
+        .collect();
+    if !removed.is_empty() {
+        warn!(
+            "Draining removed backends (no new traffic, in-flight requests complete): {}",
+            removed.join(", ")
+        );
+    }
+
+    redact::set_custom_patterns(&config.logging.redact_patterns)?;
+    log_format::set_log_format(config.logging.log_format);
+    let new_state = router_state_from_config(&config, health_state.clone());
+    router_state.store(Arc::new(new_state));
+    Ok(config)
+}
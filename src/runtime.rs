@@ -0,0 +1,35 @@
+use std::io;
+
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::config::RuntimeConfig;
+
+/// The multi-threaded runtime the router runs on.
+pub fn build(config: &RuntimeConfig) -> io::Result<Runtime> {
+    let mut builder = Builder::new_multi_thread();
+    builder
+        .enable_all()
+        .max_blocking_threads(config.max_blocking_threads);
+    if config.worker_threads > 0 {
+        builder.worker_threads(config.worker_threads);
+    }
+    builder.build()
+}
+
+/// Where the periodic background loops are spawned. With
+/// `isolate_background`, a single-threaded runtime driven by a thread of its
+/// own, which runs until the process exits; otherwise the current runtime.
+pub fn background(config: &RuntimeConfig) -> io::Result<Handle> {
+    if !config.isolate_background {
+        return Ok(Handle::current());
+    }
+    let runtime = Builder::new_current_thread()
+        .enable_all()
+        .max_blocking_threads(config.max_blocking_threads)
+        .build()?;
+    let handle = runtime.handle().clone();
+    std::thread::Builder::new()
+        .name("router-background".to_string())
+        .spawn(move || runtime.block_on(std::future::pending::<()>()))?;
+    Ok(handle)
+}
@@ -0,0 +1,222 @@
+use std::{future::Future, net::SocketAddr, sync::Arc};
+
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use futures_util::future::join_all;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+use tower_http::cors::CorsLayer;
+use tracing::{error, info};
+
+use crate::{
+    admin,
+    handlers::{
+        assign_request_id, decompress_request, discovery_endpoint, extract_rpc_method,
+        filter_response_fields, health_endpoint, log_requests, poll_endpoint, poll_subscribe,
+        poll_unsubscribe, proxy, readyz_endpoint, split_batch, trace_requests, track_metrics,
+        track_usage, unwrap_single_batch, usage_endpoint, ws_proxy,
+    },
+    health::health_check_loop,
+    metrics_export::HISTOGRAM_BUCKETS,
+    state::AppState,
+    tls::serve_tls,
+};
+
+/// A background task or server that can be stopped on its own. Dropping the
+/// handle leaves the task running.
+pub struct TaskHandle {
+    name: &'static str,
+    token: CancellationToken,
+    join: JoinHandle<Result<(), String>>,
+}
+
+impl TaskHandle {
+    /// Run `task`, which is given the handle's token and should wind down once
+    /// it is cancelled.
+    pub fn spawn<F, Fut>(name: &'static str, task: F) -> Self
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let token = CancellationToken::new();
+        let join = tokio::spawn(task(token.clone()));
+        Self { name, token, join }
+    }
+
+    /// Run a loop that knows nothing of cancellation; shutting down drops it at
+    /// its next `.await`.
+    pub fn spawn_loop<Fut>(name: &'static str, task: Fut) -> Self
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self::spawn(name, |token| async move {
+            tokio::select! {
+                _ = task => {}
+                _ = token.cancelled() => {}
+            }
+            Ok(())
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Cancelling this token is the same as [`shutdown`](Self::shutdown) without waiting.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.join.is_finished()
+    }
+
+    /// Wait for the task to end by itself (or through its token).
+    pub async fn wait(self) -> Result<(), String> {
+        match self.join.await {
+            Ok(result) => result,
+            Err(e) => Err(format!("{} task failed: {}", self.name, e)),
+        }
+    }
+
+    /// Stop the task and wait until it has.
+    pub async fn shutdown(self) -> Result<(), String> {
+        self.token.cancel();
+        self.wait().await
+    }
+}
+
+/// Install the global Prometheus recorder. The recorder cannot be removed again;
+/// serve its handle with [`operations_router`].
+pub fn install_metrics_recorder() -> Result<PrometheusHandle, String> {
+    PrometheusBuilder::new()
+        .set_buckets(HISTOGRAM_BUCKETS)
+        .map_err(|e| format!("failed to set histogram buckets: {}", e))?
+        .install_recorder()
+        .map_err(|e| format!("failed to install Prometheus recorder: {}", e))
+}
+
+/// JSON-RPC over HTTP, WebSocket upgrades on `/`, and the public endpoints.
+pub fn http_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(ws_proxy).post(proxy))
+        .route("/*path", post(proxy))
+        .route("/health", get(health_endpoint))
+        .route("/readyz", get(readyz_endpoint))
+        .route("/v1/rpc-discovery", get(discovery_endpoint))
+        .route("/v1/usage", get(usage_endpoint))
+        .route("/v1/poll", post(poll_subscribe))
+        .route("/v1/poll/:id", get(poll_endpoint).delete(poll_unsubscribe))
+        .with_state(state.clone())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            filter_response_fields,
+        ))
+        .layer(middleware::from_fn_with_state(state.clone(), track_usage))
+        .layer(middleware::from_fn(track_metrics))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            trace_requests,
+        ))
+        .layer(middleware::from_fn(log_requests))
+        .layer(middleware::from_fn(extract_rpc_method))
+        .layer(middleware::from_fn_with_state(state.clone(), split_batch))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            unwrap_single_batch,
+        ))
+        .layer(middleware::from_fn_with_state(state, decompress_request))
+        .layer(middleware::from_fn(assign_request_id))
+        .layer(CorsLayer::permissive())
+}
+
+/// WebSocket only, for the Solana convention of WS port = HTTP port + 1.
+pub fn ws_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(ws_proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(log_requests))
+        .layer(middleware::from_fn(assign_request_id))
+        .layer(CorsLayer::permissive())
+}
+
+/// `/metrics` and the admin API, kept off the public listeners. `/metrics` is
+/// only served with the Prometheus exporter's `metrics` handle.
+pub fn operations_router(state: Arc<AppState>, metrics: Option<PrometheusHandle>) -> Router {
+    let router = match metrics {
+        Some(metrics) => Router::new().route(
+            "/metrics",
+            get(move || std::future::ready(metrics.render())),
+        ),
+        None => Router::new(),
+    };
+    router
+        .nest("/admin", admin::router(state.clone()))
+        .with_state(state)
+        .layer(middleware::from_fn(log_requests))
+        .layer(middleware::from_fn(assign_request_id))
+}
+
+/// Serve `app` on every listener. Shutting down stops accepting connections and
+/// waits for in-flight requests; an error on one listener stops the others.
+pub fn start_listeners(name: &'static str, listeners: Vec<TcpListener>, app: Router) -> TaskHandle {
+    TaskHandle::spawn(name, move |token| async move {
+        let servers = listeners.into_iter().map(|listener| {
+            let app = app.clone();
+            let token = token.clone();
+            async move {
+                let result = axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(token.clone().cancelled_owned())
+                .await;
+                if let Err(e) = &result {
+                    error!("{} server error: {}", name, e);
+                    token.cancel();
+                }
+                result.map_err(|e| format!("{} server error: {}", name, e))
+            }
+        });
+        join_all(servers)
+            .await
+            .into_iter()
+            .collect::<Result<(), _>>()?;
+        info!("{} server stopped", name);
+        Ok(())
+    })
+}
+
+/// Like [`start_listeners`], terminating TLS with `acceptor`.
+pub fn start_tls_listeners(
+    name: &'static str,
+    listeners: Vec<TcpListener>,
+    app: Router,
+    acceptor: TlsAcceptor,
+) -> TaskHandle {
+    TaskHandle::spawn(name, move |token| async move {
+        let servers = listeners
+            .into_iter()
+            .map(|listener| serve_tls(listener, app.clone(), acceptor.clone(), token.clone()));
+        join_all(servers).await;
+        info!("{} server stopped", name);
+        Ok(())
+    })
+}
+
+/// The health check loop over the backends of `state`.
+pub fn start_health_checks(state: &AppState) -> TaskHandle {
+    TaskHandle::spawn_loop(
+        "health check",
+        health_check_loop(
+            state.client.clone(),
+            state.backend_clients.clone(),
+            state.state.clone(),
+        ),
+    )
+}
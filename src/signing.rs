@@ -0,0 +1,128 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::config::{Backend, SigningConfig};
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    /// Signature or timestamp header absent or not valid UTF-8
+    Missing,
+    /// Timestamp is not a unix timestamp in seconds
+    InvalidTimestamp,
+    /// Timestamp is further from the verifier's clock than the allowed skew
+    Expired,
+    /// The HMAC does not match
+    Mismatch,
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// UTC `(year, month, day)` of a unix timestamp.
+pub fn utc_date(unix: u64) -> (i64, u32, u32) {
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = (unix / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Hex HMAC-SHA256 over `"{timestamp}.{body}"`.
+pub fn sign(key: &str, timestamp: u64, body: &[u8]) -> String {
+    hex::encode(mac(key, timestamp, body).finalize().into_bytes())
+}
+
+fn mac(key: &str, timestamp: u64, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Add the timestamp, signature and (if configured) key id headers for `body`.
+pub fn apply_signature(config: &SigningConfig, headers: &mut HeaderMap, body: &[u8], now: u64) {
+    let Some(key) = config.key.as_deref() else {
+        return;
+    };
+    let signature = sign(key, now, body);
+
+    let mut insert = |name: &str, value: String| {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            headers.insert(name, value);
+        }
+    };
+    insert(&config.timestamp_header, now.to_string());
+    insert(&config.header, signature);
+    if let Some(key_id) = &config.key_id {
+        insert(&config.key_id_header, key_id.clone());
+    }
+}
+
+/// Add the `headers` configured for `backend`, replacing any sent by the
+/// client. They are marked sensitive, as they usually carry credentials.
+pub fn apply_backend_headers(backend: &Backend, headers: &mut HeaderMap) {
+    for (name, value) in &backend.headers {
+        if let (Ok(name), Ok(mut value)) = (
+            HeaderName::try_from(name.as_str()),
+            HeaderValue::try_from(value.as_str()),
+        ) {
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+    }
+}
+
+/// Reference verifier matching `apply_signature`, for gateways and tests.
+pub fn verify_signature(
+    config: &SigningConfig,
+    headers: &HeaderMap,
+    body: &[u8],
+    now: u64,
+) -> Result<(), SignatureError> {
+    let key = config.key.as_deref().ok_or(SignatureError::Missing)?;
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .ok_or(SignatureError::Missing)
+    };
+    let timestamp: u64 = header(&config.timestamp_header)?
+        .parse()
+        .map_err(|_| SignatureError::InvalidTimestamp)?;
+    let signature = hex::decode(header(&config.header)?).map_err(|_| SignatureError::Mismatch)?;
+
+    if now.abs_diff(timestamp) > config.max_clock_skew_secs {
+        return Err(SignatureError::Expired);
+    }
+
+    mac(key, timestamp, body)
+        .verify_slice(&signature)
+        .map_err(|_| SignatureError::Mismatch)
+}
+
+/// Seconds between a backend's HTTP `Date` header and `now`, if the header parses.
+pub fn clock_skew(headers: &HeaderMap, now: SystemTime) -> Option<u64> {
+    let date = headers.get("date")?.to_str().ok()?;
+    let remote = httpdate::parse_http_date(date).ok()?;
+    let skew = match now.duration_since(remote) {
+        Ok(behind) => behind,
+        Err(e) => e.duration(),
+    };
+    Some(skew.as_secs())
+}
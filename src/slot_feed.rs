@@ -0,0 +1,211 @@
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use hyper::Request;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use metrics::{counter, gauge};
+use tokio::{
+    sync::watch,
+    time::{sleep, timeout, Duration},
+};
+use tracing::{debug, info};
+
+use crate::{
+    config::{Backend, Commitment},
+    redact::redact,
+    signing::{apply_backend_headers, apply_signature, unix_now},
+    state::{AppState, RouterState, RuntimeBackend},
+};
+
+/// How often the loop checks whether local slot subscriptions were enabled.
+const DISABLED_POLL_SECS: u64 = 1;
+
+/// Latest slot at each commitment according to the feed's source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SlotView {
+    pub processed: Option<u64>,
+    pub confirmed: Option<u64>,
+    pub finalized: Option<u64>,
+}
+
+impl SlotView {
+    pub fn at(&self, commitment: Commitment) -> Option<u64> {
+        match commitment {
+            Commitment::Processed => self.processed,
+            Commitment::Confirmed => self.confirmed,
+            Commitment::Finalized => self.finalized,
+        }
+    }
+
+    fn slot_mut(&mut self, commitment: Commitment) -> &mut Option<u64> {
+        match commitment {
+            Commitment::Processed => &mut self.processed,
+            Commitment::Confirmed => &mut self.confirmed,
+            Commitment::Finalized => &mut self.finalized,
+        }
+    }
+}
+
+/// One upstream slot source fanned out to every local `slotSubscribe` and
+/// `rootSubscribe` subscriber.
+pub struct SlotFeed {
+    view: watch::Sender<SlotView>,
+    source: Mutex<Option<String>>,
+}
+
+impl Default for SlotFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SlotFeed {
+    pub fn new() -> Self {
+        Self {
+            view: watch::Sender::new(SlotView::default()),
+            source: Mutex::new(None),
+        }
+    }
+
+    /// Receiver woken whenever any commitment's slot advances.
+    pub fn subscribe(&self) -> watch::Receiver<SlotView> {
+        self.view.subscribe()
+    }
+
+    pub fn current(&self) -> SlotView {
+        *self.view.borrow()
+    }
+
+    /// Record `slot` at `commitment`. Slots never go backwards, so switching to
+    /// a source that is slightly behind does not replay old slots to clients.
+    pub fn record(&self, commitment: Commitment, slot: u64) {
+        self.view.send_if_modified(|view| {
+            let current = view.slot_mut(commitment);
+            if current.is_some_and(|c| c >= slot) {
+                return false;
+            }
+            *current = Some(slot);
+            true
+        });
+    }
+
+    /// Label of the backend the feed last polled.
+    pub fn source(&self) -> Option<String> {
+        self.source
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn set_source(&self, label: &str) {
+        let mut source = self.source.lock().unwrap_or_else(|e| e.into_inner());
+        if source.as_deref() != Some(label) {
+            info!("Slot feed now following backend {}", label);
+            *source = Some(label.to_string());
+        }
+    }
+}
+
+/// Backend in rotation furthest ahead according to the last health checks; the
+/// first one when none has reported a slot yet.
+pub fn slot_source(router_state: &RouterState) -> Option<&RuntimeBackend> {
+    let healthy = || router_state.backends.iter().filter(|b| b.in_rotation());
+    let last_slot = |b: &RuntimeBackend| {
+        router_state
+            .health_state
+            .get_status(&b.config.label)
+            .and_then(|s| s.last_slot)
+    };
+    healthy()
+        .filter(|b| last_slot(b).is_some())
+        .max_by_key(|b| last_slot(b))
+        .or_else(|| healthy().next())
+}
+
+/// `getSlot` at `commitment` from `backend`.
+pub async fn fetch_slot(
+    client: &Client<HttpsConnector<HttpConnector>, Body>,
+    backend: &Backend,
+    commitment: Commitment,
+    timeout_after: Duration,
+) -> Result<u64, String> {
+    let body = serde_json::to_vec(&serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getSlot",
+        "params": [{ "commitment": commitment.as_str() }]
+    }))
+    .map_err(|e| e.to_string())?;
+
+    let mut req = Request::post(&backend.url)
+        .header("content-type", "application/json")
+        .body(Body::empty())
+        .map_err(|e| e.to_string())?;
+    apply_backend_headers(backend, req.headers_mut());
+    if let Some(signing) = &backend.signing {
+        apply_signature(signing, req.headers_mut(), &body, unix_now());
+    }
+    *req.body_mut() = Body::from(body);
+
+    let response = timeout(timeout_after, client.request(req))
+        .await
+        .map_err(|_| format!("getSlot timed out after {}ms", timeout_after.as_millis()))?
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("getSlot returned status: {}", response.status()));
+    }
+    let bytes = http_body_util::BodyExt::collect(response.into_body())
+        .await
+        .map_err(|e| e.to_string())?
+        .to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    json.get("result")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| "getSlot response missing numeric 'result'".to_string())
+}
+
+/// Poll the slot source every `websocket.slot_poll_ms` while local slot
+/// subscriptions are enabled, at all three commitments.
+pub async fn slot_feed_loop(state: Arc<AppState>) {
+    loop {
+        let router_state = state.state.load_full();
+        let config = &router_state.websocket;
+        if !config.local_slot_subscriptions {
+            sleep(Duration::from_secs(DISABLED_POLL_SECS)).await;
+            continue;
+        }
+        let poll = Duration::from_millis(config.slot_poll_ms);
+
+        if let Some(backend) = slot_source(&router_state) {
+            state.slot_feed.set_source(&backend.config.label);
+            let fetches = Commitment::ALL.map(|commitment| {
+                let client = state.client_for(&backend.config, false);
+                async move {
+                    let result = fetch_slot(&client, &backend.config, commitment, poll).await;
+                    (commitment, result)
+                }
+            });
+            for (commitment, result) in futures_util::future::join_all(fetches).await {
+                match result {
+                    Ok(slot) => {
+                        state.slot_feed.record(commitment, slot);
+                        gauge!("slot_feed_slot", "commitment" => commitment.as_str())
+                            .set(slot as f64);
+                    }
+                    Err(e) => {
+                        debug!(
+                            "Slot feed poll of {} ({}) failed: {}",
+                            backend.config.label,
+                            commitment.as_str(),
+                            redact(&e)
+                        );
+                        counter!("slot_feed_errors_total", "backend" => backend.config.label.clone())
+                            .increment(1);
+                    }
+                }
+            }
+        }
+        sleep(poll).await;
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use hyper::body::{Body as HttpBody, Frame, SizeHint};
+
+/// Most bytes of a request body read to find its `method`. A body whose
+/// method comes later is buffered whole and parsed.
+pub const SNIFF_LIMIT: usize = 16 * 1024;
+
+/// What the start of a request body says about its top-level `method`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sniff<'a> {
+    /// A method without escapes
+    Method(&'a str),
+    /// No usable method: not an object, no `method` key before the object
+    /// ends, a method that is not a string, or not JSON
+    NoMethod,
+    /// The bytes end before the answer
+    Incomplete,
+    /// A key or the method has escapes; left to a full parse
+    Unsure,
+}
+
+fn is_ws(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+fn skip_ws(b: &[u8], mut i: usize) -> usize {
+    while i < b.len() && is_ws(b[i]) {
+        i += 1;
+    }
+    i
+}
+
+/// End of the string starting at `b[start]` (past the closing quote) and
+/// whether it has escapes.
+fn scan_string(b: &[u8], start: usize) -> Result<(usize, bool), Sniff<'static>> {
+    let mut i = start + 1;
+    let mut escaped = false;
+    while i < b.len() {
+        match b[i] {
+            b'\\' => {
+                escaped = true;
+                i += 2;
+            }
+            b'"' => return Ok((i + 1, escaped)),
+            _ => i += 1,
+        }
+    }
+    Err(Sniff::Incomplete)
+}
+
+/// End of the value starting at `b[i]`. Nested objects and arrays are only
+/// bracket-counted, not validated.
+fn skip_value(b: &[u8], mut i: usize) -> Result<usize, Sniff<'static>> {
+    match b.get(i) {
+        None => Err(Sniff::Incomplete),
+        Some(b'"') => scan_string(b, i).map(|(end, _)| end),
+        Some(b'{' | b'[') => {
+            let mut depth = 0usize;
+            while i < b.len() {
+                match b[i] {
+                    b'"' => {
+                        i = scan_string(b, i)?.0;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            Err(Sniff::Incomplete)
+        }
+        // A number or literal runs up to the next delimiter
+        Some(_) => {
+            while i < b.len() && !matches!(b[i], b',' | b'}' | b']') && !is_ws(b[i]) {
+                i += 1;
+            }
+            if i == b.len() {
+                Err(Sniff::Incomplete)
+            } else {
+                Ok(i)
+            }
+        }
+    }
+}
+
+/// Find the top-level `method` of a JSON-RPC request from the start of its
+/// body, skipping any keys before it without parsing them.
+pub fn sniff_method(prefix: &[u8]) -> Sniff<'_> {
+    let b = prefix;
+    let mut i = skip_ws(b, 0);
+    match b.get(i) {
+        None => return Sniff::Incomplete,
+        Some(b'{') => i += 1,
+        Some(_) => return Sniff::NoMethod,
+    }
+    loop {
+        i = skip_ws(b, i);
+        match b.get(i) {
+            None => return Sniff::Incomplete,
+            Some(b'"') => {}
+            Some(_) => return Sniff::NoMethod,
+        }
+        let (end, escaped) = match scan_string(b, i) {
+            Ok(key) => key,
+            Err(sniff) => return sniff,
+        };
+        if escaped {
+            return Sniff::Unsure;
+        }
+        let key = &b[i + 1..end - 1];
+        i = skip_ws(b, end);
+        match b.get(i) {
+            None => return Sniff::Incomplete,
+            Some(b':') => i = skip_ws(b, i + 1),
+            Some(_) => return Sniff::NoMethod,
+        }
+
+        if key == b"method" {
+            return match b.get(i) {
+                None => Sniff::Incomplete,
+                Some(b'"') => match scan_string(b, i) {
+                    Ok((_, true)) => Sniff::Unsure,
+                    Ok((end, false)) => std::str::from_utf8(&b[i + 1..end - 1])
+                        .map_or(Sniff::NoMethod, Sniff::Method),
+                    Err(sniff) => sniff,
+                },
+                Some(_) => Sniff::NoMethod,
+            };
+        }
+
+        i = match skip_value(b, i) {
+            Ok(end) => skip_ws(b, end),
+            Err(sniff) => return sniff,
+        };
+        match b.get(i) {
+            None => return Sniff::Incomplete,
+            Some(b',') => i += 1,
+            Some(_) => return Sniff::NoMethod,
+        }
+    }
+}
+
+/// A body whose first bytes were already read: yields them, then the rest as
+/// it arrives. The size hint covers both, so request byte counts stay exact.
+pub struct PrefixedBody<B> {
+    prefix: Option<Bytes>,
+    rest: B,
+}
+
+impl<B> PrefixedBody<B> {
+    pub fn new(prefix: Bytes, rest: B) -> Self {
+        Self {
+            prefix: Some(prefix).filter(|p| !p.is_empty()),
+            rest,
+        }
+    }
+}
+
+impl<B> HttpBody for PrefixedBody<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        if let Some(prefix) = self.prefix.take() {
+            return Poll::Ready(Some(Ok(Frame::data(prefix))));
+        }
+        Pin::new(&mut self.rest).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.prefix.is_none() && self.rest.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let read = self.prefix.as_ref().map_or(0, |p| p.len() as u64);
+        let rest = self.rest.size_hint();
+        let mut hint = SizeHint::new();
+        hint.set_lower(rest.lower() + read);
+        if let Some(upper) = rest.upper() {
+            hint.set_upper(upper + read);
+        }
+        hint
+    }
+}
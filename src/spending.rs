@@ -0,0 +1,278 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use axum::body::Body;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use metrics::counter;
+use redis::{aio::ConnectionManager, Client as RedisClient};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::time::interval;
+use tracing::{info, warn};
+
+use crate::{
+    alerts::post_json,
+    config::SpendingCapsConfig,
+    keystore::KeyInfo,
+    methods::method_info,
+    redact::{key_fingerprint, redact},
+    signing::unix_now,
+};
+
+/// Redis counter of the cost units charged to one key: `spend:<api_key>`.
+/// It never expires; `rpc-admin update --reset-spend` clears it.
+pub const REDIS_KEY_PREFIX: &str = "spend";
+
+pub fn redis_key(key: &str) -> String {
+    format!("{}:{}", REDIS_KEY_PREFIX, key)
+}
+
+/// Cost units of a request (see `methods`): its method's cost, or for a batch
+/// the sum over its requests. Unknown methods cost 1.
+pub fn request_cost(rpc_method: Option<&str>, body: &[u8]) -> u64 {
+    let cost = |method: Option<&str>| {
+        method
+            .and_then(method_info)
+            .map_or(1, |info| u64::from(info.cost))
+    };
+    if rpc_method.is_some() {
+        return cost(rpc_method);
+    }
+    match serde_json::from_slice::<Vec<Value>>(body) {
+        Ok(batch) if !batch.is_empty() => batch
+            .iter()
+            .map(|request| cost(request.get("method").and_then(Value::as_str)))
+            .sum(),
+        _ => 1,
+    }
+}
+
+#[async_trait]
+pub trait SpendStore: Send + Sync {
+    /// Add units to each key's counter, returning the new totals in order.
+    async fn add(&self, charges: &[(String, u64)]) -> Result<Vec<u64>, String>;
+}
+
+/// Spend counters on `redis_url`, shared by every router instance.
+pub struct RedisSpendStore {
+    conn: ConnectionManager,
+}
+
+impl RedisSpendStore {
+    pub async fn new(redis_url: &str) -> Result<Self, String> {
+        let client = RedisClient::open(redis_url).map_err(|e| e.to_string())?;
+        let conn = client
+            .get_connection_manager()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Self { conn })
+    }
+}
+
+#[async_trait]
+impl SpendStore for RedisSpendStore {
+    async fn add(&self, charges: &[(String, u64)]) -> Result<Vec<u64>, String> {
+        let mut pipe = redis::pipe();
+        for (key, units) in charges {
+            pipe.incr(redis_key(key), *units);
+        }
+        let mut conn = self.conn.clone();
+        pipe.query_async(&mut conn).await.map_err(|e| e.to_string())
+    }
+}
+
+/// Webhook payload sent when a key reaches its spend cap.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpendCapEvent {
+    /// Always `spend_cap_reached`
+    pub event: &'static str,
+    /// Fingerprint of the key (see `redact::key_fingerprint`)
+    pub key: String,
+    pub owner: String,
+    pub cap: f64,
+    pub spent: f64,
+    /// Requests per second the key is now held to, per router instance
+    pub throttled_rate_limit: u64,
+    pub at: u64,
+}
+
+#[derive(Debug)]
+struct KeySpend {
+    owner: String,
+    cap: f64,
+    price: f64,
+    /// Units in the store as of the last flush
+    stored: u64,
+    /// Units charged since, not yet written
+    pending: u64,
+    /// Start and request count of the current one-second throttling window
+    window: (Instant, u64),
+}
+
+impl KeySpend {
+    fn spent(&self) -> f64 {
+        (self.stored + self.pending) as f64 * self.price
+    }
+}
+
+/// Spend of keys with a `spend_cap`, charged by cost units at their tier's
+/// price. Keys that reach their cap are throttled to
+/// `spending_caps.throttled_rate_limit` instead of being cut off, so prepaid
+/// customers degrade rather than fail.
+pub struct SpendTracker {
+    config: SpendingCapsConfig,
+    store: Arc<dyn SpendStore>,
+    keys: Mutex<HashMap<String, KeySpend>>,
+}
+
+impl SpendTracker {
+    pub fn new(config: SpendingCapsConfig, store: Arc<dyn SpendStore>) -> Self {
+        Self {
+            config,
+            store,
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &SpendingCapsConfig {
+        &self.config
+    }
+
+    /// Price of one cost unit for keys of `tier`.
+    pub fn price(&self, tier: Option<&str>) -> f64 {
+        tier.and_then(|t| self.config.tier_prices.get(t))
+            .copied()
+            .unwrap_or(self.config.default_price)
+    }
+
+    /// Whether a request of `key` may go ahead: always while its spend is
+    /// under its cap (and for keys without one), else up to
+    /// `throttled_rate_limit` per second.
+    pub fn admit(&self, key: &str, info: &KeyInfo, now: Instant) -> bool {
+        let Some(cap) = info.spend_cap else {
+            return true;
+        };
+        let price = self.price(info.tier.as_deref());
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let spend = keys.entry(key.to_string()).or_insert_with(|| KeySpend {
+            owner: String::new(),
+            cap,
+            price,
+            stored: 0,
+            pending: 0,
+            window: (now, 0),
+        });
+        // Follow changes to the key's cap, tier or owner
+        spend.owner.clone_from(&info.owner);
+        spend.cap = cap;
+        spend.price = price;
+        if spend.spent() < cap {
+            return true;
+        }
+        if now.duration_since(spend.window.0) >= Duration::from_secs(1) {
+            spend.window = (now, 0);
+        }
+        spend.window.1 += 1;
+        spend.window.1 <= self.config.throttled_rate_limit
+    }
+
+    /// Charge `units` to a key admitted by [`admit`](Self::admit).
+    pub fn charge(&self, key: &str, units: u64) {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(spend) = keys.get_mut(key) {
+            spend.pending += units;
+        }
+    }
+
+    /// Spend of `key` as this instance knows it; `None` for keys it has not
+    /// seen with a cap.
+    pub fn spent(&self, key: &str) -> Option<f64> {
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        keys.get(key).map(KeySpend::spent)
+    }
+
+    /// Write pending charges to the store and learn each key's total across
+    /// instances. Returns an event for each key whose charges took it to its
+    /// cap: the counter is shared, so exactly one instance sees each crossing.
+    /// Charges that fail to write are kept for the next flush.
+    pub async fn flush(&self, now: u64) -> Result<Vec<SpendCapEvent>, String> {
+        let charges: Vec<(String, u64)> = {
+            let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+            keys.iter()
+                .filter(|(_, spend)| spend.pending > 0)
+                .map(|(key, spend)| (key.clone(), spend.pending))
+                .collect()
+        };
+        if charges.is_empty() {
+            return Ok(Vec::new());
+        }
+        let totals = self.store.add(&charges).await?;
+
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let mut events = Vec::new();
+        for ((key, units), total) in charges.iter().zip(totals) {
+            let Some(spend) = keys.get_mut(key) else {
+                continue;
+            };
+            spend.pending -= units;
+            spend.stored = total;
+            let before = total.saturating_sub(*units) as f64 * spend.price;
+            let after = total as f64 * spend.price;
+            if before < spend.cap && after >= spend.cap {
+                events.push(SpendCapEvent {
+                    event: "spend_cap_reached",
+                    key: key_fingerprint(key),
+                    owner: spend.owner.clone(),
+                    cap: spend.cap,
+                    spent: after,
+                    throttled_rate_limit: self.config.throttled_rate_limit,
+                    at: now,
+                });
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// Flush charges every `spending_caps.flush_secs` and notify the webhook of
+/// keys that reached their cap.
+pub async fn spending_loop(
+    tracker: Arc<SpendTracker>,
+    client: Client<HttpsConnector<HttpConnector>, Body>,
+) {
+    let mut ticker = interval(Duration::from_secs(tracker.config().flush_secs));
+    loop {
+        ticker.tick().await;
+        let events = match tracker.flush(unix_now()).await {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Failed to write key spend: {}", redact(&e));
+                counter!("spend_write_failures_total").increment(1);
+                continue;
+            }
+        };
+        for event in events {
+            info!(
+                "API key reached its spend cap, throttling (key={}, owner={}, cap={})",
+                event.key, event.owner, event.cap
+            );
+            counter!("spend_cap_reached_total", "owner" => event.owner.clone()).increment(1);
+            let url = tracker.config().webhook_url.clone();
+            if url.is_empty() {
+                continue;
+            }
+            let client = client.clone();
+            tokio::spawn(async move {
+                if let Err(e) = post_json(&client, &url, &event).await {
+                    warn!("Failed to deliver spend cap webhook: {}", redact(&e));
+                    counter!("spend_cap_webhook_failures_total").increment(1);
+                }
+            });
+        }
+    }
+}
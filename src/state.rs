@@ -1,65 +1,434 @@
 use std::{
     collections::HashMap,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use arc_swap::ArcSwap;
-use axum::body::Body;
+use axum::{
+    body::Body,
+    http::{
+        uri::{Authority, PathAndQuery, Scheme},
+        HeaderValue, Uri,
+    },
+};
 use hyper_tls::HttpsConnector;
-use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
 use rand::Rng;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::timeout,
+};
 use tracing::{debug, info};
 
 use crate::{
-    config::{Backend, HealthCheckConfig},
+    alerts::AlertEngine,
+    archive::is_deep,
+    auto_route::AutoWeights,
+    balancer::{self, CurrentWeight},
+    blockhash::BlockhashCache,
+    broadcast::RecentSignatures,
+    browser::OriginLimiter,
+    cache::ResponseCache,
+    circuit::CircuitBreaker,
+    coalesce::Coalescer,
+    concurrency::ConcurrencyLimiter,
+    config::{
+        AdminConfig, AlertRule, ArchiveRoutingConfig, Backend, BlockhashCheckConfig,
+        BroadcastConfig, BrowserKeyConfig, CacheConfig, CircuitBreakerConfig, CoalescingConfig,
+        ConsistencyConfig, ErrorTemplatesConfig, GenesisCheckConfig, HealthCheckConfig,
+        HedgingConfig, IntegrityConfig, KillSwitchConfig, LatencyHeatmapConfig, OffloadConfig,
+        PollBridgeConfig, PoolsConfig, PreflightPolicy, ProbesConfig, ProxyConfig, ReadinessConfig,
+        RetryConfig, RoutingConfig, RoutingMode, Subsystem, TrafficReportConfig, WebSocketConfig,
+    },
+    consistency::ConsistencyState,
+    dead_letter::DeadLetterStore,
+    genesis::Identity,
     health::HealthState,
+    heatmap::LatencyHeatmap,
+    hedging::HedgeBudget,
+    journal::JournalStore,
+    key_usage::{KeyUsageBuffer, KeyUsageStore},
     keystore::KeyStore,
+    kill_switch::KillSwitches,
+    ledger::{UsageBuffer, UsageLedger},
+    methods::{idempotency, Idempotency},
+    poll_bridge::PollBridge,
+    protocol::BackendClients,
+    reload::ReloadStatus,
+    signing::unix_now,
+    slot_feed::SlotFeed,
+    spending::SpendTracker,
+    stats::RoutingStats,
+    trace::Tracer,
+    traffic::TrafficStats,
+    ttl_tuning::TtlTuner,
+    usage::UsageTracker,
 };
 
+/// A backend URL split into parts once, so the proxy builds each upstream URI
+/// with a single allocation instead of formatting and reparsing a string.
+#[derive(Debug, Clone)]
+pub struct UpstreamTarget {
+    scheme: Scheme,
+    authority: Authority,
+    /// Path prefix without trailing slash ("" for a bare host)
+    base_path: String,
+    /// Query carried by the backend URL itself (e.g. a provider API key)
+    base_query: Option<String>,
+    /// `Host` header value for requests to this backend
+    pub host: HeaderValue,
+}
+
+impl UpstreamTarget {
+    pub fn parse(url: &str) -> Option<Self> {
+        let uri: Uri = url.parse().ok()?;
+        let host = match uri.port_u16() {
+            Some(port) => format!("{}:{}", uri.host()?, port),
+            None => uri.host()?.to_string(),
+        };
+        Some(Self {
+            scheme: uri.scheme()?.clone(),
+            authority: uri.authority()?.clone(),
+            base_path: uri.path().trim_end_matches('/').to_string(),
+            base_query: uri.query().map(str::to_string),
+            host: HeaderValue::try_from(host).ok()?,
+        })
+    }
+
+    /// Upstream URI for a client request. The root path maps to the backend URL
+    /// itself; the client's `api-key` param is dropped, the backend's own query kept.
+    pub fn uri_for(&self, path: &str, query: Option<&str>) -> Result<Uri, axum::http::Error> {
+        self.uri_with_base(&self.base_path, path, query)
+    }
+
+    /// Like [`uri_for`](Self::uri_for), with `base_path` in place of the
+    /// backend URL's path (see `Backend::method_paths`).
+    pub fn uri_with_base(
+        &self,
+        base_path: &str,
+        path: &str,
+        query: Option<&str>,
+    ) -> Result<Uri, axum::http::Error> {
+        let base_path = base_path.trim_end_matches('/');
+        let path = if path == "/" { "" } else { path };
+        let mut path_and_query = String::with_capacity(
+            base_path.len()
+                + path.len()
+                + self.base_query.as_ref().map_or(0, |q| q.len() + 1)
+                + query.map_or(0, |q| q.len() + 1)
+                + 1,
+        );
+        path_and_query.push_str(base_path);
+        path_and_query.push_str(path);
+        if path_and_query.is_empty() {
+            path_and_query.push('/');
+        }
+
+        let client_params = query
+            .into_iter()
+            .flat_map(|q| q.split('&'))
+            .filter(|p| !p.is_empty() && !p.starts_with("api-key="));
+        for (i, param) in self
+            .base_query
+            .as_deref()
+            .into_iter()
+            .chain(client_params)
+            .enumerate()
+        {
+            path_and_query.push(if i == 0 { '?' } else { '&' });
+            path_and_query.push_str(param);
+        }
+
+        Uri::builder()
+            .scheme(self.scheme.clone())
+            .authority(self.authority.clone())
+            .path_and_query(PathAndQuery::try_from(path_and_query)?)
+            .build()
+    }
+}
+
+/// Permits for a backend's concurrent heavy reads, and the requests waiting for one.
+#[derive(Debug)]
+pub struct HeavySlots {
+    permits: Arc<Semaphore>,
+    limit: usize,
+    waiting: AtomicUsize,
+}
+
+impl HeavySlots {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(limit)),
+            limit,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Wait up to `wait` for a slot. On timeout, returns how many other requests
+    /// were still waiting.
+    pub async fn acquire(&self, wait: Duration) -> Result<OwnedSemaphorePermit, usize> {
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        let result = timeout(wait, self.permits.clone().acquire_owned()).await;
+        let others = self.waiting.fetch_sub(1, Ordering::Relaxed) - 1;
+        match result {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => Err(others),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RuntimeBackend {
     pub config: Backend,
+    /// Pre-split `config.url`; `None` if the URL is not absolute
+    pub target: Option<UpstreamTarget>,
+    /// In rotation (healthy or degraded)
     pub healthy: Arc<AtomicBool>,
+    /// In rotation at reduced weight
+    pub degraded: Arc<AtomicBool>,
+    /// Moving average of proxied request latency in microseconds (0 = no samples yet)
+    pub latency_us: Arc<AtomicU64>,
+    /// Permits for concurrent heavy reads; `None` = unlimited
+    pub heavy_slots: Option<Arc<HeavySlots>>,
+    /// Passive breaker tripped by failing live traffic
+    pub circuit: Arc<CircuitBreaker>,
+    /// Quarantine for a backend on another cluster (`genesis_check`)
+    pub identity: Arc<Identity>,
+    /// Score for weighted round-robin selection (see `balancer`)
+    pub current_weight: Arc<CurrentWeight>,
 }
 
+impl RuntimeBackend {
+    pub fn new(config: Backend, healthy: bool) -> Self {
+        Self {
+            target: UpstreamTarget::parse(&config.url),
+            config,
+            healthy: Arc::new(AtomicBool::new(healthy)),
+            degraded: Arc::new(AtomicBool::new(false)),
+            latency_us: Arc::new(AtomicU64::new(0)),
+            heavy_slots: None,
+            circuit: Arc::new(CircuitBreaker::new()),
+            identity: Arc::new(Identity::new()),
+            current_weight: Arc::new(CurrentWeight::new()),
+        }
+    }
+
+    /// Share `circuit` (e.g. the one kept in `HealthState` across reloads).
+    pub fn with_circuit(mut self, circuit: Arc<CircuitBreaker>) -> Self {
+        self.circuit = circuit;
+        self
+    }
+
+    /// Share `identity` (e.g. the one kept in `HealthState` across reloads).
+    pub fn with_identity(mut self, identity: Arc<Identity>) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// Allow at most `max_in_flight` concurrent heavy reads (0 = unlimited).
+    pub fn with_heavy_limit(mut self, max_in_flight: u32) -> Self {
+        self.heavy_slots =
+            (max_in_flight > 0).then(|| Arc::new(HeavySlots::new(max_in_flight as usize)));
+        self
+    }
+
+    /// Fold one upstream response time into the moving average (weight 1/8).
+    pub fn record_latency(&self, elapsed: Duration) {
+        let sample = (elapsed.as_micros() as u64).max(1);
+        let _ = self
+            .latency_us
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |avg| {
+                Some(if avg == 0 {
+                    sample
+                } else {
+                    avg - avg / 8 + sample / 8
+                })
+            });
+    }
+
+    /// Healthy (or degraded), neither draining nor in maintenance, and not held
+    /// out by its circuit breaker or a genesis hash mismatch.
+    pub fn in_rotation(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+            && !self.config.drain
+            && !self.config.maintenance
+            && self.circuit.admits(unix_now())
+            && !self.identity.quarantines(&self.config.url)
+    }
+
+    /// Upstream URI for a client request carrying `method`, on the path the
+    /// backend's `method_paths` gives that method, else under the backend URL.
+    pub fn uri_for(
+        &self,
+        method: Option<&str>,
+        path: &str,
+        query: Option<&str>,
+    ) -> Result<Uri, String> {
+        let target = self.target.as_ref().ok_or("not an absolute URL")?;
+        let uri = match method.and_then(|m| Some((m, self.config.method_paths.get(m)?))) {
+            Some((method, base_path)) => {
+                let base_path = base_path.replace("{method}", method);
+                target.uri_with_base(&base_path, path, query)
+            }
+            None => target.uri_for(path, query),
+        };
+        uri.map_err(|e| e.to_string())
+    }
+
+    /// Whether this backend may receive `method` (false if it is in `exclude_methods`).
+    pub fn accepts(&self, method: Option<&str>) -> bool {
+        method.is_none_or(|m| !self.config.exclude_methods.iter().any(|e| e == m))
+    }
+
+    /// Selection weight, scaled down to `degraded_weight_percent` while degraded.
+    pub fn effective_weight(&self, degraded_weight_percent: u32) -> u32 {
+        if self.degraded.load(Ordering::Relaxed) {
+            (self.config.weight.saturating_mul(degraded_weight_percent) / 100).max(1)
+        } else {
+            self.config.weight
+        }
+    }
+}
+
+/// Backend group for nodes with the `spl-token-owner` account index, preferred
+/// for `getTokenAccountsByOwner`.
+pub const TOKEN_INDEX_GROUP: &str = "token-index";
+
 #[derive(Debug, Clone)]
 pub struct RouterState {
     pub backends: Vec<RuntimeBackend>,
     pub method_routes: HashMap<String, String>,
+    pub tier_routes: HashMap<String, HashMap<String, String>>,
+    pub preflight_policies: HashMap<String, PreflightPolicy>,
+    pub method_idempotency: HashMap<String, Idempotency>,
     pub health_state: Arc<HealthState>,
     pub proxy_timeout_secs: u64,
+    pub retry_transaction_version: Option<u8>,
+    pub max_decompressed_bytes: usize,
+    pub unwrap_single_batches: bool,
+    pub split_batches: bool,
+    pub split_batch_concurrency: usize,
+    /// `proxy.max_in_flight`; 0 = unlimited
+    pub max_in_flight: u32,
+    pub max_buffered_bytes: u64,
     pub health_check_config: HealthCheckConfig,
+    pub browser_keys: BrowserKeyConfig,
+    pub probes: ProbesConfig,
+    pub admin: AdminConfig,
+    pub readiness: ReadinessConfig,
+    pub hedging: HedgingConfig,
+    pub coalescing: CoalescingConfig,
+    pub broadcast: BroadcastConfig,
+    pub routing: RoutingConfig,
+    pub cache: CacheConfig,
+    pub pools: PoolsConfig,
+    pub websocket: WebSocketConfig,
+    pub offload: OffloadConfig,
+    pub blockhash_check: BlockhashCheckConfig,
+    pub poll_bridge: PollBridgeConfig,
+    pub integrity: IntegrityConfig,
+    pub retry: RetryConfig,
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub consistency: ConsistencyConfig,
+    pub genesis_check: GenesisCheckConfig,
+    pub archive_routing: ArchiveRoutingConfig,
+    pub traffic_report: TrafficReportConfig,
+    pub latency_heatmap: LatencyHeatmapConfig,
+    pub error_templates: ErrorTemplatesConfig,
+    pub kill_switches: KillSwitchConfig,
+    pub alerts: Vec<AlertRule>,
 }
 
-#[derive(Clone)]
-pub struct AppState {
-    pub client: Client<HttpsConnector<HttpConnector>, Body>,
-    pub keystore: Arc<dyn KeyStore>,
-    pub state: Arc<ArcSwap<RouterState>>,
+impl Default for RouterState {
+    fn default() -> Self {
+        Self {
+            backends: Vec::new(),
+            method_routes: HashMap::new(),
+            tier_routes: HashMap::new(),
+            preflight_policies: HashMap::new(),
+            method_idempotency: HashMap::new(),
+            health_state: Arc::new(HealthState::new(Vec::new())),
+            proxy_timeout_secs: ProxyConfig::default().timeout_secs,
+            retry_transaction_version: None,
+            max_decompressed_bytes: ProxyConfig::default().max_decompressed_bytes,
+            unwrap_single_batches: false,
+            split_batches: false,
+            split_batch_concurrency: ProxyConfig::default().split_batch_concurrency,
+            max_in_flight: 0,
+            max_buffered_bytes: ProxyConfig::default().max_buffered_bytes,
+            health_check_config: HealthCheckConfig::default(),
+            browser_keys: BrowserKeyConfig::default(),
+            probes: ProbesConfig::default(),
+            admin: AdminConfig::default(),
+            readiness: ReadinessConfig::default(),
+            hedging: HedgingConfig::default(),
+            coalescing: CoalescingConfig::default(),
+            broadcast: BroadcastConfig::default(),
+            routing: RoutingConfig::default(),
+            cache: CacheConfig::default(),
+            pools: PoolsConfig::default(),
+            websocket: WebSocketConfig::default(),
+            offload: OffloadConfig::default(),
+            blockhash_check: BlockhashCheckConfig::default(),
+            poll_bridge: PollBridgeConfig::default(),
+            integrity: IntegrityConfig::default(),
+            retry: RetryConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            consistency: ConsistencyConfig::default(),
+            genesis_check: GenesisCheckConfig::default(),
+            archive_routing: ArchiveRoutingConfig::default(),
+            traffic_report: TrafficReportConfig::default(),
+            latency_heatmap: LatencyHeatmapConfig::default(),
+            error_templates: ErrorTemplatesConfig::default(),
+            kill_switches: KillSwitchConfig::default(),
+            alerts: Vec::new(),
+        }
+    }
 }
 
-impl AppState {
-    pub fn select_backend(&self, rpc_method: Option<&str>) -> Option<(String, String)> {
-        let state = self.state.load();
+impl RouterState {
+    /// Pick a backend for `rpc_method`: its method route if that backend is in
+    /// rotation, otherwise weighted round-robin among healthy backends that
+    /// accept it (the fastest of them with `routing.mode = "least_latency"`).
+    pub fn select_backend(&self, rpc_method: Option<&str>) -> Option<&RuntimeBackend> {
+        self.select_backend_routed(rpc_method, None)
+    }
 
+    /// Like [`select_backend`](Self::select_backend), with `key_route` (the
+    /// calling key's own or tier route for `rpc_method`) taking precedence over
+    /// `method_routes`.
+    pub fn select_backend_routed(
+        &self,
+        rpc_method: Option<&str>,
+        key_route: Option<&str>,
+    ) -> Option<&RuntimeBackend> {
         // Check method-specific routing first
         if let Some(method) = rpc_method {
-            if let Some(backend_label) = state.method_routes.get(method) {
+            let route = key_route.or_else(|| self.method_routes.get(method).map(String::as_str));
+            if let Some(backend_label) = route {
                 // Find the backend by label to check its atomic health
-                if let Some(backend) = state
+                if let Some(backend) = self
                     .backends
                     .iter()
                     .find(|b| b.config.label == *backend_label)
                 {
-                    if backend.healthy.load(Ordering::Relaxed) {
+                    if backend.in_rotation() && backend.accepts(rpc_method) {
                         debug!("Method {} routed to label={}", method, backend_label);
-                        return Some((backend.config.label.clone(), backend.config.url.clone()));
+                        return Some(backend);
                     } else {
                         info!(
-                            "Method {} target label={} is unhealthy, falling back to weighted selection",
+                            "Method {} target label={} is out of rotation, falling back to weighted selection",
                             method, backend_label
                         );
                     }
@@ -67,77 +436,414 @@ impl AppState {
             }
         }
 
-        // Filter out unhealthy backends (lock-free)
-        let healthy_backends: Vec<&RuntimeBackend> = state
-            .backends
-            .iter()
-            .filter(|b| b.healthy.load(Ordering::Relaxed))
-            .collect();
+        // Filter out unhealthy backends (lock-free) and those that never take this method.
+        // Degraded backends count at reduced weight.
+        let degraded_pct = self.health_check_config.degraded_weight_percent;
+        self.select_weighted(self.eligible(rpc_method), |b| {
+            b.effective_weight(degraded_pct)
+        })
+    }
 
-        if healthy_backends.is_empty() {
-            return None; // No healthy backends available
-        }
+    /// Backends in rotation that may receive `rpc_method`. While one in
+    /// [`TOKEN_INDEX_GROUP`] qualifies, `getTokenAccountsByOwner` only goes to
+    /// those: unindexed nodes answer it by scanning every token account.
+    fn eligible<'a>(
+        &'a self,
+        rpc_method: Option<&'a str>,
+    ) -> impl Fn(&RuntimeBackend) -> bool + 'a {
+        let serves = move |b: &RuntimeBackend| b.in_rotation() && b.accepts(rpc_method);
+        let indexed = |b: &RuntimeBackend| b.config.groups.iter().any(|g| g == TOKEN_INDEX_GROUP);
+        let indexed_only = rpc_method == Some("getTokenAccountsByOwner")
+            && self.backends.iter().any(|b| serves(b) && indexed(b));
+        move |b| serves(b) && (!indexed_only || indexed(b))
+    }
 
-        // Calculate total weight of healthy backends
-        let healthy_total_weight: u32 = healthy_backends.iter().map(|b| b.config.weight).sum();
+    /// A backend in rotation for `rpc_method` other than those in `failed`, to
+    /// re-send a request whose response from them could not be used.
+    pub fn select_retry_backend(
+        &self,
+        rpc_method: Option<&str>,
+        failed: &[&str],
+    ) -> Option<&RuntimeBackend> {
+        let degraded_pct = self.health_check_config.degraded_weight_percent;
+        let eligible = self.eligible(rpc_method);
+        self.select_weighted(
+            |b| !failed.contains(&b.config.label.as_str()) && eligible(b),
+            |b| b.effective_weight(degraded_pct),
+        )
+    }
+
+    /// Whether `archive_routing` sends a request for `slot` to archive backends:
+    /// it is at least `min_depth_slots` behind the consensus tip.
+    pub fn is_deep_history(&self, slot: u64) -> bool {
+        self.archive_routing.enabled
+            && self
+                .health_state
+                .tip()
+                .is_some_and(|tip| is_deep(slot, tip, self.archive_routing.min_depth_slots))
+    }
+
+    /// An `archive` backend in rotation for `rpc_method` other than those in
+    /// `failed`, for a request reading deep history.
+    pub fn select_archive(
+        &self,
+        rpc_method: Option<&str>,
+        failed: &[&str],
+    ) -> Option<&RuntimeBackend> {
+        let degraded_pct = self.health_check_config.degraded_weight_percent;
+        let eligible = self.eligible(rpc_method);
+        self.select_weighted(
+            |b| b.config.archive && !failed.contains(&b.config.label.as_str()) && eligible(b),
+            |b| b.effective_weight(degraded_pct),
+        )
+    }
 
-        if healthy_total_weight == 0 {
-            return healthy_backends
-                .first()
-                .map(|b| (b.config.label.clone(), b.config.url.clone()));
+    /// Like [`select_backend`](Self::select_backend), but weighted by the learned
+    /// shares for `method` instead of configured weights. Backends without a
+    /// learned share get the minimum share; pinned methods keep their route.
+    pub fn select_auto(&self, method: &str, weights: &AutoWeights) -> Option<&RuntimeBackend> {
+        let shares = match weights.get(method) {
+            Some(shares) if !self.method_routes.contains_key(method) => shares,
+            _ => return self.select_backend(Some(method)),
+        };
+        let min_share = self.routing.min_share_percent * 100;
+        let degraded_pct = self.health_check_config.degraded_weight_percent;
+        self.select_weighted(self.eligible(Some(method)), |b| {
+            let share = shares.get(&b.config.label).copied().unwrap_or(min_share);
+            if b.degraded.load(Ordering::Relaxed) {
+                (share.saturating_mul(degraded_pct) / 100).max(1)
+            } else {
+                share
+            }
+        })
+    }
+
+    /// Idempotency of `method`, built in or from `method_idempotency`. Methods
+    /// classified by neither count as [`Idempotency::Idempotent`]: they fail
+    /// over as before, but are never hedged or cached.
+    pub fn idempotency(&self, method: &str) -> Idempotency {
+        idempotency(method, &self.method_idempotency).unwrap_or(Idempotency::Idempotent)
+    }
+
+    /// Whether a request with `body` may be sent to another backend after the
+    /// first failed it: not for non-idempotent methods, nor for batches
+    /// holding one. `rpc_method` is `None` for batches.
+    pub fn may_resend(&self, rpc_method: Option<&str>, body: &[u8]) -> bool {
+        let resendable = |method: &str| self.idempotency(method) != Idempotency::NonIdempotent;
+        match rpc_method {
+            Some(method) => resendable(method),
+            None => serde_json::from_slice::<Vec<serde_json::Value>>(body).map_or(true, |batch| {
+                batch
+                    .iter()
+                    .filter_map(|r| r.get("method").and_then(serde_json::Value::as_str))
+                    .all(resendable)
+            }),
         }
+    }
 
-        // Weighted random selection among healthy backends
-        let mut rng = rand::thread_rng();
-        let mut random_weight = rng.gen_range(0..healthy_total_weight);
+    /// The two fastest backends in rotation for `method` if it should be hedged:
+    /// hedging is on, the method is listed and not pinned by `method_routes`.
+    /// Backends without latency samples count as fastest so they get measured.
+    pub fn hedge_pair(&self, method: &str) -> Option<[&RuntimeBackend; 2]> {
+        if !self.hedging.enabled
+            || !self.hedging.methods.iter().any(|m| m == method)
+            || self.method_routes.contains_key(method)
+            || self.idempotency(method) != Idempotency::Safe
+        {
+            return None;
+        }
 
-        for backend in &healthy_backends {
-            if random_weight < backend.config.weight {
-                return Some((backend.config.label.clone(), backend.config.url.clone()));
+        let mut fastest: [Option<&RuntimeBackend>; 2] = [None, None];
+        let latency = |b: &RuntimeBackend| b.latency_us.load(Ordering::Relaxed);
+        for backend in self
+            .backends
+            .iter()
+            .filter(|b| b.in_rotation() && b.accepts(Some(method)))
+        {
+            if fastest[0].is_none_or(|f| latency(backend) < latency(f)) {
+                fastest = [Some(backend), fastest[0]];
+            } else if fastest[1].is_none_or(|f| latency(backend) < latency(f)) {
+                fastest[1] = Some(backend);
             }
-            random_weight -= backend.config.weight;
         }
+        Some([fastest[0]?, fastest[1]?])
+    }
 
-        // Fallback (should never reach here if weights are valid)
-        healthy_backends
-            .first()
-            .map(|b| (b.config.label.clone(), b.config.url.clone()))
+    /// Backends to broadcast `method` to: those in rotation that accept it,
+    /// fastest first, at most `broadcast.max_backends` of them (all with 0).
+    pub fn broadcast_targets(&self, method: &str) -> Vec<&RuntimeBackend> {
+        let mut targets: Vec<&RuntimeBackend> = self
+            .backends
+            .iter()
+            .filter(|b| b.in_rotation() && b.accepts(Some(method)))
+            .collect();
+        targets.sort_by_key(|b| b.latency_us.load(Ordering::Relaxed));
+        if self.broadcast.max_backends > 0 {
+            targets.truncate(self.broadcast.max_backends);
+        }
+        targets
     }
 
     /// Select a healthy backend that has WebSocket support (ws_url configured)
-    pub fn select_ws_backend(&self) -> Option<(String, String)> {
-        let state = self.state.load();
+    pub fn select_ws_backend(&self) -> Option<&RuntimeBackend> {
+        let degraded_pct = self.health_check_config.degraded_weight_percent;
+        self.select_weighted(
+            |b| b.config.ws_url.is_some() && b.in_rotation(),
+            |b| b.effective_weight(degraded_pct),
+        )
+    }
+
+    /// Whether `backend` is outside `routing.region` (never, when unset).
+    pub fn is_cross_region(&self, backend: &RuntimeBackend) -> bool {
+        self.routing
+            .region
+            .as_ref()
+            .is_some_and(|region| backend.config.region.as_ref() != Some(region))
+    }
 
-        // Filter to backends with ws_url configured and healthy (lock-free)
-        let ws_backends: Vec<&RuntimeBackend> = state
+    /// Region to spill to when no backend in `routing.region` matches
+    /// `eligible`: the one whose matching backends have the lowest average
+    /// latency. Regions without latency samples come last, in config order.
+    fn spill_region(&self, eligible: impl Fn(&RuntimeBackend) -> bool) -> Option<Option<&str>> {
+        // (region, latency sum, measured backends)
+        let mut regions: Vec<(Option<&str>, u64, u64)> = Vec::new();
+        for backend in self.backends.iter().filter(|b| eligible(b)) {
+            let region = backend.config.region.as_deref();
+            let index = match regions.iter().position(|r| r.0 == region) {
+                Some(index) => index,
+                None => {
+                    regions.push((region, 0, 0));
+                    regions.len() - 1
+                }
+            };
+            let latency = backend.latency_us.load(Ordering::Relaxed);
+            if latency > 0 {
+                regions[index].1 += latency;
+                regions[index].2 += 1;
+            }
+        }
+        regions
+            .iter()
+            .min_by_key(|(_, sum, measured)| sum.checked_div(*measured).unwrap_or(u64::MAX))
+            .map(|r| r.0)
+    }
+
+    /// Weighted round-robin among backends matching `eligible`, in
+    /// `routing.region` while any of them qualifies, otherwise in the fastest
+    /// other region.
+    fn select_weighted(
+        &self,
+        eligible: impl Fn(&RuntimeBackend) -> bool,
+        weight: impl Fn(&RuntimeBackend) -> u32,
+    ) -> Option<&RuntimeBackend> {
+        let Some(local) = self.routing.region.as_deref() else {
+            return self.weighted_choice(eligible, weight);
+        };
+        let in_region =
+            |b: &RuntimeBackend, region: Option<&str>| b.config.region.as_deref() == region;
+        if self
             .backends
             .iter()
-            .filter(|b| b.config.ws_url.is_some() && b.healthy.load(Ordering::Relaxed))
-            .collect();
+            .any(|b| in_region(b, Some(local)) && eligible(b))
+        {
+            return self.weighted_choice(|b| in_region(b, Some(local)) && eligible(b), weight);
+        }
 
-        if ws_backends.is_empty() {
+        let region = self.spill_region(&eligible)?;
+        debug!(
+            "No eligible backend in region {}, spilling to {}",
+            local,
+            region.unwrap_or("(none)")
+        );
+        self.weighted_choice(|b| in_region(b, region) && eligible(b), weight)
+    }
+
+    /// With `routing.mode = "least_latency"`, the highest moving-average
+    /// latency among `eligible` backends that still counts as fastest: within
+    /// `latency_tolerance_percent` of the lowest. Backends without samples
+    /// count as fastest so they get measured. `None` (any latency) in other
+    /// modes, and for `min_share_percent` of requests so slower backends keep
+    /// fresh samples and a recovered one can win traffic back.
+    fn latency_cutoff(&self, eligible: impl Fn(&RuntimeBackend) -> bool) -> Option<u64> {
+        if self.routing.mode != RoutingMode::LeastLatency
+            || rand::thread_rng().gen_ratio(self.routing.min_share_percent.min(100), 100)
+        {
             return None;
         }
+        let fastest = self
+            .backends
+            .iter()
+            .filter(|b| eligible(b))
+            .map(|b| b.latency_us.load(Ordering::Relaxed))
+            .min()?;
+        let tolerance = u64::from(self.routing.latency_tolerance_percent);
+        Some(fastest.saturating_add(fastest.saturating_mul(tolerance) / 100))
+    }
 
-        // Calculate total weight of WebSocket-capable backends
-        let total_weight: u32 = ws_backends.iter().map(|b| b.config.weight).sum();
+    /// Weighted round-robin among backends matching `eligible` (narrowed to
+    /// the fastest by [`latency_cutoff`](Self::latency_cutoff)), without
+    /// collecting them (this runs on every request).
+    fn weighted_choice(
+        &self,
+        eligible: impl Fn(&RuntimeBackend) -> bool,
+        weight: impl Fn(&RuntimeBackend) -> u32,
+    ) -> Option<&RuntimeBackend> {
+        let cutoff = self.latency_cutoff(&eligible);
+        let fast_enough = |b: &RuntimeBackend| {
+            cutoff.is_none_or(|cutoff| b.latency_us.load(Ordering::Relaxed) <= cutoff)
+        };
+        balancer::pick(
+            self.backends
+                .iter()
+                .filter(|b| eligible(b) && fast_enough(b)),
+            weight,
+            |b| &b.current_weight,
+        )
+    }
+}
 
-        // Weighted random selection
-        let mut rng = rand::thread_rng();
-        let mut random_weight = rng.gen_range(0..total_weight);
+/// HTTP(S) client for upstream requests, giving up on opening a connection
+/// after `connect_timeout`.
+pub fn upstream_client(
+    connect_timeout: Option<Duration>,
+) -> Client<HttpsConnector<HttpConnector>, Body> {
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_connect_timeout(connect_timeout);
+    Client::builder(TokioExecutor::new()).build(HttpsConnector::new_with_connector(http))
+}
 
-        for backend in &ws_backends {
-            if random_weight < backend.config.weight {
-                return Some((
-                    backend.config.label.clone(),
-                    backend.config.ws_url.as_ref().unwrap().clone(),
-                ));
-            }
-            random_weight -= backend.config.weight;
+#[derive(Clone)]
+pub struct AppState {
+    pub client: Client<HttpsConnector<HttpConnector>, Body>,
+    pub keystore: Arc<dyn KeyStore>,
+    pub state: Arc<ArcSwap<RouterState>>,
+    pub origin_limiter: Arc<OriginLimiter>,
+    pub usage: Arc<UsageTracker>,
+    pub reload_status: Arc<ReloadStatus>,
+    pub hedge_budget: Arc<HedgeBudget>,
+    /// Signatures of transactions broadcast and accepted recently
+    pub broadcast_signatures: Arc<RecentSignatures>,
+    pub routing_stats: Arc<RoutingStats>,
+    /// Hourly per-method totals behind `/admin/traffic`
+    pub traffic: Arc<TrafficStats>,
+    /// Per-window latency histograms behind `/admin/latency-heatmap`
+    pub latency_heatmap: Arc<LatencyHeatmap>,
+    /// Learned per-method shares used when `routing.mode = "auto"`
+    pub auto_weights: Arc<ArcSwap<AutoWeights>>,
+    pub response_cache: Arc<ResponseCache>,
+    /// TTLs chosen for `cache.ttl_tuning.methods`
+    pub ttl_tuner: Arc<TtlTuner>,
+    /// Identical reads in flight (`coalescing`)
+    pub coalescer: Arc<Coalescer>,
+    /// Separate upstream pool for write methods (`pools.separate_write_pool`)
+    pub write_client: Client<HttpsConnector<HttpConnector>, Body>,
+    /// Pools of backends that set their own `protocol`
+    pub backend_clients: Arc<BackendClients>,
+    pub alerts: Arc<AlertEngine>,
+    /// Where failed `sendTransaction` requests are captured, if enabled
+    pub dead_letters: Option<Arc<dyn DeadLetterStore>>,
+    /// Write-ahead journal of `sendTransaction` requests (`journal`)
+    pub journal: Option<Arc<dyn JournalStore>>,
+    /// Per-key daily totals behind `/admin/usage`, if enabled
+    pub usage_ledger: Option<Arc<dyn UsageLedger>>,
+    /// Counts waiting to be written to `usage_ledger`
+    pub usage_buffer: Arc<UsageBuffer>,
+    /// Per-key, per-method daily counts behind `/admin/keys/<key>/usage`, if enabled
+    pub key_usage: Option<Arc<dyn KeyUsageStore>>,
+    /// Counts waiting to be written to `key_usage`
+    pub key_usage_buffer: Arc<KeyUsageBuffer>,
+    /// Spend of keys with a `spend_cap`, if `spending_caps` is enabled
+    pub spending: Option<Arc<SpendTracker>>,
+    /// Spans of proxied requests, if `tracing` is enabled
+    pub tracer: Option<Arc<Tracer>>,
+    /// Slots behind local `slotSubscribe`/`rootSubscribe` subscriptions
+    pub slot_feed: Arc<SlotFeed>,
+    /// Recent blockhashes and their expiry, for `blockhash_check`
+    pub blockhash_cache: Arc<BlockhashCache>,
+    /// Subscriptions held for `/v1/poll` clients
+    pub poll_bridge: Arc<PollBridge>,
+    /// Subsystems switched off through the admin API
+    pub kill_switches: Arc<KillSwitches>,
+    /// Backends disagreeing on `consistency.canaries`
+    pub consistency: Arc<ConsistencyState>,
+    /// Requests in flight under `proxy.max_in_flight` and per-key limits
+    pub concurrency: Arc<ConcurrencyLimiter>,
+}
+
+impl AppState {
+    pub fn new(
+        client: Client<HttpsConnector<HttpConnector>, Body>,
+        keystore: Arc<dyn KeyStore>,
+        state: Arc<ArcSwap<RouterState>>,
+    ) -> Self {
+        Self {
+            write_client: upstream_client(None),
+            backend_clients: Arc::new(BackendClients::default()),
+            client,
+            keystore,
+            state,
+            origin_limiter: Arc::new(OriginLimiter::new()),
+            usage: Arc::new(UsageTracker::new()),
+            reload_status: Arc::new(ReloadStatus::default()),
+            hedge_budget: Arc::new(HedgeBudget::new()),
+            broadcast_signatures: Arc::new(RecentSignatures::new()),
+            routing_stats: Arc::new(RoutingStats::new()),
+            traffic: Arc::new(TrafficStats::new()),
+            latency_heatmap: Arc::new(LatencyHeatmap::new()),
+            auto_weights: Arc::new(ArcSwap::from_pointee(AutoWeights::new())),
+            response_cache: Arc::new(ResponseCache::new()),
+            ttl_tuner: Arc::new(TtlTuner::new()),
+            coalescer: Arc::new(Coalescer::new()),
+            alerts: Arc::new(AlertEngine::new()),
+            dead_letters: None,
+            journal: None,
+            usage_ledger: None,
+            usage_buffer: Arc::new(UsageBuffer::new()),
+            key_usage: None,
+            key_usage_buffer: Arc::new(KeyUsageBuffer::new()),
+            spending: None,
+            tracer: None,
+            slot_feed: Arc::new(SlotFeed::new()),
+            blockhash_cache: Arc::new(BlockhashCache::new()),
+            poll_bridge: Arc::new(PollBridge::new()),
+            kill_switches: Arc::new(KillSwitches::new()),
+            consistency: Arc::new(ConsistencyState::new()),
+            concurrency: Arc::new(ConcurrencyLimiter::new()),
         }
+    }
+
+    /// The client for requests to `backend`, from the write pool when `write`;
+    /// see [`BackendClients::pick`].
+    pub fn client_for(
+        &self,
+        backend: &Backend,
+        write: bool,
+    ) -> Client<HttpsConnector<HttpConnector>, Body> {
+        let shared = if write {
+            &self.write_client
+        } else {
+            &self.client
+        };
+        self.backend_clients.pick(shared, backend, write)
+    }
 
-        // Fallback
-        ws_backends.first().map(|b| {
+    /// Whether `subsystem` is switched off, by the config or at runtime.
+    pub fn is_disabled(&self, router_state: &RouterState, subsystem: Subsystem) -> bool {
+        router_state.kill_switches.disabled.contains(&subsystem)
+            || self.kill_switches.is_set(subsystem)
+    }
+
+    pub fn select_backend(&self, rpc_method: Option<&str>) -> Option<(String, String)> {
+        self.state
+            .load()
+            .select_backend(rpc_method)
+            .map(|b| (b.config.label.clone(), b.config.url.clone()))
+    }
+
+    /// Select a healthy backend that has WebSocket support (ws_url configured)
+    pub fn select_ws_backend(&self) -> Option<(String, String)> {
+        self.state.load().select_ws_backend().map(|b| {
             (
                 b.config.label.clone(),
                 b.config.ws_url.as_ref().unwrap().clone(),
@@ -0,0 +1,263 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use arc_swap::ArcSwap;
+use serde::Serialize;
+
+use crate::{health::shared_entry, methods::is_known_method};
+
+/// Width of one counting bucket.
+const BUCKET_SECS: u64 = 10;
+
+/// Rolling window the statistics cover.
+pub const WINDOW_SECS: u64 = 300;
+
+/// Buckets kept per method and backend, reused round robin.
+const BUCKETS: usize = (WINDOW_SECS / BUCKET_SECS) as usize;
+
+/// Upper bounds (ms) of the latency histogram buckets, plus one open-ended bucket.
+const LATENCY_BOUNDS_MS: [u64; 13] = [
+    1, 2, 5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000,
+];
+const LATENCY_SLOTS: usize = LATENCY_BOUNDS_MS.len() + 1;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    start: u64,
+    requests: u64,
+    failures: u64,
+    /// Latency histogram of successful requests, indexed like `LATENCY_BOUNDS_MS`
+    latency: [u64; LATENCY_SLOTS],
+}
+
+#[derive(Default)]
+struct AtomicBucket {
+    start: AtomicU64,
+    requests: AtomicU64,
+    failures: AtomicU64,
+    latency: [AtomicU64; LATENCY_SLOTS],
+}
+
+/// Buckets of one method on one backend, updated without locks. A bucket is
+/// reused for a new interval by whichever request first reaches it. Interval
+/// boundaries are best-effort: a request racing that reset may be counted in
+/// the old interval, the new one, or not at all.
+#[derive(Default)]
+struct Series {
+    buckets: [AtomicBucket; BUCKETS],
+}
+
+impl Series {
+    fn record(&self, success: bool, latency: Duration, bucket_start: u64) {
+        let bucket = &self.buckets[(bucket_start / BUCKET_SECS) as usize % BUCKETS];
+        let mut start = bucket.start.load(Ordering::Acquire);
+        if start < bucket_start {
+            match bucket.start.compare_exchange(
+                start,
+                bucket_start,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                // A bucket never used (start 0) has nothing to clear
+                Ok(_) if start == 0 => start = bucket_start,
+                Ok(_) => {
+                    bucket.requests.store(0, Ordering::Relaxed);
+                    bucket.failures.store(0, Ordering::Relaxed);
+                    for count in &bucket.latency {
+                        count.store(0, Ordering::Relaxed);
+                    }
+                    start = bucket_start;
+                }
+                Err(current) => start = current,
+            }
+        }
+        // Only a request whose clock lags by a whole window finds a newer bucket
+        if start != bucket_start {
+            return;
+        }
+
+        bucket.requests.fetch_add(1, Ordering::Relaxed);
+        if success {
+            let ms = latency.as_millis() as u64;
+            let slot = LATENCY_BOUNDS_MS
+                .iter()
+                .position(|bound| ms <= *bound)
+                .unwrap_or(LATENCY_BOUNDS_MS.len());
+            bucket.latency[slot].fetch_add(1, Ordering::Relaxed);
+        } else {
+            bucket.failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The buckets with counts, as of now.
+    fn buckets(&self) -> impl Iterator<Item = Bucket> + '_ {
+        self.buckets.iter().filter_map(|bucket| {
+            let start = bucket.start.load(Ordering::Acquire);
+            let requests = bucket.requests.load(Ordering::Relaxed);
+            (requests > 0).then(|| Bucket {
+                start,
+                requests,
+                failures: bucket.failures.load(Ordering::Relaxed),
+                latency: std::array::from_fn(|i| bucket.latency[i].load(Ordering::Relaxed)),
+            })
+        })
+    }
+}
+
+/// The backends that served one method.
+#[derive(Default)]
+struct MethodSeries {
+    backends: ArcSwap<HashMap<String, Arc<Series>>>,
+}
+
+/// Success rate and latency of one method on one backend over [`WINDOW_SECS`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BackendMethodStats {
+    pub requests: u64,
+    pub success_rate: f64,
+    /// Histogram estimates: the upper bound of the bucket holding the percentile
+    pub p50_ms: Option<u64>,
+    pub p99_ms: Option<u64>,
+}
+
+/// Per-method, per-backend upstream outcomes over a rolling window, so operators
+/// can tune `method_routes` with data. Recording takes no lock: the maps are
+/// snapshots swapped whole when a method or backend is first seen.
+#[derive(Default)]
+pub struct RoutingStats {
+    methods: ArcSwap<HashMap<String, Arc<MethodSeries>>>,
+}
+
+impl RoutingStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one upstream request. Unknown method names are ignored so clients
+    /// cannot grow the table.
+    pub fn record(&self, method: &str, backend: &str, success: bool, latency: Duration, now: u64) {
+        if !is_known_method(method) {
+            return;
+        }
+        let bucket_start = now - now % BUCKET_SECS;
+        // Known pairs are found in the current snapshots without allocating
+        if let Some(series) = self.methods.load().get(method) {
+            if let Some(series) = series.backends.load().get(backend) {
+                series.record(success, latency, bucket_start);
+                return;
+            }
+        }
+        shared_entry(&shared_entry(&self.methods, method).backends, backend).record(
+            success,
+            latency,
+            bucket_start,
+        );
+    }
+
+    fn series(&self, method: &str, backend: &str) -> Option<Arc<Series>> {
+        self.methods
+            .load()
+            .get(method)?
+            .backends
+            .load()
+            .get(backend)
+            .cloned()
+    }
+
+    /// Stats for one method on one backend over the window ending at `now`.
+    pub fn get(&self, method: &str, backend: &str, now: u64) -> Option<BackendMethodStats> {
+        summarize(self.series(method, backend)?.buckets(), now)
+    }
+
+    /// Stats for one method across all backends over the window ending at `now`.
+    pub fn get_method(&self, method: &str, now: u64) -> Option<BackendMethodStats> {
+        let backends = self.methods.load().get(method)?.backends.load_full();
+        summarize(backends.values().flat_map(|series| series.buckets()), now)
+    }
+
+    /// Estimated `quantile` (0-1) of the successful request latency of one
+    /// method on one backend over the window ending at `now`, in ms.
+    pub fn latency_percentile(
+        &self,
+        method: &str,
+        backend: &str,
+        quantile: f64,
+        now: u64,
+    ) -> Option<u64> {
+        let mut latency = [0u64; LATENCY_SLOTS];
+        for bucket in self
+            .series(method, backend)?
+            .buckets()
+            .filter(|b| b.start + WINDOW_SECS > now)
+        {
+            for (total, count) in latency.iter_mut().zip(bucket.latency) {
+                *total += count;
+            }
+        }
+        percentile(&latency, quantile)
+    }
+
+    /// Stats for every method and backend seen in the window ending at `now`.
+    pub fn snapshot(&self, now: u64) -> BTreeMap<String, BTreeMap<String, BackendMethodStats>> {
+        self.methods
+            .load()
+            .iter()
+            .map(|(method, series)| {
+                let backends: BTreeMap<_, _> = series
+                    .backends
+                    .load()
+                    .iter()
+                    .filter_map(|(backend, series)| {
+                        Some((backend.clone(), summarize(series.buckets(), now)?))
+                    })
+                    .collect();
+                (method.clone(), backends)
+            })
+            .filter(|(_, backends)| !backends.is_empty())
+            .collect()
+    }
+}
+
+fn summarize(buckets: impl Iterator<Item = Bucket>, now: u64) -> Option<BackendMethodStats> {
+    let mut requests = 0;
+    let mut failures = 0;
+    let mut latency = [0u64; LATENCY_SLOTS];
+    for bucket in buckets.filter(|b| b.start + WINDOW_SECS > now) {
+        requests += bucket.requests;
+        failures += bucket.failures;
+        for (total, count) in latency.iter_mut().zip(bucket.latency) {
+            *total += count;
+        }
+    }
+    if requests == 0 {
+        return None;
+    }
+
+    Some(BackendMethodStats {
+        requests,
+        success_rate: (requests - failures) as f64 / requests as f64,
+        p50_ms: percentile(&latency, 0.5),
+        p99_ms: percentile(&latency, 0.99),
+    })
+}
+
+fn percentile(histogram: &[u64; LATENCY_SLOTS], quantile: f64) -> Option<u64> {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = ((total as f64 * quantile).ceil() as u64).max(1);
+    let mut seen = 0;
+    let slot = histogram.iter().position(|count| {
+        seen += count;
+        seen >= rank
+    })?;
+    // The open-ended bucket reports the last finite bound
+    Some(LATENCY_BOUNDS_MS[slot.min(LATENCY_BOUNDS_MS.len() - 1)])
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use axum::http::{HeaderMap, HeaderValue};
+use tokio::time::Instant;
+
+/// Response header carrying the breakdown for keys with `timing` on.
+pub const TIMING_HEADER: &str = "x-timing";
+
+/// Where the router spends a request's time, in the order a request passes through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// API key validation, rate limiting and browser key checks
+    Auth,
+    /// Waiting for a heavy-read slot or for a coalesced cache fill
+    Queue,
+    /// Key defaults, cache lookup, backend selection and request preparation
+    Routing,
+    /// Upstream round trips until response headers, including failover attempts
+    Upstream,
+    /// Checks, rewrites and caching of the upstream response
+    Response,
+}
+
+const PHASES: [(Phase, &str); 5] = [
+    (Phase::Auth, "auth"),
+    (Phase::Queue, "queue"),
+    (Phase::Routing, "routing"),
+    (Phase::Upstream, "upstream"),
+    (Phase::Response, "response"),
+];
+
+/// Time spent per [`Phase`] by one request. Each [`mark`](Self::mark) charges
+/// the time since the previous one to a phase.
+#[derive(Debug, Clone)]
+pub struct RequestTiming {
+    last: Instant,
+    spent: [Duration; 5],
+}
+
+impl RequestTiming {
+    pub fn start(at: Instant) -> Self {
+        Self {
+            last: at,
+            spent: [Duration::ZERO; 5],
+        }
+    }
+
+    pub fn mark(&mut self, phase: Phase) {
+        self.mark_at(phase, Instant::now());
+    }
+
+    pub fn mark_at(&mut self, phase: Phase, now: Instant) {
+        self.spent[phase as usize] += now.saturating_duration_since(self.last);
+        self.last = now;
+    }
+
+    pub fn spent(&self, phase: Phase) -> Duration {
+        self.spent[phase as usize]
+    }
+
+    /// Charge the time since the last mark to response processing, or to
+    /// routing for requests answered without going upstream (cache hits,
+    /// rejections), then set the header. The body is streamed afterwards and
+    /// is not included.
+    pub fn annotate(mut self, headers: &mut HeaderMap, now: Instant) {
+        let phase = if self.spent(Phase::Upstream).is_zero() {
+            Phase::Routing
+        } else {
+            Phase::Response
+        };
+        self.mark_at(phase, now);
+        if let Ok(value) = HeaderValue::from_str(&self.header_value()) {
+            headers.insert(TIMING_HEADER, value);
+        }
+    }
+
+    /// `Server-Timing` syntax, milliseconds per phase:
+    /// `auth;dur=0.120, queue;dur=0.000, routing;dur=0.310, upstream;dur=45.020, response;dur=0.200`
+    pub fn header_value(&self) -> String {
+        PHASES
+            .iter()
+            .map(|(phase, name)| {
+                let ms = self.spent(*phase).as_secs_f64() * 1000.0;
+                format!("{};dur={:.3}", name, ms)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
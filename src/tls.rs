@@ -0,0 +1,207 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use arc_swap::ArcSwap;
+use axum::{extract::ConnectInfo, Extension, Router};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto::Builder, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
+use metrics::counter;
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use tokio::{net::TcpListener, time::timeout};
+use tokio_rustls::{
+    rustls::{
+        crypto::aws_lc_rs,
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+        ServerConfig,
+    },
+    TlsAcceptor,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::config::TlsConfig;
+
+/// Longest a client may take to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The certificate and key files as last read.
+#[derive(PartialEq)]
+struct Pem {
+    cert: Vec<u8>,
+    key: Vec<u8>,
+}
+
+/// Parse a PEM certificate chain and private key into a key rustls can serve.
+pub fn certified_key(cert_pem: &[u8], key_pem: &[u8]) -> Result<CertifiedKey, String> {
+    let chain = CertificateDer::pem_slice_iter(cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid certificate: {}", e))?;
+    if chain.is_empty() {
+        return Err("no certificate found".into());
+    }
+    let key = PrivateKeyDer::from_pem_slice(key_pem)
+        .map_err(|e| format!("invalid private key: {}", e))?;
+    let signing_key = aws_lc_rs::sign::any_supported_type(&key)
+        .map_err(|e| format!("unsupported private key: {}", e))?;
+    let certified = CertifiedKey::new(chain, signing_key);
+    certified
+        .keys_match()
+        .map_err(|e| format!("private key does not match the certificate: {}", e))?;
+    Ok(certified)
+}
+
+/// Serves the certificate last loaded from `tls.cert_path` and `tls.key_path`.
+/// New handshakes pick up a reloaded certificate; open connections keep theirs.
+pub struct CertResolver {
+    cert_path: String,
+    key_path: String,
+    current: ArcSwap<CertifiedKey>,
+    loaded: Mutex<Pem>,
+}
+
+impl fmt::Debug for CertResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertResolver")
+            .field("cert_path", &self.cert_path)
+            .field("key_path", &self.key_path)
+            .finish()
+    }
+}
+
+impl CertResolver {
+    /// Load the certificate files named in `config`.
+    pub fn load(config: &TlsConfig) -> Result<Self, String> {
+        let pem = read_pem(&config.cert_path, &config.key_path)?;
+        let key = certified_key(&pem.cert, &pem.key)?;
+        Ok(Self {
+            cert_path: config.cert_path.clone(),
+            key_path: config.key_path.clone(),
+            current: ArcSwap::from_pointee(key),
+            loaded: Mutex::new(pem),
+        })
+    }
+
+    /// Re-read the files and switch to their certificate if they changed.
+    /// Returns whether it switched; on error the current certificate stays.
+    pub fn reload(&self) -> Result<bool, String> {
+        let pem = read_pem(&self.cert_path, &self.key_path)?;
+        let mut loaded = self.loaded.lock().unwrap_or_else(|e| e.into_inner());
+        if *loaded == pem {
+            return Ok(false);
+        }
+        let key = certified_key(&pem.cert, &pem.key)?;
+        self.current.store(Arc::new(key));
+        *loaded = pem;
+        Ok(true)
+    }
+
+    /// The certificate chain served to new connections.
+    pub fn chain(&self) -> Vec<CertificateDer<'static>> {
+        self.current.load().cert.clone()
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.load_full())
+    }
+}
+
+fn read_pem(cert_path: &str, key_path: &str) -> Result<Pem, String> {
+    let cert = std::fs::read(cert_path).map_err(|e| format!("{}: {}", cert_path, e))?;
+    let key = std::fs::read(key_path).map_err(|e| format!("{}: {}", key_path, e))?;
+    Ok(Pem { cert, key })
+}
+
+/// Accepts TLS connections with the resolver's current certificate,
+/// negotiating HTTP/2 or HTTP/1.1.
+pub fn acceptor(resolver: Arc<CertResolver>) -> Result<TlsAcceptor, String> {
+    let mut config = ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Re-read the certificate files every `reload_secs`, so renewals (for
+/// example by certbot) take effect without a restart.
+pub async fn cert_reload_loop(resolver: Arc<CertResolver>, reload_secs: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(reload_secs));
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match resolver.reload() {
+            Ok(true) => {
+                info!("Reloaded TLS certificate from {}", resolver.cert_path);
+                counter!("rpc_tls_cert_reloads_total", "result" => "success").increment(1);
+            }
+            Ok(false) => {}
+            Err(e) => {
+                warn!("Keeping the current TLS certificate: {}", e);
+                counter!("rpc_tls_cert_reloads_total", "result" => "error").increment(1);
+            }
+        }
+    }
+}
+
+/// Serve `app` over TLS on `listener` until `token` is cancelled, then wait
+/// for open connections to finish. WebSocket upgrades are supported.
+pub async fn serve_tls(
+    listener: TcpListener,
+    app: Router,
+    acceptor: TlsAcceptor,
+    token: CancellationToken,
+) {
+    let graceful = GracefulShutdown::new();
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // Out of file descriptors and the like: back off as axum does
+                    error!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            _ = token.cancelled() => break,
+        };
+        let acceptor = acceptor.clone();
+        let app = app
+            .clone()
+            .layer(Extension(ConnectInfo::<SocketAddr>(addr)));
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let stream = match timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    debug!("TLS handshake with {} failed: {}", addr, e);
+                    counter!("rpc_tls_handshake_failures_total").increment(1);
+                    return;
+                }
+                Err(_) => {
+                    counter!("rpc_tls_handshake_failures_total").increment(1);
+                    return;
+                }
+            };
+            let builder = Builder::new(TokioExecutor::new());
+            let conn = builder
+                .serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(app))
+                .into_owned();
+            if let Err(e) = watcher.watch(conn).await {
+                debug!("Connection from {} ended: {}", addr, e);
+            }
+        });
+    }
+    graceful.shutdown().await;
+}
@@ -0,0 +1,260 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use metrics::counter;
+use rand::Rng;
+
+use crate::config::TracingConfig;
+
+/// W3C trace context header, read from clients and set on upstream requests.
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Nanoseconds since the Unix epoch.
+pub fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Identity of a span as carried in a `traceparent` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub sampled: bool,
+}
+
+impl SpanContext {
+    /// Parse a `traceparent` header value. Versions after `00` are read for
+    /// their first four fields, as the spec asks; anything malformed, or with
+    /// an all-zero id, is `None`.
+    pub fn from_traceparent(value: &str) -> Option<Self> {
+        let mut fields = value.trim().split('-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let span_id = fields.next()?;
+        let flags = fields.next()?;
+        let extra = fields.next().is_some();
+        if version.len() != 2 || version == "ff" || (version == "00" && extra) {
+            return None;
+        }
+        let lower_hex = |s: &str| s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+        if ![version, trace_id, span_id, flags]
+            .iter()
+            .all(|f| lower_hex(f))
+        {
+            return None;
+        }
+        let mut context = Self {
+            trace_id: [0; 16],
+            span_id: [0; 8],
+            sampled: false,
+        };
+        hex::decode_to_slice(trace_id, &mut context.trace_id).ok()?;
+        hex::decode_to_slice(span_id, &mut context.span_id).ok()?;
+        let mut flags_byte = [0u8; 1];
+        hex::decode_to_slice(flags, &mut flags_byte).ok()?;
+        if context.trace_id == [0; 16] || context.span_id == [0; 8] {
+            return None;
+        }
+        context.sampled = flags_byte[0] & 1 == 1;
+        Some(context)
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            hex::encode(self.trace_id),
+            hex::encode(self.span_id),
+            u8::from(self.sampled)
+        )
+    }
+}
+
+/// A finished span, queued for export.
+#[derive(Debug, Clone)]
+pub struct SpanData {
+    pub name: String,
+    pub context: SpanContext,
+    /// The client's span, when the request came with a `traceparent`
+    pub parent_span_id: Option<[u8; 8]>,
+    pub start_nanos: u64,
+    pub end_nanos: u64,
+    pub attributes: Vec<(&'static str, String)>,
+    /// HTTP status of the response; 5xx marks the span as failed
+    pub status: u16,
+}
+
+/// Starts the spans of proxied requests and holds finished ones until the
+/// exporter picks them up.
+pub struct Tracer {
+    sample_percent: u32,
+    max_queued_spans: usize,
+    queue: Mutex<Vec<SpanData>>,
+}
+
+impl Tracer {
+    pub fn new(config: &TracingConfig) -> Self {
+        Self {
+            sample_percent: config.sample_percent,
+            max_queued_spans: config.max_queued_spans,
+            queue: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Context of a new span: a child of `parent` in its trace and sampled
+    /// as it is, or the root of a new trace sampled at `sample_percent`.
+    pub fn start(&self, parent: Option<&SpanContext>) -> SpanContext {
+        let mut rng = rand::thread_rng();
+        let span_id = loop {
+            let id: [u8; 8] = rng.gen();
+            if id != [0; 8] {
+                break id;
+            }
+        };
+        match parent {
+            Some(parent) => SpanContext {
+                trace_id: parent.trace_id,
+                span_id,
+                sampled: parent.sampled,
+            },
+            None => {
+                let trace_id = loop {
+                    let id: [u8; 16] = rng.gen();
+                    if id != [0; 16] {
+                        break id;
+                    }
+                };
+                SpanContext {
+                    trace_id,
+                    span_id,
+                    sampled: rng.gen_range(0..100) < self.sample_percent,
+                }
+            }
+        }
+    }
+
+    /// Queue a finished span, or drop it if the queue is full.
+    pub fn record(&self, span: SpanData) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() >= self.max_queued_spans {
+            counter!("rpc_trace_spans_dropped_total").increment(1);
+            return;
+        }
+        queue.push(span);
+    }
+
+    /// Finished spans not yet exported.
+    pub fn drain(&self) -> Vec<SpanData> {
+        std::mem::take(&mut *self.queue.lock().unwrap())
+    }
+}
+
+/// A span in progress, recorded with its end time when dropped.
+pub struct OpenSpan {
+    tracer: Arc<Tracer>,
+    span: Option<SpanData>,
+}
+
+impl OpenSpan {
+    pub fn new(tracer: Arc<Tracer>, span: SpanData) -> Self {
+        Self {
+            tracer,
+            span: Some(span),
+        }
+    }
+}
+
+impl Drop for OpenSpan {
+    fn drop(&mut self) {
+        if let Some(mut span) = self.span.take() {
+            span.end_nanos = unix_nanos();
+            self.tracer.record(span);
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+pub mod otlp {
+    use super::*;
+    use crate::{
+        metrics_export::otlp::{client, post, signal_url},
+        redact::redact,
+    };
+    use serde_json::{json, Value};
+    use tokio::time::{interval, Duration, MissedTickBehavior};
+    use tracing::warn;
+
+    /// `ExportTraceServiceRequest` for `spans`. Ids are hex and 64-bit
+    /// integers strings, as the OTLP JSON encoding requires.
+    pub fn payload(spans: &[SpanData], service_name: &str) -> Value {
+        let spans: Vec<Value> = spans
+            .iter()
+            .map(|span| {
+                let mut attributes: Vec<Value> = span
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+                    .collect();
+                attributes.push(json!({
+                    "key": "http.response.status_code",
+                    "value": {"intValue": span.status.to_string()},
+                }));
+                let mut value = json!({
+                    "traceId": hex::encode(span.context.trace_id),
+                    "spanId": hex::encode(span.context.span_id),
+                    "name": span.name,
+                    // SPAN_KIND_SERVER
+                    "kind": 2,
+                    "startTimeUnixNano": span.start_nanos.to_string(),
+                    "endTimeUnixNano": span.end_nanos.to_string(),
+                    "attributes": attributes,
+                    // STATUS_CODE_ERROR for server errors, otherwise unset
+                    "status": {"code": if span.status >= 500 { 2 } else { 0 }},
+                });
+                if let Some(parent) = span.parent_span_id {
+                    value["parentSpanId"] = hex::encode(parent).into();
+                }
+                value
+            })
+            .collect();
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        {"key": "service.name", "value": {"stringValue": service_name}},
+                    ],
+                },
+                "scopeSpans": [{
+                    "scope": {"name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION")},
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+
+    /// Push finished spans every `config.interval_secs`. Spans of a failed
+    /// push are dropped.
+    pub async fn export_loop(tracer: Arc<Tracer>, config: TracingConfig) {
+        let client = client();
+        let url = signal_url(&config.otlp, "traces");
+        let mut ticker = interval(Duration::from_secs(config.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let spans = tracer.drain();
+            if spans.is_empty() {
+                continue;
+            }
+            let body = payload(&spans, &config.otlp.service_name);
+            if let Err(e) = post(&client, &url, &config.otlp, &body).await {
+                counter!("rpc_trace_spans_dropped_total").increment(spans.len() as u64);
+                warn!("OTLP trace export failed: {}", redact(&e));
+            }
+        }
+    }
+}
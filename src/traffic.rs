@@ -0,0 +1,256 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+
+use crate::methods::is_known_method;
+
+/// Width of one bucket of totals.
+pub const BUCKET_SECS: u64 = 3600;
+
+/// Name requests are counted under when they carry no known method, such as
+/// unsplit batches.
+pub const OTHER_METHOD: &str = "other";
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counts {
+    requests: u64,
+    request_bytes: u64,
+    response_bytes: u64,
+    /// Second currently being counted, and its requests so far
+    second: u64,
+    second_requests: u64,
+    peak_rps: u64,
+}
+
+impl Counts {
+    fn record(&mut self, request_bytes: u64, now: u64) {
+        self.requests += 1;
+        self.request_bytes += request_bytes;
+        if self.second == now {
+            self.second_requests += 1;
+        } else {
+            self.second = now;
+            self.second_requests = 1;
+        }
+        self.peak_rps = self.peak_rps.max(self.second_requests);
+    }
+
+    fn merge(&mut self, other: &Counts) {
+        self.requests += other.requests;
+        self.request_bytes += other.request_bytes;
+        self.response_bytes += other.response_bytes;
+        self.peak_rps = self.peak_rps.max(other.peak_rps);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+    start: u64,
+    methods: HashMap<String, Counts>,
+    /// All methods together; its peak is not the sum of theirs
+    total: Counts,
+}
+
+/// Request volumes, payload sizes and peak rates of one method (or of all of
+/// them) over a report period.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MethodTraffic {
+    pub method: String,
+    pub requests: u64,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    pub avg_request_bytes: u64,
+    pub avg_response_bytes: u64,
+    /// Requests per second averaged over the period
+    pub avg_rps: f64,
+    /// Most requests in any one second of the period
+    pub peak_rps: u64,
+}
+
+impl MethodTraffic {
+    fn new(method: &str, counts: &Counts, period_secs: u64) -> Self {
+        Self {
+            method: method.to_string(),
+            requests: counts.requests,
+            request_bytes: counts.request_bytes,
+            response_bytes: counts.response_bytes,
+            avg_request_bytes: counts
+                .request_bytes
+                .checked_div(counts.requests)
+                .unwrap_or(0),
+            avg_response_bytes: counts
+                .response_bytes
+                .checked_div(counts.requests)
+                .unwrap_or(0),
+            avg_rps: counts.requests as f64 / period_secs.max(1) as f64,
+            peak_rps: counts.peak_rps,
+        }
+    }
+}
+
+/// Traffic over `from..to` (unix seconds, whole hours), busiest method first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrafficReport {
+    pub from: u64,
+    pub to: u64,
+    pub methods: Vec<MethodTraffic>,
+    pub total: MethodTraffic,
+}
+
+impl TrafficReport {
+    /// The report as CSV: a header, a row per method, then the total.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "method,requests,request_bytes,response_bytes,avg_request_bytes,avg_response_bytes,avg_rps,peak_rps\n",
+        );
+        for row in self.methods.iter().chain([&self.total]) {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{:.3},{}",
+                row.method,
+                row.requests,
+                row.request_bytes,
+                row.response_bytes,
+                row.avg_request_bytes,
+                row.avg_response_bytes,
+                row.avg_rps,
+                row.peak_rps
+            );
+        }
+        csv
+    }
+}
+
+/// Hourly per-method totals behind `/admin/traffic`, for sizing committed-use
+/// plans with providers.
+#[derive(Default)]
+pub struct TrafficStats {
+    /// Oldest first
+    buckets: Mutex<VecDeque<Bucket>>,
+}
+
+impl TrafficStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a request for `method` of `request_bytes` at `now`, dropping
+    /// buckets older than `retention_secs`. Unknown methods are counted as
+    /// [`OTHER_METHOD`] so clients cannot grow the table.
+    pub fn record_request(
+        &self,
+        method: Option<&str>,
+        request_bytes: u64,
+        now: u64,
+        retention_secs: u64,
+    ) {
+        let method = method
+            .filter(|m| is_known_method(m))
+            .unwrap_or(OTHER_METHOD);
+        let start = now - now % BUCKET_SECS;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.back().map(|b| b.start) != Some(start) {
+            buckets.push_back(Bucket {
+                start,
+                ..Default::default()
+            });
+        }
+        while buckets
+            .front()
+            .is_some_and(|b| b.start + retention_secs <= start)
+        {
+            buckets.pop_front();
+        }
+
+        let bucket = buckets.back_mut().expect("bucket just pushed");
+        bucket.total.record(request_bytes, now);
+        // Look up by &str first so the hot path does not allocate
+        match bucket.methods.get_mut(method) {
+            Some(counts) => counts.record(request_bytes, now),
+            None => {
+                let mut counts = Counts::default();
+                counts.record(request_bytes, now);
+                bucket.methods.insert(method.to_string(), counts);
+            }
+        }
+    }
+
+    /// Add the `response_bytes` sent for a request counted at `at`.
+    pub fn record_response(&self, method: Option<&str>, response_bytes: u64, at: u64) {
+        let method = method
+            .filter(|m| is_known_method(m))
+            .unwrap_or(OTHER_METHOD);
+        let start = at - at % BUCKET_SECS;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(bucket) = buckets.iter_mut().rev().find(|b| b.start == start) {
+            bucket.total.response_bytes += response_bytes;
+            if let Some(counts) = bucket.methods.get_mut(method) {
+                counts.response_bytes += response_bytes;
+            }
+        }
+    }
+
+    /// Traffic in the last `hours` hours up to `now`, the current one included.
+    pub fn report(&self, hours: u64, now: u64) -> TrafficReport {
+        let to = now - now % BUCKET_SECS + BUCKET_SECS;
+        let from = to.saturating_sub(hours * BUCKET_SECS);
+        let period_secs = now.saturating_sub(from);
+
+        let mut methods: HashMap<&str, Counts> = HashMap::new();
+        let mut total = Counts::default();
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        for bucket in buckets.iter().filter(|b| b.start >= from) {
+            total.merge(&bucket.total);
+            for (method, counts) in &bucket.methods {
+                methods.entry(method).or_default().merge(counts);
+            }
+        }
+
+        let mut methods: Vec<MethodTraffic> = methods
+            .iter()
+            .map(|(method, counts)| MethodTraffic::new(method, counts, period_secs))
+            .collect();
+        methods.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.method.cmp(&b.method)));
+        TrafficReport {
+            from,
+            to,
+            methods,
+            total: MethodTraffic::new("total", &total, period_secs),
+        }
+    }
+}
+
+/// Counts the bytes of a response body as it is sent, and adds them to the
+/// totals once the body is dropped.
+pub struct ResponseMeter {
+    stats: Arc<TrafficStats>,
+    method: Option<String>,
+    at: u64,
+    bytes: u64,
+}
+
+impl ResponseMeter {
+    pub fn new(stats: Arc<TrafficStats>, method: Option<String>, at: u64) -> Self {
+        Self {
+            stats,
+            method,
+            at,
+            bytes: 0,
+        }
+    }
+
+    pub fn add(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+    }
+}
+
+impl Drop for ResponseMeter {
+    fn drop(&mut self) {
+        self.stats
+            .record_response(self.method.as_deref(), self.bytes, self.at);
+    }
+}
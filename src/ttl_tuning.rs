@@ -0,0 +1,297 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use bytes::Bytes;
+use metrics::counter;
+use rand::Rng;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{
+    cache::CacheLookup,
+    config::{Canary, TtlBounds, TtlTuningConfig},
+    consistency::{comparable, query_canary},
+    redact::redact,
+    state::AppState,
+};
+
+/// Stores of one method after which its counts are halved, so the choice
+/// follows how traffic changes rather than its whole history.
+const DECAY_STORES: u64 = 1_000;
+
+/// TTLs tried between `bounds`: doubling from `min_secs`, ending at `max_secs`.
+pub fn ttl_ladder(bounds: TtlBounds) -> Vec<u64> {
+    let mut ladder = Vec::new();
+    let mut ttl = bounds.min_secs.max(1);
+    while ttl < bounds.max_secs {
+        ladder.push(ttl);
+        ttl = ttl.saturating_mul(2);
+    }
+    ladder.push(bounds.max_secs);
+    ladder
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Arm {
+    ttl_secs: u64,
+    stores: u64,
+    hits: u64,
+    revalidations: u64,
+    mismatches: u64,
+}
+
+impl Arm {
+    fn hits_per_store(&self) -> f64 {
+        self.hits as f64 / self.stores.max(1) as f64
+    }
+
+    fn mismatch_rate(&self) -> f64 {
+        self.mismatches as f64 / self.revalidations.max(1) as f64
+    }
+
+    fn score(&self, staleness_penalty: u32) -> f64 {
+        let freshness = 1.0 - staleness_penalty as f64 * self.mismatch_rate();
+        self.hits_per_store() * freshness.max(0.0)
+    }
+}
+
+#[derive(Debug)]
+struct Arms {
+    bounds: TtlBounds,
+    arms: Vec<Arm>,
+}
+
+impl Arms {
+    fn new(bounds: TtlBounds) -> Self {
+        Self {
+            bounds,
+            arms: ttl_ladder(bounds)
+                .into_iter()
+                .map(|ttl_secs| Arm {
+                    ttl_secs,
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn arm_mut(&mut self, ttl: Duration) -> Option<&mut Arm> {
+        self.arms.iter_mut().find(|a| a.ttl_secs == ttl.as_secs())
+    }
+
+    /// The arm to exploit: the shortest untried one, else the best scoring
+    /// (the shorter on ties).
+    fn best(&self, staleness_penalty: u32) -> &Arm {
+        if let Some(untried) = self.arms.iter().find(|a| a.stores == 0) {
+            return untried;
+        }
+        self.arms
+            .iter()
+            .rev()
+            .max_by(|a, b| {
+                a.score(staleness_penalty)
+                    .total_cmp(&b.score(staleness_penalty))
+            })
+            .expect("ladder is never empty")
+    }
+}
+
+/// One TTL tried for a method, and how it fared.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ArmReport {
+    pub ttl_secs: u64,
+    pub stores: u64,
+    pub hits: u64,
+    pub revalidations: u64,
+    pub mismatches: u64,
+    pub hits_per_store: f64,
+    pub mismatch_rate: f64,
+    pub score: f64,
+}
+
+/// The TTL currently chosen for a tuned method, and every TTL it tries.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TunedMethod {
+    pub method: String,
+    pub min_secs: u64,
+    pub max_secs: u64,
+    pub ttl_secs: u64,
+    pub arms: Vec<ArmReport>,
+}
+
+/// Epsilon-greedy choice of cache TTLs for `cache.ttl_tuning.methods`. Counts
+/// survive config reloads; a method whose bounds change starts over.
+#[derive(Default)]
+pub struct TtlTuner {
+    methods: Mutex<HashMap<String, Arms>>,
+}
+
+impl TtlTuner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The TTL to store the next result of `method` with, or `None` if the
+    /// method is not tuned.
+    pub fn choose(&self, method: &str, config: &TtlTuningConfig) -> Option<Duration> {
+        let bounds = *config.methods.get(method).filter(|_| config.enabled)?;
+        let mut methods = self.methods.lock().unwrap_or_else(|e| e.into_inner());
+        let arms = match methods.get_mut(method) {
+            Some(arms) if arms.bounds == bounds => arms,
+            _ => {
+                methods.insert(method.to_string(), Arms::new(bounds));
+                methods.get_mut(method).expect("just inserted")
+            }
+        };
+        let mut rng = rand::thread_rng();
+        let ttl_secs = if rng.gen_ratio(config.explore_percent.min(100), 100) {
+            arms.arms[rng.gen_range(0..arms.arms.len())].ttl_secs
+        } else {
+            arms.best(config.staleness_penalty).ttl_secs
+        };
+        Some(Duration::from_secs(ttl_secs))
+    }
+
+    /// A result of `method` was cached for `ttl`.
+    pub fn record_store(&self, method: &str, ttl: Duration) {
+        let mut methods = self.methods.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(arms) = methods.get_mut(method) else {
+            return;
+        };
+        let Some(arm) = arms.arm_mut(ttl) else {
+            return;
+        };
+        arm.stores += 1;
+        if arms.arms.iter().map(|a| a.stores).sum::<u64>() >= DECAY_STORES {
+            for arm in &mut arms.arms {
+                arm.stores /= 2;
+                arm.hits /= 2;
+                arm.revalidations /= 2;
+                arm.mismatches /= 2;
+            }
+        }
+    }
+
+    /// A request for `method` was answered from a result cached for `ttl`.
+    pub fn record_hit(&self, method: &str, ttl: Duration) {
+        let mut methods = self.methods.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(arm) = methods.get_mut(method).and_then(|a| a.arm_mut(ttl)) {
+            arm.hits += 1;
+        }
+    }
+
+    /// A hit on a result cached for `ttl` was re-fetched; `matched` is whether
+    /// the backend still gave the same result.
+    pub fn record_revalidation(&self, method: &str, ttl: Duration, matched: bool) {
+        let mut methods = self.methods.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(arm) = methods.get_mut(method).and_then(|a| a.arm_mut(ttl)) {
+            arm.revalidations += 1;
+            if !matched {
+                arm.mismatches += 1;
+            }
+        }
+    }
+
+    /// The chosen TTL and arms of every method `config` tunes, by method name.
+    pub fn snapshot(&self, config: &TtlTuningConfig) -> Vec<TunedMethod> {
+        let methods = self.methods.lock().unwrap_or_else(|e| e.into_inner());
+        let mut snapshot: Vec<TunedMethod> = config
+            .methods
+            .iter()
+            .map(|(method, bounds)| {
+                let fresh;
+                let arms = match methods.get(method) {
+                    Some(arms) if arms.bounds == *bounds => arms,
+                    _ => {
+                        fresh = Arms::new(*bounds);
+                        &fresh
+                    }
+                };
+                let penalty = config.staleness_penalty;
+                TunedMethod {
+                    method: method.clone(),
+                    min_secs: bounds.min_secs,
+                    max_secs: bounds.max_secs,
+                    ttl_secs: arms.best(penalty).ttl_secs,
+                    arms: arms
+                        .arms
+                        .iter()
+                        .map(|a| ArmReport {
+                            ttl_secs: a.ttl_secs,
+                            stores: a.stores,
+                            hits: a.hits,
+                            revalidations: a.revalidations,
+                            mismatches: a.mismatches,
+                            hits_per_store: a.hits_per_store(),
+                            mismatch_rate: a.mismatch_rate(),
+                            score: a.score(penalty),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        snapshot.sort_by(|a, b| a.method.cmp(&b.method));
+        snapshot
+    }
+}
+
+/// Re-fetch the request behind a cache hit from a backend and record whether
+/// the `cached` result, stored for `ttl`, was still current.
+pub async fn revalidate(
+    state: &AppState,
+    lookup: &CacheLookup,
+    request: Bytes,
+    cached: Bytes,
+    ttl: Duration,
+) {
+    let router_state = state.state.load_full();
+    let Some(backend) = router_state.select_backend(Some(&lookup.method)) else {
+        return;
+    };
+    let params = serde_json::from_slice::<Value>(&request)
+        .ok()
+        .and_then(|mut r| r.get_mut("params").map(Value::take))
+        .unwrap_or(Value::Null);
+    let canary = Canary {
+        name: lookup.method.clone(),
+        method: lookup.method.clone(),
+        params,
+    };
+    let timeout_after = Duration::from_secs(router_state.proxy_timeout_secs);
+    let result = match query_canary(
+        &state.client_for(&backend.config, false),
+        &backend.config,
+        &canary,
+        timeout_after,
+    )
+    .await
+    {
+        Ok(fresh) => {
+            let matched = serde_json::from_slice::<Value>(&cached)
+                .is_ok_and(|cached| comparable(cached) == fresh);
+            state
+                .ttl_tuner
+                .record_revalidation(&lookup.method, ttl, matched);
+            if matched {
+                "match"
+            } else {
+                "mismatch"
+            }
+        }
+        Err(e) => {
+            debug!(
+                "Revalidating cached {} on {} failed: {}",
+                lookup.method,
+                backend.config.label,
+                redact(&e)
+            );
+            "error"
+        }
+    };
+    counter!(
+        "rpc_cache_revalidations_total",
+        "rpc_method" => lookup.method.clone(),
+        "result" => result
+    )
+    .increment(1);
+}
@@ -0,0 +1,184 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use metrics::gauge;
+use serde::Serialize;
+use tokio::time::{interval, Duration};
+
+use crate::signing::unix_now;
+
+/// Width of one counting bucket.
+const BUCKET_SECS: u64 = 10;
+
+/// Rolling windows reported by the usage API and metrics, as (name, seconds).
+pub const WINDOWS: &[(&str, u64)] = &[("1m", 60), ("5m", 300), ("15m", 900)];
+
+/// Longest window; older buckets are dropped.
+const RETENTION_SECS: u64 = 900;
+
+/// Classification of one authenticated request's outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    /// Body was not a JSON-RPC request (no `method` could be extracted)
+    InvalidRequest,
+    /// Other 4xx returned to the client (blocked method, rate limit, ...)
+    ClientError,
+    /// 5xx returned to the client (backend failure, timeout, no backend)
+    ServerError,
+}
+
+impl Outcome {
+    pub fn classify(status: u16, valid_request: bool) -> Self {
+        if !valid_request {
+            Outcome::InvalidRequest
+        } else if status >= 500 {
+            Outcome::ServerError
+        } else if status >= 400 {
+            Outcome::ClientError
+        } else {
+            Outcome::Success
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct WindowStats {
+    pub requests: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    pub invalid_requests: u64,
+    pub client_error_rate: f64,
+    pub server_error_rate: f64,
+    pub invalid_request_rate: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    start: u64,
+    requests: u64,
+    client_errors: u64,
+    server_errors: u64,
+    invalid_requests: u64,
+}
+
+/// Per-key request outcome counters over rolling windows, used to spot clients
+/// that burn quota on failing or malformed requests.
+#[derive(Default)]
+pub struct UsageTracker {
+    keys: Mutex<HashMap<String, VecDeque<Bucket>>>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, owner: &str, outcome: Outcome, now: u64) {
+        let bucket_start = now - now % BUCKET_SECS;
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let buckets = keys.entry(owner.to_string()).or_default();
+
+        if buckets.back().map(|b| b.start) != Some(bucket_start) {
+            buckets.push_back(Bucket {
+                start: bucket_start,
+                ..Default::default()
+            });
+        }
+        while buckets
+            .front()
+            .is_some_and(|b| b.start + RETENTION_SECS <= bucket_start)
+        {
+            buckets.pop_front();
+        }
+
+        let bucket = buckets.back_mut().expect("bucket just pushed");
+        bucket.requests += 1;
+        match outcome {
+            Outcome::Success => {}
+            Outcome::InvalidRequest => bucket.invalid_requests += 1,
+            Outcome::ClientError => bucket.client_errors += 1,
+            Outcome::ServerError => bucket.server_errors += 1,
+        }
+    }
+
+    /// Counts and ratios for `owner` over the last `window_secs`.
+    pub fn stats(&self, owner: &str, window_secs: u64, now: u64) -> WindowStats {
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        window_stats(keys.get(owner).into_iter().flatten(), window_secs, now)
+    }
+
+    /// Stats for every window in [`WINDOWS`].
+    pub fn all_windows(&self, owner: &str, now: u64) -> Vec<(&'static str, WindowStats)> {
+        WINDOWS
+            .iter()
+            .map(|(name, secs)| (*name, self.stats(owner, *secs, now)))
+            .collect()
+    }
+
+    /// Stats for every window in [`WINDOWS`] for every tracked owner, taken
+    /// under one lock. Owners with nothing left in the retention period are
+    /// reported one last time (all zero) and then forgotten.
+    pub fn export(&self, now: u64) -> Vec<(String, Vec<(&'static str, WindowStats)>)> {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let snapshot = keys
+            .iter()
+            .map(|(owner, buckets)| {
+                let windows = WINDOWS
+                    .iter()
+                    .map(|(name, secs)| (*name, window_stats(buckets, *secs, now)))
+                    .collect();
+                (owner.clone(), windows)
+            })
+            .collect();
+        keys.retain(|_, buckets| {
+            buckets
+                .back()
+                .is_some_and(|b| b.start + RETENTION_SECS > now)
+        });
+        snapshot
+    }
+}
+
+fn window_stats<'a>(
+    buckets: impl IntoIterator<Item = &'a Bucket>,
+    window_secs: u64,
+    now: u64,
+) -> WindowStats {
+    let mut stats = WindowStats::default();
+    for bucket in buckets.into_iter().filter(|b| b.start + window_secs > now) {
+        stats.requests += bucket.requests;
+        stats.client_errors += bucket.client_errors;
+        stats.server_errors += bucket.server_errors;
+        stats.invalid_requests += bucket.invalid_requests;
+    }
+
+    if stats.requests > 0 {
+        let total = stats.requests as f64;
+        stats.client_error_rate = stats.client_errors as f64 / total;
+        stats.server_error_rate = stats.server_errors as f64 / total;
+        stats.invalid_request_rate = stats.invalid_requests as f64 / total;
+    }
+    stats
+}
+
+/// Exports `rpc_key_error_rate` once per bucket, so requests only bump counters.
+pub async fn usage_metrics_loop(tracker: Arc<UsageTracker>) {
+    let mut ticker = interval(Duration::from_secs(BUCKET_SECS));
+    loop {
+        ticker.tick().await;
+        for (owner, windows) in tracker.export(unix_now()) {
+            for (window, stats) in windows {
+                for (kind, rate) in [
+                    ("client", stats.client_error_rate),
+                    ("server", stats.server_error_rate),
+                    ("invalid", stats.invalid_request_rate),
+                ] {
+                    gauge!("rpc_key_error_rate", "owner" => owner.clone(), "window" => window, "kind" => kind).set(rate);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use axum::extract::ws::Message;
+use serde::{de::IgnoredAny, Deserialize};
+use serde_json::{json, Value};
+use tokio::sync::Notify;
+
+use crate::{config::Commitment, slot_feed::SlotView};
+
+/// Payload of the router's own pings, so their pongs are not forwarded upstream.
+pub const HEARTBEAT_PAYLOAD: &[u8] = b"rpc-router-heartbeat";
+
+#[derive(Deserialize)]
+struct Envelope {
+    method: Option<IgnoredAny>,
+    id: Option<IgnoredAny>,
+}
+
+/// Whether `text` is a JSON-RPC notification (a `method` and no `id`), i.e. a
+/// subscription update rather than a response the client is waiting for.
+pub fn is_notification(text: &str) -> bool {
+    serde_json::from_str::<Envelope>(text).is_ok_and(|e| e.method.is_some() && e.id.is_none())
+}
+
+fn is_notification_message(message: &Message) -> bool {
+    matches!(message, Message::Text(text) if is_notification(text))
+}
+
+/// Messages waiting to be written to one WebSocket client. Holds at most
+/// `capacity` messages; when full, the oldest queued notification is dropped to
+/// make room. Responses and control frames are never dropped.
+pub struct ClientQueue {
+    messages: Mutex<VecDeque<Message>>,
+    notify: Notify,
+    capacity: usize,
+}
+
+impl ClientQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            messages: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            capacity,
+        }
+    }
+
+    /// Queue `message`. Returns `true` if an older notification was dropped for it.
+    pub fn push(&self, message: Message) -> bool {
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        let mut dropped = false;
+        if messages.len() >= self.capacity {
+            if let Some(oldest) = messages.iter().position(is_notification_message) {
+                messages.remove(oldest);
+                dropped = true;
+            }
+        }
+        messages.push_back(message);
+        drop(messages);
+        self.notify.notify_one();
+        dropped
+    }
+
+    pub fn pop(&self) -> Option<Message> {
+        self.messages
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Resolves after the next `push` (or immediately if one happened since the last wait).
+    pub async fn pushed(&self) {
+        self.notify.notified().await
+    }
+}
+
+/// Timestamps shared between the two halves of a proxied connection, in
+/// milliseconds since it was opened.
+pub struct ConnectionActivity {
+    opened: Instant,
+    last_data_ms: AtomicU64,
+    last_pong_ms: AtomicU64,
+}
+
+impl Default for ConnectionActivity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectionActivity {
+    pub fn new() -> Self {
+        Self {
+            opened: Instant::now(),
+            last_data_ms: AtomicU64::new(0),
+            last_pong_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        self.opened.elapsed().as_millis() as u64
+    }
+
+    /// A text or binary message went either way.
+    pub fn data(&self) {
+        self.last_data_ms.store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    /// The client answered a heartbeat.
+    pub fn pong(&self) {
+        self.last_pong_ms.store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    pub fn last_data_ms(&self) -> u64 {
+        self.last_data_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn last_pong_ms(&self) -> u64 {
+        self.last_pong_ms.load(Ordering::Relaxed)
+    }
+}
+
+/// Notifications of subscriptions that can outrun a single client: every
+/// vote, every full block and every slot status change.
+pub const FIREHOSE_NOTIFICATIONS: [&str; 3] = [
+    "voteNotification",
+    "blockNotification",
+    "slotsUpdatesNotification",
+];
+
+/// Per-key limits on the firehose notifications one connection receives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FirehoseThrottle {
+    /// Deliver at most this many notifications per second of each kind; 0 = no limit
+    pub max_per_sec: u32,
+    /// Deliver only the first of every N notifications of each kind; 0 or 1 = all
+    pub sample_every: u32,
+}
+
+impl FirehoseThrottle {
+    pub fn is_empty(&self) -> bool {
+        self.max_per_sec == 0 && self.sample_every <= 1
+    }
+}
+
+#[derive(Deserialize)]
+struct NotificationMethod<'a> {
+    #[serde(borrow)]
+    method: Option<Cow<'a, str>>,
+    id: Option<IgnoredAny>,
+}
+
+struct FirehoseStream {
+    seen: u64,
+    window_start: Instant,
+    delivered: u32,
+}
+
+/// Applies a key's [`FirehoseThrottle`] to the messages of one connection.
+/// Each kind of firehose notification is sampled and rate-limited on its own;
+/// responses and all other notifications always pass.
+pub struct FirehoseThrottler {
+    throttle: FirehoseThrottle,
+    streams: HashMap<&'static str, FirehoseStream>,
+}
+
+impl FirehoseThrottler {
+    pub fn new(throttle: FirehoseThrottle) -> Self {
+        Self {
+            throttle,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Whether the backend message `text` should reach the client.
+    pub fn admit(&mut self, text: &str, now: Instant) -> bool {
+        let Ok(message) = serde_json::from_str::<NotificationMethod>(text) else {
+            return true;
+        };
+        let kind = match (&message.method, &message.id) {
+            (Some(method), None) => FIREHOSE_NOTIFICATIONS.iter().find(|f| **f == method),
+            _ => None,
+        };
+        let Some(kind) = kind else {
+            return true;
+        };
+
+        let stream = self.streams.entry(kind).or_insert(FirehoseStream {
+            seen: 0,
+            window_start: now,
+            delivered: 0,
+        });
+        stream.seen += 1;
+        if self.throttle.sample_every > 1
+            && !(stream.seen - 1).is_multiple_of(u64::from(self.throttle.sample_every))
+        {
+            return false;
+        }
+        if now.duration_since(stream.window_start).as_secs() >= 1 {
+            stream.window_start = now;
+            stream.delivered = 0;
+        }
+        if self.throttle.max_per_sec > 0 && stream.delivered >= self.throttle.max_per_sec {
+            return false;
+        }
+        stream.delivered += 1;
+        true
+    }
+}
+
+/// Ids of subscriptions served by the router start here, far above the
+/// counters validators hand out, so unsubscribes can be told apart.
+pub const LOCAL_SUBSCRIPTION_BASE: u64 = 1 << 52;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocalKind {
+    Slot,
+    Root,
+}
+
+struct LocalSubscription {
+    kind: LocalKind,
+    commitment: Commitment,
+    last_sent: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SubscribeConfig {
+    commitment: Option<Commitment>,
+}
+
+/// `slotSubscribe` and `rootSubscribe` subscriptions of one client, answered
+/// from the router's slot feed instead of upstream.
+pub struct LocalSubscriptions {
+    min_commitment: Commitment,
+    next_id: u64,
+    subscriptions: HashMap<u64, LocalSubscription>,
+}
+
+impl LocalSubscriptions {
+    /// `min_commitment` is the lowest commitment slot subscriptions report;
+    /// clients asking for less are upgraded to it.
+    pub fn new(min_commitment: Commitment) -> Self {
+        Self {
+            min_commitment,
+            next_id: LOCAL_SUBSCRIPTION_BASE,
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// The response to `text` if it is a request this router answers itself:
+    /// a slot or root subscription, or an unsubscribe from one. Everything
+    /// else (including batches) returns `None` and goes upstream.
+    pub fn handle_request(&mut self, text: &str) -> Option<String> {
+        let request: Value = serde_json::from_str(text).ok()?;
+        let method = request.get("method")?.as_str()?;
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let params = request.get("params");
+
+        let result = match method {
+            "slotSubscribe" => {
+                // Not part of Solana's slotSubscribe: an optional `{"commitment": ..}`
+                let requested = match params.and_then(|p| p.get(0)) {
+                    Some(config) => match serde_json::from_value::<SubscribeConfig>(config.clone())
+                    {
+                        Ok(config) => config.commitment,
+                        Err(e) => return Some(invalid_params(&id, &e.to_string())),
+                    },
+                    None => None,
+                };
+                let commitment =
+                    requested.map_or(self.min_commitment, |c| c.max(self.min_commitment));
+                json!(self.subscribe(LocalKind::Slot, commitment))
+            }
+            "rootSubscribe" => json!(self.subscribe(LocalKind::Root, Commitment::Finalized)),
+            "slotUnsubscribe" | "rootUnsubscribe" => {
+                let kind = if method == "slotUnsubscribe" {
+                    LocalKind::Slot
+                } else {
+                    LocalKind::Root
+                };
+                let subscription = params.and_then(|p| p.get(0)).and_then(Value::as_u64)?;
+                if subscription < LOCAL_SUBSCRIPTION_BASE {
+                    return None;
+                }
+                match self.subscriptions.get(&subscription) {
+                    Some(s) if s.kind == kind => {
+                        self.subscriptions.remove(&subscription);
+                        json!(true)
+                    }
+                    _ => return Some(invalid_params(&id, "Invalid subscription id.")),
+                }
+            }
+            _ => return None,
+        };
+        Some(json!({"jsonrpc": "2.0", "result": result, "id": id}).to_string())
+    }
+
+    fn subscribe(&mut self, kind: LocalKind, commitment: Commitment) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.subscriptions.insert(
+            id,
+            LocalSubscription {
+                kind,
+                commitment,
+                last_sent: None,
+            },
+        );
+        id
+    }
+
+    /// Notifications for every subscription whose slot advanced in `view`.
+    /// Each subscription only ever sees increasing slots at its commitment.
+    pub fn notifications(&mut self, view: &SlotView) -> Vec<String> {
+        let mut notifications = Vec::new();
+        for (id, subscription) in &mut self.subscriptions {
+            let Some(slot) = view.at(subscription.commitment) else {
+                continue;
+            };
+            if subscription.last_sent.is_some_and(|sent| sent >= slot) {
+                continue;
+            }
+            subscription.last_sent = Some(slot);
+            let (method, result) = match subscription.kind {
+                // The feed does not know the parent; skipped slots make this approximate
+                LocalKind::Slot => (
+                    "slotNotification",
+                    json!({
+                        "parent": slot.saturating_sub(1),
+                        "root": view.finalized.unwrap_or(0),
+                        "slot": slot,
+                    }),
+                ),
+                LocalKind::Root => ("rootNotification", json!(slot)),
+            };
+            notifications.push(
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": method,
+                    "params": {"result": result, "subscription": id},
+                })
+                .to_string(),
+            );
+        }
+        notifications
+    }
+}
+
+fn invalid_params(id: &Value, message: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {"code": -32602, "message": message},
+        "id": id,
+    })
+    .to_string()
+}
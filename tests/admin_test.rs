@@ -0,0 +1,1427 @@
+use std::{
+    io::{self, Write},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use arc_swap::ArcSwap;
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use sol_rpc_router::{
+    admin,
+    config::{load_config, AdminConfig, Backend, KillSwitchConfig, Subsystem, TtlBounds},
+    dead_letter::{DeadLetter, DeadLetterStore, FileDeadLetterStore},
+    health::{BackendHealthStatus, HealthLevel, HealthState},
+    journal::{FileJournalStore, JournalEntry, JournalStore},
+    key_usage::{KeyUsageEntry, KeyUsageStore},
+    keystore::KeyStore,
+    ledger::{day_number, day_string, SqliteUsageLedger, UsageEntry, UsageLedger},
+    mock::{MockKeyStore, MockKeyUsageStore},
+    redact::key_fingerprint,
+    reload::{router_state_from_config, ConfigSource, ReloadStatus},
+    server::operations_router,
+    signing::unix_now,
+    state::{AppState, RouterState, RuntimeBackend},
+};
+use tower::ServiceExt;
+use tracing_subscriber::fmt::MakeWriter;
+
+const TOKEN: &str = "admin-token-0123456789";
+
+fn admin_state(tokens: &[&str]) -> Arc<AppState> {
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let router_state = RouterState {
+        admin: AdminConfig {
+            tokens: tokens.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    Arc::new(AppState {
+        reload_status: Arc::new(ReloadStatus::new("config.toml")),
+        ..AppState::new(
+            client,
+            Arc::new(MockKeyStore::new()),
+            Arc::new(ArcSwap::from_pointee(router_state)),
+        )
+    })
+}
+
+fn app_with_state(state: Arc<AppState>) -> Router {
+    Router::new()
+        .nest("/admin", admin::router(state.clone()))
+        .with_state(state)
+}
+
+fn admin_app(tokens: &[&str]) -> Router {
+    app_with_state(admin_state(tokens))
+}
+
+fn status_request(token: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder().uri("/admin/config/status");
+    if let Some(token) = token {
+        builder = builder.header("authorization", format!("Bearer {}", token));
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_admin_disabled_without_tokens() {
+    let response = admin_app(&[])
+        .oneshot(status_request(Some(TOKEN)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_admin_requires_valid_token() {
+    let app = admin_app(&[TOKEN]);
+
+    let response = app.clone().oneshot(status_request(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .clone()
+        .oneshot(status_request(Some("wrong-token-0123456789")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app.oneshot(status_request(Some(TOKEN))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["config_path"], "config.toml");
+    assert_eq!(json["failures"], 0);
+}
+
+#[tokio::test]
+async fn test_admin_config_is_redacted() {
+    let mut path = std::env::temp_dir();
+    path.push("sol_rpc_router_test_admin_config.toml");
+    std::fs::write(
+        &path,
+        r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://:hunter2password@redis.internal:6379"
+
+[admin]
+tokens = ["admin-token-0123456789"]
+
+[[backends]]
+label = "signed"
+url = "https://rpc.example.com/?api-key=supersecretvalue"
+weight = 1
+
+[backends.signing]
+key = "signing-key-0123456789"
+"#,
+    )
+    .unwrap();
+    let config = load_config(path.to_str().unwrap()).unwrap();
+    let hash = config.hash.clone();
+
+    let state = admin_state(&[TOKEN]);
+    state.reload_status.set_active(Arc::new(config));
+    let response = app_with_state(state)
+        .oneshot(
+            Request::builder()
+                .uri("/admin/config")
+                .header("authorization", format!("Bearer {}", TOKEN))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    for secret in [
+        "hunter2password",
+        "supersecretvalue",
+        "signing-key-0123456789",
+        TOKEN,
+    ] {
+        assert!(!text.contains(secret), "leaked {}", secret);
+    }
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["hash"], hash);
+    assert_eq!(json["source"], "config.toml");
+    assert_eq!(json["config"]["backends"][0]["label"], "signed");
+}
+
+const SECOND_TOKEN: &str = "second-admin-token-0123";
+
+fn delete_request(uri: &str, token: &str, approval: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder()
+        .method("DELETE")
+        .uri(uri)
+        .header("authorization", format!("Bearer {}", token));
+    if let Some(approval) = approval {
+        builder = builder.header(admin::APPROVAL_HEADER, approval);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_destructive_actions_require_second_approver() {
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("customer-key", "customer", 100);
+    let backend = |label: &str| {
+        RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url: format!("http://{}", label),
+                weight: 1,
+                ..Default::default()
+            },
+            true,
+        )
+    };
+    let router_state = RouterState {
+        backends: vec![backend("primary"), backend("backup")],
+        admin: AdminConfig {
+            tokens: vec![TOKEN.to_string(), SECOND_TOKEN.to_string()],
+            dual_control: true,
+        },
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        client,
+        keystore.clone(),
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
+    let app = app_with_state(state.clone());
+
+    // No approval, self-approval and an unknown approver are all refused
+    for approval in [None, Some(TOKEN), Some("not-an-admin-token-000")] {
+        let response = app
+            .clone()
+            .oneshot(delete_request("/admin/keys/customer-key", TOKEN, approval))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+    assert!(keystore
+        .validate_key("customer-key")
+        .await
+        .unwrap()
+        .is_some());
+
+    let response = app
+        .clone()
+        .oneshot(delete_request(
+            "/admin/keys/customer-key",
+            TOKEN,
+            Some(SECOND_TOKEN),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(keystore
+        .validate_key("customer-key")
+        .await
+        .unwrap()
+        .is_none());
+
+    let response = app
+        .clone()
+        .oneshot(delete_request(
+            "/admin/backends/backup",
+            SECOND_TOKEN,
+            Some(TOKEN),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(state.state.load().backends.len(), 1);
+
+    // The last backend cannot be removed
+    let response = app
+        .oneshot(delete_request(
+            "/admin/backends/primary",
+            TOKEN,
+            Some(SECOND_TOKEN),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_routing_stats_endpoint() {
+    let state = admin_state(&[TOKEN]);
+    state.routing_stats.record(
+        "getProgramAccounts",
+        "provider-a",
+        true,
+        std::time::Duration::from_millis(40),
+        sol_rpc_router::signing::unix_now(),
+    );
+    let response = app_with_state(state)
+        .oneshot(
+            Request::builder()
+                .uri("/admin/routing-stats")
+                .header("authorization", format!("Bearer {}", TOKEN))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let stats = &json["methods"]["getProgramAccounts"]["provider-a"];
+    assert_eq!(stats["requests"], 1);
+    assert_eq!(stats["success_rate"], 1.0);
+    assert_eq!(stats["p50_ms"], 50);
+}
+
+fn push_request(token: &str, approval: Option<&str>, body: String) -> Request<Body> {
+    let mut builder = Request::builder()
+        .method("PUT")
+        .uri("/admin/config")
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/toml");
+    if let Some(approval) = approval {
+        builder = builder.header(admin::APPROVAL_HEADER, approval);
+    }
+    builder.body(Body::from(body)).unwrap()
+}
+
+#[tokio::test]
+async fn test_push_config_swaps_valid_documents_only() {
+    let document = |backends: &[&str]| {
+        let mut document = format!(
+            "port = 8080\nmetrics_port = 9091\nredis_url = \"redis://localhost\"\n\n[admin]\ntokens = [\"{}\", \"{}\"]\ndual_control = true\n",
+            TOKEN, SECOND_TOKEN
+        );
+        for label in backends {
+            document.push_str(&format!(
+                "\n[[backends]]\nlabel = \"{}\"\nurl = \"http://{}\"\nweight = 1\n",
+                label, label
+            ));
+        }
+        document
+    };
+    let state = admin_state(&[TOKEN, SECOND_TOKEN]);
+    let app = app_with_state(state.clone());
+
+    // Pushing is destructive: it needs a second approver under dual control
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.admin.dual_control = true;
+        next
+    });
+    let response = app
+        .clone()
+        .oneshot(push_request(TOKEN, None, document(&["pushed"])))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app
+        .clone()
+        .oneshot(push_request(
+            TOKEN,
+            Some(SECOND_TOKEN),
+            document(&["pushed"]),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["backends"], 1);
+    assert_eq!(state.state.load().backends[0].config.label, "pushed");
+    let report = state.reload_status.snapshot();
+    assert_eq!(json["hash"], report.config_hash.unwrap());
+    assert_eq!(report.config_source, Some(ConfigSource::Admin));
+
+    // An invalid document is rejected and the pushed config stays in place
+    let response = app
+        .clone()
+        .oneshot(push_request(TOKEN, Some(SECOND_TOKEN), document(&[])))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert!(json["error"].as_str().unwrap().contains("backend"));
+    assert_eq!(state.state.load().backends[0].config.label, "pushed");
+    assert_eq!(state.reload_status.snapshot().failures, 1);
+
+    // GET /admin/config still works without approval and shows the pushed document
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/admin/config")
+                .header("authorization", format!("Bearer {}", TOKEN))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["source"], "admin");
+    assert_eq!(json["config"]["backends"][0]["label"], "pushed");
+}
+
+#[tokio::test]
+async fn test_patch_backend() {
+    let mut path = std::env::temp_dir();
+    path.push("sol_rpc_router_test_admin_patch_backend.toml");
+    std::fs::write(
+        &path,
+        r#"port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[admin]
+tokens = ["admin-token-0123456789"]
+
+# Primary provider
+[[backends]]
+label = "primary"
+url = "http://primary.invalid"
+weight = 1
+
+[[backends]]
+label = "backup"
+url = "http://backup.invalid"
+weight = 1
+"#,
+    )
+    .unwrap();
+    let path = path.to_str().unwrap().to_string();
+    let config = load_config(&path).unwrap();
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let health_state = Arc::new(HealthState::new(vec![
+        "primary".to_string(),
+        "backup".to_string(),
+    ]));
+    let router_state = router_state_from_config(&config, health_state);
+    let state = Arc::new(AppState {
+        reload_status: Arc::new(ReloadStatus::new(&path)),
+        ..AppState::new(
+            client,
+            Arc::new(MockKeyStore::new()),
+            Arc::new(ArcSwap::from_pointee(router_state)),
+        )
+    });
+    state.reload_status.set_active(Arc::new(config));
+    let app = app_with_state(state.clone());
+    let patch = |label: &str, body: &str| {
+        Request::builder()
+            .method("PATCH")
+            .uri(format!("/admin/backends/{}", label))
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(patch("primary", r#"{"weight": 5, "drain": true}"#))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let current = state.state.load();
+    assert_eq!(current.backends[0].config.weight, 5);
+    assert!(!current.backends[0].in_rotation());
+    // Not persisted unless asked
+    assert!(!load_config(&path).unwrap().backends[0].drain);
+
+    // The backup is the last one in rotation
+    let response = app
+        .clone()
+        .oneshot(patch("backup", r#"{"maintenance": true}"#))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    for (label, body, status) in [
+        ("primary", r#"{"weight": 0}"#, StatusCode::BAD_REQUEST),
+        ("unknown", r#"{"weight": 2}"#, StatusCode::NOT_FOUND),
+    ] {
+        let response = app.clone().oneshot(patch(label, body)).await.unwrap();
+        assert_eq!(response.status(), status, "{}", body);
+    }
+
+    let response = app
+        .clone()
+        .oneshot(patch("backup", r#"{"weight": 3, "persist": true}"#))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(contents.contains("# Primary provider"));
+    let reloaded = load_config(&path).unwrap();
+    assert_eq!(reloaded.backends[1].weight, 3);
+    assert_eq!(reloaded.backends[0].weight, 1);
+
+    // Marking the last backend in rotation unhealthy is refused as well
+    let response = app
+        .clone()
+        .oneshot(patch("backup", r#"{"healthy": false}"#))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = app
+        .clone()
+        .oneshot(patch("primary", r#"{"drain": false}"#))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(patch("backup", r#"{"healthy": false}"#))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["healthy"], false);
+    let current = state.state.load();
+    assert!(!current.backends[1].in_rotation());
+    let status = current.health_state.get_status("backup").unwrap();
+    assert_eq!(status.level(), HealthLevel::Unhealthy);
+    assert_eq!(status.consecutive_successes, 0);
+
+    let response = app
+        .oneshot(patch("backup", r#"{"healthy": true}"#))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(state.state.load().backends[1].in_rotation());
+}
+
+fn get_request(uri: &str) -> Request<Body> {
+    Request::builder()
+        .uri(uri)
+        .header("authorization", format!("Bearer {}", TOKEN))
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_list_backends_and_routes() {
+    let state = admin_state(&[TOKEN]);
+    let health_state = Arc::new(HealthState::new(vec![
+        "primary".to_string(),
+        "backup".to_string(),
+    ]));
+    health_state.update_status(
+        "backup",
+        BackendHealthStatus {
+            healthy: false,
+            consecutive_failures: 3,
+            last_error: Some("connection refused".to_string()),
+            ..Default::default()
+        },
+    );
+    let backend = |label: &str, healthy: bool| {
+        RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url: format!("http://{}.invalid/?api-key=secretvalue", label),
+                weight: 2,
+                ..Default::default()
+            },
+            healthy,
+        )
+    };
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.backends = vec![backend("primary", true), backend("backup", false)];
+        next.health_state = health_state.clone();
+        next.method_routes = [("getBlock".to_string(), "backup".to_string())].into();
+        next.tier_routes = [(
+            "premium".to_string(),
+            [("getSlot".to_string(), "primary".to_string())].into(),
+        )]
+        .into();
+        next
+    });
+    let app = app_with_state(state);
+
+    let response = app
+        .clone()
+        .oneshot(get_request("/admin/backends"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let backends = json["backends"].as_array().unwrap();
+    assert_eq!(backends.len(), 2);
+    assert_eq!(backends[0]["label"], "primary");
+    assert_eq!(backends[0]["in_rotation"], true);
+    assert_eq!(backends[0]["status"], "healthy");
+    assert_eq!(backends[1]["in_rotation"], false);
+    assert_eq!(backends[1]["status"], "unhealthy");
+    assert_eq!(backends[1]["consecutive_failures"], 3);
+    assert_eq!(backends[1]["last_error"], "connection refused");
+    assert_eq!(backends[1]["circuit"], "closed");
+    assert!(!backends[1]["url"].as_str().unwrap().contains("secretvalue"));
+
+    let response = app.oneshot(get_request("/admin/routes")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["method_routes"]["getBlock"], "backup");
+    assert_eq!(json["tier_routes"]["premium"]["getSlot"], "primary");
+}
+
+#[tokio::test]
+async fn test_reload_config_from_file() {
+    let mut path = std::env::temp_dir();
+    path.push("sol_rpc_router_test_admin_reload.toml");
+    let document = |label: &str| {
+        format!(
+            "port = 8080\nmetrics_port = 9091\nredis_url = \"redis://localhost\"\n\n[admin]\ntokens = [\"{}\"]\n\n[[backends]]\nlabel = \"{}\"\nurl = \"http://{}\"\nweight = 1\n",
+            TOKEN, label, label
+        )
+    };
+    std::fs::write(&path, document("before")).unwrap();
+    let path = path.to_str().unwrap().to_string();
+    let config = load_config(&path).unwrap();
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let health_state = Arc::new(HealthState::new(vec!["before".to_string()]));
+    let router_state = router_state_from_config(&config, health_state);
+    let state = Arc::new(AppState {
+        reload_status: Arc::new(ReloadStatus::new(&path)),
+        ..AppState::new(
+            client,
+            Arc::new(MockKeyStore::new()),
+            Arc::new(ArcSwap::from_pointee(router_state)),
+        )
+    });
+    let app = app_with_state(state.clone());
+    let reload = || {
+        Request::builder()
+            .method("POST")
+            .uri("/admin/config/reload")
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    std::fs::write(&path, document("after")).unwrap();
+    let response = app.clone().oneshot(reload()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(state.state.load().backends[0].config.label, "after");
+    let report = state.reload_status.snapshot();
+    assert_eq!(report.config_source, Some(ConfigSource::File));
+
+    // A broken file leaves the running config in place
+    std::fs::write(&path, "port = \"not a number\"").unwrap();
+    let response = app.oneshot(reload()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(state.state.load().backends[0].config.label, "after");
+}
+
+#[tokio::test]
+async fn test_audit_toggle() {
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("customer-key", "customer", 100);
+    let router_state = RouterState {
+        admin: AdminConfig {
+            tokens: vec![TOKEN.to_string(), SECOND_TOKEN.to_string()],
+            dual_control: true,
+        },
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        client,
+        keystore.clone(),
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
+    let app = app_with_state(state);
+    let request = |method: &str, key: &str| {
+        Request::builder()
+            .method(method)
+            .uri(format!("/admin/keys/{}/audit", key))
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let audited = || async {
+        keystore
+            .validate_key("customer-key")
+            .await
+            .unwrap()
+            .unwrap()
+            .audit
+    };
+
+    // Not destructive: no second approver needed
+    let response = app
+        .clone()
+        .oneshot(request("PUT", "customer-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(audited().await);
+
+    let response = app
+        .clone()
+        .oneshot(request("DELETE", "customer-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!audited().await);
+
+    let response = app.oneshot(request("PUT", "unknown-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_kill_switches() {
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let router_state = RouterState {
+        admin: AdminConfig {
+            tokens: vec![TOKEN.to_string()],
+            ..Default::default()
+        },
+        kill_switches: KillSwitchConfig {
+            disabled: vec![Subsystem::Retries],
+        },
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        client,
+        Arc::new(MockKeyStore::new()),
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
+    let app = app_with_state(state.clone());
+    let request = |method: &str, path: &str| {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let disabled = |subsystem| state.is_disabled(&state.state.load(), subsystem);
+
+    let response = app
+        .clone()
+        .oneshot(request("PUT", "/admin/kill-switches/hedging"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(disabled(Subsystem::Hedging));
+    assert!(!disabled(Subsystem::Cache));
+
+    let response = app
+        .clone()
+        .oneshot(request("GET", "/admin/kill-switches"))
+        .await
+        .unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let switches = &json["kill_switches"];
+    assert_eq!(switches["hedging"]["runtime"], true);
+    assert_eq!(switches["retries"]["config"], true);
+    assert_eq!(switches["cache"]["disabled"], false);
+
+    // Releasing a switch set by the config leaves the subsystem off
+    for path in [
+        "/admin/kill-switches/hedging",
+        "/admin/kill-switches/retries",
+    ] {
+        let response = app.clone().oneshot(request("DELETE", path)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert!(!disabled(Subsystem::Hedging));
+    assert!(disabled(Subsystem::Retries));
+
+    let response = app
+        .oneshot(request("PUT", "/admin/kill-switches/coalescing"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_dead_letter_endpoints() {
+    let get = |uri: &str| {
+        Request::builder()
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Disabled unless a store is configured
+    let response = admin_app(&[TOKEN])
+        .oneshot(get("/admin/dead-letters"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let mut path = std::env::temp_dir();
+    path.push("sol_rpc_router_test_admin_dead_letters.jsonl");
+    let _ = std::fs::remove_file(&path);
+    let store = Arc::new(FileDeadLetterStore::new(&path));
+    for tx in ["AQID", "BAUG"] {
+        store
+            .push(&DeadLetter {
+                id: String::new(),
+                captured_at: unix_now(),
+                owner: "trader".to_string(),
+                backend: None,
+                status: 503,
+                error: "No healthy backends available".to_string(),
+                request: format!(
+                    r#"{{"jsonrpc":"2.0","method":"sendTransaction","params":["{}"],"id":1}}"#,
+                    tx
+                ),
+            })
+            .await
+            .unwrap();
+    }
+    let state = Arc::new(AppState {
+        dead_letters: Some(store),
+        ..(*admin_state(&[TOKEN])).clone()
+    });
+    let app = app_with_state(state);
+
+    let response = app
+        .clone()
+        .oneshot(get("/admin/dead-letters?limit=1"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let letters = json["dead_letters"].as_array().unwrap();
+    assert_eq!(letters.len(), 1);
+    assert!(letters[0]["request"].as_str().unwrap().contains("BAUG"));
+
+    let id = letters[0]["id"].as_str().unwrap();
+    let response = app
+        .clone()
+        .oneshot(get(&format!("/admin/dead-letters/{}", id)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["error"], "No healthy backends available");
+
+    let response = app.oneshot(get("/admin/dead-letters/0-0")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_journal_endpoint() {
+    let get = |uri: &str| {
+        Request::builder()
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Disabled unless a journal is configured
+    let response = admin_app(&[TOKEN])
+        .oneshot(get("/admin/journal/5igned"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let mut path = std::env::temp_dir();
+    path.push("sol_rpc_router_test_admin_journal.jsonl");
+    let _ = std::fs::remove_file(&path);
+    let journal = Arc::new(FileJournalStore::new(&path, 3_600));
+    let mut entry = JournalEntry::new(
+        "5igned".to_string(),
+        "key-fingerprint".to_string(),
+        "trader".to_string(),
+        unix_now(),
+        None,
+    );
+    entry.record(
+        Some("primary"),
+        200,
+        br#"{"jsonrpc":"2.0","result":"5igned","id":1}"#,
+        unix_now(),
+        false,
+    );
+    journal.put(&entry).await.unwrap();
+    let state = Arc::new(AppState {
+        journal: Some(journal),
+        ..(*admin_state(&[TOKEN])).clone()
+    });
+    let app = app_with_state(state);
+
+    let response = app
+        .clone()
+        .oneshot(get("/admin/journal/5igned"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["status"], "accepted");
+    assert_eq!(json["owner"], "trader");
+    assert_eq!(json["outcomes"][0]["backend"], "primary");
+
+    let response = app.oneshot(get("/admin/journal/unknown")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_usage_endpoint() {
+    let get = |uri: &str| {
+        Request::builder()
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Disabled unless a ledger is configured
+    let response = admin_app(&[TOKEN])
+        .oneshot(get("/admin/usage"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let mut path = std::env::temp_dir();
+    path.push("sol_rpc_router_test_admin_usage.db");
+    let _ = std::fs::remove_file(&path);
+    let ledger = Arc::new(SqliteUsageLedger::open(path.to_str().unwrap()).unwrap());
+    let today = day_number(unix_now());
+    let entry = |owner: &str, day: u64, requests: u64| UsageEntry {
+        owner: owner.to_string(),
+        day: day_string(day),
+        requests,
+        errors: 0,
+    };
+    ledger
+        .add(&[
+            entry("trader", today, 5),
+            entry("trader", today - 3, 2),
+            entry("indexer", today, 9),
+        ])
+        .await
+        .unwrap();
+    let state = Arc::new(AppState {
+        usage_ledger: Some(ledger),
+        ..(*admin_state(&[TOKEN])).clone()
+    });
+    let app = app_with_state(state);
+
+    let response = app
+        .clone()
+        .oneshot(get("/admin/usage?owner=trader&days=2"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["to"], day_string(today));
+    assert_eq!(json["from"], day_string(today - 1));
+    assert_eq!(
+        json["usage"],
+        serde_json::json!([{"owner": "trader", "day": day_string(today), "requests": 5, "errors": 0}])
+    );
+
+    let response = app.oneshot(get("/admin/usage")).await.unwrap();
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["usage"].as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_key_usage_endpoint() {
+    let get = |uri: &str| {
+        Request::builder()
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Disabled unless key usage accounting is enabled
+    let response = admin_app(&[TOKEN])
+        .oneshot(get("/admin/keys/key-a/usage"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let store = Arc::new(MockKeyUsageStore::new());
+    let today = day_number(unix_now());
+    let entry = |day: u64, method: &str, requests: u64, bytes: u64| KeyUsageEntry {
+        key: "key-a".to_string(),
+        day: day_string(day),
+        method: method.to_string(),
+        requests,
+        response_bytes: bytes,
+    };
+    store
+        .add(&[
+            entry(today, "getSlot", 5, 500),
+            entry(today - 1, "getSlot", 2, 200),
+            entry(today - 1, "getBalance", 1, 40),
+            entry(today - 40, "getSlot", 9, 900),
+        ])
+        .await
+        .unwrap();
+    let state = Arc::new(AppState {
+        key_usage: Some(store),
+        ..(*admin_state(&[TOKEN])).clone()
+    });
+    let app = app_with_state(state);
+
+    let response = app
+        .clone()
+        .oneshot(get("/admin/keys/key-a/usage"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["key"], key_fingerprint("key-a"));
+    assert_eq!(json["to"], day_string(today));
+    assert_eq!(json["from"], day_string(today - 29));
+    assert_eq!(json["usage"].as_array().unwrap().len(), 3);
+    assert_eq!(json["usage"][0]["day"], day_string(today));
+    assert_eq!(
+        json["totals"],
+        serde_json::json!([
+            {"method": "getBalance", "requests": 1, "response_bytes": 40},
+            {"method": "getSlot", "requests": 7, "response_bytes": 700},
+        ])
+    );
+
+    let uri = format!(
+        "/admin/keys/key-a/usage?from={}&to={}",
+        day_string(today - 40),
+        day_string(today - 40)
+    );
+    let response = app.clone().oneshot(get(&uri)).await.unwrap();
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["totals"][0]["requests"], 9);
+
+    for bad in [
+        "from=2024-02-30",
+        "to=yesterday",
+        "from=2024-06-10&to=2024-06-01",
+        "from=2020-01-01&to=2024-01-01",
+    ] {
+        let uri = format!("/admin/keys/key-a/usage?{}", bad);
+        let response = app.clone().oneshot(get(&uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", bad);
+    }
+}
+
+#[tokio::test]
+async fn test_traffic_endpoint() {
+    let get = |uri: &str| {
+        Request::builder()
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Disabled unless configured
+    let state = admin_state(&[TOKEN]);
+    let app = app_with_state(state.clone());
+    let response = app.clone().oneshot(get("/admin/traffic")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.traffic_report.enabled = true;
+        next
+    });
+    let now = unix_now();
+    state
+        .traffic
+        .record_request(Some("getBlock"), 120, now, 86_400);
+    state.traffic.record_response(Some("getBlock"), 5_000, now);
+
+    let response = app
+        .clone()
+        .oneshot(get("/admin/traffic?hours=2"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["methods"][0]["method"], "getBlock");
+    assert_eq!(json["methods"][0]["response_bytes"], 5_000);
+    assert_eq!(json["total"]["requests"], 1);
+    assert_eq!(
+        json["to"].as_u64().unwrap() - json["from"].as_u64().unwrap(),
+        7200
+    );
+
+    let response = app
+        .clone()
+        .oneshot(get("/admin/traffic?format=csv"))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "text/csv");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let csv = String::from_utf8(body.to_vec()).unwrap();
+    assert!(csv.starts_with("method,requests,"));
+    assert!(csv.contains("\ngetBlock,1,120,5000,120,5000,"));
+
+    let response = app.oneshot(get("/admin/traffic?format=xml")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_latency_heatmap_endpoint() {
+    let get = |uri: &str| {
+        Request::builder()
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Disabled unless configured
+    let state = admin_state(&[TOKEN]);
+    let app = app_with_state(state.clone());
+    let response = app
+        .clone()
+        .oneshot(get("/admin/latency-heatmap?method=getSlot"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.latency_heatmap.enabled = true;
+        next
+    });
+    let now = unix_now();
+    state
+        .latency_heatmap
+        .record("getSlot", "a", Duration::from_millis(30), now, 60, 60);
+
+    let response = app
+        .clone()
+        .oneshot(get("/admin/latency-heatmap?method=getSlot&windows=5"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["method"], "getSlot");
+    assert_eq!(json["window_secs"], 60);
+    assert_eq!(json["windows"].as_array().unwrap().len(), 5);
+    assert_eq!(json["backends"][0]["backend"], "a");
+    assert_eq!(json["backends"][0]["requests"], 1);
+    assert_eq!(json["backends"][0]["counts"][4][5], 1);
+
+    for uri in [
+        "/admin/latency-heatmap",
+        "/admin/latency-heatmap?method=getEverything",
+    ] {
+        let response = app.clone().oneshot(get(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_cache_ttls_endpoint() {
+    let get = || {
+        Request::builder()
+            .uri("/admin/cache/ttls")
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Disabled unless configured
+    let state = admin_state(&[TOKEN]);
+    let app = app_with_state(state.clone());
+    let response = app.clone().oneshot(get()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.cache.ttl_tuning.enabled = true;
+        next.cache.ttl_tuning.explore_percent = 0;
+        next.cache.ttl_tuning.methods.insert(
+            "getVersion".to_string(),
+            TtlBounds {
+                min_secs: 10,
+                max_secs: 30,
+            },
+        );
+        next
+    });
+    let config = state.state.load().cache.ttl_tuning.clone();
+    let ttl = state.ttl_tuner.choose("getVersion", &config).unwrap();
+    state.ttl_tuner.record_store("getVersion", ttl);
+    state.ttl_tuner.record_hit("getVersion", ttl);
+
+    let response = app.oneshot(get()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json[0]["method"], "getVersion");
+    // The untried 20s TTL is next
+    assert_eq!(json[0]["ttl_secs"], 20);
+    let ttls: Vec<u64> = json[0]["arms"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["ttl_secs"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ttls, [10, 20, 30]);
+    assert_eq!(json[0]["arms"][0]["hits"], 1);
+}
+
+#[tokio::test]
+async fn test_test_request_endpoint() {
+    // Each backend answers with its own label
+    let mut backends = Vec::new();
+    for label in ["primary", "standby"] {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = Router::new().route(
+                "/",
+                axum::routing::post(move || async move {
+                    format!(r#"{{"jsonrpc":"2.0","result":"{}","id":1}}"#, label)
+                }),
+            );
+            axum::serve(listener, app).await.unwrap();
+        });
+        backends.push(RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url,
+                weight: 1,
+                maintenance: label == "standby",
+                ..Default::default()
+            },
+            true,
+        ));
+    }
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let router_state = RouterState {
+        backends,
+        health_state: Arc::new(HealthState::new(vec![
+            "primary".to_string(),
+            "standby".to_string(),
+        ])),
+        admin: AdminConfig {
+            tokens: vec![TOKEN.to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        client,
+        keystore,
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
+    let app = app_with_state(state);
+    let send = |body: serde_json::Value| {
+        let req = Request::builder()
+            .method("POST")
+            .uri("/admin/test-request")
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(req).await.unwrap();
+            let status = response.status();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let json = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+            (status, json)
+        }
+    };
+    let request = serde_json::json!({"jsonrpc": "2.0", "method": "getSlot", "id": 1});
+
+    // Routed like any request: only the primary is in rotation
+    let (status, json) = send(serde_json::json!({"api_key": "test-key", "request": request})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["status"], 200);
+    assert_eq!(json["backend"], "primary");
+    assert_eq!(json["pinned"], false);
+    assert_eq!(json["response"]["result"], "primary");
+    assert!(json["timings_ms"]["upstream"].as_f64().is_some());
+    assert!(json["headers"]["x-request-id"].is_string());
+
+    // A chosen backend answers even while out of rotation
+    let (status, json) = send(serde_json::json!({
+        "api_key": "test-key",
+        "request": request,
+        "backend": "standby",
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["backend"], "standby");
+    assert_eq!(json["response"]["result"], "standby");
+
+    // The key is checked like any other
+    let (_, json) = send(serde_json::json!({"api_key": "wrong-key", "request": request})).await;
+    assert_eq!(json["status"], 401);
+
+    let (status, _) = send(serde_json::json!({
+        "api_key": "test-key",
+        "request": request,
+        "backend": "missing",
+    }))
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_profile_endpoints() {
+    let get = |uri: &str| {
+        Request::builder()
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let app = admin_app(&[TOKEN]);
+
+    for uri in [
+        "/admin/profile/cpu?seconds=0",
+        "/admin/profile/cpu?seconds=301",
+        "/admin/profile/cpu?frequency=5000",
+        "/admin/profile/cpu?format=perf",
+    ] {
+        let response = app.clone().oneshot(get(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+    }
+
+    // Test binaries do not start jemalloc with profiling on
+    let response = app
+        .clone()
+        .oneshot(get("/admin/profile/heap"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+
+    let uri = "/admin/profile/cpu?seconds=1&format=flamegraph";
+    if cfg!(feature = "profiling") {
+        let first = tokio::spawn(app.clone().oneshot(get(uri)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // One profile at a time
+        let second = app.clone().oneshot(get(uri)).await.unwrap();
+        assert_eq!(second.status(), StatusCode::CONFLICT);
+        // Something to sample while the profile runs
+        let started = std::time::Instant::now();
+        let mut x = 0u64;
+        while started.elapsed() < Duration::from_millis(800) {
+            for _ in 0..1_000_000 {
+                x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(7));
+            }
+        }
+        let first = first.await.unwrap().unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(first.headers()["content-type"], "image/svg+xml");
+        let body = first.into_body().collect().await.unwrap().to_bytes();
+        assert!(String::from_utf8_lossy(&body).contains("<svg"));
+    } else {
+        let response = app.clone().oneshot(get(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+    }
+}
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Capture {
+    type Writer = Capture;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn test_admin_logs_leave_out_api_keys() {
+    const KEY: &str = "customer-key-0123456789";
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(capture.clone())
+        .with_ansi(false)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    // Each request, with the route it is logged as
+    let requests = [
+        ("DELETE", "/admin/keys/:key"),
+        ("PUT", "/admin/keys/:key/audit"),
+        ("GET", "/admin/keys/:key/usage"),
+    ];
+
+    let app = operations_router(admin_state(&[TOKEN]), None);
+    for (method, route) in requests {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(route.replace(":key", KEY))
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))));
+        app.clone().oneshot(req).await.unwrap();
+    }
+
+    let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+    assert!(!logs.contains(KEY), "{}", logs);
+    assert!(
+        logs.contains(&format!(
+            "Admin action DELETE /admin/keys/:key key={}",
+            key_fingerprint(KEY)
+        )),
+        "{}",
+        logs
+    );
+    for (method, route) in requests {
+        let line = format!("{} {} ", method, route);
+        assert!(logs.contains(&line), "{}: {}", line, logs);
+    }
+}
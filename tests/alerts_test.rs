@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use axum::{routing::post, Json, Router};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use sol_rpc_router::{
+    alerts::{metric_value, send_webhook, AlertEngine, AlertStatus, AlertTransition},
+    config::{AlertMetric, AlertOp, AlertRule, Backend},
+    consistency::ConsistencyState,
+    state::{RouterState, RuntimeBackend},
+    stats::RoutingStats,
+};
+use tokio::sync::mpsc;
+
+fn rule(name: &str, metric: AlertMetric, op: AlertOp, threshold: f64, for_secs: u64) -> AlertRule {
+    AlertRule {
+        name: name.to_string(),
+        metric,
+        op,
+        threshold,
+        for_secs,
+        method: metric.per_method().then(|| "getSlot".to_string()),
+        backend: None,
+        webhook_url: "http://127.0.0.1:1/hook".to_string(),
+    }
+}
+
+#[test]
+fn test_alert_fires_after_duration_and_resolves() {
+    let engine = AlertEngine::new();
+    let rules = [rule(
+        "down",
+        AlertMetric::UnhealthyBackends,
+        AlertOp::Ge,
+        2.0,
+        60,
+    )];
+
+    // Holding, but not for long enough yet
+    assert!(engine.evaluate(&rules, |_| Some(2.0), 1_000).is_empty());
+    assert_eq!(engine.snapshot()["down"].status, AlertStatus::Pending);
+    assert!(engine.evaluate(&rules, |_| Some(3.0), 1_050).is_empty());
+
+    let events = engine.evaluate(&rules, |_| Some(2.0), 1_060);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].status, AlertTransition::Firing);
+    assert_eq!(events[0].since, Some(1_000));
+
+    // Firing is reported once
+    assert!(engine.evaluate(&rules, |_| Some(2.0), 1_070).is_empty());
+
+    let events = engine.evaluate(&rules, |_| Some(0.0), 1_080);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].status, AlertTransition::Resolved);
+    assert_eq!(engine.snapshot()["down"].status, AlertStatus::Ok);
+}
+
+#[test]
+fn test_alert_pending_resets_when_condition_clears() {
+    let engine = AlertEngine::new();
+    let rules = [rule("slow", AlertMetric::P99Ms, AlertOp::Gt, 500.0, 300)];
+
+    engine.evaluate(&rules, |_| Some(1_000.0), 0);
+    // No data never satisfies a condition
+    engine.evaluate(&rules, |_| None, 200);
+    assert!(engine.evaluate(&rules, |_| Some(1_000.0), 310).is_empty());
+    assert_eq!(engine.snapshot()["slow"].since, Some(310));
+
+    // Rules removed from the config are forgotten
+    engine.evaluate(&[], |_| None, 320);
+    assert!(engine.snapshot().is_empty());
+}
+
+#[test]
+fn test_metric_values() {
+    let backend = |label: &str, healthy: bool| {
+        RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url: format!("http://{}", label),
+                weight: 1,
+                ..Default::default()
+            },
+            healthy,
+        )
+    };
+    let router_state = RouterState {
+        backends: vec![backend("a", true), backend("b", false), backend("c", false)],
+        ..Default::default()
+    };
+    let stats = RoutingStats::new();
+    let consistency = ConsistencyState::new();
+    let now = 1_000;
+    let value = |rule: &AlertRule| metric_value(rule, &router_state, &stats, &consistency, now);
+    for _ in 0..9 {
+        stats.record("getSlot", "a", true, Duration::from_millis(20), now);
+    }
+    stats.record("getSlot", "b", false, Duration::from_millis(20), now);
+
+    let down = rule("down", AlertMetric::UnhealthyBackends, AlertOp::Ge, 2.0, 0);
+    assert_eq!(value(&down), Some(2.0));
+
+    let success = rule("errors", AlertMetric::SuccessRate, AlertOp::Lt, 0.95, 0);
+    assert_eq!(value(&success), Some(0.9));
+    let on_a = AlertRule {
+        backend: Some("a".to_string()),
+        ..success.clone()
+    };
+    assert_eq!(value(&on_a), Some(1.0));
+
+    let p99 = rule("slow", AlertMetric::P99Ms, AlertOp::Gt, 500.0, 0);
+    assert_eq!(value(&p99), Some(25.0));
+    let unused = AlertRule {
+        method: Some("getBlock".to_string()),
+        ..p99
+    };
+    assert_eq!(value(&unused), None);
+
+    // Counted once however many canaries a backend diverges on
+    let forked = rule(
+        "forked",
+        AlertMetric::DivergentBackends,
+        AlertOp::Ge,
+        1.0,
+        0,
+    );
+    assert_eq!(value(&forked), Some(0.0));
+    consistency.record("balance", vec!["b".to_string()]);
+    consistency.record("supply", vec!["b".to_string(), "c".to_string()]);
+    assert_eq!(value(&forked), Some(2.0));
+}
+
+#[tokio::test]
+async fn test_webhook_receives_event() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(payload): Json<serde_json::Value>| async move {
+                tx.send(payload).unwrap();
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let engine = AlertEngine::new();
+    let rules = [AlertRule {
+        webhook_url: url,
+        ..rule("down", AlertMetric::UnhealthyBackends, AlertOp::Ge, 1.0, 0)
+    }];
+    let event = engine.evaluate(&rules, |_| Some(1.0), 1_000).remove(0);
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    send_webhook(&client, &event).await.unwrap();
+    let payload = rx.recv().await.unwrap();
+    assert_eq!(payload["alert"], "down");
+    assert_eq!(payload["status"], "firing");
+    assert_eq!(payload["metric"], "unhealthy_backends");
+    assert_eq!(payload["op"], ">=");
+    assert_eq!(payload["value"], 1.0);
+
+    // Unreachable webhooks are reported as errors
+    let dead = AlertRule {
+        webhook_url: "http://127.0.0.1:1/hook".to_string(),
+        ..rules[0].clone()
+    };
+    let event = AlertEngine::new()
+        .evaluate(&[dead], |_| Some(1.0), 1_000)
+        .remove(0);
+    assert!(send_webhook(&client, &event).await.is_err());
+}
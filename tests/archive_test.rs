@@ -0,0 +1,39 @@
+use sol_rpc_router::archive::{is_deep, is_slot_method, requested_slot};
+
+#[test]
+fn test_requested_slot() {
+    assert_eq!(
+        requested_slot(
+            br#"{"jsonrpc":"2.0","id":1,"method":"getBlock","params":[430,{"encoding":"json"}]}"#
+        ),
+        Some(430)
+    );
+    assert_eq!(
+        requested_slot(br#"{"jsonrpc":"2.0","id":1,"method":"getBlocks","params":[5,10]}"#),
+        Some(5)
+    );
+    assert_eq!(
+        requested_slot(br#"{"jsonrpc":"2.0","id":1,"method":"getBlock"}"#),
+        None
+    );
+    assert_eq!(
+        requested_slot(br#"{"jsonrpc":"2.0","id":1,"method":"getBlock","params":["430"]}"#),
+        None
+    );
+    // Batches are not inspected
+    assert_eq!(
+        requested_slot(br#"[{"jsonrpc":"2.0","id":1,"method":"getBlock","params":[430]}]"#),
+        None
+    );
+}
+
+#[test]
+fn test_is_deep() {
+    assert!(is_slot_method("getBlock"));
+    assert!(!is_slot_method("getTransaction"));
+
+    assert!(is_deep(1_000, 101_000, 100_000));
+    assert!(!is_deep(1_001, 101_000, 100_000));
+    // A slot ahead of the tip is never deep
+    assert!(!is_deep(200_000, 101_000, 100_000));
+}
@@ -0,0 +1,40 @@
+use sol_rpc_router::audit::{params_hash, LogPrivacy, PRIVACY_LEVELS};
+
+#[test]
+fn test_params_hash() {
+    let hash = params_hash(br#"{"jsonrpc":"2.0","method":"getBalance","params":["Acc1"],"id":1}"#)
+        .unwrap();
+    assert_eq!(hash.len(), 12);
+
+    // Only the params count: other ids and methods hash the same
+    assert_eq!(
+        params_hash(br#"{"jsonrpc":"2.0","method":"getAccountInfo","params":["Acc1"],"id":7}"#),
+        Some(hash.clone())
+    );
+    assert_ne!(
+        params_hash(br#"{"jsonrpc":"2.0","method":"getBalance","params":["Acc2"],"id":1}"#),
+        Some(hash.clone())
+    );
+
+    let batch =
+        params_hash(br#"[{"method":"getBalance","params":["Acc1"]},{"method":"getSlot"}, 7]"#)
+            .unwrap();
+    assert_ne!(batch, hash);
+
+    assert_eq!(params_hash(b"not json"), None);
+    assert!(params_hash(b"\"just a string\"").is_some());
+}
+
+#[test]
+fn test_log_privacy_levels() {
+    for level in PRIVACY_LEVELS {
+        assert_eq!(LogPrivacy::parse(level).unwrap().as_str(), *level);
+    }
+    assert!(LogPrivacy::parse("hidden").is_err());
+    assert_eq!(LogPrivacy::default(), LogPrivacy::Full);
+
+    assert!(!LogPrivacy::None.allows_metadata());
+    assert!(LogPrivacy::Metadata.allows_metadata());
+    assert!(!LogPrivacy::Metadata.allows_params());
+    assert!(LogPrivacy::Full.allows_params());
+}
@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+
+use sol_rpc_router::{
+    auto_route::compute_weights, config::RoutingConfig, stats::BackendMethodStats,
+};
+
+fn stats(requests: u64, success_rate: f64, p50_ms: u64) -> BackendMethodStats {
+    BackendMethodStats {
+        requests,
+        success_rate,
+        p50_ms: Some(p50_ms),
+        p99_ms: Some(p50_ms * 4),
+    }
+}
+
+#[test]
+fn test_faster_backend_gets_larger_share() {
+    let snapshot = BTreeMap::from([(
+        "getProgramAccounts".to_string(),
+        BTreeMap::from([
+            ("fast".to_string(), stats(100, 1.0, 100)),
+            ("slow".to_string(), stats(100, 1.0, 400)),
+            ("new".to_string(), stats(3, 1.0, 10)),
+        ]),
+    )]);
+    let weights = compute_weights(&snapshot, &RoutingConfig::default());
+    let shares = &weights["getProgramAccounts"];
+
+    // Each keeps 5%; the remaining 90% splits 4:1 by score
+    assert_eq!(shares["fast"], 500 + 7_200);
+    assert_eq!(shares["slow"], 500 + 1_800);
+    // Too few samples to learn from
+    assert!(!shares.contains_key("new"));
+}
+
+#[test]
+fn test_failing_backend_keeps_only_minimum_share() {
+    let snapshot = BTreeMap::from([(
+        "getBlock".to_string(),
+        BTreeMap::from([
+            ("ok".to_string(), stats(50, 1.0, 200)),
+            (
+                "broken".to_string(),
+                BackendMethodStats {
+                    requests: 50,
+                    success_rate: 0.0,
+                    p50_ms: None,
+                    p99_ms: None,
+                },
+            ),
+        ]),
+    )]);
+    let config = RoutingConfig {
+        min_share_percent: 10,
+        ..Default::default()
+    };
+    let shares = &compute_weights(&snapshot, &config)["getBlock"];
+    assert_eq!(shares["broken"], 1_000);
+    assert_eq!(shares["ok"], 9_000);
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use http_body_util::BodyExt;
+use sol_rpc_router::backpressure::{QueueDepth, RetryHint, CORRELATION_HEADER, SHED_ERROR_CODE};
+
+#[test]
+fn test_queue_depth_classes() {
+    assert_eq!(QueueDepth::classify(0, 4), QueueDepth::Empty);
+    assert_eq!(QueueDepth::classify(3, 4), QueueDepth::Low);
+    assert_eq!(QueueDepth::classify(4, 4), QueueDepth::High);
+    assert_eq!(QueueDepth::classify(40, 4), QueueDepth::High);
+}
+
+#[test]
+fn test_retry_hint_scales_with_queue() {
+    let latency = Duration::from_millis(400);
+    let hint = RetryHint::for_queue(latency, 0, 2);
+    assert_eq!(hint.retry_after, latency);
+    assert_eq!(hint.queue_depth, QueueDepth::Empty);
+
+    // Two rounds of slots ahead: the queue drains in about three turns
+    let hint = RetryHint::for_queue(latency, 4, 2);
+    assert_eq!(hint.retry_after, latency * 3);
+    assert_eq!(hint.queue_depth, QueueDepth::High);
+
+    // Clamped, and no latency samples yet still suggests a short wait
+    assert_eq!(
+        RetryHint::for_queue(Duration::ZERO, 1, 2).retry_after,
+        Duration::from_millis(100)
+    );
+    assert_eq!(
+        RetryHint::new(Duration::from_secs(600), QueueDepth::Empty).retry_after,
+        Duration::from_secs(30)
+    );
+
+    let (a, b) = (
+        RetryHint::new(latency, QueueDepth::Low),
+        RetryHint::new(latency, QueueDepth::Low),
+    );
+    assert_eq!(a.correlation_id.len(), 16);
+    assert_ne!(a.correlation_id, b.correlation_id);
+}
+
+#[tokio::test]
+async fn test_retry_hint_response() {
+    let hint = RetryHint::new(Duration::from_millis(1500), QueueDepth::Low);
+    let resp = hint.response(
+        "Backend is at its limit for heavy requests",
+        br#"[{"jsonrpc":"2.0","method":"getProgramAccounts","id":1},{"jsonrpc":"2.0","method":"getProgramAccounts","id":"b"}]"#,
+    );
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(resp.headers()["retry-after"], "2");
+    assert_eq!(
+        resp.headers()[CORRELATION_HEADER],
+        hint.correlation_id.as_str()
+    );
+
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let errors = json.as_array().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0]["id"], 1);
+    assert_eq!(errors[1]["id"], "b");
+    let error = &errors[0]["error"];
+    assert_eq!(error["code"], SHED_ERROR_CODE);
+    assert_eq!(error["data"]["retry_after_ms"], 1500);
+    assert_eq!(error["data"]["queue_depth"], "low");
+    assert_eq!(
+        error["data"]["correlation_id"],
+        hint.correlation_id.as_str()
+    );
+
+    // A body that is not JSON still gets a JSON-RPC error, with a null id
+    let body = hint
+        .response("No healthy backends available", b"not json")
+        .into_body()
+        .collect()
+        .await
+        .unwrap()
+        .to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["id"].is_null());
+    assert_eq!(json["error"]["message"], "No healthy backends available");
+}
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use sol_rpc_router::balancer::{pick, CurrentWeight};
+
+struct Node {
+    label: &'static str,
+    weight: u32,
+    current: CurrentWeight,
+}
+
+fn nodes(weights: &[(&'static str, u32)]) -> Vec<Node> {
+    weights
+        .iter()
+        .map(|&(label, weight)| Node {
+            label,
+            weight,
+            current: CurrentWeight::new(),
+        })
+        .collect()
+}
+
+fn next(nodes: &[Node]) -> &'static str {
+    pick(nodes, |n| n.weight, |n| &n.current).unwrap().label
+}
+
+#[test]
+fn test_smooth_sequence() {
+    let nodes = nodes(&[("a", 5), ("b", 1), ("c", 1)]);
+    let sequence: Vec<_> = (0..14).map(|_| next(&nodes)).collect();
+    // The heavy backend is interleaved with the others instead of running first
+    let cycle = ["a", "a", "b", "a", "c", "a", "a"];
+    assert_eq!(sequence[..7], cycle);
+    assert_eq!(sequence[7..], cycle);
+}
+
+#[test]
+fn test_every_window_follows_the_weights() {
+    let nodes = nodes(&[("a", 3), ("b", 2), ("c", 1)]);
+    let sequence: Vec<_> = (0..600).map(|_| next(&nodes)).collect();
+    for window in sequence.chunks(6) {
+        for (label, weight) in [("a", 3), ("b", 2), ("c", 1)] {
+            assert_eq!(window.iter().filter(|l| **l == label).count(), weight);
+        }
+    }
+}
+
+#[test]
+fn test_zero_weights() {
+    let nodes = nodes(&[("a", 0), ("b", 2), ("c", 0)]);
+    assert!((0..10).all(|_| next(&nodes) == "b"));
+
+    // With no weight anywhere the first candidate is used
+    let nodes = self::nodes(&[("a", 0), ("b", 0)]);
+    assert_eq!(next(&nodes), "a");
+    assert!(pick(&nodes[..0], |n| n.weight, |n| &n.current).is_none());
+}
+
+#[test]
+fn test_concurrent_picks_keep_the_spread() {
+    let nodes = Arc::new(nodes(&[("a", 3), ("b", 1)]));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let nodes = nodes.clone();
+            std::thread::spawn(move || (0..1_000).filter(|_| next(&nodes) == "a").count())
+        })
+        .collect();
+    let a: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+    assert!((5_900..=6_100).contains(&a), "a picked {} of 8000 times", a);
+}
@@ -0,0 +1,98 @@
+use axum::{
+    body::{Body, Bytes},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use http_body_util::BodyExt;
+use serde_json::json;
+use sol_rpc_router::batch::{error_entry, join, rewrap, split, unwrap_single};
+
+#[test]
+fn test_unwrap_single() {
+    let body = Bytes::from_static(b" [ {\"method\":\"getSlot\",\"id\":1} ]\n");
+    assert_eq!(
+        unwrap_single(&body).unwrap(),
+        Bytes::from_static(b"{\"method\":\"getSlot\",\"id\":1}")
+    );
+
+    for body in [
+        &b"{\"method\":\"getSlot\"}"[..],
+        b"[]",
+        b"[1]",
+        b"[{\"id\":1},{\"id\":2}]",
+        b"[{\"id\":1}",
+        b"[{\"id\":1} x]",
+    ] {
+        assert_eq!(unwrap_single(&Bytes::copy_from_slice(body)), None);
+    }
+}
+
+async fn body_of(response: Response) -> String {
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_rewrap_json_responses_only() {
+    let response = rewrap(Json(json!({"result": 1})).into_response());
+    assert_eq!(body_of(response).await, r#"[{"result":1}]"#);
+
+    // The length grows by the two brackets
+    let response = Response::builder()
+        .header("content-type", "application/json")
+        .header("content-length", "2")
+        .body(Body::from("{}"))
+        .unwrap();
+    let response = rewrap(response);
+    assert_eq!(response.headers()["content-length"], "4");
+    assert_eq!(body_of(response).await, "[{}]");
+
+    let response = rewrap((StatusCode::UNAUTHORIZED, "Unauthorized").into_response());
+    assert_eq!(body_of(response).await, "Unauthorized");
+}
+
+#[test]
+fn test_split() {
+    let body =
+        Bytes::from_static(b"[{\"method\":\"getSlot\",\"id\":1}, {\"method\":\"getHealth\"}]");
+    let items = split(&body).unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(
+        items[0].body,
+        Bytes::from_static(b"{\"method\":\"getSlot\",\"id\":1}")
+    );
+    assert_eq!(items[0].id, json!(1));
+    assert_eq!(items[1].id, json!(null));
+
+    for body in [
+        &b"{\"method\":\"getSlot\"}"[..],
+        b"[{\"method\":\"getSlot\"}]",
+        b"[{\"method\":\"getSlot\"},{\"id\":2}]",
+        b"[{\"method\":\"getSlot\"},1]",
+        b"[{\"method\":\"getSlot\"}",
+    ] {
+        assert!(split(&Bytes::copy_from_slice(body)).is_none());
+    }
+}
+
+#[test]
+fn test_error_entry_and_join() {
+    let entry = error_entry(
+        &json!(4),
+        StatusCode::TOO_MANY_REQUESTS,
+        b"Rate limit exceeded",
+    );
+    let value: serde_json::Value = serde_json::from_slice(&entry).unwrap();
+    assert_eq!(value["id"], 4);
+    assert_eq!(value["error"]["message"], "Rate limit exceeded");
+    assert_eq!(value["error"]["data"]["status"], 429);
+
+    let entry = error_entry(&json!("x"), StatusCode::BAD_GATEWAY, b"");
+    let value: serde_json::Value = serde_json::from_slice(&entry).unwrap();
+    assert_eq!(value["error"]["message"], "Bad Gateway");
+
+    let joined = join(&[Bytes::from_static(b"{}"), Bytes::from_static(b"[1]")]);
+    assert_eq!(joined, Bytes::from_static(b"[{},[1]]"));
+    assert_eq!(join(&[]), Bytes::from_static(b"[]"));
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use axum::{routing::post, Router};
+use sol_rpc_router::bench::{parse_mix, run, BenchOptions};
+
+async fn start_mock_backend() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|| async { "{\"jsonrpc\":\"2.0\",\"result\":42,\"id\":1}" }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    format!("http://{}", addr)
+}
+
+#[test]
+fn test_parse_mix() {
+    let mix = parse_mix("getSlot:3, getLatestBlockhash").unwrap();
+    assert_eq!(mix.len(), 2);
+    assert_eq!(mix[0].method, "getSlot");
+    assert_eq!(mix[0].weight, 3);
+    assert_eq!(mix[1].method, "getLatestBlockhash");
+    assert_eq!(mix[1].weight, 1);
+
+    assert!(parse_mix("getSlot:0").is_err());
+    assert!(parse_mix("getSlot:abc").is_err());
+    assert!(parse_mix("").is_err());
+}
+
+#[tokio::test]
+async fn test_bench_run_reports_per_method() {
+    let url = start_mock_backend().await;
+
+    let report = run(BenchOptions {
+        url,
+        rps: 200,
+        duration: Duration::from_millis(500),
+        concurrency: 8,
+        mix: parse_mix("getSlot:1,getBlockHeight:1").unwrap(),
+    })
+    .await;
+
+    assert!(report.total.requests > 0);
+    assert_eq!(report.total.http_errors, 0);
+    assert_eq!(report.total.rpc_errors, 0);
+    let per_method_total: u64 = report.per_method.values().map(|s| s.requests).sum();
+    assert_eq!(per_method_total, report.total.requests);
+    assert!(report.total.p99_ms >= report.total.p50_ms);
+}
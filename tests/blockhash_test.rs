@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use axum::{routing::post, Json, Router};
+use base64::Engine;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use serde_json::{json, Value};
+use sol_rpc_router::{
+    blockhash::{
+        fetch_latest_blockhash, first_signature, recent_blockhash, BlockhashCache, SendTransaction,
+        MAX_PROCESSING_AGE,
+    },
+    config::Backend,
+};
+
+const BLOCKHASH: [u8; 32] = [42; 32];
+
+/// Two signatures, a header, three account keys, the blockhash and one empty
+/// instruction; versioned messages get the `0x80` prefix.
+fn transaction(versioned: bool) -> Vec<u8> {
+    let mut tx = vec![2u8];
+    tx.extend([0xaa; 128]);
+    if versioned {
+        tx.push(0x80);
+    }
+    tx.extend([2, 0, 1, 3]);
+    tx.extend([0xbb; 96]);
+    tx.extend(BLOCKHASH);
+    tx.extend([1, 2, 0, 0]);
+    tx
+}
+
+#[test]
+fn test_recent_blockhash_of_legacy_and_versioned_transactions() {
+    assert_eq!(recent_blockhash(&transaction(false)), Some(BLOCKHASH));
+    assert_eq!(recent_blockhash(&transaction(true)), Some(BLOCKHASH));
+
+    // Truncated before the end of the blockhash
+    let tx = transaction(false);
+    assert_eq!(recent_blockhash(&tx[..tx.len() - 10]), None);
+    assert_eq!(recent_blockhash(&[]), None);
+}
+
+#[test]
+fn test_first_signature() {
+    assert_eq!(first_signature(&transaction(true)), Some([0xaa; 64]));
+    assert_eq!(first_signature(&[0]), None);
+    assert_eq!(first_signature(&[1, 0xaa]), None);
+}
+
+#[test]
+fn test_send_transaction_from_request() {
+    let tx = transaction(true);
+    let expected = bs58::encode(BLOCKHASH).into_string();
+
+    let base58 = json!({
+        "jsonrpc": "2.0", "id": 3, "method": "sendTransaction",
+        "params": [bs58::encode(&tx).into_string()]
+    });
+    let parsed = SendTransaction::from_request(base58.to_string().as_bytes()).unwrap();
+    assert_eq!(parsed.blockhash, expected);
+    assert_eq!(parsed.signature, bs58::encode([0xaa; 64]).into_string());
+    assert_eq!(parsed.id, json!(3));
+
+    let base64 = json!({
+        "jsonrpc": "2.0", "id": "a", "method": "sendTransaction",
+        "params": [base64::engine::general_purpose::STANDARD.encode(&tx), {"encoding": "base64"}]
+    });
+    let parsed = SendTransaction::from_request(base64.to_string().as_bytes()).unwrap();
+    assert_eq!(parsed.blockhash, expected);
+
+    let response: Value = serde_json::from_str(&parsed.expired_response()).unwrap();
+    assert_eq!(response["error"]["code"], json!(-32002));
+    assert_eq!(response["id"], json!("a"));
+
+    // Undecodable or batched requests are left alone
+    for body in [
+        r#"{"jsonrpc":"2.0","id":1,"method":"sendTransaction","params":["not base58!"]}"#,
+        r#"{"jsonrpc":"2.0","id":1,"method":"sendTransaction","params":["AQID",{"encoding":"jsonParsed"}]}"#,
+        r#"[{"jsonrpc":"2.0","id":1,"method":"sendTransaction","params":["AQID"]}]"#,
+    ] {
+        assert!(
+            SendTransaction::from_request(body.as_bytes()).is_none(),
+            "{}",
+            body
+        );
+    }
+}
+
+#[test]
+fn test_blockhash_cache_expiry() {
+    let cache = BlockhashCache::new();
+    assert!(!cache.is_expired("old"));
+
+    cache.record("old", 1_000);
+    assert_eq!(cache.block_height(), Some(1_000 - MAX_PROCESSING_AGE));
+    assert!(!cache.is_expired("old"));
+
+    // The chain moves past the old blockhash's last valid height
+    cache.record("new", 1_000 + MAX_PROCESSING_AGE + 1);
+    assert!(cache.is_expired("old"));
+    assert!(!cache.is_expired("new"));
+    // Never seen: forwarded
+    assert!(!cache.is_expired("unknown"));
+
+    // A lagging source does not bring the height back down
+    cache.record("lagging", 900);
+    assert!(cache.is_expired("old"));
+}
+
+#[tokio::test]
+async fn test_fetch_latest_blockhash() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|Json(request): Json<Value>| async move {
+                assert_eq!(request["method"], "getLatestBlockhash");
+                Json(json!({
+                    "jsonrpc": "2.0",
+                    "result": {
+                        "context": {"slot": 100},
+                        "value": {"blockhash": "hash", "lastValidBlockHeight": 250}
+                    },
+                    "id": 1
+                }))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let backend = Backend {
+        label: "b1".to_string(),
+        url: format!("http://{}", addr),
+        weight: 1,
+        ..Default::default()
+    };
+    let latest = fetch_latest_blockhash(&client, &backend, Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(latest, ("hash".to_string(), 250));
+}
@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use sol_rpc_router::broadcast::{accepted_response, is_accepted, RecentSignatures};
+
+#[test]
+fn test_recent_signatures() {
+    let recent = RecentSignatures::new();
+    let window = Duration::from_secs(30);
+    assert!(!recent.contains("sig", window));
+
+    recent.insert("sig", window);
+    assert!(recent.contains("sig", window));
+    assert!(!recent.contains("other", window));
+    // Outside a shorter window
+    std::thread::sleep(Duration::from_millis(5));
+    assert!(!recent.contains("sig", Duration::from_millis(1)));
+}
+
+#[test]
+fn test_is_accepted() {
+    assert!(is_accepted(br#"{"jsonrpc":"2.0","result":"5VER","id":1}"#));
+    assert!(!is_accepted(
+        br#"{"jsonrpc":"2.0","error":{"code":-32002,"message":"failed"},"id":1}"#
+    ));
+    assert!(!is_accepted(b"Too many requests"));
+
+    let response: Value = serde_json::from_str(&accepted_response("5VER", &json!("a"))).unwrap();
+    assert_eq!(
+        response,
+        json!({"jsonrpc": "2.0", "result": "5VER", "id": "a"})
+    );
+}
@@ -0,0 +1,370 @@
+use std::{collections::HashMap, time::Duration};
+
+use sol_rpc_router::{
+    cache::{
+        cache_policy, min_context_slot_not_reached, with_min_context_slot, CacheLookup,
+        CachePolicy, Flight, ResponseCache,
+    },
+    config::{Commitment, CommitmentTtl},
+};
+
+#[test]
+fn test_cache_policy() {
+    assert_eq!(cache_policy("getGenesisHash"), Some(CachePolicy::Forever));
+    assert_eq!(cache_policy("getEpochSchedule"), Some(CachePolicy::Forever));
+    assert_eq!(cache_policy("getEpochInfo"), Some(CachePolicy::PerSlot));
+    assert_eq!(
+        cache_policy("getTokenAccountsByOwner"),
+        Some(CachePolicy::PerSlot)
+    );
+    assert_eq!(cache_policy("getSlot"), None);
+}
+
+#[test]
+fn test_lookup_keys_on_method_and_params() {
+    let a = CacheLookup::from_request(
+        br#"{"jsonrpc":"2.0","method":"getEpochInfo","params":[{"commitment":"finalized"}],"id":1}"#,
+    )
+    .unwrap();
+    let b = CacheLookup::from_request(
+        br#"{"jsonrpc":"2.0","method":"getEpochInfo","params":[{"commitment":"processed"}],"id":1}"#,
+    )
+    .unwrap();
+    assert_ne!(a.key, b.key);
+    assert_eq!(a.policy, CachePolicy::PerSlot);
+
+    // Batches, unparseable bodies and other methods are not cacheable
+    assert!(
+        CacheLookup::from_request(br#"[{"jsonrpc":"2.0","method":"getGenesisHash","id":1}]"#)
+            .is_none()
+    );
+    assert!(CacheLookup::from_request(b"not json").is_none());
+    assert!(CacheLookup::from_request(br#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#).is_none());
+}
+
+#[test]
+fn test_lookup_keys_on_commitment() {
+    let key = |body: &str| CacheLookup::from_request(body.as_bytes()).map(|l| l.key);
+    let epoch_info = |config: &str| {
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"getEpochInfo","params":[{}],"id":1}}"#,
+            config
+        )
+    };
+
+    // Deprecated names share entries with the levels they stand for
+    let finalized = key(&epoch_info(r#"{"commitment":"finalized"}"#)).unwrap();
+    assert_eq!(
+        key(&epoch_info(r#"{"commitment":"max"}"#)).unwrap(),
+        finalized
+    );
+    let lookup = CacheLookup::from_request(epoch_info(r#"{"commitment":"recent"}"#).as_bytes());
+    assert_eq!(lookup.unwrap().commitment, Some(Commitment::Processed));
+
+    // Leaving it to the node is not assumed to mean any particular level
+    let default =
+        CacheLookup::from_request(br#"{"jsonrpc":"2.0","method":"getEpochInfo","id":1}"#).unwrap();
+    assert_eq!(default.commitment, None);
+    assert_ne!(default.key, finalized);
+
+    // Other consistency parameters keep entries apart; field order does not
+    let min_slot = key(&epoch_info(
+        r#"{"commitment":"finalized","minContextSlot":300000000}"#,
+    ))
+    .unwrap();
+    assert_ne!(min_slot, finalized);
+    assert_eq!(
+        key(&epoch_info(
+            r#"{"minContextSlot":300000000,"commitment":"root"}"#
+        ))
+        .unwrap(),
+        min_slot
+    );
+
+    // An unknown commitment cannot be placed, so it is not cached
+    assert!(key(&epoch_info(r#"{"commitment":"eventual"}"#)).is_none());
+}
+
+#[test]
+fn test_lookup_response_uses_request_id() {
+    let lookup =
+        CacheLookup::from_request(br#"{"jsonrpc":"2.0","method":"getGenesisHash","id":"abc"}"#)
+            .unwrap();
+    let body = lookup.response(br#""hash""#);
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"jsonrpc": "2.0", "result": "hash", "id": "abc"})
+    );
+}
+
+#[tokio::test]
+async fn test_store_skips_errors() {
+    let cache = ResponseCache::new();
+    let lookup =
+        CacheLookup::from_request(br#"{"jsonrpc":"2.0","method":"getGenesisHash","id":1}"#)
+            .unwrap();
+
+    cache
+        .store(
+            &lookup,
+            br#"{"jsonrpc":"2.0","error":{"code":-32005,"message":"Node is behind"},"id":1}"#,
+        )
+        .await;
+    assert!(cache.get(&lookup).await.is_none());
+
+    cache
+        .store(&lookup, br#"{"jsonrpc":"2.0","result":"hash","id":1}"#)
+        .await;
+    assert_eq!(cache.get(&lookup).await.unwrap().as_ref(), br#""hash""#);
+}
+
+#[tokio::test]
+async fn test_per_slot_entries_expire() {
+    let cache = ResponseCache::new();
+    let lookup =
+        CacheLookup::from_request(br#"{"jsonrpc":"2.0","method":"getEpochInfo","id":1}"#).unwrap();
+    cache
+        .store(
+            &lookup,
+            br#"{"jsonrpc":"2.0","result":{"epoch":600},"id":1}"#,
+        )
+        .await;
+    assert!(cache.get(&lookup).await.is_some());
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(cache.get(&lookup).await.is_none());
+}
+
+#[tokio::test]
+async fn test_outage_only_entries_are_served_stale_only() {
+    let cache = ResponseCache::new();
+    let body = br#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#;
+    assert!(CacheLookup::from_request(body).is_none());
+    let lookup = CacheLookup::from_request_or_stale(body, &["getSlot".to_string()]).unwrap();
+    assert_eq!(lookup.policy, CachePolicy::OutageOnly);
+
+    cache
+        .store(&lookup, br#"{"jsonrpc":"2.0","result":312000000,"id":1}"#)
+        .await;
+    assert!(cache.get(&lookup).await.is_none());
+    let (result, age) = cache
+        .get_stale(&lookup, Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(result.as_ref(), b"312000000");
+    assert!(age < Duration::from_secs(60));
+
+    // Beyond the staleness bound
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(cache
+        .get_stale(&lookup, Duration::from_millis(10))
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_per_slot_ttl_follows_commitment() {
+    let cache = ResponseCache::new();
+    let ttl = CommitmentTtl {
+        processed: 1,
+        confirmed: 1,
+        finalized: 4,
+    };
+    let lookup = |commitment: &str| {
+        let body = format!(
+            r#"{{"jsonrpc":"2.0","method":"getEpochInfo","params":[{{"commitment":"{}"}}],"id":1}}"#,
+            commitment
+        );
+        CacheLookup::from_request(body.as_bytes())
+            .unwrap()
+            .with_commitment_ttl(&ttl)
+    };
+    let (processed, finalized) = (lookup("processed"), lookup("finalized"));
+    assert_eq!(finalized.ttl, processed.ttl * 4);
+
+    let response = br#"{"jsonrpc":"2.0","result":{"epoch":600},"id":1}"#;
+    cache.store(&processed, response).await;
+    cache.store(&finalized, response).await;
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(cache.get(&processed).await.is_none());
+    assert!(cache.get(&finalized).await.is_some());
+}
+
+#[tokio::test]
+async fn test_configured_methods_use_their_ttl() {
+    let cache = ResponseCache::new();
+    let mut method_ttl_secs = HashMap::new();
+    method_ttl_secs.insert("getVersion".to_string(), 1);
+    method_ttl_secs.insert("getGenesisHash".to_string(), 1);
+
+    let body = br#"{"jsonrpc":"2.0","method":"getVersion","id":1}"#;
+    assert!(CacheLookup::from_request(body).is_none());
+    let lookup = CacheLookup::from_request_configured(body, &method_ttl_secs, &[])
+        .unwrap()
+        .with_commitment_ttl(&CommitmentTtl::default());
+    assert_eq!(lookup.policy, CachePolicy::Configured);
+    assert_eq!(lookup.ttl, Duration::from_secs(1));
+
+    // A configured TTL replaces the built-in policy
+    let genesis = CacheLookup::from_request_configured(
+        br#"{"jsonrpc":"2.0","method":"getGenesisHash","id":1}"#,
+        &method_ttl_secs,
+        &[],
+    )
+    .unwrap();
+    assert_eq!(genesis.policy, CachePolicy::Configured);
+
+    cache
+        .store(
+            &lookup,
+            br#"{"jsonrpc":"2.0","result":{"solana-core":"2.1.0"},"id":1}"#,
+        )
+        .await;
+    assert!(cache.get(&lookup).await.is_some());
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(cache.get(&lookup).await.is_none());
+}
+
+#[test]
+fn test_token_accounts_key_on_owner_program_and_encoding() {
+    let key = |owner: &str, filter: &str, encoding: &str| {
+        let body = format!(
+            r#"{{"jsonrpc":"2.0","method":"getTokenAccountsByOwner","params":["{}",{},{{"encoding":"{}","commitment":"confirmed"}}],"id":1}}"#,
+            owner, filter, encoding
+        );
+        CacheLookup::from_request(body.as_bytes()).unwrap().key
+    };
+    let program = r#"{"programId":"TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"}"#;
+    let base = key("Owner1", program, "jsonParsed");
+    assert_eq!(key("Owner1", program, "jsonParsed"), base);
+    assert_ne!(key("Owner2", program, "jsonParsed"), base);
+    assert_ne!(
+        key(
+            "Owner1",
+            r#"{"programId":"TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"}"#,
+            "jsonParsed"
+        ),
+        base
+    );
+    assert_ne!(key("Owner1", program, "base64"), base);
+}
+
+#[tokio::test]
+async fn test_concurrent_misses_are_coalesced() {
+    let cache = ResponseCache::new();
+    let lookup =
+        CacheLookup::from_request(br#"{"jsonrpc":"2.0","method":"getGenesisHash","id":1}"#)
+            .unwrap();
+
+    let Flight::Leader(guard) = cache.begin(&lookup) else {
+        panic!("first request should fetch");
+    };
+    let Flight::Follower(mut done) = cache.begin(&lookup) else {
+        panic!("second request should wait");
+    };
+    let waiter = tokio::spawn(async move { done.changed().await });
+
+    cache
+        .store(&lookup, br#"{"jsonrpc":"2.0","result":"hash","id":1}"#)
+        .await;
+    drop(guard);
+    tokio::time::timeout(Duration::from_secs(1), waiter)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap_err();
+    assert!(cache.get(&lookup).await.is_some());
+
+    // Once done, the next miss fetches again
+    assert!(matches!(cache.begin(&lookup), Flight::Leader(_)));
+}
+
+#[test]
+fn test_min_context_slot_rewrite() {
+    let rewrite = |body: &str, slot| {
+        with_min_context_slot(body.as_bytes(), slot)
+            .map(|b| serde_json::from_slice::<serde_json::Value>(&b).unwrap()["params"].clone())
+    };
+    assert_eq!(
+        rewrite(
+            r#"{"jsonrpc":"2.0","method":"getAccountInfo","params":["Acc1"],"id":1}"#,
+            100
+        ),
+        Some(serde_json::json!(["Acc1", {"minContextSlot": 101}]))
+    );
+    // The config object is kept, and a later minContextSlot too
+    assert_eq!(
+        rewrite(
+            r#"{"jsonrpc":"2.0","method":"getMultipleAccounts","params":[["A","B"],{"encoding":"base64","minContextSlot":500}],"id":1}"#,
+            100
+        ),
+        Some(serde_json::json!([["A", "B"], {"encoding": "base64", "minContextSlot": 500}]))
+    );
+    // The filter of token account lookups is not the config
+    assert_eq!(
+        rewrite(
+            r#"{"jsonrpc":"2.0","method":"getTokenAccountsByOwner","params":["Own",{"mint":"M"}],"id":1}"#,
+            7
+        ),
+        Some(serde_json::json!(["Own", {"mint": "M"}, {"minContextSlot": 8}]))
+    );
+    assert_eq!(
+        rewrite(r#"{"jsonrpc":"2.0","method":"getVersion","id":1}"#, 7),
+        None
+    );
+    assert_eq!(
+        rewrite(
+            r#"{"jsonrpc":"2.0","method":"getAccountInfo","params":[],"id":1}"#,
+            7
+        ),
+        None
+    );
+
+    assert!(min_context_slot_not_reached(
+        br#"{"jsonrpc":"2.0","error":{"code":-32016,"message":"Minimum context slot has not been reached"},"id":1}"#
+    ));
+    assert!(!min_context_slot_not_reached(
+        br#"{"jsonrpc":"2.0","error":{"code":-32005,"message":"Node is behind"},"id":1}"#
+    ));
+}
+
+#[tokio::test]
+async fn test_stored_context_slot_and_extension() {
+    let cache = ResponseCache::new();
+    let body = br#"{"jsonrpc":"2.0","method":"getAccountInfo","params":["Acc1"],"id":1}"#;
+    let ttls = HashMap::from([("getAccountInfo".to_string(), 60)]);
+    let mut lookup = CacheLookup::from_request_configured(body, &ttls, &[]).unwrap();
+    lookup.ttl = Duration::from_millis(50);
+    cache
+        .store(
+            &lookup,
+            br#"{"jsonrpc":"2.0","result":{"context":{"slot":42},"value":null},"id":1}"#,
+        )
+        .await;
+    let (result, slot) = cache.get_with_slot(&lookup).await.unwrap();
+    assert_eq!(slot, 42);
+
+    // Extending keeps an expired result for another TTL
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(cache.get(&lookup).await.is_none());
+    cache.extend(&lookup, result.clone(), slot).await;
+    assert_eq!(cache.get(&lookup).await, Some(result));
+
+    // Results without a context have nothing to revalidate against
+    let lookup = CacheLookup::from_request_configured(
+        br#"{"jsonrpc":"2.0","method":"getVersion","id":1}"#,
+        &HashMap::from([("getVersion".to_string(), 60)]),
+        &[],
+    )
+    .unwrap();
+    cache
+        .store(
+            &lookup,
+            br#"{"jsonrpc":"2.0","result":{"solana-core":"2.0"},"id":1}"#,
+        )
+        .await;
+    assert!(cache.get_with_slot(&lookup).await.is_none());
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use sol_rpc_router::{
+    circuit::{CircuitBreaker, CircuitState},
+    config::{parse_config, Backend, CircuitBreakerConfig},
+    health::HealthState,
+    reload::router_state_from_config,
+    signing::unix_now,
+    state::RuntimeBackend,
+};
+
+fn config() -> CircuitBreakerConfig {
+    CircuitBreakerConfig {
+        enabled: true,
+        window_secs: 10,
+        min_requests: 4,
+        failure_percent: 50,
+        cooldown_secs: 30,
+    }
+}
+
+#[test]
+fn test_trips_on_failure_rate_over_window() {
+    let config = config();
+    let breaker = CircuitBreaker::new();
+
+    // Below min_requests nothing trips, however bad
+    for _ in 0..3 {
+        breaker.record("a", false, 1_000, &config);
+    }
+    assert_eq!(breaker.state(1_000), CircuitState::Closed);
+
+    // Failures that left the window no longer count
+    breaker.record("a", true, 1_010, &config);
+    breaker.record("a", true, 1_010, &config);
+    breaker.record("a", false, 1_011, &config);
+    assert_eq!(breaker.state(1_011), CircuitState::Closed);
+
+    // 2 of 4 failed within the window
+    breaker.record("a", false, 1_012, &config);
+    assert_eq!(breaker.state(1_012), CircuitState::Open);
+    assert!(!breaker.admits(1_041));
+}
+
+#[test]
+fn test_half_open_probe_decides() {
+    let config = config();
+    let breaker = CircuitBreaker::new();
+    for _ in 0..4 {
+        breaker.record("a", false, 1_000, &config);
+    }
+    assert!(!breaker.admits(1_000));
+
+    // After the cooldown a single probe is let through
+    assert_eq!(breaker.state(1_030), CircuitState::HalfOpen);
+    assert!(breaker.admits(1_030));
+    breaker.begin(1_030);
+    assert!(!breaker.admits(1_031));
+
+    // A failed probe reopens the breaker for another cooldown
+    breaker.record("a", false, 1_031, &config);
+    assert_eq!(breaker.state(1_031), CircuitState::Open);
+    assert!(!breaker.admits(1_060));
+
+    // A probe that never reports back is given up on after a cooldown
+    breaker.begin(1_061);
+    assert!(!breaker.admits(1_062));
+    assert!(breaker.admits(1_091));
+
+    // A successful one closes it with a fresh window
+    breaker.record("a", true, 1_091, &config);
+    assert_eq!(breaker.state(1_091), CircuitState::Closed);
+    breaker.record("a", false, 1_092, &config);
+    assert!(breaker.admits(1_092));
+}
+
+#[test]
+fn test_open_breaker_takes_backend_out_of_rotation() {
+    let backend = RuntimeBackend::new(
+        Backend {
+            label: "a".to_string(),
+            url: "http://localhost:9000".to_string(),
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    assert!(backend.in_rotation());
+    for _ in 0..4 {
+        backend.circuit.record("a", false, unix_now(), &config());
+    }
+    assert!(!backend.in_rotation());
+}
+
+#[test]
+fn test_breakers_survive_reload_unless_disabled() {
+    let mut config = parse_config(
+        r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[circuit_breaker]
+enabled = true
+min_requests = 1
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#,
+    )
+    .unwrap();
+    let health_state = Arc::new(HealthState::new(vec!["a".to_string()]));
+    let state = router_state_from_config(&config, health_state.clone());
+    state.backends[0]
+        .circuit
+        .record("a", false, unix_now(), &config.circuit_breaker);
+
+    let reloaded = router_state_from_config(&config, health_state.clone());
+    assert!(!reloaded.backends[0].in_rotation());
+
+    config.circuit_breaker.enabled = false;
+    let disabled = router_state_from_config(&config, health_state);
+    assert!(disabled.backends[0].in_rotation());
+}
@@ -0,0 +1,51 @@
+use std::io::Write;
+
+use sol_rpc_router::cli::check_config;
+
+fn write_temp_config(name: &str, content: &str) -> String {
+    let mut path = std::env::temp_dir();
+    path.push(format!("sol_rpc_router_test_cli_{}.toml", name));
+    let path_str = path.to_str().unwrap().to_string();
+
+    let mut f = std::fs::File::create(&path).unwrap();
+    f.write_all(content.as_bytes()).unwrap();
+    path_str
+}
+
+#[tokio::test]
+async fn test_check_config_valid() {
+    let path = write_temp_config(
+        "valid",
+        r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#,
+    );
+    assert!(check_config(&path, false).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_check_config_invalid() {
+    let path = write_temp_config(
+        "no_backends",
+        r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+backends = []
+"#,
+    );
+    let err = check_config(&path, false).await.unwrap_err();
+    assert!(err.to_string().contains("backend"), "{}", err);
+
+    let err = check_config("/nonexistent/config.toml", false)
+        .await
+        .unwrap_err();
+    assert!(!err.to_string().is_empty());
+}
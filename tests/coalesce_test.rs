@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use sol_rpc_router::{
+    cache::ReadKey,
+    coalesce::{follow, Coalesce, Coalescer, Leader, Shared},
+};
+
+fn lead(coalescer: &Coalescer, key: &str) -> Leader {
+    match coalescer.begin(key) {
+        Coalesce::Leader(leader) => leader,
+        Coalesce::Follower(_) => panic!("expected to lead {}", key),
+    }
+}
+
+#[test]
+fn test_read_key_ignores_id_and_normalizes_commitment() {
+    let a = ReadKey::from_request(
+        br#"{"jsonrpc":"2.0","id":1,"method":"getSlot","params":[{"commitment":"finalized"}]}"#,
+    )
+    .unwrap();
+    let b = ReadKey::from_request(
+        br#"{"jsonrpc":"2.0","id":"b","method":"getSlot","params":[{"commitment":"max"}]}"#,
+    )
+    .unwrap();
+    let c = ReadKey::from_request(br#"{"jsonrpc":"2.0","id":2,"method":"getSlot"}"#).unwrap();
+    assert_eq!(a.key, b.key);
+    assert_ne!(a.key, c.key);
+    assert_eq!(a.id, "1");
+    assert_eq!(b.id, "\"b\"");
+
+    assert!(ReadKey::from_request(br#"[{"method":"getSlot"}]"#).is_none());
+    assert!(
+        ReadKey::from_request(br#"{"method":"getSlot","params":[{"commitment":"someday"}]}"#)
+            .is_none()
+    );
+
+    assert_eq!(
+        b.response("result", b"42"),
+        br#"{"jsonrpc":"2.0","result":42,"id":"b"}"#.to_vec()
+    );
+}
+
+#[test]
+fn test_shared_from_response() {
+    let shared =
+        Shared::from_response(br#"{"jsonrpc":"2.0","result":{"a": 1},"id":7}"#, "a").unwrap();
+    assert_eq!(shared.field, "result");
+    assert_eq!(shared.value, Bytes::from_static(br#"{"a": 1}"#));
+    assert_eq!(shared.backend, "a");
+
+    let shared = Shared::from_response(
+        br#"{"jsonrpc":"2.0","error":{"code":-32005,"message":"behind"},"id":7}"#,
+        "a",
+    )
+    .unwrap();
+    assert_eq!(shared.field, "error");
+
+    let null = Shared::from_response(br#"{"jsonrpc":"2.0","result":null,"id":7}"#, "a").unwrap();
+    assert_eq!(null.value, Bytes::from_static(b"null"));
+
+    assert!(Shared::from_response(br#"[{"result":1}]"#, "a").is_none());
+    assert!(Shared::from_response(br#"{"id":7}"#, "a").is_none());
+    assert!(Shared::from_response(b"not json", "a").is_none());
+}
+
+#[tokio::test]
+async fn test_followers_receive_leader_answer() {
+    let coalescer = Coalescer::new();
+    let leader = lead(&coalescer, "getSlot");
+    let Coalesce::Follower(first) = coalescer.begin("getSlot") else {
+        panic!("expected to follow");
+    };
+    let Coalesce::Follower(second) = coalescer.begin("getSlot") else {
+        panic!("expected to follow");
+    };
+    // Other reads are not held up
+    drop(lead(&coalescer, "getBlockHeight"));
+
+    let waiting = tokio::spawn(follow(first, Duration::from_secs(5)));
+    leader.share(Shared::from_response(br#"{"result":42}"#, "a").unwrap());
+    assert_eq!(
+        waiting.await.unwrap().unwrap().value,
+        Bytes::from_static(b"42")
+    );
+    let late = follow(second, Duration::from_secs(5)).await.unwrap();
+    assert_eq!(late.backend, "a");
+
+    // The flight is over: the next request leads again
+    assert!(coalescer.is_empty());
+    let _next = lead(&coalescer, "getSlot");
+}
+
+#[tokio::test]
+async fn test_followers_released_when_leader_fails() {
+    let coalescer = Coalescer::new();
+    let leader = lead(&coalescer, "getSlot");
+    let Coalesce::Follower(answer) = coalescer.begin("getSlot") else {
+        panic!("expected to follow");
+    };
+    drop(leader);
+    assert!(follow(answer, Duration::from_secs(5)).await.is_none());
+    assert!(coalescer.is_empty());
+}
+
+#[tokio::test]
+async fn test_follow_times_out() {
+    let coalescer = Coalescer::new();
+    let _leader = lead(&coalescer, "getSlot");
+    let Coalesce::Follower(answer) = coalescer.begin("getSlot") else {
+        panic!("expected to follow");
+    };
+    assert!(follow(answer, Duration::from_millis(10)).await.is_none());
+    assert_eq!(coalescer.len(), 1);
+}
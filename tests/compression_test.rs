@@ -0,0 +1,75 @@
+use std::io::Write;
+
+use sol_rpc_router::compression::{decode, ContentEncoding, DecodeError};
+
+const BODY: &[u8] =
+    br#"[{"jsonrpc":"2.0","id":1,"method":"getSlot"},{"jsonrpc":"2.0","id":2,"method":"getSlot"}]"#;
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn test_parse_content_encoding() {
+    assert_eq!(
+        ContentEncoding::parse("gzip"),
+        Ok(Some(ContentEncoding::Gzip))
+    );
+    assert_eq!(
+        ContentEncoding::parse("X-GZIP"),
+        Ok(Some(ContentEncoding::Gzip))
+    );
+    assert_eq!(
+        ContentEncoding::parse(" zstd "),
+        Ok(Some(ContentEncoding::Zstd))
+    );
+    assert_eq!(ContentEncoding::parse("identity"), Ok(None));
+    assert!(ContentEncoding::parse("br").is_err());
+    assert!(ContentEncoding::parse("gzip, zstd").is_err());
+}
+
+#[test]
+fn test_decode_round_trips() {
+    assert_eq!(
+        decode(ContentEncoding::Gzip, &gzip(BODY), 1024).unwrap(),
+        BODY
+    );
+    let zstd = zstd::encode_all(BODY, 0).unwrap();
+    assert_eq!(decode(ContentEncoding::Zstd, &zstd, 1024).unwrap(), BODY);
+}
+
+#[test]
+fn test_decode_enforces_limit() {
+    // A few KiB on the wire, a megabyte decoded
+    let bomb = gzip(&vec![b' '; 1024 * 1024]);
+    assert!(bomb.len() < 8 * 1024);
+    assert_eq!(
+        decode(ContentEncoding::Gzip, &bomb, 64 * 1024),
+        Err(DecodeError::TooLarge)
+    );
+    let zstd = zstd::encode_all(&vec![b' '; 1024 * 1024][..], 0).unwrap();
+    assert_eq!(
+        decode(ContentEncoding::Zstd, &zstd, 64 * 1024),
+        Err(DecodeError::TooLarge)
+    );
+
+    // Exactly at the limit is fine
+    assert_eq!(
+        decode(ContentEncoding::Gzip, &gzip(BODY), BODY.len()).unwrap(),
+        BODY
+    );
+}
+
+#[test]
+fn test_decode_rejects_corrupt_input() {
+    assert!(matches!(
+        decode(ContentEncoding::Gzip, BODY, 1024),
+        Err(DecodeError::Invalid(_))
+    ));
+    assert!(matches!(
+        decode(ContentEncoding::Zstd, BODY, 1024),
+        Err(DecodeError::Invalid(_))
+    ));
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use sol_rpc_router::concurrency::{ConcurrencyLimiter, Saturated};
+
+#[test]
+fn test_global_limit() {
+    let limiter = Arc::new(ConcurrencyLimiter::new());
+    let first = limiter.try_acquire(2, "a", "alice", 0).unwrap();
+    let second = limiter.try_acquire(2, "b", "bob", 0).unwrap();
+    assert_eq!(limiter.in_flight(), 2);
+    assert_eq!(
+        limiter.try_acquire(2, "c", "carol", 0).err(),
+        Some(Saturated::Global)
+    );
+
+    drop(first);
+    assert_eq!(limiter.in_flight(), 1);
+    let third = limiter.try_acquire(2, "c", "carol", 0).unwrap();
+    drop((second, third));
+    assert_eq!(limiter.in_flight(), 0);
+
+    // Without limits every request is admitted and counted
+    let permits: Vec<_> = (0..100)
+        .map(|_| limiter.try_acquire(0, "a", "alice", 0).unwrap())
+        .collect();
+    assert_eq!(limiter.in_flight(), 100);
+    drop(permits);
+    assert_eq!(limiter.in_flight(), 0);
+}
+
+#[test]
+fn test_key_limit() {
+    let limiter = Arc::new(ConcurrencyLimiter::new());
+    let first = limiter.try_acquire(0, "a", "alice", 1).unwrap();
+    assert_eq!(limiter.key_in_flight("a"), 1);
+    assert_eq!(
+        limiter.try_acquire(0, "a", "alice", 1).err(),
+        Some(Saturated::Key)
+    );
+    // Other keys have their own slots
+    let other = limiter.try_acquire(0, "b", "bob", 1).unwrap();
+
+    // A request shed at its key limit gives back its global slot
+    let limited = Arc::new(ConcurrencyLimiter::new());
+    let held = limited.try_acquire(2, "a", "alice", 1).unwrap();
+    assert!(limited.try_acquire(2, "a", "alice", 1).is_err());
+    assert_eq!(limited.in_flight(), 1);
+    let _b = limited.try_acquire(2, "b", "bob", 0).unwrap();
+    drop(held);
+
+    drop((first, other));
+    assert_eq!(limiter.key_in_flight("a"), 0);
+    assert_eq!(limiter.in_flight(), 0);
+}
+
+#[test]
+fn test_changed_limits_apply_to_new_requests() {
+    let limiter = Arc::new(ConcurrencyLimiter::new());
+    let old = limiter.try_acquire(1, "a", "alice", 1).unwrap();
+    assert!(limiter.try_acquire(1, "a", "alice", 1).is_err());
+
+    // Raised limits start counting afresh
+    let new = limiter.try_acquire(2, "a", "alice", 2).unwrap();
+    assert_eq!(limiter.key_in_flight("a"), 1);
+    let newer = limiter.try_acquire(2, "a", "alice", 2).unwrap();
+    assert_eq!(
+        limiter.try_acquire(2, "a", "alice", 2).err(),
+        Some(Saturated::Global)
+    );
+
+    // Releasing a permit of the old limit leaves the new one alone
+    drop(old);
+    assert_eq!(limiter.key_in_flight("a"), 2);
+    drop((new, newer));
+    assert_eq!(limiter.key_in_flight("a"), 0);
+    assert_eq!(limiter.in_flight(), 0);
+}
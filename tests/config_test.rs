@@ -1,6 +1,14 @@
-use std::io::Write;
+use std::{collections::HashMap, io::Write, time::Duration};
 
-use sol_rpc_router::config::load_config;
+use sol_rpc_router::{
+    config::{
+        load_config, parse_config, parse_config_with_overrides, AlertMetric, AlertOp, Commitment,
+        DeadLetterStoreKind, HttpVersion, JournalStoreKind, KeystoreOutagePolicy, LogFormat,
+        MetricsExporterKind, RoutingMode, Subsystem, UsageLedgerStoreKind, ValueSource,
+    },
+    methods::Idempotency,
+    redact::redact,
+};
 
 fn write_temp_config(name: &str, content: &str) -> String {
     let mut path = std::env::temp_dir();
@@ -200,6 +208,81 @@ timeout_secs = 0
     );
 }
 
+#[test]
+fn test_load_config_max_decompressed_bytes() {
+    let config = |bytes: usize| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "b1"
+url = "http://localhost:9000"
+weight = 1
+
+[proxy]
+max_decompressed_bytes = {}
+"#,
+            bytes
+        )
+    };
+
+    let path = write_temp_config("decompressed_ok", &config(1024 * 1024));
+    let loaded = load_config(&path).unwrap();
+    assert_eq!(loaded.proxy.max_decompressed_bytes, 1024 * 1024);
+
+    for (name, bytes) in [
+        ("decompressed_zero", 0),
+        ("decompressed_huge", 64 * 1024 * 1024),
+    ] {
+        let path = write_temp_config(name, &config(bytes));
+        let err = load_config(&path).unwrap_err();
+        assert!(
+            err.to_string().contains("max_decompressed_bytes"),
+            "Expected 'max_decompressed_bytes' in error: {}",
+            err
+        );
+    }
+}
+
+#[test]
+fn test_load_config_max_buffered_bytes() {
+    let config = |bytes: u64| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "b1"
+url = "http://localhost:9000"
+weight = 1
+
+[proxy]
+max_buffered_bytes = {}
+"#,
+            bytes
+        )
+    };
+
+    let path = write_temp_config("buffered_default", &config(16 * 1024 * 1024));
+    assert_eq!(
+        load_config(&path).unwrap().proxy.max_buffered_bytes,
+        16 * 1024 * 1024
+    );
+
+    let path = write_temp_config("buffered_zero", &config(0));
+    let err = load_config(&path).unwrap_err();
+    assert!(
+        err.to_string().contains("max_buffered_bytes"),
+        "Expected 'max_buffered_bytes' in error: {}",
+        err
+    );
+}
+
 #[test]
 fn test_load_config_unknown_method_route() {
     let path = write_temp_config(
@@ -232,6 +315,365 @@ getSlot = "nonexistent"
     );
 }
 
+#[test]
+fn test_load_config_tier_routes() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "shared"
+url = "http://localhost:9000"
+weight = 1
+
+[[backends]]
+label = "premium"
+url = "http://localhost:9001"
+weight = 1
+exclude_methods = ["getBlock"]
+"#;
+    let valid = format!(
+        "{}\n[tier_routes.gold]\ngetProgramAccounts = \"premium\"\n",
+        base
+    );
+    let config = load_config(&write_temp_config("tier_routes", &valid)).unwrap();
+    assert_eq!(config.tier_routes["gold"]["getProgramAccounts"], "premium");
+
+    for (name, route) in [
+        ("tier_routes_unknown_backend", "getSlot = \"missing\""),
+        ("tier_routes_unknown_method", "getSlots = \"premium\""),
+        ("tier_routes_excluded", "getBlock = \"premium\""),
+    ] {
+        let invalid = format!("{}\n[tier_routes.gold]\n{}\n", base, route);
+        assert!(
+            load_config(&write_temp_config(name, &invalid)).is_err(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_load_config_regions() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "fra"
+url = "http://localhost:9000"
+weight = 1
+region = "fra"
+
+[[backends]]
+label = "nyc"
+url = "http://localhost:9001"
+weight = 1
+region = "nyc"
+"#;
+    let valid = format!("{}\n[routing]\nregion = \"fra\"\n", base);
+    let config = load_config(&write_temp_config("regions", &valid)).unwrap();
+    assert_eq!(config.routing.region.as_deref(), Some("fra"));
+    assert_eq!(config.backends[1].region.as_deref(), Some("nyc"));
+
+    let invalid = format!("{}\n[routing]\nregion = \"sgp\"\n", base);
+    let err = load_config(&write_temp_config("regions_unknown", &invalid)).unwrap_err();
+    assert!(
+        err.to_string().contains("no backend is in region 'sgp'"),
+        "Expected unknown region in error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_load_config_offload() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "b1"
+url = "http://localhost:9000"
+weight = 1
+
+[offload]
+enabled = true
+endpoint = "https://s3.us-east-1.amazonaws.com"
+bucket = "rpc-responses"
+access_key_id = "AKIDEXAMPLE"
+"#;
+    std::env::set_var("TEST_OFFLOAD_SECRET", "offload-secret-value");
+    let valid = format!("{}secret_access_key_env = \"TEST_OFFLOAD_SECRET\"\n", base);
+    let config = load_config(&write_temp_config("offload", &valid)).unwrap();
+    assert_eq!(
+        config.offload.secret_access_key.as_deref(),
+        Some("offload-secret-value")
+    );
+    assert_eq!(
+        config.redacted()["offload"]["secret_access_key"],
+        "[REDACTED]"
+    );
+
+    for (name, extra) in [
+        ("offload_no_secret", ""),
+        (
+            "offload_unset_env",
+            "secret_access_key_env = \"TEST_OFFLOAD_UNSET\"\n",
+        ),
+        (
+            "offload_expiry",
+            "secret_access_key = \"s\"\nurl_expiry_secs = 604801\n",
+        ),
+    ] {
+        let invalid = format!("{}{}", base, extra);
+        assert!(
+            load_config(&write_temp_config(name, &invalid)).is_err(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_load_config_preflight_policies() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "b1"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let valid = format!(
+        "{}\n[preflight_policies.retail]\nrequire_preflight = true\nmin_preflight_commitment = \"confirmed\"\nmax_retries = 0\n",
+        base
+    );
+    let config = load_config(&write_temp_config("preflight_policies", &valid)).unwrap();
+    let policy = &config.preflight_policies["retail"];
+    assert!(policy.require_preflight);
+    assert_eq!(policy.min_preflight_commitment, Some(Commitment::Confirmed));
+    assert_eq!(policy.max_retries, Some(0));
+
+    let typo = format!(
+        "{}\n[preflight_policies.retail]\nskip_preflight = false\n",
+        base
+    );
+    assert!(load_config(&write_temp_config("preflight_policies_typo", &typo)).is_err());
+}
+
+#[test]
+fn test_load_config_poll_bridge() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "b1"
+url = "http://localhost:9000"
+weight = 1
+
+[poll_bridge]
+enabled = true
+"#;
+    let config = load_config(&write_temp_config("poll_bridge", base)).unwrap();
+    assert_eq!(config.poll_bridge.max_subscriptions_per_key, 10);
+    assert_eq!(config.poll_bridge.max_wait_secs, 30);
+
+    for (name, extra) in [
+        ("poll_bridge_limit", "max_subscriptions_per_key = 0\n"),
+        ("poll_bridge_wait", "max_wait_secs = 0\n"),
+        ("poll_bridge_idle", "idle_timeout_secs = 0\n"),
+        ("poll_bridge_buffer", "max_buffered = 0\n"),
+    ] {
+        let invalid = format!("{}{}", base, extra);
+        assert!(
+            load_config(&write_temp_config(name, &invalid)).is_err(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_load_config_integrity() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "b1"
+url = "http://localhost:9000"
+weight = 1
+
+[integrity]
+enabled = true
+"#;
+    let config = load_config(&write_temp_config("integrity", base)).unwrap();
+    assert!(config.integrity.applies_to(Some("getBalance")));
+    assert!(config.integrity.verify_json);
+    assert!(config.integrity.verify_digest);
+
+    let listed = format!("{}methods = [\"getBlock\"]\n", base);
+    let config = load_config(&write_temp_config("integrity_methods", &listed)).unwrap();
+    assert!(config.integrity.applies_to(Some("getBlock")));
+    assert!(!config.integrity.applies_to(Some("getBalance")));
+
+    for (name, extra) in [
+        ("integrity_max_bytes", "max_bytes = 0\n"),
+        ("integrity_unknown", "methods = [\"getNothing\"]\n"),
+    ] {
+        let invalid = format!("{}{}", base, extra);
+        assert!(
+            load_config(&write_temp_config(name, &invalid)).is_err(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_load_config_blockhash_check() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "b1"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("blockhash_default", base)).unwrap();
+    assert!(!config.blockhash_check.enabled);
+    assert_eq!(config.blockhash_check.poll_ms, 1000);
+
+    let enabled = format!(
+        "{}\n[blockhash_check]\nenabled = true\npoll_ms = 500\n",
+        base
+    );
+    let config = load_config(&write_temp_config("blockhash_enabled", &enabled)).unwrap();
+    assert!(config.blockhash_check.enabled);
+    assert_eq!(config.blockhash_check.poll_ms, 500);
+
+    let invalid = format!("{}\n[blockhash_check]\nenabled = true\npoll_ms = 0\n", base);
+    assert!(load_config(&write_temp_config("blockhash_invalid", &invalid)).is_err());
+}
+
+#[test]
+fn test_load_config_usage_ledger() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "b1"
+url = "http://localhost:9000"
+weight = 1
+
+[usage_ledger]
+enabled = true
+"#;
+    let config = load_config(&write_temp_config("usage_ledger", base)).unwrap();
+    assert_eq!(config.usage_ledger.store, UsageLedgerStoreKind::Redis);
+    assert_eq!(config.usage_ledger.retention_days, 90);
+    assert!(!config.traffic_report.enabled);
+    assert_eq!(config.traffic_report.retention_days, 30);
+    assert!(!config.latency_heatmap.enabled);
+    assert_eq!(config.latency_heatmap.window_secs, 60);
+    assert_eq!(config.latency_heatmap.windows, 60);
+    assert!(!config.key_usage.enabled);
+    assert_eq!(config.key_usage.retention_days, 400);
+    assert_eq!(config.key_usage.flush_secs, 10);
+
+    let sqlite = format!(
+        "{}store = \"sqlite\"\npath = \"/var/lib/router/usage.db\"\n",
+        base
+    );
+    let config = load_config(&write_temp_config("usage_ledger_sqlite", &sqlite)).unwrap();
+    assert_eq!(config.usage_ledger.store, UsageLedgerStoreKind::Sqlite);
+    assert_eq!(config.usage_ledger.path, "/var/lib/router/usage.db");
+
+    for (name, extra) in [
+        ("usage_ledger_retention", "retention_days = 0\n"),
+        ("usage_ledger_flush", "flush_secs = 0\n"),
+        ("usage_ledger_path", "store = \"sqlite\"\npath = \"\"\n"),
+        (
+            "traffic_report_retention",
+            "\n[traffic_report]\nretention_days = 91\n",
+        ),
+        (
+            "latency_heatmap_window",
+            "\n[latency_heatmap]\nwindow_secs = 0\n",
+        ),
+        (
+            "latency_heatmap_windows",
+            "\n[latency_heatmap]\nwindows = 1441\n",
+        ),
+        ("key_usage_retention", "\n[key_usage]\nretention_days = 0\n"),
+        ("key_usage_flush", "\n[key_usage]\nflush_secs = 0\n"),
+    ] {
+        let invalid = format!("{}{}", base, extra);
+        assert!(
+            load_config(&write_temp_config(name, &invalid)).is_err(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_load_config_spending_caps() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "b1"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("spending_caps_default", base)).unwrap();
+    assert!(!config.spending_caps.enabled);
+    assert_eq!(config.spending_caps.throttled_rate_limit, 1);
+    assert!(config.spending_caps.webhook_url.is_empty());
+
+    let enabled = format!(
+        "{}\n[spending_caps]\nenabled = true\ndefault_price = 0.00002\nwebhook_url = \"https://hooks.example.com/spend?token=hunter2\"\n\n[spending_caps.tier_prices]\npro = 0.00001\n",
+        base
+    );
+    let config = load_config(&write_temp_config("spending_caps", &enabled)).unwrap();
+    assert_eq!(config.spending_caps.tier_prices["pro"], 0.00001);
+    let redacted = config.redacted().to_string();
+    assert!(!redacted.contains("hunter2"));
+
+    for (name, section) in [
+        ("spending_caps_price", "default_price = -1.0"),
+        ("spending_caps_tier_price", "tier_prices = { pro = -0.1 }"),
+        ("spending_caps_throttle", "throttled_rate_limit = 0"),
+        ("spending_caps_flush", "flush_secs = 0"),
+        (
+            "spending_caps_webhook",
+            "webhook_url = \"hooks.example.com\"",
+        ),
+    ] {
+        let invalid = format!("{}\n[spending_caps]\n{}\n", base, section);
+        assert!(
+            load_config(&write_temp_config(name, &invalid)).is_err(),
+            "{}",
+            name
+        );
+    }
+}
+
 #[test]
 fn test_load_config_missing_metrics_port() {
     let path = write_temp_config(
@@ -296,3 +738,1890 @@ weight = 1
         err
     );
 }
+
+#[test]
+fn test_load_config_signing_requires_key() {
+    let path = write_temp_config(
+        "signing_no_key",
+        r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "gateway"
+url = "http://localhost:9000"
+weight = 1
+
+[backends.signing]
+key_env = "SOL_RPC_ROUTER_TEST_UNSET_SIGNING_KEY"
+"#,
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("key_env"), "{}", err);
+}
+
+#[test]
+fn test_load_config_exclude_methods() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "light"
+url = "http://localhost:9000"
+weight = 1
+exclude_methods = ["getProgramAccounts"]
+"#;
+    let path = write_temp_config("exclude_methods_valid", base);
+    let config = load_config(&path).unwrap();
+    assert_eq!(
+        config.backends[0].exclude_methods,
+        vec!["getProgramAccounts"]
+    );
+
+    let path = write_temp_config(
+        "exclude_methods_unknown",
+        &base.replace("getProgramAccounts", "getProgramAcounts"),
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("unknown method 'getProgramAcounts'"),
+        "{}",
+        err
+    );
+
+    let path = write_temp_config(
+        "exclude_methods_route_conflict",
+        &format!(
+            "{}\n[method_routes]\ngetProgramAccounts = \"light\"\n",
+            base
+        ),
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("excludes it"), "{}", err);
+}
+
+#[test]
+fn test_load_config_method_paths() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "provider"
+url = "http://localhost:9000"
+weight = 1
+method_paths = { getTransactions = "/v0/transactions" }
+"#;
+    let path = write_temp_config("method_paths_valid", base);
+    let config = load_config(&path).unwrap();
+    assert_eq!(
+        config.backends[0].method_paths["getTransactions"],
+        "/v0/transactions"
+    );
+
+    let path = write_temp_config(
+        "method_paths_relative",
+        &base.replace("\"/v0/transactions\"", "\"v0/transactions\""),
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("must start with '/'"), "{}", err);
+
+    let path = write_temp_config(
+        "method_paths_invalid",
+        &base.replace("/v0/transactions", "/v0/trans actions"),
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("invalid method path"), "{}", err);
+}
+
+#[test]
+fn test_load_config_probes() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+
+[probes]
+cidrs = ["10.20.0.0/16", "2001:db8::/32"]
+keys = ["probe-key"]
+"#;
+    let path = write_temp_config("probes_valid", base);
+    let config = load_config(&path).unwrap();
+    let probes = &config.probes;
+    assert!(probes.is_probe("10.20.3.4".parse().unwrap(), "customer-key"));
+    assert!(probes.is_probe("2001:db8::1".parse().unwrap(), "customer-key"));
+    assert!(probes.is_probe("192.0.2.1".parse().unwrap(), "new-key, probe-key"));
+    assert!(!probes.is_probe("10.21.0.1".parse().unwrap(), "customer-key"));
+    assert_eq!(config.redacted()["probes"]["keys"][0], "[REDACTED]");
+
+    let path = write_temp_config("probes_bad_cidr", &base.replace("/16", "/33"));
+    assert!(load_config(&path).is_err());
+
+    let path = write_temp_config("probes_empty_key", &base.replace("probe-key", " "));
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("probes.keys"), "{}", err);
+}
+
+#[test]
+fn test_load_config_hash_tracks_contents() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let first = load_config(&write_temp_config("hash_a", base)).unwrap();
+    let same = load_config(&write_temp_config("hash_b", base)).unwrap();
+    let changed = load_config(&write_temp_config(
+        "hash_c",
+        &base.replace("weight = 1", "weight = 2"),
+    ))
+    .unwrap();
+
+    assert_eq!(first.hash.len(), 12);
+    assert_eq!(first.hash, same.hash);
+    assert_ne!(first.hash, changed.hash);
+}
+
+#[test]
+fn test_parse_config_env_overrides() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let overrides = |vars: &[(&str, &str)]| -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+    let plain = parse_config(base).unwrap();
+    let config = parse_config_with_overrides(
+        base,
+        &overrides(&[
+            ("ROUTER__REDIS_URL", "redis://cache:6379"),
+            ("ROUTER__BACKENDS__0__WEIGHT", "3"),
+            ("ROUTER__HEALTH_CHECK__INTERVAL_SECS", "10"),
+            ("ROUTER__ADMIN__TOKENS", r#"["admin-token-0123456789"]"#),
+        ]),
+    )
+    .unwrap();
+    assert_eq!(config.redis_url, "redis://cache:6379");
+    assert_eq!(config.backends[0].weight, 3);
+    assert_eq!(config.health_check.interval_secs, 10);
+    assert_eq!(config.admin.tokens, vec!["admin-token-0123456789"]);
+    assert_ne!(config.hash, plain.hash);
+    assert_eq!(
+        config
+            .overrides
+            .get("backends.0.weight")
+            .map(String::as_str),
+        Some("ROUTER__BACKENDS__0__WEIGHT")
+    );
+
+    let sources: HashMap<String, ValueSource> = config
+        .effective_values(base)
+        .into_iter()
+        .map(|(path, _, source)| (path, source))
+        .collect();
+    assert_eq!(
+        sources["health_check.interval_secs"],
+        ValueSource::Env("ROUTER__HEALTH_CHECK__INTERVAL_SECS".to_string())
+    );
+    assert_eq!(sources["backends.0.label"], ValueSource::File);
+    assert_eq!(sources["proxy.timeout_secs"], ValueSource::Default);
+
+    // Overrides go through the same validation as the file
+    for vars in [
+        &[("ROUTER__BACKENDS__0__WEIGHT", "0")][..],
+        &[("ROUTER__BACKENDS__3__WEIGHT", "1")],
+        &[("ROUTER__PORT", "not-a-port")],
+        &[("ROUTER____PORT", "1")],
+    ] {
+        assert!(
+            parse_config_with_overrides(base, &overrides(vars)).is_err(),
+            "{:?}",
+            vars
+        );
+    }
+}
+
+#[test]
+fn test_load_config_requires_a_backend_in_rotation() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+drain = true
+
+[[backends]]
+label = "b"
+url = "http://localhost:9001"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("drain_one", base)).unwrap();
+    assert!(config.backends[0].drain);
+    assert!(!config.backends[1].maintenance);
+
+    let all_out = format!("{}maintenance = true\n", base);
+    let err = load_config(&write_temp_config("drain_all", &all_out)).unwrap_err();
+    assert!(err.to_string().contains("drain and maintenance"), "{}", err);
+}
+
+#[test]
+fn test_load_config_adaptive_health_bounds() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[health_check]
+adaptive = true
+interval_secs = 30
+min_interval_secs = 5
+max_interval_secs = 300
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("adaptive_valid", base)).unwrap();
+    assert!(config.health_check.adaptive);
+
+    let path = write_temp_config(
+        "adaptive_inverted",
+        &base.replace("min_interval_secs = 5", "min_interval_secs = 60"),
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("min_interval_secs"), "{}", err);
+}
+
+#[test]
+fn test_load_config_reference_sources() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[health_check.reference_sources]]
+label = "explorer"
+url = "https://explorer.example.com/api/status"
+kind = "http"
+json_pointer = "/data/slot"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("reference_valid", base)).unwrap();
+    assert_eq!(config.health_check.reference_sources.len(), 1);
+
+    let path = write_temp_config(
+        "reference_no_pointer",
+        &base.replace("json_pointer = \"/data/slot\"\n", ""),
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("json_pointer"), "{}", err);
+
+    let path = write_temp_config(
+        "reference_rpc_wrong_method",
+        &base.replace("kind = \"http\"", "kind = \"rpc\"").replace(
+            "[[health_check.reference_sources]]",
+            "[health_check]\nmethod = \"getHealth\"\n\n[[health_check.reference_sources]]",
+        ),
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("getSlot"), "{}", err);
+
+    // An explorer reports a slot, which cannot be compared to block heights
+    let path = write_temp_config(
+        "reference_http_block_height",
+        &base.replace(
+            "[[health_check.reference_sources]]",
+            "[health_check]\nmethod = \"getBlockHeight\"\n\n[[health_check.reference_sources]]",
+        ),
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("kind http requires"), "{}", err);
+}
+
+#[test]
+fn test_load_config_readiness_groups() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[readiness]
+required_groups = ["archival", "standard"]
+
+[[backends]]
+label = "archive"
+url = "http://localhost:9000"
+weight = 1
+groups = ["archival"]
+
+[[backends]]
+label = "fast"
+url = "http://localhost:9001"
+weight = 1
+groups = ["standard"]
+"#;
+    let config = load_config(&write_temp_config("readiness_valid", base)).unwrap();
+    assert_eq!(config.backends[0].groups, vec!["archival"]);
+
+    let path = write_temp_config(
+        "readiness_unknown_group",
+        &base.replace("\"standard\"]\n\n[[", "\"standard\", \"das\"]\n\n[["),
+    );
+    let err = load_config(&path).unwrap_err();
+    assert!(err.to_string().contains("'das'"), "{}", err);
+}
+
+#[test]
+fn test_load_config_hedging_and_routing() {
+    let config_for = |hedging: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[hedging]
+{}
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#,
+            hedging
+        )
+    };
+
+    let config = load_config(&write_temp_config("hedging_default", &config_for(""))).unwrap();
+    assert!(!config.hedging.enabled);
+    assert_eq!(
+        config.hedging.methods,
+        vec!["getSlot", "getBlockHeight", "getLatestBlockhash"]
+    );
+    assert_eq!(config.hedging.budget_percent, 10);
+    assert!(!config.hedging.is_delayed());
+    assert_eq!(config.routing.mode, RoutingMode::Weighted);
+    assert_eq!(config.routing.latency_tolerance_percent, 20);
+
+    let config = load_config(&write_temp_config(
+        "hedging_delayed",
+        &config_for("delay_ms = 200\ndelay_percentile = 95"),
+    ))
+    .unwrap();
+    assert_eq!(config.hedging.delay_ms, 200);
+    assert_eq!(config.hedging.delay_percentile, 95);
+    assert!(config.hedging.is_delayed());
+
+    let config = load_config(&write_temp_config(
+        "routing_least_latency",
+        &config_for("[routing]\nmode = \"least_latency\""),
+    ))
+    .unwrap();
+    assert_eq!(config.routing.mode, RoutingMode::LeastLatency);
+    assert_eq!(
+        config.bind_addresses,
+        vec![
+            "0.0.0.0".parse::<std::net::IpAddr>().unwrap(),
+            "::".parse().unwrap()
+        ]
+    );
+
+    for (name, hedging, expected) in [
+        ("hedging_budget", "budget_percent = 150", "budget_percent"),
+        (
+            "hedging_percentile",
+            "delay_percentile = 100",
+            "delay_percentile",
+        ),
+        (
+            "hedging_delay",
+            "delay_ms = 30000\n[proxy]\ntimeout_secs = 30",
+            "delay_ms",
+        ),
+        (
+            "hedging_unknown",
+            "methods = [\"getSlots\"]",
+            "unknown method 'getSlots'",
+        ),
+        (
+            "hedging_write",
+            "methods = [\"sendTransaction\"]",
+            "cannot be sent twice",
+        ),
+        (
+            "routing_min_share",
+            "[routing]\nmode = \"auto\"\nmin_share_percent = 0",
+            "min_share_percent",
+        ),
+        (
+            "routing_latency_tolerance",
+            "[routing]\nmode = \"least_latency\"\nlatency_tolerance_percent = 150",
+            "latency_tolerance_percent",
+        ),
+    ] {
+        let err = load_config(&write_temp_config(name, &config_for(hedging))).unwrap_err();
+        assert!(err.to_string().contains(expected), "{}", err);
+    }
+}
+
+#[test]
+fn test_load_config_coalescing() {
+    let config_for = |coalescing: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[coalescing]
+{}
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#,
+            coalescing
+        )
+    };
+
+    let config = load_config(&write_temp_config("coalescing_default", &config_for(""))).unwrap();
+    assert!(!config.coalescing.enabled);
+    assert_eq!(
+        config.coalescing.methods,
+        vec!["getSlot", "getBlockHeight", "getLatestBlockhash"]
+    );
+    assert_eq!(config.coalescing.max_response_bytes, 1024 * 1024);
+
+    let config = load_config(&write_temp_config(
+        "coalescing_enabled",
+        &config_for("enabled = true\nmethods = [\"getAccountInfo\"]"),
+    ))
+    .unwrap();
+    assert!(config.coalescing.enabled);
+    assert_eq!(config.coalescing.methods, vec!["getAccountInfo"]);
+
+    for (name, coalescing, expected) in [
+        (
+            "coalescing_unknown",
+            "methods = [\"getSlots\"]",
+            "unknown method 'getSlots'",
+        ),
+        (
+            "coalescing_write",
+            "methods = [\"sendTransaction\"]",
+            "is not a read",
+        ),
+        (
+            "coalescing_max_bytes",
+            "max_response_bytes = 0",
+            "max_response_bytes",
+        ),
+    ] {
+        let err = load_config(&write_temp_config(name, &config_for(coalescing))).unwrap_err();
+        assert!(err.to_string().contains(expected), "{}", err);
+    }
+}
+
+#[test]
+fn test_load_config_bind_addresses() {
+    let config_for = |bind: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+bind_addresses = {}
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#,
+            bind
+        )
+    };
+
+    let config = load_config(&write_temp_config(
+        "bind_v6_only",
+        &config_for(r#"["::1", "2001:db8::10"]"#),
+    ))
+    .unwrap();
+    assert_eq!(config.bind_addresses.len(), 2);
+
+    for (name, bind) in [
+        ("bind_empty", "[]"),
+        ("bind_duplicate", r#"["::", "::"]"#),
+        ("bind_invalid", r#"["localhost"]"#),
+    ] {
+        assert!(load_config(&write_temp_config(name, &config_for(bind))).is_err());
+    }
+}
+
+#[test]
+fn test_load_config_admin_bind_addresses() {
+    let config_for = |admin_bind: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+{}
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#,
+            admin_bind
+        )
+    };
+
+    // Loopback only unless configured
+    let config = load_config(&write_temp_config("admin_bind_default", &config_for(""))).unwrap();
+    assert_eq!(
+        config.admin_bind_addresses,
+        vec!["127.0.0.1".parse::<std::net::IpAddr>().unwrap()]
+    );
+
+    let config = load_config(&write_temp_config(
+        "admin_bind_private",
+        &config_for(r#"admin_bind_addresses = ["10.0.0.5", "::1"]"#),
+    ))
+    .unwrap();
+    assert_eq!(config.admin_bind_addresses.len(), 2);
+    assert_eq!(config.bind_addresses.len(), 2);
+
+    for (name, admin_bind, expected) in [
+        (
+            "admin_bind_empty",
+            "admin_bind_addresses = []",
+            "must not be empty",
+        ),
+        (
+            "admin_bind_duplicate",
+            r#"admin_bind_addresses = ["::1", "::1"]"#,
+            "lists ::1 twice",
+        ),
+    ] {
+        let err = load_config(&write_temp_config(name, &config_for(admin_bind))).unwrap_err();
+        assert!(err.to_string().contains(expected), "{}", err);
+    }
+}
+
+#[test]
+fn test_load_config_cache() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("cache_default", base)).unwrap();
+    assert!(config.cache.enabled);
+
+    let disabled = format!("{}\n[cache]\nenabled = false\n", base);
+    let config = load_config(&write_temp_config("cache_disabled", &disabled)).unwrap();
+    assert!(!config.cache.enabled);
+    assert_eq!(config.cache.serve_stale_secs, 0);
+
+    let stale = format!(
+        "{}\n[cache]\nserve_stale_secs = 120\nstale_methods = [\"getSlot\", \"getBalance\"]\n",
+        base
+    );
+    let config = load_config(&write_temp_config("cache_stale", &stale)).unwrap();
+    assert_eq!(config.cache.serve_stale_secs, 120);
+    assert_eq!(config.cache.stale_methods, vec!["getSlot", "getBalance"]);
+    assert_eq!(config.cache.commitment_ttl_slots.finalized, 4);
+
+    let ttl = format!(
+        "{}\n[cache.commitment_ttl_slots]\nconfirmed = 2\nfinalized = 32\n",
+        base
+    );
+    let config = load_config(&write_temp_config("cache_ttl", &ttl)).unwrap();
+    let slots = config.cache.commitment_ttl_slots;
+    assert_eq!(
+        (slots.processed, slots.confirmed, slots.finalized),
+        (1, 2, 32)
+    );
+
+    let methods = format!(
+        "{}\n[cache.method_ttl_secs]\ngetVersion = 300\ngetGenesisHash = 3600\n",
+        base
+    );
+    let config = load_config(&write_temp_config("cache_methods", &methods)).unwrap();
+    assert_eq!(config.cache.method_ttl_secs["getVersion"], 300);
+    assert_eq!(config.cache.method_ttl_secs["getGenesisHash"], 3600);
+    assert!(!config.cache.ttl_tuning.enabled);
+
+    let tuned = format!(
+        "{}\n[cache.method_ttl_secs]\ngetVersion = 300\n[cache.ttl_tuning]\nenabled = true\nmethods = {{ getVersion = {{ min_secs = 60, max_secs = 900 }} }}\n",
+        base
+    );
+    let config = load_config(&write_temp_config("cache_tuned", &tuned)).unwrap();
+    let tuning = &config.cache.ttl_tuning;
+    assert_eq!(tuning.methods["getVersion"].max_secs, 900);
+    assert_eq!(
+        (
+            tuning.explore_percent,
+            tuning.revalidate_percent,
+            tuning.staleness_penalty
+        ),
+        (10, 5, 10)
+    );
+    assert!(!config.cache.network_stats.enabled);
+
+    let network = format!(
+        "{}\n[cache.network_stats]\nenabled = true\ncross_check = true\nttl_secs = 600\n",
+        base
+    );
+    let config = load_config(&write_temp_config("cache_network", &network)).unwrap();
+    let network = &config.cache.network_stats;
+    assert!(network.enabled && network.cross_check);
+    assert_eq!((network.ttl_secs, network.tolerance_bps), (600, 10));
+
+    let revalidated = format!(
+        "{}\n[cache]\nrevalidate_methods = [\"getAccountInfo\", \"getTokenAccountsByOwner\"]\n[cache.method_ttl_secs]\ngetAccountInfo = 2\n",
+        base
+    );
+    let config = load_config(&write_temp_config("cache_revalidate", &revalidated)).unwrap();
+    assert_eq!(
+        config.cache.revalidate_methods,
+        vec!["getAccountInfo", "getTokenAccountsByOwner"]
+    );
+
+    for (name, section) in [
+        ("cache_ttl_zero", "commitment_ttl_slots = { processed = 0 }"),
+        ("cache_ttl_too_long", "commitment_ttl_slots = { finalized = 1000 }"),
+        ("cache_stale_too_old", "serve_stale_secs = 7200"),
+        ("cache_stale_unknown", "stale_methods = [\"getSlots\"]"),
+        ("cache_stale_write", "stale_methods = [\"sendTransaction\"]"),
+        ("ttl_unknown", "method_ttl_secs = { getVersions = 60 }"),
+        ("ttl_write", "method_ttl_secs = { sendTransaction = 60 }"),
+        ("ttl_zero", "method_ttl_secs = { getVersion = 0 }"),
+        ("ttl_long", "method_ttl_secs = { getVersion = 100000 }"),
+        (
+            "tuned_not_configured",
+            "ttl_tuning = { methods = { getVersion = { min_secs = 1, max_secs = 2 } } }",
+        ),
+        (
+            "tuned_inverted",
+            "method_ttl_secs = { getVersion = 60 }\nttl_tuning = { methods = { getVersion = { min_secs = 20, max_secs = 10 } } }",
+        ),
+        (
+            "tuned_zero",
+            "method_ttl_secs = { getVersion = 60 }\nttl_tuning = { methods = { getVersion = { min_secs = 0, max_secs = 10 } } }",
+        ),
+        (
+            "tuned_empty",
+            "ttl_tuning = { enabled = true }",
+        ),
+        ("revalidate_no_slot", "method_ttl_secs = { getVersion = 60 }\nrevalidate_methods = [\"getVersion\"]"),
+        ("revalidate_uncached", "revalidate_methods = [\"getAccountInfo\"]"),
+        ("network_ttl_zero", "network_stats = { ttl_secs = 0 }"),
+        ("network_tolerance", "network_stats = { tolerance_bps = 10001 }"),
+        (
+            "tuned_explore",
+            "method_ttl_secs = { getVersion = 60 }\nttl_tuning = { enabled = true, explore_percent = 101, methods = { getVersion = { min_secs = 1, max_secs = 2 } } }",
+        ),
+    ] {
+        let invalid = format!("{}\n[cache]\n{}\n", base, section);
+        assert!(load_config(&write_temp_config(name, &invalid)).is_err());
+    }
+}
+
+#[test]
+fn test_load_config_kill_switches() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("kill_switches_default", base)).unwrap();
+    assert!(config.kill_switches.disabled.is_empty());
+
+    let off = format!(
+        "{}\n[kill_switches]\ndisabled = [\"cache\", \"hedging\"]\n",
+        base
+    );
+    let config = load_config(&write_temp_config("kill_switches_off", &off)).unwrap();
+    assert_eq!(
+        config.kill_switches.disabled,
+        vec![Subsystem::Cache, Subsystem::Hedging]
+    );
+
+    let unknown = format!("{}\n[kill_switches]\ndisabled = [\"caching\"]\n", base);
+    assert!(load_config(&write_temp_config("kill_switches_unknown", &unknown)).is_err());
+}
+
+#[test]
+fn test_load_config_log_format() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("log_format_default", base)).unwrap();
+    assert_eq!(config.logging.log_format, LogFormat::Text);
+
+    let json = format!("{}\n[logging]\nlog_format = \"json\"\n", base);
+    let config = load_config(&write_temp_config("log_format_json", &json)).unwrap();
+    assert_eq!(config.logging.log_format, LogFormat::Json);
+
+    let unknown = format!("{}\n[logging]\nlog_format = \"xml\"\n", base);
+    assert!(load_config(&write_temp_config("log_format_unknown", &unknown)).is_err());
+}
+
+#[test]
+fn test_load_config_retry() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("retry_default", base)).unwrap();
+    assert_eq!(config.retry.max_attempts, 1);
+    assert_eq!(config.retry.retryable_status, vec![429, 500, 502, 503, 504]);
+    // Without failover, attempts get the whole proxy timeout
+    assert_eq!(config.retry.attempt_timeout(30), Duration::from_secs(30));
+
+    let retry = format!(
+        "{}\n[retry]\nmax_attempts = 3\nattempt_timeout_secs = 5\nretryable_status = [503]\n",
+        base
+    );
+    let config = load_config(&write_temp_config("retry_custom", &retry)).unwrap();
+    assert_eq!(config.retry.max_attempts, 3);
+    assert_eq!(config.retry.attempt_timeout(30), Duration::from_secs(5));
+    assert!(config.retry.retries_status(503));
+    assert!(!config.retry.retries_status(500));
+    assert_eq!(config.retry.first_byte_timeout(None), None);
+
+    let first_byte = format!(
+        "{}first_byte_timeout_ms = 0\n\n[retry]\nmax_attempts = 2\nfirst_byte_timeout_ms = 250\nkeep_slow_attempts = true\n",
+        base
+    );
+    let config = load_config(&write_temp_config("retry_first_byte", &first_byte)).unwrap();
+    assert!(config.retry.keep_slow_attempts);
+    assert_eq!(
+        config.retry.first_byte_timeout(None),
+        Some(Duration::from_millis(250))
+    );
+    // The backend switches it off for itself
+    let backend = config.backends[0].first_byte_timeout_ms;
+    assert_eq!(config.retry.first_byte_timeout(backend), None);
+
+    for (name, section) in [
+        ("retry_zero", "max_attempts = 0"),
+        ("retry_too_many", "max_attempts = 10"),
+        ("retry_success", "retryable_status = [200]"),
+    ] {
+        let invalid = format!("{}\n[retry]\n{}\n", base, section);
+        assert!(load_config(&write_temp_config(name, &invalid)).is_err());
+    }
+}
+
+#[test]
+fn test_load_config_method_idempotency() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("idempotency_default", base)).unwrap();
+    assert!(config.method_idempotency.is_empty());
+
+    // Custom methods classified safe may be hedged and cached
+    let custom = format!(
+        "{}\n[method_idempotency]\ngetAssetsByOwner = \"safe\"\nmintCompressedNft = \"non_idempotent\"\n\n[hedging]\nmethods = [\"getAssetsByOwner\"]\n\n[cache.method_ttl_secs]\ngetAssetsByOwner = 5\n",
+        base
+    );
+    let config = load_config(&write_temp_config("idempotency_custom", &custom)).unwrap();
+    assert_eq!(
+        config.method_idempotency["mintCompressedNft"],
+        Idempotency::NonIdempotent
+    );
+    assert_eq!(config.cache.method_ttl_secs["getAssetsByOwner"], 5);
+
+    for (name, section, expected) in [
+        (
+            "idempotency_builtin",
+            "[method_idempotency]\nrequestAirdrop = \"safe\"",
+            "built-in method",
+        ),
+        (
+            "idempotency_unknown",
+            "[method_idempotency]\ngetAssetsByOwner = \"sometimes\"",
+            "unknown variant",
+        ),
+        (
+            "idempotency_hedge",
+            "[method_idempotency]\nmintCompressedNft = \"idempotent\"\n\n[hedging]\nmethods = [\"mintCompressedNft\"]",
+            "cannot be sent twice",
+        ),
+        (
+            "idempotency_cache",
+            "[method_idempotency]\nmintCompressedNft = \"non_idempotent\"\n\n[cache]\nstale_methods = [\"mintCompressedNft\"]",
+            "is not a read",
+        ),
+    ] {
+        let invalid = format!("{}\n{}\n", base, section);
+        let err = load_config(&write_temp_config(name, &invalid)).unwrap_err();
+        assert!(err.to_string().contains(expected), "{}: {}", name, err);
+    }
+}
+
+#[test]
+fn test_load_config_circuit_breaker() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("breaker_default", base)).unwrap();
+    assert!(!config.circuit_breaker.enabled);
+    assert_eq!(config.circuit_breaker.failure_percent, 50);
+
+    let custom = format!(
+        "{}\n[circuit_breaker]\nenabled = true\nmin_requests = 50\ncooldown_secs = 10\n",
+        base
+    );
+    let config = load_config(&write_temp_config("breaker_custom", &custom)).unwrap();
+    assert_eq!(config.circuit_breaker.min_requests, 50);
+    assert_eq!(config.circuit_breaker.cooldown_secs, 10);
+
+    for (name, section) in [
+        ("breaker_window", "enabled = true\nwindow_secs = 0"),
+        ("breaker_cooldown", "enabled = true\ncooldown_secs = 0"),
+        ("breaker_percent", "failure_percent = 0"),
+        ("breaker_over_100", "failure_percent = 150"),
+    ] {
+        let invalid = format!("{}\n[circuit_breaker]\n{}\n", base, section);
+        let path = write_temp_config(name, &invalid);
+        assert!(load_config(&path).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn test_load_config_consistency() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("consistency_default", base)).unwrap();
+    assert!(!config.consistency.enabled);
+    assert!(config.consistency.canaries.is_empty());
+
+    let canaries = r#"
+[consistency]
+enabled = true
+interval_secs = 30
+
+[[consistency.canaries]]
+name = "supply"
+method = "getSupply"
+
+[[consistency.canaries]]
+name = "treasury"
+method = "getBalance"
+params = ["11111111111111111111111111111111"]
+"#;
+    let valid = format!("{}{}", base, canaries);
+    let config = load_config(&write_temp_config("consistency_custom", &valid)).unwrap();
+    assert_eq!(config.consistency.interval_secs, 30);
+    assert_eq!(config.consistency.canaries.len(), 2);
+    assert!(config.consistency.canaries[0].params.is_null());
+
+    for (name, section) in [
+        ("consistency_interval", "interval_secs = 0"),
+        (
+            "consistency_unnamed",
+            "[[consistency.canaries]]\nname = \"\"\nmethod = \"getSlot\"",
+        ),
+        (
+            "consistency_write",
+            "[[consistency.canaries]]\nname = \"tx\"\nmethod = \"sendTransaction\"",
+        ),
+        (
+            "consistency_params",
+            "[[consistency.canaries]]\nname = \"slot\"\nmethod = \"getSlot\"\nparams = 1",
+        ),
+    ] {
+        let invalid = format!("{}\n[consistency]\nenabled = true\n{}\n", base, section);
+        let path = write_temp_config(name, &invalid);
+        assert!(load_config(&path).is_err(), "{}", name);
+    }
+
+    // Canary names identify metrics, so must be unique
+    let duplicate = format!(
+        "{}{}\n[[consistency.canaries]]\nname = \"supply\"\nmethod = \"getSlot\"\n",
+        base, canaries
+    );
+    assert!(load_config(&write_temp_config("consistency_duplicate", &duplicate)).is_err());
+}
+
+#[test]
+fn test_load_config_genesis_check() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("genesis_default", base)).unwrap();
+    assert!(!config.genesis_check.enabled);
+
+    let valid = format!(
+        "{}\n[genesis_check]\nenabled = true\nnetwork = \"devnet\"\n",
+        base
+    );
+    let config = load_config(&write_temp_config("genesis_network", &valid)).unwrap();
+    assert_eq!(
+        config.genesis_check.expected(),
+        Some("EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG")
+    );
+
+    for (name, section) in [
+        ("genesis_none", "enabled = true"),
+        ("genesis_unknown", "network = \"mainnet\""),
+        ("genesis_hash", "expected_hash = \"not-a-hash\""),
+        (
+            "genesis_both",
+            "enabled = true\nnetwork = \"devnet\"\nexpected_hash = \"EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG\"",
+        ),
+        (
+            "genesis_interval",
+            "enabled = true\nnetwork = \"devnet\"\ninterval_secs = 0",
+        ),
+    ] {
+        let invalid = format!("{}\n[genesis_check]\n{}\n", base, section);
+        let path = write_temp_config(name, &invalid);
+        assert!(load_config(&path).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn test_load_config_archive_routing() {
+    let config_for = |archive: bool, extra: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+{}
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+archive = {}
+"#,
+            extra, archive
+        )
+    };
+    let config = load_config(&write_temp_config(
+        "archive_default",
+        &config_for(false, ""),
+    ))
+    .unwrap();
+    assert!(!config.archive_routing.enabled);
+    assert_eq!(config.archive_routing.min_depth_slots, 100_000);
+    assert!(!config.backends[0].archive);
+
+    let enabled = "[archive_routing]\nenabled = true";
+    let config = load_config(&write_temp_config(
+        "archive_enabled",
+        &config_for(true, enabled),
+    ))
+    .unwrap();
+    assert!(config.archive_routing.enabled);
+    assert!(config.backends[0].archive);
+
+    for (name, archive, extra) in [
+        ("archive_no_backend", false, enabled.to_string()),
+        (
+            "archive_depth",
+            true,
+            format!("{}\nmin_depth_slots = 0", enabled),
+        ),
+        (
+            "archive_block_height",
+            true,
+            format!("[health_check]\nmethod = \"getBlockHeight\"\n\n{}", enabled),
+        ),
+    ] {
+        let path = write_temp_config(name, &config_for(archive, &extra));
+        assert!(load_config(&path).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn test_load_config_error_templates() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("error_templates_default", base)).unwrap();
+    assert!(config.error_templates.unauthorized.is_none());
+
+    let templates = format!(
+        "{}{}",
+        base,
+        r#"
+[error_templates.rate_limited]
+code = "RATE_LIMITED"
+message = "{message}"
+links = { upgrade = "https://example.com/pricing" }
+
+[error_templates.no_backend]
+error = { code = "{status}", ticket = "{correlation_id}" }
+"#
+    );
+    let config = load_config(&write_temp_config("error_templates_custom", &templates)).unwrap();
+    let links = &config.error_templates.rate_limited.unwrap()["links"];
+    assert_eq!(links["upgrade"], "https://example.com/pricing");
+    assert!(config.error_templates.no_backend.is_some());
+
+    // Only no_backend has a retry hint to fill in
+    for (name, section) in [
+        ("templates_hint", "unauthorized.id = \"{queue_depth}\""),
+        ("templates_unknown", "forbidden = { code = \"NO\" }"),
+        ("templates_scalar", "unauthorized = \"denied\""),
+    ] {
+        let invalid = format!("{}\n[error_templates]\n{}\n", base, section);
+        let path = write_temp_config(name, &invalid);
+        assert!(load_config(&path).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn test_load_config_dead_letter() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("dead_letter_default", base)).unwrap();
+    assert!(!config.dead_letter.enabled);
+    assert_eq!(config.dead_letter.store, DeadLetterStoreKind::Redis);
+
+    let file = format!(
+        "{}\n[dead_letter]\nenabled = true\nstore = \"file\"\npath = \"/var/lib/router/dead.jsonl\"\n",
+        base
+    );
+    let config = load_config(&write_temp_config("dead_letter_file", &file)).unwrap();
+    assert_eq!(config.dead_letter.store, DeadLetterStoreKind::File);
+
+    for (name, section) in [
+        ("dead_letter_no_path", "store = \"file\"\npath = \"\""),
+        ("dead_letter_zero_cap", "max_entries = 0"),
+    ] {
+        let invalid = format!("{}\n[dead_letter]\n{}\n", base, section);
+        assert!(load_config(&write_temp_config(name, &invalid)).is_err());
+    }
+}
+
+#[test]
+fn test_load_config_journal() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("journal_default", base)).unwrap();
+    assert!(!config.journal.enabled);
+    assert_eq!(config.journal.store, JournalStoreKind::Redis);
+    assert_eq!(config.journal.retention_secs, 86_400);
+    assert_eq!(config.journal.resume_secs, 90);
+
+    let file = format!(
+        "{}\n[journal]\nenabled = true\nstore = \"file\"\npath = \"/var/lib/router/journal.jsonl\"\nresume_secs = 0\n",
+        base
+    );
+    let config = load_config(&write_temp_config("journal_file", &file)).unwrap();
+    assert_eq!(config.journal.store, JournalStoreKind::File);
+    assert_eq!(config.journal.resume_secs, 0);
+
+    for (name, section) in [
+        ("journal_no_path", "store = \"file\"\npath = \"\""),
+        ("journal_zero_retention", "retention_secs = 0"),
+        (
+            "journal_long_resume",
+            "retention_secs = 60\nresume_secs = 61",
+        ),
+    ] {
+        let invalid = format!("{}\n[journal]\n{}\n", base, section);
+        assert!(load_config(&write_temp_config(name, &invalid)).is_err());
+    }
+}
+
+#[test]
+fn test_load_config_tls() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("tls_default", base)).unwrap();
+    assert!(!config.tls.enabled);
+    assert_eq!(config.tls.reload_secs, 300);
+
+    let tls = format!(
+        "{}\n[tls]\nenabled = true\ncert_path = \"/etc/router/fullchain.pem\"\nkey_path = \"/etc/router/privkey.pem\"\n",
+        base
+    );
+    let config = load_config(&write_temp_config("tls_enabled", &tls)).unwrap();
+    assert_eq!(config.tls.cert_path, "/etc/router/fullchain.pem");
+
+    for (name, section) in [
+        ("tls_no_cert", "enabled = true\nkey_path = \"key.pem\""),
+        ("tls_no_key", "enabled = true\ncert_path = \"cert.pem\""),
+        ("tls_zero_reload", "reload_secs = 0"),
+    ] {
+        let invalid = format!("{}\n[tls]\n{}\n", base, section);
+        assert!(load_config(&write_temp_config(name, &invalid)).is_err());
+    }
+}
+
+#[test]
+fn test_load_config_backend_protocol() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+"#;
+    let backend = |url: &str, protocol: &str| {
+        format!(
+            "{}\n[[backends]]\nlabel = \"a\"\nurl = \"{}\"\nweight = 1\n[backends.protocol]\n{}\n",
+            base, url, protocol
+        )
+    };
+
+    let config = load_config(&write_temp_config(
+        "protocol_default",
+        &backend("http://localhost:9000", ""),
+    ))
+    .unwrap();
+    let protocol = &config.backends[0].protocol;
+    assert_eq!(protocol.http_version, HttpVersion::Auto);
+    assert!(protocol.alpn.is_empty());
+    assert!(protocol.allow_plaintext);
+    assert!(!protocol.is_custom());
+
+    let config = load_config(&write_temp_config(
+        "protocol_h2c",
+        &backend("http://10.0.0.5:8899", "http_version = \"http2\""),
+    ))
+    .unwrap();
+    assert_eq!(config.backends[0].protocol.http_version, HttpVersion::Http2);
+    assert_eq!(config.backends[0].protocol.alpn_offer(), ["h2"]);
+
+    let config = load_config(&write_temp_config(
+        "protocol_alpn",
+        &backend(
+            "https://rpc.example.com",
+            "alpn = [\"h2\", \"http/1.1\"]\nallow_plaintext = false",
+        ),
+    ))
+    .unwrap();
+    assert_eq!(config.backends[0].protocol.alpn_offer(), ["h2", "http/1.1"]);
+
+    for (name, url, protocol) in [
+        (
+            "protocol_plaintext",
+            "http://localhost:9000",
+            "allow_plaintext = false",
+        ),
+        (
+            "protocol_alpn_http",
+            "http://localhost:9000",
+            "alpn = [\"h2\"]",
+        ),
+        (
+            "protocol_alpn_unknown",
+            "https://rpc.example.com",
+            "alpn = [\"h3\"]",
+        ),
+        (
+            "protocol_alpn_twice",
+            "https://rpc.example.com",
+            "alpn = [\"h2\", \"h2\"]",
+        ),
+        (
+            "protocol_http1_h2",
+            "https://rpc.example.com",
+            "http_version = \"http1\"\nalpn = [\"h2\"]",
+        ),
+        (
+            "protocol_http2_no_h2",
+            "https://rpc.example.com",
+            "http_version = \"http2\"\nalpn = [\"http/1.1\"]",
+        ),
+    ] {
+        let err = load_config(&write_temp_config(name, &backend(url, protocol)))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("protocol."), "{}: {}", name, err);
+    }
+
+    let ws = backend("https://rpc.example.com", "allow_plaintext = false").replace(
+        "weight = 1\n",
+        "weight = 1\nws_url = \"ws://localhost:9001\"\n",
+    );
+    assert!(load_config(&write_temp_config("protocol_plain_ws", &ws)).is_err());
+}
+
+#[test]
+fn test_load_config_backend_headers_and_query_params() {
+    std::env::set_var("TEST_PROVIDER_KEY", "provider-key-1234");
+    let config_for = |auth: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "helius"
+url = "https://mainnet.example.com/rpc?cluster=main"
+ws_url = "wss://mainnet.example.com"
+weight = 1
+{}
+"#,
+            auth
+        )
+    };
+
+    let config = load_config(&write_temp_config(
+        "backend_auth",
+        &config_for(
+            "headers = { x-api-key = \"${TEST_PROVIDER_KEY}\", x-tenant = \"acme\" }\nurl_query_params = { api-key = \"${TEST_PROVIDER_KEY}\", tag = \"a b\" }",
+        ),
+    ))
+    .unwrap();
+    let backend = &config.backends[0];
+    assert_eq!(backend.headers["x-api-key"], "provider-key-1234");
+    assert_eq!(backend.headers["x-tenant"], "acme");
+    assert_eq!(
+        backend.url,
+        "https://mainnet.example.com/rpc?cluster=main&api-key=provider-key-1234&tag=a%20b"
+    );
+    assert_eq!(
+        backend.ws_url.as_deref(),
+        Some("wss://mainnet.example.com?api-key=provider-key-1234&tag=a%20b")
+    );
+    let redacted = config.redacted().to_string();
+    assert!(!redacted.contains("provider-key-1234"), "{}", redacted);
+    assert!(!redacted.contains("acme"), "{}", redacted);
+
+    for (name, auth) in [
+        (
+            "backend_auth_unset",
+            "headers = { x-api-key = \"${TEST_PROVIDER_KEY_UNSET}\" }",
+        ),
+        (
+            "backend_auth_unterminated",
+            "url_query_params = { api-key = \"${TEST_PROVIDER_KEY\" }",
+        ),
+        (
+            "backend_auth_header_name",
+            "headers = { \"x key\" = \"1\" }",
+        ),
+        (
+            "backend_auth_header_value",
+            "headers = { x-key = \"a\\nb\" }",
+        ),
+        (
+            "backend_auth_param_name",
+            "url_query_params = { \"\" = \"1\" }",
+        ),
+    ] {
+        let err = load_config(&write_temp_config(name, &config_for(auth)))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("helius"), "{}: {}", name, err);
+    }
+}
+
+#[test]
+fn test_load_config_metrics_exporter() {
+    std::env::set_var("TEST_OTLP_KEY", "otlp-key-5678");
+    let config_for = |metrics: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+
+[metrics]
+{}
+"#,
+            metrics
+        )
+    };
+
+    let config = load_config(&write_temp_config("metrics_default", &config_for(""))).unwrap();
+    assert_eq!(config.metrics.exporter, MetricsExporterKind::Prometheus);
+    assert_eq!(config.metrics.interval_secs, 10);
+    assert_eq!(config.metrics.statsd.address, "127.0.0.1:8125");
+    assert!(config.metrics.statsd.tags);
+
+    let config = load_config(&write_temp_config(
+        "metrics_statsd",
+        &config_for("exporter = \"statsd\"\nstatsd = { address = \"statsd.local:8125\", prefix = \"router.\", tags = false }"),
+    ))
+    .unwrap();
+    assert_eq!(config.metrics.exporter, MetricsExporterKind::Statsd);
+    assert_eq!(config.metrics.statsd.prefix, "router.");
+    assert!(!config.metrics.statsd.tags);
+
+    let config = load_config(&write_temp_config(
+        "metrics_otlp",
+        &config_for("exporter = \"otlp\"\ninterval_secs = 30\notlp = { endpoint = \"https://otel.example.com\", headers = { x-api-key = \"${TEST_OTLP_KEY}\" } }"),
+    ))
+    .unwrap();
+    assert_eq!(config.metrics.exporter, MetricsExporterKind::Otlp);
+    assert_eq!(config.metrics.interval_secs, 30);
+    assert_eq!(config.metrics.otlp.headers["x-api-key"], "otlp-key-5678");
+    let redacted = config.redacted().to_string();
+    assert!(!redacted.contains("otlp-key-5678"), "{}", redacted);
+
+    for (name, metrics, expected) in [
+        (
+            "metrics_unknown",
+            "exporter = \"graphite\"",
+            "unknown variant",
+        ),
+        (
+            "metrics_interval",
+            "interval_secs = 0",
+            "metrics.interval_secs",
+        ),
+        (
+            "metrics_statsd_address",
+            "exporter = \"statsd\"\nstatsd = { address = \"statsd.local\" }",
+            "metrics.statsd.address",
+        ),
+        (
+            "metrics_otlp_endpoint",
+            "exporter = \"otlp\"\notlp = { endpoint = \"otel.example.com:4318\" }",
+            "metrics.otlp.endpoint",
+        ),
+        (
+            "metrics_otlp_header",
+            "exporter = \"otlp\"\notlp = { headers = { x-key = \"${TEST_OTLP_KEY_UNSET}\" } }",
+            "metrics.otlp header",
+        ),
+    ] {
+        let err = load_config(&write_temp_config(name, &config_for(metrics)))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(expected), "{}: {}", name, err);
+    }
+}
+
+#[test]
+fn test_load_config_tracing() {
+    std::env::set_var("TEST_TRACING_KEY", "tempo-key-1234");
+    let config_for = |tracing: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+
+[tracing]
+{}
+"#,
+            tracing
+        )
+    };
+
+    let config = load_config(&write_temp_config("tracing_default", &config_for(""))).unwrap();
+    assert!(!config.tracing.enabled);
+    assert_eq!(config.tracing.sample_percent, 100);
+    assert_eq!(config.tracing.otlp.endpoint, "http://127.0.0.1:4318");
+
+    let config = load_config(&write_temp_config(
+        "tracing_enabled",
+        &config_for("enabled = true
+sample_percent = 10
+otlp = { endpoint = \"https://tempo.example.com\", headers = { authorization = \"Bearer ${TEST_TRACING_KEY}\" } }"),
+    ))
+    .unwrap();
+    assert!(config.tracing.enabled);
+    assert_eq!(config.tracing.sample_percent, 10);
+    assert_eq!(
+        config.tracing.otlp.headers["authorization"],
+        "Bearer tempo-key-1234"
+    );
+    let redacted = config.redacted().to_string();
+    assert!(!redacted.contains("tempo-key-1234"), "{}", redacted);
+
+    // Settings of a disabled section are not checked
+    load_config(&write_temp_config(
+        "tracing_disabled",
+        &config_for("sample_percent = 500"),
+    ))
+    .unwrap();
+
+    for (name, tracing, expected) in [
+        (
+            "tracing_sample",
+            "enabled = true\nsample_percent = 101",
+            "tracing.sample_percent",
+        ),
+        (
+            "tracing_interval",
+            "enabled = true\ninterval_secs = 0",
+            "tracing.interval_secs",
+        ),
+        (
+            "tracing_queue",
+            "enabled = true\nmax_queued_spans = 0",
+            "tracing.max_queued_spans",
+        ),
+        (
+            "tracing_endpoint",
+            "enabled = true\notlp = { endpoint = \"tempo:4318\" }",
+            "tracing.otlp.endpoint",
+        ),
+    ] {
+        let err = load_config(&write_temp_config(name, &config_for(tracing)))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(expected), "{}: {}", name, err);
+    }
+}
+
+#[test]
+fn test_load_config_runtime() {
+    let config_for = |runtime: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+
+[runtime]
+{}
+"#,
+            runtime
+        )
+    };
+
+    let config = load_config(&write_temp_config("runtime_default", &config_for(""))).unwrap();
+    assert_eq!(config.runtime.worker_threads, 0);
+    assert_eq!(config.runtime.max_blocking_threads, 512);
+    assert!(!config.runtime.isolate_background);
+
+    let config = load_config(&write_temp_config(
+        "runtime_small",
+        &config_for("worker_threads = 2\nmax_blocking_threads = 16\nisolate_background = true"),
+    ))
+    .unwrap();
+    assert_eq!(config.runtime.worker_threads, 2);
+    assert_eq!(config.runtime.max_blocking_threads, 16);
+    assert!(config.runtime.isolate_background);
+
+    let err = load_config(&write_temp_config(
+        "runtime_blocking",
+        &config_for("max_blocking_threads = 0"),
+    ))
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("runtime.max_blocking_threads"), "{}", err);
+}
+
+#[test]
+fn test_load_config_key_cache() {
+    let config_for = |key_cache: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+
+[key_cache]
+{}
+"#,
+            key_cache
+        )
+    };
+
+    let config = load_config(&write_temp_config("key_cache_default", &config_for(""))).unwrap();
+    assert_eq!(config.key_cache.ttl_secs, 60);
+    assert_eq!(config.key_cache.negative_ttl_secs, 10);
+    assert_eq!(config.key_cache.max_entries, 100_000);
+
+    let config = load_config(&write_temp_config(
+        "key_cache_no_negative",
+        &config_for("ttl_secs = 300\nnegative_ttl_secs = 0"),
+    ))
+    .unwrap();
+    assert_eq!(config.key_cache.ttl_secs, 300);
+    assert_eq!(config.key_cache.negative_ttl_secs, 0);
+
+    for (name, key_cache, expected) in [
+        ("key_cache_ttl", "ttl_secs = 0", "key_cache.ttl_secs"),
+        ("key_cache_size", "max_entries = 0", "key_cache.max_entries"),
+    ] {
+        let err = load_config(&write_temp_config(name, &config_for(key_cache)))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(expected), "{}: {}", name, err);
+    }
+}
+
+#[test]
+fn test_load_config_keystore_outage() {
+    let config_for = |outage: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+
+[keystore_outage]
+{}
+"#,
+            outage
+        )
+    };
+
+    let config = load_config(&write_temp_config("outage_default", &config_for(""))).unwrap();
+    assert_eq!(
+        config.keystore_outage.policy,
+        KeystoreOutagePolicy::FailClosed
+    );
+    assert_eq!(config.keystore_outage.remember_secs, 3600);
+
+    let config = load_config(&write_temp_config(
+        "outage_static",
+        &config_for(
+            "policy = \"static_keys\"\nstatic_keys = [{ key = \"partner-key\", owner = \"partner\" }]",
+        ),
+    ))
+    .unwrap();
+    assert_eq!(
+        config.keystore_outage.policy,
+        KeystoreOutagePolicy::StaticKeys
+    );
+    assert_eq!(config.keystore_outage.static_keys[0].owner, "partner");
+    let redacted = config.redacted();
+    assert_eq!(
+        redacted["keystore_outage"]["static_keys"][0]["key"],
+        "[REDACTED]"
+    );
+    assert_eq!(
+        redacted["keystore_outage"]["static_keys"][0]["owner"],
+        "partner"
+    );
+
+    // Keys can be kept out of the file
+    std::env::set_var("TEST_STATIC_KEY", "static-env-key-5678");
+    let config = load_config(&write_temp_config(
+        "outage_static_env",
+        &config_for(
+            "policy = \"static_keys\"\nstatic_keys = [{ key = \"${TEST_STATIC_KEY}\", owner = \"partner\" }]",
+        ),
+    ))
+    .unwrap();
+    assert_eq!(
+        config.keystore_outage.static_keys[0].key,
+        "static-env-key-5678"
+    );
+    assert_eq!(
+        redact("rejected static-env-key-5678"),
+        "rejected [REDACTED]"
+    );
+
+    for (name, outage, expected) in [
+        (
+            "outage_remember",
+            "policy = \"fail_open\"\nremember_secs = 0",
+            "keystore_outage.remember_secs",
+        ),
+        (
+            "outage_no_static",
+            "policy = \"static_keys\"",
+            "keystore_outage.static_keys",
+        ),
+        (
+            "outage_duplicate",
+            "static_keys = [{ key = \"k\", owner = \"a\" }, { key = \"k\", owner = \"b\" }]",
+            "twice",
+        ),
+        (
+            "outage_policy",
+            "policy = \"fail_sideways\"",
+            "unknown variant",
+        ),
+        (
+            "outage_unset_env",
+            "static_keys = [{ key = \"${TEST_STATIC_KEY_UNSET}\", owner = \"partner\" }]",
+            "'TEST_STATIC_KEY_UNSET' is not set",
+        ),
+    ] {
+        let err = load_config(&write_temp_config(name, &config_for(outage)))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(expected), "{}: {}", name, err);
+    }
+}
+
+#[test]
+fn test_load_config_pools() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("pools_default", base)).unwrap();
+    assert!(config.pools.separate_write_pool);
+    assert_eq!(config.pools.heavy_max_in_flight, 0);
+    assert_eq!(config.pools.heavy_min_cost, 5);
+
+    let invalid = format!("{}\n[pools]\nheavy_min_cost = 0\n", base);
+    assert!(load_config(&write_temp_config("pools_zero_cost", &invalid)).is_err());
+}
+
+#[test]
+fn test_load_config_websocket() {
+    let base = r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+"#;
+    let config = load_config(&write_temp_config("websocket_default", base)).unwrap();
+    assert_eq!(config.websocket.ping_interval_secs, 30);
+    assert_eq!(config.websocket.pong_timeout_secs, 10);
+    assert_eq!(config.websocket.idle_timeout_secs, 0);
+    assert_eq!(config.websocket.max_queued_messages, 1024);
+
+    // Heartbeats off: the pong timeout is unused
+    let no_heartbeat = format!(
+        "{}\n[websocket]\nping_interval_secs = 0\npong_timeout_secs = 0\n",
+        base
+    );
+    assert!(load_config(&write_temp_config("websocket_no_ping", &no_heartbeat)).is_ok());
+
+    let invalid = format!("{}\n[websocket]\npong_timeout_secs = 0\n", base);
+    assert!(load_config(&write_temp_config("websocket_zero_pong", &invalid)).is_err());
+    let invalid = format!("{}\n[websocket]\nmax_queued_messages = 0\n", base);
+    assert!(load_config(&write_temp_config("websocket_zero_queue", &invalid)).is_err());
+
+    assert!(!config.websocket.local_slot_subscriptions);
+    assert_eq!(config.websocket.slot_commitment, Commitment::Processed);
+    let local = format!(
+        "{}\n[websocket]\nlocal_slot_subscriptions = true\nslot_commitment = \"confirmed\"\n",
+        base
+    );
+    let config = load_config(&write_temp_config("websocket_local", &local)).unwrap();
+    assert_eq!(config.websocket.slot_commitment, Commitment::Confirmed);
+    let invalid = format!(
+        "{}\n[websocket]\nlocal_slot_subscriptions = true\nslot_poll_ms = 0\n",
+        base
+    );
+    assert!(load_config(&write_temp_config("websocket_zero_poll", &invalid)).is_err());
+}
+
+#[test]
+fn test_load_config_alerts() {
+    let config_for = |alerts: &str| {
+        format!(
+            r#"
+port = 8080
+metrics_port = 9091
+redis_url = "redis://localhost"
+
+[[backends]]
+label = "a"
+url = "http://localhost:9000"
+weight = 1
+{}"#,
+            alerts
+        )
+    };
+
+    let valid = r#"
+[[alerts]]
+name = "backends-down"
+metric = "unhealthy_backends"
+op = ">="
+threshold = 2
+for_secs = 60
+webhook_url = "https://hooks.example.com/router"
+
+[[alerts]]
+name = "slow-getslot"
+metric = "p99_ms"
+method = "getSlot"
+backend = "a"
+op = ">"
+threshold = 500
+for_secs = 300
+webhook_url = "https://hooks.example.com/router"
+"#;
+    let config = load_config(&write_temp_config("alerts_valid", &config_for(valid))).unwrap();
+    assert_eq!(config.alerts.len(), 2);
+    assert_eq!(config.alerts[1].metric, AlertMetric::P99Ms);
+    assert_eq!(config.alerts[1].op, AlertOp::Gt);
+
+    let alert = |fields: &str| {
+        format!(
+            "\n[[alerts]]\nname = \"x\"\nop = \">\"\nthreshold = 1\n{}\n",
+            fields
+        )
+    };
+    for (name, alerts) in [
+        (
+            "alerts_no_method",
+            alert("metric = \"p99_ms\"\nwebhook_url = \"https://hooks.example.com\""),
+        ),
+        (
+            "alerts_unknown_backend",
+            alert("metric = \"success_rate\"\nmethod = \"getSlot\"\nbackend = \"b\"\nwebhook_url = \"https://hooks.example.com\""),
+        ),
+        (
+            "alerts_bad_webhook",
+            alert("metric = \"unhealthy_backends\"\nwebhook_url = \"hooks.example.com\""),
+        ),
+        (
+            "alerts_duplicate",
+            format!(
+                "{0}{0}",
+                alert("metric = \"unhealthy_backends\"\nwebhook_url = \"https://hooks.example.com\"")
+            ),
+        ),
+    ] {
+        assert!(load_config(&write_temp_config(name, &config_for(&alerts))).is_err(), "{}", name);
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use axum::{routing::post, Json, Router};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use serde_json::{json, Value};
+use sol_rpc_router::{
+    config::{Backend, Canary},
+    consistency::{comparable, divergent, query_canary, ConsistencyState},
+};
+
+fn answers(values: &[(&str, Value)]) -> Vec<(String, Value)> {
+    values
+        .iter()
+        .map(|(label, value)| (label.to_string(), value.clone()))
+        .collect()
+}
+
+#[test]
+fn test_divergent_against_majority() {
+    // All agree
+    let agree = answers(&[("a", json!(5)), ("b", json!(5)), ("c", json!(5))]);
+    assert!(divergent(&agree).is_empty());
+
+    // One out of three disagrees
+    let forked = answers(&[("a", json!(5)), ("b", json!(7)), ("c", json!(5))]);
+    assert_eq!(divergent(&forked), vec!["b"]);
+
+    // No strict majority: nobody can be trusted
+    let split = answers(&[("a", json!(5)), ("b", json!(7))]);
+    assert_eq!(divergent(&split), vec!["a", "b"]);
+
+    // A single answer has nothing to compare against
+    assert!(divergent(&answers(&[("a", json!(5))])).is_empty());
+}
+
+#[test]
+fn test_comparable_drops_context() {
+    let wrapped = json!({"context": {"slot": 100}, "value": {"amount": "10"}});
+    assert_eq!(comparable(wrapped), json!({"amount": "10"}));
+    assert_eq!(comparable(json!(42)), json!(42));
+    assert_eq!(comparable(json!({"value": 1})), json!({"value": 1}));
+}
+
+#[test]
+fn test_consistency_state_records_changes() {
+    let state = ConsistencyState::new();
+    assert!(!state.record("balance", vec![]));
+    assert!(state.record("balance", vec!["b".to_string()]));
+    assert!(!state.record("balance", vec!["b".to_string()]));
+    state.record("supply", vec!["b".to_string(), "c".to_string()]);
+    assert_eq!(state.divergent_backends().len(), 2);
+
+    // Removed canaries stop counting
+    let balance = Canary {
+        name: "balance".to_string(),
+        method: "getBalance".to_string(),
+        params: Value::Null,
+    };
+    state.retain(&[balance]);
+    assert_eq!(state.snapshot().len(), 1);
+    assert_eq!(
+        state.divergent_backends().into_iter().collect::<Vec<_>>(),
+        vec!["b"]
+    );
+}
+
+#[tokio::test]
+async fn test_query_canary() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|Json(request): Json<Value>| async move {
+                assert_eq!(request["method"], "getBalance");
+                assert_eq!(request["params"], json!(["acct"]));
+                Json(json!({
+                    "jsonrpc": "2.0",
+                    "result": {"context": {"slot": 100}, "value": 1000},
+                    "id": 1
+                }))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let backend = Backend {
+        label: "b1".to_string(),
+        url: format!("http://{}", addr),
+        weight: 1,
+        ..Default::default()
+    };
+    let canary = Canary {
+        name: "balance".to_string(),
+        method: "getBalance".to_string(),
+        params: json!(["acct"]),
+    };
+    let answer = query_canary(&client, &backend, &canary, Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(answer, json!(1000));
+}
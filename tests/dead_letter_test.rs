@@ -0,0 +1,52 @@
+use sol_rpc_router::dead_letter::{DeadLetter, DeadLetterStore, FileDeadLetterStore};
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    let mut path = std::env::temp_dir();
+    path.push(format!("sol_rpc_router_test_dead_letters_{}.jsonl", name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn letter(signature: &str) -> DeadLetter {
+    DeadLetter {
+        id: String::new(),
+        captured_at: 1_700_000_000,
+        owner: "trader".to_string(),
+        backend: Some("primary".to_string()),
+        status: 502,
+        error: "connection refused".to_string(),
+        request: format!(
+            r#"{{"jsonrpc":"2.0","method":"sendTransaction","params":["{}"],"id":1}}"#,
+            signature
+        ),
+    }
+}
+
+#[tokio::test]
+async fn test_file_store_lists_newest_first() {
+    let store = FileDeadLetterStore::new(temp_path("order"));
+    assert!(store.list(10).await.unwrap().is_empty());
+
+    for tx in ["first", "second", "third"] {
+        store.push(&letter(tx)).await.unwrap();
+    }
+
+    let letters = store.list(2).await.unwrap();
+    assert_eq!(letters.len(), 2);
+    assert!(letters[0].request.contains("third"));
+    assert!(letters[1].request.contains("second"));
+    assert_ne!(letters[0].id, letters[1].id);
+}
+
+#[tokio::test]
+async fn test_file_store_get_by_id() {
+    let store = FileDeadLetterStore::new(temp_path("get"));
+    store.push(&letter("only")).await.unwrap();
+
+    let listed = store.list(1).await.unwrap().remove(0);
+    let fetched = store.get(&listed.id).await.unwrap().unwrap();
+    assert_eq!(fetched, listed);
+    assert_eq!(fetched.owner, "trader");
+    assert_eq!(fetched.status, 502);
+    assert!(store.get("0-0").await.unwrap().is_none());
+}
@@ -0,0 +1,205 @@
+use serde_json::{json, Value};
+use sol_rpc_router::{
+    config::{Commitment, PreflightPolicy},
+    defaults::{
+        apply_defaults, apply_preflight_policy, is_unsupported_version_error,
+        with_transaction_version, RequestDefaults,
+    },
+};
+
+fn defaults(commitment: Option<&str>, encoding: Option<&str>) -> RequestDefaults {
+    RequestDefaults::new(commitment.map(str::to_string), encoding.map(str::to_string)).unwrap()
+}
+
+fn apply(body: Value, defaults: &RequestDefaults) -> Option<Value> {
+    apply_defaults(&serde_json::to_vec(&body).unwrap(), defaults)
+        .map(|bytes| serde_json::from_slice(&bytes).unwrap())
+}
+
+#[test]
+fn test_injects_missing_config() {
+    let d = defaults(Some("confirmed"), Some("base64"));
+
+    let out = apply(
+        json!({"jsonrpc": "2.0", "id": 1, "method": "getAccountInfo", "params": ["Acc1"]}),
+        &d,
+    )
+    .unwrap();
+    assert_eq!(
+        out["params"],
+        json!(["Acc1", {"commitment": "confirmed", "encoding": "base64"}])
+    );
+
+    // Config at index 0 with no params at all
+    let out = apply(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}), &d).unwrap();
+    assert_eq!(out["params"], json!([{"commitment": "confirmed"}]));
+}
+
+#[test]
+fn test_client_values_win() {
+    let d = defaults(Some("confirmed"), Some("base64"));
+    let out = apply(
+        json!({"jsonrpc": "2.0", "id": 1, "method": "getAccountInfo",
+               "params": ["Acc1", {"commitment": "processed"}]}),
+        &d,
+    )
+    .unwrap();
+    assert_eq!(
+        out["params"][1],
+        json!({"commitment": "processed", "encoding": "base64"})
+    );
+
+    let untouched = json!({"jsonrpc": "2.0", "id": 1, "method": "getBalance",
+                           "params": ["Acc1", {"commitment": "finalized"}]});
+    assert!(apply(untouched, &d).is_none());
+}
+
+#[test]
+fn test_skips_unsupported_values_and_methods() {
+    // getTransaction does not accept processed, and jsonParsed is valid for it
+    let d = defaults(Some("processed"), Some("jsonParsed"));
+    let out = apply(
+        json!({"jsonrpc": "2.0", "id": 1, "method": "getTransaction", "params": ["Sig1"]}),
+        &d,
+    )
+    .unwrap();
+    assert_eq!(out["params"], json!(["Sig1", {"encoding": "jsonParsed"}]));
+
+    // sendTransaction's encoding describes the input transaction: never touched
+    let send = json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": ["tx"]});
+    assert!(apply(send, &d).is_none());
+
+    // A missing required param means there is no slot for the config object
+    let short = json!({"jsonrpc": "2.0", "id": 1, "method": "getTokenAccountsByOwner",
+                       "params": ["Owner"]});
+    assert!(apply(short, &d).is_none());
+}
+
+#[test]
+fn test_batch_requests() {
+    let d = defaults(Some("finalized"), None);
+    let out = apply(
+        json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "getSlot", "params": []},
+            {"jsonrpc": "2.0", "id": 2, "method": "getHealth"}
+        ]),
+        &d,
+    )
+    .unwrap();
+    assert_eq!(out[0]["params"], json!([{"commitment": "finalized"}]));
+    assert!(out[1].get("params").is_none());
+}
+
+#[test]
+fn test_rejects_unknown_values() {
+    assert!(RequestDefaults::new(Some("max".to_string()), None).is_err());
+    assert!(RequestDefaults::new(None, Some("binary".to_string())).is_err());
+    assert!(RequestDefaults::new(None, None).unwrap().is_empty());
+}
+
+fn enforce(body: Value, policy: &PreflightPolicy) -> Option<Value> {
+    apply_preflight_policy(&serde_json::to_vec(&body).unwrap(), policy)
+        .map(|bytes| serde_json::from_slice(&bytes).unwrap())
+}
+
+#[test]
+fn test_preflight_policy() {
+    let policy = PreflightPolicy {
+        require_preflight: true,
+        min_preflight_commitment: Some(Commitment::Confirmed),
+        max_retries: Some(0),
+    };
+    let send = |config: Value| json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": ["tx", config]});
+
+    let out = enforce(
+        send(json!({"skipPreflight": true, "preflightCommitment": "processed", "maxRetries": 5})),
+        &policy,
+    )
+    .unwrap();
+    assert_eq!(
+        out["params"][1],
+        json!({"skipPreflight": false, "preflightCommitment": "confirmed", "maxRetries": 0})
+    );
+
+    // Deprecated names are ranked; stricter commitments and a missing one are kept
+    let out = enforce(send(json!({"preflightCommitment": "recent"})), &policy).unwrap();
+    assert_eq!(out["params"][1]["preflightCommitment"], "confirmed");
+    let out = enforce(send(json!({"preflightCommitment": "finalized"})), &policy).unwrap();
+    assert_eq!(
+        out["params"][1],
+        json!({"preflightCommitment": "finalized", "maxRetries": 0})
+    );
+
+    // Without a config object one is added
+    let out = enforce(
+        json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": ["tx"]}),
+        &policy,
+    )
+    .unwrap();
+    assert_eq!(out["params"][1], json!({"maxRetries": 0}));
+
+    // Already compliant, or not a sendTransaction: untouched
+    assert!(enforce(
+        send(json!({"skipPreflight": false, "maxRetries": 0})),
+        &policy
+    )
+    .is_none());
+    assert!(enforce(
+        json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot", "params": []}),
+        &policy
+    )
+    .is_none());
+
+    // Each call of a batch
+    let batch = json!([
+        send(json!({"skipPreflight": true, "maxRetries": 0})),
+        {"jsonrpc": "2.0", "id": 2, "method": "getSlot"}
+    ]);
+    let out = enforce(batch, &policy).unwrap();
+    assert_eq!(out[0]["params"][1]["skipPreflight"], json!(false));
+    assert!(out[1].get("params").is_none());
+}
+
+#[test]
+fn test_with_transaction_version() {
+    let rewrite = |body: Value| {
+        with_transaction_version(&serde_json::to_vec(&body).unwrap(), 0)
+            .map(|bytes| serde_json::from_slice::<Value>(&bytes).unwrap())
+    };
+
+    let rewritten =
+        rewrite(json!({"jsonrpc": "2.0", "method": "getBlock", "params": [430], "id": 1}));
+    assert_eq!(
+        rewritten.unwrap()["params"],
+        json!([430, {"maxSupportedTransactionVersion": 0}])
+    );
+    let rewritten =
+        rewrite(json!({"method": "getTransaction", "params": ["sig", {"encoding": "json"}]}));
+    assert_eq!(
+        rewritten.unwrap()["params"][1],
+        json!({"encoding": "json", "maxSupportedTransactionVersion": 0})
+    );
+
+    // Already set, other methods, missing slot and batches are left alone
+    assert!(rewrite(
+        json!({"method": "getBlock", "params": [430, {"maxSupportedTransactionVersion": 0}]})
+    )
+    .is_none());
+    assert!(rewrite(json!({"method": "getBalance", "params": ["Acc1"]})).is_none());
+    assert!(rewrite(json!({"method": "getBlock"})).is_none());
+    assert!(rewrite(json!([{"method": "getBlock", "params": [430]}])).is_none());
+}
+
+#[test]
+fn test_is_unsupported_version_error() {
+    assert!(is_unsupported_version_error(
+        br#"{"jsonrpc":"2.0","error":{"code":-32015,"message":"Transaction version (0) is not supported"},"id":1}"#
+    ));
+    assert!(!is_unsupported_version_error(
+        br#"{"jsonrpc":"2.0","error":{"code":-32009,"message":"Slot skipped"},"id":1}"#
+    ));
+    assert!(!is_unsupported_version_error(
+        br#"{"jsonrpc":"2.0","result":null,"id":1}"#
+    ));
+    assert!(!is_unsupported_version_error(b"not json"));
+}
@@ -0,0 +1,73 @@
+use serde_json::{json, Value};
+use sol_rpc_router::{
+    config::ErrorTemplatesConfig,
+    error_templates::{error_response, placeholders, render, RouterError},
+};
+
+#[test]
+fn test_render_fills_placeholders() {
+    let template = json!({
+        "error": {
+            "code": "{status}",
+            "message": "Router says: {message}",
+            "links": ["https://example.com/status/{status}"],
+            "retryable": true,
+        }
+    });
+    let vars = [
+        ("status", Value::from(429)),
+        ("message", Value::from("Rate limit exceeded")),
+    ];
+    assert_eq!(
+        render(&template, &vars),
+        json!({
+            "error": {
+                "code": 429,
+                "message": "Router says: Rate limit exceeded",
+                "links": ["https://example.com/status/429"],
+                "retryable": true,
+            }
+        })
+    );
+}
+
+#[test]
+fn test_placeholders() {
+    let template = json!({"a": "{status} {message}", "b": ["{x_y}"], "c": "{Not} {} {", "d": 1});
+    assert_eq!(placeholders(&template), vec!["status", "message", "x_y"]);
+}
+
+#[tokio::test]
+async fn test_error_response_without_template_is_plain_text() {
+    use http_body_util::BodyExt;
+
+    let templates = ErrorTemplatesConfig::default();
+    let response = error_response(&templates, RouterError::RateLimited);
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers()["retry-after"], "1");
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&body[..], b"Rate limit exceeded");
+
+    let templates = ErrorTemplatesConfig {
+        rate_limited: Some(json!({"code": "SLOW_DOWN"})),
+        ..Default::default()
+    };
+    let response = error_response(&templates, RouterError::RateLimited);
+    assert_eq!(response.headers()["content-type"], "application/json");
+    assert_eq!(response.headers()["retry-after"], "1");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "SLOW_DOWN");
+}
+
+#[test]
+fn test_keystore_unavailable_response() {
+    let response = error_response(
+        &ErrorTemplatesConfig::default(),
+        RouterError::KeystoreUnavailable,
+    );
+    assert_eq!(response.status(), 503);
+    assert_eq!(response.headers()["retry-after"], "5");
+    assert!(!response.headers().contains_key("x-ratelimit-remaining"));
+}
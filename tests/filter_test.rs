@@ -0,0 +1,66 @@
+use serde_json::{json, Value};
+use sol_rpc_router::filter::{filter_response, parse_fields};
+
+fn filter(response: Value, fields: &str) -> Value {
+    let fields = parse_fields(fields).unwrap();
+    let filtered = filter_response(&serde_json::to_vec(&response).unwrap(), &fields).unwrap();
+    serde_json::from_slice(&filtered).unwrap()
+}
+
+#[test]
+fn test_parse_fields() {
+    assert_eq!(
+        parse_fields(" /result/blockhash , /result/parentSlot,").unwrap(),
+        vec!["/result/blockhash", "/result/parentSlot"]
+    );
+    assert!(parse_fields("").is_err());
+    assert!(parse_fields("result/blockhash").is_err());
+    assert!(parse_fields(&vec!["/a"; 33].join(",")).is_err());
+}
+
+#[test]
+fn test_keeps_only_listed_fields() {
+    let block = json!({
+        "jsonrpc": "2.0",
+        "id": 7,
+        "result": {
+            "blockhash": "abc",
+            "parentSlot": 429,
+            "transactions": [
+                {"meta": {"fee": 5000, "logMessages": ["long"]}},
+                {"meta": {"fee": 10000, "logMessages": ["longer"]}}
+            ]
+        }
+    });
+
+    assert_eq!(
+        filter(block.clone(), "/result/blockhash,/result/missing"),
+        json!({"jsonrpc": "2.0", "id": 7, "result": {"blockhash": "abc"}})
+    );
+    // Array shapes are kept, earlier positions become null
+    assert_eq!(
+        filter(block, "/result/transactions/1/meta/fee"),
+        json!({"jsonrpc": "2.0", "id": 7, "result": {"transactions": [null, {"meta": {"fee": 10000}}]}})
+    );
+}
+
+#[test]
+fn test_errors_and_batches() {
+    let error =
+        json!({"jsonrpc": "2.0", "id": 1, "error": {"code": -32009, "message": "Slot skipped"}});
+    assert_eq!(filter(error.clone(), "/result/blockhash"), error);
+
+    let batch = json!([
+        {"jsonrpc": "2.0", "id": 1, "result": {"context": {"slot": 1}, "value": 42}},
+        error
+    ]);
+    assert_eq!(
+        filter(batch, "/result/value"),
+        json!([
+            {"jsonrpc": "2.0", "id": 1, "result": {"value": 42}},
+            {"jsonrpc": "2.0", "id": 1, "error": {"code": -32009, "message": "Slot skipped"}}
+        ])
+    );
+
+    assert!(filter_response(b"not json", &["/result".to_string()]).is_none());
+}
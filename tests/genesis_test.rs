@@ -0,0 +1,137 @@
+use std::{collections::HashMap, sync::Arc};
+
+use arc_swap::ArcSwap;
+use axum::{routing::post, Json, Router};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use serde_json::{json, Value};
+use sol_rpc_router::{
+    config::{Backend, GenesisCheckConfig, KNOWN_NETWORKS},
+    genesis::{verify_genesis, Identity},
+    health::HealthState,
+    mock::MockKeyStore,
+    state::{AppState, RouterState, RuntimeBackend},
+};
+
+const MAINNET: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+const DEVNET: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
+
+/// A backend answering `getGenesisHash` with `hash`.
+async fn start_backend(hash: &'static str) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<Value>| async move {
+                assert_eq!(request["method"], "getGenesisHash");
+                Json(json!({"jsonrpc": "2.0", "result": hash, "id": 1}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+    url
+}
+
+#[test]
+fn test_expected_hash() {
+    let mut config = GenesisCheckConfig {
+        network: Some("mainnet-beta".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(config.expected(), Some(MAINNET));
+    config.network = Some("devnet".to_string());
+    assert_eq!(config.expected(), Some(DEVNET));
+    assert_eq!(KNOWN_NETWORKS.len(), 3);
+
+    config.network = None;
+    assert_eq!(config.expected(), None);
+    config.expected_hash = Some("local".to_string());
+    assert_eq!(config.expected(), Some("local"));
+}
+
+#[test]
+fn test_identity_quarantines_the_url_it_was_caught_on() {
+    let identity = Identity::new();
+    assert!(!identity.quarantines("http://a"));
+
+    assert!(identity.record("http://a", DEVNET, MAINNET));
+    assert!(identity.quarantines("http://a"));
+    assert_eq!(identity.mismatch("http://a").as_deref(), Some(DEVNET));
+    // Still wrong: no change to report
+    assert!(!identity.record("http://a", DEVNET, MAINNET));
+
+    // A reload pointing the backend elsewhere lifts it until checked again
+    assert!(!identity.quarantines("http://b"));
+
+    assert!(identity.record("http://a", MAINNET, MAINNET));
+    assert!(!identity.quarantines("http://a"));
+
+    identity.record("http://a", DEVNET, MAINNET);
+    identity.clear();
+    assert!(!identity.quarantines("http://a"));
+}
+
+#[tokio::test]
+async fn test_verify_genesis_quarantines_other_clusters() {
+    let mainnet = start_backend(MAINNET).await;
+    let devnet = start_backend(DEVNET).await;
+
+    let labels = ["mainnet", "devnet"];
+    let health_state = Arc::new(HealthState::new(
+        labels.iter().map(|l| l.to_string()).collect(),
+    ));
+    let backends = labels
+        .into_iter()
+        .zip([mainnet, devnet])
+        .map(|(label, url)| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url,
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            )
+            .with_identity(health_state.identity(label))
+        })
+        .collect();
+    let router_state = RouterState {
+        backends,
+        method_routes: HashMap::new(),
+        health_state: health_state.clone(),
+        ..Default::default()
+    };
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let state = AppState::new(
+        client,
+        Arc::new(MockKeyStore::new()),
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    );
+    let in_rotation = |state: &AppState| -> Vec<bool> {
+        let router_state = state.state.load();
+        router_state
+            .backends
+            .iter()
+            .map(|b| b.in_rotation())
+            .collect()
+    };
+
+    // Disabled by default
+    verify_genesis(&state).await;
+    assert_eq!(in_rotation(&state), [true, true]);
+
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.genesis_check.enabled = true;
+        next.genesis_check.network = Some("mainnet-beta".to_string());
+        next
+    });
+    verify_genesis(&state).await;
+    assert_eq!(in_rotation(&state), [true, false]);
+    // Kept for the label across reloads
+    assert!(health_state
+        .identity("devnet")
+        .quarantines(&state.state.load().backends[1].config.url));
+}
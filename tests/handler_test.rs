@@ -1,5 +1,7 @@
-use std::{collections::HashMap, sync::Arc};
-use std::sync::atomic::AtomicBool;
+// Baseline test setup predates this lint
+#![allow(clippy::field_reassign_with_default)]
+
+use std::{collections::HashMap, io::Write, sync::Arc, time::Duration};
 
 use arc_swap::ArcSwap;
 use axum::{
@@ -7,17 +9,39 @@ use axum::{
     http::{Request, StatusCode},
     middleware,
     routing::{get, post},
-    Router,
+    Json, Router,
 };
 use http_body_util::BodyExt;
 use hyper_tls::HttpsConnector;
 use hyper_util::client::legacy::Client;
 use sol_rpc_router::{
-    config::{Backend, HealthCheckConfig},
-    handlers::{extract_rpc_method, health_endpoint, proxy, RpcMethod},
-    health::{BackendHealthStatus, HealthState},
-    mock::MockKeyStore,
+    audit::LogPrivacy,
+    circuit::CircuitState,
+    config::{
+        ArchiveRoutingConfig, Backend, BrowserKeyConfig, ErrorTemplatesConfig, HealthCheckConfig,
+        OffloadConfig, PreflightPolicy, ProbesConfig, SigningConfig, SpendingCapsConfig, Subsystem,
+        TracingConfig, TtlBounds,
+    },
+    dead_letter::{DeadLetterStore, FileDeadLetterStore},
+    defaults::RequestDefaults,
+    error_templates::RATE_LIMIT_REMAINING_HEADER,
+    handlers::{
+        assign_request_id, decompress_request, discovery_endpoint, extract_rpc_method,
+        filter_response_fields, health_endpoint, proxy, readyz_endpoint, resume_journal,
+        split_batch, trace_requests, track_usage, unwrap_single_batch, usage_endpoint, ClientOwner,
+        ProbeRequest, RpcMethod, SelectedBackend, REQUEST_ID_HEADER,
+    },
+    health::{BackendHealthStatus, HealthLevel, HealthState},
+    journal::{FileJournalStore, JournalEntry, JournalStatus, JournalStore},
+    keystore::KeyStore,
+    methods::{Idempotency, MethodCategory},
+    mock::{MockKeyStore, MockKeyUsageStore, MockSpendStore},
+    redact::key_fingerprint,
+    signing::{unix_now, verify_signature},
+    spending::SpendTracker,
     state::{AppState, RouterState, RuntimeBackend},
+    timing::TIMING_HEADER,
+    trace::{SpanContext, Tracer, TRACEPARENT},
 };
 use tower::ServiceExt; // for oneshot
 
@@ -33,13 +57,14 @@ fn make_app_state(
         health_state,
         proxy_timeout_secs: 5,
         health_check_config: HealthCheckConfig::default(),
+        ..Default::default()
     };
 
-    Arc::new(AppState {
+    Arc::new(AppState::new(
         client,
         keystore,
-        state: Arc::new(ArcSwap::from_pointee(router_state)),
-    })
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ))
 }
 
 async fn start_mock_backend() -> String {
@@ -71,12 +96,10 @@ async fn test_proxy_handler_success() {
         url: backend_url.clone(),
         ws_url: None,
         weight: 100,
+        ..Default::default()
     };
 
-    let runtime_backend = RuntimeBackend {
-        config: backend,
-        healthy: Arc::new(AtomicBool::new(true)),
-    };
+    let runtime_backend = RuntimeBackend::new(backend, true);
 
     let health_state = Arc::new(HealthState::new(vec!["mock-backend".to_string()]));
     let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
@@ -104,6 +127,56 @@ async fn test_proxy_handler_success() {
     assert!(body_str.contains("result"));
 }
 
+#[tokio::test]
+async fn test_proxy_adds_timing_header_for_opted_in_keys() {
+    let backend_url = start_mock_backend().await;
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    keystore.add_key("timed-key", "tester", 100);
+    keystore.set_timing("timed-key", true);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "mock-backend".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["mock-backend".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send_as = |key: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+    let response = app.clone().oneshot(send_as("test-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(TIMING_HEADER).is_none());
+
+    let response = app.oneshot(send_as("timed-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let timing = response.headers()[TIMING_HEADER].to_str().unwrap();
+    let phases: Vec<&str> = timing
+        .split(", ")
+        .map(|phase| phase.split(';').next().unwrap())
+        .collect();
+    assert_eq!(
+        phases,
+        vec!["auth", "queue", "routing", "upstream", "response"]
+    );
+    assert!(!timing.contains("upstream;dur=0.000"));
+}
+
 #[tokio::test]
 async fn test_proxy_handler_unauthorized() {
     let https = HttpsConnector::new();
@@ -161,6 +234,100 @@ async fn test_proxy_handler_rate_limited() {
     let response = app.oneshot(req).await.unwrap();
 
     assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["retry-after"], "1");
+    assert_eq!(response.headers()[RATE_LIMIT_REMAINING_HEADER], "0");
+}
+
+#[tokio::test]
+async fn test_proxy_reports_remaining_rate_limit() {
+    let backend_url = start_mock_backend().await;
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("limited-key", "tester", 10);
+    keystore.set_rate_limit_remaining("limited-key", Some(7));
+    keystore.add_key("unlimited-key", "tester", 0);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "mock-backend".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["mock-backend".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send_as = |key: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+    let response = app.clone().oneshot(send_as("limited-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[RATE_LIMIT_REMAINING_HEADER], "7");
+
+    let response = app.oneshot(send_as("unlimited-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key(RATE_LIMIT_REMAINING_HEADER));
+}
+
+#[tokio::test]
+async fn test_proxy_uses_first_usable_fallback_key() {
+    let backend_url = start_mock_backend().await;
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("limit-key", "old-owner", 10);
+    keystore
+        .rate_limited_keys
+        .lock()
+        .unwrap()
+        .push("limit-key".to_string());
+    keystore.add_key("spare-key", "spare-owner", 10);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "primary".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["primary".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+    let request = |keys: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", keys))
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(request("unknown-key,limit-key,spare-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.extensions().get::<ClientOwner>().unwrap().0,
+        "spare-owner"
+    );
+
+    let response = app.oneshot(request("unknown-key,limit-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
 }
 
 // --- Proxy error path tests ---
@@ -195,7 +362,7 @@ async fn test_proxy_keystore_internal_error() {
     let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(https);
     let keystore = Arc::new(MockKeyStore::new());
     keystore.add_key("err-key", "tester", 100);
-    keystore.set_error("err-key", "Redis connection failed");
+    keystore.set_invalid("err-key", "Invalid rate_limit");
 
     let health_state = Arc::new(HealthState::new(vec![]));
     let state = make_app_state(client, keystore, vec![], health_state);
@@ -216,6 +383,34 @@ async fn test_proxy_keystore_internal_error() {
     assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
 }
 
+#[tokio::test]
+async fn test_proxy_keystore_unavailable() {
+    let https = HttpsConnector::new();
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(https);
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("down-key", "tester", 100);
+    keystore.set_error("down-key", "Redis connection failed");
+
+    let health_state = Arc::new(HealthState::new(vec![]));
+    let state = make_app_state(client, keystore, vec![], health_state);
+
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/?api-key=down-key")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"jsonrpc":"2.0","method":"test","id":1}"#))
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["retry-after"], "5");
+}
+
 #[tokio::test]
 async fn test_proxy_no_healthy_backends() {
     let backend_url = start_mock_backend().await;
@@ -230,12 +425,10 @@ async fn test_proxy_no_healthy_backends() {
         url: backend_url.clone(),
         ws_url: None,
         weight: 1,
+        ..Default::default()
     };
 
-    let runtime_backend = RuntimeBackend {
-        config: backend,
-        healthy: Arc::new(AtomicBool::new(false)), // Start unhealthy
-    };
+    let runtime_backend = RuntimeBackend::new(backend, false);
 
     let health_state = Arc::new(HealthState::new(vec!["sick-backend".to_string()]));
     let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
@@ -254,6 +447,79 @@ async fn test_proxy_no_healthy_backends() {
 
     let response = app.oneshot(req).await.unwrap();
     assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    // Clients are told when to come back: after the next health check
+    assert_eq!(response.headers()["retry-after"], "30");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["id"], 1);
+    assert_eq!(json["error"]["data"]["queue_depth"], "empty");
+    assert_eq!(json["error"]["data"]["retry_after_ms"], 30_000);
+}
+
+#[tokio::test]
+async fn test_proxy_answers_with_error_templates() {
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let backend = Backend {
+        label: "sick-backend".to_string(),
+        url: "http://127.0.0.1:1".to_string(),
+        weight: 1,
+        ..Default::default()
+    };
+    let router_state = RouterState {
+        backends: vec![RuntimeBackend::new(backend, false)],
+        health_state: Arc::new(HealthState::new(vec!["sick-backend".to_string()])),
+        error_templates: ErrorTemplatesConfig {
+            unauthorized: Some(serde_json::json!({
+                "code": "AUTH_REQUIRED",
+                "status": "{status}",
+                "docs": "https://docs.example.com/errors#{status}",
+            })),
+            no_backend: Some(serde_json::json!({
+                "code": "UNAVAILABLE",
+                "message": "{message}",
+                "ticket": "{correlation_id}",
+            })),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        client,
+        keystore,
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+    let request = |key: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(request("wrong-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "AUTH_REQUIRED");
+    assert_eq!(json["status"], 401);
+    assert_eq!(json["docs"], "https://docs.example.com/errors#401");
+
+    // The retry hint's headers are kept, and its correlation id can be quoted
+    let response = app.oneshot(request("test-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["retry-after"], "30");
+    let correlation_id = response.headers()["x-correlation-id"].clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["message"], "No healthy backends available");
+    assert_eq!(json["ticket"], correlation_id.to_str().unwrap());
 }
 
 // --- Health endpoint tests ---
@@ -267,10 +533,7 @@ fn make_health_state(backends: &[Backend]) -> Arc<AppState> {
 
     let runtime_backends = backends
         .iter()
-        .map(|b| RuntimeBackend {
-            config: b.clone(),
-            healthy: Arc::new(AtomicBool::new(true)),
-        })
+        .map(|b| RuntimeBackend::new(b.clone(), true))
         .collect();
 
     make_app_state(client, keystore, runtime_backends, health_state)
@@ -283,12 +546,14 @@ fn test_backends() -> Vec<Backend> {
             url: "http://a".to_string(),
             ws_url: None,
             weight: 1,
+            ..Default::default()
         },
         Backend {
             label: "b".to_string(),
             url: "http://b".to_string(),
             ws_url: None,
             weight: 1,
+            ..Default::default()
         },
     ]
 }
@@ -448,3 +713,3969 @@ async fn test_extract_rpc_method_invalid_json() {
     let body = response.into_body().collect().await.unwrap().to_bytes();
     assert_eq!(String::from_utf8(body.to_vec()).unwrap(), "none");
 }
+
+#[tokio::test]
+async fn test_extract_rpc_method_multi_chunk_body() {
+    let app = Router::new()
+        .route(
+            "/",
+            post(|req: Request<Body>| async move {
+                let method = req.extensions().get::<RpcMethod>().unwrap().0.clone();
+                let body = req.into_body().collect().await.unwrap().to_bytes();
+                format!("{} {}", method, body.len())
+            }),
+        )
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let chunks = [
+        r#"{"jsonrpc":"2.0","#,
+        r#""method":"getBalance","#,
+        r#""params":["11111111111111111111111111111111"],"id":1}"#,
+    ];
+    let total: usize = chunks.iter().map(|c| c.len()).sum();
+    let stream = futures_util::stream::iter(
+        chunks.map(|c| Ok::<_, std::io::Error>(axum::body::Bytes::from_static(c.as_bytes()))),
+    );
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/")
+        .header("content-type", "application/json")
+        .header("content-length", total)
+        .body(Body::from_stream(stream))
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(
+        String::from_utf8(body.to_vec()).unwrap(),
+        format!("getBalance {}", total)
+    );
+}
+
+#[tokio::test]
+async fn test_extract_rpc_method_streams_rest_of_body() {
+    // The handler answers from the first chunk alone: the middleware must not
+    // wait for the rest of the body
+    let app = Router::new()
+        .route(
+            "/",
+            post(|req: Request<Body>| async move {
+                let method = req.extensions().get::<RpcMethod>().unwrap().0.clone();
+                let mut body = req.into_body();
+                let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+                format!("{} {}", method, first.len())
+            }),
+        )
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let head = r#"{"jsonrpc":"2.0","method":"sendTransaction","params":[""#;
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<axum::body::Bytes, std::io::Error>>(1);
+    tx.send(Ok(axum::body::Bytes::from_static(head.as_bytes())))
+        .await
+        .unwrap();
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    let req = Request::builder()
+        .method("POST")
+        .uri("/")
+        .body(Body::from_stream(stream))
+        .unwrap();
+
+    let response = tokio::time::timeout(Duration::from_secs(5), app.oneshot(req))
+        .await
+        .expect("middleware waited for the whole body")
+        .unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, format!("sendTransaction {}", head.len()));
+    drop(tx);
+}
+
+#[tokio::test]
+async fn test_extract_rpc_method_parses_late_or_escaped_method() {
+    let app = Router::new()
+        .route(
+            "/",
+            post(|req: Request<Body>| async move {
+                let method = req.extensions().get::<RpcMethod>().map(|m| m.0.clone());
+                let body = req.into_body().collect().await.unwrap().to_bytes();
+                format!("{:?} {}", method, body.len())
+            }),
+        )
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    // Past the sniffed prefix, and with an escape: both need a full parse
+    let late = format!(
+        r#"{{"params":["{}"],"method":"getBalance","id":1}}"#,
+        "a".repeat(40 * 1024)
+    );
+    let escaped = r#"{"method":"get\u0042alance","id":1}"#.to_string();
+    for body in [late, escaped] {
+        let req = Request::builder()
+            .method("POST")
+            .uri("/")
+            .body(Body::from(body.clone()))
+            .unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        let text = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(text, format!("Some(\"getBalance\") {}", body.len()));
+    }
+}
+
+#[tokio::test]
+async fn test_proxy_streams_upstream_response() {
+    // The backend sends the start of a large result and holds the rest back
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<axum::body::Bytes, std::io::Error>>(1);
+    let rx = Arc::new(tokio::sync::Mutex::new(Some(rx)));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move || async move {
+                let rx = rx.lock().await.take().unwrap();
+                Body::from_stream(futures_util::stream::unfold(rx, |mut rx| async move {
+                    rx.recv().await.map(|chunk| (chunk, rx))
+                }))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "slow".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["slow".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let head = r#"{"jsonrpc":"2.0","result":{"transactions":["#;
+    tx.send(Ok(axum::body::Bytes::from_static(head.as_bytes())))
+        .await
+        .unwrap();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/?api-key=test-key")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"jsonrpc":"2.0","method":"getBlock","params":[1],"id":1}"#,
+        ))
+        .unwrap();
+    let first = tokio::time::timeout(Duration::from_secs(5), async {
+        let response = app.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body();
+        let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        (first, body)
+    })
+    .await
+    .expect("proxy waited for the whole response");
+    assert_eq!(first.0, head.as_bytes());
+
+    tx.send(Ok(axum::body::Bytes::from_static(b"]},\"id\":1}")))
+        .await
+        .unwrap();
+    drop(tx);
+    let rest = first.1.collect().await.unwrap().to_bytes();
+    assert_eq!(rest, &b"]},\"id\":1}"[..]);
+}
+
+// --- Browser key tests ---
+
+async fn browser_key_app(per_origin_ip_rate_limit: u64) -> Router {
+    let backend_url = start_mock_backend().await;
+
+    let https = HttpsConnector::new();
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(https);
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_browser_key(
+        "pub-key",
+        "dapp",
+        1000,
+        &["https://app.example.com", "https://*.example.org"],
+    );
+
+    let backend = Backend {
+        label: "mock-backend".to_string(),
+        url: backend_url,
+        ws_url: None,
+        weight: 1,
+        ..Default::default()
+    };
+    let runtime_backend = RuntimeBackend::new(backend, true);
+
+    let router_state = RouterState {
+        backends: vec![runtime_backend],
+        browser_keys: BrowserKeyConfig {
+            per_origin_ip_rate_limit,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        client,
+        keystore,
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
+
+    Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method))
+}
+
+fn browser_request(origin: Option<&str>, method: &str) -> Request<Body> {
+    let mut builder = Request::builder()
+        .method("POST")
+        .uri("/?api-key=pub-key")
+        .header("content-type", "application/json");
+    if let Some(origin) = origin {
+        builder = builder.header("origin", origin);
+    }
+    builder
+        .body(Body::from(format!(
+            r#"{{"jsonrpc":"2.0","method":"{}","id":1}}"#,
+            method
+        )))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_browser_key_allowed_origin() {
+    let app = browser_key_app(100).await;
+
+    let response = app
+        .clone()
+        .oneshot(browser_request(Some("https://app.example.com"), "getSlot"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(browser_request(Some("https://www.example.org"), "getSlot"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_browser_key_rejects_unknown_or_missing_origin() {
+    let app = browser_key_app(100).await;
+
+    let response = app
+        .clone()
+        .oneshot(browser_request(Some("https://evil.com"), "getSlot"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app.oneshot(browser_request(None, "getSlot")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_browser_key_blocked_method() {
+    let app = browser_key_app(100).await;
+
+    let response = app
+        .oneshot(browser_request(
+            Some("https://app.example.com"),
+            "getProgramAccounts",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_browser_key_per_origin_rate_limit() {
+    let app = browser_key_app(2).await;
+
+    for _ in 0..2 {
+        let response = app
+            .clone()
+            .oneshot(browser_request(Some("https://app.example.com"), "getSlot"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let response = app
+        .clone()
+        .oneshot(browser_request(Some("https://app.example.com"), "getSlot"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // A different origin has its own window
+    let response = app
+        .oneshot(browser_request(Some("https://shop.example.org"), "getSlot"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_proxy_signs_requests_for_signing_backends() {
+    let signing = SigningConfig {
+        key: Some("gateway-shared-secret".to_string()),
+        ..Default::default()
+    };
+
+    // Backend that only answers correctly signed requests
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let verifier = signing.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(
+                move |headers: axum::http::HeaderMap, body: axum::body::Bytes| async move {
+                    match verify_signature(&verifier, &headers, &body, unix_now()) {
+                        Ok(()) => (
+                            StatusCode::OK,
+                            "{\"jsonrpc\":\"2.0\",\"result\":1,\"id\":1}",
+                        ),
+                        Err(_) => (StatusCode::UNAUTHORIZED, "bad signature"),
+                    }
+                },
+            ),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "signed".to_string(),
+            url: backend_url,
+            weight: 1,
+            signing: Some(signing),
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["signed".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/?api-key=test-key")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_proxy_injects_backend_headers_and_query_params() {
+    // Backend that echoes its API key header and query
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(
+                |headers: axum::http::HeaderMap, uri: axum::http::Uri| async move {
+                    Json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "result": {
+                            "key": headers.get("x-api-key").and_then(|v| v.to_str().ok()),
+                            "query": uri.query(),
+                        },
+                        "id": 1,
+                    }))
+                },
+            ),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "provider".to_string(),
+            // As `url_query_params` leave it after loading the config
+            url: format!("{}?api-key=provider-secret", backend_url),
+            weight: 1,
+            headers: [("x-api-key".to_string(), "provider-secret".to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["provider".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/?api-key=test-key")
+        .header("content-type", "application/json")
+        .header("x-api-key", "client-value")
+        .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["result"]["key"], "provider-secret");
+    assert_eq!(body["result"]["query"], "api-key=provider-secret");
+}
+
+#[tokio::test]
+async fn test_discovery_document_reflects_config() {
+    let mut backends = test_backends();
+    backends[1].exclude_methods = vec!["getProgramAccounts".to_string()];
+    let state = make_health_state(&backends);
+
+    let app = Router::new()
+        .route("/v1/rpc-discovery", get(discovery_endpoint))
+        .with_state(state);
+    let req = Request::builder()
+        .uri("/v1/rpc-discovery")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["openrpc"], "1.2.6");
+    let methods = json["methods"].as_array().unwrap();
+    let find = |name: &str| methods.iter().find(|m| m["name"] == name);
+
+    let get_slot = find("getSlot").unwrap();
+    assert_eq!(get_slot["x-routing-class"], "standard");
+    assert_eq!(get_slot["x-backends"], serde_json::json!(["a", "b"]));
+
+    let gpa = find("getProgramAccounts").unwrap();
+    assert_eq!(gpa["x-backends"], serde_json::json!(["a"]));
+    assert_eq!(gpa["x-browser-keys-allowed"], false);
+
+    assert_eq!(find("getBlock").unwrap()["x-routing-class"], "archival");
+    assert_eq!(find("sendTransaction").unwrap()["x-routing-class"], "write");
+
+    // No backend has a ws_url, so subscriptions are not advertised
+    assert!(find("accountSubscribe").is_none());
+    assert_eq!(json["x-limits"]["websocket"], false);
+}
+
+#[tokio::test]
+async fn test_usage_endpoint_reports_error_rates() {
+    let backend_url = start_mock_backend().await;
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+
+    let backend = Backend {
+        label: "mock-backend".to_string(),
+        url: backend_url,
+        weight: 1,
+        ..Default::default()
+    };
+    let health_state = Arc::new(HealthState::new(vec!["mock-backend".to_string()]));
+    let state = make_app_state(
+        client,
+        keystore,
+        vec![RuntimeBackend::new(backend, true)],
+        health_state,
+    );
+
+    let app = Router::new()
+        .route("/", post(proxy))
+        .route("/v1/usage", get(usage_endpoint))
+        .with_state(state.clone())
+        .layer(middleware::from_fn_with_state(state, track_usage))
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    for body in [r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#, "not json"] {
+        let req = Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .body(Body::from(body))
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap();
+    }
+
+    let req = Request::builder()
+        .uri("/v1/usage?api-key=test-key")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["owner"], "tester");
+    assert_eq!(json["windows"]["1m"]["requests"], 2);
+    assert_eq!(json["windows"]["1m"]["invalid_requests"], 1);
+    assert_eq!(json["windows"]["1m"]["invalid_request_rate"], 0.5);
+}
+
+#[tokio::test]
+async fn test_traffic_report_counts_payload_bytes() {
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "reader", 100);
+    let backend = Backend {
+        label: "mock-backend".to_string(),
+        url: start_mock_backend().await,
+        weight: 1,
+        ..Default::default()
+    };
+    let health_state = Arc::new(HealthState::new(vec!["mock-backend".to_string()]));
+    let state = make_app_state(
+        client,
+        keystore,
+        vec![RuntimeBackend::new(backend, true)],
+        health_state,
+    );
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.traffic_report.enabled = true;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn_with_state(state.clone(), track_usage))
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let body = r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#;
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/?api-key=test-key")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let sent = response.into_body().collect().await.unwrap().to_bytes();
+
+    let report = state.traffic.report(1, unix_now());
+    assert_eq!(report.methods.len(), 1);
+    assert_eq!(report.methods[0].method, "getSlot");
+    assert_eq!(report.methods[0].request_bytes, body.len() as u64);
+    assert_eq!(report.methods[0].response_bytes, sent.len() as u64);
+}
+
+#[tokio::test]
+async fn test_probes_skip_rate_limits_and_usage() {
+    let backend_url = start_mock_backend().await;
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("probe-key", "blackbox", 1);
+    keystore.add_key("customer-key", "customer", 1);
+    for key in ["probe-key", "customer-key"] {
+        keystore
+            .rate_limited_keys
+            .lock()
+            .unwrap()
+            .push(key.to_string());
+    }
+
+    let backend = Backend {
+        label: "mock-backend".to_string(),
+        url: backend_url,
+        weight: 1,
+        ..Default::default()
+    };
+    let health_state = Arc::new(HealthState::new(vec!["mock-backend".to_string()]));
+    let state = make_app_state(
+        client,
+        keystore,
+        vec![RuntimeBackend::new(backend, true)],
+        health_state,
+    );
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.probes = ProbesConfig {
+            keys: vec!["probe-key".to_string()],
+            ..Default::default()
+        };
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn_with_state(state.clone(), track_usage))
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send_as = |key: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+    let response = app.clone().oneshot(send_as("probe-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.extensions().get::<ProbeRequest>().is_some());
+    let response = app.oneshot(send_as("customer-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let now = unix_now();
+    assert_eq!(state.usage.stats("blackbox", 60, now).requests, 0);
+}
+
+#[tokio::test]
+async fn test_proxy_forwards_upstream_bytes_unchanged() {
+    // Formatting a JSON re-serialization would normalize: whitespace, key order,
+    // escaped unicode and a number beyond f64 precision.
+    const UPSTREAM: &str = "{ \"id\":1,\n  \"result\": {\"z\":\"\\u00e9\", \"a\": 12345678901234567890123},\"jsonrpc\":\"2.0\" }";
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|| async {
+                (
+                    [("content-type", "application/json"), ("x-upstream", "1")],
+                    UPSTREAM,
+                )
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "raw".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["raw".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/?api-key=test-key")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"jsonrpc":"2.0","method":"getAccountInfo","id":1}"#,
+        ))
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-upstream"], "1");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, UPSTREAM.as_bytes());
+}
+
+#[tokio::test]
+async fn test_proxy_follows_key_method_routes() {
+    // Each backend answers with its own label
+    let mut backends = Vec::new();
+    for (label, weight) in [("shared", 1), ("dedicated", 0)] {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = Router::new().route("/", post(move || async move { label }));
+            axum::serve(listener, app).await.unwrap();
+        });
+        backends.push(RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url,
+                weight,
+                ..Default::default()
+            },
+            true,
+        ));
+    }
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("plain-key", "plain", 100);
+    keystore.add_key("premium-key", "premium", 100);
+    keystore.set_routes("premium-key", &[("getProgramAccounts", "dedicated")], None);
+    keystore.add_key("tier-key", "gold", 100);
+    keystore.set_routes("tier-key", &[], Some("gold"));
+    let health_state = Arc::new(HealthState::new(vec![
+        "shared".to_string(),
+        "dedicated".to_string(),
+    ]));
+    let state = make_app_state(client, keystore, backends, health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        let gold = HashMap::from([("getBlock".to_string(), "dedicated".to_string())]);
+        next.tier_routes = HashMap::from([("gold".to_string(), gold)]);
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let served_by = |key: &str, method: &str| {
+        let app = app.clone();
+        let req = Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","method":"{}","id":1}}"#,
+                method
+            )))
+            .unwrap();
+        async move {
+            let response = app.oneshot(req).await.unwrap();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            String::from_utf8(body.to_vec()).unwrap()
+        }
+    };
+
+    assert_eq!(
+        served_by("premium-key", "getProgramAccounts").await,
+        "dedicated"
+    );
+    assert_eq!(served_by("premium-key", "getBalance").await, "shared");
+    assert_eq!(served_by("plain-key", "getProgramAccounts").await, "shared");
+    assert_eq!(served_by("tier-key", "getBlock").await, "dedicated");
+    assert_eq!(served_by("tier-key", "getProgramAccounts").await, "shared");
+}
+
+#[tokio::test]
+async fn test_proxy_enforces_key_method_lists() {
+    // Backend counts the requests that reach it
+    let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let counter = hits.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move || async move {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                r#"{"jsonrpc":"2.0","result":1,"id":1}"#
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+    let backends = vec![RuntimeBackend::new(
+        Backend {
+            label: "backend-1".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    )];
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("reader-key", "reader", 100);
+    keystore.set_methods("reader-key", &["getBalance", "getSlot"], &[]);
+    keystore.add_key("no-send-key", "no-send", 100);
+    keystore.set_methods("no-send-key", &[], &["sendTransaction"]);
+    keystore.add_key("das-key", "das", 100);
+    keystore.set_scopes("das-key", &[MethodCategory::Das]);
+    let health_state = Arc::new(HealthState::new(vec!["backend-1".to_string()]));
+    let state = make_app_state(client, keystore, backends, health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = |key: &str, body: &str| {
+        let app = app.clone();
+        let req = Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        async move {
+            let response = app.oneshot(req).await.unwrap();
+            let status = response.status();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            (status, body)
+        }
+    };
+
+    let (status, body) = send(
+        "reader-key",
+        r#"{"jsonrpc":"2.0","method":"getBalance","id":1}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"], 1);
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // Denied methods get a JSON-RPC error and never reach the backend
+    let (status, body) = send(
+        "reader-key",
+        r#"{"jsonrpc":"2.0","method":"getBlock","id":7}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["error"]["code"], -32601);
+    assert_eq!(body["id"], 7);
+    let (_, body) = send(
+        "no-send-key",
+        r#"[{"jsonrpc":"2.0","method":"getSlot","id":1},{"jsonrpc":"2.0","method":"sendTransaction","id":2}]"#,
+    )
+    .await;
+    assert_eq!(body[0]["error"]["code"], -32601);
+    assert_eq!(body[1]["error"]["code"], -32601);
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    let (_, body) = send(
+        "no-send-key",
+        r#"{"jsonrpc":"2.0","method":"getBlock","id":1}"#,
+    )
+    .await;
+    assert_eq!(body["result"], 1);
+
+    // Scoped keys call the methods of their categories only
+    let (_, body) = send("das-key", r#"{"jsonrpc":"2.0","method":"getAsset","id":1}"#).await;
+    assert_eq!(body["result"], 1);
+    let (_, body) = send("das-key", r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#).await;
+    assert_eq!(body["error"]["code"], -32601);
+}
+
+#[tokio::test]
+async fn test_proxy_injects_key_defaults() {
+    // Backend echoes the request body it received
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route("/", post(|body: axum::body::Bytes| async move { body }));
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("plain-key", "plain", 100);
+    keystore.add_key("defaults-key", "standardized", 100);
+    keystore.set_defaults(
+        "defaults-key",
+        RequestDefaults::new(Some("confirmed".to_string()), None).unwrap(),
+    );
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "echo".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["echo".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let body = r#"{"jsonrpc":"2.0","method":"getBalance","params":["Acc1"],"id":1}"#;
+    let send = |key: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .header("content-length", body.len())
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(send("defaults-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let echoed = response.into_body().collect().await.unwrap().to_bytes();
+    let echoed: serde_json::Value = serde_json::from_slice(&echoed).unwrap();
+    assert_eq!(
+        echoed["params"],
+        serde_json::json!(["Acc1", {"commitment": "confirmed"}])
+    );
+
+    // Keys without defaults are forwarded byte for byte
+    let response = app.oneshot(send("plain-key")).await.unwrap();
+    let echoed = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(echoed, body.as_bytes());
+}
+
+#[tokio::test]
+async fn test_proxy_enforces_tier_preflight_policy() {
+    // Backend echoes the request body it received
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route("/", post(|body: axum::body::Bytes| async move { body }));
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("plain-key", "plain", 100);
+    keystore.add_key("retail-key", "retail", 100);
+    keystore.set_routes("retail-key", &[], Some("retail"));
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "echo".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["echo".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.preflight_policies = HashMap::from([(
+            "retail".to_string(),
+            PreflightPolicy {
+                require_preflight: true,
+                max_retries: Some(0),
+                ..Default::default()
+            },
+        )]);
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let body = r#"{"jsonrpc":"2.0","method":"sendTransaction","params":["tx",{"skipPreflight":true}],"id":1}"#;
+    let send = |key: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .header("content-length", body.len())
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(send("retail-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let echoed = response.into_body().collect().await.unwrap().to_bytes();
+    let echoed: serde_json::Value = serde_json::from_slice(&echoed).unwrap();
+    assert_eq!(
+        echoed["params"],
+        serde_json::json!(["tx", {"skipPreflight": false, "maxRetries": 0}])
+    );
+
+    // Keys outside the tier are forwarded byte for byte
+    let response = app.oneshot(send("plain-key")).await.unwrap();
+    let echoed = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(echoed, body.as_bytes());
+}
+
+#[tokio::test]
+async fn test_proxy_forwards_audited_requests_unchanged() {
+    // Backend echoes the request body it received
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route("/", post(|body: axum::body::Bytes| async move { body }));
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("audited-key", "audited", 100);
+    assert!(keystore.set_audit("audited-key", true).await.unwrap());
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "echo".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["echo".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let body = r#"{"jsonrpc":"2.0","method":"getBalance","params":["Acc1"],"id":1}"#;
+    let request = Request::builder()
+        .method("POST")
+        .uri("/?api-key=audited-key")
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.extensions().get::<SelectedBackend>().unwrap().0,
+        "echo"
+    );
+    let echoed = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(echoed, body.as_bytes());
+}
+
+#[tokio::test]
+async fn test_readyz_requires_every_group() {
+    let backends: Vec<Backend> = [("archive", "archival"), ("fast", "standard")]
+        .iter()
+        .map(|(label, group)| Backend {
+            label: label.to_string(),
+            url: format!("http://{}", label),
+            weight: 1,
+            groups: vec![group.to_string()],
+            ..Default::default()
+        })
+        .collect();
+    let state = make_health_state(&backends);
+    let app = Router::new()
+        .route("/readyz", get(readyz_endpoint))
+        .with_state(state.clone());
+    let readyz = || {
+        Request::builder()
+            .uri("/readyz")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Only the archival backend is down: "any backend" readiness still passes
+    state.state.load().backends[0]
+        .healthy
+        .store(false, std::sync::atomic::Ordering::Relaxed);
+    let response = app.clone().oneshot(readyz()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.readiness.required_groups = vec!["archival".to_string(), "standard".to_string()];
+        next
+    });
+    let response = app.oneshot(readyz()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["ready"], false);
+    assert_eq!(json["groups"]["archival"]["in_rotation"], 0);
+    assert_eq!(json["groups"]["standard"]["in_rotation"], 1);
+}
+
+#[tokio::test]
+async fn test_proxy_retries_unsupported_transaction_version() {
+    // Backend rejects getBlock without maxSupportedTransactionVersion, like a real node
+    // hitting a block with versioned transactions, and echoes the params otherwise
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|Json(request): Json<serde_json::Value>| async move {
+                if request["params"][1]["maxSupportedTransactionVersion"].is_null() {
+                    Json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "error": {"code": -32015, "message": "Transaction version (0) is not supported"},
+                        "id": 1
+                    }))
+                } else {
+                    Json(serde_json::json!({"jsonrpc": "2.0", "result": request["params"], "id": 1}))
+                }
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "legacy-client", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "versioned".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["versioned".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let body = r#"{"jsonrpc":"2.0","method":"getBlock","params":[430,{"encoding":"json"}],"id":1}"#;
+    let send = || {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    // Disabled by default: the upstream error reaches the client unchanged
+    let response = app.clone().oneshot(send()).await.unwrap();
+    assert!(response.headers().get("x-rpc-router-retry").is_none());
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["error"]["code"], -32015);
+
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.retry_transaction_version = Some(0);
+        next
+    });
+    let response = app.oneshot(send()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["x-rpc-router-retry"],
+        "maxSupportedTransactionVersion=0"
+    );
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(
+        json["result"],
+        serde_json::json!([430, {"encoding": "json", "maxSupportedTransactionVersion": 0}])
+    );
+}
+
+#[tokio::test]
+async fn test_proxy_streams_chunked_responses_past_version_check() {
+    // A large chunked getBlock response whose end the backend holds back
+    let (tail_tx, tail_rx) = tokio::sync::oneshot::channel::<()>();
+    let tail_rx = Arc::new(std::sync::Mutex::new(Some(tail_rx)));
+    let head = format!(r#"{{"jsonrpc":"2.0","result":"{}"#, "a".repeat(4096));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let chunk = head.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move || async move {
+                let tail_rx = tail_rx.lock().unwrap().take().unwrap();
+                let tail = futures_util::stream::once(async move {
+                    tail_rx.await.ok();
+                    Ok(r#"","id":1}"#.to_string())
+                });
+                let chunks = futures_util::StreamExt::chain(
+                    futures_util::stream::iter([Ok::<_, std::io::Error>(chunk)]),
+                    tail,
+                );
+                Body::from_stream(chunks)
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "legacy-client", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "versioned".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["versioned".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.retry_transaction_version = Some(0);
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/?api-key=test-key")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"jsonrpc":"2.0","method":"getBlock","params":[430],"id":1}"#,
+        ))
+        .unwrap();
+    let response = tokio::time::timeout(Duration::from_secs(5), app.oneshot(req))
+        .await
+        .expect("response held until the backend finished")
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("x-rpc-router-retry").is_none());
+
+    tail_tx.send(()).unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, format!(r#"{}","id":1}}"#, head));
+}
+
+#[tokio::test]
+async fn test_proxy_hedges_to_fastest_response() {
+    async fn start_backend(name: &'static str, delay: Duration) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = Router::new().route(
+                "/",
+                post(move || async move {
+                    tokio::time::sleep(delay).await;
+                    format!("{{\"jsonrpc\":\"2.0\",\"result\":\"{}\",\"id\":1}}", name)
+                }),
+            );
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    }
+    let slow = start_backend("slow", Duration::from_secs(3)).await;
+    let fast = start_backend("fast", Duration::ZERO).await;
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "trader", 100);
+    let backends = [("slow", slow), ("fast", fast)]
+        .into_iter()
+        .map(|(label, url)| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url,
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            )
+        })
+        .collect();
+    let health_state = Arc::new(HealthState::new(vec![
+        "slow".to_string(),
+        "fast".to_string(),
+    ]));
+    let state = make_app_state(client, keystore, backends, health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.hedging.enabled = true;
+        next.hedging.budget_percent = 100;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/?api-key=test-key")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+        .unwrap();
+    let started = std::time::Instant::now();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["result"], "fast");
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    // The winner's latency is recorded for the next pick
+    let router_state = state.state.load();
+    assert!(
+        router_state.backends[1]
+            .latency_us
+            .load(std::sync::atomic::Ordering::Relaxed)
+            > 0
+    );
+}
+
+#[tokio::test]
+async fn test_proxy_delayed_hedge() {
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+    async fn start_backend(
+        name: &'static str,
+        delay_ms: Arc<AtomicU64>,
+        hits: Arc<AtomicUsize>,
+    ) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = Router::new().route(
+                "/",
+                post(move || async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    let delay = delay_ms.load(Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    format!("{{\"jsonrpc\":\"2.0\",\"result\":\"{}\",\"id\":1}}", name)
+                }),
+            );
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    }
+    let delays = [Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0))];
+    let hits = [Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0))];
+    let mut backends = Vec::new();
+    for (i, label) in ["first", "second"].into_iter().enumerate() {
+        let url = start_backend(label, delays[i].clone(), hits[i].clone()).await;
+        backends.push(RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url,
+                weight: 1,
+                ..Default::default()
+            },
+            true,
+        ));
+    }
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "trader", 100);
+    let health_state = Arc::new(HealthState::new(vec![
+        "first".to_string(),
+        "second".to_string(),
+    ]));
+    let state = make_app_state(client, keystore, backends, health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.hedging.enabled = true;
+        next.hedging.budget_percent = 100;
+        next.hedging.delay_ms = 300;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let get_slot = |app: Router| {
+        let state = state.clone();
+        async move {
+            // "first" is the faster backend, so it gets the request first
+            let router_state = state.state.load();
+            router_state.backends[0]
+                .latency_us
+                .store(1, Ordering::Relaxed);
+            router_state.backends[1]
+                .latency_us
+                .store(1_000_000, Ordering::Relaxed);
+            let request = Request::builder()
+                .method("POST")
+                .uri("/?api-key=test-key")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+                .unwrap();
+            let started = std::time::Instant::now();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let json: serde_json::Value =
+                serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            (
+                json["result"].as_str().unwrap().to_string(),
+                started.elapsed(),
+            )
+        }
+    };
+
+    // Answered within the delay: the second backend never hears of it
+    let (winner, _) = get_slot(app.clone()).await;
+    assert_eq!(winner, "first");
+    assert_eq!(hits[1].load(Ordering::SeqCst), 0);
+
+    // Slower than the delay: hedged, and the second backend wins
+    delays[0].store(3_000, Ordering::SeqCst);
+    let (winner, elapsed) = get_slot(app.clone()).await;
+    assert_eq!(winner, "second");
+    assert!(elapsed >= Duration::from_millis(300), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+    assert_eq!(hits[1].load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_send_transaction_broadcast() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // One backend rejects the transaction at once, the other accepts it a bit later
+    async fn start_backend(accepts: bool, hits: Arc<AtomicUsize>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = Router::new().route(
+                "/",
+                post(move || async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    if accepts {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        r#"{"jsonrpc":"2.0","result":"accepted","id":1}"#
+                    } else {
+                        r#"{"jsonrpc":"2.0","error":{"code":-32002,"message":"rejected"},"id":1}"#
+                    }
+                }),
+            );
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    }
+    let hits = [Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0))];
+    let rejecting = start_backend(false, hits[0].clone()).await;
+    let accepting = start_backend(true, hits[1].clone()).await;
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "trader", 100);
+    let labels = ["rejecting", "accepting"];
+    let backends = labels
+        .into_iter()
+        .zip([rejecting, accepting])
+        .map(|(label, url)| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url,
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            )
+        })
+        .collect();
+    let health_state = Arc::new(HealthState::new(
+        labels.iter().map(|l| l.to_string()).collect(),
+    ));
+    let state = make_app_state(client, keystore.clone(), backends, health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    // One signature, a legacy header, one account key and the blockhash
+    let mut tx = vec![1u8];
+    tx.extend([7; 64]);
+    tx.extend([1, 0, 0, 1]);
+    tx.extend([8; 32]);
+    tx.extend([9; 32]);
+    tx.push(0);
+    let body = serde_json::json!({
+        "jsonrpc": "2.0", "id": 1, "method": "sendTransaction",
+        "params": [bs58::encode(&tx).into_string()]
+    })
+    .to_string();
+    let request = || {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(body.clone()))
+            .unwrap()
+    };
+    let total_hits = || hits.iter().map(|h| h.load(Ordering::SeqCst)).sum::<usize>();
+
+    // Off by default: one backend gets the transaction
+    app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(total_hits(), 1);
+
+    // The key opts in: both get it and the acceptance is served
+    keystore.set_broadcast("test-key", true);
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.extensions().get::<SelectedBackend>().unwrap().0,
+        "accepting"
+    );
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["result"], "accepted");
+    assert_eq!(total_hits(), 3);
+
+    // Sent again, it is answered with its signature without reaching a backend
+    let response = app.oneshot(request()).await.unwrap();
+    assert_eq!(
+        response.extensions().get::<SelectedBackend>().unwrap().0,
+        "broadcast-dedup"
+    );
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["result"], bs58::encode([7; 64]).into_string());
+    assert_eq!(json["id"], 1);
+    assert_eq!(total_hits(), 3);
+}
+
+#[tokio::test]
+async fn test_send_transaction_journal() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Accepts every transaction, answering with the signature it was sent
+    let hits = Arc::new(AtomicUsize::new(0));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let backend_hits = hits.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| async move {
+                backend_hits.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({"jsonrpc": "2.0", "result": "accepted", "id": request["id"]}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let mut path = std::env::temp_dir();
+    path.push("sol_rpc_router_test_handler_journal.jsonl");
+    let _ = std::fs::remove_file(&path);
+    let journal = Arc::new(FileJournalStore::new(&path, 3_600));
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "trader", 100);
+    keystore.add_key("private-key", "institution", 100);
+    keystore.set_privacy("private-key", LogPrivacy::Metadata);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "primary".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["primary".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let state = Arc::new(AppState {
+        journal: Some(journal.clone()),
+        ..(*state).clone()
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    // One signature, a legacy header, one account key and the blockhash
+    let send_body = |signature: u8| {
+        let mut tx = vec![1u8];
+        tx.extend([signature; 64]);
+        tx.extend([1, 0, 0, 1]);
+        tx.extend([8; 32]);
+        tx.extend([9; 32]);
+        tx.push(0);
+        let body = serde_json::json!({
+            "jsonrpc": "2.0", "id": 1, "method": "sendTransaction",
+            "params": [bs58::encode(&tx).into_string()]
+        });
+        (
+            bs58::encode([signature; 64]).into_string(),
+            body.to_string(),
+        )
+    };
+    let request = |key: &str, body: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let (signature, body) = send_body(7);
+    let response = app
+        .clone()
+        .oneshot(request("test-key", &body))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let entry = journal.get(&signature).await.unwrap().unwrap();
+    assert_eq!(entry.status, JournalStatus::Accepted);
+    assert_eq!(entry.key, key_fingerprint("test-key"));
+    assert_eq!(entry.owner, "trader");
+    assert_eq!(entry.outcomes.len(), 1);
+    assert_eq!(entry.outcomes[0].backend.as_deref(), Some("primary"));
+    assert_eq!(entry.request.as_deref(), Some(body.as_str()));
+
+    // Keys without full logging are journaled without the transaction
+    let (private_signature, body) = send_body(6);
+    app.oneshot(request("private-key", &body)).await.unwrap();
+    let entry = journal.get(&private_signature).await.unwrap().unwrap();
+    assert_eq!(entry.status, JournalStatus::Accepted);
+    assert!(entry.request.is_none());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // Left in flight by a previous run: resumed at startup
+    let (signature, body) = send_body(5);
+    let pending = JournalEntry::new(
+        signature.clone(),
+        key_fingerprint("test-key"),
+        "trader".to_string(),
+        unix_now() - 10,
+        Some(body),
+    );
+    journal.put(&pending).await.unwrap();
+    resume_journal(state, 90).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    let entry = journal.get(&signature).await.unwrap().unwrap();
+    assert_eq!(entry.status, JournalStatus::Accepted);
+    assert!(entry.outcomes[0].resumed);
+    assert!(journal.pending(0).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_archive_routing_sends_deep_history_to_archive_backends() {
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "reader", 100);
+    let mut backends = Vec::new();
+    for (label, weight, archive) in [("recent", 1, false), ("archive", 0, true)] {
+        backends.push(RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url: start_mock_backend().await,
+                weight,
+                archive,
+                ..Default::default()
+            },
+            true,
+        ));
+    }
+    let health_state = Arc::new(HealthState::new(vec![
+        "recent".to_string(),
+        "archive".to_string(),
+    ]));
+    health_state.set_tip(1_000_000);
+    let state = make_app_state(client, keystore, backends, health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.archive_routing = ArchiveRoutingConfig {
+            enabled: true,
+            min_depth_slots: 1000,
+        };
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let routed_to = |method: &str, slot: u64| {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"{}","params":[{}]}}"#,
+                method, slot
+            )))
+            .unwrap();
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            response
+                .extensions()
+                .get::<SelectedBackend>()
+                .unwrap()
+                .0
+                .clone()
+        }
+    };
+
+    assert_eq!(routed_to("getBlock", 10).await, "archive");
+    assert_eq!(routed_to("getBlocks", 998_000).await, "archive");
+    assert_eq!(routed_to("getBlock", 999_500).await, "recent");
+    // Only methods addressing a slot are inspected
+    assert_eq!(routed_to("getBalance", 10).await, "recent");
+
+    // Without an archive backend in rotation, deep history goes anywhere
+    state.state.load().backends[1]
+        .healthy
+        .store(false, std::sync::atomic::Ordering::Relaxed);
+    assert_eq!(routed_to("getBlock", 10).await, "recent");
+}
+
+#[tokio::test]
+async fn test_request_ids_reach_backends_and_clients() {
+    // Backend answers with the request id it received
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|headers: axum::http::HeaderMap| async move {
+                let id = headers[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+                Json(serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": id}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "ids".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["ids".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method))
+        .layer(middleware::from_fn(assign_request_id));
+    let send = |id: Option<&str>| {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json");
+        if let Some(id) = id {
+            request = request.header(REQUEST_ID_HEADER, id);
+        }
+        request
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+    let returned = |response: axum::response::Response| async move {
+        let header = response.headers()[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let json: serde_json::Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        (header, json["result"].as_str().unwrap().to_string())
+    };
+
+    // A client's id is passed through
+    let response = app.clone().oneshot(send(Some("trace-42"))).await.unwrap();
+    let (header, seen) = returned(response).await;
+    assert_eq!((header.as_str(), seen.as_str()), ("trace-42", "trace-42"));
+
+    // Otherwise one is generated, also for ids that are too long
+    for id in [None, Some("x".repeat(200).as_str())] {
+        let response = app.clone().oneshot(send(id)).await.unwrap();
+        let (header, seen) = returned(response).await;
+        assert_eq!(header.len(), 32);
+        assert_eq!(header, seen);
+    }
+}
+
+#[tokio::test]
+async fn test_filter_response_fields() {
+    // Backend reports whether the filter header reached it
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|headers: axum::http::HeaderMap| async move {
+                Json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": {
+                        "blockhash": "abc",
+                        "sawHeader": headers.contains_key("x-response-fields"),
+                        "transactions": ["large"]
+                    }
+                }))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "mobile", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "block".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["block".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            filter_response_fields,
+        ))
+        .layer(middleware::from_fn(extract_rpc_method));
+    let send = |fields: &str| {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .header("x-response-fields", fields)
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","method":"getBlock","params":[430],"id":1}"#,
+            ))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(send("/result/blockhash,/result/sawHeader"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": {"blockhash": "abc", "sawHeader": false}})
+    );
+
+    let response = app.clone().oneshot(send("result")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Responses over the buffering limit are passed through whole
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.max_buffered_bytes = 16;
+        next
+    });
+    let response = app.oneshot(send("/result/blockhash")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["result"]["transactions"][0], "large");
+}
+
+#[tokio::test]
+async fn test_proxy_serves_genesis_hash_from_cache() {
+    // Backend counts how often it is asked
+    let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let backend_hits = hits.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| async move {
+                backend_hits.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Json(serde_json::json!({"jsonrpc": "2.0", "result": "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d", "id": request["id"]}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "genesis".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["genesis".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = |id: u64| {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","method":"getGenesisHash","id":{}}}"#,
+                id
+            )))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(send(1)).await.unwrap();
+    assert_eq!(response.headers()["x-cache"], "MISS");
+
+    // The second request never reaches the backend and gets its own id back
+    let response = app.clone().oneshot(send(7)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-cache"], "HIT");
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(
+        json["result"],
+        "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d"
+    );
+    assert_eq!(json["id"], 7);
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // So does its kill switch, until released
+    state.kill_switches.set(Subsystem::Cache, true);
+    let response = app.clone().oneshot(send(8)).await.unwrap();
+    assert!(response.headers().get("x-cache").is_none());
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+    state.kill_switches.set(Subsystem::Cache, false);
+    let response = app.clone().oneshot(send(9)).await.unwrap();
+    assert_eq!(response.headers()["x-cache"], "HIT");
+
+    // Disabling the cache sends every request upstream
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.cache.enabled = false;
+        next
+    });
+    let response = app.oneshot(send(10)).await.unwrap();
+    assert!(response.headers().get("x-cache").is_none());
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_tuned_cache_hits_are_revalidated() {
+    // Backend whose answer changes on every call
+    let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let backend_calls = calls.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| async move {
+                let n = backend_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Json(serde_json::json!({"jsonrpc": "2.0", "result": {"feature-set": n}, "id": request["id"]}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "version".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["version".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.cache
+            .method_ttl_secs
+            .insert("getVersion".to_string(), 60);
+        let tuning = &mut next.cache.ttl_tuning;
+        tuning.enabled = true;
+        tuning.explore_percent = 0;
+        tuning.revalidate_percent = 100;
+        tuning.methods.insert(
+            "getVersion".to_string(),
+            TtlBounds {
+                min_secs: 5,
+                max_secs: 10,
+            },
+        );
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = || {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","method":"getVersion","id":1}"#,
+            ))
+            .unwrap()
+    };
+    let response = app.clone().oneshot(send()).await.unwrap();
+    assert_eq!(response.headers()["x-cache"], "MISS");
+    let response = app.oneshot(send()).await.unwrap();
+    assert_eq!(response.headers()["x-cache"], "HIT");
+
+    // The hit is re-fetched in the background and found stale
+    let config = state.state.load().cache.ttl_tuning.clone();
+    let mut arm = None;
+    for _ in 0..50 {
+        let snapshot = state.ttl_tuner.snapshot(&config);
+        if snapshot[0].arms[0].revalidations > 0 {
+            arm = Some(snapshot[0].arms[0].clone());
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let arm = arm.expect("hit was revalidated");
+    assert_eq!(arm.ttl_secs, 5);
+    assert_eq!((arm.stores, arm.hits, arm.mismatches), (1, 1, 1));
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_expired_account_reads_revalidated_by_slot() {
+    // Node at slot `tip`, behind any request asking for a later one
+    let tip = Arc::new(std::sync::atomic::AtomicU64::new(100));
+    let min_slots = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let (node_tip, seen) = (tip.clone(), min_slots.clone());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| async move {
+                let slot = node_tip.load(std::sync::atomic::Ordering::SeqCst);
+                let min_slot = request["params"][1]["minContextSlot"].as_u64();
+                seen.lock().unwrap().push(min_slot);
+                if min_slot.is_some_and(|min| min > slot) {
+                    return Json(serde_json::json!({"jsonrpc": "2.0", "error": {"code": -32016, "message": "Minimum context slot has not been reached"}, "id": request["id"]}));
+                }
+                Json(serde_json::json!({"jsonrpc": "2.0", "result": {"context": {"slot": slot}, "value": {"lamports": slot}}, "id": request["id"]}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "node".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["node".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.cache
+            .method_ttl_secs
+            .insert("getAccountInfo".to_string(), 1);
+        next.cache.revalidate_methods = vec!["getAccountInfo".to_string()];
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = |id: u64| {
+        let body = format!(
+            r#"{{"jsonrpc":"2.0","method":"getAccountInfo","params":["Acc1"],"id":{}}}"#,
+            id
+        );
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let read = |response: axum::response::Response| async move {
+        let cache = response.headers()["x-cache"].to_str().unwrap().to_string();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (
+            cache,
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+        )
+    };
+
+    let (cache, body) = read(app.clone().oneshot(send(1)).await.unwrap()).await;
+    assert_eq!(cache, "MISS");
+    assert_eq!(body["result"]["context"]["slot"], 100);
+
+    // Expired, but the node has nothing past slot 100: the entry is kept
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let (cache, body) = read(app.clone().oneshot(send(2)).await.unwrap()).await;
+    assert_eq!(cache, "REVALIDATED");
+    assert_eq!(body["result"]["value"]["lamports"], 100);
+    assert_eq!(body["id"], 2);
+    let (cache, _) = read(app.clone().oneshot(send(3)).await.unwrap()).await;
+    assert_eq!(cache, "HIT");
+
+    // Once the node moves on, the newer result replaces it
+    tip.store(105, std::sync::atomic::Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let (cache, body) = read(app.oneshot(send(4)).await.unwrap()).await;
+    assert_eq!(cache, "MISS");
+    assert_eq!(body["result"]["context"]["slot"], 105);
+    assert_eq!(*min_slots.lock().unwrap(), vec![None, Some(101), Some(101)]);
+}
+
+#[tokio::test]
+async fn test_network_stats_cached_after_cross_check() {
+    // Two backends reporting a total supply each; one can be made to disagree
+    let totals = [
+        Arc::new(std::sync::atomic::AtomicU64::new(1_000_000)),
+        Arc::new(std::sync::atomic::AtomicU64::new(1_000_500)),
+    ];
+    let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let mut backends = Vec::new();
+    for (i, total) in totals.iter().enumerate() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (total, backend_hits) = (total.clone(), hits.clone());
+        tokio::spawn(async move {
+            let app = Router::new().route(
+                "/",
+                post(move |Json(request): Json<serde_json::Value>| async move {
+                    backend_hits.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    let total = total.load(std::sync::atomic::Ordering::SeqCst);
+                    Json(serde_json::json!({"jsonrpc": "2.0", "result": {"context": {"slot": 1 + i}, "value": {"total": total}}, "id": request["id"]}))
+                }),
+            );
+            axum::serve(listener, app).await.unwrap();
+        });
+        backends.push(RuntimeBackend::new(
+            Backend {
+                label: format!("node-{}", i),
+                url,
+                weight: 1,
+                ..Default::default()
+            },
+            true,
+        ));
+    }
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let health_state = Arc::new(HealthState::new(vec![
+        "node-0".to_string(),
+        "node-1".to_string(),
+    ]));
+    let state = make_app_state(client, keystore, backends, health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.cache.network_stats.enabled = true;
+        next.cache.network_stats.cross_check = true;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = |commitment: &str| {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","method":"getSupply","params":[{{"commitment":"{}"}}],"id":1}}"#,
+                commitment
+            )))
+            .unwrap()
+    };
+    let hit_count = || hits.load(std::sync::atomic::Ordering::SeqCst);
+
+    // Totals within 10 basis points agree: the answer is cross-checked once,
+    // then served from the cache
+    let response = app.clone().oneshot(send("finalized")).await.unwrap();
+    assert_eq!(response.headers()["x-cache"], "MISS");
+    assert_eq!(hit_count(), 2);
+    let response = app.clone().oneshot(send("finalized")).await.unwrap();
+    assert_eq!(response.headers()["x-cache"], "HIT");
+    assert_eq!(hit_count(), 2);
+
+    // Disagreeing answers are passed on but not cached
+    totals[1].store(2_000_000, std::sync::atomic::Ordering::SeqCst);
+    for expected in [4, 6] {
+        let response = app.clone().oneshot(send("confirmed")).await.unwrap();
+        assert_eq!(response.headers()["x-cache"], "MISS");
+        assert_eq!(hit_count(), expected);
+    }
+}
+
+#[tokio::test]
+async fn test_proxy_coalesces_concurrent_token_account_requests() {
+    // Slow backend counting how often it is asked
+    let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let backend_hits = hits.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| async move {
+                backend_hits.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Json(serde_json::json!({"jsonrpc": "2.0", "result": {"context": {"slot": 1}, "value": []}, "id": request["id"]}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "tokens".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["tokens".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let requests = (1..=5).map(|id| {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","method":"getTokenAccountsByOwner","params":["Owner1",{{"mint":"Mint1"}},{{"encoding":"jsonParsed"}}],"id":{}}}"#,
+                id
+            )))
+            .unwrap();
+        app.clone().oneshot(request)
+    });
+    let responses = futures_util::future::join_all(requests).await;
+
+    // One request went upstream; the others waited for its answer
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+    let mut misses = 0;
+    for (id, response) in (1..=5).zip(responses) {
+        let response = response.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        misses += (response.headers()["x-cache"] == "MISS") as u32;
+        let json: serde_json::Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        assert_eq!(json["id"], id);
+    }
+    assert_eq!(misses, 1);
+}
+
+#[tokio::test]
+async fn test_proxy_coalesces_identical_concurrent_reads() {
+    // Slow backend counting how often it is asked
+    let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let backend_hits = hits.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| async move {
+                backend_hits.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Json(serde_json::json!({"jsonrpc": "2.0", "result": 312_000_000u64, "id": request["id"]}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "slots".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["slots".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.coalescing.enabled = true;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = |id: u32, commitment: &str| {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","method":"getSlot","params":[{{"commitment":"{}"}}],"id":{}}}"#,
+                commitment, id
+            )))
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+    let requests = (1..=5).map(|id| send(id, "confirmed"));
+    let responses = futures_util::future::join_all(requests).await;
+
+    // One request went upstream; the others shared its answer under their own id
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+    for (id, response) in (1..=5).zip(responses) {
+        let response = response.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        assert_eq!(json["result"], 312_000_000u64);
+        assert_eq!(json["id"], id);
+    }
+    assert!(state.coalescer.is_empty());
+
+    // Different commitments are different reads, and nothing is cached
+    let responses =
+        futures_util::future::join_all([send(6, "confirmed"), send(7, "finalized")]).await;
+    assert!(responses
+        .iter()
+        .all(|r| r.as_ref().unwrap().status() == StatusCode::OK));
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_proxy_ejects_backend_failing_requests() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "node is down") }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "failing".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["failing".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.health_check_config.passive_failures_threshold = 2;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = || {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+    let response = app.clone().oneshot(send()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(state.state.load().backends[0].in_rotation());
+
+    // The second failure in a row takes it out of rotation without waiting for a check
+    app.clone().oneshot(send()).await.unwrap();
+    let router_state = state.state.load();
+    assert!(!router_state.backends[0].in_rotation());
+    let status = router_state.health_state.get_status("failing").unwrap();
+    assert_eq!(status.level(), HealthLevel::Unhealthy);
+    let response = app.oneshot(send()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_proxy_serves_stale_results_when_no_backend_is_healthy() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|Json(request): Json<serde_json::Value>| async move {
+                Json(serde_json::json!({"jsonrpc": "2.0", "result": 312_000_000u64, "id": request["id"]}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "only".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let healthy = runtime_backend.healthy.clone();
+    let health_state = Arc::new(HealthState::new(vec!["only".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.cache.serve_stale_secs = 60;
+        next.cache.stale_methods = vec!["getSlot".to_string()];
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = |method: &str, id: u64| {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","method":"{}","id":{}}}"#,
+                method, id
+            )))
+            .unwrap()
+    };
+
+    // Remembered while healthy, but not cached
+    let response = app.clone().oneshot(send("getSlot", 1)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("x-cache").is_none());
+
+    healthy.store(false, std::sync::atomic::Ordering::Relaxed);
+    let response = app.clone().oneshot(send("getSlot", 9)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-served-stale"], "true");
+    assert!(response.headers().contains_key("age"));
+    let json: serde_json::Value =
+        serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(json["result"], 312_000_000u64);
+    assert_eq!(json["id"], 9);
+
+    // Nothing remembered for this method: still a 503
+    let response = app.clone().oneshot(send("getBalance", 10)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // Stale serving off: 503 even with a remembered result
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.cache.serve_stale_secs = 0;
+        next
+    });
+    let response = app.oneshot(send("getSlot", 11)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_failed_send_transaction_is_dead_lettered() {
+    // Backend fails every request, as during a provider outage
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|| async { (StatusCode::SERVICE_UNAVAILABLE, "overloaded") }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let mut path = std::env::temp_dir();
+    path.push("sol_rpc_router_test_handler_dead_letters.jsonl");
+    let _ = std::fs::remove_file(&path);
+    let store = Arc::new(FileDeadLetterStore::new(&path));
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "trader", 100);
+    keystore.add_key("private-key", "institution", 100);
+    keystore.set_privacy("private-key", LogPrivacy::Metadata);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "overloaded".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["overloaded".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let state = Arc::new(AppState {
+        dead_letters: Some(store.clone()),
+        ..(*state).clone()
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send_as = |key: &str, body: &'static str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let send = |body: &'static str| send_as("test-key", body);
+    let transaction = r#"{"jsonrpc":"2.0","method":"sendTransaction","params":["AQID"],"id":1}"#;
+    // Keys that forbid param logging are never captured
+    let response = app
+        .clone()
+        .oneshot(send_as("private-key", transaction))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        response.extensions().get::<LogPrivacy>(),
+        Some(&LogPrivacy::Metadata)
+    );
+    let response = app.clone().oneshot(send(transaction)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    // Reads are not captured
+    let response = app
+        .oneshot(send(r#"{"jsonrpc":"2.0","method":"getSlot","id":2}"#))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // The store write happens in the background
+    let mut letters = Vec::new();
+    for _ in 0..50 {
+        letters = store.list(10).await.unwrap();
+        if !letters.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].request, transaction);
+    assert_eq!(letters[0].owner, "trader");
+    assert_eq!(letters[0].backend.as_deref(), Some("overloaded"));
+    assert_eq!(letters[0].status, 503);
+}
+
+#[tokio::test]
+async fn test_send_transaction_with_expired_blockhash_is_not_forwarded() {
+    use base64::Engine;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let forwarded = Arc::new(AtomicUsize::new(0));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let backend_forwarded = forwarded.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move || async move {
+                backend_forwarded.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({"jsonrpc": "2.0", "result": "sig", "id": 1}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "trader", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "primary".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["primary".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.blockhash_check.enabled = true;
+        next
+    });
+    let old = bs58::encode([7u8; 32]).into_string();
+    let latest = bs58::encode([8u8; 32]).into_string();
+    state.blockhash_cache.record(&old, 100);
+    state.blockhash_cache.record(&latest, 400);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    // One signature, legacy header, one account key, then the blockhash
+    let send = |blockhash: u8| {
+        let mut tx = vec![1u8];
+        tx.extend([0u8; 64]);
+        tx.extend([1, 0, 0, 1]);
+        tx.extend([9u8; 32]);
+        tx.extend([blockhash; 32]);
+        tx.push(0);
+        let encoded = base64::engine::general_purpose::STANDARD.encode(tx);
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "sendTransaction",
+            "params": [encoded, {"encoding": "base64"}],
+            "id": 5
+        });
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(send(7)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.extensions().get::<SelectedBackend>().unwrap().0,
+        "blockhash-check"
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], -32002);
+    assert_eq!(json["error"]["data"]["err"], "BlockhashNotFound");
+    assert_eq!(json["id"], 5);
+    assert_eq!(forwarded.load(Ordering::SeqCst), 0);
+
+    // A current blockhash goes through
+    let response = app.oneshot(send(8)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(forwarded.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_truncated_response_is_retried_on_another_backend() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Announces 100 bytes, sends a few and hangs up
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broken_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = socket.read(&mut request).await;
+            let _ = socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 100\r\n\r\n{\"jsonrpc\":",
+                )
+                .await;
+        }
+    });
+    let healthy_url = start_mock_backend().await;
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let backends = [("broken", broken_url), ("healthy", healthy_url)]
+        .into_iter()
+        .map(|(label, url)| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url,
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            )
+        })
+        .collect();
+    let health_state = Arc::new(HealthState::new(vec![
+        "broken".to_string(),
+        "healthy".to_string(),
+    ]));
+    let state = make_app_state(client, keystore, backends, health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+    let request = || {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","method":"getBalance","params":["Acc1"],"id":1}"#,
+            ))
+            .unwrap()
+    };
+
+    // Without verification the client gets the broken response
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.method_routes
+            .insert("getBalance".to_string(), "broken".to_string());
+        next
+    });
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.into_body().collect().await.is_err());
+
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.integrity.enabled = true;
+        next
+    });
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-rpc-router-retry"], "integrity");
+    assert_eq!(
+        response.extensions().get::<SelectedBackend>().unwrap().0,
+        "healthy"
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, "{\"jsonrpc\":\"2.0\",\"result\":\"ok\",\"id\":1}");
+
+    // Nowhere else to go: a clean error instead of a partial body
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.backends.retain(|b| b.config.label == "broken");
+        next
+    });
+    let response = app.oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn test_proxy_fails_over_to_other_backends() {
+    // Always overloaded
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let failing_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|| async { (StatusCode::SERVICE_UNAVAILABLE, "overloaded") }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+    // Nothing listening
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let refused_url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let healthy_url = start_mock_backend().await;
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let labels = ["failing", "refused", "healthy"];
+    let backends = labels
+        .into_iter()
+        .zip([failing_url, refused_url, healthy_url])
+        .map(|(label, url)| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url,
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            )
+        })
+        .collect();
+    let health_state = Arc::new(HealthState::new(
+        labels.iter().map(|l| l.to_string()).collect(),
+    ));
+    let state = make_app_state(client, keystore, backends, health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.method_routes
+            .insert("getBalance".to_string(), "failing".to_string());
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+    let request = || {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","method":"getBalance","params":["Acc1"],"id":1}"#,
+            ))
+            .unwrap()
+    };
+
+    // Off by default
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // Past the 503 and the refused connection, whichever comes first
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.retry.max_attempts = 3;
+        next
+    });
+    for _ in 0..5 {
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-rpc-router-retry"], "failover");
+        assert_eq!(
+            response.extensions().get::<SelectedBackend>().unwrap().0,
+            "healthy"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "{\"jsonrpc\":\"2.0\",\"result\":\"ok\",\"id\":1}");
+    }
+
+    // Unlisted statuses are passed on
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.retry.retryable_status = vec![429, 502];
+        next
+    });
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // As is everything while retries are switched off
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.retry.retryable_status = vec![503];
+        next
+    });
+    state.kill_switches.set(Subsystem::Retries, true);
+    let response = app.oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_proxy_sends_non_idempotent_requests_once() {
+    // Always overloaded, counting the requests it gets
+    let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let failing_url = format!("http://{}", listener.local_addr().unwrap());
+    let counter = hits.clone();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move || async move {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                (StatusCode::SERVICE_UNAVAILABLE, "overloaded")
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+    let healthy_url = start_mock_backend().await;
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let labels = ["failing", "healthy"];
+    let backends = labels
+        .into_iter()
+        .zip([failing_url, healthy_url])
+        .map(|(label, url)| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url,
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            )
+        })
+        .collect();
+    let health_state = Arc::new(HealthState::new(
+        labels.iter().map(|l| l.to_string()).collect(),
+    ));
+    let state = make_app_state(client, keystore, backends, health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.retry.max_attempts = 2;
+        for method in ["sendTransaction", "requestAirdrop", "mintCompressedNft"] {
+            next.method_routes
+                .insert(method.to_string(), "failing".to_string());
+        }
+        next.method_idempotency
+            .insert("mintCompressedNft".to_string(), Idempotency::NonIdempotent);
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+    let send = |method: &str| {
+        let req = Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","method":"{}","params":["tx"],"id":1}}"#,
+                method
+            )))
+            .unwrap();
+        app.clone().oneshot(req)
+    };
+
+    // Idempotent: the cluster deduplicates a transaction by its signature
+    let response = send("sendTransaction").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-rpc-router-retry"], "failover");
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    for method in ["requestAirdrop", "mintCompressedNft"] {
+        let response = send(method).await.unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::SERVICE_UNAVAILABLE,
+            "{}",
+            method
+        );
+    }
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_first_byte_timeout_fails_over_early() {
+    async fn start_backend(name: &'static str, delay: Duration) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = Router::new().route(
+                "/",
+                post(move || async move {
+                    tokio::time::sleep(delay).await;
+                    format!("{{\"jsonrpc\":\"2.0\",\"result\":\"{}\",\"id\":1}}", name)
+                }),
+            );
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    }
+    let slow = start_backend("slow", Duration::from_millis(400)).await;
+    let fast = start_backend("fast", Duration::ZERO).await;
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let labels = ["slow", "fast"];
+    let backends = labels
+        .into_iter()
+        .zip([slow, fast])
+        .map(|(label, url)| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url,
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            )
+        })
+        .collect();
+    let health_state = Arc::new(HealthState::new(
+        labels.iter().map(|l| l.to_string()).collect(),
+    ));
+    let state = make_app_state(client, keystore, backends, health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.method_routes
+            .insert("getBalance".to_string(), "slow".to_string());
+        next.retry.max_attempts = 2;
+        next.retry.first_byte_timeout_ms = 50;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+    let request = || {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","method":"getBalance","params":["Acc1"],"id":1}"#,
+            ))
+            .unwrap()
+    };
+    let selected = |response: &axum::response::Response| {
+        response
+            .extensions()
+            .get::<SelectedBackend>()
+            .unwrap()
+            .0
+            .clone()
+    };
+
+    // The slow backend misses its first byte and the next one answers
+    let started = std::time::Instant::now();
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert!(started.elapsed() < Duration::from_millis(300));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-rpc-router-retry"], "failover");
+    assert_eq!(selected(&response), "fast");
+
+    // A backend override switches it off
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.backends[0].config.first_byte_timeout_ms = Some(0);
+        next
+    });
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(selected(&response), "slow");
+}
+
+#[tokio::test]
+async fn test_failing_live_traffic_trips_circuit_breaker() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let failing_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "broken") }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+    let healthy_url = start_mock_backend().await;
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let labels = ["failing", "healthy"];
+    let backends = labels
+        .into_iter()
+        .zip([failing_url, healthy_url])
+        .map(|(label, url)| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url,
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            )
+        })
+        .collect();
+    let health_state = Arc::new(HealthState::new(
+        labels.iter().map(|l| l.to_string()).collect(),
+    ));
+    let state = make_app_state(client, keystore, backends, health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.circuit_breaker.enabled = true;
+        next.circuit_breaker.min_requests = 3;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+    let request = || {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+
+    // The health checks still see both backends as healthy
+    let mut failures = 0;
+    for _ in 0..50 {
+        let response = app.clone().oneshot(request()).await.unwrap();
+        if response.status() == StatusCode::INTERNAL_SERVER_ERROR {
+            failures += 1;
+        }
+    }
+    assert_eq!(failures, 3);
+    let router_state = state.state.load();
+    assert_eq!(
+        router_state.backends[0].circuit.state(unix_now()),
+        CircuitState::Open
+    );
+    assert!(router_state.backends[1].in_rotation());
+}
+
+#[tokio::test]
+async fn test_heavy_reads_are_limited_per_backend() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Backend records how many getProgramAccounts calls it serves at once
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    let (backend_in_flight, backend_peak) = (in_flight.clone(), peak.clone());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| async move {
+                if request["method"] == "getProgramAccounts" {
+                    let now = backend_in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    backend_peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                    backend_in_flight.fetch_sub(1, Ordering::SeqCst);
+                }
+                Json(serde_json::json!({"jsonrpc": "2.0", "result": [], "id": request["id"]}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "scanner", 100);
+    let runtime_backend = RuntimeBackend::new(
+        Backend {
+            label: "shared".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    )
+    .with_heavy_limit(1);
+    let health_state = Arc::new(HealthState::new(vec!["shared".to_string()]));
+    let state = make_app_state(client, keystore, vec![runtime_backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = |method: &str| {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","method":"{}","params":["11111111111111111111111111111111"],"id":1}}"#,
+                method
+            )))
+            .unwrap();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let started = std::time::Instant::now();
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            // Drain the body so the heavy slot is released
+            response.into_body().collect().await.unwrap();
+            (status, started.elapsed())
+        })
+    };
+
+    let scans = [send("getProgramAccounts"), send("getProgramAccounts")];
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // A write is not held up behind the queued heavy read
+    let (status, elapsed) = send("sendTransaction").await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert!(elapsed < Duration::from_millis(250), "{:?}", elapsed);
+
+    for scan in scans {
+        assert_eq!(scan.await.unwrap().0, StatusCode::OK);
+    }
+    assert_eq!(peak.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_single_batches_are_unwrapped_for_routing() {
+    let backend = |slot: u64| async move {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = Router::new().route(
+                "/",
+                post(move |body: String| async move {
+                    let request: serde_json::Value = serde_json::from_str(&body).unwrap();
+                    // Batches reach the backend untouched
+                    let result = match request {
+                        serde_json::Value::Array(_) => serde_json::json!("batch"),
+                        _ => serde_json::json!(slot),
+                    };
+                    Json(serde_json::json!({"jsonrpc": "2.0", "result": result, "id": 1}))
+                }),
+            );
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    };
+    let general = RuntimeBackend::new(
+        Backend {
+            label: "general".to_string(),
+            url: backend(1).await,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let slots = RuntimeBackend::new(
+        Backend {
+            label: "slots".to_string(),
+            url: backend(2).await,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let health_state = Arc::new(HealthState::new(vec![
+        "general".to_string(),
+        "slots".to_string(),
+    ]));
+    let state = make_app_state(client, keystore, vec![general, slots], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.method_routes = [("getSlot".to_string(), "slots".to_string())].into();
+        next.unwrap_single_batches = true;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method))
+        .layer(middleware::from_fn_with_state(state, unwrap_single_batch));
+    let send = |body: &'static str| {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    // Routed by its method, answered as a batch
+    let single = r#" [{"jsonrpc":"2.0","method":"getSlot","id":1}] "#;
+    let response = app.clone().oneshot(send(single)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.extensions().get::<SelectedBackend>().unwrap().0,
+        "slots"
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([{"jsonrpc": "2.0", "result": 2, "id": 1}])
+    );
+
+    // Larger batches are forwarded as they are
+    let pair = r#"[{"jsonrpc":"2.0","method":"getSlot","id":1},{"jsonrpc":"2.0","method":"getSlot","id":2}]"#;
+    let response = app.oneshot(send(pair)).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["result"], "batch");
+}
+
+#[tokio::test]
+async fn test_batches_are_split_and_routed_per_request() {
+    let backend = |label: &'static str| async move {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let app = Router::new().route(
+                "/",
+                post(move |body: String| async move {
+                    let request: serde_json::Value = serde_json::from_str(&body).unwrap();
+                    let id = &request["id"];
+                    Json(serde_json::json!({"jsonrpc": "2.0", "result": label, "id": id}))
+                }),
+            );
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    };
+    let general = RuntimeBackend::new(
+        Backend {
+            label: "general".to_string(),
+            url: backend("general").await,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let slots = RuntimeBackend::new(
+        Backend {
+            label: "slots".to_string(),
+            url: backend("slots").await,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let health_state = Arc::new(HealthState::new(vec![
+        "general".to_string(),
+        "slots".to_string(),
+    ]));
+    let state = make_app_state(client, keystore, vec![general, slots], health_state);
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.method_routes = [
+            ("getSlot".to_string(), "slots".to_string()),
+            ("getBalance".to_string(), "general".to_string()),
+        ]
+        .into();
+        next.split_batches = true;
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method))
+        .layer(middleware::from_fn_with_state(state.clone(), split_batch));
+    let send = |key: &str, body: &'static str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    // Each request goes to its own route; answers keep the batch's order
+    let batch = r#"[{"jsonrpc":"2.0","method":"getBalance","id":"a"},
+        {"jsonrpc":"2.0","method":"getSlot","id":7},
+        {"jsonrpc":"2.0","method":"getBalance","id":3}]"#;
+    let response = app.clone().oneshot(send("test-key", batch)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {"jsonrpc": "2.0", "result": "general", "id": "a"},
+            {"jsonrpc": "2.0", "result": "slots", "id": 7},
+            {"jsonrpc": "2.0", "result": "general", "id": 3},
+        ])
+    );
+
+    // A rejection shared by every request is returned as it is
+    let response = app.clone().oneshot(send("wrong-key", batch)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Batches that are not all requests are forwarded whole
+    let mixed = r#"[{"jsonrpc":"2.0","method":"getSlot","id":1},{"id":2}]"#;
+    let response = app.clone().oneshot(send("test-key", mixed)).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json.is_object());
+
+    // Answers over the buffering limit become errors instead of being held
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.max_buffered_bytes = 16;
+        next
+    });
+    let response = app.oneshot(send("test-key", batch)).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json[1]["id"], 7);
+    assert_eq!(json[1]["error"]["message"], "Response too large");
+    assert_eq!(json[1]["error"]["data"]["status"], 502);
+}
+
+// --- decompress_request middleware tests ---
+
+/// Echoes the extracted method and the body it received, behind both middlewares.
+fn decompressing_app(max_decompressed_bytes: usize) -> Router {
+    let router_state = RouterState {
+        max_decompressed_bytes,
+        ..Default::default()
+    };
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let state = Arc::new(AppState::new(
+        client,
+        Arc::new(MockKeyStore::new()),
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
+    Router::new()
+        .route(
+            "/",
+            post(|req: Request<Body>| async move {
+                let method = req.extensions().get::<RpcMethod>().map(|m| m.0.clone());
+                let encoding = req.headers().get("content-encoding").is_some();
+                let body = req.into_body().collect().await.unwrap().to_bytes();
+                format!(
+                    "{}|{}|{}",
+                    method.unwrap_or_default(),
+                    encoding,
+                    String::from_utf8_lossy(&body)
+                )
+            }),
+        )
+        .layer(middleware::from_fn(extract_rpc_method))
+        .layer(middleware::from_fn_with_state(state, decompress_request))
+}
+
+fn compressed_request(encoding: &str, body: Vec<u8>) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri("/")
+        .header("content-type", "application/json")
+        .header("content-encoding", encoding)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[tokio::test]
+async fn test_decompress_request_before_method_extraction() {
+    let body = br#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#;
+    for req in [
+        compressed_request("gzip", gzip(body)),
+        compressed_request("zstd", zstd::encode_all(&body[..], 0).unwrap()),
+    ] {
+        let response = decompressing_app(1024).oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let echoed = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            String::from_utf8(echoed.to_vec()).unwrap(),
+            format!("getSlot|false|{}", String::from_utf8_lossy(body))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_decompress_request_rejections() {
+    let oversized = gzip(&vec![b' '; 4096]);
+    let response = decompressing_app(1024)
+        .oneshot(compressed_request("gzip", oversized))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let response = decompressing_app(1024)
+        .oneshot(compressed_request("gzip", b"not gzip".to_vec()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = decompressing_app(1024)
+        .oneshot(compressed_request("br", b"{}".to_vec()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn test_proxy_offloads_large_responses_on_request() {
+    let block = format!(
+        r#"{{"jsonrpc":"2.0","result":{{"blockhash":"{}"}},"id":1}}"#,
+        "x".repeat(4096)
+    );
+    let backend_url = {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let block = block.clone();
+        tokio::spawn(async move {
+            let app = Router::new().route("/", post(move || async move { block }));
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    };
+
+    // Object store that keeps every PUT
+    let stored: Arc<std::sync::Mutex<Vec<(String, String)>>> = Arc::default();
+    let store_url = {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let stored = stored.clone();
+        tokio::spawn(async move {
+            let app = Router::new().route(
+                "/*key",
+                axum::routing::put(move |req: Request<Body>| async move {
+                    let path = req.uri().path().to_string();
+                    assert!(req.headers().contains_key("authorization"));
+                    let body = req.into_body().collect().await.unwrap().to_bytes();
+                    let body = String::from_utf8(body.to_vec()).unwrap();
+                    stored.lock().unwrap().push((path, body));
+                }),
+            );
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    };
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let backend = Backend {
+        label: "archive".to_string(),
+        url: backend_url,
+        weight: 1,
+        ..Default::default()
+    };
+    let health_state = Arc::new(HealthState::new(vec!["archive".to_string()]));
+    let state = make_app_state(
+        client,
+        keystore,
+        vec![RuntimeBackend::new(backend, true)],
+        health_state,
+    );
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.offload = OffloadConfig {
+            enabled: true,
+            endpoint: store_url.clone(),
+            bucket: "responses".to_string(),
+            access_key_id: "test".to_string(),
+            secret_access_key: Some("secret".to_string()),
+            min_bytes: 1024,
+            ..Default::default()
+        };
+        next
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+    let request = |offload: bool| {
+        let mut builder = Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header("content-type", "application/json");
+        if offload {
+            builder = builder.header("x-rpc-router-offload", "1");
+        }
+        builder
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","method":"getBlock","id":1}"#,
+            ))
+            .unwrap()
+    };
+
+    // Without the header the response is streamed as usual
+    let response = app.clone().oneshot(request(false)).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, block.as_bytes());
+    assert!(stored.lock().unwrap().is_empty());
+
+    let response = app.oneshot(request(true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-rpc-router-offloaded"], "true");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let answer: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(answer["size_bytes"], block.len());
+
+    let stored = stored.lock().unwrap();
+    assert_eq!(stored.len(), 1);
+    let (path, object) = &stored[0];
+    assert!(path.starts_with("/responses/rpc-responses/"), "{}", path);
+    assert_eq!(object, &block);
+    let url = answer["url"].as_str().unwrap();
+    assert!(
+        url.starts_with(&format!("{}{}?", store_url, path)),
+        "{}",
+        url
+    );
+    assert!(url.contains("X-Amz-Signature="));
+}
+
+#[tokio::test]
+async fn test_track_usage_records_key_usage() {
+    let backend_url = start_mock_backend().await;
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    keystore.add_key("private-key", "tester", 100);
+    keystore.set_privacy("private-key", LogPrivacy::None);
+    let backend = Backend {
+        label: "mock-backend".to_string(),
+        url: backend_url,
+        ..Default::default()
+    };
+    let health_state = Arc::new(HealthState::new(vec!["mock-backend".to_string()]));
+    let router_state = RouterState {
+        backends: vec![RuntimeBackend::new(backend, true)],
+        health_state,
+        proxy_timeout_secs: 5,
+        ..Default::default()
+    };
+    let state = Arc::new(AppState {
+        key_usage: Some(Arc::new(MockKeyUsageStore::new())),
+        ..AppState::new(
+            client,
+            keystore,
+            Arc::new(ArcSwap::from_pointee(router_state)),
+        )
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn_with_state(state.clone(), track_usage))
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send_as = |key: &str, method: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","method":"{}","id":1}}"#,
+                method
+            )))
+            .unwrap()
+    };
+    let response = app
+        .clone()
+        .oneshot(send_as("test-key", "getSlot"))
+        .await
+        .unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let response = app
+        .clone()
+        .oneshot(send_as("test-key", "madeUpMethod"))
+        .await
+        .unwrap();
+    response.into_body().collect().await.unwrap();
+    let response = app
+        .oneshot(send_as("private-key", "getSlot"))
+        .await
+        .unwrap();
+    response.into_body().collect().await.unwrap();
+
+    let mut entries = state.key_usage_buffer.take();
+    entries.sort_by(|a, b| a.method.cmp(&b.method));
+    let recorded: Vec<_> = entries
+        .iter()
+        .map(|e| (&*e.key, &*e.method, e.requests, e.response_bytes))
+        .collect();
+    assert_eq!(
+        recorded,
+        vec![
+            ("test-key", "getSlot", 1, body.len() as u64),
+            ("test-key", "other", 1, body.len() as u64),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_proxy_throttles_keys_at_spend_cap() {
+    let backend_url = start_mock_backend().await;
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("prepaid-key", "prepaid", 100);
+    keystore.set_spend_cap("prepaid-key", Some(2.0));
+    let backend = Backend {
+        label: "mock-backend".to_string(),
+        url: backend_url,
+        ..Default::default()
+    };
+    let health_state = Arc::new(HealthState::new(vec!["mock-backend".to_string()]));
+    let router_state = RouterState {
+        backends: vec![RuntimeBackend::new(backend, true)],
+        health_state,
+        proxy_timeout_secs: 5,
+        ..Default::default()
+    };
+    let config = SpendingCapsConfig {
+        enabled: true,
+        default_price: 1.0,
+        throttled_rate_limit: 1,
+        ..Default::default()
+    };
+    let tracker = Arc::new(SpendTracker::new(config, Arc::new(MockSpendStore::new())));
+    let state = Arc::new(AppState {
+        spending: Some(tracker.clone()),
+        ..AppState::new(
+            client,
+            keystore.clone(),
+            Arc::new(ArcSwap::from_pointee(router_state)),
+        )
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state)
+        .layer(middleware::from_fn(extract_rpc_method));
+
+    let send = || {
+        Request::builder()
+            .method("POST")
+            .uri("/?api-key=prepaid-key")
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+    // Two requests reach the cap, then one per second gets through
+    for _ in 0..3 {
+        let response = app.clone().oneshot(send()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = app.clone().oneshot(send()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(tracker.spent("prepaid-key"), Some(3.0));
+
+    // A raised cap lifts the throttle; batches are charged for each request
+    keystore.set_spend_cap("prepaid-key", Some(100.0));
+    let batch = r#"[{"jsonrpc":"2.0","method":"getBlock","id":1},{"jsonrpc":"2.0","method":"getSlot","id":2}]"#;
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/?api-key=prepaid-key")
+                .body(Body::from(batch))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(tracker.spent("prepaid-key"), Some(9.0));
+}
+
+#[tokio::test]
+async fn test_proxy_sheds_requests_over_concurrency_limits() {
+    let backend_url = start_mock_backend().await;
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("limited-key", "limited", 100);
+    keystore.set_max_in_flight("limited-key", 1);
+    keystore.add_key("other-key", "other", 100);
+    let backend = RuntimeBackend::new(
+        Backend {
+            label: "mock-backend".to_string(),
+            url: backend_url,
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+    let health_state = Arc::new(HealthState::new(vec!["mock-backend".to_string()]));
+    let state = make_app_state(client, keystore, vec![backend], health_state);
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn(extract_rpc_method));
+    let request = |key: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/?api-key={}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","method":"getSlot","params":[],"id":7}"#,
+            ))
+            .unwrap()
+    };
+
+    // The first response holds the key's only slot until its body is sent
+    let first = app.clone().oneshot(request("limited-key")).await.unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(state.concurrency.key_in_flight("limited-key"), 1);
+
+    let shed = app.clone().oneshot(request("limited-key")).await.unwrap();
+    assert_eq!(shed.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(shed.headers()["retry-after"], "1");
+    let body: serde_json::Value =
+        serde_json::from_slice(&shed.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(body["id"], 7);
+    assert_eq!(body["error"]["code"], -32050);
+    assert_eq!(
+        body["error"]["message"],
+        "Too many concurrent requests for this API key"
+    );
+
+    // Other keys are only held to the global limit
+    let other = app.clone().oneshot(request("other-key")).await.unwrap();
+    assert_eq!(other.status(), StatusCode::OK);
+    drop(other);
+
+    first.into_body().collect().await.unwrap();
+    assert_eq!(state.concurrency.key_in_flight("limited-key"), 0);
+    let again = app.clone().oneshot(request("limited-key")).await.unwrap();
+    assert_eq!(again.status(), StatusCode::OK);
+    drop(again);
+
+    // The global limit applies across keys
+    state.state.rcu(|current| {
+        let mut next = (**current).clone();
+        next.max_in_flight = 1;
+        next
+    });
+    let held = app.clone().oneshot(request("other-key")).await.unwrap();
+    let shed = app.clone().oneshot(request("limited-key")).await.unwrap();
+    assert_eq!(shed.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body: serde_json::Value =
+        serde_json::from_slice(&shed.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(
+        body["error"]["message"],
+        "Router is at its concurrency limit"
+    );
+    assert_eq!(state.concurrency.in_flight(), 1);
+    drop(held);
+    assert_eq!(state.concurrency.in_flight(), 0);
+}
+
+#[tokio::test]
+async fn test_proxy_traces_requests_and_propagates_traceparent() {
+    // Backend answers with the traceparent it received
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|headers: axum::http::HeaderMap| async move {
+                let traceparent = headers[TRACEPARENT].to_str().unwrap().to_string();
+                Json(serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": traceparent}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let backend = Backend {
+        label: "traced".to_string(),
+        url: backend_url,
+        ..Default::default()
+    };
+    let health_state = Arc::new(HealthState::new(vec!["traced".to_string()]));
+    let router_state = RouterState {
+        backends: vec![RuntimeBackend::new(backend, true)],
+        health_state,
+        proxy_timeout_secs: 5,
+        ..Default::default()
+    };
+    let tracer = Arc::new(Tracer::new(&TracingConfig {
+        enabled: true,
+        ..Default::default()
+    }));
+    let state = Arc::new(AppState {
+        tracer: Some(tracer.clone()),
+        ..AppState::new(
+            client,
+            keystore,
+            Arc::new(ArcSwap::from_pointee(router_state)),
+        )
+    });
+    let app = Router::new()
+        .route("/", post(proxy))
+        .with_state(state.clone())
+        .layer(middleware::from_fn_with_state(state, trace_requests))
+        .layer(middleware::from_fn(extract_rpc_method))
+        .layer(middleware::from_fn(assign_request_id));
+    let send = |traceparent: Option<&str>| {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/?api-key=test-key")
+            .header(REQUEST_ID_HEADER, "req-1");
+        if let Some(traceparent) = traceparent {
+            request = request.header(TRACEPARENT, traceparent);
+        }
+        request
+            .body(Body::from(r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#))
+            .unwrap()
+    };
+    let seen_upstream = |response: axum::response::Response| async move {
+        let json: serde_json::Value =
+            serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes())
+                .unwrap();
+        SpanContext::from_traceparent(json["result"].as_str().unwrap()).unwrap()
+    };
+
+    // A new trace: the backend sees the router's span
+    let response = app.clone().oneshot(send(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let upstream = seen_upstream(response).await;
+    assert!(upstream.sampled);
+    let spans = tracer.drain();
+    assert_eq!(spans.len(), 1);
+    let span = &spans[0];
+    assert_eq!(span.context, upstream);
+    assert_eq!(span.name, "getSlot");
+    assert_eq!(span.parent_span_id, None);
+    assert_eq!(span.status, 200);
+    assert!(span.end_nanos >= span.start_nanos);
+    let attribute = |key: &str| {
+        span.attributes
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    assert_eq!(attribute("rpc.method"), Some("getSlot"));
+    assert_eq!(attribute("backend"), Some("traced"));
+    assert_eq!(attribute("owner"), Some("tester"));
+    assert_eq!(attribute("request_id"), Some("req-1"));
+
+    // A client's trace is joined, with the client's span as parent
+    let joined = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let client_context = SpanContext::from_traceparent(joined).unwrap();
+    let response = app.clone().oneshot(send(Some(joined))).await.unwrap();
+    let upstream = seen_upstream(response).await;
+    assert_eq!(upstream.trace_id, client_context.trace_id);
+    assert_ne!(upstream.span_id, client_context.span_id);
+    let spans = tracer.drain();
+    assert_eq!(spans[0].context, upstream);
+    assert_eq!(spans[0].parent_span_id, Some(client_context.span_id));
+
+    // Traces the client did not sample are propagated but not recorded
+    let unsampled = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00";
+    let response = app.clone().oneshot(send(Some(unsampled))).await.unwrap();
+    let upstream = seen_upstream(response).await;
+    assert!(!upstream.sampled);
+    assert_eq!(upstream.trace_id, client_context.trace_id);
+    assert!(tracer.drain().is_empty());
+}
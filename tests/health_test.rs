@@ -0,0 +1,306 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    routing::{get, post},
+    Json, Router,
+};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use serde_json::json;
+use sol_rpc_router::{
+    config::{Backend, HealthCheckConfig, ReferenceKind, ReferenceSource},
+    health::{
+        fetch_reference_slot, next_check_interval, record_check, record_passive, record_request,
+        slot_lag, BackendHealthStatus, HealthLevel, HealthState,
+    },
+    state::RuntimeBackend,
+};
+
+fn config() -> HealthCheckConfig {
+    HealthCheckConfig {
+        consecutive_failures_threshold: 3,
+        consecutive_successes_threshold: 2,
+        max_slot_lag: 50,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_failures_degrade_then_mark_unhealthy() {
+    let config = config();
+    let mut status = BackendHealthStatus::default();
+    let failure: Result<Option<u64>, String> = Err("timeout".to_string());
+
+    record_check(&mut status, &failure, None, &config);
+    assert_eq!(status.level(), HealthLevel::Degraded);
+    record_check(&mut status, &failure, None, &config);
+    assert_eq!(status.level(), HealthLevel::Degraded);
+    record_check(&mut status, &failure, None, &config);
+    assert_eq!(status.level(), HealthLevel::Unhealthy);
+
+    // Recovery requires the success threshold
+    record_check(&mut status, &Ok(Some(1000)), Some(1000), &config);
+    assert_eq!(status.level(), HealthLevel::Unhealthy);
+    record_check(&mut status, &Ok(Some(1000)), Some(1000), &config);
+    assert_eq!(status.level(), HealthLevel::Healthy);
+    assert!(status.last_error.is_none());
+}
+
+#[test]
+fn test_moderate_lag_is_degraded_not_failure() {
+    let config = config();
+    let mut status = BackendHealthStatus::default();
+
+    // 80 behind: over max_slot_lag (50) but within 2x
+    for _ in 0..5 {
+        record_check(&mut status, &Ok(Some(920)), Some(1000), &config);
+    }
+    assert_eq!(status.level(), HealthLevel::Degraded);
+    assert_eq!(status.consecutive_failures, 0);
+
+    // Caught up again
+    record_check(&mut status, &Ok(Some(1000)), Some(1000), &config);
+    assert_eq!(status.level(), HealthLevel::Healthy);
+}
+
+#[test]
+fn test_severe_lag_counts_as_failure() {
+    let config = config();
+    let mut status = BackendHealthStatus::default();
+
+    // 150 behind: beyond 2x max_slot_lag
+    for _ in 0..3 {
+        record_check(&mut status, &Ok(Some(850)), Some(1000), &config);
+    }
+    assert_eq!(status.level(), HealthLevel::Unhealthy);
+    assert!(status.last_error.unwrap().contains("150 slots behind"));
+}
+
+#[test]
+fn test_failed_requests_eject_until_checks_pass() {
+    let config = config();
+    let mut status = BackendHealthStatus::default();
+
+    // A success resets the count
+    assert!(!record_request(&mut status, false, 3));
+    assert!(!record_request(&mut status, false, 3));
+    assert!(!record_request(&mut status, true, 3));
+    assert_eq!(status.consecutive_request_failures, 0);
+    assert_eq!(status.level(), HealthLevel::Healthy);
+
+    assert!(!record_request(&mut status, false, 3));
+    assert!(!record_request(&mut status, false, 3));
+    assert!(record_request(&mut status, false, 3));
+    assert_eq!(status.level(), HealthLevel::Unhealthy);
+    assert!(status
+        .last_error
+        .as_deref()
+        .unwrap()
+        .contains("proxied requests"));
+    assert!(status.next_check_time.is_none());
+    // Already out of rotation: nothing more to count
+    assert!(!record_request(&mut status, false, 3));
+
+    // Checks bring it back, requests do not
+    assert!(!record_request(&mut status, true, 3));
+    assert_eq!(status.level(), HealthLevel::Unhealthy);
+    record_check(&mut status, &Ok(Some(1000)), Some(1000), &config);
+    record_check(&mut status, &Ok(Some(1000)), Some(1000), &config);
+    assert_eq!(status.level(), HealthLevel::Healthy);
+}
+
+#[test]
+fn test_record_passive_takes_backend_out_of_rotation() {
+    let state = HealthState::new(vec!["a".to_string()]);
+    let backend = RuntimeBackend::new(
+        Backend {
+            label: "a".to_string(),
+            url: "http://localhost:9000".to_string(),
+            weight: 1,
+            ..Default::default()
+        },
+        true,
+    );
+
+    // Off unless a threshold is set
+    for _ in 0..5 {
+        record_passive(&state, &backend, false, 0);
+    }
+    assert!(backend.in_rotation());
+    assert_eq!(
+        state.get_status("a").unwrap().consecutive_request_failures,
+        0
+    );
+
+    record_passive(&state, &backend, false, 2);
+    assert!(backend.in_rotation());
+    assert_eq!(
+        state.get_status("a").unwrap().consecutive_request_failures,
+        1
+    );
+    record_passive(&state, &backend, false, 2);
+    assert!(!backend.in_rotation());
+    assert_eq!(
+        state.get_status("a").unwrap().level(),
+        HealthLevel::Unhealthy
+    );
+
+    // Unknown backends are ignored
+    assert!(!state.record_request("b", false, 1));
+}
+
+#[test]
+fn test_slot_lag() {
+    assert_eq!(slot_lag(&Ok(Some(950)), Some(1000)), Some(50));
+    // The freshest backend sets the tip
+    assert_eq!(slot_lag(&Ok(Some(1000)), Some(1000)), Some(0));
+    // Nothing to report without both a slot and a tip
+    assert_eq!(slot_lag(&Ok(None), Some(1000)), None);
+    assert_eq!(slot_lag(&Ok(Some(950)), None), None);
+    assert_eq!(slot_lag(&Err("timed out".to_string()), Some(1000)), None);
+}
+
+#[test]
+fn test_adaptive_check_interval() {
+    let fixed = config();
+    let adaptive = HealthCheckConfig {
+        adaptive: true,
+        interval_secs: 30,
+        min_interval_secs: 5,
+        max_interval_secs: 300,
+        ..config()
+    };
+    let mut status = BackendHealthStatus::default();
+    assert_eq!(
+        next_check_interval(&status, &fixed),
+        Duration::from_secs(30)
+    );
+    assert_eq!(
+        next_check_interval(&status, &adaptive),
+        Duration::from_secs(30)
+    );
+
+    // A failing backend is probed at the minimum interval
+    record_check(&mut status, &Err("timeout".to_string()), None, &adaptive);
+    assert_eq!(
+        next_check_interval(&status, &adaptive),
+        Duration::from_secs(5)
+    );
+    assert_eq!(
+        next_check_interval(&status, &fixed),
+        Duration::from_secs(30)
+    );
+
+    // Stable backends back off, doubling every 10 clean checks up to the maximum
+    for _ in 0..10 {
+        record_check(&mut status, &Ok(Some(1000)), Some(1000), &adaptive);
+    }
+    assert_eq!(status.last_slot, Some(1000));
+    assert_eq!(
+        next_check_interval(&status, &adaptive),
+        Duration::from_secs(60)
+    );
+    for _ in 0..50 {
+        record_check(&mut status, &Ok(Some(1000)), Some(1000), &adaptive);
+    }
+    assert_eq!(
+        next_check_interval(&status, &adaptive),
+        Duration::from_secs(300)
+    );
+
+    // Lag makes it degraded again: back to the minimum
+    record_check(&mut status, &Ok(Some(920)), Some(1000), &adaptive);
+    assert_eq!(
+        next_check_interval(&status, &adaptive),
+        Duration::from_secs(5)
+    );
+}
+
+#[tokio::test]
+async fn test_fetch_reference_slot_rpc_and_http() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new()
+            .route(
+                "/",
+                post(|| async { Json(json!({"jsonrpc": "2.0", "result": 1234, "id": 1})) }),
+            )
+            .route(
+                "/api/status",
+                get(|| async { Json(json!({"data": {"absoluteSlot": "5678"}})) }),
+            )
+            .route(
+                "/api/huge",
+                get(|| async { Json(json!({"data": {"slot": 1, "pad": "x".repeat(100_000)}})) }),
+            );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let config = config();
+    let source = |kind, path: &str, pointer: Option<&str>| ReferenceSource {
+        label: "oracle".to_string(),
+        url: format!("{}{}", base, path),
+        kind,
+        json_pointer: pointer.map(str::to_string),
+    };
+
+    let rpc = source(ReferenceKind::Rpc, "/", None);
+    assert_eq!(fetch_reference_slot(&client, &rpc, &config).await, Ok(1234));
+
+    let http = source(
+        ReferenceKind::Http,
+        "/api/status",
+        Some("/data/absoluteSlot"),
+    );
+    assert_eq!(
+        fetch_reference_slot(&client, &http, &config).await,
+        Ok(5678)
+    );
+
+    let missing = source(ReferenceKind::Http, "/api/status", Some("/data/slot"));
+    assert!(fetch_reference_slot(&client, &missing, &config)
+        .await
+        .is_err());
+
+    // Oversized responses are not read in full
+    let huge = source(ReferenceKind::Http, "/api/huge", Some("/data/slot"));
+    assert!(fetch_reference_slot(&client, &huge, &config)
+        .await
+        .unwrap_err()
+        .contains("Failed to read response body"));
+}
+
+#[test]
+fn test_health_state_concurrent_updates() {
+    let labels: Vec<String> = (0..8).map(|i| format!("b{}", i)).collect();
+    let state = Arc::new(HealthState::new(vec!["b0".to_string()]));
+
+    // Writers on different labels never lose each other's updates, and every
+    // caller gets the same circuit breaker for a label
+    let threads: Vec<_> = labels
+        .iter()
+        .cloned()
+        .map(|label| {
+            let state = state.clone();
+            std::thread::spawn(move || {
+                for failures in 1..=50 {
+                    let status = BackendHealthStatus {
+                        consecutive_failures: failures,
+                        ..Default::default()
+                    };
+                    state.update_status(&label, status);
+                }
+                state.circuit("shared")
+            })
+        })
+        .collect();
+    let circuits: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+    let statuses = state.get_all_statuses();
+    assert_eq!(statuses.len(), labels.len());
+    assert!(statuses.values().all(|s| s.consecutive_failures == 50));
+    let shared = state.circuit("shared");
+    assert!(circuits.iter().all(|c| Arc::ptr_eq(c, &shared)));
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use sol_rpc_router::heatmap::{LatencyHeatmap, BUCKET_BOUNDS_MS};
+
+const WINDOW: u64 = 60;
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[test]
+fn test_heatmap_rows_per_window() {
+    let heatmap = LatencyHeatmap::new();
+    let now = 1_000 * WINDOW;
+    heatmap.record("getSlot", "b", ms(3), now - WINDOW, WINDOW, 10);
+    heatmap.record("getSlot", "a", ms(3), now + 5, WINDOW, 10);
+    heatmap.record("getSlot", "a", ms(40_000), now + 6, WINDOW, 10);
+    heatmap.record("getBlock", "a", ms(700), now + 7, WINDOW, 10);
+    // Unknown methods are not kept
+    heatmap.record("getEverything", "a", ms(1), now, WINDOW, 10);
+
+    let map = heatmap.heatmap("getSlot", 3, now + 10, WINDOW);
+    assert_eq!(map.from, now - 2 * WINDOW);
+    assert_eq!(map.to, now + WINDOW);
+    assert_eq!(map.windows, [now - 2 * WINDOW, now - WINDOW, now]);
+    assert_eq!(map.bounds_ms.len(), BUCKET_BOUNDS_MS.len() + 1);
+    assert_eq!(map.bounds_ms[2], Some(5));
+    assert_eq!(map.bounds_ms.last(), Some(&None));
+
+    let labels: Vec<&str> = map.backends.iter().map(|b| b.backend.as_str()).collect();
+    assert_eq!(labels, ["a", "b"]);
+    let a = &map.backends[0];
+    assert_eq!(a.requests, 2);
+    assert_eq!(a.counts.len(), 3);
+    assert!(a.counts[0].iter().all(|count| *count == 0));
+    assert_eq!(a.counts[2][2], 1);
+    assert_eq!(a.counts[2][BUCKET_BOUNDS_MS.len()], 1);
+    assert_eq!(map.backends[1].counts[1][2], 1);
+
+    assert!(heatmap
+        .heatmap("getEverything", 3, now, WINDOW)
+        .backends
+        .is_empty());
+}
+
+#[test]
+fn test_heatmap_drops_old_windows() {
+    let heatmap = LatencyHeatmap::new();
+    heatmap.record("getSlot", "a", ms(3), 10 * WINDOW, WINDOW, 2);
+    heatmap.record("getSlot", "a", ms(3), 11 * WINDOW, WINDOW, 2);
+    heatmap.record("getSlot", "a", ms(3), 12 * WINDOW, WINDOW, 2);
+    assert_eq!(
+        heatmap.heatmap("getSlot", 5, 12 * WINDOW, WINDOW).backends[0].requests,
+        2
+    );
+
+    // A new window width starts over
+    heatmap.record("getSlot", "a", ms(3), 12 * WINDOW, 30, 2);
+    assert_eq!(
+        heatmap.heatmap("getSlot", 2, 12 * WINDOW, 30).backends[0].requests,
+        1
+    );
+    assert!(heatmap
+        .heatmap("getSlot", 5, 12 * WINDOW, WINDOW)
+        .backends
+        .is_empty());
+}
@@ -0,0 +1,32 @@
+use sol_rpc_router::hedging::HedgeBudget;
+
+#[test]
+fn test_budget_limits_hedge_share() {
+    let budget = HedgeBudget::new();
+    let hedged = (0..100).filter(|_| budget.try_acquire(10)).count();
+    assert_eq!(hedged, 10);
+
+    let budget = HedgeBudget::new();
+    assert!((0..100).all(|_| budget.try_acquire(100)));
+
+    let budget = HedgeBudget::new();
+    assert!(!(0..100).any(|_| budget.try_acquire(0)));
+}
+
+#[test]
+fn test_budget_saved_for_delayed_hedges() {
+    let budget = HedgeBudget::new();
+    assert!(!budget.try_spend());
+    for _ in 0..25 {
+        budget.earn(10);
+    }
+    assert!(budget.try_spend());
+    assert!(budget.try_spend());
+    assert!(!budget.try_spend());
+
+    // Savings stop at ten hedges
+    for _ in 0..1_000 {
+        budget.earn(100);
+    }
+    assert_eq!((0..20).filter(|_| budget.try_spend()).count(), 10);
+}
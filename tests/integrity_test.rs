@@ -0,0 +1,128 @@
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue},
+};
+use base64::Engine;
+use bytes::Bytes;
+use futures_util::stream;
+use http_body_util::BodyExt;
+use sha2::{Digest, Sha256};
+use sol_rpc_router::{
+    config::IntegrityConfig,
+    integrity::{buffer, expected_digest, verify, Buffered, IntegrityFailure},
+};
+
+const BODY: &[u8] = br#"{"jsonrpc":"2.0","result":{"value":42},"id":1}"#;
+
+fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+fn sha256_b64(body: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(Sha256::digest(body))
+}
+
+#[test]
+fn test_expected_digest_header_forms() {
+    let digest = Sha256::digest(BODY).to_vec();
+    for (name, value) in [
+        ("content-digest", format!("sha-256=:{}:", sha256_b64(BODY))),
+        (
+            "repr-digest",
+            format!("sha-512=:AAAA:, sha-256=:{}:", sha256_b64(BODY)),
+        ),
+        ("digest", format!("SHA-256={}", sha256_b64(BODY))),
+    ] {
+        assert_eq!(
+            expected_digest(&headers(&[(name, value)])),
+            Some(digest.clone()),
+            "{}",
+            name
+        );
+    }
+    // Only other algorithms: nothing to check against
+    assert_eq!(
+        expected_digest(&headers(&[("digest", "MD5=AAAA".to_string())])),
+        None
+    );
+    assert_eq!(expected_digest(&HeaderMap::new()), None);
+}
+
+#[test]
+fn test_verify() {
+    let config = IntegrityConfig::default();
+    let length = BODY.len().to_string();
+
+    assert_eq!(
+        verify(
+            &headers(&[("content-length", length.clone())]),
+            BODY,
+            &config
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        verify(
+            &headers(&[("content-length", "1000".to_string())]),
+            BODY,
+            &config
+        ),
+        Err(IntegrityFailure::LengthMismatch)
+    );
+
+    let signed = headers(&[
+        ("content-length", length),
+        ("repr-digest", format!("sha-256=:{}:", sha256_b64(b"other"))),
+    ]);
+    assert_eq!(
+        verify(&signed, BODY, &config),
+        Err(IntegrityFailure::DigestMismatch)
+    );
+    let unchecked = IntegrityConfig {
+        verify_digest: false,
+        ..Default::default()
+    };
+    assert_eq!(verify(&signed, BODY, &unchecked), Ok(()));
+
+    // Cut short without a Content-Length: only the JSON check notices
+    let cut = &BODY[..20];
+    assert_eq!(
+        verify(&HeaderMap::new(), cut, &config),
+        Err(IntegrityFailure::InvalidJson)
+    );
+    let compressed = headers(&[("content-encoding", "gzip".to_string())]);
+    assert_eq!(verify(&compressed, cut, &config), Ok(()));
+}
+
+#[tokio::test]
+async fn test_buffer() {
+    match buffer(Body::from(BODY), 1024).await {
+        Ok(Buffered::Complete(body)) => assert_eq!(body, BODY),
+        _ => panic!("expected a complete body"),
+    }
+
+    // Past the limit, the whole body is still passed on
+    let chunks = stream::iter(
+        BODY.chunks(8)
+            .map(|c| Ok::<_, std::io::Error>(Bytes::from(c))),
+    );
+    match buffer(Body::from_stream(chunks), 16).await {
+        Ok(Buffered::TooLarge(body)) => {
+            assert_eq!(body.collect().await.unwrap().to_bytes(), BODY)
+        }
+        _ => panic!("expected an oversized body"),
+    }
+
+    let failing = stream::iter([
+        Ok(Bytes::from_static(b"{\"jsonrpc\"")),
+        Err(std::io::Error::other("connection reset")),
+    ]);
+    assert!(matches!(
+        buffer(Body::from_stream(failing), 1024).await,
+        Err(IntegrityFailure::Truncated)
+    ));
+}
@@ -0,0 +1,95 @@
+use sol_rpc_router::{
+    journal::{FileJournalStore, JournalEntry, JournalStatus, JournalStore},
+    signing::unix_now,
+};
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    let mut path = std::env::temp_dir();
+    path.push(format!("sol_rpc_router_test_journal_{}.jsonl", name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn entry(signature: &str, journaled_at: u64) -> JournalEntry {
+    JournalEntry::new(
+        signature.to_string(),
+        "key-fingerprint".to_string(),
+        "trader".to_string(),
+        journaled_at,
+        Some(format!(
+            r#"{{"jsonrpc":"2.0","method":"sendTransaction","params":["{}"],"id":1}}"#,
+            signature
+        )),
+    )
+}
+
+#[test]
+fn test_record_outcomes() {
+    let mut entry = entry("sig", 1_700_000_000);
+    assert_eq!(entry.status, JournalStatus::Pending);
+
+    entry.record(None, 502, b"", 1_700_000_001, false);
+    assert_eq!(entry.status, JournalStatus::Failed);
+    assert_eq!(entry.outcomes[0].result, "error");
+
+    let rejected =
+        br#"{"jsonrpc":"2.0","error":{"code":-32002,"message":"Blockhash not found"},"id":1}"#;
+    entry.record(Some("primary"), 200, rejected, 1_700_000_002, false);
+    assert_eq!(entry.status, JournalStatus::Rejected);
+
+    let accepted = br#"{"jsonrpc":"2.0","result":"sig","id":1}"#;
+    entry.record(Some("backup"), 200, accepted, 1_700_000_003, true);
+    assert_eq!(entry.status, JournalStatus::Accepted);
+    assert_eq!(entry.outcomes[2].backend.as_deref(), Some("backup"));
+    assert!(entry.outcomes[2].resumed);
+
+    // Once accepted, later answers do not change the verdict
+    entry.record(Some("primary"), 500, b"", 1_700_000_004, false);
+    assert_eq!(entry.status, JournalStatus::Accepted);
+    assert_eq!(entry.outcomes.len(), 4);
+}
+
+#[tokio::test]
+async fn test_file_store_keeps_latest_version() {
+    let store = FileJournalStore::new(temp_path("latest"), 3_600);
+    let now = unix_now();
+    assert!(store.get("a").await.unwrap().is_none());
+
+    let mut a = entry("a", now);
+    store.put(&a).await.unwrap();
+    store.put(&entry("b", now)).await.unwrap();
+    a.record(
+        Some("primary"),
+        200,
+        br#"{"jsonrpc":"2.0","result":"a","id":1}"#,
+        now,
+        false,
+    );
+    store.put(&a).await.unwrap();
+
+    let fetched = store.get("a").await.unwrap().unwrap();
+    assert_eq!(fetched, a);
+    assert_eq!(fetched.status, JournalStatus::Accepted);
+
+    // Only b never got an outcome
+    let pending = store.pending(now - 60).await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].signature, "b");
+}
+
+#[tokio::test]
+async fn test_file_store_pending_window_and_retention() {
+    let store = FileJournalStore::new(temp_path("window"), 3_600);
+    let now = unix_now();
+    store.put(&entry("recent", now - 10)).await.unwrap();
+    store.put(&entry("stale", now - 600)).await.unwrap();
+    store.put(&entry("expired", now - 7_200)).await.unwrap();
+
+    let pending = store.pending(now - 90).await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].signature, "recent");
+
+    // Past the resume window but still retained
+    assert!(store.get("stale").await.unwrap().is_some());
+    assert!(store.get("expired").await.unwrap().is_none());
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use sol_rpc_router::{
+    key_usage::{entries_from_fields, usage_method, KeyUsageBuffer, KeyUsageEntry, KeyUsageStore},
+    ledger::day_string,
+    mock::MockKeyUsageStore,
+};
+
+fn entry(key: &str, day: u64, method: &str, requests: u64, bytes: u64) -> KeyUsageEntry {
+    KeyUsageEntry {
+        key: key.to_string(),
+        day: day_string(day),
+        method: method.to_string(),
+        requests,
+        response_bytes: bytes,
+    }
+}
+
+#[test]
+fn test_usage_method() {
+    assert_eq!(usage_method(Some("getSlot")), "getSlot");
+    assert_eq!(usage_method(Some("madeUpMethod")), "other");
+    assert_eq!(usage_method(None), "other");
+}
+
+#[test]
+fn test_key_usage_buffer_aggregates_per_key_day_and_method() {
+    let buffer = KeyUsageBuffer::new();
+    let day = 19_884;
+    let now = day * 86_400;
+    buffer.record("key-a", "getSlot", 100, now);
+    buffer.record("key-a", "getSlot", 50, now + 10);
+    buffer.record("key-a", "getBalance", 20, now);
+    buffer.record("key-b", "getSlot", 10, now);
+    buffer.record("key-a", "getSlot", 5, now + 86_400);
+
+    let mut entries = buffer.take();
+    entries.sort_by(|a, b| (&a.day, &a.key, &a.method).cmp(&(&b.day, &b.key, &b.method)));
+    assert_eq!(
+        entries,
+        vec![
+            entry("key-a", day, "getBalance", 1, 20),
+            entry("key-a", day, "getSlot", 2, 150),
+            entry("key-b", day, "getSlot", 1, 10),
+            entry("key-a", day + 1, "getSlot", 1, 5),
+        ]
+    );
+    assert!(buffer.take().is_empty());
+}
+
+#[test]
+fn test_entries_from_fields() {
+    let fields: HashMap<String, u64> = [
+        ("getSlot:requests", 3),
+        ("getSlot:bytes", 300),
+        ("getBalance:requests", 1),
+        ("getBalance:bytes", 40),
+        ("malformed", 9),
+    ]
+    .into_iter()
+    .map(|(f, v)| (f.to_string(), v))
+    .collect();
+
+    assert_eq!(
+        entries_from_fields("key-a", &day_string(19_884), &fields),
+        vec![
+            entry("key-a", 19_884, "getBalance", 1, 40),
+            entry("key-a", 19_884, "getSlot", 3, 300),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_mock_key_usage_store_merges_and_queries_range() {
+    let store = MockKeyUsageStore::new();
+    store
+        .add(&[
+            entry("key-a", 10, "getSlot", 1, 10),
+            entry("key-a", 11, "getSlot", 1, 10),
+            entry("key-a", 12, "getSlot", 1, 10),
+            entry("key-b", 11, "getSlot", 1, 10),
+        ])
+        .await
+        .unwrap();
+    store
+        .add(&[
+            entry("key-a", 11, "getSlot", 2, 5),
+            entry("key-a", 11, "getBalance", 1, 1),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.query("key-a", 11, 12).await.unwrap(),
+        vec![
+            entry("key-a", 12, "getSlot", 1, 10),
+            entry("key-a", 11, "getBalance", 1, 1),
+            entry("key-a", 11, "getSlot", 3, 15),
+        ]
+    );
+    assert!(store.query("key-c", 0, 100).await.unwrap().is_empty());
+}
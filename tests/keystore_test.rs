@@ -1,4 +1,16 @@
-use sol_rpc_router::{keystore::KeyStore, mock::MockKeyStore};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use sol_rpc_router::{
+    audit::LogPrivacy,
+    config::{KeystoreOutageConfig, KeystoreOutagePolicy, StaticKey},
+    keystore::{
+        lookup_key_list, parse_spend_cap, validate_key_list, KeyCache, KeyInfo, KeyKind, KeyStore,
+        KeyStoreError, OutageKeyStore, MAX_KEYS_PER_REQUEST,
+    },
+    methods::MethodCategory,
+    mock::MockKeyStore,
+    signing::unix_now,
+};
 
 #[tokio::test]
 async fn test_validate_key_valid() {
@@ -31,6 +43,18 @@ async fn test_validate_key_inactive() {
     assert!(result.unwrap().is_none());
 }
 
+#[tokio::test]
+async fn test_validate_key_expired() {
+    let store = MockKeyStore::new();
+    store.add_key("expiring-key", "owner4", 100);
+    store.set_expires_at("expiring-key", Some(unix_now() + 3600));
+    assert!(store.validate_key("expiring-key").await.unwrap().is_some());
+
+    store.set_expires_at("expiring-key", Some(unix_now()));
+    assert!(store.validate_key("expiring-key").await.unwrap().is_none());
+    assert!(store.lookup_key("expiring-key").await.unwrap().is_none());
+}
+
 #[tokio::test]
 async fn test_validate_key_rate_limit() {
     let store = MockKeyStore::new();
@@ -45,7 +69,95 @@ async fn test_validate_key_rate_limit() {
 
     let result = store.validate_key("limited-key").await;
     assert!(result.is_err());
-    assert_eq!(result.err().unwrap(), "Rate limit exceeded");
+    assert_eq!(result.err().unwrap(), KeyStoreError::RateLimited);
+}
+
+#[tokio::test]
+async fn test_validate_key_list_falls_back_in_order() {
+    let store = MockKeyStore::new();
+    store.add_key("old-key", "owner", 100);
+    store.add_key("limited-key", "owner", 100);
+    store
+        .rate_limited_keys
+        .lock()
+        .unwrap()
+        .push("limited-key".to_string());
+
+    // Not provisioned yet, then exhausted: the old key serves
+    let (key, info) = validate_key_list(&store, "new-key, limited-key,old-key")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(key, "old-key");
+    assert_eq!(info.owner, "owner");
+
+    // Once the new key exists, later keys are not looked up
+    store.add_key("new-key", "owner", 100);
+    let (key, _) = validate_key_list(&store, "new-key,old-key")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(key, "new-key");
+    assert_eq!(store.get_call_count("old-key"), 1);
+
+    // A single key behaves as before
+    let (key, _) = validate_key_list(&store, "old-key").await.unwrap().unwrap();
+    assert_eq!(key, "old-key");
+}
+
+#[tokio::test]
+async fn test_lookup_key_list_skips_rate_limits() {
+    let store = MockKeyStore::new();
+    store.add_key("limited-key", "owner", 100);
+    store
+        .rate_limited_keys
+        .lock()
+        .unwrap()
+        .push("limited-key".to_string());
+    store.set_inactive("revoked-key");
+
+    let (key, _) = lookup_key_list(&store, "limited-key")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(key, "limited-key");
+    assert_eq!(store.get_call_count("limited-key"), 1);
+    assert!(lookup_key_list(&store, "revoked-key")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_validate_key_list_failures() {
+    let store = MockKeyStore::new();
+    store.add_key("limited-key", "owner", 100);
+    store
+        .rate_limited_keys
+        .lock()
+        .unwrap()
+        .push("limited-key".to_string());
+    store.set_error("broken-key", "connection refused");
+
+    assert!(validate_key_list(&store, "a,b").await.unwrap().is_none());
+    assert!(validate_key_list(&store, ",").await.unwrap().is_none());
+    // A rate limit is reported over a lookup error, and that over an unknown key
+    assert_eq!(
+        validate_key_list(&store, "a,broken-key,limited-key")
+            .await
+            .err(),
+        Some(KeyStoreError::RateLimited)
+    );
+    assert_eq!(
+        validate_key_list(&store, "a,broken-key").await.err(),
+        Some(KeyStoreError::Unavailable("connection refused".to_string()))
+    );
+
+    // Keys past the limit are never looked up
+    store.add_key("last-key", "owner", 100);
+    let keys = format!("{}last-key", "x,".repeat(MAX_KEYS_PER_REQUEST));
+    assert!(validate_key_list(&store, &keys).await.unwrap().is_none());
+    assert_eq!(store.get_call_count("last-key"), 0);
 }
 
 #[tokio::test]
@@ -60,6 +172,33 @@ async fn test_validate_key_call_count() {
     assert_eq!(store.get_call_count("count-key"), 3);
 }
 
+#[tokio::test]
+async fn test_key_cache_ttls() {
+    let info = KeyInfo {
+        owner: "owner".to_string(),
+        ..KeyInfo::default()
+    };
+    let cache = KeyCache::new(Duration::from_secs(60), Duration::from_millis(50), 100);
+    cache.insert("valid", Some(info)).await;
+    cache.insert("unknown", None).await;
+    assert_eq!(cache.get("valid").await.unwrap().unwrap().owner, "owner");
+    assert!(cache.get("unknown").await.unwrap().is_none());
+    assert!(cache.get("other").await.is_none());
+
+    // Invalid keys are asked about again sooner
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(cache.get("unknown").await.is_none());
+    assert!(cache.get("valid").await.is_some());
+
+    cache.invalidate("valid").await;
+    assert!(cache.get("valid").await.is_none());
+
+    // Without a negative TTL, invalid keys are not cached at all
+    let cache = KeyCache::new(Duration::from_secs(60), Duration::ZERO, 100);
+    cache.insert("unknown", None).await;
+    assert!(cache.get("unknown").await.is_none());
+}
+
 #[tokio::test]
 async fn test_validate_key_custom_error() {
     let store = MockKeyStore::new();
@@ -68,5 +207,347 @@ async fn test_validate_key_custom_error() {
 
     let result = store.validate_key("err-key").await;
     assert!(result.is_err());
-    assert_eq!(result.err().unwrap(), "Redis connection failed");
+    assert_eq!(
+        result.err().unwrap(),
+        KeyStoreError::Unavailable("Redis connection failed".to_string())
+    );
+}
+
+#[test]
+fn test_key_info_from_fields() {
+    let mut fields = HashMap::new();
+    fields.insert("owner".to_string(), "acme".to_string());
+    fields.insert("rate_limit".to_string(), "25".to_string());
+
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert_eq!(info.owner, "acme");
+    assert_eq!(info.rate_limit, 25);
+    assert_eq!(info.kind, KeyKind::Standard);
+
+    fields.insert("kind".to_string(), "browser".to_string());
+    fields.insert(
+        "allowed_origins".to_string(),
+        "https://a.com, https://b.com".to_string(),
+    );
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert_eq!(info.kind, KeyKind::Browser);
+    assert_eq!(info.allowed_origins, vec!["https://a.com", "https://b.com"]);
+
+    fields.insert("default_commitment".to_string(), "confirmed".to_string());
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert_eq!(info.defaults.commitment.as_deref(), Some("confirmed"));
+    assert_eq!(info.defaults.encoding, None);
+
+    fields.insert("default_encoding".to_string(), "binary".to_string());
+    assert!(KeyInfo::from_fields(&fields).is_err());
+    fields.remove("default_encoding");
+
+    assert!(!info.audit);
+    fields.insert("audit".to_string(), "true".to_string());
+    assert!(KeyInfo::from_fields(&fields).unwrap().audit);
+
+    assert!(!info.timing);
+    fields.insert("timing".to_string(), "true".to_string());
+    assert!(KeyInfo::from_fields(&fields).unwrap().timing);
+
+    assert!(!info.broadcast);
+    fields.insert("broadcast".to_string(), "true".to_string());
+    assert!(KeyInfo::from_fields(&fields).unwrap().broadcast);
+
+    assert_eq!(info.privacy, LogPrivacy::Full);
+    fields.insert("privacy".to_string(), "metadata".to_string());
+    assert_eq!(
+        KeyInfo::from_fields(&fields).unwrap().privacy,
+        LogPrivacy::Metadata
+    );
+    fields.insert("privacy".to_string(), "secret".to_string());
+    assert!(KeyInfo::from_fields(&fields).is_err());
+    fields.remove("privacy");
+
+    assert!(info.firehose.is_empty());
+    fields.insert("ws_max_notifications_per_sec".to_string(), "50".to_string());
+    fields.insert("ws_sample_every".to_string(), "10".to_string());
+    let firehose = KeyInfo::from_fields(&fields).unwrap().firehose;
+    assert_eq!((firehose.max_per_sec, firehose.sample_every), (50, 10));
+    fields.insert("ws_sample_every".to_string(), "often".to_string());
+    assert!(KeyInfo::from_fields(&fields).is_err());
+    fields.remove("ws_sample_every");
+
+    assert_eq!(info.max_in_flight, 0);
+    fields.insert("max_in_flight".to_string(), "4".to_string());
+    assert_eq!(KeyInfo::from_fields(&fields).unwrap().max_in_flight, 4);
+    fields.insert("max_in_flight".to_string(), "-1".to_string());
+    assert!(KeyInfo::from_fields(&fields).is_err());
+    fields.remove("max_in_flight");
+
+    fields.remove("rate_limit");
+    assert!(KeyInfo::from_fields(&fields).is_err());
+}
+
+#[test]
+fn test_key_info_method_routes() {
+    let mut fields = HashMap::new();
+    fields.insert("owner".to_string(), "acme".to_string());
+    fields.insert("rate_limit".to_string(), "25".to_string());
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert!(info.method_routes.is_empty());
+    assert_eq!(info.tier, None);
+
+    fields.insert(
+        "method_routes".to_string(),
+        "getProgramAccounts=acme-node, getBlock=archive".to_string(),
+    );
+    fields.insert("tier".to_string(), "premium".to_string());
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert_eq!(info.method_routes["getProgramAccounts"], "acme-node");
+    assert_eq!(info.method_routes["getBlock"], "archive");
+    assert_eq!(info.tier.as_deref(), Some("premium"));
+
+    // The key's own routes win over its tier's
+    let mut premium = HashMap::new();
+    premium.insert("getBlock".to_string(), "premium-pool".to_string());
+    premium.insert("getSlot".to_string(), "premium-pool".to_string());
+    let mut tier_routes = HashMap::new();
+    tier_routes.insert("premium".to_string(), premium);
+    assert_eq!(info.route("getBlock", &tier_routes), Some("archive"));
+    assert_eq!(info.route("getSlot", &tier_routes), Some("premium-pool"));
+    assert_eq!(info.route("getBalance", &tier_routes), None);
+
+    for invalid in [
+        "getProgramAccounts",
+        "getProgramAcounts=acme-node",
+        "=acme-node",
+    ] {
+        fields.insert("method_routes".to_string(), invalid.to_string());
+        assert!(KeyInfo::from_fields(&fields).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_key_info_method_lists() {
+    let mut fields = HashMap::new();
+    fields.insert("owner".to_string(), "acme".to_string());
+    fields.insert("rate_limit".to_string(), "25".to_string());
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert!(!info.restricts_methods());
+    assert!(info.allows_method("sendTransaction"));
+
+    fields.insert(
+        "allowed_methods".to_string(),
+        "getBalance, getSlot,sendTransaction".to_string(),
+    );
+    fields.insert("blocked_methods".to_string(), "sendTransaction".to_string());
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert!(info.restricts_methods());
+    assert!(info.allows_method("getBalance"));
+    assert!(!info.allows_method("getBlock"));
+    // The denylist wins over the allowlist
+    assert!(!info.allows_method("sendTransaction"));
+
+    fields.insert("allowed_methods".to_string(), "getBalanse".to_string());
+    assert!(KeyInfo::from_fields(&fields).is_err());
+}
+
+#[test]
+fn test_key_info_scopes() {
+    let mut fields = HashMap::new();
+    fields.insert("owner".to_string(), "acme".to_string());
+    fields.insert("rate_limit".to_string(), "25".to_string());
+    fields.insert("scopes".to_string(), "read, das,read".to_string());
+    fields.insert("allowed_methods".to_string(), "sendTransaction".to_string());
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert_eq!(info.scopes, vec![MethodCategory::Read, MethodCategory::Das]);
+    assert!(info.restricts_methods());
+    assert!(info.allows_method("getBlock"));
+    assert!(info.allows_method("getAssetsByOwner"));
+    // Listed methods are allowed on top of the scopes
+    assert!(info.allows_method("sendTransaction"));
+    assert!(!info.allows_method("requestAirdrop"));
+    assert!(!info.allows_method("accountSubscribe"));
+    assert!(!info.allows_method("getValidatorExtras"));
+
+    fields.insert("scopes".to_string(), "reads".to_string());
+    assert!(KeyInfo::from_fields(&fields).is_err());
+}
+
+#[test]
+fn test_method_categories() {
+    assert_eq!(MethodCategory::of("getBalance"), MethodCategory::Read);
+    assert_eq!(MethodCategory::of("getGenesisHash"), MethodCategory::Read);
+    assert_eq!(MethodCategory::of("sendTransaction"), MethodCategory::Write);
+    assert_eq!(
+        MethodCategory::of("logsUnsubscribe"),
+        MethodCategory::Subscribe
+    );
+    assert_eq!(MethodCategory::of("searchAssets"), MethodCategory::Das);
+    assert_eq!(
+        MethodCategory::of("getValidatorExtras"),
+        MethodCategory::AdminExtensions
+    );
+    assert_eq!(
+        MethodCategory::parse("admin-extensions"),
+        Ok(MethodCategory::AdminExtensions)
+    );
+    assert!(MethodCategory::parse("Read").is_err());
+}
+
+#[test]
+fn test_key_info_spend_cap() {
+    let mut fields = HashMap::new();
+    fields.insert("owner".to_string(), "acme".to_string());
+    fields.insert("rate_limit".to_string(), "25".to_string());
+    assert_eq!(KeyInfo::from_fields(&fields).unwrap().spend_cap, None);
+
+    fields.insert("spend_cap".to_string(), "250.5".to_string());
+    assert_eq!(
+        KeyInfo::from_fields(&fields).unwrap().spend_cap,
+        Some(250.5)
+    );
+
+    assert_eq!(parse_spend_cap(" 0 "), Ok(0.0));
+    for invalid in ["-1", "NaN", "inf", "ten"] {
+        assert!(parse_spend_cap(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_key_info_expires_at() {
+    let mut fields = HashMap::new();
+    fields.insert("owner".to_string(), "acme".to_string());
+    fields.insert("rate_limit".to_string(), "25".to_string());
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert_eq!(info.expires_at, None);
+    assert!(!info.is_expired(u64::MAX));
+
+    fields.insert("expires_at".to_string(), "1767225600".to_string());
+    let info = KeyInfo::from_fields(&fields).unwrap();
+    assert_eq!(info.expires_at, Some(1_767_225_600));
+    assert!(!info.is_expired(1_767_225_599));
+    assert!(info.is_expired(1_767_225_600));
+
+    fields.insert("expires_at".to_string(), "tomorrow".to_string());
+    assert!(KeyInfo::from_fields(&fields).is_err());
+}
+
+fn outage_store(
+    inner: &Arc<MockKeyStore>,
+    policy: KeystoreOutagePolicy,
+    remember_secs: u64,
+) -> OutageKeyStore {
+    let config = KeystoreOutageConfig {
+        policy,
+        remember_secs,
+        static_keys: vec![StaticKey {
+            key: "static-key".to_string(),
+            owner: "partner".to_string(),
+        }],
+    };
+    OutageKeyStore::new(inner.clone(), &config, 100)
+}
+
+#[tokio::test]
+async fn test_outage_fail_closed() {
+    let inner = Arc::new(MockKeyStore::new());
+    inner.add_key("seen-key", "owner1", 10);
+    let store = outage_store(&inner, KeystoreOutagePolicy::FailClosed, 60);
+
+    assert!(store.validate_key("seen-key").await.unwrap().is_some());
+    inner.set_error("seen-key", "Redis connection failed");
+    assert_eq!(
+        store.validate_key("seen-key").await.unwrap_err(),
+        KeyStoreError::Unavailable("Redis connection failed".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_outage_fail_open_remembers_validated_keys() {
+    let inner = Arc::new(MockKeyStore::new());
+    inner.add_key("seen-key", "owner1", 10);
+    inner.add_key("unseen-key", "owner2", 10);
+    let store = outage_store(&inner, KeystoreOutagePolicy::FailOpen, 1);
+
+    assert!(store.validate_key("seen-key").await.unwrap().is_some());
+    for key in ["seen-key", "unseen-key", "static-key"] {
+        inner.set_error(key, "Redis connection failed");
+    }
+
+    // Served from the last validation, without a rate limit bucket
+    let info = store.validate_key("seen-key").await.unwrap().unwrap();
+    assert_eq!(info.owner, "owner1");
+    assert_eq!(info.rate_limit_remaining, None);
+    assert!(store.lookup_key("seen-key").await.unwrap().is_some());
+    assert!(store.validate_key("unseen-key").await.is_err());
+    assert!(store.validate_key("static-key").await.is_err());
+
+    // Rate limits are still enforced while the store answers
+    inner.error_keys.lock().unwrap().clear();
+    inner
+        .rate_limited_keys
+        .lock()
+        .unwrap()
+        .push("seen-key".to_string());
+    assert_eq!(
+        store.validate_key("seen-key").await.unwrap_err(),
+        KeyStoreError::RateLimited
+    );
+
+    // Remembered keys are forgotten after `remember_secs`
+    inner.set_error("seen-key", "Redis connection failed");
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(store.validate_key("seen-key").await.is_err());
+}
+
+#[tokio::test]
+async fn test_outage_fail_open_forgets_revoked_keys() {
+    let inner = Arc::new(MockKeyStore::new());
+    inner.add_key("seen-key", "owner1", 10);
+    let store = outage_store(&inner, KeystoreOutagePolicy::FailOpen, 60);
+
+    assert!(store.validate_key("seen-key").await.unwrap().is_some());
+    inner.set_inactive("seen-key");
+    assert!(store.validate_key("seen-key").await.unwrap().is_none());
+    inner.set_error("seen-key", "Redis connection failed");
+    assert!(store.validate_key("seen-key").await.is_err());
+}
+
+#[tokio::test]
+async fn test_outage_policy_ignores_malformed_keys() {
+    let inner = Arc::new(MockKeyStore::new());
+    inner.add_key("seen-key", "owner1", 10);
+    inner.add_key("static-key", "partner", 10);
+    for policy in [
+        KeystoreOutagePolicy::FailOpen,
+        KeystoreOutagePolicy::StaticKeys,
+    ] {
+        let store = outage_store(&inner, policy, 60);
+        assert!(store.validate_key("seen-key").await.unwrap().is_some());
+        for key in ["seen-key", "static-key"] {
+            inner.set_invalid(key, "Invalid spend_cap: abc");
+            assert_eq!(
+                store.validate_key(key).await.unwrap_err(),
+                KeyStoreError::Invalid("Invalid spend_cap: abc".to_string())
+            );
+        }
+        inner.error_keys.lock().unwrap().clear();
+    }
+}
+
+#[tokio::test]
+async fn test_outage_static_keys() {
+    let inner = Arc::new(MockKeyStore::new());
+    inner.add_key("seen-key", "owner1", 10);
+    let store = outage_store(&inner, KeystoreOutagePolicy::StaticKeys, 60);
+
+    assert!(store.validate_key("seen-key").await.unwrap().is_some());
+    inner.set_error("seen-key", "Redis connection failed");
+    inner.set_error("static-key", "Redis connection failed");
+
+    assert!(store.validate_key("seen-key").await.is_err());
+    let info = validate_key_list(&store, "seen-key,static-key")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.0, "static-key");
+    assert_eq!(info.1.owner, "partner");
+    assert_eq!(info.1.rate_limit, 0);
 }
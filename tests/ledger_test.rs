@@ -0,0 +1,104 @@
+use sol_rpc_router::{
+    ledger::{
+        day_number, day_string, parse_day, SqliteUsageLedger, UsageBuffer, UsageEntry, UsageLedger,
+    },
+    signing::utc_date,
+};
+
+fn temp_db(name: &str) -> String {
+    let mut path = std::env::temp_dir();
+    path.push(format!("sol_rpc_router_test_ledger_{}.db", name));
+    let _ = std::fs::remove_file(&path);
+    path.to_str().unwrap().to_string()
+}
+
+fn entry(owner: &str, day: u64, requests: u64, errors: u64) -> UsageEntry {
+    UsageEntry {
+        owner: owner.to_string(),
+        day: day_string(day),
+        requests,
+        errors,
+    }
+}
+
+#[test]
+fn test_day_strings() {
+    assert_eq!(utc_date(0), (1970, 1, 1));
+    assert_eq!(utc_date(951_782_400), (2000, 2, 29));
+    assert_eq!(day_string(day_number(1_718_000_000)), "2024-06-10");
+}
+
+#[test]
+fn test_parse_day() {
+    assert_eq!(parse_day("1970-01-01"), Some(0));
+    assert_eq!(parse_day("2000-02-29"), Some(951_782_400 / 86_400));
+    assert_eq!(parse_day("2024-06-10"), Some(day_number(1_718_000_000)));
+    for invalid in [
+        "2024-02-30",
+        "2023-02-29",
+        "2024-13-01",
+        "1969-12-31",
+        "2024-6-10",
+        "today",
+        "",
+    ] {
+        assert_eq!(parse_day(invalid), None, "{}", invalid);
+    }
+}
+
+#[test]
+fn test_usage_buffer_aggregates_per_owner_and_day() {
+    let buffer = UsageBuffer::new();
+    let day = 19_884;
+    let now = day * 86_400;
+    buffer.record("alice", false, now);
+    buffer.record("alice", true, now + 10);
+    buffer.record("bob", false, now);
+    buffer.record("alice", false, now + 86_400);
+
+    let mut entries = buffer.take();
+    entries.sort_by(|a, b| (&a.day, &a.owner).cmp(&(&b.day, &b.owner)));
+    assert_eq!(
+        entries,
+        vec![
+            entry("alice", day, 2, 1),
+            entry("bob", day, 1, 0),
+            entry("alice", day + 1, 1, 0),
+        ]
+    );
+    assert!(buffer.take().is_empty());
+}
+
+#[tokio::test]
+async fn test_sqlite_ledger_accumulates_and_prunes() {
+    let path = temp_db("accumulate");
+    let ledger = SqliteUsageLedger::open(&path).unwrap();
+    let day = 19_884;
+    ledger
+        .add(&[entry("alice", day - 1, 5, 0), entry("alice", day, 3, 1)])
+        .await
+        .unwrap();
+    ledger
+        .add(&[entry("alice", day, 2, 2), entry("bob", day, 7, 0)])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        ledger.query(None, day - 1, day).await.unwrap(),
+        vec![
+            entry("alice", day, 5, 3),
+            entry("bob", day, 7, 0),
+            entry("alice", day - 1, 5, 0),
+        ]
+    );
+    assert_eq!(
+        ledger.query(Some("bob"), day - 1, day).await.unwrap(),
+        vec![entry("bob", day, 7, 0)]
+    );
+
+    // Totals survive reopening the database
+    drop(ledger);
+    let ledger = SqliteUsageLedger::open(&path).unwrap();
+    assert_eq!(ledger.prune(day).await.unwrap(), 1);
+    assert_eq!(ledger.query(None, day - 30, day).await.unwrap().len(), 2);
+}
@@ -0,0 +1,78 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use sol_rpc_router::{
+    config::LogFormat,
+    log_format::{is_json, set_log_format, RouterFormat},
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Capture {
+    type Writer = Capture;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+impl Capture {
+    fn take(&self) -> String {
+        String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+    }
+}
+
+#[test]
+fn test_log_format_switches_between_text_and_json() {
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(capture.clone())
+        .with_ansi(false)
+        .event_format(RouterFormat::default())
+        .finish();
+
+    tracing::subscriber::with_default(subscriber, || {
+        assert!(!is_json());
+        tracing::info!(rpc_method = "getSlot", "request");
+        let text = capture.take();
+        assert!(text.contains(" INFO "), "{}", text);
+        assert!(text.contains("request rpc_method=\"getSlot\""), "{}", text);
+
+        set_log_format(LogFormat::Json);
+        tracing::info!(
+            target: "access",
+            rpc_method = "getSlot",
+            status = 200u16,
+            duration_ms = 1.5,
+            owner = None::<&str>,
+            "request"
+        );
+        set_log_format(LogFormat::Text);
+        let line = capture.take();
+        let json: serde_json::Value = serde_json::from_str(line.trim_end()).unwrap();
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["target"], "access");
+        assert_eq!(json["message"], "request");
+        assert_eq!(json["rpc_method"], "getSlot");
+        assert_eq!(json["status"], 200);
+        assert_eq!(json["duration_ms"], 1.5);
+        assert!(json["timestamp"].as_str().unwrap().contains('T'));
+        // Unset fields are left out
+        assert!(json.get("owner").is_none());
+    });
+}
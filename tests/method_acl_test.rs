@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+use sol_rpc_router::{
+    keystore::KeyInfo,
+    method_acl::{check, METHOD_NOT_ALLOWED_CODE},
+    methods::MethodCategory,
+};
+
+fn key(allowed: &str, blocked: &str) -> KeyInfo {
+    let mut fields = HashMap::new();
+    fields.insert("owner".to_string(), "acme".to_string());
+    fields.insert("rate_limit".to_string(), "10".to_string());
+    fields.insert("allowed_methods".to_string(), allowed.to_string());
+    fields.insert("blocked_methods".to_string(), blocked.to_string());
+    KeyInfo::from_fields(&fields).unwrap()
+}
+
+fn parse(body: &str) -> Value {
+    serde_json::from_str(body).unwrap()
+}
+
+#[test]
+fn test_check_single_request() {
+    let key = key("getBalance", "");
+    assert_eq!(
+        check(br#"{"jsonrpc":"2.0","method":"getBalance","id":1}"#, &key),
+        None
+    );
+
+    let denied = check(br#"{"jsonrpc":"2.0","method":"getBlock","id":"a"}"#, &key).unwrap();
+    assert_eq!(denied.method, "getBlock");
+    let body = parse(&denied.body);
+    assert_eq!(body["jsonrpc"], "2.0");
+    assert_eq!(body["id"], "a");
+    assert_eq!(body["error"]["code"], METHOD_NOT_ALLOWED_CODE);
+    assert_eq!(
+        body["error"]["message"],
+        "Method 'getBlock' is not allowed for this API key"
+    );
+}
+
+#[test]
+fn test_check_batch_is_refused_whole() {
+    let key = key("", "sendTransaction");
+    let allowed = br#"[{"jsonrpc":"2.0","method":"getSlot","id":1}]"#;
+    assert_eq!(check(allowed, &key), None);
+
+    let denied = check(
+        br#"[{"jsonrpc":"2.0","method":"getSlot","id":1},{"jsonrpc":"2.0","method":"sendTransaction","id":2}]"#,
+        &key,
+    )
+    .unwrap();
+    assert_eq!(denied.method, "sendTransaction");
+    let body = parse(&denied.body);
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(body[0]["id"], 1);
+    assert_eq!(body[0]["error"]["code"], METHOD_NOT_ALLOWED_CODE);
+    assert!(body[0]["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Batch refused"));
+    assert_eq!(body[1]["id"], 2);
+    assert_eq!(
+        body[1]["error"]["message"],
+        "Method 'sendTransaction' is not allowed for this API key"
+    );
+}
+
+#[test]
+fn test_check_leaves_unparseable_bodies() {
+    let key = key("getBalance", "");
+    assert_eq!(check(b"not json", &key), None);
+    assert_eq!(check(br#"{"jsonrpc":"2.0","id":1}"#, &key), None);
+}
+
+#[test]
+fn test_check_scoped_key() {
+    let mut key = key("getBlock", "sendTransaction");
+    key.scopes = vec![MethodCategory::Read, MethodCategory::Write];
+    let batch = br#"[{"jsonrpc":"2.0","method":"getSlot","id":1},{"jsonrpc":"2.0","method":"getBlock","id":2}]"#;
+    assert_eq!(check(batch, &key), None);
+    // Blocked methods stay blocked inside a scope
+    let denied = check(
+        br#"{"jsonrpc":"2.0","method":"sendTransaction","id":1}"#,
+        &key,
+    )
+    .unwrap();
+    assert_eq!(denied.method, "sendTransaction");
+    let denied = check(
+        br#"{"jsonrpc":"2.0","method":"slotSubscribe","id":1}"#,
+        &key,
+    )
+    .unwrap();
+    assert_eq!(denied.method, "slotSubscribe");
+}
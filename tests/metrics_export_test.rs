@@ -0,0 +1,258 @@
+use std::sync::Arc;
+
+use metrics::{counter, gauge, histogram};
+use sol_rpc_router::metrics_export::{
+    MetricsRegistry, PushRecorder, SampleValue, HISTOGRAM_BUCKETS,
+};
+
+/// A registry fed through a local recorder with a counter, a gauge and a
+/// histogram.
+fn recorded() -> Arc<MetricsRegistry> {
+    let registry = Arc::new(MetricsRegistry::new());
+    let recorder = PushRecorder(registry.clone());
+    metrics::with_local_recorder(&recorder, || {
+        counter!("rpc_requests_total", "backend" => "a", "rpc_method" => "getSlot").increment(2);
+        counter!("rpc_requests_total", "backend" => "a", "rpc_method" => "getSlot").increment(1);
+        gauge!("rpc_backend_health", "backend" => "a").set(1.0);
+        histogram!("rpc_request_duration_seconds", "backend" => "a").record(0.02);
+        histogram!("rpc_request_duration_seconds", "backend" => "a").record(30.0);
+    });
+    registry
+}
+
+fn labels(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_registry_snapshot() {
+    let registry = recorded();
+    let samples = registry.snapshot();
+    assert_eq!(samples.len(), 3);
+
+    assert_eq!(samples[0].name, "rpc_backend_health");
+    assert_eq!(samples[0].value, SampleValue::Gauge(1.0));
+
+    assert_eq!(samples[1].name, "rpc_request_duration_seconds");
+    let SampleValue::Histogram(histogram) = &samples[1].value else {
+        panic!("{:?}", samples[1]);
+    };
+    assert_eq!(histogram.count, 2);
+    assert_eq!(histogram.sum, 30.02);
+    assert_eq!(histogram.bucket_counts.len(), HISTOGRAM_BUCKETS.len() + 1);
+    // 0.02 falls in the 0.025 bucket, 30 above the highest bound
+    assert_eq!(histogram.bucket_counts[3], 1);
+    assert_eq!(histogram.bucket_counts[HISTOGRAM_BUCKETS.len()], 1);
+    assert_eq!(histogram.pending, vec![0.02, 30.0]);
+
+    assert_eq!(samples[2].name, "rpc_requests_total");
+    assert_eq!(
+        samples[2].labels,
+        labels(&[("backend", "a"), ("rpc_method", "getSlot")])
+    );
+    assert_eq!(samples[2].value, SampleValue::Counter(3));
+
+    // Pending histogram values are handed over once; totals stay
+    let SampleValue::Histogram(histogram) = &registry.snapshot()[1].value else {
+        panic!();
+    };
+    assert_eq!(histogram.count, 2);
+    assert!(histogram.pending.is_empty());
+}
+
+#[cfg(feature = "statsd")]
+#[tokio::test]
+async fn test_statsd_exporter() {
+    use sol_rpc_router::{
+        config::StatsdConfig,
+        metrics_export::{statsd::StatsdExporter, MetricsExporter},
+    };
+    use tokio::net::UdpSocket;
+
+    let agent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = StatsdConfig {
+        address: agent.local_addr().unwrap().to_string(),
+        prefix: "router.".to_string(),
+        tags: true,
+    };
+    let mut exporter = StatsdExporter::connect(&config).await.unwrap();
+    let registry = recorded();
+
+    exporter.export(&registry.snapshot()).await.unwrap();
+    let mut buf = [0u8; 2048];
+    let len = agent.recv(&mut buf).await.unwrap();
+    let packet = std::str::from_utf8(&buf[..len]).unwrap();
+    assert_eq!(
+        packet.lines().collect::<Vec<_>>(),
+        vec![
+            "router.rpc_backend_health:1|g|#backend:a",
+            "router.rpc_request_duration_seconds:0.02|h|#backend:a",
+            "router.rpc_request_duration_seconds:30|h|#backend:a",
+            "router.rpc_requests_total:3|c|#backend:a,rpc_method:getSlot",
+        ]
+    );
+
+    // Counters go out as deltas; unchanged ones are skipped
+    let recorder = PushRecorder(registry.clone());
+    metrics::with_local_recorder(&recorder, || {
+        counter!("rpc_requests_total", "backend" => "a", "rpc_method" => "getSlot").increment(4);
+    });
+    assert_eq!(
+        exporter.lines(&registry.snapshot()),
+        vec![
+            "router.rpc_backend_health:1|g|#backend:a",
+            "router.rpc_requests_total:4|c|#backend:a,rpc_method:getSlot",
+        ]
+    );
+    assert_eq!(
+        exporter.lines(&registry.snapshot()),
+        vec!["router.rpc_backend_health:1|g|#backend:a"]
+    );
+}
+
+#[cfg(feature = "statsd")]
+#[tokio::test]
+async fn test_statsd_plain_names() {
+    use sol_rpc_router::{
+        config::StatsdConfig,
+        metrics_export::{statsd::StatsdExporter, Sample},
+    };
+
+    let config = StatsdConfig {
+        tags: false,
+        ..StatsdConfig::default()
+    };
+    let mut exporter = StatsdExporter::connect(&config).await.unwrap();
+    let samples = [Sample {
+        name: "rpc_requests_total".to_string(),
+        labels: labels(&[("backend", "node-1"), ("rpc_method", "get.Slot")]),
+        value: SampleValue::Counter(5),
+    }];
+    assert_eq!(
+        exporter.lines(&samples),
+        vec!["rpc_requests_total.node-1.get_Slot:5|c"]
+    );
+}
+
+#[cfg(feature = "otlp")]
+#[test]
+fn test_otlp_payload() {
+    use sol_rpc_router::metrics_export::{otlp::payload, HistogramSnapshot, Sample};
+
+    let samples = [
+        Sample {
+            name: "rpc_requests_total".to_string(),
+            labels: labels(&[("backend", "a")]),
+            value: SampleValue::Counter(7),
+        },
+        Sample {
+            name: "rpc_requests_total".to_string(),
+            labels: labels(&[("backend", "b")]),
+            value: SampleValue::Counter(1),
+        },
+        Sample {
+            name: "rpc_backend_health".to_string(),
+            labels: Vec::new(),
+            value: SampleValue::Gauge(0.5),
+        },
+        Sample {
+            name: "rpc_request_duration_seconds".to_string(),
+            labels: Vec::new(),
+            value: SampleValue::Histogram(HistogramSnapshot {
+                count: 1,
+                sum: 0.2,
+                bucket_counts: vec![0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0],
+                pending: vec![0.2],
+            }),
+        },
+    ];
+    let body = payload(&samples, "router-1", 1_000, 2_000);
+    let resource = &body["resourceMetrics"][0];
+    assert_eq!(
+        resource["resource"]["attributes"][0]["value"]["stringValue"],
+        "router-1"
+    );
+    let metrics = resource["scopeMetrics"][0]["metrics"].as_array().unwrap();
+    assert_eq!(metrics.len(), 3);
+
+    let gauge = &metrics[0];
+    assert_eq!(gauge["name"], "rpc_backend_health");
+    assert_eq!(gauge["gauge"]["dataPoints"][0]["asDouble"], 0.5);
+    assert_eq!(gauge["gauge"]["dataPoints"][0]["timeUnixNano"], "2000");
+
+    let histogram = &metrics[1]["histogram"];
+    assert_eq!(histogram["aggregationTemporality"], 2);
+    let point = &histogram["dataPoints"][0];
+    assert_eq!(point["count"], "1");
+    assert_eq!(point["bucketCounts"][6], "1");
+    assert_eq!(
+        point["explicitBounds"].as_array().unwrap().len(),
+        HISTOGRAM_BUCKETS.len()
+    );
+
+    let sum = &metrics[2]["sum"];
+    assert_eq!(sum["isMonotonic"], true);
+    let points = sum["dataPoints"].as_array().unwrap();
+    assert_eq!(points.len(), 2);
+    assert_eq!(points[0]["asInt"], "7");
+    assert_eq!(points[0]["startTimeUnixNano"], "1000");
+    assert_eq!(points[0]["attributes"][0]["key"], "backend");
+    assert_eq!(points[1]["attributes"][0]["value"]["stringValue"], "b");
+}
+
+#[cfg(feature = "otlp")]
+#[tokio::test]
+async fn test_otlp_exporter_posts_to_collector() {
+    use std::collections::HashMap;
+
+    use axum::{http::HeaderMap, routing::post, Json, Router};
+    use sol_rpc_router::{
+        config::OtlpConfig,
+        metrics_export::{otlp::OtlpExporter, MetricsExporter},
+    };
+    use tokio::sync::mpsc;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/v1/metrics",
+            post(
+                move |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                    tx.send((headers, body)).unwrap();
+                },
+            ),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let config = OtlpConfig {
+        endpoint,
+        headers: HashMap::from([("x-api-key".to_string(), "collector-key".to_string())]),
+        ..OtlpConfig::default()
+    };
+    let mut exporter = OtlpExporter::new(&config);
+    exporter.export(&recorded().snapshot()).await.unwrap();
+    let (headers, body) = rx.recv().await.unwrap();
+    assert_eq!(headers["x-api-key"], "collector-key");
+    assert_eq!(
+        body["resourceMetrics"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+        "sol-rpc-router"
+    );
+    let metrics = body["resourceMetrics"][0]["scopeMetrics"][0]["metrics"]
+        .as_array()
+        .unwrap();
+    assert_eq!(metrics.len(), 3);
+
+    // A collector that is down is reported
+    let config = OtlpConfig {
+        endpoint: "http://127.0.0.1:1".to_string(),
+        ..OtlpConfig::default()
+    };
+    let mut exporter = OtlpExporter::new(&config);
+    assert!(exporter.export(&recorded().snapshot()).await.is_err());
+}
@@ -0,0 +1,70 @@
+use std::net::{IpAddr, SocketAddr};
+
+use sol_rpc_router::{
+    browser::OriginLimiter,
+    net::{bind_all, canonical_addr, canonical_ip, rate_limit_subject},
+};
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_mapped_addresses_become_ipv4() {
+    assert_eq!(canonical_ip(ip("::ffff:203.0.113.7")), ip("203.0.113.7"));
+    assert_eq!(canonical_ip(ip("2001:db8::1")), ip("2001:db8::1"));
+    assert_eq!(
+        canonical_addr("[::ffff:203.0.113.7]:4000".parse().unwrap()),
+        "203.0.113.7:4000".parse::<SocketAddr>().unwrap()
+    );
+}
+
+#[test]
+fn test_rate_limit_subject_groups_ipv6_by_64() {
+    assert_eq!(rate_limit_subject(ip("203.0.113.7")), ip("203.0.113.7"));
+    assert_eq!(
+        rate_limit_subject(ip("::ffff:203.0.113.7")),
+        ip("203.0.113.7")
+    );
+    assert_eq!(
+        rate_limit_subject(ip("2001:db8:1:2:aaaa::1")),
+        rate_limit_subject(ip("2001:db8:1:2:bbbb::2"))
+    );
+    assert_ne!(
+        rate_limit_subject(ip("2001:db8:1:2::1")),
+        rate_limit_subject(ip("2001:db8:1:3::1"))
+    );
+
+    // Rotating addresses inside one /64 does not reset the browser-key limit
+    let limiter = OriginLimiter::new();
+    assert!(limiter.check("owner", "https://app", ip("2001:db8:1:2::1"), 1));
+    assert!(!limiter.check("owner", "https://app", ip("2001:db8:1:2::2"), 1));
+    assert!(limiter.check("owner", "https://app", ip("2001:db8:1:3::1"), 1));
+}
+
+#[tokio::test]
+async fn test_bind_all_shares_port_across_families() {
+    // Find a free port, then bind it on both loopback addresses
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let listeners = match bind_all(&[ip("127.0.0.1"), ip("::1")], port) {
+        Ok(listeners) => listeners,
+        // Hosts without IPv6 cannot run this check
+        Err(e) if e.kind() == std::io::ErrorKind::AddrNotAvailable => return,
+        Err(e) => panic!("bind failed: {}", e),
+    };
+    let addrs: Vec<SocketAddr> = listeners.iter().map(|l| l.local_addr().unwrap()).collect();
+    assert_eq!(
+        addrs,
+        vec![
+            SocketAddr::new(ip("127.0.0.1"), port),
+            SocketAddr::new(ip("::1"), port)
+        ]
+    );
+
+    // Binding the same address twice fails
+    assert!(bind_all(&[ip("127.0.0.1")], port).is_err());
+}
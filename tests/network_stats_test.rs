@@ -0,0 +1,74 @@
+use serde_json::json;
+use sol_rpc_router::{
+    cache::CachePolicy,
+    config::NetworkStatsCacheConfig,
+    network_stats::{agrees, is_network_stats, lookup},
+};
+
+#[test]
+fn test_network_stats_requests() {
+    assert!(is_network_stats("getSupply", None));
+    assert!(is_network_stats("getInflationRate", Some(&json!([]))));
+    assert!(is_network_stats(
+        "getInflationReward",
+        Some(&json!([["addr"], {"epoch": 600}]))
+    ));
+    // Rewards of the latest epoch change at every boundary
+    assert!(!is_network_stats(
+        "getInflationReward",
+        Some(&json!([["addr"]]))
+    ));
+    assert!(!is_network_stats(
+        "getInflationReward",
+        Some(&json!([["addr"], {"commitment": "finalized"}]))
+    ));
+    assert!(!is_network_stats("getBalance", None));
+}
+
+#[test]
+fn test_network_stats_lookup() {
+    let config = NetworkStatsCacheConfig {
+        enabled: true,
+        ttl_secs: 120,
+        ..Default::default()
+    };
+    let body =
+        br#"{"jsonrpc":"2.0","method":"getSupply","params":[{"commitment":"confirmed"}],"id":7}"#;
+    let found = lookup(body, &config).unwrap();
+    assert_eq!(found.policy, CachePolicy::Network);
+    assert_eq!(found.ttl.as_secs(), 120);
+    assert_eq!(found.id, "7");
+    assert!(found.cross_check.is_none());
+
+    let config = NetworkStatsCacheConfig {
+        cross_check: true,
+        ..config
+    };
+    assert_eq!(
+        lookup(body, &config).unwrap().cross_check.as_deref(),
+        Some(&body[..])
+    );
+    assert!(lookup(br#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#, &config).is_none());
+    assert!(lookup(b"[]", &config).is_none());
+}
+
+#[test]
+fn test_answers_agree_within_tolerance() {
+    let a = json!({"total": 1_000_000, "circulating": 500_000, "nonCirculatingAccounts": ["x"]});
+    let close =
+        json!({"total": 1_000_900, "circulating": 500_000, "nonCirculatingAccounts": ["x"]});
+    assert!(agrees(&a, &a, 0));
+    assert!(agrees(&a, &close, 10));
+    assert!(!agrees(&a, &close, 5));
+    // Shapes and other values must match exactly
+    let other_accounts =
+        json!({"total": 1_000_000, "circulating": 500_000, "nonCirculatingAccounts": ["y"]});
+    assert!(!agrees(&a, &other_accounts, 10_000));
+    assert!(!agrees(&a, &json!({"total": 1_000_000}), 10_000));
+    assert!(agrees(
+        &json!({"epoch": 600, "foundation": 0.05}),
+        &json!({"epoch": 600, "foundation": 0.05}),
+        0
+    ));
+    assert!(!agrees(&json!(1), &json!("1"), 10_000));
+}
@@ -0,0 +1,303 @@
+use std::{sync::Arc, time::Duration};
+
+use arc_swap::ArcSwap;
+use axum::{
+    body::Body,
+    extract::ws::{Message, WebSocketUpgrade},
+    http::{Request, StatusCode},
+    routing::{get, post},
+    Router,
+};
+use http_body_util::BodyExt;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use serde_json::{json, Value};
+use sol_rpc_router::{
+    config::{Backend, Commitment, PollBridgeConfig, WebSocketConfig},
+    handlers::{poll_endpoint, poll_subscribe, poll_unsubscribe},
+    mock::MockKeyStore,
+    poll_bridge::PollBridge,
+    signing::unix_now,
+    state::{AppState, RouterState, RuntimeBackend},
+};
+use tower::ServiceExt;
+
+#[test]
+fn test_reserve_limits_subscriptions_per_key() {
+    let bridge = PollBridge::new();
+    let (first, _) = bridge
+        .reserve("key-a", "alice", "accountSubscribe", 10, 2)
+        .unwrap();
+    bridge
+        .reserve("key-a", "alice", "logsSubscribe", 10, 2)
+        .unwrap();
+    assert!(bridge
+        .reserve("key-a", "alice", "slotSubscribe", 10, 2)
+        .is_none());
+    // Other keys have their own allowance, and cannot see key-a's subscriptions
+    bridge
+        .reserve("key-b", "bob", "slotSubscribe", 10, 2)
+        .unwrap();
+    assert!(bridge.get(&first, "key-b").is_none());
+    assert!(!bridge.remove(&first, "key-b"));
+
+    assert!(bridge.remove(&first, "key-a"));
+    assert!(bridge.get(&first, "key-a").is_none());
+    bridge
+        .reserve("key-a", "alice", "slotSubscribe", 10, 2)
+        .unwrap();
+    assert_eq!(bridge.len(), 3);
+
+    assert_eq!(bridge.expire_idle(unix_now() + 60, 120), 0);
+    assert_eq!(bridge.expire_idle(unix_now() + 121, 120), 3);
+    assert!(bridge.is_empty());
+}
+
+#[tokio::test]
+async fn test_poll_buffers_and_drops_oldest() {
+    let bridge = PollBridge::new();
+    let (_, bridged) = bridge
+        .reserve("key", "owner", "slotSubscribe", 2, 1)
+        .unwrap();
+
+    // Nothing buffered: the poll waits, then returns empty
+    let batch = bridged.poll(Duration::from_millis(50)).await;
+    assert!(batch.notifications.is_empty());
+    assert!(!batch.closed);
+
+    for n in 1..=3 {
+        bridged.push(json!(n));
+    }
+    let batch = bridged.poll(Duration::from_secs(5)).await;
+    assert_eq!(batch.notifications, vec![json!(2), json!(3)]);
+    assert_eq!(batch.dropped, 1);
+
+    // A waiting poll returns as soon as something arrives
+    let pusher = bridged.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        pusher.push(json!(4));
+    });
+    let batch = bridged.poll(Duration::from_secs(5)).await;
+    assert_eq!(batch.notifications, vec![json!(4)]);
+    assert_eq!(batch.dropped, 0);
+
+    bridged.close();
+    assert!(bridged.poll(Duration::from_secs(5)).await.closed);
+}
+
+/// WebSocket backend confirming every subscription with id 7 and then sending
+/// one `accountNotification`.
+async fn start_backend() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            get(|ws: WebSocketUpgrade| async move {
+                ws.on_upgrade(|mut socket| async move {
+                    while let Some(Ok(Message::Text(text))) = socket.recv().await {
+                        let request: Value = serde_json::from_str(&text).unwrap();
+                        let response = json!({"jsonrpc": "2.0", "result": 7, "id": request["id"]});
+                        let notification = json!({
+                            "jsonrpc": "2.0",
+                            "method": "accountNotification",
+                            "params": {"result": {"value": {"lamports": 5}}, "subscription": 7}
+                        });
+                        for message in [response, notification] {
+                            if socket
+                                .send(Message::Text(message.to_string()))
+                                .await
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    }
+                })
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("ws://{}", addr)
+}
+
+fn bridge_app(ws_url: Option<String>, websocket: WebSocketConfig) -> (Router, Arc<AppState>) {
+    let backend = Backend {
+        label: "ws-backend".to_string(),
+        url: "http://127.0.0.1:1".to_string(),
+        ws_url,
+        weight: 1,
+        ..Default::default()
+    };
+    let router_state = RouterState {
+        backends: vec![RuntimeBackend::new(backend, true)],
+        websocket,
+        poll_bridge: PollBridgeConfig {
+            enabled: true,
+            max_subscriptions_per_key: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let state = Arc::new(AppState::new(
+        client,
+        keystore,
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
+    let app = Router::new()
+        .route("/v1/poll", post(poll_subscribe))
+        .route("/v1/poll/:id", get(poll_endpoint).delete(poll_unsubscribe))
+        .with_state(state.clone());
+    (app, state)
+}
+
+fn request(method: &str, uri: &str, body: Option<Value>) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+        .unwrap()
+}
+
+async fn json_body(response: axum::response::Response) -> Value {
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_bridged_upstream_subscription() {
+    let (app, state) = bridge_app(Some(start_backend().await), WebSocketConfig::default());
+    let subscribe = json!({
+        "jsonrpc": "2.0", "id": 1, "method": "accountSubscribe", "params": ["Acc1"]
+    });
+
+    let response = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/v1/poll?api-key=test-key",
+            Some(subscribe.clone()),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let id = json_body(response).await["subscription_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // One per key
+    let response = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/v1/poll?api-key=test-key",
+            Some(subscribe),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let poll = format!("/v1/poll/{}?api-key=test-key&wait_ms=5000", id);
+    let response = app
+        .clone()
+        .oneshot(request("GET", &poll, None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let batch = json_body(response).await;
+    assert_eq!(batch["notifications"][0]["method"], "accountNotification");
+    assert_eq!(batch["closed"], json!(false));
+
+    let response = app
+        .clone()
+        .oneshot(request("DELETE", &poll, None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(state.poll_bridge.is_empty());
+    let response = app.oneshot(request("GET", &poll, None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_bridged_slot_subscription_uses_slot_feed() {
+    let websocket = WebSocketConfig {
+        local_slot_subscriptions: true,
+        ..Default::default()
+    };
+    // No ws_url: the subscription never reaches a backend
+    let (app, state) = bridge_app(None, websocket);
+
+    let subscribe = json!({"jsonrpc": "2.0", "id": 1, "method": "slotSubscribe"});
+    let response = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/v1/poll?api-key=test-key",
+            Some(subscribe),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let id = json_body(response).await["subscription_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    state.slot_feed.record(Commitment::Processed, 101);
+    let poll = format!("/v1/poll/{}?api-key=test-key&wait_ms=5000", id);
+    let response = app.oneshot(request("GET", &poll, None)).await.unwrap();
+    let batch = json_body(response).await;
+    assert_eq!(batch["notifications"][0]["method"], "slotNotification");
+    assert_eq!(
+        batch["notifications"][0]["params"]["result"]["slot"],
+        json!(101)
+    );
+}
+
+#[tokio::test]
+async fn test_poll_bridge_rejects_other_requests() {
+    let (app, _) = bridge_app(None, WebSocketConfig::default());
+
+    let not_subscribe = json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"});
+    let response = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/v1/poll?api-key=test-key",
+            Some(not_subscribe),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let subscribe =
+        json!({"jsonrpc": "2.0", "id": 1, "method": "accountSubscribe", "params": ["Acc1"]});
+    let response = app
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/v1/poll?api-key=wrong",
+            Some(subscribe.clone()),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // No backend has a ws_url
+    let response = app
+        .oneshot(request(
+            "POST",
+            "/v1/poll?api-key=test-key",
+            Some(subscribe),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
@@ -0,0 +1,82 @@
+use axum::{body::Body, http::Request, routing::post, Router};
+use http_body_util::BodyExt;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use sol_rpc_router::{
+    config::{Backend, BackendProtocol, HttpVersion},
+    protocol::{protocol_client, BackendClients},
+    state::upstream_client,
+};
+
+/// A plaintext backend answering with the HTTP version it was spoken to in.
+async fn start_version_backend() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|req: Request<Body>| async move { format!("{:?}", req.version()) }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+async fn version_seen(client: &Client<HttpsConnector<HttpConnector>, Body>, url: &str) -> String {
+    let req = Request::post(url).body(Body::from("{}")).unwrap();
+    let resp = client.request(req).await.unwrap();
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_protocol_client_versions() {
+    let url = start_version_backend().await;
+
+    let h2c = BackendProtocol {
+        http_version: HttpVersion::Http2,
+        ..Default::default()
+    };
+    assert_eq!(
+        version_seen(&protocol_client(&h2c, None), &url).await,
+        "HTTP/2.0"
+    );
+
+    let http1 = BackendProtocol {
+        http_version: HttpVersion::Http1,
+        ..Default::default()
+    };
+    assert_eq!(
+        version_seen(&protocol_client(&http1, None), &url).await,
+        "HTTP/1.1"
+    );
+}
+
+#[tokio::test]
+async fn test_backend_clients_pick() {
+    let url = start_version_backend().await;
+    let shared = upstream_client(None);
+    let clients = BackendClients::default();
+
+    let plain = Backend {
+        label: "plain".to_string(),
+        url: url.clone(),
+        weight: 1,
+        ..Default::default()
+    };
+    let h2c = Backend {
+        label: "h2c".to_string(),
+        protocol: BackendProtocol {
+            http_version: HttpVersion::Http2,
+            ..Default::default()
+        },
+        ..plain.clone()
+    };
+
+    let client = clients.pick(&shared, &plain, false);
+    assert_eq!(version_seen(&client, &url).await, "HTTP/1.1");
+    for write in [false, true] {
+        let client = clients.pick(&shared, &h2c, write);
+        assert_eq!(version_seen(&client, &url).await, "HTTP/2.0");
+    }
+}
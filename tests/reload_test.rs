@@ -0,0 +1,158 @@
+use std::{io::Write, sync::Arc};
+
+use arc_swap::ArcSwap;
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use sol_rpc_router::{
+    config::load_config,
+    health::{BackendHealthStatus, HealthState},
+    reload::{push_config, reload_config, router_state_from_config, ConfigSource, ReloadStatus},
+};
+
+fn write_config(name: &str, content: &str) -> String {
+    let mut path = std::env::temp_dir();
+    path.push(format!("sol_rpc_router_test_reload_{}.toml", name));
+    let mut f = std::fs::File::create(&path).unwrap();
+    f.write_all(content.as_bytes()).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn config_with_backends(backends: &[(&str, &str)], extra: &str) -> String {
+    let mut content =
+        String::from("port = 8080\nmetrics_port = 9091\nredis_url = \"redis://localhost\"\n");
+    content.push_str(extra);
+    for (label, url) in backends {
+        content.push_str(&format!(
+            "\n[[backends]]\nlabel = \"{}\"\nurl = \"{}\"\nweight = 1\n",
+            label, url
+        ));
+    }
+    content
+}
+
+struct Fixture {
+    path: String,
+    state: ArcSwap<sol_rpc_router::state::RouterState>,
+    health_state: Arc<HealthState>,
+    status: ReloadStatus,
+}
+
+fn fixture(name: &str) -> Fixture {
+    let path = write_config(name, &config_with_backends(&[("a", "http://a")], ""));
+    let config = load_config(&path).unwrap();
+    let health_state = Arc::new(HealthState::new(vec!["a".to_string()]));
+    let state = ArcSwap::from_pointee(router_state_from_config(&config, health_state.clone()));
+    Fixture {
+        status: ReloadStatus::new(&path),
+        path,
+        state,
+        health_state,
+    }
+}
+
+fn client(
+) -> Client<HttpsConnector<hyper_util::client::legacy::connect::HttpConnector>, axum::body::Body> {
+    Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new())
+}
+
+#[tokio::test]
+async fn test_reload_swaps_valid_config_and_keeps_health() {
+    let f = fixture("valid");
+    f.health_state.update_status(
+        "a",
+        BackendHealthStatus {
+            healthy: false,
+            ..Default::default()
+        },
+    );
+
+    std::fs::write(
+        &f.path,
+        config_with_backends(&[("a", "http://a"), ("b", "http://b")], ""),
+    )
+    .unwrap();
+    let result = reload_config(&f.path, &client(), &f.state, &f.health_state, &f.status).await;
+    assert_eq!(result, Ok(2));
+
+    let state = f.state.load();
+    assert_eq!(state.backends.len(), 2);
+    assert!(!state.backends[0]
+        .healthy
+        .load(std::sync::atomic::Ordering::Relaxed));
+    assert!(state.backends[1]
+        .healthy
+        .load(std::sync::atomic::Ordering::Relaxed));
+
+    let report = f.status.snapshot();
+    assert_eq!(report.successes, 1);
+    assert_eq!(report.backends, 2);
+    assert!(report.last_error.is_none());
+    assert_eq!(
+        report.config_hash.as_deref(),
+        Some(load_config(&f.path).unwrap().hash.as_str())
+    );
+}
+
+#[tokio::test]
+async fn test_reload_rejects_invalid_config() {
+    let f = fixture("invalid");
+
+    std::fs::write(&f.path, "port = 8080\nbackends = [ oops").unwrap();
+    let result = reload_config(&f.path, &client(), &f.state, &f.health_state, &f.status).await;
+    assert!(result.is_err());
+
+    // Previous state is untouched
+    assert_eq!(f.state.load().backends.len(), 1);
+    let report = f.status.snapshot();
+    assert_eq!(report.failures, 1);
+    assert_eq!(report.successes, 0);
+    assert!(report.last_error.is_some());
+}
+
+#[tokio::test]
+async fn test_reload_rejects_unreachable_new_backend_when_probing() {
+    let f = fixture("probe");
+
+    std::fs::write(
+        &f.path,
+        config_with_backends(
+            &[("a", "http://a"), ("dead", "http://127.0.0.1:1")],
+            "\n[reload]\nprobe_backends = true\n\n[health_check]\ntimeout_secs = 1\n",
+        ),
+    )
+    .unwrap();
+    let result = reload_config(&f.path, &client(), &f.state, &f.health_state, &f.status).await;
+
+    let err = result.unwrap_err();
+    assert!(err.contains("Backend 'dead' unreachable"), "{}", err);
+    assert_eq!(f.state.load().backends.len(), 1);
+}
+
+#[tokio::test]
+async fn test_push_config_probes_like_file_reload() {
+    let f = fixture("push");
+
+    let document = config_with_backends(
+        &[("a", "http://a"), ("dead", "http://127.0.0.1:1")],
+        "\n[reload]\nprobe_backends = true\n\n[health_check]\ntimeout_secs = 1\n",
+    );
+    let err = push_config(&document, &client(), &f.state, &f.health_state, &f.status)
+        .await
+        .unwrap_err();
+    assert!(err.contains("Backend 'dead' unreachable"), "{}", err);
+    assert_eq!(f.state.load().backends.len(), 1);
+
+    // The config file is left alone: a later reload restores its contents
+    let document = config_with_backends(&[("a", "http://a"), ("b", "http://b")], "");
+    push_config(&document, &client(), &f.state, &f.health_state, &f.status)
+        .await
+        .unwrap();
+    assert_eq!(f.state.load().backends.len(), 2);
+    assert_eq!(f.status.snapshot().config_source, Some(ConfigSource::Admin));
+
+    reload_config(&f.path, &client(), &f.state, &f.health_state, &f.status)
+        .await
+        .unwrap();
+    assert_eq!(f.state.load().backends.len(), 1);
+    assert_eq!(f.status.snapshot().config_source, Some(ConfigSource::File));
+}
@@ -1,14 +1,19 @@
-use std::{collections::HashMap, sync::Arc};
-use std::sync::atomic::{AtomicBool, Ordering};
+// Baseline test setup predates these lints
+#![allow(clippy::field_reassign_with_default, clippy::useless_vec)]
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::sync::atomic::Ordering;
 
 use arc_swap::ArcSwap;
 use hyper_tls::HttpsConnector;
 use hyper_util::client::legacy::Client;
 use sol_rpc_router::{
-    config::{Backend, HealthCheckConfig},
+    auto_route::AutoWeights,
+    config::{Backend, HealthCheckConfig, RoutingMode},
     health::{BackendHealthStatus, HealthState},
+    methods::{builtin_idempotency, Idempotency},
     mock::MockKeyStore,
-    state::{AppState, RouterState, RuntimeBackend},
+    state::{AppState, RouterState, RuntimeBackend, UpstreamTarget, TOKEN_INDEX_GROUP},
 };
 
 fn create_test_state() -> AppState {
@@ -22,21 +27,20 @@ fn create_test_state() -> AppState {
             url: "http://primary".to_string(),
             ws_url: None,
             weight: 100,
+            ..Default::default()
         },
         Backend {
             label: "secondary".to_string(),
             url: "http://secondary".to_string(),
             ws_url: None,
             weight: 0,
+            ..Default::default()
         },
     ];
 
     let backends = backend_configs
         .iter()
-        .map(|b| RuntimeBackend {
-            config: b.clone(),
-            healthy: Arc::new(AtomicBool::new(true)),
-        })
+        .map(|b| RuntimeBackend::new(b.clone(), true))
         .collect();
 
     let backend_labels = backend_configs.iter().map(|b| b.label.clone()).collect();
@@ -48,13 +52,14 @@ fn create_test_state() -> AppState {
         health_state,
         proxy_timeout_secs: 10,
         health_check_config: HealthCheckConfig::default(),
+        ..Default::default()
     };
 
-    AppState {
+    AppState::new(
         client,
         keystore,
-        state: Arc::new(ArcSwap::from_pointee(router_state)),
-    }
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    )
 }
 
 #[test]
@@ -64,24 +69,26 @@ fn test_select_backend_weighted() {
     let keystore = Arc::new(MockKeyStore::new());
 
     let backends = vec![
-        RuntimeBackend {
-            config: Backend {
+        RuntimeBackend::new(
+            Backend {
                 label: "primary".to_string(),
                 url: "http://primary".to_string(),
                 ws_url: None,
                 weight: 1,
+                ..Default::default()
             },
-            healthy: Arc::new(AtomicBool::new(true)),
-        },
-        RuntimeBackend {
-            config: Backend {
+            true,
+        ),
+        RuntimeBackend::new(
+            Backend {
                 label: "secondary".to_string(),
                 url: "http://secondary".to_string(),
                 ws_url: None,
                 weight: 1,
+                ..Default::default()
             },
-            healthy: Arc::new(AtomicBool::new(true)),
-        },
+            true,
+        ),
     ];
 
     let health_state = Arc::new(HealthState::new(vec![
@@ -95,13 +102,14 @@ fn test_select_backend_weighted() {
         health_state,
         proxy_timeout_secs: 10,
         health_check_config: HealthCheckConfig::default(),
+        ..Default::default()
     };
 
-    let state = AppState {
+    let state = AppState::new(
         client,
         keystore,
-        state: Arc::new(ArcSwap::from_pointee(router_state)),
-    };
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    );
 
     let iterations = 1000;
     let mut primary_count = 0;
@@ -121,6 +129,23 @@ fn test_select_backend_weighted() {
     assert!(secondary_count > 400);
 }
 
+#[test]
+fn test_select_backend_round_robin_spread() {
+    let state = create_test_state();
+    let mut router_state = (**state.state.load()).clone();
+    router_state.backends[0].config.weight = 3;
+    router_state.backends[1].config.weight = 1;
+    state.state.store(Arc::new(router_state));
+
+    // Every four requests hold exactly three for primary and one for secondary
+    for _ in 0..25 {
+        let window: Vec<String> = (0..4)
+            .map(|_| state.select_backend(None).unwrap().0)
+            .collect();
+        assert_eq!(window.iter().filter(|l| *l == "primary").count(), 3);
+    }
+}
+
 #[test]
 fn test_select_backend_method_override() {
     let https = HttpsConnector::new();
@@ -128,24 +153,26 @@ fn test_select_backend_method_override() {
     let keystore = Arc::new(MockKeyStore::new());
 
     let backends = vec![
-        RuntimeBackend {
-            config: Backend {
+        RuntimeBackend::new(
+            Backend {
                 label: "primary".to_string(),
                 url: "http://primary".to_string(),
                 ws_url: None,
                 weight: 100,
+                ..Default::default()
             },
-            healthy: Arc::new(AtomicBool::new(true)),
-        },
-        RuntimeBackend {
-            config: Backend {
+            true,
+        ),
+        RuntimeBackend::new(
+            Backend {
                 label: "secondary".to_string(),
                 url: "http://secondary".to_string(),
                 ws_url: None,
                 weight: 0,
+                ..Default::default()
             },
-            healthy: Arc::new(AtomicBool::new(true)),
-        },
+            true,
+        ),
     ];
 
     let health_state = Arc::new(HealthState::new(vec![
@@ -162,13 +189,14 @@ fn test_select_backend_method_override() {
         health_state,
         proxy_timeout_secs: 10,
         health_check_config: HealthCheckConfig::default(),
+        ..Default::default()
     };
 
-    let state = AppState {
+    let state = AppState::new(
         client,
         keystore,
-        state: Arc::new(ArcSwap::from_pointee(router_state)),
-    };
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    );
 
     let (label, _) = state.select_backend(Some("eth_call")).unwrap();
     assert_eq!(label, "secondary");
@@ -178,6 +206,29 @@ fn test_select_backend_method_override() {
     assert_eq!(label, "primary");
 }
 
+#[test]
+fn test_select_backend_key_route_overrides_method_route() {
+    let state = create_test_state();
+    let mut router_state = (**state.state.load()).clone();
+    router_state
+        .method_routes
+        .insert("getProgramAccounts".to_string(), "primary".to_string());
+
+    let select = |route| {
+        router_state
+            .select_backend_routed(Some("getProgramAccounts"), route)
+            .map(|b| b.config.label.as_str())
+    };
+    assert_eq!(select(None), Some("primary"));
+    assert_eq!(select(Some("secondary")), Some("secondary"));
+    // Unknown or unhealthy targets fall back to weighted selection
+    assert_eq!(select(Some("gone")), Some("primary"));
+    router_state.backends[1]
+        .healthy
+        .store(false, Ordering::Relaxed);
+    assert_eq!(select(Some("secondary")), Some("primary"));
+}
+
 #[test]
 fn test_select_backend_unhealthy_fallback() {
     let state = create_test_state();
@@ -206,6 +257,461 @@ fn test_select_backend_all_unhealthy() {
     assert!(state.select_backend(None).is_none());
 }
 
+#[test]
+fn test_select_backend_degraded_reduced_weight() {
+    let https = HttpsConnector::new();
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(https);
+    let keystore = Arc::new(MockKeyStore::new());
+
+    let backends: Vec<RuntimeBackend> = ["fast", "slow"]
+        .iter()
+        .map(|label| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url: format!("http://{}", label),
+                    weight: 100,
+                    ..Default::default()
+                },
+                true,
+            )
+        })
+        .collect();
+    // Default degraded_weight_percent is 25 -> slow gets 25 of 125
+    backends[1].degraded.store(true, Ordering::Relaxed);
+
+    let router_state = RouterState {
+        backends,
+        health_state: Arc::new(HealthState::new(vec![
+            "fast".to_string(),
+            "slow".to_string(),
+        ])),
+        ..Default::default()
+    };
+    let state = AppState::new(
+        client,
+        keystore,
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    );
+
+    let slow = (0..10_000)
+        .filter(|_| state.select_backend(None).unwrap().0 == "slow")
+        .count();
+    assert!(
+        (1_200..2_800).contains(&slow),
+        "slow selected {} times",
+        slow
+    );
+}
+
+#[test]
+fn test_select_backend_respects_exclude_methods() {
+    let state = create_test_state();
+    let mut router_state = (**state.state.load()).clone();
+    router_state.backends[0].config.exclude_methods = vec!["getProgramAccounts".to_string()];
+    router_state
+        .method_routes
+        .insert("getProgramAccounts".to_string(), "primary".to_string());
+    state.state.store(Arc::new(router_state));
+
+    // Neither the method route nor weighted selection may pick the excluding backend
+    for _ in 0..50 {
+        let (label, _) = state.select_backend(Some("getProgramAccounts")).unwrap();
+        assert_eq!(label, "secondary");
+    }
+    let (label, _) = state.select_backend(Some("getSlot")).unwrap();
+    assert_eq!(label, "primary");
+}
+
+#[test]
+fn test_hedge_pair_picks_two_fastest() {
+    let backends: Vec<RuntimeBackend> = [("a", 900), ("b", 300), ("c", 0), ("d", 500)]
+        .iter()
+        .map(|(label, latency_ms)| {
+            let backend = RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url: format!("http://{}", label),
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            );
+            if *latency_ms > 0 {
+                backend.record_latency(Duration::from_millis(*latency_ms));
+            }
+            backend
+        })
+        .collect();
+    let mut state = RouterState {
+        backends,
+        ..Default::default()
+    };
+    fn labels(state: &RouterState) -> Option<[&str; 2]> {
+        state
+            .hedge_pair("getSlot")
+            .map(|pair| pair.map(|b| b.config.label.as_str()))
+    }
+
+    // Disabled by default
+    assert_eq!(labels(&state), None);
+
+    state.hedging.enabled = true;
+    // "c" has no samples yet, so it is tried first
+    assert_eq!(labels(&state), Some(["c", "b"]));
+    assert_eq!(state.hedge_pair("getBalance").map(|_| ()), None);
+
+    state.backends[2].healthy.store(false, Ordering::Relaxed);
+    assert_eq!(labels(&state), Some(["b", "d"]));
+
+    // Only safe methods are hedged
+    state.hedging.methods.push("mintCompressedNft".to_string());
+    assert_eq!(state.hedge_pair("mintCompressedNft").map(|_| ()), None);
+    state
+        .method_idempotency
+        .insert("mintCompressedNft".to_string(), Idempotency::Safe);
+    assert!(state.hedge_pair("mintCompressedNft").is_some());
+
+    // Pinned methods are never hedged
+    state
+        .method_routes
+        .insert("getSlot".to_string(), "a".to_string());
+    assert_eq!(labels(&state), None);
+    state.method_routes.clear();
+
+    // Fewer than two backends in rotation
+    state.backends[1].healthy.store(false, Ordering::Relaxed);
+    state.backends[3].healthy.store(false, Ordering::Relaxed);
+    assert_eq!(labels(&state), None);
+}
+
+#[test]
+fn test_method_idempotency() {
+    assert_eq!(builtin_idempotency("getBalance"), Some(Idempotency::Safe));
+    assert_eq!(
+        builtin_idempotency("sendTransaction"),
+        Some(Idempotency::Idempotent)
+    );
+    assert_eq!(
+        builtin_idempotency("requestAirdrop"),
+        Some(Idempotency::NonIdempotent)
+    );
+    assert_eq!(
+        builtin_idempotency("accountSubscribe"),
+        Some(Idempotency::NonIdempotent)
+    );
+    assert_eq!(builtin_idempotency("getAssetsByOwner"), None);
+
+    let mut state = RouterState::default();
+    // Unclassified methods fail over, but are never hedged or cached
+    assert_eq!(
+        state.idempotency("getAssetsByOwner"),
+        Idempotency::Idempotent
+    );
+    state
+        .method_idempotency
+        .insert("getAssetsByOwner".to_string(), Idempotency::Safe);
+    state
+        .method_idempotency
+        .insert("mintCompressedNft".to_string(), Idempotency::NonIdempotent);
+    assert_eq!(state.idempotency("getAssetsByOwner"), Idempotency::Safe);
+
+    assert!(state.may_resend(Some("sendTransaction"), b""));
+    assert!(state.may_resend(Some("getAssetsByOwner"), b""));
+    assert!(!state.may_resend(Some("requestAirdrop"), b""));
+    assert!(!state.may_resend(Some("mintCompressedNft"), b""));
+    // A batch is resent only if every request in it may be
+    let batch = |methods: &[&str]| {
+        let requests: Vec<String> = methods
+            .iter()
+            .map(|m| format!(r#"{{"jsonrpc":"2.0","method":"{}","id":1}}"#, m))
+            .collect();
+        format!("[{}]", requests.join(","))
+    };
+    assert!(state.may_resend(None, batch(&["getSlot", "sendTransaction"]).as_bytes()));
+    assert!(!state.may_resend(None, batch(&["getSlot", "mintCompressedNft"]).as_bytes()));
+    assert!(state.may_resend(None, b"not json"));
+}
+
+#[test]
+fn test_broadcast_targets_fastest_first() {
+    let backends: Vec<RuntimeBackend> = [("a", 900), ("b", 300), ("c", 500)]
+        .iter()
+        .map(|(label, latency_ms)| {
+            let backend = RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url: format!("http://{}", label),
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            );
+            backend.record_latency(Duration::from_millis(*latency_ms));
+            backend
+        })
+        .collect();
+    let mut state = RouterState {
+        backends,
+        ..Default::default()
+    };
+    fn labels(state: &RouterState) -> Vec<&str> {
+        state
+            .broadcast_targets("sendTransaction")
+            .iter()
+            .map(|b| b.config.label.as_str())
+            .collect()
+    }
+
+    assert_eq!(labels(&state), ["b", "c", "a"]);
+    state.broadcast.max_backends = 2;
+    assert_eq!(labels(&state), ["b", "c"]);
+
+    // Only backends in rotation that accept the method
+    state.backends[1].healthy.store(false, Ordering::Relaxed);
+    state.backends[2].config.exclude_methods = vec!["sendTransaction".to_string()];
+    assert_eq!(labels(&state), ["a"]);
+}
+
+#[test]
+fn test_least_latency_prefers_fastest() {
+    let backends: Vec<RuntimeBackend> = [("a", 900), ("b", 300), ("c", 330)]
+        .iter()
+        .map(|(label, latency_ms)| {
+            let backend = RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url: format!("http://{}", label),
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            );
+            backend.record_latency(Duration::from_millis(*latency_ms));
+            backend
+        })
+        .collect();
+    let mut state = RouterState {
+        backends,
+        ..Default::default()
+    };
+    state.routing.mode = RoutingMode::LeastLatency;
+    state.routing.min_share_percent = 0;
+    fn picks(state: &RouterState) -> HashMap<String, usize> {
+        let mut picks = HashMap::new();
+        for _ in 0..200 {
+            let backend = state.select_backend(Some("getBalance")).unwrap();
+            *picks.entry(backend.config.label.clone()).or_default() += 1;
+        }
+        picks
+    }
+
+    // "c" is within the 20% tolerance of "b", so weights break the tie
+    let counts = picks(&state);
+    assert!(!counts.contains_key("a"));
+    assert!(counts["b"] > 0 && counts["c"] > 0);
+
+    state.routing.latency_tolerance_percent = 0;
+    assert_eq!(picks(&state).keys().collect::<Vec<_>>(), ["b"]);
+
+    // Unmeasured backends go first so they get measured
+    state.backends[0].latency_us.store(0, Ordering::Relaxed);
+    assert_eq!(picks(&state).keys().collect::<Vec<_>>(), ["a"]);
+
+    // Every backend keeps min_share_percent of the traffic
+    state.backends[0].record_latency(Duration::from_millis(900));
+    state.routing.min_share_percent = 50;
+    assert!(picks(&state).contains_key("a"));
+}
+
+#[test]
+fn test_select_auto_uses_learned_shares() {
+    let backends = ["a", "b", "c"]
+        .iter()
+        .map(|label| {
+            RuntimeBackend::new(
+                Backend {
+                    label: label.to_string(),
+                    url: format!("http://{}", label),
+                    weight: 1,
+                    ..Default::default()
+                },
+                true,
+            )
+        })
+        .collect();
+    let mut state = RouterState {
+        backends,
+        ..Default::default()
+    };
+    // "c" has no learned share yet and falls back to the 5% minimum
+    let weights: AutoWeights = HashMap::from([(
+        "getBlock".to_string(),
+        HashMap::from([("a".to_string(), 9_000), ("b".to_string(), 500)]),
+    )]);
+
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for _ in 0..10_000 {
+        let backend = state.select_auto("getBlock", &weights).unwrap();
+        *counts.entry(backend.config.label.clone()).or_default() += 1;
+    }
+    assert!(counts["a"] > 8_500, "{:?}", counts);
+    assert!(counts["b"] > 200 && counts["c"] > 200, "{:?}", counts);
+
+    // Operator pins override learned shares
+    state
+        .method_routes
+        .insert("getBlock".to_string(), "c".to_string());
+    for _ in 0..50 {
+        let backend = state.select_auto("getBlock", &weights).unwrap();
+        assert_eq!(backend.config.label, "c");
+    }
+}
+
+#[test]
+fn test_select_backend_prefers_router_region() {
+    let backends = [
+        ("fra-1", Some("fra"), 0),
+        ("fra-2", Some("fra"), 0),
+        ("ams-1", Some("ams"), 40),
+        ("nyc-1", Some("nyc"), 90),
+        ("nyc-2", Some("nyc"), 10),
+        ("any-1", None, 0),
+    ]
+    .iter()
+    .map(|(label, region, latency_ms)| {
+        let backend = RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url: format!("http://{}", label),
+                weight: 1,
+                region: region.map(String::from),
+                ..Default::default()
+            },
+            true,
+        );
+        if *latency_ms > 0 {
+            backend.record_latency(Duration::from_millis(*latency_ms));
+        }
+        backend
+    })
+    .collect();
+    let mut state = RouterState {
+        backends,
+        ..Default::default()
+    };
+    let picks = |state: &RouterState| -> Vec<String> {
+        (0..50)
+            .map(|_| state.select_backend(Some("getSlot")).unwrap())
+            .map(|b| b.config.label.clone())
+            .collect()
+    };
+
+    // Without a router region every backend is in play
+    assert!(picks(&state).iter().any(|l| !l.starts_with("fra")));
+
+    state.routing.region = Some("fra".to_string());
+    assert!(picks(&state).iter().all(|l| l.starts_with("fra")));
+    assert!(!state.is_cross_region(&state.backends[0]));
+    assert!(state.is_cross_region(&state.backends[5]));
+
+    // Local region down: spill to the region with the lowest average latency
+    // (ams 40ms beats nyc's 50ms average; unmeasured backends come last)
+    state.backends[0].healthy.store(false, Ordering::Relaxed);
+    state.backends[1].healthy.store(false, Ordering::Relaxed);
+    assert!(picks(&state).iter().all(|l| l == "ams-1"));
+
+    state.backends[2].healthy.store(false, Ordering::Relaxed);
+    assert!(picks(&state).iter().all(|l| l.starts_with("nyc")));
+
+    // Back home as soon as a local backend recovers
+    state.backends[1].healthy.store(true, Ordering::Relaxed);
+    assert!(picks(&state).iter().all(|l| l == "fra-2"));
+}
+
+#[test]
+fn test_drained_and_maintenance_backends_get_no_traffic() {
+    let backend = |label: &str, drain: bool, maintenance: bool| {
+        RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url: format!("http://{}", label),
+                ws_url: Some(format!("ws://{}", label)),
+                weight: 1,
+                drain,
+                maintenance,
+                ..Default::default()
+            },
+            true,
+        )
+    };
+    let state = RouterState {
+        backends: vec![
+            backend("draining", true, false),
+            backend("serving", false, false),
+            backend("maintenance", false, true),
+        ],
+        method_routes: HashMap::from([("getSlot".to_string(), "draining".to_string())]),
+        ..Default::default()
+    };
+
+    for _ in 0..50 {
+        // Method routes to a draining backend fall back to weighted selection
+        let picked = state.select_backend(Some("getSlot")).unwrap();
+        assert_eq!(picked.config.label, "serving");
+        let picked = state.select_ws_backend().unwrap();
+        assert_eq!(picked.config.label, "serving");
+    }
+}
+
+#[test]
+fn test_token_accounts_prefer_token_index() {
+    let backend = |label: &str, groups: &[&str]| {
+        RuntimeBackend::new(
+            Backend {
+                label: label.to_string(),
+                url: format!("http://{}", label),
+                weight: 1,
+                groups: groups.iter().map(|g| g.to_string()).collect(),
+                ..Default::default()
+            },
+            true,
+        )
+    };
+    let state = RouterState {
+        backends: vec![
+            backend("plain", &[]),
+            backend("indexed", &["archival", TOKEN_INDEX_GROUP]),
+        ],
+        ..Default::default()
+    };
+
+    // Learned shares cannot send it to the unindexed backend either
+    let weights: AutoWeights = HashMap::from([(
+        "getTokenAccountsByOwner".to_string(),
+        HashMap::from([("plain".to_string(), 9_000), ("indexed".to_string(), 1_000)]),
+    )]);
+    let mut others = 0;
+    for _ in 0..50 {
+        let picked = state
+            .select_backend(Some("getTokenAccountsByOwner"))
+            .unwrap();
+        assert_eq!(picked.config.label, "indexed");
+        let picked = state.select_auto("getTokenAccountsByOwner", &weights);
+        assert_eq!(picked.unwrap().config.label, "indexed");
+        let picked = state.select_backend(Some("getBalance")).unwrap();
+        others += (picked.config.label == "plain") as u32;
+    }
+    assert!(others > 0);
+
+    // Without an indexed backend in rotation, any backend serves it
+    state.backends[1].healthy.store(false, Ordering::Relaxed);
+    let picked = state
+        .select_backend(Some("getTokenAccountsByOwner"))
+        .unwrap();
+    assert_eq!(picked.config.label, "plain");
+}
+
 // --- WebSocket backend selection tests ---
 
 fn create_ws_test_state() -> AppState {
@@ -219,21 +725,20 @@ fn create_ws_test_state() -> AppState {
             url: "http://ws-a".to_string(),
             ws_url: Some("ws://ws-a".to_string()),
             weight: 1,
+            ..Default::default()
         },
         Backend {
             label: "ws-b".to_string(),
             url: "http://ws-b".to_string(),
             ws_url: Some("ws://ws-b".to_string()),
             weight: 1,
+            ..Default::default()
         },
     ];
 
     let backends = backend_configs
         .iter()
-        .map(|b| RuntimeBackend {
-            config: b.clone(),
-            healthy: Arc::new(AtomicBool::new(true)),
-        })
+        .map(|b| RuntimeBackend::new(b.clone(), true))
         .collect();
 
     let backend_labels = backend_configs.iter().map(|b| b.label.clone()).collect();
@@ -245,13 +750,14 @@ fn create_ws_test_state() -> AppState {
         health_state,
         proxy_timeout_secs: 10,
         health_check_config: HealthCheckConfig::default(),
+        ..Default::default()
     };
 
-    AppState {
+    AppState::new(
         client,
         keystore,
-        state: Arc::new(ArcSwap::from_pointee(router_state)),
-    }
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    )
 }
 
 #[test]
@@ -293,3 +799,76 @@ fn test_select_ws_backend_unhealthy_excluded() {
         assert_eq!(label, "ws-b");
     }
 }
+
+#[test]
+fn test_upstream_target_builds_uris() {
+    let target = UpstreamTarget::parse("https://rpc.example.com:8443/").unwrap();
+    assert_eq!(target.host, "rpc.example.com:8443");
+    let uri = |t: &UpstreamTarget, path, query| t.uri_for(path, query).unwrap().to_string();
+
+    assert_eq!(
+        uri(&target, "/", Some("api-key=secret")),
+        "https://rpc.example.com:8443/"
+    );
+    assert_eq!(
+        uri(
+            &target,
+            "/v0/slot",
+            Some("api-key=secret&commitment=finalized")
+        ),
+        "https://rpc.example.com:8443/v0/slot?commitment=finalized"
+    );
+
+    // Provider key embedded in the backend URL is kept; the client's is dropped
+    let target = UpstreamTarget::parse("https://mainnet.provider.io/rpc?api-key=provider").unwrap();
+    assert_eq!(target.host, "mainnet.provider.io");
+    assert_eq!(
+        uri(&target, "/", Some("api-key=client&x=1")),
+        "https://mainnet.provider.io/rpc?api-key=provider&x=1"
+    );
+
+    assert!(UpstreamTarget::parse("not a url").is_none());
+}
+
+#[test]
+fn test_method_paths_override_backend_path() {
+    let backend = RuntimeBackend::new(
+        Backend {
+            label: "provider".to_string(),
+            url: "https://mainnet.provider.io/rpc?api-key=provider".to_string(),
+            weight: 1,
+            method_paths: [
+                ("getTransactions", "/v0/transactions"),
+                ("getAssetsByOwner", "/das/{method}/"),
+            ]
+            .into_iter()
+            .map(|(method, path)| (method.to_string(), path.to_string()))
+            .collect(),
+            ..Default::default()
+        },
+        true,
+    );
+    let uri = |method| {
+        backend
+            .uri_for(method, "/", Some("api-key=client"))
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(
+        uri(Some("getTransactions")),
+        "https://mainnet.provider.io/v0/transactions?api-key=provider"
+    );
+    assert_eq!(
+        uri(Some("getAssetsByOwner")),
+        "https://mainnet.provider.io/das/getAssetsByOwner?api-key=provider"
+    );
+    assert_eq!(
+        uri(Some("getSlot")),
+        "https://mainnet.provider.io/rpc?api-key=provider"
+    );
+    assert_eq!(
+        uri(None),
+        "https://mainnet.provider.io/rpc?api-key=provider"
+    );
+}
@@ -0,0 +1,48 @@
+use sol_rpc_router::{config::RuntimeConfig, runtime};
+
+#[test]
+fn test_runtime_worker_threads() {
+    let config = RuntimeConfig {
+        worker_threads: 3,
+        ..RuntimeConfig::default()
+    };
+    let rt = runtime::build(&config).unwrap();
+    assert_eq!(rt.metrics().num_workers(), 3);
+    assert_eq!(rt.block_on(async { 1 + 1 }), 2);
+}
+
+#[test]
+fn test_background_runtime() {
+    let thread_name = || std::thread::current().name().map(str::to_string);
+    let rt = runtime::build(&RuntimeConfig::default()).unwrap();
+
+    // Shared with the proxy by default
+    let shared = rt.block_on(async {
+        let background = runtime::background(&RuntimeConfig::default()).unwrap();
+        background
+            .spawn(async move { thread_name() })
+            .await
+            .unwrap()
+    });
+    assert_ne!(shared.as_deref(), Some("router-background"));
+
+    // Isolated loops run on their own thread, even while the proxy's are busy
+    let config = RuntimeConfig {
+        isolate_background: true,
+        ..RuntimeConfig::default()
+    };
+    let isolated = rt.block_on(async {
+        let background = runtime::background(&config).unwrap();
+        let ticks = background.spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_millis(10));
+            for _ in 0..3 {
+                interval.tick().await;
+            }
+            thread_name()
+        });
+        // Holds this thread without yielding
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        ticks.await.unwrap()
+    });
+    assert_eq!(isolated.as_deref(), Some("router-background"));
+}
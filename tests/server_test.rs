@@ -0,0 +1,77 @@
+use std::{sync::Arc, time::Duration};
+
+use arc_swap::ArcSwap;
+use axum::{body::Body, http::StatusCode};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use sol_rpc_router::{
+    mock::MockKeyStore,
+    server::{http_router, start_health_checks, start_listeners, TaskHandle},
+    state::{AppState, RouterState},
+};
+use tokio::{net::TcpListener, time::timeout};
+
+fn app_state() -> Arc<AppState> {
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    Arc::new(AppState::new(
+        client,
+        Arc::new(MockKeyStore::new()),
+        Arc::new(ArcSwap::from_pointee(RouterState::default())),
+    ))
+}
+
+#[tokio::test]
+async fn test_task_handle_shutdown_and_errors() {
+    // A loop that never ends on its own still stops
+    let forever = TaskHandle::spawn_loop("forever", std::future::pending());
+    assert_eq!(forever.name(), "forever");
+    assert!(!forever.is_finished());
+    let stopped = timeout(Duration::from_secs(1), forever.shutdown()).await;
+    assert_eq!(stopped.unwrap(), Ok(()));
+
+    // Cancelling the token from elsewhere works the same
+    let cooperative = TaskHandle::spawn("cooperative", |token| async move {
+        token.cancelled().await;
+        Ok(())
+    });
+    cooperative.token().cancel();
+    assert_eq!(cooperative.wait().await, Ok(()));
+
+    // Errors and panics come back from wait
+    let failing = TaskHandle::spawn("failing", |_| async { Err("boom".to_string()) });
+    assert_eq!(failing.wait().await, Err("boom".to_string()));
+    let panicking = TaskHandle::spawn("panicking", |_| async { panic!("boom") });
+    assert!(panicking
+        .wait()
+        .await
+        .unwrap_err()
+        .starts_with("panicking task failed"));
+}
+
+#[tokio::test]
+async fn test_health_checks_shut_down() {
+    let health_checks = start_health_checks(&app_state());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!health_checks.is_finished());
+
+    let stopped = timeout(Duration::from_secs(1), health_checks.shutdown()).await;
+    assert_eq!(stopped.unwrap(), Ok(()));
+}
+
+#[tokio::test]
+async fn test_listeners_serve_until_shut_down() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = start_listeners("HTTP", vec![listener], http_router(app_state()));
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build_http::<Body>();
+    let url = format!("http://{}/health", addr);
+    let response = client.get(url.parse().unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    drop(response);
+    drop(client);
+
+    let stopped = timeout(Duration::from_secs(5), server.shutdown()).await;
+    assert_eq!(stopped.unwrap(), Ok(()));
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
@@ -0,0 +1,97 @@
+use axum::http::HeaderMap;
+use sol_rpc_router::{
+    config::{Backend, SigningConfig},
+    signing::{
+        apply_backend_headers, apply_signature, clock_skew, sign, verify_signature, SignatureError,
+    },
+};
+
+fn config() -> SigningConfig {
+    SigningConfig {
+        key: Some("shared-gateway-secret".to_string()),
+        key_id: Some("router-1".to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_sign_is_deterministic() {
+    let a = sign("secret", 1_700_000_000, b"{}");
+    assert_eq!(a, sign("secret", 1_700_000_000, b"{}"));
+    assert_ne!(a, sign("secret", 1_700_000_001, b"{}"));
+    assert_ne!(a, sign("other", 1_700_000_000, b"{}"));
+    assert_eq!(a.len(), 64);
+}
+
+#[test]
+fn test_apply_and_verify_round_trip() {
+    let config = config();
+    let mut headers = HeaderMap::new();
+    apply_signature(&config, &mut headers, b"body", 1_700_000_000);
+
+    assert_eq!(headers["x-signature-timestamp"], "1700000000");
+    assert_eq!(headers["x-signature-key-id"], "router-1");
+    assert_eq!(
+        verify_signature(&config, &headers, b"body", 1_700_000_010),
+        Ok(())
+    );
+    assert_eq!(
+        verify_signature(&config, &headers, b"tampered", 1_700_000_010),
+        Err(SignatureError::Mismatch)
+    );
+}
+
+#[test]
+fn test_verify_enforces_clock_skew() {
+    let config = config();
+    let mut headers = HeaderMap::new();
+    apply_signature(&config, &mut headers, b"body", 1_700_000_000);
+
+    // Default tolerance is 30s in either direction
+    assert!(verify_signature(&config, &headers, b"body", 1_699_999_970).is_ok());
+    assert_eq!(
+        verify_signature(&config, &headers, b"body", 1_700_000_031),
+        Err(SignatureError::Expired)
+    );
+    assert_eq!(
+        verify_signature(&config, &HeaderMap::new(), b"body", 1_700_000_000),
+        Err(SignatureError::Missing)
+    );
+}
+
+#[test]
+fn test_clock_skew_from_date_header() {
+    let now = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "date",
+        httpdate::fmt_http_date(now - std::time::Duration::from_secs(45))
+            .parse()
+            .unwrap(),
+    );
+    assert_eq!(clock_skew(&headers, now), Some(45));
+    assert_eq!(clock_skew(&HeaderMap::new(), now), None);
+}
+
+#[test]
+fn test_apply_backend_headers() {
+    let backend = Backend {
+        headers: [
+            ("x-api-key".to_string(), "provider-key".to_string()),
+            ("authorization".to_string(), "Bearer token".to_string()),
+        ]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert("authorization", "Bearer client".parse().unwrap());
+    headers.insert("content-type", "application/json".parse().unwrap());
+    apply_backend_headers(&backend, &mut headers);
+
+    assert_eq!(headers["x-api-key"], "provider-key");
+    // Configured headers replace the client's
+    assert_eq!(headers["authorization"], "Bearer token");
+    assert!(headers["authorization"].is_sensitive());
+    assert_eq!(headers["content-type"], "application/json");
+}
@@ -0,0 +1,104 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{routing::post, Json, Router};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use serde_json::{json, Value};
+use sol_rpc_router::{
+    config::{Backend, Commitment},
+    health::{BackendHealthStatus, HealthState},
+    slot_feed::{fetch_slot, slot_source, SlotFeed},
+    state::{RouterState, RuntimeBackend},
+};
+
+fn backend(label: &str, healthy: bool) -> RuntimeBackend {
+    RuntimeBackend::new(
+        Backend {
+            label: label.to_string(),
+            url: format!("http://{}.invalid", label),
+            weight: 1,
+            ..Default::default()
+        },
+        healthy,
+    )
+}
+
+#[test]
+fn test_slot_feed_never_goes_backwards() {
+    let feed = SlotFeed::new();
+    let mut slots = feed.subscribe();
+
+    feed.record(Commitment::Confirmed, 100);
+    assert!(slots.has_changed().unwrap());
+    assert_eq!(slots.borrow_and_update().confirmed, Some(100));
+
+    // A source that is behind does not replay older slots
+    feed.record(Commitment::Confirmed, 98);
+    feed.record(Commitment::Confirmed, 100);
+    assert!(!slots.has_changed().unwrap());
+
+    feed.record(Commitment::Finalized, 70);
+    let view = feed.current();
+    assert_eq!(view.at(Commitment::Confirmed), Some(100));
+    assert_eq!(view.at(Commitment::Finalized), Some(70));
+    assert_eq!(view.at(Commitment::Processed), None);
+}
+
+#[test]
+fn test_slot_source_follows_furthest_ahead_healthy_backend() {
+    let labels = ["a", "b", "c"].map(String::from).to_vec();
+    let health_state = Arc::new(HealthState::new(labels));
+    let router_state = RouterState {
+        backends: vec![backend("a", true), backend("b", true), backend("c", false)],
+        health_state: health_state.clone(),
+        ..Default::default()
+    };
+
+    // Nothing reported yet: first healthy backend
+    assert_eq!(slot_source(&router_state).unwrap().config.label, "a");
+
+    for (label, slot) in [("a", 100), ("b", 105), ("c", 200)] {
+        health_state.update_status(
+            label,
+            BackendHealthStatus {
+                last_slot: Some(slot),
+                ..Default::default()
+            },
+        );
+    }
+    // "c" is furthest ahead but out of rotation
+    assert_eq!(slot_source(&router_state).unwrap().config.label, "b");
+}
+
+#[tokio::test]
+async fn test_fetch_slot_requests_commitment() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            post(|Json(request): Json<Value>| async move {
+                let slot = match request["params"][0]["commitment"].as_str() {
+                    Some("finalized") => 68,
+                    Some("confirmed") => 100,
+                    _ => 101,
+                };
+                Json(json!({"jsonrpc": "2.0", "result": slot, "id": 1}))
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let backend = Backend {
+        label: "a".to_string(),
+        url: format!("http://{}", addr),
+        weight: 1,
+        ..Default::default()
+    };
+    let timeout = Duration::from_secs(1);
+    let fetch = |c| fetch_slot(&client, &backend, c, timeout);
+    assert_eq!(fetch(Commitment::Processed).await, Ok(101));
+    assert_eq!(fetch(Commitment::Confirmed).await, Ok(100));
+    assert_eq!(fetch(Commitment::Finalized).await, Ok(68));
+}
@@ -0,0 +1,59 @@
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Body as _;
+use sol_rpc_router::sniff::{sniff_method, PrefixedBody, Sniff};
+
+#[test]
+fn test_sniff_method() {
+    let cases: &[(&str, Sniff)] = &[
+        (
+            r#"{"jsonrpc":"2.0","method":"getSlot","id":1}"#,
+            Sniff::Method("getSlot"),
+        ),
+        (r#" { "method" : "getSlot""#, Sniff::Method("getSlot")),
+        // Keys before the method are skipped whatever they hold
+        (
+            r#"{"params":[{"a":"}]"},[1,2],null],"id":"x\"y","n":-1.5e3,"method":"getBlock""#,
+            Sniff::Method("getBlock"),
+        ),
+        (
+            r#"{"params":{"method":"nested"},"method":"getSlot"}"#,
+            Sniff::Method("getSlot"),
+        ),
+        // Not there yet
+        ("", Sniff::Incomplete),
+        (r#"{"jsonrpc":"2.0","#, Sniff::Incomplete),
+        (r#"{"method":"getSl"#, Sniff::Incomplete),
+        (r#"{"params":[1,{"a":"b"#, Sniff::Incomplete),
+        (r#"{"id":12"#, Sniff::Incomplete),
+        // No usable method
+        (r#"[{"method":"getSlot"}]"#, Sniff::NoMethod),
+        (r#"{"jsonrpc":"2.0","id":1}"#, Sniff::NoMethod),
+        (r#"{"method":null}"#, Sniff::NoMethod),
+        (r#"{"method":7}"#, Sniff::NoMethod),
+        ("{}", Sniff::NoMethod),
+        ("not json", Sniff::NoMethod),
+        (r#"{"id" 1}"#, Sniff::NoMethod),
+        // Escapes are left to a full parse
+        (r#"{"method":"get\u0053lot"}"#, Sniff::Unsure),
+        (r#"{"meth\u006fd":"getSlot"}"#, Sniff::Unsure),
+    ];
+    for (body, expected) in cases {
+        assert_eq!(sniff_method(body.as_bytes()), *expected, "{}", body);
+    }
+}
+
+#[tokio::test]
+async fn test_prefixed_body() {
+    let body = PrefixedBody::new(
+        Bytes::from_static(b"{\"method\""),
+        Full::new(Bytes::from_static(b":\"getSlot\"}")),
+    );
+    assert_eq!(body.size_hint().exact(), Some(20));
+    assert!(!body.is_end_stream());
+    let bytes = body.collect().await.unwrap().to_bytes();
+    assert_eq!(bytes, &b"{\"method\":\"getSlot\"}"[..]);
+
+    let empty = PrefixedBody::new(Bytes::new(), Full::new(Bytes::new()));
+    assert!(empty.is_end_stream());
+}
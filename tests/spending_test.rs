@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use sol_rpc_router::{
+    config::SpendingCapsConfig,
+    keystore::KeyInfo,
+    mock::MockSpendStore,
+    redact::key_fingerprint,
+    spending::{request_cost, SpendTracker},
+};
+
+fn capped_key(cap: f64, tier: Option<&str>) -> KeyInfo {
+    KeyInfo {
+        owner: "prepaid".to_string(),
+        rate_limit: 100,
+        tier: tier.map(str::to_string),
+        spend_cap: Some(cap),
+        ..Default::default()
+    }
+}
+
+fn tracker(store: &MockSpendStore) -> SpendTracker {
+    let config = SpendingCapsConfig {
+        enabled: true,
+        tier_prices: HashMap::from([("pro".to_string(), 0.5)]),
+        default_price: 1.0,
+        throttled_rate_limit: 2,
+        ..Default::default()
+    };
+    SpendTracker::new(config, Arc::new(store.clone()))
+}
+
+#[test]
+fn test_request_cost() {
+    assert_eq!(request_cost(Some("getSlot"), b""), 1);
+    assert_eq!(request_cost(Some("getProgramAccounts"), b""), 10);
+    assert_eq!(request_cost(Some("madeUpMethod"), b""), 1);
+
+    let batch = br#"[{"method":"getBlock"},{"method":"getSlot"},{"id":3}]"#;
+    assert_eq!(request_cost(None, batch), 7);
+    assert_eq!(request_cost(None, b"[]"), 1);
+    assert_eq!(request_cost(None, b"not json"), 1);
+}
+
+#[test]
+fn test_price_by_tier() {
+    let tracker = tracker(&MockSpendStore::new());
+    assert_eq!(tracker.price(Some("pro")), 0.5);
+    assert_eq!(tracker.price(Some("unpriced")), 1.0);
+    assert_eq!(tracker.price(None), 1.0);
+}
+
+#[test]
+fn test_keys_at_cap_are_throttled() {
+    let tracker = tracker(&MockSpendStore::new());
+    let key = capped_key(10.0, None);
+    let now = Instant::now();
+
+    assert!(tracker.admit("key-a", &key, now));
+    tracker.charge("key-a", 9);
+    assert_eq!(tracker.spent("key-a"), Some(9.0));
+    assert!(tracker.admit("key-a", &key, now));
+    tracker.charge("key-a", 1);
+
+    // At the cap: `throttled_rate_limit` requests per second
+    assert!(tracker.admit("key-a", &key, now));
+    assert!(tracker.admit("key-a", &key, now));
+    assert!(!tracker.admit("key-a", &key, now));
+    assert!(tracker.admit("key-a", &key, now + Duration::from_secs(1)));
+
+    // Raising the cap lifts the throttle
+    assert!(tracker.admit("key-a", &capped_key(20.0, None), now));
+    assert!(tracker.admit("key-a", &capped_key(20.0, None), now));
+
+    // Keys without a cap are never tracked
+    let uncapped = KeyInfo::default();
+    for _ in 0..5 {
+        assert!(tracker.admit("key-b", &uncapped, now));
+    }
+    tracker.charge("key-b", 100);
+    assert_eq!(tracker.spent("key-b"), None);
+}
+
+#[tokio::test]
+async fn test_flush_reports_keys_reaching_their_cap() {
+    let store = MockSpendStore::new();
+    let tracker = tracker(&store);
+    let now = Instant::now();
+    tracker.admit("key-a", &capped_key(5.0, Some("pro")), now);
+    tracker.admit("key-b", &capped_key(100.0, None), now);
+    tracker.charge("key-a", 12);
+    tracker.charge("key-b", 1);
+
+    let events = tracker.flush(1_700_000_000).await.unwrap();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.event, "spend_cap_reached");
+    assert_eq!(event.key, key_fingerprint("key-a"));
+    assert_eq!(event.owner, "prepaid");
+    assert_eq!(event.cap, 5.0);
+    assert_eq!(event.spent, 6.0);
+    assert_eq!(event.throttled_rate_limit, 2);
+    assert_eq!(store.totals.lock().unwrap()["key-a"], 12);
+
+    // Only the flush that crosses the cap reports it
+    tracker.charge("key-a", 20);
+    assert!(tracker.flush(1_700_000_010).await.unwrap().is_empty());
+    assert_eq!(tracker.spent("key-a"), Some(16.0));
+}
+
+#[tokio::test]
+async fn test_flush_learns_totals_and_keeps_failed_charges() {
+    let store = MockSpendStore::new();
+    // Charges made through another router instance
+    store.totals.lock().unwrap().insert("key-a".to_string(), 50);
+    let tracker = tracker(&store);
+    let key = capped_key(40.0, None);
+    let now = Instant::now();
+    assert!(tracker.admit("key-a", &key, now));
+    tracker.charge("key-a", 1);
+
+    *store.fail.lock().unwrap() = true;
+    assert!(tracker.flush(0).await.is_err());
+    assert_eq!(tracker.spent("key-a"), Some(1.0));
+
+    *store.fail.lock().unwrap() = false;
+    assert!(tracker.flush(0).await.unwrap().is_empty());
+    assert_eq!(store.totals.lock().unwrap()["key-a"], 51);
+    assert_eq!(tracker.spent("key-a"), Some(51.0));
+    assert!(tracker.admit("key-a", &key, now));
+    assert!(tracker.admit("key-a", &key, now));
+    assert!(!tracker.admit("key-a", &key, now));
+}
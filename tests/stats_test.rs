@@ -0,0 +1,81 @@
+use std::{sync::Arc, time::Duration};
+
+use sol_rpc_router::stats::{RoutingStats, WINDOW_SECS};
+
+#[test]
+fn test_success_rate_and_percentiles() {
+    let stats = RoutingStats::new();
+    let now = 1_000_000;
+    for i in 0..100 {
+        // 98 fast requests, one slow one and one failure
+        let (success, ms) = match i {
+            0 => (false, 5),
+            1 => (true, 4_000),
+            _ => (true, 20),
+        };
+        stats.record("getBlock", "a", success, Duration::from_millis(ms), now);
+    }
+
+    let block = stats.get("getBlock", "a", now).unwrap();
+    assert_eq!(block.requests, 100);
+    assert!((block.success_rate - 0.99).abs() < 1e-9);
+    assert_eq!(block.p50_ms, Some(25));
+    assert_eq!(block.p99_ms, Some(5_000));
+    assert_eq!(
+        stats.latency_percentile("getBlock", "a", 0.9, now),
+        Some(25)
+    );
+    assert_eq!(stats.latency_percentile("getBlock", "b", 0.9, now), None);
+
+    // Only failures: no latency estimate
+    stats.record("getSlot", "b", false, Duration::from_millis(3), now);
+    let slot = stats.get("getSlot", "b", now).unwrap();
+    assert_eq!(slot.success_rate, 0.0);
+    assert_eq!(slot.p50_ms, None);
+}
+
+#[test]
+fn test_window_and_unknown_methods() {
+    let stats = RoutingStats::new();
+    let now = 1_000_000;
+    stats.record("getBalance", "a", true, Duration::from_millis(1), now);
+    stats.record("notARealMethod", "a", true, Duration::from_millis(1), now);
+
+    let snapshot = stats.snapshot(now);
+    assert_eq!(snapshot.keys().collect::<Vec<_>>(), vec!["getBalance"]);
+
+    // Older than the window: dropped from the report
+    assert!(stats.snapshot(now + WINDOW_SECS + 10).is_empty());
+    assert!(stats
+        .get("getBalance", "a", now + WINDOW_SECS + 10)
+        .is_none());
+}
+
+#[test]
+fn test_concurrent_records_and_bucket_reuse() {
+    let stats = Arc::new(RoutingStats::new());
+    let now = 1_000_000;
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let stats = stats.clone();
+            std::thread::spawn(move || {
+                let backend = if i % 2 == 0 { "a" } else { "b" };
+                for _ in 0..1_000 {
+                    stats.record("getSlot", backend, true, Duration::from_millis(1), now);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(stats.get("getSlot", "a", now).unwrap().requests, 4_000);
+    assert_eq!(stats.get_method("getSlot", now).unwrap().requests, 8_000);
+
+    // A window later the same bucket starts over
+    let later = now + WINDOW_SECS;
+    stats.record("getSlot", "a", false, Duration::from_millis(1), later);
+    let slot = stats.get("getSlot", "a", later).unwrap();
+    assert_eq!(slot.requests, 1);
+    assert_eq!(slot.success_rate, 0.0);
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use sol_rpc_router::timing::{Phase, RequestTiming, TIMING_HEADER};
+use tokio::time::Instant;
+
+#[test]
+fn test_marks_charge_time_since_previous_mark() {
+    let start = Instant::now();
+    let ms = |n| start + Duration::from_millis(n);
+    let mut timing = RequestTiming::start(start);
+    timing.mark_at(Phase::Auth, ms(2));
+    timing.mark_at(Phase::Routing, ms(3));
+    timing.mark_at(Phase::Queue, ms(10));
+    timing.mark_at(Phase::Routing, ms(11));
+    timing.mark_at(Phase::Upstream, ms(51));
+
+    assert_eq!(timing.spent(Phase::Auth), Duration::from_millis(2));
+    assert_eq!(timing.spent(Phase::Queue), Duration::from_millis(7));
+    assert_eq!(timing.spent(Phase::Routing), Duration::from_millis(2));
+    assert_eq!(timing.spent(Phase::Upstream), Duration::from_millis(40));
+
+    let mut headers = HeaderMap::new();
+    timing.annotate(&mut headers, ms(52));
+    assert_eq!(
+        headers[TIMING_HEADER],
+        "auth;dur=2.000, queue;dur=7.000, routing;dur=2.000, upstream;dur=40.000, response;dur=1.000"
+    );
+}
+
+#[test]
+fn test_requests_not_sent_upstream_end_in_routing() {
+    let start = Instant::now();
+    let mut timing = RequestTiming::start(start);
+    timing.mark_at(Phase::Auth, start + Duration::from_millis(1));
+
+    let mut headers = HeaderMap::new();
+    timing.annotate(&mut headers, start + Duration::from_millis(4));
+    let value = headers[TIMING_HEADER].to_str().unwrap();
+    assert!(value.contains("routing;dur=3.000"));
+    assert!(value.ends_with("upstream;dur=0.000, response;dur=0.000"));
+}
@@ -0,0 +1,143 @@
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
+use axum::{extract::ConnectInfo, routing::get, Router};
+use rcgen::generate_simple_self_signed;
+use rustls_pki_types::{CertificateDer, ServerName};
+use sol_rpc_router::{
+    config::TlsConfig,
+    tls::{acceptor, certified_key, serve_tls, CertResolver},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::{
+    rustls::{crypto::aws_lc_rs, ClientConfig, RootCertStore},
+    TlsConnector,
+};
+use tokio_util::sync::CancellationToken;
+
+/// A self-signed certificate for `localhost` and its key, as PEM.
+fn self_signed() -> (String, String, CertificateDer<'static>) {
+    let generated = generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    (
+        generated.cert.pem(),
+        generated.signing_key.serialize_pem(),
+        generated.cert.der().clone(),
+    )
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let mut path = std::env::temp_dir();
+    path.push(format!("sol_rpc_router_test_tls_{}.pem", name));
+    path
+}
+
+fn write_pair(name: &str, cert: &str, key: &str) -> TlsConfig {
+    let cert_path = temp_path(&format!("{}_cert", name));
+    let key_path = temp_path(&format!("{}_key", name));
+    std::fs::write(&cert_path, cert).unwrap();
+    std::fs::write(&key_path, key).unwrap();
+    TlsConfig {
+        enabled: true,
+        cert_path: cert_path.to_string_lossy().into_owned(),
+        key_path: key_path.to_string_lossy().into_owned(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_certified_key() {
+    let (cert, key, _) = self_signed();
+    let (_, other_key, _) = self_signed();
+    assert!(certified_key(cert.as_bytes(), key.as_bytes()).is_ok());
+
+    let err = certified_key(cert.as_bytes(), other_key.as_bytes()).unwrap_err();
+    assert!(err.contains("does not match"), "{}", err);
+    assert!(certified_key(b"", key.as_bytes()).is_err());
+    assert!(certified_key(cert.as_bytes(), b"not a key").is_err());
+}
+
+#[test]
+fn test_resolver_reloads_renewed_certificate() {
+    let (cert, key, der) = self_signed();
+    let config = write_pair("reload", &cert, &key);
+    let resolver = CertResolver::load(&config).unwrap();
+    assert_eq!(resolver.chain()[0], der);
+    assert!(!resolver.reload().unwrap());
+
+    // A renewal replaces both files
+    let (renewed, renewed_key, renewed_der) = self_signed();
+    std::fs::write(&config.cert_path, &renewed).unwrap();
+    std::fs::write(&config.key_path, &renewed_key).unwrap();
+    assert!(resolver.reload().unwrap());
+    assert_eq!(resolver.chain()[0], renewed_der);
+
+    // Halfway through a renewal the pair does not match: keep serving the last one
+    std::fs::write(&config.cert_path, &cert).unwrap();
+    assert!(resolver.reload().is_err());
+    assert_eq!(resolver.chain()[0], renewed_der);
+
+    std::fs::write(&config.key_path, &key).unwrap();
+    assert!(resolver.reload().unwrap());
+    assert_eq!(resolver.chain()[0], der);
+
+    let missing = TlsConfig {
+        cert_path: "/nonexistent/cert.pem".to_string(),
+        ..config
+    };
+    assert!(CertResolver::load(&missing).is_err());
+}
+
+#[tokio::test]
+async fn test_serve_tls() {
+    let (cert, key, der) = self_signed();
+    let resolver = Arc::new(CertResolver::load(&write_pair("serve", &cert, &key)).unwrap());
+    let app = Router::new().route(
+        "/",
+        get(|ConnectInfo(addr): ConnectInfo<SocketAddr>| async move { addr.ip().to_string() }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let token = CancellationToken::new();
+    let server = tokio::spawn(serve_tls(
+        listener,
+        app,
+        acceptor(resolver).unwrap(),
+        token.clone(),
+    ));
+
+    let mut roots = RootCertStore::empty();
+    roots.add(der).unwrap();
+    let client = ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut stream = TlsConnector::from(Arc::new(client))
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.ends_with("127.0.0.1"), "{}", response);
+
+    // A client that does not speak TLS gets nowhere
+    let mut plain = TcpStream::connect(addr).await.unwrap();
+    plain
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = Vec::new();
+    let _ = plain.read_to_end(&mut buf).await;
+    assert!(!String::from_utf8_lossy(&buf).contains("200 OK"));
+
+    token.cancel();
+    server.await.unwrap();
+}
@@ -0,0 +1,175 @@
+use sol_rpc_router::{
+    config::TracingConfig,
+    trace::{SpanContext, SpanData, Tracer},
+};
+
+fn tracer(sample_percent: u32, max_queued_spans: usize) -> Tracer {
+    Tracer::new(&TracingConfig {
+        enabled: true,
+        sample_percent,
+        max_queued_spans,
+        ..TracingConfig::default()
+    })
+}
+
+fn span(context: SpanContext, status: u16) -> SpanData {
+    SpanData {
+        name: "getSlot".to_string(),
+        context,
+        parent_span_id: Some([1; 8]),
+        start_nanos: 1_000,
+        end_nanos: 3_000,
+        attributes: vec![
+            ("rpc.method", "getSlot".to_string()),
+            ("backend", "node-1".to_string()),
+        ],
+        status,
+    }
+}
+
+#[test]
+fn test_traceparent_parsing() {
+    let context =
+        SpanContext::from_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+            .unwrap();
+    assert_eq!(context.trace_id[0], 0x4b);
+    assert_eq!(context.span_id[7], 0xb7);
+    assert!(context.sampled);
+    assert_eq!(
+        context.traceparent(),
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+    );
+
+    // Later versions may add fields
+    assert!(SpanContext::from_traceparent(
+        "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra"
+    )
+    .is_some_and(|c| !c.sampled));
+
+    for invalid in [
+        "",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+    ] {
+        assert_eq!(SpanContext::from_traceparent(invalid), None, "{}", invalid);
+    }
+}
+
+#[test]
+fn test_tracer_sampling_and_queue() {
+    let parent =
+        SpanContext::from_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00")
+            .unwrap();
+
+    // Children follow their parent's decision, whatever the sample rate
+    let child = tracer(100, 10).start(Some(&parent));
+    assert_eq!(child.trace_id, parent.trace_id);
+    assert_ne!(child.span_id, parent.span_id);
+    assert!(!child.sampled);
+
+    // New traces are sampled at sample_percent
+    assert!((0..50).all(|_| tracer(100, 10).start(None).sampled));
+    assert!((0..50).all(|_| !tracer(0, 10).start(None).sampled));
+    let a = tracer(100, 10).start(None);
+    let b = tracer(100, 10).start(None);
+    assert_ne!(a.trace_id, b.trace_id);
+
+    // Spans past the queue limit are dropped
+    let tracer = tracer(100, 2);
+    for _ in 0..3 {
+        tracer.record(span(a, 200));
+    }
+    assert_eq!(tracer.drain().len(), 2);
+    assert!(tracer.drain().is_empty());
+}
+
+#[cfg(feature = "otlp")]
+#[test]
+fn test_otlp_trace_payload() {
+    use sol_rpc_router::trace::otlp::payload;
+
+    let context =
+        SpanContext::from_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+            .unwrap();
+    let body = payload(&[span(context, 200), span(context, 502)], "router-1");
+    let resource = &body["resourceSpans"][0];
+    assert_eq!(
+        resource["resource"]["attributes"][0]["value"]["stringValue"],
+        "router-1"
+    );
+    let spans = resource["scopeSpans"][0]["spans"].as_array().unwrap();
+    assert_eq!(spans.len(), 2);
+
+    let span = &spans[0];
+    assert_eq!(span["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(span["spanId"], "00f067aa0ba902b7");
+    assert_eq!(span["parentSpanId"], "0101010101010101");
+    assert_eq!(span["name"], "getSlot");
+    assert_eq!(span["kind"], 2);
+    assert_eq!(span["startTimeUnixNano"], "1000");
+    assert_eq!(span["endTimeUnixNano"], "3000");
+    assert_eq!(span["status"]["code"], 0);
+    let attributes = span["attributes"].as_array().unwrap();
+    assert_eq!(attributes[1]["key"], "backend");
+    assert_eq!(attributes[1]["value"]["stringValue"], "node-1");
+    assert_eq!(attributes[2]["key"], "http.response.status_code");
+    assert_eq!(attributes[2]["value"]["intValue"], "200");
+
+    // Server errors fail the span
+    assert_eq!(spans[1]["status"]["code"], 2);
+}
+
+#[cfg(feature = "otlp")]
+#[tokio::test]
+async fn test_otlp_trace_export_loop() {
+    use std::{collections::HashMap, sync::Arc};
+
+    use axum::{http::HeaderMap, routing::post, Json, Router};
+    use sol_rpc_router::{config::OtlpConfig, trace::otlp::export_loop};
+    use tokio::sync::mpsc;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/v1/traces",
+            post(
+                move |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                    tx.send((headers, body)).unwrap();
+                },
+            ),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let config = TracingConfig {
+        enabled: true,
+        interval_secs: 1,
+        otlp: OtlpConfig {
+            endpoint,
+            headers: HashMap::from([("authorization".to_string(), "Bearer t".to_string())]),
+            ..OtlpConfig::default()
+        },
+        ..TracingConfig::default()
+    };
+    let tracer = Arc::new(Tracer::new(&config));
+    let context = tracer.start(None);
+    tracer.record(span(context, 200));
+    tokio::spawn(export_loop(tracer.clone(), config));
+
+    let (headers, body) = rx.recv().await.unwrap();
+    assert_eq!(headers["authorization"], "Bearer t");
+    let spans = body["resourceSpans"][0]["scopeSpans"][0]["spans"]
+        .as_array()
+        .unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["traceId"], hex::encode(context.trace_id));
+    assert!(tracer.drain().is_empty());
+}
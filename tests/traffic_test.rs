@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use sol_rpc_router::traffic::{ResponseMeter, TrafficStats, BUCKET_SECS, OTHER_METHOD};
+
+const RETENTION: u64 = 24 * BUCKET_SECS;
+
+#[test]
+fn test_traffic_report_totals_and_peaks() {
+    let stats = Arc::new(TrafficStats::new());
+    let now = 100 * BUCKET_SECS + 10;
+
+    // Three getBalance calls in one second, one in the next
+    for at in [now, now, now, now + 1] {
+        stats.record_request(Some("getBalance"), 100, at, RETENTION);
+        let mut meter = ResponseMeter::new(stats.clone(), Some("getBalance".to_string()), at);
+        meter.add(300);
+    }
+    stats.record_request(Some("getBlock"), 50, now + 1, RETENTION);
+    stats.record_response(Some("getBlock"), 20_000, now + 1);
+    // Unknown methods, and requests without one, are pooled
+    stats.record_request(Some("getEverything"), 10, now + 2, RETENTION);
+    stats.record_request(None, 10, now + 2, RETENTION);
+
+    let report = stats.report(1, now + 10);
+    assert_eq!(report.from, 100 * BUCKET_SECS);
+    assert_eq!(report.to, 101 * BUCKET_SECS);
+    let methods: Vec<&str> = report.methods.iter().map(|m| m.method.as_str()).collect();
+    assert_eq!(methods, ["getBalance", OTHER_METHOD, "getBlock"]);
+
+    let balance = &report.methods[0];
+    assert_eq!(balance.requests, 4);
+    assert_eq!(balance.request_bytes, 400);
+    assert_eq!(balance.response_bytes, 1200);
+    assert_eq!(balance.avg_response_bytes, 300);
+    assert_eq!(balance.peak_rps, 3);
+    assert_eq!(report.methods[2].avg_response_bytes, 20_000);
+
+    assert_eq!(report.total.requests, 7);
+    assert_eq!(report.total.response_bytes, 21_200);
+    // getBalance and getBlock share the busiest second
+    assert_eq!(report.total.peak_rps, 3);
+    assert_eq!(report.total.avg_rps, 7.0 / 20.0);
+}
+
+#[test]
+fn test_traffic_report_period_and_retention() {
+    let stats = TrafficStats::new();
+    let hour = |h: u64| h * BUCKET_SECS;
+    stats.record_request(Some("getSlot"), 10, hour(10), RETENTION);
+    stats.record_request(Some("getSlot"), 10, hour(12), RETENTION);
+
+    assert_eq!(stats.report(1, hour(12) + 5).total.requests, 1);
+    assert_eq!(stats.report(3, hour(12) + 5).total.requests, 2);
+
+    // Hours beyond the retention are dropped as new ones start
+    stats.record_request(Some("getSlot"), 10, hour(35), RETENTION);
+    assert_eq!(stats.report(48, hour(35)).total.requests, 2);
+}
+
+#[test]
+fn test_traffic_report_csv() {
+    let stats = TrafficStats::new();
+    stats.record_request(Some("getSlot"), 10, 5, RETENTION);
+    let csv = stats.report(1, 5).to_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "method,requests,request_bytes,response_bytes,avg_request_bytes,avg_response_bytes,avg_rps,peak_rps",
+            "getSlot,1,10,0,10,0,0.200,1",
+            "total,1,10,0,10,0,0.200,1",
+        ]
+    );
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashMap, time::Duration};
+
+use sol_rpc_router::{
+    config::{TtlBounds, TtlTuningConfig},
+    ttl_tuning::{ttl_ladder, TtlTuner},
+};
+
+fn tuning(min_secs: u64, max_secs: u64) -> TtlTuningConfig {
+    TtlTuningConfig {
+        enabled: true,
+        methods: HashMap::from([("getVersion".to_string(), TtlBounds { min_secs, max_secs })]),
+        explore_percent: 0,
+        revalidate_percent: 0,
+        staleness_penalty: 10,
+    }
+}
+
+fn secs(s: u64) -> Duration {
+    Duration::from_secs(s)
+}
+
+#[test]
+fn test_ttl_ladder_doubles_up_to_max() {
+    assert_eq!(
+        ttl_ladder(TtlBounds {
+            min_secs: 5,
+            max_secs: 60
+        }),
+        [5, 10, 20, 40, 60]
+    );
+    assert_eq!(
+        ttl_ladder(TtlBounds {
+            min_secs: 30,
+            max_secs: 30
+        }),
+        [30]
+    );
+}
+
+#[test]
+fn test_untuned_methods_are_not_chosen_for() {
+    let tuner = TtlTuner::new();
+    let mut config = tuning(1, 4);
+    assert_eq!(tuner.choose("getHealth", &config), None);
+    config.enabled = false;
+    assert_eq!(tuner.choose("getVersion", &config), None);
+}
+
+#[test]
+fn test_tuner_tries_every_ttl_then_keeps_the_best() {
+    let tuner = TtlTuner::new();
+    let config = tuning(1, 4);
+
+    // Each TTL is tried once, shortest first
+    for expected in [1, 2, 4] {
+        let ttl = tuner.choose("getVersion", &config).unwrap();
+        assert_eq!(ttl, secs(expected));
+        tuner.record_store("getVersion", ttl);
+    }
+
+    // Longer TTLs serve more hits per stored result
+    for _ in 0..3 {
+        tuner.record_hit("getVersion", secs(2));
+    }
+    for _ in 0..6 {
+        tuner.record_hit("getVersion", secs(4));
+    }
+    assert_eq!(tuner.choose("getVersion", &config), Some(secs(4)));
+
+    // Until its hits turn out stale
+    tuner.record_revalidation("getVersion", secs(4), false);
+    tuner.record_revalidation("getVersion", secs(4), true);
+    tuner.record_revalidation("getVersion", secs(2), true);
+    assert_eq!(tuner.choose("getVersion", &config), Some(secs(2)));
+
+    let snapshot = tuner.snapshot(&config);
+    assert_eq!(snapshot.len(), 1);
+    assert_eq!(snapshot[0].method, "getVersion");
+    assert_eq!(snapshot[0].ttl_secs, 2);
+    let longest = &snapshot[0].arms[2];
+    assert_eq!((longest.stores, longest.hits), (1, 6));
+    assert_eq!((longest.revalidations, longest.mismatches), (2, 1));
+    assert_eq!(longest.mismatch_rate, 0.5);
+    assert_eq!(longest.score, 0.0);
+}
+
+#[test]
+fn test_tuner_starts_over_when_bounds_change() {
+    let tuner = TtlTuner::new();
+    let config = tuning(1, 2);
+    for _ in 0..2 {
+        let ttl = tuner.choose("getVersion", &config).unwrap();
+        tuner.record_store("getVersion", ttl);
+    }
+    tuner.record_hit("getVersion", secs(2));
+    assert_eq!(tuner.choose("getVersion", &config), Some(secs(2)));
+
+    let config = tuning(10, 20);
+    assert_eq!(tuner.choose("getVersion", &config), Some(secs(10)));
+    // Hits on results stored under the old bounds are not counted
+    tuner.record_hit("getVersion", secs(2));
+    let snapshot = tuner.snapshot(&config);
+    assert!(snapshot[0]
+        .arms
+        .iter()
+        .all(|a| a.hits == 0 && a.stores == 0));
+}
+
+#[test]
+fn test_tuner_decays_old_counts() {
+    let tuner = TtlTuner::new();
+    let config = tuning(5, 5);
+    tuner.choose("getVersion", &config);
+    for _ in 0..999 {
+        tuner.record_store("getVersion", secs(5));
+    }
+    tuner.record_hit("getVersion", secs(5));
+    tuner.record_hit("getVersion", secs(5));
+    tuner.record_store("getVersion", secs(5));
+    let arm = &tuner.snapshot(&config)[0].arms[0];
+    assert_eq!((arm.stores, arm.hits), (500, 1));
+}
@@ -0,0 +1,89 @@
+use sol_rpc_router::usage::{Outcome, UsageTracker};
+
+#[test]
+fn test_outcome_classification() {
+    assert_eq!(Outcome::classify(200, true), Outcome::Success);
+    assert_eq!(Outcome::classify(200, false), Outcome::InvalidRequest);
+    assert_eq!(Outcome::classify(403, true), Outcome::ClientError);
+    assert_eq!(Outcome::classify(502, true), Outcome::ServerError);
+}
+
+#[test]
+fn test_error_rates_per_key() {
+    let tracker = UsageTracker::new();
+    let now = 1_700_000_000;
+
+    for _ in 0..6 {
+        tracker.record("alice", Outcome::Success, now);
+    }
+    tracker.record("alice", Outcome::InvalidRequest, now);
+    tracker.record("alice", Outcome::InvalidRequest, now);
+    tracker.record("alice", Outcome::ClientError, now);
+    tracker.record("alice", Outcome::ServerError, now);
+    tracker.record("bob", Outcome::ServerError, now);
+
+    let stats = tracker.stats("alice", 60, now);
+    assert_eq!(stats.requests, 10);
+    assert_eq!(stats.invalid_requests, 2);
+    assert!((stats.invalid_request_rate - 0.2).abs() < f64::EPSILON);
+    assert!((stats.client_error_rate - 0.1).abs() < f64::EPSILON);
+    assert!((stats.server_error_rate - 0.1).abs() < f64::EPSILON);
+
+    assert_eq!(tracker.stats("bob", 60, now).server_error_rate, 1.0);
+    assert_eq!(tracker.stats("carol", 60, now).requests, 0);
+}
+
+#[test]
+fn test_windows_roll_over() {
+    let tracker = UsageTracker::new();
+    let start = 1_700_000_000;
+
+    tracker.record("alice", Outcome::InvalidRequest, start);
+    tracker.record("alice", Outcome::Success, start + 120);
+
+    let now = start + 120;
+    // The invalid request fell out of the 1m window but not the 5m one
+    assert_eq!(tracker.stats("alice", 60, now).invalid_requests, 0);
+    assert_eq!(tracker.stats("alice", 300, now).invalid_requests, 1);
+
+    let windows = tracker.all_windows("alice", now);
+    assert_eq!(
+        windows.iter().map(|(w, _)| *w).collect::<Vec<_>>(),
+        ["1m", "5m", "15m"]
+    );
+
+    // Buckets beyond the 15m retention are dropped on the next write
+    tracker.record("alice", Outcome::Success, start + 1_100);
+    assert_eq!(tracker.stats("alice", 900, start + 1_100).requests, 1);
+}
+
+#[test]
+fn test_export_reports_every_owner_and_forgets_idle_ones() {
+    let tracker = UsageTracker::new();
+    let start = 1_700_000_000;
+
+    tracker.record("alice", Outcome::ServerError, start);
+    tracker.record("bob", Outcome::Success, start + 600);
+
+    let mut exported = tracker.export(start + 600);
+    exported.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        exported.iter().map(|(o, _)| o.as_str()).collect::<Vec<_>>(),
+        ["alice", "bob"]
+    );
+    let (_, alice) = &exported[0];
+    assert_eq!(alice[0].1.requests, 0);
+    assert_eq!(alice[2].1.server_error_rate, 1.0);
+
+    // Alice is reported at zero once past retention, then dropped
+    let exported = tracker.export(start + 1_000);
+    assert_eq!(exported.len(), 2);
+    assert!(exported
+        .iter()
+        .all(|(o, w)| o != "alice" || w.iter().all(|(_, s)| s.requests == 0)));
+    let exported = tracker.export(start + 1_000);
+    assert_eq!(
+        exported.iter().map(|(o, _)| o.as_str()).collect::<Vec<_>>(),
+        ["bob"]
+    );
+}
@@ -0,0 +1,420 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use arc_swap::ArcSwap;
+use axum::{
+    extract::ws::{Message, WebSocketUpgrade},
+    routing::get,
+    Router,
+};
+use futures_util::{SinkExt, StreamExt};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use serde_json::{json, Value};
+use sol_rpc_router::{
+    config::{Backend, Commitment, WebSocketConfig},
+    handlers::ws_proxy,
+    mock::MockKeyStore,
+    slot_feed::SlotView,
+    state::{AppState, RouterState, RuntimeBackend},
+    ws::{
+        is_notification, ClientQueue, FirehoseThrottle, FirehoseThrottler, LocalSubscriptions,
+        LOCAL_SUBSCRIPTION_BASE,
+    },
+};
+use tokio_tungstenite::{connect_async, tungstenite::Message as TungsteniteMessage};
+
+const NOTIFICATION: &str = r#"{"jsonrpc":"2.0","method":"slotNotification","params":{"result":{"slot":1},"subscription":7}}"#;
+
+fn text(message: Option<Message>) -> String {
+    match message {
+        Some(Message::Text(text)) => text,
+        other => panic!("expected a text message, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_is_notification() {
+    assert!(is_notification(NOTIFICATION));
+    assert!(!is_notification(r#"{"jsonrpc":"2.0","result":7,"id":1}"#));
+    assert!(!is_notification(
+        r#"{"jsonrpc":"2.0","method":"slotSubscribe","id":1}"#
+    ));
+    assert!(!is_notification("not json"));
+}
+
+#[test]
+fn test_queue_drops_oldest_notification_when_full() {
+    let queue = ClientQueue::new(3);
+    let response = r#"{"jsonrpc":"2.0","result":7,"id":1}"#;
+    assert!(!queue.push(Message::Text(response.to_string())));
+    assert!(!queue.push(Message::Text(NOTIFICATION.replace("1}", "2}"))));
+    assert!(!queue.push(Message::Text(NOTIFICATION.replace("1}", "3}"))));
+
+    // Full: the oldest notification goes, the response before it stays
+    assert!(queue.push(Message::Text(NOTIFICATION.replace("1}", "4}"))));
+    assert_eq!(queue.len(), 3);
+    assert_eq!(text(queue.pop()), response);
+    assert!(text(queue.pop()).contains(r#""slot":3"#));
+    assert!(text(queue.pop()).contains(r#""slot":4"#));
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn test_queue_never_drops_responses() {
+    let queue = ClientQueue::new(1);
+    assert!(!queue.push(Message::Text(r#"{"result":1,"id":1}"#.to_string())));
+    assert!(!queue.push(Message::Text(r#"{"result":2,"id":2}"#.to_string())));
+    assert_eq!(queue.len(), 2);
+}
+
+fn json_of(text: &str) -> Value {
+    serde_json::from_str(text).unwrap()
+}
+
+#[test]
+fn test_local_slot_subscriptions() {
+    let mut local = LocalSubscriptions::new(Commitment::Confirmed);
+
+    let response = local
+        .handle_request(r#"{"jsonrpc":"2.0","id":1,"method":"slotSubscribe"}"#)
+        .unwrap();
+    let slot_sub = json_of(&response)["result"].as_u64().unwrap();
+    assert!(slot_sub >= LOCAL_SUBSCRIPTION_BASE);
+    let response = local
+        .handle_request(r#"{"jsonrpc":"2.0","id":2,"method":"rootSubscribe"}"#)
+        .unwrap();
+    let root_sub = json_of(&response)["result"].as_u64().unwrap();
+    assert_eq!(local.len(), 2);
+
+    // Other methods, and unsubscribes from upstream ids, are not ours
+    assert!(local
+        .handle_request(r#"{"jsonrpc":"2.0","id":3,"method":"accountSubscribe","params":["x"]}"#)
+        .is_none());
+    assert!(local
+        .handle_request(r#"{"jsonrpc":"2.0","id":4,"method":"slotUnsubscribe","params":[7]}"#)
+        .is_none());
+
+    let view = SlotView {
+        processed: Some(102),
+        confirmed: Some(100),
+        finalized: Some(68),
+    };
+    let notifications: Vec<Value> = local
+        .notifications(&view)
+        .iter()
+        .map(|n| json_of(n))
+        .collect();
+    assert_eq!(notifications.len(), 2);
+    let slot = notifications
+        .iter()
+        .find(|n| n["method"] == "slotNotification")
+        .unwrap();
+    // Processed is below the configured commitment: confirmed slots are reported
+    assert_eq!(
+        slot["params"],
+        json!({"result": {"parent": 99, "root": 68, "slot": 100}, "subscription": slot_sub})
+    );
+    let root = notifications
+        .iter()
+        .find(|n| n["method"] == "rootNotification")
+        .unwrap();
+    assert_eq!(
+        root["params"],
+        json!({"result": 68, "subscription": root_sub})
+    );
+
+    // Nothing advanced, nothing sent
+    assert!(local.notifications(&view).is_empty());
+
+    let unsubscribe =
+        json!({"jsonrpc": "2.0", "id": 5, "method": "slotUnsubscribe", "params": [slot_sub]});
+    let response = local.handle_request(&unsubscribe.to_string()).unwrap();
+    assert_eq!(json_of(&response)["result"], json!(true));
+    let response = local.handle_request(&unsubscribe.to_string()).unwrap();
+    assert_eq!(json_of(&response)["error"]["code"], json!(-32602));
+    assert_eq!(local.len(), 1);
+}
+
+#[test]
+fn test_local_slot_subscription_commitment() {
+    let mut local = LocalSubscriptions::new(Commitment::Processed);
+    let view = SlotView {
+        processed: Some(102),
+        confirmed: Some(100),
+        finalized: Some(68),
+    };
+
+    local
+        .handle_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"slotSubscribe","params":[{"commitment":"finalized"}]}"#,
+        )
+        .unwrap();
+    let notifications = local.notifications(&view);
+    assert_eq!(
+        json_of(&notifications[0])["params"]["result"]["slot"],
+        json!(68)
+    );
+
+    let response = local
+        .handle_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"slotSubscribe","params":[{"commitment":"recent"}]}"#,
+        )
+        .unwrap();
+    assert_eq!(json_of(&response)["error"]["code"], json!(-32602));
+    assert_eq!(local.len(), 1);
+}
+
+fn vote(n: u64) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "voteNotification",
+        "params": {"result": {"slot": n}, "subscription": 1}
+    })
+    .to_string()
+}
+
+#[test]
+fn test_firehose_sampling() {
+    let mut throttler = FirehoseThrottler::new(FirehoseThrottle {
+        max_per_sec: 0,
+        sample_every: 3,
+    });
+    let now = std::time::Instant::now();
+    let admitted: Vec<u64> = (1..=7)
+        .filter(|n| throttler.admit(&vote(*n), now))
+        .collect();
+    assert_eq!(admitted, vec![1, 4, 7]);
+
+    // Other notifications and responses are never throttled
+    for _ in 0..5 {
+        assert!(throttler.admit(NOTIFICATION, now));
+        assert!(throttler.admit(r#"{"jsonrpc":"2.0","result":1,"id":1}"#, now));
+    }
+}
+
+#[test]
+fn test_firehose_rate_limit() {
+    let mut throttler = FirehoseThrottler::new(FirehoseThrottle {
+        max_per_sec: 2,
+        sample_every: 0,
+    });
+    let block = r#"{"jsonrpc":"2.0","method":"blockNotification","params":{"subscription":2}}"#;
+    let start = std::time::Instant::now();
+    assert!(throttler.admit(&vote(1), start));
+    assert!(throttler.admit(&vote(2), start));
+    assert!(!throttler.admit(&vote(3), start));
+    // Each kind has its own budget
+    assert!(throttler.admit(block, start));
+
+    let later = start + Duration::from_millis(1001);
+    assert!(throttler.admit(&vote(4), later));
+    assert!(throttler.admit(&vote(5), later));
+    assert!(!throttler.admit(&vote(6), later));
+}
+
+/// Echoing WebSocket backend behind a router using `config`; returns the
+/// router's URL and state.
+async fn start_router(config: WebSocketConfig) -> (String, Arc<AppState>) {
+    let backend = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_addr = backend.local_addr().unwrap();
+    tokio::spawn(async move {
+        let app = Router::new().route(
+            "/",
+            get(|ws: WebSocketUpgrade| async move {
+                ws.on_upgrade(|mut socket| async move {
+                    while let Some(Ok(msg)) = socket.recv().await {
+                        if let Message::Text(text) = msg {
+                            if socket.send(Message::Text(text)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                })
+            }),
+        );
+        axum::serve(backend, app).await.unwrap();
+    });
+
+    let backend = Backend {
+        label: "ws-backend".to_string(),
+        url: format!("http://{}", backend_addr),
+        ws_url: Some(format!("ws://{}", backend_addr)),
+        weight: 1,
+        ..Default::default()
+    };
+    let router_state = RouterState {
+        backends: vec![RuntimeBackend::new(backend, true)],
+        websocket: config,
+        ..Default::default()
+    };
+    let keystore = Arc::new(MockKeyStore::new());
+    keystore.add_key("test-key", "tester", 100);
+    // Receives every third vote notification
+    keystore.add_key("firehose-key", "tester", 100);
+    keystore.set_firehose(
+        "firehose-key",
+        FirehoseThrottle {
+            max_per_sec: 0,
+            sample_every: 3,
+        },
+    );
+    let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(HttpsConnector::new());
+    let state = Arc::new(AppState::new(
+        client,
+        keystore,
+        Arc::new(ArcSwap::from_pointee(router_state)),
+    ));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new()
+        .route("/", get(ws_proxy))
+        .with_state(state.clone());
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+    (format!("ws://{}/?api-key=test-key", addr), state)
+}
+
+fn heartbeat_config() -> WebSocketConfig {
+    WebSocketConfig {
+        ping_interval_secs: 1,
+        pong_timeout_secs: 1,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_ws_client_answering_pings_stays_connected() {
+    let (url, _) = start_router(heartbeat_config()).await;
+    let (mut socket, _) = connect_async(url).await.unwrap();
+
+    // Reading answers the router's pings; keep at it past several timeouts
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(4);
+    let mut pings = 0;
+    while let Ok(msg) = tokio::time::timeout_at(deadline, socket.next()).await {
+        match msg {
+            Some(Ok(TungsteniteMessage::Ping(_))) => pings += 1,
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+    assert!(pings >= 2);
+
+    socket
+        .send(TungsteniteMessage::Text("hello".to_string()))
+        .await
+        .unwrap();
+    loop {
+        match socket.next().await {
+            Some(Ok(TungsteniteMessage::Text(text))) => {
+                assert_eq!(text, "hello");
+                break;
+            }
+            Some(Ok(TungsteniteMessage::Ping(_))) => continue,
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_ws_client_missing_pongs_is_disconnected() {
+    let (url, _) = start_router(heartbeat_config()).await;
+    let (mut socket, _) = connect_async(url).await.unwrap();
+
+    // Not reading means not answering the first ping
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let mut closed = false;
+    while let Some(Ok(msg)) = socket.next().await {
+        if matches!(msg, TungsteniteMessage::Close(_)) {
+            closed = true;
+        }
+    }
+    assert!(closed);
+}
+
+async fn next_json<S>(socket: &mut S) -> Value
+where
+    S: StreamExt<Item = Result<TungsteniteMessage, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        match socket.next().await {
+            Some(Ok(TungsteniteMessage::Text(text))) => return json_of(&text),
+            Some(Ok(_)) => continue,
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_ws_slot_subscribe_served_from_slot_feed() {
+    let (url, state) = start_router(WebSocketConfig {
+        local_slot_subscriptions: true,
+        ..Default::default()
+    })
+    .await;
+    let (mut socket, _) = connect_async(url).await.unwrap();
+
+    let subscribe = r#"{"jsonrpc":"2.0","id":1,"method":"slotSubscribe"}"#;
+    socket
+        .send(TungsteniteMessage::Text(subscribe.to_string()))
+        .await
+        .unwrap();
+    let response = next_json(&mut socket).await;
+    let subscription = response["result"].as_u64().unwrap();
+    assert!(subscription >= LOCAL_SUBSCRIPTION_BASE);
+
+    state.slot_feed.record(Commitment::Finalized, 68);
+    state.slot_feed.record(Commitment::Processed, 101);
+    let notification = next_json(&mut socket).await;
+    assert_eq!(notification["method"], "slotNotification");
+    assert_eq!(notification["params"]["subscription"], json!(subscription));
+    assert_eq!(notification["params"]["result"]["slot"], json!(101));
+
+    // Everything else still goes to the backend
+    socket
+        .send(TungsteniteMessage::Text("hello".to_string()))
+        .await
+        .unwrap();
+    loop {
+        match socket.next().await {
+            Some(Ok(TungsteniteMessage::Text(text))) if text == "hello" => break,
+            Some(Ok(_)) => continue,
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_ws_firehose_throttled_per_key() {
+    let (url, _) = start_router(WebSocketConfig::default()).await;
+    let url = url.replace("test-key", "firehose-key");
+    let (mut socket, _) = connect_async(url).await.unwrap();
+
+    // The echo backend sends the votes back as notifications
+    for n in 1..=6 {
+        socket
+            .send(TungsteniteMessage::Text(vote(n)))
+            .await
+            .unwrap();
+    }
+    socket
+        .send(TungsteniteMessage::Text(NOTIFICATION.to_string()))
+        .await
+        .unwrap();
+
+    let mut slots = Vec::new();
+    loop {
+        let message = next_json(&mut socket).await;
+        if message["method"] == "slotNotification" {
+            break;
+        }
+        slots.push(message["params"]["result"]["slot"].as_u64().unwrap());
+    }
+    assert_eq!(slots, vec![1, 4]);
+}